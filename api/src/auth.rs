@@ -145,7 +145,27 @@ pub async fn authenticate(
             }),
         )
     })?;
+    authenticate_token(token, state).await
+}
 
+/// Authenticate with the token from a `?token=` query param if given, else
+/// from the headers. For EventSource, which can't set an Authorization header.
+pub async fn authenticate_query(
+    headers: &HeaderMap,
+    query_token: Option<String>,
+    state: &AppState,
+) -> Result<AuthUser, (StatusCode, Json<ErrorBody>)> {
+    match query_token {
+        Some(token) => authenticate_token(token, state).await,
+        None => authenticate(headers, state).await,
+    }
+}
+
+/// Validate a token and its DB session.
+async fn authenticate_token(
+    token: String,
+    state: &AppState,
+) -> Result<AuthUser, (StatusCode, Json<ErrorBody>)> {
     // Validate JWT
     let claims = validate_jwt(&token, &state.jwt_secret).map_err(|e| {
         (
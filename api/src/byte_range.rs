@@ -0,0 +1,82 @@
+//! `Range: bytes=...` request header parsing for file downloads: what part
+//! of a `len`-byte file to send.
+//!
+//! Only single ranges are honoured. Multi-range requests and headers we can't
+//! parse get the whole file, which RFC 9110 allows a server to do.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No (usable) Range header: send everything with 200.
+    Full,
+    /// Inclusive byte range, sent with 206.
+    Partial { start: u64, end: u64 },
+    /// Range lies outside the file: answer 416.
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    pub fn parse(header: Option<&str>, len: u64) -> Self {
+        let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+            return ByteRange::Full;
+        };
+        if spec.contains(',') {
+            return ByteRange::Full;
+        }
+        let Some((first, last)) = spec.split_once('-') else {
+            return ByteRange::Full;
+        };
+        let (first, last) = (first.trim(), last.trim());
+
+        if first.is_empty() {
+            // Suffix range: the final `n` bytes
+            let Ok(n) = last.parse::<u64>() else { return ByteRange::Full };
+            if n == 0 || len == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            return ByteRange::Partial { start: len.saturating_sub(n), end: len - 1 };
+        }
+
+        let Ok(start) = first.parse::<u64>() else { return ByteRange::Full };
+        let end = if last.is_empty() {
+            u64::MAX
+        } else {
+            match last.parse::<u64>() {
+                Ok(end) if end >= start => end,
+                _ => return ByteRange::Full,
+            }
+        };
+        if start >= len {
+            return ByteRange::Unsatisfiable;
+        }
+        ByteRange::Partial { start, end: end.min(len - 1) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ranges() {
+        let parse = |h: &str| ByteRange::parse(Some(h), 1000);
+        assert_eq!(ByteRange::parse(None, 1000), ByteRange::Full);
+        assert_eq!(parse("bytes=0-99"), ByteRange::Partial { start: 0, end: 99 });
+        assert_eq!(parse("bytes=500-"), ByteRange::Partial { start: 500, end: 999 });
+        assert_eq!(parse("bytes=900-5000"), ByteRange::Partial { start: 900, end: 999 });
+        assert_eq!(parse("bytes=-100"), ByteRange::Partial { start: 900, end: 999 });
+        assert_eq!(parse("bytes=-5000"), ByteRange::Partial { start: 0, end: 999 });
+    }
+
+    #[test]
+    fn test_parse_unusable_ranges() {
+        let parse = |h: &str| ByteRange::parse(Some(h), 1000);
+        assert_eq!(parse("bytes=1000-"), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=-0"), ByteRange::Unsatisfiable);
+        assert_eq!(ByteRange::parse(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+        // Ignored: whole file
+        assert_eq!(parse("bytes=0-9,20-29"), ByteRange::Full);
+        assert_eq!(parse("bytes=50-10"), ByteRange::Full);
+        assert_eq!(parse("items=0-9"), ByteRange::Full);
+        assert_eq!(parse("bytes=abc"), ByteRange::Full);
+    }
+}
@@ -3,9 +3,12 @@
 /// REST API for the Hermes Download Nexus web dashboard.
 /// Provides OTP authentication, task management, and admin endpoints.
 mod auth;
+mod byte_range;
+mod progress;
 mod routes;
+mod transfers;
 
-use axum::routing::{delete, get, post, put};
+use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
 use sqlx::sqlite::SqlitePool;
 use std::sync::Arc;
@@ -20,6 +23,15 @@ pub struct AppState {
     pub admin_chat_id: i64,
     pub session_ttl: i64,
     pub download_dir: String,
+    /// Download roots files may be served from (DOWNLOAD_DIR plus any
+    /// AUDIO_DIR / VIDEO_DIR / PLAYLIST_DIR overrides).
+    pub storage: hermes_shared::storage_dirs::StorageDirs,
+    /// Files currently being streamed to clients.
+    pub transfers: Arc<transfers::Transfers>,
+    /// How long deleted files stay on disk before removal.
+    pub file_delete_grace: std::time::Duration,
+    /// Live task changes for the SSE endpoints.
+    pub progress: Arc<progress::ProgressHub>,
 }
 
 #[tokio::main]
@@ -37,13 +49,8 @@ async fn main() -> anyhow::Result<()> {
 
     // Config
     let database_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "./hermes.db".to_string());
-    let database_path = std::path::Path::new(&database_path)
-        .canonicalize()
-        .unwrap_or_else(|_| std::path::PathBuf::from(&database_path));
-    // Strip Windows UNC prefix (\\?\) which breaks SQLite URL parsing
-    let db_path_str = database_path.display().to_string();
-    let db_path_str = db_path_str.strip_prefix(r"\\?\").unwrap_or(&db_path_str).to_string();
-    info!("Database path: {}", db_path_str);
+    let database_url = hermes_shared::db::resolve_database_url(&database_path);
+    info!("Database: {}", database_url);
     let bot_token = std::env::var("TELEGRAM_BOT_TOKEN")
         .or_else(|_| std::env::var("TELOXIDE_TOKEN"))
         .expect("TELEGRAM_BOT_TOKEN or TELOXIDE_TOKEN must be set");
@@ -67,9 +74,20 @@ async fn main() -> anyhow::Result<()> {
         .unwrap_or(300);
     let download_dir = std::env::var("DOWNLOAD_DIR")
         .unwrap_or_else(|_| "./downloads".to_string());
+    let metrics_interval: u64 = std::env::var("METRICS_SNAPSHOT_INTERVAL_SECS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    let metrics_retention_days: i64 = std::env::var("METRICS_RETENTION_DAYS")
+        .unwrap_or_else(|_| "90".to_string())
+        .parse()
+        .unwrap_or(90);
+    let file_delete_grace_secs: u64 = std::env::var("FILE_DELETE_GRACE_SECS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .unwrap_or(30);
 
     // Database
-    let database_url = format!("sqlite://{}?mode=rwc", db_path_str);
     let pool = hermes_shared::db::create_pool(&database_url).await?;
     hermes_shared::db::run_migrations(&pool).await?;
 
@@ -80,8 +98,13 @@ async fn main() -> anyhow::Result<()> {
         jwt_secret,
         admin_chat_id,
         session_ttl,
+        storage: hermes_shared::storage_dirs::StorageDirs::from_env(&download_dir),
         download_dir,
+        transfers: Arc::new(transfers::Transfers::default()),
+        file_delete_grace: std::time::Duration::from_secs(file_delete_grace_secs),
+        progress: Arc::new(progress::ProgressHub::default()),
     });
+    state.progress.spawn_poller(pool.clone());
 
     // Background session cleanup
     let cleanup_pool = pool.clone();
@@ -97,6 +120,35 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // Expired OTP sessions are pruned every 30s, well ahead of the general cleanup
+    let otp_cleanup_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            if let Err(e) = hermes_shared::db::cleanup_expired_otp_sessions(&otp_cleanup_pool).await {
+                tracing::warn!("OTP session cleanup error: {}", e);
+            }
+        }
+    });
+
+    // Periodic metrics snapshots for the dashboard's history charts
+    let metrics_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(metrics_interval.max(60)));
+        loop {
+            interval.tick().await;
+            if let Err(e) = hermes_shared::db::record_metrics_snapshot(&metrics_pool).await {
+                tracing::warn!("Metrics snapshot error: {}", e);
+            }
+            match hermes_shared::db::prune_metrics_history(&metrics_pool, metrics_retention_days).await {
+                Ok(n) if n > 0 => info!("Pruned {} old metrics snapshots", n),
+                Err(e) => tracing::warn!("Metrics prune error: {}", e),
+                _ => {}
+            }
+        }
+    });
+
     // CORS
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -118,11 +170,15 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/auth/logout", delete(routes::logout))
         .route("/api/download", post(routes::submit_download))
         .route("/api/download/batch", post(routes::batch_download))
+        .route("/api/batch/:batch_id", get(routes::get_batch))
         .route("/api/tasks", get(routes::list_tasks))
+        .route("/api/tasks/bulk", patch(routes::bulk_update_labels))
+        .route("/api/tasks/events", get(routes::task_events))
         .route("/api/tasks/:id", get(routes::get_task))
         .route("/api/tasks/:id", delete(routes::cancel_task))
         .route("/api/tasks/:id", put(routes::update_task))
         .route("/api/tasks/:id/retry", post(routes::retry_task))
+        .route("/api/tasks/:id/events", get(routes::single_task_events))
         .route("/api/files", get(routes::list_files))
         .route("/api/files/history", delete(routes::clear_history))
         .route("/api/files/:id/download", get(routes::download_file))
@@ -130,9 +186,22 @@ async fn main() -> anyhow::Result<()> {
         // User preferences
         .route("/api/user/preferences", get(routes::get_user_preferences))
         .route("/api/user/preferences", put(routes::update_user_preferences))
+        .route("/api/user/quota", get(routes::get_user_quota))
+        .route("/api/user/settings", get(routes::list_user_settings))
+        .route("/api/user/settings/:key", get(routes::get_user_setting))
+        .route("/api/user/settings/:key", put(routes::put_user_setting))
+        // Watch-later list
+        .route("/api/saved", get(routes::list_saved_items))
+        .route("/api/saved", post(routes::create_saved_item))
+        .route("/api/saved/:id", delete(routes::delete_saved_item))
         // Admin routes
         .route("/api/admin/stats", get(routes::admin_stats))
+        .route("/api/admin/metrics-history", get(routes::admin_metrics_history))
+        .route("/api/admin/drain", get(routes::admin_drain_status))
+        .route("/api/admin/drain", post(routes::admin_start_drain))
+        .route("/api/admin/drain", delete(routes::admin_stop_drain))
         .route("/api/admin/users", get(routes::admin_users))
+        .route("/api/admin/users/:id/ban", put(routes::admin_ban_user))
         .route("/api/admin/logs", get(routes::admin_logs))
         .route("/api/admin/settings", get(routes::admin_get_settings))
         .route("/api/admin/settings", put(routes::admin_update_settings))
@@ -0,0 +1,242 @@
+/// Live task progress for the dashboard (Server-Sent Events).
+///
+/// The bot runs in its own process and mirrors queue progress into the
+/// `tasks` table. One poller here reads the unfinished rows, diffs them
+/// against the previous tick and publishes the changes on a broadcast
+/// channel. Every SSE stream subscribes to that channel, so the database is
+/// read once per tick no matter how many dashboards are open.
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::response::sse::Event;
+use futures_util::Stream;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use hermes_shared::db;
+use hermes_shared::models::Task;
+
+/// How often the poller reads task rows.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Events buffered per subscriber before a slow one starts skipping.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// A change to one task, as sent to the dashboard.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskEvent {
+    /// SSE event name: "status" when the task changed state, else "progress".
+    #[serde(skip)]
+    pub kind: &'static str,
+    #[serde(skip)]
+    pub chat_id: i64,
+    pub task_id: String,
+    pub status: String,
+    pub progress: i32,
+    pub speed: Option<String>,
+    pub eta_secs: Option<i64>,
+    pub error: Option<String>,
+}
+
+impl TaskEvent {
+    pub fn status(task: &Task) -> Self {
+        Self {
+            kind: "status",
+            chat_id: task.chat_id,
+            task_id: task.id.clone(),
+            status: task.status.clone(),
+            progress: task.progress,
+            speed: task.speed.clone(),
+            eta_secs: task.eta_secs,
+            error: task.error_msg.clone(),
+        }
+    }
+
+    /// Whether the task is finished and won't change again.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self.status.as_str(), "done" | "error" | "cancelled")
+    }
+
+    fn to_sse(&self) -> Event {
+        Event::default().event(self.kind).json_data(self).unwrap_or_default()
+    }
+}
+
+/// The event announcing how `task` changed since it looked like `prev`.
+fn change_event(prev: Option<&TaskEvent>, task: &Task) -> Option<TaskEvent> {
+    let current = TaskEvent::status(task);
+    match prev {
+        Some(prev) if prev.status == current.status => {
+            let moved = prev.progress != current.progress
+                || prev.speed != current.speed
+                || prev.eta_secs != current.eta_secs;
+            moved.then_some(TaskEvent { kind: "progress", ..current })
+        }
+        _ => Some(current),
+    }
+}
+
+/// Fan-out point between the poller and SSE streams.
+pub struct ProgressHub {
+    tx: broadcast::Sender<TaskEvent>,
+}
+
+impl Default for ProgressHub {
+    fn default() -> Self {
+        Self { tx: broadcast::channel(CHANNEL_CAPACITY).0 }
+    }
+}
+
+impl ProgressHub {
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Poll the database for task changes while anyone is subscribed.
+    pub fn spawn_poller(self: &Arc<Self>, pool: SqlitePool) {
+        let hub = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut last: HashMap<String, TaskEvent> = HashMap::new();
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if hub.tx.receiver_count() == 0 {
+                    last.clear();
+                    continue;
+                }
+                let active = match db::get_active_tasks(&pool).await {
+                    Ok(tasks) => tasks,
+                    Err(e) => {
+                        warn!("Progress poll failed: {}", e);
+                        continue;
+                    }
+                };
+
+                let mut seen = HashMap::with_capacity(active.len());
+                for task in &active {
+                    if let Some(event) = change_event(last.get(&task.id), task) {
+                        let _ = hub.tx.send(event);
+                    }
+                    seen.insert(task.id.clone(), TaskEvent::status(task));
+                }
+                // Tasks that left the active set: report how they ended
+                for (id, prev) in &last {
+                    if seen.contains_key(id) {
+                        continue;
+                    }
+                    if let Ok(Some(task)) = db::get_task_by_id(&pool, id).await {
+                        if let Some(event) = change_event(Some(prev), &task) {
+                            let _ = hub.tx.send(event);
+                        }
+                    }
+                }
+                last = seen;
+            }
+        });
+    }
+}
+
+struct StreamState {
+    pending: VecDeque<TaskEvent>,
+    rx: broadcast::Receiver<TaskEvent>,
+    chat_id: i64,
+    task_id: Option<String>,
+    finished: bool,
+}
+
+/// SSE events for a chat's tasks (or only `task_id`), starting with `initial`.
+/// A single-task stream ends once the task finishes.
+pub fn event_stream(
+    initial: Vec<TaskEvent>,
+    rx: broadcast::Receiver<TaskEvent>,
+    chat_id: i64,
+    task_id: Option<String>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let state = StreamState { pending: initial.into(), rx, chat_id, task_id, finished: false };
+    futures_util::stream::unfold(state, |mut state| async move {
+        if state.finished {
+            return None;
+        }
+        let event = match state.pending.pop_front() {
+            Some(event) => event,
+            None => loop {
+                match state.rx.recv().await {
+                    Ok(event) if event.chat_id == state.chat_id
+                        && state.task_id.as_ref().is_none_or(|id| *id == event.task_id) => break event,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Progress stream for chat {} skipped {} event(s)", state.chat_id, skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+        };
+        state.finished = state.task_id.is_some() && event.is_terminal();
+        Some((Ok(event.to_sse()), state))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn task(id: &str, chat_id: i64, status: &str, progress: i32) -> Task {
+        Task {
+            id: id.to_string(),
+            chat_id,
+            task_type: "youtube_dl".to_string(),
+            url: "https://youtu.be/x".to_string(),
+            label: None,
+            status: status.to_string(),
+            progress,
+            file_path: None,
+            file_url: None,
+            scheduled_at: None,
+            started_at: None,
+            finished_at: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            error_msg: None,
+            batch_id: None,
+            speed: None,
+            eta_secs: None,
+            priority: "normal".to_string(),
+            file_size: None,
+            ipc_request: None,
+        }
+    }
+
+    #[test]
+    fn test_change_event_kinds() {
+        let queued = TaskEvent::status(&task("t1", 1, "queued", 0));
+        assert_eq!(change_event(None, &task("t1", 1, "queued", 0)).unwrap().kind, "status");
+        assert_eq!(change_event(Some(&queued), &task("t1", 1, "queued", 0)), None);
+        assert_eq!(change_event(Some(&queued), &task("t1", 1, "running", 5)).unwrap().kind, "status");
+
+        let running = TaskEvent::status(&task("t1", 1, "running", 5));
+        let event = change_event(Some(&running), &task("t1", 1, "running", 40)).unwrap();
+        assert_eq!((event.kind, event.progress), ("progress", 40));
+    }
+
+    #[tokio::test]
+    async fn test_task_stream_filters_and_ends_when_finished() {
+        let hub = ProgressHub::default();
+        let initial = vec![TaskEvent::status(&task("t1", 1, "running", 10))];
+        let stream = event_stream(initial, hub.subscribe(), 1, Some("t1".to_string()));
+
+        let other_chat = TaskEvent::status(&task("t1", 2, "running", 50));
+        let other_task = TaskEvent::status(&task("t2", 1, "running", 50));
+        for event in [other_chat, other_task, TaskEvent::status(&task("t1", 1, "done", 100))] {
+            hub.tx.send(event).unwrap();
+        }
+
+        let events: Vec<_> = tokio::time::timeout(Duration::from_secs(1), stream.collect::<Vec<_>>())
+            .await
+            .expect("stream should end after the task is done");
+        assert_eq!(events.len(), 2);
+    }
+}
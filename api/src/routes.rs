@@ -2,16 +2,24 @@
 use axum::body::Body;
 use axum::extract::{Path, Query, State};
 use axum::http::{HeaderMap, StatusCode, header};
-use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use futures_util::Stream;
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::ReaderStream;
 use tracing::{info, warn, error};
 
 use hermes_shared::db;
+use hermes_shared::safe_path;
 
 use crate::auth;
+use crate::byte_range::ByteRange;
+use crate::progress::{self, TaskEvent};
+use crate::transfers::{self, TrackedFile};
 use crate::AppState;
 
 // ====== REQUEST / RESPONSE TYPES ======
@@ -48,6 +56,23 @@ pub struct AuthResponse {
 #[derive(Deserialize)]
 pub struct TasksQuery {
     pub status: Option<String>,
+    /// 1-based page number.
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// `created_at` (default) or `finished_at`.
+    pub sort: Option<String>,
+    /// `desc` (default) or `asc`.
+    pub order: Option<String>,
+}
+
+/// Default and largest page size for GET /api/tasks.
+const TASKS_PER_PAGE: i64 = 100;
+const MAX_TASKS_PER_PAGE: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Session token, for clients that can't send headers (EventSource).
+    pub token: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -55,6 +80,21 @@ pub struct DownloadBody {
     pub url: String,
     #[serde(default = "default_download_type")]
     pub download_type: String,
+    /// Start later instead of now: "22:00", "+2h" (user's timezone) or RFC 3339.
+    #[serde(default)]
+    pub scheduled_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveItemBody {
+    pub url: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BanBody {
+    pub banned: bool,
 }
 
 fn default_download_type() -> String {
@@ -74,6 +114,18 @@ pub struct UpdateTaskBody {
     pub label: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct BulkLabelBody {
+    pub ids: Vec<String>,
+    pub label: String,
+}
+
+#[derive(Deserialize)]
+pub struct MetricsHistoryQuery {
+    /// RFC 3339 or "YYYY-MM-DD HH:MM:SS" (UTC). Defaults to 30 days ago.
+    pub since: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct LogsQuery {
     /// Comma-separated service names: hermes-bot,hermes-api,hermes-ui
@@ -116,14 +168,26 @@ pub async fn request_otp(
     let otp = auth::generate_otp();
 
     // Store in DB
-    if let Err(e) = db::create_otp_session(&state.pool, chat_id, &otp).await {
-        warn!("Failed to create OTP session: {}", e);
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(MessageResponse {
-                message: "Failed to create OTP session".to_string(),
-            }),
-        ));
+    match db::create_otp_session(&state.pool, chat_id, &otp).await {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("OTP session cap reached, rejecting request for chat_id {}", chat_id);
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(MessageResponse {
+                    message: "Too many pending logins. Try again in a few minutes.".to_string(),
+                }),
+            ));
+        }
+        Err(e) => {
+            warn!("Failed to create OTP session: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(MessageResponse {
+                    message: "Failed to create OTP session".to_string(),
+                }),
+            ));
+        }
     }
 
     // Send via Telegram
@@ -379,6 +443,39 @@ pub async fn token_login(
 
 // ====== DOWNLOAD ROUTE ======
 
+/// Refuse `count` new downloads: `403` for banned users, `429` with the reason
+/// and quota standing when the quota is used up or has fewer than `count`
+/// downloads left. The admin is never limited.
+async fn download_refusal(state: &AppState, chat_id: i64, count: usize) -> Option<(StatusCode, Json<serde_json::Value>)> {
+    if chat_id == state.admin_chat_id {
+        return None;
+    }
+    if db::is_banned_user(&state.pool, chat_id).await.unwrap_or(false) {
+        return Some((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "You have been banned from this bot" })),
+        ));
+    }
+    let status = match db::get_quota_status(&state.pool, chat_id).await {
+        Ok(status) => status,
+        Err(e) => {
+            warn!("Quota check failed for {}: {}", chat_id, e);
+            return None;
+        }
+    };
+    let reason = status.exceeded().or_else(|| {
+        let remaining = status.downloads_remaining()?;
+        (count as u64 > remaining).then(|| format!(
+            "Only {} download{} left in your quota, this batch has {}.",
+            remaining, if remaining == 1 { "" } else { "s" }, count
+        ))
+    })?;
+    Some((
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(serde_json::json!({ "error": reason, "quota": status })),
+    ))
+}
+
 /// POST /api/download - Queue a download from the web dashboard
 pub async fn submit_download(
     State(state): State<Arc<AppState>>,
@@ -395,11 +492,44 @@ pub async fn submit_download(
         ));
     }
 
+    if let Some(refused) = download_refusal(&state, user.chat_id, 1).await {
+        return Ok(refused);
+    }
+
     let task_id = uuid::Uuid::new_v4().to_string();
     let task_type = "youtube_dl";
     let label = Some(body.download_type.as_str());
 
-    match db::create_web_task(&state.pool, &task_id, user.chat_id, &url, task_type, label).await {
+    if let Some(when) = body.scheduled_at.as_deref().filter(|w| !w.trim().is_empty()) {
+        let tz = db::get_user_setting_or_default(&state.pool, user.chat_id, "timezone").await;
+        let at = match hermes_shared::schedule::parse_start_time(when, chrono::Utc::now(), hermes_shared::user_settings::timezone(&tz)) {
+            Ok(at) => at,
+            Err(e) => return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e })))),
+        };
+        return match db::create_scheduled_task(&state.pool, &task_id, user.chat_id, &url, task_type, label, at.naive_utc()).await {
+            Ok(_) => {
+                info!("Web download scheduled: task={} chat_id={} at={}", task_id, user.chat_id, at);
+                Ok((
+                    StatusCode::CREATED,
+                    Json(serde_json::json!({
+                        "task_id": task_id,
+                        "message": "Download scheduled",
+                        "status": "scheduled",
+                        "scheduled_at": at.to_rfc3339(),
+                    })),
+                ))
+            }
+            Err(e) => {
+                warn!("Failed to create scheduled task: {}", e);
+                Ok((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": format!("Failed to schedule: {}", e) })),
+                ))
+            }
+        };
+    }
+
+    match db::create_web_task(&state.pool, &task_id, user.chat_id, &url, task_type, label, None).await {
         Ok(_) => {
             info!("Web download queued: task={} chat_id={} url={}", task_id, user.chat_id, url);
             Ok((
@@ -433,6 +563,8 @@ pub async fn batch_download(
         .map(|u| u.trim().to_string())
         .filter(|u| !u.is_empty())
         .collect();
+    // The same link pasted twice (or as youtu.be and watch?v=) is one download
+    let (urls, duplicates_removed) = hermes_shared::url_canon::dedup_urls(&urls);
 
     if urls.is_empty() {
         return Ok((
@@ -440,6 +572,9 @@ pub async fn batch_download(
             Json(serde_json::json!({ "error": "No valid URLs provided" })),
         ));
     }
+    if let Some(refused) = download_refusal(&state, user.chat_id, urls.len()).await {
+        return Ok(refused);
+    }
 
     if urls.len() > 20 {
         return Ok((
@@ -450,14 +585,15 @@ pub async fn batch_download(
 
     let task_type = "youtube_dl";
     let label = Some(body.download_type.as_str());
+    let batch_id = uuid::Uuid::new_v4().to_string();
     let mut created = Vec::new();
     let mut errors = Vec::new();
 
     for url in &urls {
         let task_id = uuid::Uuid::new_v4().to_string();
-        match db::create_web_task(&state.pool, &task_id, user.chat_id, url, task_type, label).await {
+        match db::create_web_task(&state.pool, &task_id, user.chat_id, url, task_type, label, Some(&batch_id)).await {
             Ok(_) => {
-                info!("Batch download queued: task={} url={}", task_id, url);
+                info!("Batch download queued: batch={} task={} url={}", batch_id, task_id, url);
                 created.push(serde_json::json!({ "task_id": task_id, "url": url }));
             }
             Err(e) => {
@@ -470,7 +606,10 @@ pub async fn batch_download(
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({
+            // No batch to track when every URL failed
+            "batch_id": if created.is_empty() { None } else { Some(&batch_id) },
             "created": created.len(),
+            "duplicates_removed": duplicates_removed,
             "failed": errors.len(),
             "tasks": created,
             "errors": errors,
@@ -478,8 +617,53 @@ pub async fn batch_download(
     ))
 }
 
+/// GET /api/batch/:batch_id - Aggregate progress of a batch download
+pub async fn get_batch(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(batch_id): Path<String>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    // Scoped to the caller, so another user's batch id reads as not found
+    let summary = match db::get_batch_summary(&state.pool, &batch_id, user.chat_id).await {
+        Ok(Some(summary)) => summary,
+        Ok(None) => {
+            return Ok((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": "Batch not found" })),
+            ));
+        }
+        Err(e) => {
+            return Ok((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": format!("Failed to fetch batch: {}", e) })),
+            ));
+        }
+    };
+
+    match db::get_batch_tasks(&state.pool, &batch_id, user.chat_id).await {
+        Ok(tasks) => Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({ "batch": summary, "tasks": tasks })),
+        )),
+        Err(e) => Ok((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("Failed to fetch batch: {}", e) })),
+        )),
+    }
+}
+
 // ====== TASK ROUTES ======
 
+/// The caller's `timezone` setting and its current UTC offset. Task timestamps
+/// stay UTC in responses; the dashboard renders them in this zone.
+async fn timezone_fields(state: &AppState, chat_id: i64) -> (String, i32) {
+    let tz = db::get_user_setting_or_default(&state.pool, chat_id, "timezone").await;
+    let offset = hermes_shared::user_settings::utc_offset_secs(&tz);
+    (hermes_shared::user_settings::timezone(&tz).name().to_string(), offset)
+}
+
 /// GET /api/tasks
 pub async fn list_tasks(
     State(state): State<Arc<AppState>>,
@@ -488,8 +672,40 @@ pub async fn list_tasks(
 ) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
     let user = auth::authenticate(&headers, &state).await?;
 
-    match db::get_user_tasks_by_status(&state.pool, user.chat_id, query.status.as_deref()).await {
-        Ok(tasks) => Ok((StatusCode::OK, Json(serde_json::json!({ "tasks": tasks })))),
+    let sort = match query.sort.as_deref() {
+        None => db::TaskSort::default(),
+        Some(s) => match db::TaskSort::parse(s) {
+            Some(sort) => sort,
+            None => return Ok((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "sort must be created_at or finished_at" })),
+            )),
+        },
+    };
+    let descending = match query.order.as_deref() {
+        None | Some("desc") => true,
+        Some("asc") => false,
+        Some(_) => return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "order must be asc or desc" })),
+        )),
+    };
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(TASKS_PER_PAGE).clamp(1, MAX_TASKS_PER_PAGE);
+
+    let (timezone, utc_offset_secs) = timezone_fields(&state, user.chat_id).await;
+    match db::get_user_tasks_page(
+        &state.pool, user.chat_id, query.status.as_deref(),
+        sort, descending, per_page, (page - 1).saturating_mul(per_page),
+    ).await {
+        Ok((tasks, total)) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "tasks": tasks,
+            "total": total,
+            "page": page,
+            "per_page": per_page,
+            "timezone": timezone,
+            "utc_offset_secs": utc_offset_secs,
+        })))),
         Err(e) => Ok((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": format!("Failed to fetch tasks: {}", e) })),
@@ -497,6 +713,47 @@ pub async fn list_tasks(
     }
 }
 
+/// GET /api/tasks/events — live progress for all of the user's unfinished
+/// tasks (Server-Sent Events), starting with their current state.
+pub async fn task_events(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate_query(&headers, query.token, &state).await?;
+
+    // Subscribe before reading the rows so no change falls in between
+    let rx = state.progress.subscribe();
+    let initial = db::get_active_tasks(&state.pool).await
+        .unwrap_or_default()
+        .iter()
+        .filter(|t| t.chat_id == user.chat_id)
+        .map(TaskEvent::status)
+        .collect();
+    let stream = progress::event_stream(initial, rx, user.chat_id, None);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// GET /api/tasks/:id/events — live progress for one task (Server-Sent
+/// Events). Starts with its current state and ends once it finishes.
+pub async fn single_task_events(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(task_id): Path<String>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate_query(&headers, query.token, &state).await?;
+
+    let rx = state.progress.subscribe();
+    let task = match db::get_task_by_id(&state.pool, &task_id).await {
+        Ok(Some(task)) if task.chat_id == user.chat_id => task,
+        Ok(_) => return Err((StatusCode::NOT_FOUND, Json(auth::ErrorBody { error: "Task not found".to_string() }))),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(auth::ErrorBody { error: format!("{}", e) }))),
+    };
+    let stream = progress::event_stream(vec![TaskEvent::status(&task)], rx, user.chat_id, Some(task_id));
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 /// GET /api/tasks/:id
 pub async fn get_task(
     State(state): State<Arc<AppState>>,
@@ -513,7 +770,12 @@ pub async fn get_task(
                     Json(serde_json::json!({ "error": "Access denied" })),
                 ));
             }
-            Ok((StatusCode::OK, Json(serde_json::json!({ "task": task }))))
+            let (timezone, utc_offset_secs) = timezone_fields(&state, user.chat_id).await;
+            Ok((StatusCode::OK, Json(serde_json::json!({
+                "task": task,
+                "timezone": timezone,
+                "utc_offset_secs": utc_offset_secs,
+            }))))
         }
         Ok(None) => Ok((
             StatusCode::NOT_FOUND,
@@ -527,6 +789,9 @@ pub async fn get_task(
 }
 
 /// DELETE /api/tasks/:id
+///
+/// Marks the task cancelled; the bot notices within a few seconds and stops
+/// the download, worker process included.
 pub async fn cancel_task(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -592,6 +857,9 @@ pub async fn retry_task(
         Ok(None) => return Ok((StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "Task not found" })))),
         Err(e) => return Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": format!("{}", e) })))),
     }
+    if let Some(refused) = download_refusal(&state, user.chat_id, 1).await {
+        return Ok(refused);
+    }
 
     match db::retry_task(&state.pool, &task_id).await {
         Ok(true) => {
@@ -630,6 +898,46 @@ pub async fn update_task(
     }
 }
 
+/// Most tasks one bulk label update may touch.
+const MAX_BULK_TASKS: usize = 500;
+
+/// PATCH /api/tasks/bulk - Set the label on several of the user's tasks at once.
+/// Tasks that don't exist or belong to someone else are skipped and listed.
+pub async fn bulk_update_labels(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<BulkLabelBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    let label = body.label.trim().to_lowercase();
+    if !hermes_shared::models::TASK_LABELS.contains(&label.as_str()) {
+        return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("Unknown label '{}'. Use one of: {}", body.label, hermes_shared::models::TASK_LABELS.join(", ")),
+        }))));
+    }
+    if body.ids.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "No task ids given" }))));
+    }
+    if body.ids.len() > MAX_BULK_TASKS {
+        return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": format!("At most {} tasks per request", MAX_BULK_TASKS),
+        }))));
+    }
+
+    match db::bulk_update_labels(&state.pool, user.chat_id, &body.ids, &label).await {
+        Ok((updated, skipped)) => {
+            info!("Bulk label update: user={} label={} updated={} skipped={}", user.chat_id, label, updated, skipped.len());
+            Ok((StatusCode::OK, Json(serde_json::json!({
+                "updated": updated,
+                "skipped": skipped.len(),
+                "skipped_ids": skipped,
+            }))))
+        }
+        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": format!("{}", e) })))),
+    }
+}
+
 // ====== FILES ROUTES ======
 
 /// GET /api/files
@@ -639,8 +947,13 @@ pub async fn list_files(
 ) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
     let user = auth::authenticate(&headers, &state).await?;
 
+    let (timezone, utc_offset_secs) = timezone_fields(&state, user.chat_id).await;
     match db::get_user_completed_files(&state.pool, user.chat_id).await {
-        Ok(files) => Ok((StatusCode::OK, Json(serde_json::json!({ "files": files })))),
+        Ok(files) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "files": files,
+            "timezone": timezone,
+            "utc_offset_secs": utc_offset_secs,
+        })))),
         Err(e) => Ok((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": format!("{}", e) })),
@@ -653,7 +966,7 @@ pub async fn download_file(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(task_id): Path<String>,
-) -> Result<impl IntoResponse, (StatusCode, Json<auth::ErrorBody>)> {
+) -> Result<Response, (StatusCode, Json<auth::ErrorBody>)> {
     let user = auth::authenticate(&headers, &state).await?;
 
     let task = db::get_task_by_id(&state.pool, &task_id)
@@ -668,23 +981,21 @@ pub async fn download_file(
     let file_path = task.file_path
         .ok_or_else(|| (StatusCode::NOT_FOUND, Json(auth::ErrorBody { error: "No file for this task".into() })))?;
 
-    let path = std::path::Path::new(&file_path);
+    let path = contained_file_path(&state, &task_id, &file_path)
+        .ok_or_else(|| (StatusCode::FORBIDDEN, Json(auth::ErrorBody { error: "Access denied".into() })))?;
+    let path = path.as_path();
     if !path.exists() {
         return Err((StatusCode::NOT_FOUND, Json(auth::ErrorBody { error: "File not found on disk".into() })));
     }
 
-    let filename = path.file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("download");
-
-    let file = tokio::fs::File::open(&file_path)
+    file_response(&state, path, &headers)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(auth::ErrorBody { error: format!("Cannot open file: {}", e) })))?;
-
-    let stream = ReaderStream::new(file);
-    let body = Body::from_stream(stream);
+        .map_err(|(status, error)| (status, Json(auth::ErrorBody { error })))
+}
 
-    let content_type = if filename.ends_with(".mp4") || filename.ends_with(".mkv") || filename.ends_with(".webm") {
+/// Content-Type for a downloaded file, by extension.
+fn content_type_for(filename: &str) -> &'static str {
+    if filename.ends_with(".mp4") || filename.ends_with(".mkv") || filename.ends_with(".webm") {
         "video/mp4"
     } else if filename.ends_with(".mp3") {
         "audio/mpeg"
@@ -698,18 +1009,82 @@ pub async fn download_file(
         "audio/wav"
     } else {
         "application/octet-stream"
+    }
+}
+
+/// Stream `path` as an attachment, honouring a single-range `Range` header
+/// (206 + Content-Range) so players can seek and downloads can resume.
+async fn file_response(
+    state: &AppState,
+    path: &std::path::Path,
+    headers: &HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let filename = path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("download");
+
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot open file: {}", e)))?;
+    let len = file.metadata()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot read file: {}", e)))?
+        .len();
+
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    let (status, start, end) = match ByteRange::parse(range, len) {
+        ByteRange::Full => (StatusCode::OK, 0, len),
+        ByteRange::Partial { start, end } => (StatusCode::PARTIAL_CONTENT, start, end + 1),
+        ByteRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+            ).into_response());
+        }
     };
+    if start > 0 {
+        file.seek(std::io::SeekFrom::Start(start))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Cannot read file: {}", e)))?;
+    }
 
+    // Registered until the body is dropped, so deletion waits for the transfer
+    let reader = TrackedFile::new(file, state.transfers.begin(path)).take(end - start);
+    let body = Body::from_stream(ReaderStream::new(reader));
     let disposition = format!("attachment; filename=\"{}\"", filename.replace('"', "_"));
 
-    Ok((
-        StatusCode::OK,
+    let mut response = (
+        status,
         [
-            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_TYPE, content_type_for(filename).to_string()),
             (header::CONTENT_DISPOSITION, disposition),
+            (header::ACCEPT_RANGES, "bytes".to_string()),
+            (header::CONTENT_LENGTH, (end - start).to_string()),
         ],
         body,
-    ))
+    ).into_response();
+    if status == StatusCode::PARTIAL_CONTENT {
+        let content_range = format!("bytes {}-{}/{}", start, end - 1, len);
+        if let Ok(value) = content_range.parse() {
+            response.headers_mut().insert(header::CONTENT_RANGE, value);
+        }
+    }
+    Ok(response)
+}
+
+/// Resolve a task's stored file path, refusing anything outside the download
+/// roots (a tampered row, a symlink, `..` components). Violations are logged.
+fn contained_file_path(state: &AppState, task_id: &str, file_path: &str) -> Option<std::path::PathBuf> {
+    let resolved = state.storage.roots().into_iter().find_map(|root| {
+        safe_path::resolve_within(std::path::Path::new(root), std::path::Path::new(file_path))
+    });
+    if resolved.is_none() {
+        error!(
+            "Refusing file outside download roots: task={} path={:?} roots={:?}",
+            task_id, file_path, state.storage.roots()
+        );
+    }
+    resolved
 }
 
 /// GET /api/dl/:task_id - Public (no auth) file download via temporary token.
@@ -718,8 +1093,9 @@ pub async fn download_file(
 /// sessions table by the bot when a file is too large to send via Telegram.
 pub async fn public_download_file(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Path(task_id): Path<String>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
     // Validate token
     let _chat_id = hermes_shared::db::validate_file_download_token(&state.pool, &task_id)
         .await
@@ -733,42 +1109,15 @@ pub async fn public_download_file(
 
     let file_path = task.file_path.ok_or(StatusCode::NOT_FOUND)?;
 
-    let path = std::path::Path::new(&file_path);
+    let path = contained_file_path(&state, &task_id, &file_path).ok_or(StatusCode::FORBIDDEN)?;
+    let path = path.as_path();
     if !path.exists() {
         return Err(StatusCode::NOT_FOUND);
     }
 
-    let filename = path.file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("download");
-
-    let file = tokio::fs::File::open(&file_path)
+    file_response(&state, path, &headers)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let stream = ReaderStream::new(file);
-    let body = Body::from_stream(stream);
-
-    let content_type = if filename.ends_with(".mp4") || filename.ends_with(".mkv") || filename.ends_with(".webm") {
-        "video/mp4"
-    } else if filename.ends_with(".mp3") {
-        "audio/mpeg"
-    } else if filename.ends_with(".m4a") || filename.ends_with(".aac") {
-        "audio/mp4"
-    } else {
-        "application/octet-stream"
-    };
-
-    let disposition = format!("attachment; filename=\"{}\"", filename.replace('"', "_"));
-
-    Ok((
-        StatusCode::OK,
-        [
-            (header::CONTENT_TYPE, content_type.to_string()),
-            (header::CONTENT_DISPOSITION, disposition),
-        ],
-        body,
-    ))
+        .map_err(|(status, _)| status)
 }
 
 /// DELETE /api/files/:id - Delete a completed download file from disk and DB
@@ -789,17 +1138,11 @@ pub async fn delete_file(
         return Ok((StatusCode::FORBIDDEN, Json(serde_json::json!({ "error": "Access denied" }))));
     }
 
-    // Delete file from disk
+    // Delete file from disk (after the grace period and any in-flight download)
     if let Some(ref file_path) = task.file_path {
-        let path = std::path::Path::new(file_path);
-        if path.exists() {
-            if let Err(e) = std::fs::remove_file(path) {
-                warn!("Failed to delete file {}: {}", file_path, e);
-            }
-        }
-        // Also try to clean up the empty task directory
-        if let Some(parent) = path.parent() {
-            let _ = std::fs::remove_dir(parent); // only succeeds if empty
+        match contained_file_path(&state, &task_id, file_path) {
+            Some(path) => transfers::schedule_removal(state.transfers.clone(), path, state.file_delete_grace),
+            None => return Ok((StatusCode::FORBIDDEN, Json(serde_json::json!({ "error": "Access denied" })))),
         }
     }
 
@@ -814,36 +1157,74 @@ pub async fn delete_file(
 }
 
 /// DELETE /api/files/history - Clear all completed download history and files
+///
+/// Files go first and a row is only removed once its file is gone, so an
+/// interrupted clear can simply be repeated. Files that cannot be deleted keep
+/// their row and are reported in `files_failed`.
 pub async fn clear_history(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
 ) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
     let user = auth::authenticate(&headers, &state).await?;
 
-    match db::clear_user_history(&state.pool, user.chat_id).await {
-        Ok(file_paths) => {
-            let mut deleted_files = 0;
-            for path_opt in &file_paths {
-                if let Some(file_path) = path_opt {
-                    let path = std::path::Path::new(file_path);
-                    if path.exists() {
-                        if std::fs::remove_file(path).is_ok() {
-                            deleted_files += 1;
-                        }
-                        // Try to clean up empty parent dir
-                        if let Some(parent) = path.parent() {
-                            let _ = std::fs::remove_dir(parent);
+    let rows = match db::get_user_history_files(&state.pool, user.chat_id).await {
+        Ok(rows) => rows,
+        Err(e) => return Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": format!("{}", e) })))),
+    };
+
+    let (mut cleared, mut deleted_files, mut pending_files, mut failed_files) = (0, 0, 0, 0);
+    for (task_id, file_path) in rows {
+        let path = match file_path {
+            Some(file_path) => match contained_file_path(&state, &task_id, &file_path) {
+                Some(path) => Some(path).filter(|p| p.exists()),
+                None => {
+                    failed_files += 1;
+                    continue;
+                }
+            },
+            None => None,
+        };
+        if let Some(path) = path {
+            match transfers::try_remove_now(&state.transfers, &path, state.file_delete_grace) {
+                Some(Ok(())) => deleted_files += 1,
+                Some(Err(_)) => {
+                    failed_files += 1;
+                    continue;
+                }
+                None => {
+                    // The row goes once the deferred removal succeeds
+                    pending_files += 1;
+                    let pool = state.pool.clone();
+                    transfers::schedule_removal_then(state.transfers.clone(), path, state.file_delete_grace, move |result| async move {
+                        if result.is_ok() {
+                            let _ = db::delete_task(&pool, &task_id).await;
                         }
-                    }
+                    });
+                    continue;
                 }
             }
-            info!("History cleared: user={}, records={}, files_deleted={}", user.chat_id, file_paths.len(), deleted_files);
-            Ok((StatusCode::OK, Json(serde_json::json!({
-                "message": format!("Cleared {} records, deleted {} files", file_paths.len(), deleted_files)
-            }))))
         }
-        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": format!("{}", e) })))),
+        match db::delete_task(&state.pool, &task_id).await {
+            Ok(()) => cleared += 1,
+            Err(e) => warn!("Failed to delete history row {}: {}", task_id, e),
+        }
+    }
+
+    info!(
+        "History cleared: user={}, records={}, files_deleted={}, files_pending={}, files_failed={}",
+        user.chat_id, cleared, deleted_files, pending_files, failed_files
+    );
+    let mut message = format!("Cleared {} records, deleted {} files", cleared + pending_files, deleted_files + pending_files);
+    if failed_files > 0 {
+        message.push_str(&format!(", {} files could not be deleted", failed_files));
     }
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "message": message,
+        "records_cleared": cleared,
+        "files_deleted": deleted_files,
+        "files_pending": pending_files,
+        "files_failed": failed_files,
+    }))))
 }
 
 // ====== ADMIN ROUTES ======
@@ -864,6 +1245,114 @@ pub async fn admin_stats(
     }
 }
 
+/// This instance's drain flag plus the counts a deploy script polls until
+/// `drained` is true.
+async fn drain_status(state: &AppState) -> (StatusCode, Json<serde_json::Value>) {
+    let instance = db::instance_id();
+    let draining = db::is_web_queue_draining(&state.pool, &instance).await;
+    let stats = db::get_system_stats(&state.pool).await;
+    match (draining, stats) {
+        (Ok(draining), Ok(stats)) => (StatusCode::OK, Json(serde_json::json!({
+            "instance": instance,
+            "draining": draining,
+            "running": stats.running_tasks,
+            "queued": stats.queued_tasks,
+            "drained": draining && stats.running_tasks == 0,
+        }))),
+        (Err(e), _) | (_, Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{}", e) })),
+        ),
+    }
+}
+
+/// POST /api/admin/drain - Stop this instance claiming web tasks ahead of a deploy
+pub async fn admin_start_drain(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let admin = auth::authenticate_admin(&headers, &state).await?;
+
+    if let Err(e) = db::set_web_queue_draining(&state.pool, &db::instance_id(), true).await {
+        return Ok((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{}", e) })),
+        ));
+    }
+    tracing::info!("Web queue drain started by {}", admin.chat_id);
+    Ok(drain_status(&state).await)
+}
+
+/// GET /api/admin/drain - Poll drain progress
+pub async fn admin_drain_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let _admin = auth::authenticate_admin(&headers, &state).await?;
+    Ok(drain_status(&state).await)
+}
+
+/// DELETE /api/admin/drain - Resume claiming web tasks on this instance
+pub async fn admin_stop_drain(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let admin = auth::authenticate_admin(&headers, &state).await?;
+
+    if let Err(e) = db::set_web_queue_draining(&state.pool, &db::instance_id(), false).await {
+        return Ok((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{}", e) })),
+        ));
+    }
+    tracing::info!("Web queue drain lifted by {}", admin.chat_id);
+    Ok(drain_status(&state).await)
+}
+
+/// GET /api/admin/metrics-history?since=... - Periodic counter snapshots for charts
+pub async fn admin_metrics_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<MetricsHistoryQuery>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let _admin = auth::authenticate_admin(&headers, &state).await?;
+
+    let since = match query.since.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => chrono::Utc::now() - chrono::Duration::days(30),
+        Some(s) => match parse_since(s) {
+            Some(t) => t,
+            None => return Ok((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "since must be RFC 3339 or YYYY-MM-DD HH:MM:SS" })),
+            )),
+        },
+    };
+    // Same format SQLite uses for CURRENT_TIMESTAMP, so the comparison is lexical
+    let since = since.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    match db::get_metrics_history(&state.pool, &since).await {
+        Ok(snapshots) => Ok((StatusCode::OK, Json(serde_json::json!({ "since": since, "snapshots": snapshots })))),
+        Err(e) => Ok((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{}", e) })),
+        )),
+    }
+}
+
+/// Parse a `since` query value as RFC 3339, a UTC datetime or a bare date.
+fn parse_since(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&chrono::Utc));
+    }
+    if let Ok(t) = chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Some(t.and_utc());
+    }
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|t| t.and_utc())
+}
+
 /// GET /api/admin/users
 pub async fn admin_users(
     State(state): State<Arc<AppState>>,
@@ -880,6 +1369,39 @@ pub async fn admin_users(
     }
 }
 
+/// PUT /api/admin/users/:id/ban - Ban or unban a user (`{ "banned": true }`)
+pub async fn admin_ban_user(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<i64>,
+    Json(body): Json<BanBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let admin = auth::authenticate_admin(&headers, &state).await?;
+
+    if body.banned && chat_id == state.admin_chat_id {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "The admin can't be banned" })),
+        ));
+    }
+
+    match db::set_user_banned(&state.pool, chat_id, body.banned).await {
+        Ok(changed) => {
+            if changed {
+                info!("User {} {} by admin {}", chat_id, if body.banned { "banned" } else { "unbanned" }, admin.chat_id);
+            }
+            Ok((
+                StatusCode::OK,
+                Json(serde_json::json!({ "chat_id": chat_id, "banned": body.banned, "changed": changed })),
+            ))
+        }
+        Err(e) => Ok((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{}", e) })),
+        )),
+    }
+}
+
 /// GET /api/admin/logs - Fetch recent system logs from journald
 pub async fn admin_logs(
     State(state): State<Arc<AppState>>,
@@ -1030,10 +1552,9 @@ pub async fn admin_logs(
                             "warn"
                         } else if message.contains("  DEBUG ") || message.starts_with("DEBUG ")
                             || message.contains(" - DEBUG - ")
+                            || message.contains("  TRACE ") || message.starts_with("TRACE ")
                         {
                             "debug"
-                        } else if message.contains("  TRACE ") || message.starts_with("TRACE ") {
-                            "debug"
                         } else if message.contains("  INFO ") || message.starts_with("INFO ")
                             || message.contains(" - INFO - ")
                         {
@@ -1260,7 +1781,7 @@ pub async fn update_user_preferences(
 
     // Validate and apply each field
     if let Some(v) = obj.get("audio_format").and_then(|v| v.as_str()) {
-        if ["mp3", "m4a", "opus", "flac"].contains(&v) {
+        if hermes_shared::models::AUDIO_FORMATS.contains(&v) {
             prefs.audio_format = v.to_string();
         } else {
             return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
@@ -1270,7 +1791,7 @@ pub async fn update_user_preferences(
     }
 
     if let Some(v) = obj.get("audio_quality").and_then(|v| v.as_str()) {
-        if ["0", "128", "192", "256", "320"].contains(&v) {
+        if hermes_shared::models::AUDIO_QUALITIES.contains(&v) {
             prefs.audio_quality = v.to_string();
         } else {
             return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
@@ -1280,7 +1801,7 @@ pub async fn update_user_preferences(
     }
 
     if let Some(v) = obj.get("default_mode").and_then(|v| v.as_str()) {
-        if ["audio", "video"].contains(&v) {
+        if hermes_shared::models::DOWNLOAD_MODES.contains(&v) {
             prefs.default_mode = v.to_string();
         } else {
             return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
@@ -1300,7 +1821,7 @@ pub async fn update_user_preferences(
     }
 
     if let Some(v) = obj.get("video_quality").and_then(|v| v.as_str()) {
-        if ["best", "1080", "720", "480"].contains(&v) {
+        if hermes_shared::models::VIDEO_QUALITIES.contains(&v) {
             prefs.video_quality = v.to_string();
         } else {
             return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
@@ -1319,3 +1840,216 @@ pub async fn update_user_preferences(
         })))),
     }
 }
+
+// ====== QUOTAS ======
+
+/// GET /api/user/quota — downloads and data used in the last 24 hours and
+/// 7 days, with the remaining allowance (`null` = no limit)
+pub async fn get_user_quota(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    let status = if user.chat_id == state.admin_chat_id {
+        db::get_quota_usage(&state.pool, user.chat_id).await.map(|usage| {
+            hermes_shared::quota::QuotaStatus::new(hermes_shared::quota::QuotaLimits::default(), usage, true)
+        })
+    } else {
+        db::get_quota_status(&state.pool, user.chat_id).await
+    };
+
+    match status {
+        Ok(status) => Ok((StatusCode::OK, Json(serde_json::json!(status)))),
+        Err(e) => Ok((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("Failed to read quota: {}", e) })),
+        )),
+    }
+}
+
+// ====== GENERIC USER SETTINGS ======
+
+/// GET /api/user/settings — all registered settings with the user's values
+pub async fn list_user_settings(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    let stored: std::collections::HashMap<String, String> =
+        db::get_user_settings(&state.pool, user.chat_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+
+    let mut settings = serde_json::Map::new();
+    for def in hermes_shared::user_settings::REGISTRY {
+        let value = stored.get(def.key).map(String::as_str).unwrap_or(def.default);
+        settings.insert(def.key.to_string(), serde_json::json!({
+            "value": value,
+            "default": def.default,
+            "description": def.description,
+        }));
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({ "settings": settings }))))
+}
+
+/// GET /api/user/settings/:key
+pub async fn get_user_setting(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    let Some(def) = hermes_shared::user_settings::find(&key) else {
+        return Ok((StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "error": format!("Unknown setting '{}'", key)
+        }))));
+    };
+
+    match db::get_user_setting(&state.pool, user.chat_id, def.key).await {
+        Ok(value) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "key": def.key,
+            "value": value.as_deref().unwrap_or(def.default),
+            "default": def.default,
+        })))),
+        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "error": format!("{}", e)
+        })))),
+    }
+}
+
+/// PUT /api/user/settings/:key — body: { "value": "..." }
+pub async fn put_user_setting(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    if hermes_shared::user_settings::find(&key).is_none() {
+        return Ok((StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "error": format!("Unknown setting '{}'", key)
+        }))));
+    }
+
+    // Accept strings as-is and stringify scalar JSON values (bools, numbers)
+    let raw = match body.get("value") {
+        Some(serde_json::Value::String(v)) => v.clone(),
+        Some(v @ (serde_json::Value::Bool(_) | serde_json::Value::Number(_))) => v.to_string(),
+        _ => {
+            return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "error": "Missing 'value' in request body"
+            }))));
+        }
+    };
+
+    let value = match hermes_shared::user_settings::validate(&key, &raw) {
+        Ok(v) => v,
+        Err(msg) => {
+            return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": msg }))));
+        }
+    };
+
+    match db::set_user_setting(&state.pool, user.chat_id, &key, &value).await {
+        Ok(_) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "message": "Setting saved",
+            "key": key,
+            "value": value,
+        })))),
+        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "error": format!("Failed to save setting: {}", e)
+        })))),
+    }
+}
+
+// ====== SAVED ITEMS (WATCH LATER) ======
+
+/// GET /api/saved — the user's watch-later list, newest first
+pub async fn list_saved_items(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    match db::get_saved_items(&state.pool, user.chat_id).await {
+        Ok(items) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "items": items,
+            "max": hermes_shared::models::MAX_SAVED_ITEMS,
+        })))),
+        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "error": format!("Failed to read saved items: {}", e)
+        })))),
+    }
+}
+
+/// POST /api/saved — body: { "url": "...", "note": "..." }
+pub async fn create_saved_item(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<SaveItemBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    let url = body.url.trim();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Ok((StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "A URL starting with http:// or https:// is required"
+        }))));
+    }
+    let note: Option<String> = body.note.as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.chars().take(200).collect());
+
+    let max = hermes_shared::models::MAX_SAVED_ITEMS;
+    let count = db::count_saved_items(&state.pool, user.chat_id).await.unwrap_or(0);
+    if count >= max {
+        return Ok((StatusCode::CONFLICT, Json(serde_json::json!({
+            "error": format!("Saved list is full ({}/{})", count, max)
+        }))));
+    }
+
+    let id = match db::create_saved_item(&state.pool, user.chat_id, url, note.as_deref()).await {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return Ok((StatusCode::CONFLICT, Json(serde_json::json!({
+                "error": "This URL is already saved"
+            }))));
+        }
+        Err(e) => {
+            return Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "error": format!("Failed to save: {}", e)
+            }))));
+        }
+    };
+
+    info!("Saved item #{} created by user={}", id, user.chat_id);
+    match db::get_saved_item(&state.pool, user.chat_id, id).await {
+        Ok(Some(item)) => Ok((StatusCode::CREATED, Json(serde_json::json!({
+            "message": "Saved",
+            "item": item,
+        })))),
+        _ => Ok((StatusCode::CREATED, Json(serde_json::json!({ "message": "Saved", "id": id })))),
+    }
+}
+
+/// DELETE /api/saved/:id
+pub async fn delete_saved_item(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<auth::ErrorBody>)> {
+    let user = auth::authenticate(&headers, &state).await?;
+
+    match db::delete_saved_item(&state.pool, user.chat_id, id).await {
+        Ok(true) => Ok((StatusCode::OK, Json(serde_json::json!({ "message": "Removed" })))),
+        Ok(false) => Ok((StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "Saved item not found" })))),
+        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": format!("{}", e) })))),
+    }
+}
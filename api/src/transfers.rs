@@ -0,0 +1,155 @@
+/// In-flight file transfer tracking.
+///
+/// File downloads register the path they are streaming; deletions go through
+/// `schedule_removal`, which waits out a grace period and any active transfer
+/// of the same file before touching the disk.
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::{info, warn};
+
+/// How often a pending removal re-checks for active transfers.
+const BUSY_POLL: Duration = Duration::from_secs(5);
+
+/// Reference counts of files currently being streamed.
+#[derive(Default)]
+pub struct Transfers {
+    active: Mutex<HashMap<PathBuf, usize>>,
+}
+
+impl Transfers {
+    /// Register a transfer of `path`. It stays active until the guard is dropped.
+    pub fn begin(self: &Arc<Self>, path: &Path) -> TransferGuard {
+        *self.active.lock().unwrap().entry(path.to_path_buf()).or_insert(0) += 1;
+        TransferGuard { transfers: Arc::clone(self), path: path.to_path_buf() }
+    }
+
+    /// Whether any transfer of `path` is in progress.
+    pub fn is_active(&self, path: &Path) -> bool {
+        self.active.lock().unwrap().contains_key(path)
+    }
+}
+
+/// Keeps a transfer registered while alive.
+pub struct TransferGuard {
+    transfers: Arc<Transfers>,
+    path: PathBuf,
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        let mut active = self.transfers.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.path) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// A file that counts as an active transfer until the response body is dropped.
+pub struct TrackedFile {
+    file: tokio::fs::File,
+    _guard: TransferGuard,
+}
+
+impl TrackedFile {
+    pub fn new(file: tokio::fs::File, guard: TransferGuard) -> Self {
+        Self { file, _guard: guard }
+    }
+}
+
+impl AsyncRead for TrackedFile {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+/// Delete a file (and its task directory, if left empty) once the grace period
+/// has passed and nobody is downloading it.
+pub fn schedule_removal(transfers: Arc<Transfers>, path: PathBuf, grace: Duration) {
+    if try_remove_now(&transfers, &path, grace).is_none() {
+        schedule_removal_then(transfers, path, grace, |_| async {});
+    }
+}
+
+/// Remove `path` right away when there is no grace period and no active
+/// transfer. Returns None when the removal has to be deferred instead.
+pub fn try_remove_now(transfers: &Transfers, path: &Path, grace: Duration) -> Option<io::Result<()>> {
+    (grace.is_zero() && !transfers.is_active(path)).then(|| remove_file_and_dir(path))
+}
+
+/// Deferred removal (see `schedule_removal`); `then` runs with the outcome once
+/// the file is gone or could not be deleted.
+pub fn schedule_removal_then<F, Fut>(transfers: Arc<Transfers>, path: PathBuf, grace: Duration, then: F)
+where
+    F: FnOnce(io::Result<()>) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send,
+{
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
+        while transfers.is_active(&path) {
+            info!("Deferring removal of {} until its download finishes", path.display());
+            tokio::time::sleep(BUSY_POLL).await;
+        }
+        then(remove_file_and_dir(&path)).await;
+    });
+}
+
+/// A file that is already gone counts as removed.
+fn remove_file_and_dir(path: &Path) -> io::Result<()> {
+    if path.exists() {
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!("Failed to delete file {}: {}", path.display(), e);
+            return Err(e);
+        }
+    }
+    // Also try to clean up the empty task directory
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::remove_dir(parent); // only succeeds if empty
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guards_are_reference_counted() {
+        let transfers = Arc::new(Transfers::default());
+        let path = Path::new("/downloads/task/file.mp3");
+
+        let first = transfers.begin(path);
+        let second = transfers.begin(path);
+        drop(first);
+        assert!(transfers.is_active(path));
+        drop(second);
+        assert!(!transfers.is_active(path));
+    }
+
+    #[test]
+    fn test_remove_now_only_when_idle_and_no_grace() {
+        let transfers = Arc::new(Transfers::default());
+        let dir = std::env::temp_dir().join(format!("hermes-transfers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.mp3");
+        std::fs::write(&path, b"data").unwrap();
+
+        assert!(try_remove_now(&transfers, &path, Duration::from_secs(30)).is_none());
+        let guard = transfers.begin(&path);
+        assert!(try_remove_now(&transfers, &path, Duration::ZERO).is_none());
+        drop(guard);
+        assert!(matches!(try_remove_now(&transfers, &path, Duration::ZERO), Some(Ok(()))));
+        assert!(!path.exists() && !dir.exists());
+        // Already gone counts as removed
+        assert!(matches!(try_remove_now(&transfers, &path, Duration::ZERO), Some(Ok(()))));
+    }
+}
@@ -0,0 +1,33 @@
+/// Embeds build identity for /version: the git commit hash (HERMES_GIT_HASH)
+/// and the latest commit subjects ($OUT_DIR/recent_changes.txt).
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    let hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=HERMES_GIT_HASH={}", hash);
+
+    // Commit subjects, minus any leading "[ticket]" tag
+    let changes = git(&["log", "-5", "--pretty=%s"])
+        .unwrap_or_default()
+        .lines()
+        .map(|line| match line.strip_prefix('[').and_then(|rest| rest.split_once("] ")) {
+            Some((_, subject)) => subject.to_string(),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(std::path::Path::new(&out_dir).join("recent_changes.txt"), changes)
+        .expect("write recent_changes.txt");
+
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+}
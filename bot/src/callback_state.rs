@@ -8,6 +8,9 @@ use tokio::sync::Mutex;
 use teloxide::types::MessageId;
 use tracing::debug;
 
+use hermes_shared::feeds::Episode;
+use hermes_shared::search_filters::SearchFilters;
+
 /// Download mode: video or audio.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DownloadMode {
@@ -47,6 +50,16 @@ pub struct FormatOption {
     pub extract_audio: bool,
     pub audio_format: Option<String>,
     pub audio_quality: Option<String>,
+    /// Estimated size in bytes, when the source reports one.
+    pub filesize: Option<u64>,
+}
+
+/// An audio track language offered for multi-audio (dubbed) videos.
+#[derive(Debug, Clone)]
+pub struct AudioLanguage {
+    pub code: String,
+    pub label: String,
+    pub original: bool,
 }
 
 /// Pending selection state stored while user views the quality keyboard.
@@ -58,6 +71,12 @@ pub struct PendingSelection {
     pub formats: Vec<FormatOption>,
     pub created_at: std::time::Instant,
     pub title: String,
+    /// Audio languages to choose from; empty for single-audio videos.
+    pub languages: Vec<AudioLanguage>,
+    /// Chosen language code (defaults to the original/first track).
+    pub audio_language: Option<String>,
+    /// Video only: also fetch the subtitle track and send it as a file.
+    pub subtitles: bool,
 }
 
 /// Thread-safe store for pending callback selections.
@@ -84,6 +103,28 @@ impl CallbackStateStore {
         self.inner.lock().await.remove(key)
     }
 
+    /// Select an audio language by index. Returns the updated selection.
+    pub async fn select_language(&self, key: &str, index: usize) -> Option<PendingSelection> {
+        let mut map = self.inner.lock().await;
+        let pending = map.get_mut(key)?;
+        let code = pending.languages.get(index)?.code.clone();
+        pending.audio_language = Some(code);
+        Some(pending.clone())
+    }
+
+    /// Flip the "Include subtitles" toggle. Returns the updated selection.
+    pub async fn toggle_subtitles(&self, key: &str) -> Option<PendingSelection> {
+        let mut map = self.inner.lock().await;
+        let pending = map.get_mut(key)?;
+        pending.subtitles = !pending.subtitles;
+        Some(pending.clone())
+    }
+
+    /// Number of selections currently tracked.
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.len()
+    }
+
     /// Remove expired entries (older than TTL).
     pub async fn cleanup_expired(&self, ttl_secs: u64) {
         let now = std::time::Instant::now();
@@ -97,12 +138,93 @@ impl CallbackStateStore {
     }
 }
 
+/// A `GetFormats` result, parsed and ready to build the quality menu from.
+#[derive(Debug, Clone)]
+pub struct CachedFormats {
+    pub title: String,
+    pub duration: String,
+    pub formats: Vec<FormatOption>,
+    pub languages: Vec<AudioLanguage>,
+    fetched_at: std::time::Instant,
+}
+
+impl CachedFormats {
+    pub fn new(title: String, duration: String, formats: Vec<FormatOption>, languages: Vec<AudioLanguage>) -> Self {
+        Self { title, duration, formats, languages, fetched_at: std::time::Instant::now() }
+    }
+}
+
+/// Short-lived cache of format lists keyed by canonical URL and mode, so
+/// reopening /dv or /da on the same video skips the worker round-trip.
+#[derive(Clone)]
+pub struct FormatCache {
+    inner: Arc<Mutex<HashMap<(String, String), CachedFormats>>>,
+    ttl: std::time::Duration,
+    capacity: usize,
+}
+
+impl FormatCache {
+    pub fn new(ttl: std::time::Duration, capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn key(url: &str, mode: &DownloadMode) -> (String, String) {
+        (hermes_shared::url_canon::canonical_url(url), mode.as_str().to_string())
+    }
+
+    /// Cached formats for this URL and mode, if fetched within the TTL.
+    pub async fn get(&self, url: &str, mode: &DownloadMode) -> Option<CachedFormats> {
+        let key = Self::key(url, mode);
+        let mut map = self.inner.lock().await;
+        match map.get(&key) {
+            Some(entry) if entry.fetched_at.elapsed() < self.ttl => Some(entry.clone()),
+            Some(_) => {
+                map.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache a fetched format list. When full, expired entries go first, then
+    /// the oldest one.
+    pub async fn insert(&self, url: &str, mode: &DownloadMode, entry: CachedFormats) {
+        let key = Self::key(url, mode);
+        let mut map = self.inner.lock().await;
+        if !map.contains_key(&key) && map.len() >= self.capacity {
+            let ttl = self.ttl;
+            map.retain(|_, v| v.fetched_at.elapsed() < ttl);
+            if map.len() >= self.capacity {
+                let oldest = map.iter().min_by_key(|(_, v)| v.fetched_at).map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    map.remove(&oldest);
+                }
+            }
+        }
+        map.insert(key, entry);
+    }
+}
+
 /// Encode callback data for an inline button.
 /// Format: "mode:prefix:index" e.g. "dv:a3f2b1:2"
 pub fn encode_callback(mode: &DownloadMode, prefix: &str, index: usize) -> String {
     format!("{}:{}:{}", mode.callback_prefix(), prefix, index)
 }
 
+/// Encode audio-language callback data. Format: "al:prefix:index"
+pub fn encode_language_callback(prefix: &str, index: usize) -> String {
+    format!("al:{}:{}", prefix, index)
+}
+
+/// Encode the "Include subtitles" toggle. Format: "sb:prefix:0"
+pub fn encode_subtitles_callback(prefix: &str) -> String {
+    format!("sb:{}:0", prefix)
+}
+
 /// Encode cancel callback data.
 pub fn encode_cancel(prefix: &str) -> String {
     format!("cx:{}", prefix)
@@ -135,11 +257,30 @@ pub fn parse_format_options(formats: &[serde_json::Value]) -> Vec<FormatOption>
                 extract_audio: f.get("extract_audio").and_then(|v| v.as_bool()).unwrap_or(false),
                 audio_format: f.get("audio_format").and_then(|v| v.as_str()).map(String::from),
                 audio_quality: f.get("audio_quality").and_then(|v| v.as_str()).map(String::from),
+                filesize: f.get("filesize_approx").and_then(|v| v.as_u64()).filter(|&n| n > 0),
             })
         })
         .collect()
 }
 
+/// Parse audio languages from a format_list response, original track first.
+pub fn parse_audio_languages(languages: &[serde_json::Value]) -> Vec<AudioLanguage> {
+    let mut parsed: Vec<AudioLanguage> = languages
+        .iter()
+        .filter_map(|l| {
+            let code = l.get("code")?.as_str()?.to_string();
+            Some(AudioLanguage {
+                label: l.get("label").and_then(|v| v.as_str()).unwrap_or(&code).to_string(),
+                original: l.get("original").and_then(|v| v.as_bool()).unwrap_or(false),
+                code,
+            })
+        })
+        .collect();
+    // Stable sort keeps the worker's order otherwise
+    parsed.sort_by_key(|l| !l.original);
+    parsed
+}
+
 /// A single search result item for inline keyboard selection.
 #[derive(Debug, Clone)]
 pub struct SearchResultItem {
@@ -148,9 +289,16 @@ pub struct SearchResultItem {
 }
 
 /// Pending search results waiting for user button-tap.
+///
+/// `results` holds every page loaded so far, so result indices in callbacks
+/// stay valid after "More" loads the next page.
 #[derive(Debug, Clone)]
 pub struct SearchPending {
+    pub query:      String,
+    pub filters:    SearchFilters,
     pub results:    Vec<SearchResultItem>,
+    /// The worker reported a further page after the last one loaded.
+    pub has_more:   bool,
     pub created_at: std::time::Instant,
 }
 
@@ -175,6 +323,24 @@ impl SearchStateStore {
         self.inner.lock().await.get(key).cloned()
     }
 
+    /// Add the page of results starting at `offset` and return the updated
+    /// entry. A page that was already added (double tap on "More") is dropped.
+    pub async fn append(&self, key: &str, offset: usize, results: Vec<SearchResultItem>, has_more: bool) -> Option<SearchPending> {
+        let mut map = self.inner.lock().await;
+        let pending = map.get_mut(key)?;
+        if pending.results.len() != offset {
+            return Some(pending.clone());
+        }
+        pending.results.extend(results);
+        pending.has_more = has_more;
+        pending.created_at = std::time::Instant::now();
+        Some(pending.clone())
+    }
+
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.len()
+    }
+
     pub async fn cleanup_expired(&self, ttl_secs: u64) {
         let now = std::time::Instant::now();
         let mut map = self.inner.lock().await;
@@ -187,11 +353,107 @@ pub fn encode_search_callback(prefix: &str, index: usize) -> String {
     format!("sr:{}:{}", prefix, index)
 }
 
+/// Encode a search page callback ("More" / "Back").  Format: "sm:prefix:offset"
+pub fn encode_search_page_callback(prefix: &str, offset: usize) -> String {
+    format!("sm:{}:{}", prefix, offset)
+}
+
 /// Encode search-format callback data.  Format: "sf:prefix:index:a" (audio) or ":v" (video)
 pub fn encode_search_format_callback(prefix: &str, index: usize, is_audio: bool) -> String {
     format!("sf:{}:{}:{}", prefix, index, if is_audio { "a" } else { "v" })
 }
 
+/// Episodes listed by /podcast, waiting for a tap.
+#[derive(Debug, Clone)]
+pub struct PodcastPending {
+    pub feed_title: String,
+    pub episodes:   Vec<Episode>,
+    pub created_at: std::time::Instant,
+}
+
+/// Thread-safe store for /podcast episode keyboards.
+/// Uses peek (not take) so several episodes can be picked from one list.
+#[derive(Clone)]
+pub struct PodcastStateStore {
+    inner: Arc<Mutex<HashMap<String, PodcastPending>>>,
+}
+
+impl PodcastStateStore {
+    pub fn new() -> Self {
+        Self { inner: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub async fn store(&self, key: String, pending: PodcastPending) {
+        self.inner.lock().await.insert(key, pending);
+    }
+
+    pub async fn peek(&self, key: &str) -> Option<PodcastPending> {
+        self.inner.lock().await.get(key).cloned()
+    }
+
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.len()
+    }
+
+    pub async fn cleanup_expired(&self, ttl_secs: u64) {
+        let now = std::time::Instant::now();
+        let mut map = self.inner.lock().await;
+        map.retain(|_, v| now.duration_since(v.created_at).as_secs() < ttl_secs);
+    }
+}
+
+/// Encode a /podcast episode callback.  Format: "pe:prefix:index"
+pub fn encode_podcast_callback(prefix: &str, index: usize) -> String {
+    format!("pe:{}:{}", prefix, index)
+}
+
+/// A finished download's link, behind the "Download again" / "Other quality"
+/// buttons on its completion message.
+#[derive(Debug, Clone)]
+pub struct RedownloadEntry {
+    pub url:        String,
+    pub mode:       DownloadMode,
+    pub created_at: std::time::Instant,
+}
+
+/// Thread-safe store for completion-message buttons, so the URL (often longer
+/// than the 64-byte callback limit) doesn't have to travel in the button.
+/// Uses peek (not take) so the buttons keep working until the entry expires.
+#[derive(Clone)]
+pub struct RedownloadStateStore {
+    inner: Arc<Mutex<HashMap<String, RedownloadEntry>>>,
+}
+
+impl RedownloadStateStore {
+    pub fn new() -> Self {
+        Self { inner: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    pub async fn store(&self, key: String, entry: RedownloadEntry) {
+        self.inner.lock().await.insert(key, entry);
+    }
+
+    pub async fn peek(&self, key: &str) -> Option<RedownloadEntry> {
+        self.inner.lock().await.get(key).cloned()
+    }
+
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.len()
+    }
+
+    pub async fn cleanup_expired(&self, ttl_secs: u64) {
+        let now = std::time::Instant::now();
+        let mut map = self.inner.lock().await;
+        map.retain(|_, v| now.duration_since(v.created_at).as_secs() < ttl_secs);
+    }
+}
+
+/// Encode a completion-message button.  Format: "rd:prefix:0" (download
+/// again) or "rd:prefix:1" (pick another quality)
+pub fn encode_redownload_callback(prefix: &str, other_quality: bool) -> String {
+    format!("rd:{}:{}", prefix, other_quality as u8)
+}
+
 /// Pending playlist download — awaiting user choice of scope, limit, and format.
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -246,6 +508,10 @@ impl PlaylistStateStore {
         self.inner.lock().await.remove(key)
     }
 
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.len()
+    }
+
     pub async fn cleanup_expired(&self, ttl_secs: u64) {
         let now = std::time::Instant::now();
         let mut map = self.inner.lock().await;
@@ -267,3 +533,225 @@ pub fn encode_playlist_limit(key: &str, limit: u32) -> String {
 pub fn encode_playlist_format(key: &str, is_audio: bool) -> String {
     format!("pf:{}:{}", key, if is_audio { "a" } else { "v" })
 }
+
+/// A /history button.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryAction {
+    /// Show this page (0-based).
+    Page(usize),
+    /// Download this task's URL again.
+    Redownload(String),
+}
+
+/// Encode history-page callback. Format: "hp:page"
+pub fn encode_history_page(page: usize) -> String {
+    format!("hp:{}", page)
+}
+
+/// Encode history re-download callback. Format: "hr:task_id"
+pub fn encode_history_redownload(task_id: &str) -> String {
+    format!("hr:{}", task_id)
+}
+
+/// Decode a /history callback ("hp:" or "hr:").
+pub fn decode_history_callback(data: &str) -> Option<HistoryAction> {
+    if let Some(page) = data.strip_prefix("hp:") {
+        return page.parse().ok().map(HistoryAction::Page);
+    }
+    data.strip_prefix("hr:")
+        .filter(|id| !id.is_empty())
+        .map(|id| HistoryAction::Redownload(id.to_string()))
+}
+
+/// A /settings button: each one cycles (or toggles) one preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsAction {
+    Mode,
+    AudioFormat,
+    AudioQuality,
+    VideoQuality,
+    Dedup,
+    /// Back to the defaults (the same as a user who never changed anything).
+    Reset,
+    Close,
+}
+
+impl SettingsAction {
+    const CODES: &'static [(SettingsAction, &'static str)] = &[
+        (SettingsAction::Mode, "mode"),
+        (SettingsAction::AudioFormat, "af"),
+        (SettingsAction::AudioQuality, "aq"),
+        (SettingsAction::VideoQuality, "vq"),
+        (SettingsAction::Dedup, "dedup"),
+        (SettingsAction::Reset, "reset"),
+        (SettingsAction::Close, "x"),
+    ];
+}
+
+/// Encode a /settings callback. Format: "st:field", e.g. "st:af"
+pub fn encode_settings_callback(action: SettingsAction) -> String {
+    let code = SettingsAction::CODES.iter().find(|(a, _)| *a == action).map(|(_, c)| *c).unwrap_or("x");
+    format!("st:{}", code)
+}
+
+/// Decode a /settings callback ("st:").
+pub fn decode_settings_callback(data: &str) -> Option<SettingsAction> {
+    let code = data.strip_prefix("st:")?;
+    SettingsAction::CODES.iter().find(|(_, c)| *c == code).map(|(a, _)| *a)
+}
+
+/// A /saved button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SavedAction {
+    /// Download the item now, as audio (true) or video (false).
+    Download { id: i64, audio: bool },
+    /// Take the item off the list.
+    Remove(i64),
+}
+
+/// Encode a /saved callback. Format: "sv:a|v|x:id", e.g. "sv:a:12"
+pub fn encode_saved_callback(action: SavedAction) -> String {
+    match action {
+        SavedAction::Download { id, audio } => format!("sv:{}:{}", if audio { "a" } else { "v" }, id),
+        SavedAction::Remove(id) => format!("sv:x:{}", id),
+    }
+}
+
+/// Decode a /saved callback ("sv:").
+pub fn decode_saved_callback(data: &str) -> Option<SavedAction> {
+    let (code, id) = data.strip_prefix("sv:")?.split_once(':')?;
+    let id = id.parse().ok()?;
+    match code {
+        "a" => Some(SavedAction::Download { id, audio: true }),
+        "v" => Some(SavedAction::Download { id, audio: false }),
+        "x" => Some(SavedAction::Remove(id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_audio_languages_puts_original_first() {
+        let data = serde_json::json!([
+            {"code": "es", "label": "Spanish", "original": false},
+            {"code": "en", "label": "English", "original": true},
+            {"code": "de"},
+            {"label": "missing code"},
+        ]);
+        let langs = parse_audio_languages(data.as_array().unwrap());
+        let codes: Vec<&str> = langs.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, vec!["en", "es", "de"]);
+        assert_eq!(langs[2].label, "de");
+    }
+
+    #[tokio::test]
+    async fn test_format_cache_ttl_and_capacity() {
+        let entry = || CachedFormats::new("t".into(), "1:00".into(), Vec::new(), Vec::new());
+        let cache = FormatCache::new(std::time::Duration::from_secs(60), 2);
+
+        cache.insert("https://youtu.be/dQw4w9WgXcQ", &DownloadMode::Audio, entry()).await;
+        // Same video through another URL form, but not the other mode
+        assert!(cache.get("https://www.youtube.com/watch?v=dQw4w9WgXcQ&si=x", &DownloadMode::Audio).await.is_some());
+        assert!(cache.get("https://youtu.be/dQw4w9WgXcQ", &DownloadMode::Video).await.is_none());
+
+        cache.insert("https://example.com/a", &DownloadMode::Video, entry()).await;
+        cache.insert("https://example.com/b", &DownloadMode::Video, entry()).await;
+        // Oldest entry evicted at capacity
+        assert!(cache.get("https://youtu.be/dQw4w9WgXcQ", &DownloadMode::Audio).await.is_none());
+        assert!(cache.get("https://example.com/b", &DownloadMode::Video).await.is_some());
+
+        let expired = FormatCache::new(std::time::Duration::ZERO, 2);
+        expired.insert("https://example.com/a", &DownloadMode::Audio, entry()).await;
+        assert!(expired.get("https://example.com/a", &DownloadMode::Audio).await.is_none());
+    }
+
+    #[test]
+    fn test_language_callback_round_trip() {
+        let data = encode_language_callback("a3f2b1", 2);
+        assert_eq!(decode_callback(&data), Some(("al".to_string(), "a3f2b1".to_string(), 2)));
+        let data = encode_subtitles_callback("a3f2b1");
+        assert_eq!(decode_callback(&data), Some(("sb".to_string(), "a3f2b1".to_string(), 0)));
+    }
+
+    #[tokio::test]
+    async fn test_search_store_append_pages() {
+        let item = |n: usize| SearchResultItem { url: format!("https://youtu.be/{}", n), title: n.to_string() };
+        let store = SearchStateStore::new();
+        store.store("k".into(), SearchPending {
+            query: "lo-fi".into(),
+            filters: SearchFilters::default(),
+            results: (0..10).map(item).collect(),
+            has_more: true,
+            created_at: std::time::Instant::now(),
+        }).await;
+
+        let pending = store.append("k", 10, (10..15).map(item).collect(), false).await.unwrap();
+        assert_eq!(pending.results.len(), 15);
+        assert!(!pending.has_more);
+        // A second tap on the same "More" doesn't add the page twice
+        let pending = store.append("k", 10, (10..15).map(item).collect(), true).await.unwrap();
+        assert_eq!(pending.results.len(), 15);
+        assert!(store.append("missing", 0, Vec::new(), false).await.is_none());
+        assert_eq!(encode_search_page_callback("k", 10), "sm:k:10");
+        assert_eq!(decode_callback("sm:k:10"), Some(("sm".to_string(), "k".to_string(), 10)));
+    }
+
+    #[tokio::test]
+    async fn test_redownload_store_keeps_entry_until_expiry() {
+        let store = RedownloadStateStore::new();
+        store.store("a1b2c3".into(), RedownloadEntry {
+            url: "https://youtu.be/dQw4w9WgXcQ".into(),
+            mode: DownloadMode::Video,
+            created_at: std::time::Instant::now(),
+        }).await;
+        // Both buttons can be tapped, more than once
+        assert!(store.peek("a1b2c3").await.is_some());
+        assert_eq!(store.peek("a1b2c3").await.unwrap().mode, DownloadMode::Video);
+        store.cleanup_expired(0).await;
+        assert_eq!(store.len().await, 0);
+
+        let data = encode_redownload_callback("a1b2c3", true);
+        assert_eq!(decode_callback(&data), Some(("rd".to_string(), "a1b2c3".to_string(), 1)));
+        assert_eq!(encode_redownload_callback("a1b2c3", false), "rd:a1b2c3:0");
+    }
+
+    #[test]
+    fn test_settings_callback_round_trip() {
+        for (action, _) in SettingsAction::CODES {
+            assert_eq!(decode_settings_callback(&encode_settings_callback(*action)), Some(*action));
+        }
+        assert_eq!(decode_settings_callback("st:reset"), Some(SettingsAction::Reset));
+        assert_eq!(decode_settings_callback("st:nope"), None);
+        assert_eq!(decode_settings_callback("sb:a3f2b1:0"), None);
+    }
+
+    #[test]
+    fn test_history_callback_round_trip() {
+        assert_eq!(decode_history_callback(&encode_history_page(3)), Some(HistoryAction::Page(3)));
+        let id = "c4a1f2e8-0000-4000-8000-000000000000";
+        let data = encode_history_redownload(id);
+        assert!(data.len() <= 64);
+        assert_eq!(decode_history_callback(&data), Some(HistoryAction::Redownload(id.to_string())));
+        assert_eq!(decode_history_callback("hp:x"), None);
+        assert_eq!(decode_history_callback("hr:"), None);
+        assert_eq!(decode_history_callback("pc:abc:p"), None);
+    }
+
+    #[test]
+    fn test_saved_callback_round_trip() {
+        for action in [
+            SavedAction::Download { id: 12, audio: true },
+            SavedAction::Download { id: 12, audio: false },
+            SavedAction::Remove(7),
+        ] {
+            assert_eq!(decode_saved_callback(&encode_saved_callback(action)), Some(action));
+        }
+        assert_eq!(encode_saved_callback(SavedAction::Remove(7)), "sv:x:7");
+        assert_eq!(decode_saved_callback("sv:q:1"), None);
+        assert_eq!(decode_saved_callback("sv:a:"), None);
+        assert_eq!(decode_saved_callback("sm:abc:10"), None);
+    }
+}
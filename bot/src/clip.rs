@@ -0,0 +1,76 @@
+//! Time ranges for `/clip <url> <start> <end>`.
+//!
+//! Times are seconds (`95`), `M:SS` (`1:35`) or `H:MM:SS` (`1:02:03`). The
+//! worker downloads only that section of the video (yt-dlp `--download-sections`).
+
+/// Seconds into the video for `90`, `1:30` or `1:01:30`. Minutes and seconds
+/// after the first field must be below 60.
+pub fn parse_timestamp(input: &str) -> Option<u64> {
+    let fields: Vec<&str> = input.trim().split(':').collect();
+    if fields.len() > 3 || fields.iter().any(|f| f.is_empty() || !f.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let mut total: u64 = 0;
+    for (i, field) in fields.iter().enumerate() {
+        let n: u64 = field.parse().ok()?;
+        if i > 0 && n >= 60 {
+            return None;
+        }
+        total = total.checked_mul(60)?.checked_add(n)?;
+    }
+    Some(total)
+}
+
+/// Parse `start` and `end` into a range of at most `max_secs` seconds, or a
+/// user-facing reason it can't be clipped.
+pub fn parse_range(start: &str, end: &str, max_secs: u64) -> Result<(u64, u64), String> {
+    let read = |s: &str| {
+        parse_timestamp(s).ok_or_else(|| format!("Can't read the time \"{}\". Use e.g. 95, 1:35 or 1:02:03.", s))
+    };
+    let (start, end) = (read(start)?, read(end)?);
+    if end <= start {
+        return Err("The end time must come after the start time.".to_string());
+    }
+    if end - start > max_secs {
+        return Err(format!("Clips can be at most {} long.", format_length(max_secs)));
+    }
+    Ok((start, end))
+}
+
+/// "45s", "3 min" or "2 min 30s" for a clip length.
+pub fn format_length(secs: u64) -> String {
+    match (secs / 60, secs % 60) {
+        (0, s) => format!("{}s", s),
+        (m, 0) => format!("{} min", m),
+        (m, s) => format!("{} min {}s", m, s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("95"), Some(95));
+        assert_eq!(parse_timestamp("1:35"), Some(95));
+        assert_eq!(parse_timestamp("01:02:03"), Some(3723));
+        assert_eq!(parse_timestamp("0:00"), Some(0));
+        assert_eq!(parse_timestamp("1:60"), None);
+        assert_eq!(parse_timestamp("1::2"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("-5"), None);
+        assert_eq!(parse_timestamp("1m30s"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("1:00", "1:30", 600), Ok((60, 90)));
+        assert!(parse_range("1:30", "1:00", 600).is_err());
+        assert!(parse_range("1:00", "1:00", 600).is_err());
+        assert_eq!(parse_range("0", "11:00", 600), Err("Clips can be at most 10 min long.".to_string()));
+        assert!(parse_range("x", "1:00", 600).unwrap_err().contains("\"x\""));
+        assert_eq!(format_length(150), "2 min 30s");
+        assert_eq!(format_length(45), "45s");
+    }
+}
@@ -1,42 +1,515 @@
 /// Telegram bot command handlers.
 ///
-/// Handles /start, /help, /download, /dv, /da, /do, /search, /status, /cancel, /ping, /upcook, /chatid.
+/// Handles /start, /help, /download, /dv, /da, /do, /search, /status, /cancel, /ping, /upcook, /chatid,
+/// /allowuser, /denyuser, /ban, /unban, /subscribe, /unsubscribe, /subscriptions, /sysinfo, /archive,
+/// /setting, /settings, /retrycookie, /link, /wallpaper, /hardsubs, /subs, /clip, /both, /concat,
+/// /transcribe, /estimate, /fit, /failed, /version, /schedule, /quota, /podcast, /save, /saved.
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use teloxide::prelude::*;
-use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode, Recipient};
+use teloxide::types::{
+    CallbackQuery, ChosenInlineResult, InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery,
+    InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+    MessageId, ParseMode, Recipient,
+};
 use teloxide::utils::command::BotCommands;
 use tracing::{info, error, warn};
 use uuid::Uuid;
+use once_cell::sync::Lazy;
 use tokio::time::Instant;
 
+use hermes_shared::errors::{HermesError, IpcError};
 use hermes_shared::ipc_protocol::*;
-use hermes_shared::task_queue::TaskQueue;
+use hermes_shared::caption;
+use hermes_shared::models::MAX_SAVED_ITEMS;
+use hermes_shared::search_filters::{parse_search_query, SearchFilters};
+use hermes_shared::task_queue::{Priority as QueuePriority, TaskQueue};
+use hermes_shared::storage_dirs::{StorageDirs, StorageKind};
 use sqlx::SqlitePool;
 
-use crate::workers::python_dispatcher::PythonDispatcher;
+use crate::workers::python_dispatcher::Priority;
+use crate::workers::worker_pool::WorkerPool;
+use crate::clip;
 use crate::callback_state::{
     CallbackStateStore, SearchStateStore, SearchPending, SearchResultItem,
-    PlaylistStateStore, PlaylistPending,
-    DownloadMode, FormatOption, PendingSelection,
+    PlaylistStateStore, PlaylistPending, PodcastStateStore, PodcastPending, encode_podcast_callback,
+    RedownloadStateStore, RedownloadEntry, encode_redownload_callback,
+    AudioLanguage, CachedFormats, DownloadMode, FormatCache, FormatOption, PendingSelection,
     decode_callback, encode_callback, encode_cancel, parse_format_options,
-    encode_search_callback, encode_search_format_callback,
+    encode_language_callback, encode_subtitles_callback, parse_audio_languages,
+    encode_search_callback, encode_search_format_callback, encode_search_page_callback,
     encode_playlist_confirm, encode_playlist_limit, encode_playlist_format,
+    HistoryAction, decode_history_callback, encode_history_page, encode_history_redownload,
+    SettingsAction, decode_settings_callback, encode_settings_callback,
+    SavedAction, decode_saved_callback, encode_saved_callback,
 };
+use crate::deep_link::{self, StartPayload};
+use crate::file_split;
 use crate::link_detector;
+use crate::media_group;
+use crate::subscriptions;
+use crate::sysinfo;
+use crate::task_prefix::{self, PrefixMatch, resolve_task_prefix};
+use crate::user_errors;
 use crate::link_detector::DetectedLink;
+use crate::text::{decorate, decorate_markdown, escape_markdown_v2, escape_markdown_v2_code, truncate_filename, MAX_FILENAME_BYTES};
 
 /// Read the dashboard base URL from env or use the default.
 fn dashboard_base_url() -> String {
     std::env::var("DASHBOARD_URL")
-        .unwrap_or_else(|_| "https://tg-herms-bot.pgwiz.cloud".to_string())
+        .unwrap_or_else(|_| "https://tg-hermes-bot.pgwiz.cloud".to_string())
+}
+
+/// How often each subscription is re-checked (SUBSCRIPTION_INTERVAL_SECS, default daily).
+pub fn subscription_interval_secs() -> i64 {
+    std::env::var("SUBSCRIPTION_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .filter(|&s| s >= 300)
+        .unwrap_or(86_400)
+}
+
+/// Max silence between worker events before a download counts as stalled
+/// (IPC_IDLE_TIMEOUT_SECS, default 120).
+fn ipc_idle_timeout_secs() -> u64 {
+    std::env::var("IPC_IDLE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(120)
+}
+
+/// How long a download waits for a crashed or restarting worker before failing.
+fn worker_down_grace_secs() -> u64 {
+    std::env::var("WORKER_DOWN_GRACE_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(120)
+}
+
+/// Pause between send attempts while the worker is down.
+const WORKER_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Reply to links from sites we don't download from (see `YTDLP_GENERIC_ENABLED`).
+const UNSUPPORTED_LINK_TEXT: &str =
+    "❌ This site isn't supported. Send a YouTube, Spotify or Telegram link, or a direct file link.";
+
+/// Whether large files and channel copies may go through the MTProto account (MPROTO).
+fn mtproto_enabled() -> bool {
+    std::env::var("MPROTO")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false)
+}
+
+/// Send files over 50 MB as parts when MTProto is off (SPLIT_LARGE_FILES, default true).
+fn split_large_files() -> bool {
+    std::env::var("SPLIT_LARGE_FILES")
+        .map(|v| v.to_lowercase() != "false")
+        .unwrap_or(true)
+}
+
+/// Private storage channel used by the MTProto account (STORAGE_CHANNEL_ID, 0 if unset).
+fn storage_channel_id() -> i64 {
+    std::env::var("STORAGE_CHANNEL_ID")
+        .ok().and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+/// Leave a public channel again after joining it to copy a post
+/// (MTPROTO_LEAVE_AFTER_JOIN, default true).
+fn mtproto_leave_after_join() -> bool {
+    std::env::var("MTPROTO_LEAVE_AFTER_JOIN")
+        .map(|v| v.to_lowercase() != "false")
+        .unwrap_or(true)
+}
+
+/// Overall limit for a /hardsubs task, download plus re-encode
+/// (HARDSUBS_TIMEOUT_SECS for the re-encode, default 1 hour, plus 10 min).
+fn hardsubs_timeout_secs() -> u64 {
+    let burn = std::env::var("HARDSUBS_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(3600);
+    burn + 600
+}
+
+/// Time allowed for joining a /concat playlist into one file
+/// (CONCAT_TIMEOUT_SECS, default 1 hour, plus 5 min of slack for the worker).
+fn concat_timeout_secs() -> u64 {
+    let join = std::env::var("CONCAT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(3600);
+    join + 300
+}
+
+/// Time allowed for transcribing a /transcribe download
+/// (TRANSCRIBE_TIMEOUT_SECS, default 1 hour, plus 5 min of slack for the worker).
+fn transcribe_timeout_secs() -> u64 {
+    let transcribe = std::env::var("TRANSCRIBE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(3600);
+    transcribe + 300
+}
+
+/// Longest section /clip downloads (CLIP_MAX_SECS, default 10 min).
+fn clip_max_secs() -> u64 {
+    std::env::var("CLIP_MAX_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(600)
+}
+
+/// Subtitle language for /subs without a language and the "Include subtitles"
+/// toggle (SUBTITLE_LANG, default "en").
+fn default_subtitle_lang() -> String {
+    std::env::var("SUBTITLE_LANG")
+        .ok()
+        .map(|l| l.trim().to_string())
+        .filter(|l| is_subtitle_lang(l))
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Subtitle language codes as yt-dlp reports them ("en", "pt-BR", "zh-Hans").
+fn is_subtitle_lang(lang: &str) -> bool {
+    !lang.is_empty() && lang.len() <= 16 && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Size /fit re-encodes an oversized video down to (FIT_TARGET_MB, default 49,
+/// just under the 50 MB Bot API upload limit).
+fn fit_target_bytes() -> u64 {
+    std::env::var("FIT_TARGET_MB")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&mb| mb > 0)
+        .unwrap_or(49)
+        * 1024 * 1024
+}
+
+/// Time allowed for a /fit re-encode
+/// (COMPRESS_TIMEOUT_SECS, default 30 min, plus 5 min of slack for the worker).
+fn compress_timeout_secs() -> u64 {
+    let compress = std::env::var("COMPRESS_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(1800);
+    compress + 300
+}
+
+/// Playlist items joined by /concat (CONCAT_MAX_ITEMS, default 25).
+fn concat_max_items() -> u32 {
+    std::env::var("CONCAT_MAX_ITEMS")
+        .ok()
+        .and_then(|s| s.parse::<u32>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(25)
+}
+
+/// Largest download non-admins may start, in MB (MAX_DOWNLOAD_MB, unset or 0 = no cap).
+fn max_download_mb() -> Option<u64> {
+    std::env::var("MAX_DOWNLOAD_MB")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|&mb| mb > 0)
+}
+
+/// Refuse a new download when the chat has used up its quota (see `/quota`).
+/// Sends the reason and returns true if refused. The admin is never limited.
+async fn quota_exceeded(bot: &Bot, chat_id: ChatId, state: &AppState) -> ResponseResult<bool> {
+    let Some(pool) = &state.db_pool else { return Ok(false) };
+    if state.admin_chat_id == Some(chat_id.0) {
+        return Ok(false);
+    }
+    let reason = match hermes_shared::db::get_quota_status(pool, chat_id.0).await {
+        Ok(status) => status.exceeded(),
+        Err(e) => {
+            warn!("Quota check failed for {}: {}", chat_id.0, e);
+            None
+        }
+    };
+    match reason {
+        Some(reason) => {
+            bot.send_message(chat_id, decorate(format!("⛔ {}\n\nSee /quota for your remaining allowance.", reason))).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Parallel range requests per direct file download (NATIVE_DOWNLOAD_CHUNKS, default 4).
+fn native_download_chunks() -> usize {
+    std::env::var("NATIVE_DOWNLOAD_CHUNKS")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(4)
+}
+
+/// Bandwidth cap per direct file download (DOWNLOAD_RATE_LIMIT, e.g. "2M"; unset = none).
+fn download_rate_limit() -> Option<u64> {
+    std::env::var("DOWNLOAD_RATE_LIMIT")
+        .ok()
+        .and_then(|s| hermes_downloader::parse_rate(&s))
+}
+
+/// User-Agent / header overrides for downloads: a global default from
+/// DOWNLOAD_USER_AGENT and DOWNLOAD_HTTP_HEADERS ("Name: value | Name2: value"),
+/// plus per-site profiles from DOWNLOAD_HTTP_PROFILES, a JSON object keyed by
+/// host, e.g. {"vimeo.com": {"http_headers": {"Referer": "https://vimeo.com/"}}}.
+/// Invalid entries are logged and ignored.
+struct HttpProfiles {
+    global: HttpOptions,
+    by_host: Vec<(String, HttpOptions)>,
+}
+
+static HTTP_PROFILES: Lazy<HttpProfiles> = Lazy::new(|| {
+    let mut global = HttpOptions {
+        user_agent: std::env::var("DOWNLOAD_USER_AGENT").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+        http_headers: Default::default(),
+    };
+    if let Ok(list) = std::env::var("DOWNLOAD_HTTP_HEADERS") {
+        match parse_header_list(&list) {
+            Ok(headers) => global.http_headers = headers,
+            Err(e) => warn!("Ignoring DOWNLOAD_HTTP_HEADERS: {}", e),
+        }
+    }
+    if let Err(e) = global.validate() {
+        warn!("Ignoring global download HTTP options: {}", e);
+        global = HttpOptions::default();
+    }
+
+    let mut by_host = Vec::new();
+    if let Ok(json) = std::env::var("DOWNLOAD_HTTP_PROFILES") {
+        match serde_json::from_str::<HashMap<String, HttpOptions>>(&json) {
+            Ok(profiles) => {
+                for (host, options) in profiles {
+                    match options.validate() {
+                        Ok(()) => by_host.push((host.trim().trim_start_matches("www.").to_lowercase(), options)),
+                        Err(e) => warn!("Ignoring DOWNLOAD_HTTP_PROFILES entry {}: {}", host, e),
+                    }
+                }
+            }
+            Err(e) => warn!("Ignoring DOWNLOAD_HTTP_PROFILES: {}", e),
+        }
+    }
+    HttpProfiles { global, by_host }
+});
+
+/// HTTP options for downloading `url`: the global defaults overlaid with the
+/// profile of its host (a profile for "vimeo.com" also covers "player.vimeo.com").
+fn http_options_for(url: &str) -> HttpOptions {
+    let host = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or("")
+        .rsplit('@')
+        .next()
+        .unwrap_or("")
+        .split(':')
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+
+    let mut options = HTTP_PROFILES.global.clone();
+    for (profile_host, profile) in &HTTP_PROFILES.by_host {
+        if host == *profile_host || host.ends_with(&format!(".{}", profile_host)) {
+            options.merge(profile);
+        }
+    }
+    options
+}
+
+/// How many playlist tracks are uploaded at once (PLAYLIST_SEND_CONCURRENCY,
+/// default 1 = in order, capped at 5 to stay clear of Telegram's flood limits).
+fn playlist_send_concurrency() -> usize {
+    std::env::var("PLAYLIST_SEND_CONCURRENCY")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1)
+        .clamp(1, 5)
+}
+
+/// Whether playlist tracks are sent as albums of up to 10 (PLAYLIST_ALBUMS, default true).
+fn playlist_albums() -> bool {
+    std::env::var("PLAYLIST_ALBUMS")
+        .map(|v| v.to_lowercase() != "false")
+        .unwrap_or(true)
+}
+
+/// Pause after each playlist track upload, per send slot (PLAYLIST_SEND_DELAY_MS, default 500).
+fn playlist_send_delay() -> std::time::Duration {
+    let ms = std::env::var("PLAYLIST_SEND_DELAY_MS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(500);
+    std::time::Duration::from_millis(ms)
+}
+
+/// Comment tag stamped into downloaded media for provenance (FILE_METADATA_TAG, off when unset).
+fn file_metadata_tag() -> Option<String> {
+    std::env::var("FILE_METADATA_TAG")
+        .ok()
+        .map(|s| s.trim().chars().filter(|c| !c.is_control()).take(100).collect::<String>())
+        .filter(|s| !s.is_empty())
+}
+
+/// How long shutdown waits for running downloads before marking them interrupted
+/// (SHUTDOWN_DRAIN_SECS, default 10).
+pub fn shutdown_drain_secs() -> u64 {
+    std::env::var("SHUTDOWN_DRAIN_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(10)
+}
+
+/// Lifetime of /link download tokens (DOWNLOAD_LINK_TTL_SECS, default 24h).
+fn download_link_ttl_secs() -> i64 {
+    std::env::var("DOWNLOAD_LINK_TTL_SECS")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .filter(|&s| s >= 60)
+        .unwrap_or(86_400)
+}
+
+/// Human-readable TTL: "24h", "90m".
+fn format_ttl(secs: i64) -> String {
+    if secs >= 3600 && secs % 3600 == 0 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}m", secs / 60)
+    }
+}
+
+/// Global caption template for sent files (CAPTION_TEMPLATE, default none).
+/// Users override it with the `caption_template` setting.
+fn caption_template() -> String {
+    std::env::var("CAPTION_TEMPLATE").unwrap_or_default()
+}
+
+/// Caption for a delivered file: the user's `caption_template` (or the global
+/// one) filled from `fields`. `None` when captions are off or nothing is left.
+async fn delivery_caption(state: &AppState, chat_id: i64, fields: &caption::CaptionFields) -> Option<String> {
+    let user_value = match &state.db_pool {
+        Some(pool) => hermes_shared::db::get_user_setting_or_default(pool, chat_id, "caption_template").await,
+        None => caption::USE_DEFAULT.to_string(),
+    };
+    let global = caption_template();
+    let template = caption::effective_template(&user_value, &global)?;
+    caption::render(template, fields).map(decorate)
+}
+
+/// Whether the user turned on the `deliver_as_link` setting.
+async fn prefers_link_delivery(state: &AppState, chat_id: i64) -> bool {
+    match &state.db_pool {
+        Some(pool) => hermes_shared::db::get_user_setting_or_default(pool, chat_id, "deliver_as_link").await == "true",
+        None => false,
+    }
+}
+
+/// True if the user keeps downloads in their library only (store_only setting).
+async fn prefers_store_only(state: &AppState, chat_id: i64) -> bool {
+    match &state.db_pool {
+        Some(pool) => hermes_shared::db::get_user_setting_or_default(pool, chat_id, "store_only").await == "true",
+        None => false,
+    }
+}
+
+/// Resampling params for audio downloads from the user's audio_sample_rate /
+/// audio_channels settings. Empty when both keep the source.
+async fn audio_output_params(state: &AppState, chat_id: i64) -> Vec<(&'static str, serde_json::Value)> {
+    let Some(pool) = &state.db_pool else {
+        return Vec::new();
+    };
+    let mut params = Vec::new();
+    let rate = hermes_shared::db::get_user_setting_or_default(pool, chat_id, "audio_sample_rate").await;
+    if let Ok(hz) = rate.parse::<u32>() {
+        params.push(("audio_sample_rate", serde_json::json!(hz)));
+    }
+    match hermes_shared::db::get_user_setting_or_default(pool, chat_id, "audio_channels").await.as_str() {
+        "mono" => params.push(("audio_channels", serde_json::json!(1))),
+        "stereo" => params.push(("audio_channels", serde_json::json!(2))),
+        _ => {}
+    }
+    params
+}
+
+/// How /start shows the chat id (START_CHAT_ID_FORMAT): "code" (monospace, default) or "plain".
+fn start_chat_id_format() -> String {
+    std::env::var("START_CHAT_ID_FORMAT")
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_else(|_| "code".to_string())
+}
+
+/// Max subscriptions per user (MAX_SUBSCRIPTIONS_PER_USER, default 10).
+fn max_subscriptions_per_user() -> i64 {
+    std::env::var("MAX_SUBSCRIPTIONS_PER_USER")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(10)
+}
+
+/// Newest items looked at per subscription check (SUBSCRIPTION_MAX_ITEMS, default 10).
+fn subscription_max_items() -> u32 {
+    std::env::var("SUBSCRIPTION_MAX_ITEMS")
+        .ok()
+        .and_then(|s| s.parse::<u32>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(10)
+}
+
+/// File extensions the bot will send (ALLOWED_FILE_EXTENSIONS, comma-separated).
+/// Anything else the worker produces is treated as a malformed output.
+static ALLOWED_FILE_EXTENSIONS: Lazy<HashSet<String>> = Lazy::new(|| {
+    let list = std::env::var("ALLOWED_FILE_EXTENSIONS").unwrap_or_else(|_| {
+        "mp3,m4a,aac,opus,ogg,oga,flac,wav,mp4,webm,mkv,mov,avi,m4v,jpg,jpeg,png,webp,zip".to_string()
+    });
+    list.split(',')
+        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+});
+
+/// Whether a produced file has an extension on the send allowlist.
+fn is_allowed_output_file(name: &str) -> bool {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| ALLOWED_FILE_EXTENSIONS.contains(&e.to_lowercase()))
+        .unwrap_or(false)
+}
+
+/// Name to save a direct file link under: HLS playlists (`.m3u8`) become an
+/// mp4, anything else needs an extension on the send allowlist.
+fn direct_download_name(name: &str) -> Option<String> {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if ext.eq_ignore_ascii_case("m3u8") => Some(format!("{}.mp4", stem)),
+        _ => is_allowed_output_file(name).then(|| name.to_string()),
+    }
 }
 
 /// Build the per-user, per-task output directory path.
-/// Structure: <download_dir>/<chat_id>/<task_id>/
+/// Structure: <base>/<chat_id>/<task_id>/, where `base` is the root for the
+/// task's kind (see `StorageDirs::base_for`).
+///
+/// A task id that is not a plain path component (separators, `..`) is
+/// logged and replaced, so the directory always stays inside `base`.
 pub fn task_output_dir(base: &str, chat_id: i64, task_id: &str) -> String {
+    let task_dir = if hermes_shared::safe_path::is_safe_component(task_id) {
+        task_id.to_string()
+    } else {
+        error!("Unsafe task id {:?} for output dir, replacing it", task_id);
+        Uuid::new_v4().to_string()
+    };
     let path = std::path::PathBuf::from(base)
         .join(chat_id.to_string())
-        .join(task_id);
+        .join(task_dir);
     path.to_string_lossy().to_string()
 }
 
@@ -53,11 +526,13 @@ async fn load_user_prefs(state: &AppState, chat_id: i64) -> hermes_shared::model
 #[command(rename_rule = "lowercase", description = "Hermes Download Bot commands:")]
 pub enum Command {
     #[command(description = "Start the bot")]
-    Start,
+    Start(String),
     #[command(description = "Show help")]
     Help,
     #[command(description = "Download audio from a URL")]
     Download(String),
+    #[command(description = "Download and get a direct link instead of the file: /link <url>")]
+    Link(String),
     #[command(description = "Download video (choose quality)")]
     Dv(String),
     #[command(description = "Download audio (choose quality)")]
@@ -72,14 +547,44 @@ pub enum Command {
     Playlistv2(String),
     #[command(description = "Search YouTube")]
     Search(String),
+    #[command(description = "Full-resolution thumbnail as a file: /wallpaper <url>")]
+    Wallpaper(String),
+    #[command(description = "Video with burned-in subtitles: /hardsubs <url> <lang>")]
+    Hardsubs(String),
+    #[command(description = "Video plus its audio as a separate file: /both <url>")]
+    Both(String),
+    #[command(description = "Whole playlist as one file: /concat <playlist_url> [video]")]
+    Concat(String),
+    #[command(description = "Only part of a video: /clip <url> <start> <end> [audio]")]
+    Clip(String),
+    #[command(description = "Subtitles as a file: /subs <url> [lang] [vtt]")]
+    Subs(String),
+    #[command(description = "Latest episodes of a podcast feed: /podcast <feed-url>")]
+    Podcast(String),
+    #[command(description = "Speech to text as a document: /transcribe <url> [lang]")]
+    Transcribe(String),
+    #[command(description = "Video squeezed under the upload limit: /fit <url>")]
+    Fit(String),
+    #[command(description = "Size and download time per quality before downloading: /estimate <url>")]
+    Estimate(String),
+    #[command(description = "Retry a failed download with cookies: /retrycookie <task-id>")]
+    RetryCookie(String),
     #[command(description = "Check task status")]
     Status,
+    #[command(description = "Recent failed downloads, with one-tap retry")]
+    Failed,
     #[command(description = "Cancel a download")]
     Cancel(String),
+    #[command(description = "Download later: /schedule <22:00|+2h> <url>, /schedule to list")]
+    Schedule(String),
     #[command(description = "View download history")]
     History,
+    #[command(description = "Remaining download allowance; admins: /quota set|reset <chat_id>")]
+    Quota(String),
     #[command(description = "Health check")]
     Ping,
+    #[command(description = "Bot and worker versions, recent changes")]
+    Version,
     #[command(description = "Update cookies (admin)")]
     Upcook(String),
     #[command(description = "Show your Telegram Chat ID")]
@@ -90,6 +595,32 @@ pub enum Command {
     DedupToggle,
     #[command(description = "Show deduplication status")]
     DedupStatus,
+    #[command(description = "Allowlist a user: /allowuser <chat_id> (admin)")]
+    AllowUser(String),
+    #[command(description = "Remove a user from the allowlist: /denyuser <chat_id> (admin)")]
+    DenyUser(String),
+    #[command(description = "Block a user from the bot and the API: /ban <chat_id> (admin)")]
+    Ban(String),
+    #[command(description = "Lift a ban: /unban <chat_id> (admin)")]
+    Unban(String),
+    #[command(description = "Follow a playlist/channel: /subscribe <url> or /subscribe video <url>")]
+    Subscribe(String),
+    #[command(description = "Stop following: /unsubscribe <id>")]
+    Unsubscribe(String),
+    #[command(description = "List your subscriptions")]
+    Subscriptions,
+    #[command(description = "Keep a link to download later: /save <url> [note]")]
+    Save(String),
+    #[command(description = "Your saved links, with download and remove buttons")]
+    Saved,
+    #[command(description = "View or change a setting: /setting [key] [value]")]
+    Setting(String),
+    #[command(description = "Download preferences as buttons")]
+    Settings,
+    #[command(description = "Copy channel posts to you and delete the originals (admin)")]
+    Archive(String),
+    #[command(description = "Process resource usage (admin)")]
+    Sysinfo,
     #[command(description = "off")]
     Restart,
     #[command(description = "off")]
@@ -98,14 +629,70 @@ pub enum Command {
 
 /// Shared application state passed to handlers.
 pub struct AppState {
-    pub dispatcher: PythonDispatcher,
+    pub dispatcher: WorkerPool,
     pub task_queue: TaskQueue,
     pub download_dir: String,
+    /// Per-kind download roots (AUDIO_DIR, VIDEO_DIR, PLAYLIST_DIR).
+    pub storage: StorageDirs,
     pub callback_store: CallbackStateStore,
+    /// Recent `GetFormats` results for the /dv and /da quality menus.
+    pub format_cache: FormatCache,
     pub search_store: SearchStateStore,
     pub playlist_store: PlaylistStateStore,
+    /// Episodes listed by /podcast.
+    pub podcast_store: PodcastStateStore,
+    /// Links behind the buttons on completion messages.
+    pub redownload_store: RedownloadStateStore,
     pub db_pool: Option<SqlitePool>,
     pub admin_chat_id: Option<i64>,
+    /// Static allowlist from ALLOWED_USERS. `None` means the bot is public.
+    pub allowed_users: Option<HashSet<i64>>,
+    /// Shared cap on all direct file downloads together (DOWNLOAD_RATE_LIMIT_TOTAL).
+    pub download_limiter: Option<Arc<hermes_downloader::RateLimiter>>,
+    pub started_at: std::time::Instant,
+}
+
+impl AppState {
+    /// Whether a chat may use the bot. See `access_denied`.
+    pub async fn is_user_allowed(&self, chat_id: i64) -> bool {
+        self.access_denied(chat_id).await.is_none()
+    }
+
+    /// The reply for a chat that may not use the bot, or `None` if it may.
+    /// Admins are always allowed and banned chats never are; in allowlist mode
+    /// other chats must be in ALLOWED_USERS or the DB allowlist.
+    pub async fn access_denied(&self, chat_id: i64) -> Option<&'static str> {
+        if self.admin_chat_id == Some(chat_id) {
+            return None;
+        }
+        let access = match &self.db_pool {
+            Some(pool) => hermes_shared::db::get_user_access(pool, chat_id)
+                .await
+                .unwrap_or_default(),
+            None => Default::default(),
+        };
+        if access.banned {
+            return Some("🚫 You have been banned from this bot.");
+        }
+        match &self.allowed_users {
+            Some(allowed) if !allowed.contains(&chat_id) && !access.allowlisted => {
+                Some("This is a private bot.")
+            }
+            _ => None,
+        }
+    }
+
+    /// Queue priority for a new task: the admin's jobs go first, bulk jobs
+    /// (playlists, merges, subscription checks) last.
+    pub fn task_priority(&self, chat_id: i64, task_type: &str) -> QueuePriority {
+        if self.admin_chat_id == Some(chat_id) {
+            QueuePriority::High
+        } else if matches!(task_type, "playlist" | "concat" | "subscription") {
+            QueuePriority::Low
+        } else {
+            QueuePriority::Normal
+        }
+    }
 }
 
 /// Handle incoming commands.
@@ -115,6 +702,11 @@ pub async fn handle_command(
     cmd: Command,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
+    if let Some(reply) = state.access_denied(msg.chat.id.0).await {
+        bot.send_message(msg.chat.id, decorate(reply)).await?;
+        return Ok(());
+    }
+
     // Track user in DB (captures username from Telegram)
     if let Some(pool) = &state.db_pool {
         let username = msg.from()
@@ -123,9 +715,20 @@ pub async fn handle_command(
     }
 
     match cmd {
-        Command::Start => cmd_start(bot, msg).await,
+        Command::Start(payload) => cmd_start(bot, msg, payload, state).await,
         Command::Help => cmd_help(bot, msg).await,
         Command::Download(url) => cmd_download(bot, msg, url, state).await,
+        Command::Link(url) => cmd_link(bot, msg, url, state).await,
+        Command::Wallpaper(url) => cmd_wallpaper(bot, msg, url, state).await,
+        Command::Hardsubs(args) => cmd_hardsubs(bot, msg, args, state).await,
+        Command::Both(url) => cmd_both(bot, msg, url, state).await,
+        Command::Concat(args) => cmd_concat(bot, msg, args, state).await,
+        Command::Clip(args) => cmd_clip(bot, msg, args, state).await,
+        Command::Subs(args) => cmd_subs(bot, msg, args, state).await,
+        Command::Podcast(url) => cmd_podcast(bot, msg, url, state).await,
+        Command::Transcribe(args) => cmd_transcribe(bot, msg, args, state).await,
+        Command::Estimate(args) => cmd_estimate(bot, msg, args, state).await,
+        Command::Fit(url) => cmd_fit(bot, msg, url, state).await,
         Command::Dv(url) => cmd_download_with_quality(bot, msg, url, DownloadMode::Video, state).await,
         Command::Da(url) => cmd_download_with_quality(bot, msg, url, DownloadMode::Audio, state).await,
         Command::Do(url) => cmd_direct_download(bot, msg, url, state).await,
@@ -133,22 +736,61 @@ pub async fn handle_command(
         Command::Playlist(url) => cmd_playlist_preview(bot, msg, url, state, false).await,
         Command::Playlistv2(url) => cmd_playlist_preview(bot, msg, url, state, true).await,
         Command::Search(query) => cmd_search(bot, msg, query, state).await,
+        Command::RetryCookie(task_id) => cmd_retry_cookie(bot, msg, task_id, state).await,
         Command::Status => cmd_status(bot, msg, state).await,
+        Command::Failed => cmd_failed(bot, msg, state).await,
         Command::Cancel(task_id) => cmd_cancel(bot, msg, task_id, state).await,
-        Command::History => cmd_history(bot, msg).await,
+        Command::History => cmd_history(bot, msg, state).await,
+        Command::Schedule(args) => cmd_schedule(bot, msg, args, state).await,
+        Command::Quota(args) => cmd_quota(bot, msg, args, state).await,
         Command::Ping => cmd_ping(bot, msg, state).await,
+        Command::Version => cmd_version(bot, msg, state).await,
         Command::Upcook(content) => cmd_upcook(bot, msg, content, state).await,
         Command::Chatid => cmd_chatid(bot, msg).await,
         Command::Allow(secs_str) => cmd_allow(bot, msg, secs_str, state).await,
         Command::DedupToggle => cmd_dedup_toggle(bot, msg, state).await,
         Command::DedupStatus => cmd_dedup_status(bot, msg, state).await,
+        Command::Subscribe(args) => cmd_subscribe(bot, msg, args, state).await,
+        Command::Unsubscribe(arg) => cmd_unsubscribe(bot, msg, arg, state).await,
+        Command::Subscriptions => cmd_subscriptions(bot, msg, state).await,
+        Command::Save(args) => cmd_save(bot, msg, args, state).await,
+        Command::Saved => cmd_saved(bot, msg, state).await,
+        Command::AllowUser(arg) => cmd_allowlist_edit(bot, msg, arg, true, state).await,
+        Command::DenyUser(arg) => cmd_allowlist_edit(bot, msg, arg, false, state).await,
+        Command::Ban(arg) => cmd_ban(bot, msg, arg, true, state).await,
+        Command::Unban(arg) => cmd_ban(bot, msg, arg, false, state).await,
+        Command::Setting(args) => cmd_setting(bot, msg, args, state).await,
+        Command::Settings => cmd_settings(bot, msg, state).await,
+        Command::Archive(text) => cmd_archive(bot, msg, text, state).await,
+        Command::Sysinfo => cmd_sysinfo(bot, msg, state).await,
         Command::Restart => cmd_restart(bot, msg, state).await,
         Command::Update => cmd_update(bot, msg, state).await,
     }
 }
 
-/// /start - Welcome message
-async fn cmd_start(bot: Bot, msg: Message) -> ResponseResult<()> {
+/// /start - Welcome message, or the action of a deep-link payload
+/// (`t.me/<bot>?start=...`, see `deep_link`)
+async fn cmd_start(bot: Bot, msg: Message, payload: String, state: Arc<AppState>) -> ResponseResult<()> {
+    match deep_link::parse_start_payload(&payload) {
+        Some(StartPayload::Login) => return send_dashboard_login_link(&bot, msg.chat.id, 120, &state).await,
+        Some(StartPayload::Download(url)) => {
+            info!("Deep-link download for chat {}: {}", msg.chat.id, url);
+            return match link_detector::detect_first_link(&url) {
+                Some(link) if link.is_playlist() => cmd_playlist_confirm(bot, msg, url, state).await,
+                Some(link) if link.is_telegram() => cmd_telegram_forward(bot, msg, vec![link], state).await,
+                _ => cmd_download(bot, msg, url, state).await,
+            };
+        }
+        None if !payload.trim().is_empty() => {
+            info!("Ignoring unknown /start payload from chat {}: {}", msg.chat.id, payload.trim());
+        }
+        None => {}
+    }
+    send_welcome(bot, msg).await
+}
+
+/// Welcome and command overview (/start, /help)
+async fn send_welcome(bot: Bot, msg: Message) -> ResponseResult<()> {
     let chat_id = msg.chat.id.0;
     let help_text = format!("\
 🎵 Hermes Download Bot
@@ -163,10 +805,20 @@ Multiple links? I'll batch them all.
 
 🎬 Single Downloads
 /download <url> — Audio (fast, default)
+/link <url> — Get a download link instead of the file
+/wallpaper <url> — Full-resolution thumbnail
+/hardsubs <url> <lang> — Video with subtitles burned in
+/subs <url> [lang] — Subtitles only, as .srt (add vtt for .vtt)
+/clip <url> <start> <end> — Just that part, e.g. 1:30 2:00
+/both <url> — Video and its audio as two files
+/podcast <feed-url> — Pick an episode from a podcast feed
+/transcribe <url> [lang] — Speech to text (slow)
+/fit <url> — Video compressed to fit Telegram's limit
 /dv <url> — Video — pick quality
 /da <url> — Audio — pick format
 /dv high <url> — Best video (no cap)
 /da high <url> — Best audio quality
+/estimate <url> — Size & download time per quality first
 
 🌐 Any Site (yt-dlp)
 /do <url> — Best video
@@ -176,43 +828,101 @@ Multiple links? I'll batch them all.
 📋 Playlists
 /playlist <url> — Preview, choose limit & format
 /playlistv2 <url> — Preview, choose limit (video)
+/concat <url> — Whole playlist joined into one file
+
+🔔 Subscriptions
+/subscribe <url> — Auto-download new uploads
+/subscriptions — List · /unsubscribe <id>
 
 🔍 Search
-/search <query> — YouTube (10 results)
+/search <query> — YouTube, 10 per page (duration:, date:, type: filters)
 
 📊 Tasks
 /status — Active & recent downloads
+/failed — Recent failures, retry with one tap
 /cancel <id> — Cancel a download
+/schedule <time> <url> — Download later (22:00, +2h)
+/retrycookie <id> — Retry a failed download with cookies
+/quota — Remaining download allowance
+/save <url> — Watch later · /saved — Your list
 
 ⚙️ Account
+/settings — Default format, quality & mode
 /chatid — Your Chat ID
 /allow botp — Dashboard login link
 /ping — Health check
+/version — Bot & worker versions
 /help — This message
 
 💡 Tip: Forward t.me links to grab files from channels.
 
 🌐 Dashboard: {}", dashboard_base_url());
-    bot.send_message(msg.chat.id, help_text).await?;
-    // Chat ID in monospace so the user can easily copy it
-    bot.send_message(msg.chat.id, format!("🔐 Your Chat ID: `{}`", chat_id))
+    bot.send_message(msg.chat.id, decorate(help_text)).await?;
+    // Chat ID in monospace so the user can easily copy it. Group ids are negative,
+    // so the value is escaped; if Telegram still rejects the markup, fall back to plain text.
+    let plain = format!("🔐 Your Chat ID: {}", chat_id);
+    if start_chat_id_format() == "plain" {
+        bot.send_message(msg.chat.id, decorate(plain)).await?;
+    } else if let Err(e) = bot.send_message(msg.chat.id, decorate_markdown(format!(
+        "🔐 Your Chat ID: `{}`", escape_markdown_v2(&chat_id.to_string())
+    )))
         .parse_mode(ParseMode::MarkdownV2)
-        .await?;
+        .await
+    {
+        warn!("MarkdownV2 chat id message failed, sending plain text: {}", e);
+        bot.send_message(msg.chat.id, decorate(plain)).await?;
+    }
     Ok(())
 }
 
 /// /help - Show help
 async fn cmd_help(bot: Bot, msg: Message) -> ResponseResult<()> {
-    cmd_start(bot, msg).await
+    send_welcome(bot, msg).await
 }
 
 /// /chatid - Send the user their Telegram Chat ID
 async fn cmd_chatid(bot: Bot, msg: Message) -> ResponseResult<()> {
     let chat_id = msg.chat.id.0;
-    bot.send_message(msg.chat.id, format!(
+    bot.send_message(msg.chat.id, decorate(format!(
         "🔐 Your Chat ID\n\n{}\n\nAccess Dashboard:\n{}\n\nPaste your Chat ID there to log in.",
         chat_id, dashboard_base_url()
-    )).await?;
+    ))).await?;
+    Ok(())
+}
+
+/// Send a single-use dashboard login link valid for `secs` (/allow botp, /start login).
+async fn send_dashboard_login_link(
+    bot: &Bot,
+    chat_id: ChatId,
+    secs: i64,
+    state: &AppState,
+) -> ResponseResult<()> {
+    let pool = match &state.db_pool {
+        Some(p) => p,
+        None => {
+            bot.send_message(chat_id, decorate("❌ Database unavailable")).await?;
+            return Ok(());
+        }
+    };
+
+    let token = format!("{:x}", uuid::Uuid::new_v4());
+
+    match hermes_shared::db::create_user_bypass_session(pool, chat_id.0, &token, secs).await {
+        Ok(_) => {
+            let login_url = format!("{}/?token={}", dashboard_base_url(), token);
+            bot.send_message(chat_id, decorate(format!(
+                "🔗 Direct Dashboard Login\n\n\
+                 Click to open (expires in {}s):\n{}\n\n\
+                 This is a single-use link.",
+                secs, login_url
+            ))).await?;
+        }
+        Err(e) => {
+            bot.send_message(chat_id, decorate(format!("❌ Failed to create login link: {}", e)))
+                .await?;
+        }
+    }
+
     Ok(())
 }
 
@@ -236,47 +946,20 @@ async fn cmd_allow(
             match rest.parse::<i64>() {
                 Ok(n) if n > 0 && n <= 300 => n,
                 _ => {
-                    bot.send_message(msg.chat.id, "⚠️ Invalid duration.\n\nUsage: /allow botp [seconds]\nDefault: 120, max: 300")
+                    bot.send_message(msg.chat.id, decorate("⚠️ Invalid duration.\n\nUsage: /allow botp [seconds]\nDefault: 120, max: 300"))
                         .await?;
                     return Ok(());
                 }
             }
         };
 
-        let pool = match &state.db_pool {
-            Some(p) => p,
-            None => {
-                bot.send_message(msg.chat.id, "❌ Database unavailable").await?;
-                return Ok(());
-            }
-        };
-
-        let chat_id = msg.chat.id.0;
-        let token = format!("{:x}", uuid::Uuid::new_v4());
-
-        match hermes_shared::db::create_user_bypass_session(pool, chat_id, &token, secs).await {
-            Ok(_) => {
-                let login_url = format!("{}/?token={}", dashboard_base_url(), token);
-                bot.send_message(msg.chat.id, format!(
-                    "🔗 Direct Dashboard Login\n\n\
-                     Click to open (expires in {}s):\n{}\n\n\
-                     This is a single-use link.",
-                    secs, login_url
-                )).await?;
-            }
-            Err(e) => {
-                bot.send_message(msg.chat.id, format!("❌ Failed to create login link: {}", e))
-                    .await?;
-            }
-        }
-
-        return Ok(());
+        return send_dashboard_login_link(&bot, msg.chat.id, secs, &state).await;
     }
 
     // Global allow window — admin only
     if state.admin_chat_id != Some(msg.chat.id.0) {
         bot.send_message(msg.chat.id,
-            "Usage:\n/allow botp — Get a direct dashboard login link\n/allow botp 60 — Link valid for 60 seconds\n\n(Global /allow <seconds> is admin-only)"
+            decorate("Usage:\n/allow botp — Get a direct dashboard login link\n/allow botp 60 — Link valid for 60 seconds\n\n(Global /allow <seconds> is admin-only)")
         ).await?;
         return Ok(());
     }
@@ -284,12 +967,12 @@ async fn cmd_allow(
     let secs: i64 = match args.parse::<i64>() {
         Ok(n) if n > 0 && n <= 300 => n,
         Ok(_) => {
-            bot.send_message(msg.chat.id, "⚠️ Invalid Duration\n\nSeconds must be between 1 and 300.")
+            bot.send_message(msg.chat.id, decorate("⚠️ Invalid Duration\n\nSeconds must be between 1 and 300."))
                 .await?;
             return Ok(());
         }
         Err(_) => {
-            bot.send_message(msg.chat.id, "⚠️ Invalid Input\n\nUsage:\n/allow botp [secs] — Personal login link\n/allow <seconds> — Global OTP-free window (admin)")
+            bot.send_message(msg.chat.id, decorate("⚠️ Invalid Input\n\nUsage:\n/allow botp [secs] — Personal login link\n/allow <seconds> — Global OTP-free window (admin)"))
                 .await?;
             return Ok(());
         }
@@ -303,32 +986,32 @@ async fn cmd_allow(
                 let admin_id = state.admin_chat_id.unwrap_or(msg.chat.id.0);
 
                 // Create a JWT session for the admin
-                if let Ok(_) = hermes_shared::db::create_jwt_session(pool, admin_id, &token, secs).await {
+                if hermes_shared::db::create_jwt_session(pool, admin_id, &token, secs).await.is_ok() {
                     let dashboard_url = format!("{}/?token={}", dashboard_base_url(), token);
                     bot.send_message(
                         msg.chat.id,
-                        format!(
+                        decorate(format!(
                             "✅ Quick Login Window Opened\n\n⏱️ Duration: {} seconds\n\n📋 Your Chat ID:\n{}\n\n🔗 Direct Access Link:\n{}\n\n📝 Steps:\n1. Copy your Chat ID above\n2. Click the dashboard link\n3. If prompted, paste your Chat ID\n\n⚠️ This is for emergency access only. Use with caution.",
                             secs, admin_id, dashboard_url
-                        ),
+                        )),
                     ).await?;
                 } else {
                     bot.send_message(
                         msg.chat.id,
-                        format!(
+                        decorate(format!(
                             "✅ Quick Login Window Opened\n\n⏱️ Duration: {} seconds\n\n📋 Your Chat ID:\n{}\n\n🔓 Anyone with this Chat ID can now log in without OTP.\n\n📝 Steps:\n1. Copy your Chat ID above\n2. Go to: {}\n3. Paste Chat ID to log in\n\n⚠️ This is for emergency access only. Use with caution.",
                             secs, admin_id, dashboard_base_url()
-                        ),
+                        )),
                     ).await?;
                 }
             }
             Err(e) => {
-                bot.send_message(msg.chat.id, format!("❌ Failed to Open Window\n\nError: {}", e))
+                bot.send_message(msg.chat.id, decorate(format!("❌ Failed to Open Window\n\nError: {}", e)))
                     .await?;
             }
         }
     } else {
-        bot.send_message(msg.chat.id, "❌ Database unavailable")
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable"))
             .await?;
     }
 
@@ -342,1045 +1025,4856 @@ async fn cmd_download(
     url: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let url = url.trim().to_string();
-    if url.is_empty() {
-        bot.send_message(msg.chat.id, "⬇️ *Download Audio*\n\nUsage: `/download <url>`\n\nExample:\n`/download https://youtu.be/dQw4w9WgXcQ`")
+    if url.trim().is_empty() {
+        bot.send_message(msg.chat.id, decorate_markdown("⬇️ *Download Audio*\n\nUsage: `/download <url>`\n\nExample:\n`/download https://youtu.be/dQw4w9WgXcQ`"))
             .parse_mode(ParseMode::MarkdownV2)
             .await?;
         return Ok(());
     }
+    download_url(bot, msg, url, state, false, None).await
+}
 
-    // Detect link type
-    let link = match link_detector::detect_first_link(&url) {
-        Some(l) if l.is_telegram() => {
-            // Delegate Telegram links to the forward handler
-            return cmd_telegram_forward(bot, msg, vec![l], state).await;
-        }
-        Some(l) if l.is_supported() => l,
-        Some(l) => l, // Generic URL — let yt-dlp try it
-        None => {
-            bot.send_message(msg.chat.id, "❌ Could not detect a valid URL. Please check and try again.").await?;
+/// /link <url> - Download, then reply with a temporary HTTP link instead of uploading
+async fn cmd_link(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    if url.trim().is_empty() {
+        bot.send_message(msg.chat.id, decorate_markdown("🔗 *Download Link*\n\nUsage: `/link <url>`\n\nDownloads the file and sends a direct download link instead of uploading it to Telegram"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+    if state.db_pool.is_none() {
+        bot.send_message(msg.chat.id, "Download links are unavailable (no database).").await?;
+        return Ok(());
+    }
+    download_url(bot, msg, url, state, true, None).await
+}
+
+/// /wallpaper <url> - Send the largest thumbnail uncompressed, as a document
+async fn cmd_wallpaper(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let link = match link_detector::detect_first_link(url.trim()) {
+        Some(l) if !l.is_telegram() => l,
+        _ => {
+            bot.send_message(chat_id, decorate_markdown("🖼 *Wallpaper*\n\nUsage: `/wallpaper <url>`\n\nSends the video thumbnail at full resolution as a file"))
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
             return Ok(());
         }
     };
 
+    let status = bot.send_message(chat_id, decorate("🖼 Fetching thumbnail...")).await?;
     let task_id = Uuid::new_v4().to_string();
-    let short_id = task_id[..8].to_string();
-    let chat_id = msg.chat.id;
-    let is_playlist = link.is_playlist();
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Other), chat_id.0, &task_id);
+    let request = thumbnail_request(&task_id, link.url(), &out_dir);
+
+    let response = match state.dispatcher.send_and_wait(&request, 60).await {
+        Ok(r) if r.is_done() => r,
+        Ok(r) => {
+            let text = user_errors::from_ipc_response(&r).render("wallpaper");
+            bot.edit_message_text(chat_id, status.id, decorate(format!("❌ {}", text))).await?;
+            return Ok(());
+        }
+        Err(e) => {
+            let text = user_errors::from_hermes(&e).render("wallpaper");
+            bot.edit_message_text(chat_id, status.id, decorate(format!("❌ {}", text))).await?;
+            return Ok(());
+        }
+    };
 
-    // Fast-path: if this URL was already downloaded and the file still exists on disk,
-    // skip yt-dlp entirely and deliver from cache.
-    if !is_playlist {
-        if let Some(pool) = &state.db_pool {
-            if let Some((prev_task_id, prev_path, ch_msg_opt)) =
-                hermes_shared::db::find_cached_download(pool, link.url()).await
-            {
-                if std::path::Path::new(&prev_path).exists() {
-                    let sm = bot.send_message(chat_id, "⚡ Already downloaded — serving from cache...").await?;
-                    let prev_filename = std::path::Path::new(&prev_path)
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("download")
-                        .to_string();
-                    let bot2   = bot.clone();
-                    let state2 = state.clone();
-                    let sm_id  = sm.id;
-                    tokio::spawn(async move {
-                        let _ = deliver_file(
-                            &bot2, chat_id, &prev_path, &prev_filename,
-                            &prev_task_id, DownloadMode::Audio, ch_msg_opt, &state2,
-                        ).await;
-                        let _ = bot2.delete_message(chat_id, sm_id).await;
-                    });
-                    return Ok(());
-                }
-            }
+    let Some(file_path) = response.data.get("file_path").and_then(|v| v.as_str()) else {
+        bot.edit_message_text(chat_id, status.id, decorate("❌ No thumbnail was saved.")).await?;
+        return Ok(());
+    };
+    let title = response.data.get("title").and_then(|v| v.as_str()).unwrap_or("");
+    let width = response.data.get("width").and_then(|v| v.as_u64()).unwrap_or(0);
+    let height = response.data.get("height").and_then(|v| v.as_u64()).unwrap_or(0);
+
+    let mut caption = title.to_string();
+    if width > 0 && height > 0 {
+        caption.push_str(&format!("\n{}×{}", width, height));
+        if width < 1280 {
+            caption.push_str(" (no HD thumbnail available)");
         }
     }
 
-    // Enqueue
-    state.task_queue.enqueue(&task_id, chat_id.0, link.ipc_action()).await;
-
-    // Create DB record so the task shows in web dashboard
-    if let Some(pool) = &state.db_pool {
-        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, link.ipc_action(), link.url(), None).await;
+    // A document keeps the original pixels; send_photo would recompress it
+    let filename = if title.is_empty() { "wallpaper.jpg".to_string() } else { format!("{}.jpg", title.replace(['/', '\\'], "_")) };
+    let filename = truncate_filename(&filename, MAX_FILENAME_BYTES);
+    let input = teloxide::types::InputFile::file(file_path).file_name(filename);
+    match bot.send_document(chat_id, input).caption(caption).await {
+        Ok(_) => {
+            let _ = bot.delete_message(chat_id, status.id).await;
+        }
+        Err(e) => {
+            let text = user_errors::from_telegram(&e, "send the thumbnail").render("wallpaper");
+            bot.edit_message_text(chat_id, status.id, decorate(text)).await?;
+        }
     }
 
-    // Send initial feedback
-    let status_icon = if is_playlist { "📋" } else { "⏳" };
-    let status_msg = bot.send_message(chat_id, format!(
-        "{} Task Queued [{}]\n\nSource:\n{}",
-        status_icon, short_id, link.url()
-    ))
-        .await?;
-    let status_msg_id = status_msg.id;
-
-    // Build IPC request
-    let out_dir = task_output_dir(&state.download_dir, chat_id.0, &task_id);
+    let _ = tokio::fs::remove_dir_all(&out_dir).await;
+    Ok(())
+}
 
-    if is_playlist {
-        // For playlists: Direct user to /playlist command for format selection
-        bot.send_message(chat_id, format!(
-            "📋 **This is a Playlist**\n\n\
-            Use the `/playlist` command to:\n\
-            • Preview tracks\n\
-            • Choose how many to download\n\
-            • Select audio or video format\n\n\
-            Example:\n\
-            `/playlist {}`",
-            link.url()
+/// /hardsubs <url> <lang> - Download the video and burn the `lang` subtitles into it
+async fn cmd_hardsubs(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let mut parts = args.split_whitespace();
+    let (url, lang) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let link = link_detector::detect_first_link(url).filter(|l| !l.is_telegram() && !l.is_playlist());
+    let Some(link) = link.filter(|_| is_subtitle_lang(lang)) else {
+        bot.send_message(chat_id, decorate_markdown(
+            "🔤 *Burned\\-in subtitles*\n\n\
+             Usage: `/hardsubs <url> <lang>`\n\
+             Example: `/hardsubs https://youtu.be/xyz en`\n\n\
+             The video is re-encoded with the subtitles drawn in, so they show on any player\\. \
+             This takes much longer than a normal download\\."
         ))
-        .parse_mode(ParseMode::MarkdownV2)
-        .await?;
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+
+    if quota_exceeded(&bot, chat_id, &state).await? {
         return Ok(());
     }
 
-    let prefs = load_user_prefs(&state, chat_id.0).await;
-    let extract_audio = prefs.default_mode == "audio";
-    let dl_mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
-    let request = download_request_prefs(
-        &task_id, link.url(), extract_audio,
-        &prefs.audio_format, &prefs.audio_quality,
-        &out_dir, chat_id.0,
-    );
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+
+    let priority = state.task_priority(chat_id.0, "hardsubs");
+    state.task_queue.enqueue(&task_id, chat_id.0, "hardsubs", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "hardsubs", link.url(), Some("video"), priority).await;
+    }
+
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nSubtitles: {} (burned in)",
+        short_id, link.url(), lang
+    ))).await?;
+
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Video), chat_id.0, &task_id);
+    let request = hardsubs_request(&task_id, link.url(), lang, &out_dir, chat_id.0);
 
-    // Spawn download in background so the teloxide handler returns immediately.
-    // This prevents blocking all other commands for this chat during the download.
     tokio::spawn(async move {
         let _ = execute_download_and_send(
-            &bot,
-            chat_id,
-            status_msg_id,
-            &short_id,
-            "download",
-            &task_id,
-            &request,
-            dl_mode,
-            &state,
+            &bot, chat_id, status_msg.id, &short_id, "hardsubs",
+            &task_id, &request, DownloadMode::Video, &state,
         ).await;
     });
 
     Ok(())
 }
 
-/// /do <url> - Download from any yt-dlp supported site (generic).
-/// /do mp3 <url> - Download as MP3 audio.
-/// /do f <url> - Show format picker.
-/// Bypasses the link type detection and sends any URL directly to yt-dlp.
-async fn cmd_direct_download(
+/// /clip <url> <start> <end> [audio] - Download only a time range of the video
+async fn cmd_clip(
     bot: Bot,
     msg: Message,
     args: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let args = args.trim().to_string();
-    if args.is_empty() {
-        bot.send_message(msg.chat.id,
-            "Usage:\n\
-             /do <url> — Download best video\n\
-             /do mp3 <url> — Download as MP3 audio\n\
-             /do f <url> — Pick format (audio/video quality)\n\n\
-             Supports any yt-dlp compatible site:\n\
-             SoundCloud, Vimeo, Twitter/X, and more."
-        ).await?;
-        return Ok(());
+    let chat_id = msg.chat.id;
+    let mut url = "";
+    let mut times = Vec::new();
+    let mut audio = false;
+    for word in args.split_whitespace() {
+        match word.to_lowercase().as_str() {
+            "audio" | "mp3" => audio = true,
+            _ if url.is_empty() && link_detector::detect_first_link(word).is_some() => url = word,
+            _ => times.push(word),
+        }
     }
-
-    // Parse subcommand: first token may be "mp3" or "f"
-    let (sub, url) = {
-        let parts: Vec<&str> = args.splitn(2, char::is_whitespace).collect();
-        let first = parts[0].to_lowercase();
-        if (first == "mp3" || first == "f") && parts.len() == 2 {
-            (first, parts[1].trim().to_string())
-        } else {
-            (String::new(), args)
+    let link = link_detector::detect_first_link(url)
+        .filter(|l| !l.is_telegram() && !l.is_playlist() && !l.is_clip());
+    let (Some(link), [start, end]) = (link, times.as_slice()) else {
+        bot.send_message(chat_id, decorate_markdown(format!(
+            "✂️ *Clip*\n\n\
+             Usage: `/clip <url> <start> <end>`\n\
+             Example: `/clip https://youtu.be/xyz 1:30 2:00`\n\n\
+             Downloads only that part of the video\\. Times are seconds, `M:SS` or `H:MM:SS`, \
+             up to {} per clip\\. Add `audio` for just the sound\\.",
+            escape_markdown_v2(&clip::format_length(clip_max_secs()))
+        )))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+    let (start, end) = match clip::parse_range(start, end, clip_max_secs()) {
+        Ok(range) => range,
+        Err(reason) => {
+            bot.send_message(chat_id, decorate(reason)).await?;
+            return Ok(());
         }
     };
-
-    if url.is_empty() {
-        bot.send_message(msg.chat.id, "Please provide a URL after the subcommand.").await?;
-        return Ok(());
-    }
-
-    // Basic URL validation
-    if !url.starts_with("http://") && !url.starts_with("https://") {
-        bot.send_message(msg.chat.id, "Please provide a valid URL starting with http:// or https://").await?;
+    if quota_exceeded(&bot, chat_id, &state).await? {
         return Ok(());
     }
 
-    // /do f <url> → format picker
-    if sub == "f" {
-        return cmd_download_with_quality(bot, msg, url, DownloadMode::Video, state).await;
-    }
-
-    // /do mp3 <url> → audio, /do <url> → best video
-    let extract_audio = sub == "mp3";
-    let mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
-    let mode_label = if extract_audio { "audio" } else { "video" };
-
     let task_id = Uuid::new_v4().to_string();
     let short_id = task_id[..8].to_string();
-    let chat_id = msg.chat.id;
-
-    // Enqueue
-    state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl").await;
+    let mode = if audio { DownloadMode::Audio } else { DownloadMode::Video };
 
+    let priority = state.task_priority(chat_id.0, "clip");
+    state.task_queue.enqueue(&task_id, chat_id.0, "clip", priority).await;
     if let Some(pool) = &state.db_pool {
-        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label)).await;
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "clip", link.url(), Some(mode.as_str()), priority).await;
     }
 
-    let status_msg = bot.send_message(chat_id, format!(
-        "⏳ Task Queued [{}] ({})\n\nSource:\n{}", short_id, mode_label, url
-    )).await?;
-    let status_msg_id = status_msg.id;
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nClip: {} – {} ({})",
+        short_id, link.url(), format_eta(start), format_eta(end), clip::format_length(end - start)
+    ))).await?;
 
-    let out_dir = task_output_dir(&state.download_dir, chat_id.0, &task_id);
-    let prefs = load_user_prefs(&state, chat_id.0).await;
-    let request = download_request_prefs(
-        &task_id, &url, extract_audio,
-        &prefs.audio_format, &prefs.audio_quality,
-        &out_dir, chat_id.0,
-    );
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(audio)), chat_id.0, &task_id);
+    let request = clip_request(&task_id, link.url(), audio, start, end, &out_dir, chat_id.0);
 
     tokio::spawn(async move {
         let _ = execute_download_and_send(
-            &bot,
-            chat_id,
-            status_msg_id,
-            &short_id,
-            mode_label,
-            &task_id,
-            &request,
-            mode,
-            &state,
+            &bot, chat_id, status_msg.id, &short_id, "clip",
+            &task_id, &request, mode, &state,
         ).await;
     });
 
     Ok(())
 }
 
-/// /downloadv2 <url> - Best quality video (no height cap).
-/// /downloadv2 mp3 <url> - Best quality audio (quality 0 = best VBR).
-async fn cmd_download_v2(
+/// /subs <url> [lang] [srt|vtt] - Just the subtitle track, as a file
+async fn cmd_subs(
     bot: Bot,
     msg: Message,
     args: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let args = args.trim().to_string();
-    if args.is_empty() {
-        bot.send_message(msg.chat.id,
-            "Usage:\n\
-             /downloadv2 <url> — Best quality video (no resolution cap)\n\
-             /downloadv2 mp3 <url> — Best quality audio\n\n\
-             Supports any yt-dlp compatible site."
-        ).await?;
+    let chat_id = msg.chat.id;
+    let mut url = "";
+    let mut lang = default_subtitle_lang();
+    let mut format = "srt";
+    for word in args.split_whitespace() {
+        match word.to_lowercase().as_str() {
+            "srt" => format = "srt",
+            "vtt" => format = "vtt",
+            _ if url.is_empty() && link_detector::detect_first_link(word).is_some() => url = word,
+            _ => lang = word.to_string(),
+        }
+    }
+    let link = link_detector::detect_first_link(url).filter(|l| !l.is_telegram() && !l.is_playlist());
+    let Some(link) = link.filter(|_| is_subtitle_lang(&lang)) else {
+        bot.send_message(chat_id, decorate_markdown(
+            "💬 *Subtitles*\n\n\
+             Usage: `/subs <url> [lang] [vtt]`\n\
+             Example: `/subs https://youtu.be/xyz de`\n\n\
+             Sends the subtitle track as an \\.srt file \\(or \\.vtt\\), without the video\\. \
+             Automatic captions are used when there are no manual subtitles\\."
+        ))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+    if quota_exceeded(&bot, chat_id, &state).await? {
         return Ok(());
     }
 
-    // Parse subcommand: first token may be "mp3"
-    let (sub, url) = {
-        let parts: Vec<&str> = args.splitn(2, char::is_whitespace).collect();
-        let first = parts[0].to_lowercase();
-        if first == "mp3" && parts.len() == 2 {
-            (first, parts[1].trim().to_string())
-        } else {
-            (String::new(), args)
-        }
-    };
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
 
-    if url.is_empty() {
-        bot.send_message(msg.chat.id, "Please provide a URL after the subcommand.").await?;
+    let priority = state.task_priority(chat_id.0, "subtitles");
+    state.task_queue.enqueue(&task_id, chat_id.0, "subtitles", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "subtitles", link.url(), Some(&lang), priority).await;
+    }
+
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nSubtitles: {} ({})",
+        short_id, link.url(), lang, format
+    ))).await?;
+
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Video), chat_id.0, &task_id);
+    let request = subtitles_request(&task_id, link.url(), &lang, format, &out_dir)
+        .with_http_options(&http_options_for(link.url()));
+    tokio::spawn(async move {
+        let _ = execute_subs(&bot, chat_id, status_msg.id, &short_id, &task_id, &request, &state).await;
+    });
+
+    Ok(())
+}
+
+/// Run a /subs task and send the subtitle file as a document.
+async fn execute_subs(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    request: &IPCRequest,
+    state: &AppState,
+) -> ResponseResult<()> {
+    let cancel = state.task_queue.cancellation(task_id).await;
+    if !acquire_worker_slot(bot, chat_id, status_msg_id, short_id, task_id, &cancel, state).await? {
         return Ok(());
     }
 
-    if !url.starts_with("http://") && !url.starts_with("https://") {
-        bot.send_message(msg.chat.id, "Please provide a valid URL starting with http:// or https://").await?;
+    let step = run_worker_step(
+        bot, chat_id, status_msg_id, short_id, task_id, "Fetching subtitles",
+        request, &cancel, 300, state,
+    ).await;
+    let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
         return Ok(());
+    };
+
+    let file_path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("");
+    state.task_queue.complete(task_id).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::complete_task(pool, task_id, file_path).await;
     }
 
-    let extract_audio = sub == "mp3";
-    let mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
-    let mode_label = if extract_audio { "audio (best)" } else { "video (best)" };
+    match send_subtitle_file(bot, chat_id, file_path).await {
+        Ok(_) => {
+            let _ = bot.delete_message(chat_id, status_msg_id).await;
+        }
+        Err(e) => {
+            let text = user_errors::from_telegram(&e, "send the subtitles").render(short_id);
+            bot.edit_message_text(chat_id, status_msg_id, decorate(text)).await?;
+        }
+    }
+    Ok(())
+}
 
-    let task_id = Uuid::new_v4().to_string();
-    let short_id = task_id[..8].to_string();
+/// Send a subtitle file the worker wrote (/subs, "Include subtitles") as a document.
+async fn send_subtitle_file(bot: &Bot, chat_id: ChatId, file_path: &str) -> ResponseResult<Message> {
+    let filename = std::path::Path::new(file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("subtitles.srt");
+    let input = teloxide::types::InputFile::file(file_path)
+        .file_name(truncate_filename(filename, MAX_FILENAME_BYTES));
+    with_retry_after(|| bot.send_document(chat_id, input.clone()).send()).await
+}
+
+/// /both <url> - Download the video once and send it together with its audio track
+async fn cmd_both(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
     let chat_id = msg.chat.id;
+    let Some(link) = link_detector::detect_first_link(url.trim()).filter(|l| !l.is_telegram() && !l.is_playlist()) else {
+        bot.send_message(chat_id, decorate_markdown(
+            "🎬🎵 *Video \\+ audio*\n\n\
+             Usage: `/both <url>`\n\n\
+             Sends the video and, separately, its audio track\\. \
+             The audio is taken from the downloaded video, so nothing is downloaded twice\\."
+        ))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+
+    if quota_exceeded(&bot, chat_id, &state).await? {
+        return Ok(());
+    }
 
-    state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl").await;
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
 
+    let priority = state.task_priority(chat_id.0, "both");
+    state.task_queue.enqueue(&task_id, chat_id.0, "both", priority).await;
     if let Some(pool) = &state.db_pool {
-        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label)).await;
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "both", link.url(), Some("video"), priority).await;
     }
 
-    let status_msg = bot.send_message(chat_id, format!(
-        "⏳ Task Queued [{}] ({})\n\nSource:\n{}", short_id, mode_label, url
-    )).await?;
-    let status_msg_id = status_msg.id;
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nDelivers: video + audio",
+        short_id, link.url()
+    ))).await?;
 
-    let out_dir = task_output_dir(&state.download_dir, chat_id.0, &task_id);
     let prefs = load_user_prefs(&state, chat_id.0).await;
-
-    // Build IPC request with best-quality format strings (no height cap)
-    let mut params = serde_json::json!({
-        "extract_audio": extract_audio,
-        "audio_format": prefs.audio_format,
-        "audio_quality": "0",
-        "output_dir": out_dir,
-        "user_chat_id": chat_id.0,
-    });
-    if !extract_audio {
-        // Uncapped video format — no height<=1080 restriction
-        params["format"] = serde_json::json!(
-            "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best"
-        );
-    }
-    let request = IPCRequest::new(&task_id, IPCAction::YoutubeDl)
-        .with_url(&url)
-        .with_params(params);
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Video), chat_id.0, &task_id);
+    let request = download_request(&task_id, link.url(), false, &out_dir, chat_id.0)
+        .with_param("also_audio", prefs.audio_format.as_str());
 
     tokio::spawn(async move {
         let _ = execute_download_and_send(
-            &bot,
-            chat_id,
-            status_msg_id,
-            &short_id,
-            mode_label,
-            &task_id,
-            &request,
-            mode,
-            &state,
+            &bot, chat_id, status_msg.id, &short_id, "both",
+            &task_id, &request, DownloadMode::Video, &state,
         ).await;
     });
 
     Ok(())
 }
 
-/// Forward/copy messages from Telegram channels to the user.
-/// Handles both single links and batch (multiple links).
-async fn cmd_telegram_forward(
+/// /concat <playlist_url> [video] - Download a playlist and join it into one continuous file
+async fn cmd_concat(
     bot: Bot,
     msg: Message,
-    links: Vec<DetectedLink>,
-    _state: Arc<AppState>,
+    args: String,
+    state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    // Filter to only Telegram links
-    let tg_links: Vec<&DetectedLink> = links.iter()
-        .filter(|l| l.is_telegram())
-        .collect();
+    let chat_id = msg.chat.id;
+    let mut url = "";
+    let mut is_audio = true;
+    for word in args.split_whitespace() {
+        match word.to_lowercase().as_str() {
+            "video" => is_audio = false,
+            "audio" => is_audio = true,
+            _ => url = word,
+        }
+    }
+    let Some(link) = link_detector::detect_first_link(url).filter(|l| l.is_playlist()) else {
+        bot.send_message(chat_id, decorate_markdown(format!(
+            "🎚 *Playlist as one file*\n\n\
+             Usage: `/concat <playlist_url> [video]`\n\
+             Example: `/concat https://youtube.com/playlist?list=xyz`\n\n\
+             Downloads up to {} tracks and joins them into one continuous file \\(audio by default\\)\\.",
+            concat_max_items()
+        )))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
 
-    if tg_links.is_empty() {
-        bot.send_message(msg.chat.id, "No valid Telegram links found.").await?;
+    if quota_exceeded(&bot, chat_id, &state).await? {
         return Ok(());
     }
 
-    let chat_id = msg.chat.id;
-    let total = tg_links.len();
-
-    if total == 1 {
-        // Single link - simple forward
-        let link = tg_links[0];
-        let status_msg = bot.send_message(chat_id, "Forwarding from channel...").await?;
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let mode_label = if is_audio { "audio" } else { "video" };
 
-        match copy_telegram_message(&bot, chat_id, link).await {
-            Ok(()) => {
-                // Status message served its purpose — remove it
-                let _ = bot.delete_message(chat_id, status_msg.id).await;
-            }
-            Err(e) => {
-                let err_text = telegram_error_message(&e);
-                let _ = bot.edit_message_text(chat_id, status_msg.id, err_text).await;
-            }
-        }
-    } else {
-        // Batch - forward multiple
-        let status_msg = bot.send_message(chat_id, format!(
-            "Forwarding 0/{} files...", total
-        )).await?;
-        let status_id = status_msg.id;
+    let priority = state.task_priority(chat_id.0, "concat");
+    state.task_queue.enqueue(&task_id, chat_id.0, "concat", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "concat", link.url(), Some(mode_label), priority).await;
+    }
 
-        let mut success_count = 0usize;
-        let mut failed = 0usize;
-        let mut last_edit = Instant::now();
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nJoining up to {} tracks into one {} file",
+        short_id, link.url(), concat_max_items(), mode_label
+    ))).await?;
 
-        for (i, link) in tg_links.iter().enumerate() {
-            match copy_telegram_message(&bot, chat_id, link).await {
-                Ok(()) => success_count += 1,
-                Err(e) => {
-                    failed += 1;
-                    warn!("Telegram forward failed for {}: {}", link.url(), e);
-                }
-            }
+    let url = link.url().to_string();
+    tokio::spawn(async move {
+        let _ = execute_concat(&bot, chat_id, status_msg.id, &short_id, &task_id, &url, is_audio, &state).await;
+    });
 
-            // Throttle progress edits (every 3 messages or every 2 seconds)
-            let done = i + 1;
-            if done == total || (done % 3 == 0 && last_edit.elapsed().as_secs() >= 2) {
-                let _ = bot.edit_message_text(chat_id, status_id, format!(
-                    "Forwarding {}/{}", done, total
-                )).await;
-                last_edit = Instant::now();
-            }
+    Ok(())
+}
 
-            // Rate limit: 10s between copies (configurable via TELEGRAM_BATCH_DELAY_SECS)
-            if done < total {
-                let delay_secs: u64 = std::env::var("TELEGRAM_BATCH_DELAY_SECS")
-                    .ok()
-                    .and_then(|v| v.parse().ok())
-                    .unwrap_or(10);
-                tokio::time::sleep(std::time::Duration::from_secs(delay_secs)).await;
-            }
-        }
+/// Run a /concat task: download the playlist tracks (step 1), ask the worker to
+/// join them in playlist order (step 2), then deliver the single result.
+#[allow(clippy::too_many_arguments)]
+async fn execute_concat(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    url: &str,
+    is_audio: bool,
+    state: &AppState,
+) -> ResponseResult<()> {
+    let cancel = state.task_queue.cancellation(task_id).await;
+    if !acquire_worker_slot(bot, chat_id, status_msg_id, short_id, task_id, &cancel, state).await? {
+        return Ok(());
+    }
 
-        // Final summary
-        let summary = if failed == 0 {
-            format!("Copied {} message{}", success_count, if success_count == 1 { "" } else { "s" })
-        } else {
-            format!("Copied {}/{} ({} failed)", success_count, total, failed)
-        };
-        let _ = bot.edit_message_text(chat_id, status_id, summary).await;
+    // Step 1: the tracks. No archive, so every item is fetched fresh and the
+    // worker's numbered file names keep playlist order.
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Playlist), chat_id.0, task_id);
+    let prefs = load_user_prefs(state, chat_id.0).await;
+    let request = playlist_request_opts(
+        task_id, url, &out_dir, Some(concat_max_items()), is_audio, None, chat_id.0,
+        Some(prefs.audio_format.as_str()),
+    ).with_http_options(&http_options_for(url));
+    let step = run_worker_step(
+        bot, chat_id, status_msg_id, short_id, task_id, "Step 1/2: downloading tracks",
+        &request, &cancel, 1800, state,
+    ).await;
+    let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
+        return Ok(());
+    };
+
+    let files: Vec<String> = response.data.get("files")
+        .and_then(|v| v.as_array())
+        .map(|files| files.iter()
+            .filter_map(|f| f.get("path")?.as_str().map(String::from))
+            .collect())
+        .unwrap_or_default();
+    let title = response.data.get("playlist_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Playlist")
+        .to_string();
+    info!("[{short_id}] Concat: {} track(s) downloaded from '{}'", files.len(), title);
+
+    // Step 2: join them
+    let request = concat_request(task_id, &files, is_audio, &title, &out_dir);
+    let step = run_worker_step(
+        bot, chat_id, status_msg_id, short_id, task_id,
+        &format!("Step 2/2: joining {} tracks", files.len()),
+        &request, &cancel, concat_timeout_secs(), state,
+    ).await;
+    let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
+        return Ok(());
+    };
+
+    let file_path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("");
+    let filename = response.data.get("filename").and_then(|v| v.as_str()).unwrap_or("mix");
+    state.task_queue.complete(task_id).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::complete_task(pool, task_id, file_path).await;
     }
+    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "Download complete [{}]\nFile: {} ({} tracks)", short_id, filename, files.len()
+    ))).await;
 
-    Ok(())
+    let mode = if is_audio { DownloadMode::Audio } else { DownloadMode::Video };
+    let as_link = prefers_link_delivery(state, chat_id.0).await;
+    deliver_file(bot, chat_id, file_path, filename, task_id, mode, None, None, None, as_link, state).await
 }
 
-/// Copy a single message from a Telegram channel to the user via copy_message.
-///
-/// copy_message sends content without the "Forwarded from" header, regardless of
-/// whether the original is media or text — the user just receives the content cleanly.
-async fn copy_telegram_message(
+/// Wait for a download slot for a multi-step task, or report why there is none
+/// (cancelled while queued, queue closed) and return false.
+async fn acquire_worker_slot(
     bot: &Bot,
     chat_id: ChatId,
-    link: &DetectedLink,
-) -> Result<(), teloxide::RequestError> {
-    if let DetectedLink::TelegramFile { username, channel_id, message_id, .. } = link {
-        let from_chat: Recipient = if let Some(uname) = username {
-            Recipient::ChannelUsername(format!("@{}", uname))
-        } else if let Some(cid) = channel_id {
-            Recipient::Id(ChatId(*cid))
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    cancel: &tokio::sync::Notify,
+    state: &AppState,
+) -> ResponseResult<bool> {
+    let acquired = tokio::select! {
+        biased;
+        _ = cancel.notified() => false,
+        ok = state.task_queue.acquire(task_id) => ok,
+    };
+    if !acquired {
+        let text = if state.task_queue.is_cancelled(task_id).await {
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::cancel_task(pool, task_id).await;
+            }
+            format!("Cancelled [{}]", short_id)
         } else {
-            return Err(teloxide::RequestError::Api(
-                teloxide::ApiError::Unknown("Invalid channel reference".to_string())
-            ));
+            format!("Failed to acquire download slot [{}]", short_id)
         };
+        bot.edit_message_text(chat_id, status_msg_id, decorate(text)).await?;
+    }
+    Ok(acquired)
+}
 
-        // copy_message delivers the content without any "Forwarded from" header
-        bot.copy_message(chat_id, from_chat, MessageId(*message_id)).await?;
-        Ok(())
-    } else {
-        Ok(())
+/// Send one step of a multi-step task (/concat, /transcribe) to the worker and
+/// wait for its final event, showing progress and worker retries under `label`.
+#[allow(clippy::too_many_arguments)]
+async fn run_worker_step(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    label: &str,
+    request: &IPCRequest,
+    cancel: &tokio::sync::Notify,
+    timeout_secs: u64,
+    state: &AppState,
+) -> Result<StreamEnd, HermesError> {
+    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "{} [{}]", label, short_id
+    ))).await;
+    let mut rx = state.dispatcher.send(request, Priority::Bulk).await?;
+    let idle_timeout = tokio::time::Duration::from_secs(ipc_idle_timeout_secs());
+    let mut last_edit = Instant::now();
+
+    let end = tokio::time::timeout(tokio::time::Duration::from_secs(timeout_secs), async {
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = cancel.notified() => return StreamEnd::Cancelled,
+                next = tokio::time::timeout(idle_timeout, rx.recv()) => next,
+            };
+            let response = match next {
+                Ok(Some(response)) => response,
+                Ok(None) => return StreamEnd::Closed,
+                Err(_) => return StreamEnd::Stalled,
+            };
+            if response.is_retry() {
+                let (attempt, max) = response.retry_attempt().unwrap_or((2, None));
+                let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(
+                    format_retry_status(label, short_id, attempt, max, response.retry_reason().as_deref())
+                )).await;
+                last_edit = Instant::now();
+                continue;
+            }
+            if response.error_code().as_deref() == Some("CANCELLED") {
+                return StreamEnd::Cancelled;
+            }
+            if !response.is_progress() {
+                return StreamEnd::Response(response);
+            }
+            let pct = response.progress_percent().unwrap_or(0);
+            state.task_queue.update_progress(task_id, pct, response.progress_speed(), response.progress_eta()).await;
+            if last_edit.elapsed().as_secs() >= 3 {
+                let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                    "{} [{}]\n{} {}%", label, short_id, progress_bar(pct), pct
+                ))).await;
+                last_edit = Instant::now();
+            }
+        }
+    }).await.unwrap_or(StreamEnd::Stalled);
+
+    if matches!(end, StreamEnd::Stalled) {
+        abandon_on_worker(state, task_id).await;
     }
+    state.dispatcher.remove_pending(task_id).await;
+    Ok(end)
 }
 
-/// Convert a Telegram API error to a user-friendly message.
-fn telegram_error_message(err: &teloxide::RequestError) -> String {
-    let err_str = err.to_string();
-    if err_str.contains("chat not found") {
-        "I don't have access to that channel.\nAdd me to the channel first, or make sure the link is correct.".to_string()
-    } else if err_str.contains("message to copy not found") || err_str.contains("message not found") {
-        "Message not found. It may have been deleted.".to_string()
-    } else if err_str.contains("bot was kicked") || err_str.contains("bot is not a member") {
-        "I'm not a member of that channel. Add me first.".to_string()
-    } else {
-        format!("Failed to forward: {}", err_str)
+/// Turn the outcome of a worker step into its `done` response, or report the
+/// failure (task, DB and status message) and return None.
+async fn finish_worker_step(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    step: Result<StreamEnd, HermesError>,
+    state: &AppState,
+) -> ResponseResult<Option<IPCResponse>> {
+    let error_msg = match step {
+        Ok(StreamEnd::Response(response)) if !response.is_error() => return Ok(Some(response)),
+        Ok(StreamEnd::Response(response)) => user_errors::from_ipc_response(&response).render(short_id),
+        Ok(StreamEnd::Cancelled) => {
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::cancel_task(pool, task_id).await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!("Cancelled [{}]", short_id))).await?;
+            return Ok(None);
+        }
+        Ok(StreamEnd::Closed) => "Worker connection lost".to_string(),
+        Ok(StreamEnd::Stalled) => "Download stalled".to_string(),
+        Err(e) => user_errors::from_hermes(&e).render(short_id),
+    };
+    state.task_queue.fail(task_id).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::fail_task(pool, task_id, &error_msg).await;
     }
+    bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "Download failed [{}]\n{}", short_id, error_msg
+    ))).await?;
+    Ok(None)
 }
 
-/// /dv or /da - Download with quality selection menu
-async fn cmd_download_with_quality(
+/// /transcribe <url> [lang] - Download the audio and turn its speech into a text document.
+/// Unlike subtitles, this works on anything with speech, but takes a while.
+async fn cmd_transcribe(
     bot: Bot,
     msg: Message,
-    url: String,
-    mode: DownloadMode,
+    args: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let url = url.trim().to_string();
-    if url.is_empty() {
-        let cmd = if mode == DownloadMode::Video { "/dv" } else { "/da" };
-        let mode_name = mode.as_str();
-        bot.send_message(msg.chat.id, format!(
-            "Usage:\n\
-             {} <url> — Choose {} quality from a menu\n\
-             {} high <url> — Download best {} quality instantly\n\n\
-             Example:\n\
-             {} https://youtu.be/dQw4w9WgXcQ",
-            cmd, mode_name, cmd, mode_name, cmd
-        )).await?;
-        return Ok(());
-    }
-
-    // Check for "high" subcommand: /dv high <url> or /da high <url>
-    let (is_high, url) = {
-        let parts: Vec<&str> = url.splitn(2, char::is_whitespace).collect();
-        let first = parts[0].to_lowercase();
-        if first == "high" && parts.len() == 2 {
-            (true, parts[1].trim().to_string())
+    let chat_id = msg.chat.id;
+    let mut url = "";
+    let mut language = "auto".to_string();
+    for word in args.split_whitespace() {
+        let lower = word.to_lowercase();
+        if lower.len() <= 3 && lower.chars().all(|c| c.is_ascii_lowercase()) {
+            language = lower;
         } else {
-            (false, url)
-        }
-    };
-
-    // Detect link type
-    let link = match link_detector::detect_first_link(&url) {
-        Some(l) if l.is_supported() && !l.is_telegram() => l,
-        Some(l) if l.is_telegram() => {
-            bot.send_message(msg.chat.id, "Quality selection is not available for Telegram links. Just paste the link directly.").await?;
-            return Ok(());
-        }
-        Some(l) => l, // Generic URL — let yt-dlp try format listing
-        None => {
-            bot.send_message(msg.chat.id, "Could not detect a valid YouTube URL.").await?;
-            return Ok(());
+            url = word;
         }
+    }
+    let Some(link) = link_detector::detect_first_link(url).filter(|l| !l.is_telegram() && !l.is_playlist()) else {
+        bot.send_message(chat_id, decorate_markdown(
+            "📝 *Transcribe*\n\n\
+             Usage: `/transcribe <url> [lang]`\n\
+             Example: `/transcribe https://youtu.be/xyz en`\n\n\
+             Downloads the audio and converts its speech to a text document\\. \
+             The language is detected automatically when left out\\. \
+             This is slow: expect a few minutes for a long episode\\."
+        ))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
     };
 
-    if link.is_playlist() {
-        bot.send_message(msg.chat.id, "Quality selection is not available for playlists. Use /playlist instead.").await?;
+    if quota_exceeded(&bot, chat_id, &state).await? {
         return Ok(());
     }
 
-    let chat_id = msg.chat.id;
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
 
-    // /dv high or /da high — best quality direct download, no format picker
-    if is_high {
-        let extract_audio = mode == DownloadMode::Audio;
-        let mode_label = if extract_audio { "audio (best)" } else { "video (best)" };
+    let priority = state.task_priority(chat_id.0, "transcribe");
+    state.task_queue.enqueue(&task_id, chat_id.0, "transcribe", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "transcribe", link.url(), Some(&language), priority).await;
+    }
 
-        let task_id = Uuid::new_v4().to_string();
-        let short_id = task_id[..8].to_string();
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nTranscript language: {}",
+        short_id, link.url(), language
+    ))).await?;
 
-        state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl").await;
-        if let Some(pool) = &state.db_pool {
-            let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", link.url(), Some(mode_label)).await;
-        }
+    let url = link.url().to_string();
+    tokio::spawn(async move {
+        let _ = execute_transcribe(&bot, chat_id, status_msg.id, &short_id, &task_id, &url, &language, &state).await;
+    });
 
-        let status_msg = bot.send_message(chat_id, format!(
-            "⚡ Best Quality [{}] ({})\n\nSource:\n{}", short_id, mode_label, link.url()
-        )).await?;
-        let status_msg_id = status_msg.id;
+    Ok(())
+}
 
-        let out_dir = task_output_dir(&state.download_dir, chat_id.0, &task_id);
-        let prefs = load_user_prefs(&state, chat_id.0).await;
+/// Run a /transcribe task: download the audio (step 1), have the worker run
+/// speech-to-text over it (step 2), then send the transcript as a document.
+#[allow(clippy::too_many_arguments)]
+async fn execute_transcribe(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    url: &str,
+    language: &str,
+    state: &AppState,
+) -> ResponseResult<()> {
+    let cancel = state.task_queue.cancellation(task_id).await;
+    if !acquire_worker_slot(bot, chat_id, status_msg_id, short_id, task_id, &cancel, state).await? {
+        return Ok(());
+    }
 
-        let mut params = serde_json::json!({
-            "extract_audio": extract_audio,
-            "audio_format": prefs.audio_format,
-            "audio_quality": "0",
-            "output_dir": out_dir,
-            "user_chat_id": chat_id.0,
-        });
-        if !extract_audio {
-            // Uncapped video format — no height<=1080 restriction
-            params["format"] = serde_json::json!(
-                "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best"
-            );
-        }
-        let request = IPCRequest::new(&task_id, IPCAction::YoutubeDl)
-            .with_url(link.url())
-            .with_params(params);
+    // Step 1: the audio
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Audio), chat_id.0, task_id);
+    let mut request = download_request(task_id, url, true, &out_dir, chat_id.0)
+        .with_http_options(&http_options_for(url));
+    let is_admin = state.admin_chat_id.map(|id| id == chat_id.0).unwrap_or(false);
+    if let Some(max_mb) = max_download_mb().filter(|_| !is_admin) {
+        request = request.with_param("max_filesize_mb", max_mb);
+    }
+    let step = run_worker_step(
+        bot, chat_id, status_msg_id, short_id, task_id, "Step 1/2: downloading audio",
+        &request, &cancel, 600, state,
+    ).await;
+    let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
+        return Ok(());
+    };
+    let audio_path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let title = std::path::Path::new(&audio_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Transcript")
+        .to_string();
+
+    // Step 2: speech to text
+    let request = transcribe_request(task_id, &audio_path, language, &title, &out_dir);
+    let step = run_worker_step(
+        bot, chat_id, status_msg_id, short_id, task_id, "Step 2/2: transcribing (this can take a while)",
+        &request, &cancel, transcribe_timeout_secs(), state,
+    ).await;
+    // The audio was only needed for the transcript
+    let _ = tokio::fs::remove_file(&audio_path).await;
+    let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
+        return Ok(());
+    };
 
-        let dl_mode = mode.clone();
-        tokio::spawn(async move {
-            let _ = execute_download_and_send(
-                &bot, chat_id, status_msg_id, &short_id, mode_label,
-                &task_id, &request, dl_mode, &state,
-            ).await;
-        });
+    let file_path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("");
+    let filename = response.data.get("filename").and_then(|v| v.as_str()).unwrap_or("transcript.txt");
+    let detected = response.data.get("language").and_then(|v| v.as_str()).unwrap_or(language);
+    let duration = response.data.get("duration").and_then(|v| v.as_u64()).unwrap_or(0);
+    state.task_queue.complete(task_id).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::complete_task(pool, task_id, file_path).await;
+    }
 
-        return Ok(());
+    let caption = format!("📝 {}\nLanguage: {} · Audio: {}", title, detected, format_eta(duration));
+    let input = teloxide::types::InputFile::file(file_path)
+        .file_name(truncate_filename(filename, MAX_FILENAME_BYTES));
+    match bot.send_document(chat_id, input).caption(decorate(caption)).await {
+        Ok(_) => {
+            let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Transcript ready [{}]", short_id
+            ))).await;
+        }
+        Err(e) => {
+            let text = user_errors::from_telegram(&e, "send the transcript").render(short_id);
+            bot.edit_message_text(chat_id, status_msg_id, decorate(text)).await?;
+        }
     }
+    Ok(())
+}
 
-    let mode_label = mode.as_str();
+/// /fit <url> - Download a video and, if it is over the upload limit,
+/// re-encode it down to size instead of sending a link.
+async fn cmd_fit(bot: Bot, msg: Message, url: String, state: Arc<AppState>) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let Some(link) = link_detector::detect_first_link(url.trim()).filter(|l| !l.is_telegram() && !l.is_playlist()) else {
+        bot.send_message(chat_id, decorate_markdown(
+            "🗜 *Fit*\n\n\
+             Usage: `/fit <url>`\n\n\
+             Downloads the video and, when it is too big to send here, re\\-encodes it to fit\\. \
+             Compressed videos lose quality, and the re\\-encode can take several minutes\\. \
+             Videos too long to fit at a watchable quality are sent as a link instead\\."
+        ))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
 
-    let fetching_msg = bot.send_message(chat_id, format!(
-        "Fetching {} formats...", mode_label
-    )).await?;
+    if quota_exceeded(&bot, chat_id, &state).await? {
+        return Ok(());
+    }
 
-    // Fetch formats from Python worker
     let task_id = Uuid::new_v4().to_string();
-    let request = get_formats_request(&task_id, link.url(), mode_label);
-
-    match state.dispatcher.send_and_wait(&request, 30).await {
-        Ok(response) => {
-            if response.is_error() {
-                let err = response.error_message().unwrap_or_else(|| "Failed to fetch formats".into());
-                bot.edit_message_text(chat_id, fetching_msg.id, format!(
-                    "Error: {}", err
-                )).await?;
-                return Ok(());
-            }
+    let short_id = task_id[..8].to_string();
 
-            let title = response.data.get("title")
-                .and_then(|v| v.as_str())
-                .unwrap_or("Unknown");
-            let duration_str = response.data.get("duration_string")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
+    let priority = state.task_priority(chat_id.0, "compress");
+    state.task_queue.enqueue(&task_id, chat_id.0, "compress", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "compress", link.url(), Some("video"), priority).await;
+    }
 
-            let formats_data = response.data.get("formats")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default();
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}]\n\nSource:\n{}\nIf it is over {} MB it will be compressed (lower quality, slower).",
+        short_id, link.url(), fit_target_bytes() / 1024 / 1024
+    ))).await?;
 
-            if formats_data.is_empty() {
-                bot.edit_message_text(chat_id, fetching_msg.id,
-                    "No formats available for this video."
-                ).await?;
-                return Ok(());
-            }
+    let url = link.url().to_string();
+    tokio::spawn(async move {
+        let _ = execute_fit(&bot, chat_id, status_msg.id, &short_id, &task_id, &url, &state).await;
+    });
 
-            let format_options = parse_format_options(&formats_data);
+    Ok(())
+}
 
-            // Generate a short key for callback data
-            let key = task_id[..6].to_string();
+/// Run a /fit task: download the video (step 1) and, only when it is over the
+/// target size, have the worker re-encode it (step 2). If it cannot fit, the
+/// original goes through the normal oversized-file delivery (MTProto or link).
+async fn execute_fit(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    url: &str,
+    state: &AppState,
+) -> ResponseResult<()> {
+    let cancel = state.task_queue.cancellation(task_id).await;
+    if !acquire_worker_slot(bot, chat_id, status_msg_id, short_id, task_id, &cancel, state).await? {
+        return Ok(());
+    }
+
+    // Step 1: the video
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Video), chat_id.0, task_id);
+    let mut request = download_request(task_id, url, false, &out_dir, chat_id.0)
+        .with_http_options(&http_options_for(url));
+    let is_admin = state.admin_chat_id.map(|id| id == chat_id.0).unwrap_or(false);
+    if let Some(max_mb) = max_download_mb().filter(|_| !is_admin) {
+        request = request.with_param("max_filesize_mb", max_mb);
+    }
+    let step = run_worker_step(
+        bot, chat_id, status_msg_id, short_id, task_id, "Step 1/2: downloading video",
+        &request, &cancel, 600, state,
+    ).await;
+    let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
+        return Ok(());
+    };
+    let video_path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let video_size = tokio::fs::metadata(&video_path).await.map(|m| m.len()).unwrap_or(0);
+    let target = fit_target_bytes();
+
+    // Step 2: compress, unless it already fits
+    let mut note = String::new();
+    let (file_path, compressed) = if video_size <= target {
+        (video_path.clone(), false)
+    } else {
+        let request = compress_request(task_id, &video_path, target, &out_dir);
+        let step = run_worker_step(
+            bot, chat_id, status_msg_id, short_id, task_id,
+            &format!("Step 2/2: compressing {:.0} MB to fit (this can take a while)", video_size as f64 / 1024.0 / 1024.0),
+            &request, &cancel, compress_timeout_secs(), state,
+        ).await;
+        match step {
+            Ok(StreamEnd::Response(response)) if response.error_code().as_deref() == Some("COMPRESS_CANNOT_FIT") => {
+                info!("[{short_id}] Too long to compress under {} bytes, delivering original", target);
+                note = "\nToo long to compress to a watchable size, sending it as is.".to_string();
+                (video_path.clone(), false)
+            }
+            step => {
+                let Some(response) = finish_worker_step(bot, chat_id, status_msg_id, short_id, task_id, step, state).await? else {
+                    return Ok(());
+                };
+                let path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                let size = response.data.get("file_size").and_then(|v| v.as_u64()).unwrap_or(0);
+                note = format!(
+                    "\nCompressed {:.1} MB → {:.1} MB",
+                    video_size as f64 / 1024.0 / 1024.0, size as f64 / 1024.0 / 1024.0
+                );
+                (path, true)
+            }
+        }
+    };
+    if compressed {
+        let _ = tokio::fs::remove_file(&video_path).await;
+    }
+
+    state.task_queue.complete(task_id).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::complete_task(pool, task_id, &file_path).await;
+    }
+    let filename = std::path::Path::new(&file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("video.mp4")
+        .to_string();
+    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "Download complete [{}]\nFile: {}{}", short_id, filename, note
+    ))).await;
+    deliver_file(bot, chat_id, &file_path, &filename, task_id, DownloadMode::Video, None, None, None, false, state).await
+}
+
+/// Shared body of /download and /link. `as_link` delivers a download link instead of the file;
+/// `extract_audio` overrides the user's default mode.
+async fn download_url(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+    as_link: bool,
+    extract_audio: Option<bool>,
+) -> ResponseResult<()> {
+    let url = url.trim().to_string();
+
+    // Detect link type
+    let link = match link_detector::detect_first_link(&url) {
+        Some(l) if l.is_telegram() => {
+            // Delegate Telegram links to the forward handler
+            return cmd_telegram_forward(bot, msg, vec![l], state).await;
+        }
+        Some(l) if l.is_spotify() => {
+            return cmd_spotify(bot, msg, l.url().to_string(), state).await;
+        }
+        Some(l) if l.is_feed() => {
+            return cmd_podcast(bot, msg, l.url().to_string(), state).await;
+        }
+        Some(l) if l.is_supported() => l,
+        Some(l) if l.direct_file_name().is_some() => l, // Direct file — yt-dlp fetches it as-is
+        Some(_) => {
+            bot.send_message(msg.chat.id, decorate(UNSUPPORTED_LINK_TEXT)).await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, decorate("❌ Could not detect a valid URL. Please check and try again.")).await?;
+            return Ok(());
+        }
+    };
+    if quota_exceeded(&bot, msg.chat.id, &state).await? {
+        return Ok(());
+    }
+
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let chat_id = msg.chat.id;
+    let is_playlist = link.is_playlist();
+
+    let prefs = load_user_prefs(&state, chat_id.0).await;
+    let extract_audio = extract_audio.unwrap_or(prefs.default_mode == "audio");
+    let dl_mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
+
+    // Fast-path: if this URL was already downloaded in the requested mode and
+    // the file still exists on disk, skip yt-dlp entirely and deliver from cache.
+    if !is_playlist {
+        if let Some(pool) = &state.db_pool {
+            if let Some((prev_task_id, prev_path, ch_msg_opt)) =
+                hermes_shared::db::find_cached_download(pool, link.url()).await
+            {
+                let is_video = media_group::MediaKind::for_file(&prev_path) == media_group::MediaKind::Video;
+                if is_video != extract_audio && std::path::Path::new(&prev_path).exists() {
+                    let sm = bot.send_message(chat_id, decorate("⚡ Already downloaded — serving from cache...")).await?;
+                    let prev_filename = std::path::Path::new(&prev_path)
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("download")
+                        .to_string();
+                    // Only the file name and URL are known without the worker's metadata
+                    let fields = caption::CaptionFields {
+                        title: std::path::Path::new(&prev_filename).file_stem().and_then(|s| s.to_str()).map(String::from),
+                        filename: Some(prev_filename.clone()),
+                        url: Some(link.url().to_string()),
+                        ..Default::default()
+                    };
+                    let bot2   = bot.clone();
+                    let state2 = state.clone();
+                    let sm_id  = sm.id;
+                    tokio::spawn(async move {
+                        let caption = delivery_caption(&state2, chat_id.0, &fields).await;
+                        let _ = deliver_file(
+                            &bot2, chat_id, &prev_path, &prev_filename,
+                            &prev_task_id, dl_mode, None, caption, ch_msg_opt, as_link, &state2,
+                        ).await;
+                        let _ = bot2.delete_message(chat_id, sm_id).await;
+                    });
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Enqueue
+    let priority = state.task_priority(chat_id.0, link.ipc_action());
+    state.task_queue.enqueue(&task_id, chat_id.0, link.ipc_action(), priority).await;
+
+    // Create DB record so the task shows in web dashboard
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, link.ipc_action(), link.url(), None, priority).await;
+    }
+
+    // Send initial feedback
+    let status_icon = if is_playlist { "📋" } else { "⏳" };
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "{} Task Queued [{}]\n\nSource:\n{}",
+        status_icon, short_id, link.url()
+    )))
+        .await?;
+    let status_msg_id = status_msg.id;
+
+    if is_playlist {
+        // For playlists: Direct user to /playlist command for format selection
+        bot.send_message(chat_id, decorate_markdown(format!(
+            "📋 **This is a Playlist**\n\n\
+            Use the `/playlist` command to:\n\
+            • Preview tracks\n\
+            • Choose how many to download\n\
+            • Select audio or video format\n\n\
+            Example:\n\
+            `/playlist {}`",
+            escape_markdown_v2_code(link.url())
+        )))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+        return Ok(());
+    }
+
+    // Build IPC request
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(extract_audio)), chat_id.0, &task_id);
+    let mut request = download_request_prefs(
+        &task_id, link.url(), extract_audio,
+        &prefs,
+        &out_dir, chat_id.0,
+    );
+    if as_link {
+        request = request.with_param("deliver_as_link", true);
+    }
+    let kind = if as_link { "link" } else { "download" };
+
+    // Spawn download in background so the teloxide handler returns immediately.
+    // This prevents blocking all other commands for this chat during the download.
+    tokio::spawn(async move {
+        let _ = execute_download_and_send(
+            &bot,
+            chat_id,
+            status_msg_id,
+            &short_id,
+            kind,
+            &task_id,
+            &request,
+            dl_mode,
+            &state,
+        ).await;
+    });
+
+    Ok(())
+}
+
+/// /do <url> - Download from any yt-dlp supported site (generic).
+/// /do mp3 <url> - Download as MP3 audio.
+/// /do f <url> - Show format picker.
+/// Bypasses the link type detection and sends any URL directly to yt-dlp.
+async fn cmd_direct_download(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let args = args.trim().to_string();
+    if args.is_empty() {
+        bot.send_message(msg.chat.id,
+            decorate("Usage:\n\
+             /do <url> — Download best video\n\
+             /do mp3 <url> — Download as MP3 audio\n\
+             /do f <url> — Pick format (audio/video quality)\n\n\
+             Supports any yt-dlp compatible site:\n\
+             SoundCloud, Vimeo, Twitter/X, and more.")
+        ).await?;
+        return Ok(());
+    }
+
+    // Parse subcommand: first token may be "mp3" or "f"
+    let (sub, url) = {
+        let parts: Vec<&str> = args.splitn(2, char::is_whitespace).collect();
+        let first = parts[0].to_lowercase();
+        if (first == "mp3" || first == "f") && parts.len() == 2 {
+            (first, parts[1].trim().to_string())
+        } else {
+            (String::new(), args)
+        }
+    };
+
+    if url.is_empty() {
+        bot.send_message(msg.chat.id, "Please provide a URL after the subcommand.").await?;
+        return Ok(());
+    }
+
+    // Basic URL validation
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bot.send_message(msg.chat.id, "Please provide a valid URL starting with http:// or https://").await?;
+        return Ok(());
+    }
+    if quota_exceeded(&bot, msg.chat.id, &state).await? {
+        return Ok(());
+    }
+
+    // /do f <url> → format picker
+    if sub == "f" {
+        return cmd_download_with_quality(bot, msg, url, DownloadMode::Video, state).await;
+    }
+
+    // /do mp3 <url> → audio, /do <url> → best video
+    let extract_audio = sub == "mp3";
+    let mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
+    let mode_label = if extract_audio { "audio" } else { "video" };
+
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let chat_id = msg.chat.id;
+
+    // Enqueue
+    let priority = state.task_priority(chat_id.0, "youtube_dl");
+    state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl", priority).await;
+
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label), priority).await;
+    }
+
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}] ({})\n\nSource:\n{}", short_id, mode_label, url
+    ))).await?;
+    let status_msg_id = status_msg.id;
+
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(extract_audio)), chat_id.0, &task_id);
+    let prefs = load_user_prefs(&state, chat_id.0).await;
+    let request = download_request_prefs(
+        &task_id, &url, extract_audio,
+        &prefs,
+        &out_dir, chat_id.0,
+    );
+
+    tokio::spawn(async move {
+        let _ = execute_download_and_send(
+            &bot,
+            chat_id,
+            status_msg_id,
+            &short_id,
+            mode_label,
+            &task_id,
+            &request,
+            mode,
+            &state,
+        ).await;
+    });
+
+    Ok(())
+}
+
+/// /downloadv2 <url> - Best quality video (no height cap).
+/// /downloadv2 mp3 <url> - Best quality audio (quality 0 = best VBR).
+async fn cmd_download_v2(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let args = args.trim().to_string();
+    if args.is_empty() {
+        bot.send_message(msg.chat.id,
+            decorate("Usage:\n\
+             /downloadv2 <url> — Best quality video (no resolution cap)\n\
+             /downloadv2 mp3 <url> — Best quality audio\n\n\
+             Supports any yt-dlp compatible site.")
+        ).await?;
+        return Ok(());
+    }
+
+    // Parse subcommand: first token may be "mp3"
+    let (sub, url) = {
+        let parts: Vec<&str> = args.splitn(2, char::is_whitespace).collect();
+        let first = parts[0].to_lowercase();
+        if first == "mp3" && parts.len() == 2 {
+            (first, parts[1].trim().to_string())
+        } else {
+            (String::new(), args)
+        }
+    };
+
+    if url.is_empty() {
+        bot.send_message(msg.chat.id, "Please provide a URL after the subcommand.").await?;
+        return Ok(());
+    }
+
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bot.send_message(msg.chat.id, "Please provide a valid URL starting with http:// or https://").await?;
+        return Ok(());
+    }
+
+    let extract_audio = sub == "mp3";
+    let mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
+    let mode_label = if extract_audio { "audio (best)" } else { "video (best)" };
+
+    if quota_exceeded(&bot, msg.chat.id, &state).await? {
+        return Ok(());
+    }
+
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let chat_id = msg.chat.id;
+
+    let priority = state.task_priority(chat_id.0, "youtube_dl");
+    state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl", priority).await;
+
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label), priority).await;
+    }
+
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}] ({})\n\nSource:\n{}", short_id, mode_label, url
+    ))).await?;
+    let status_msg_id = status_msg.id;
+
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(extract_audio)), chat_id.0, &task_id);
+    let prefs = load_user_prefs(&state, chat_id.0).await;
+
+    // Build IPC request with best-quality format strings (no height cap)
+    let mut params = serde_json::json!({
+        "extract_audio": extract_audio,
+        "audio_format": prefs.audio_format,
+        "audio_quality": "0",
+        "output_dir": out_dir,
+        "user_chat_id": chat_id.0,
+    });
+    if !extract_audio {
+        // Uncapped video format — no height<=1080 restriction
+        params["format"] = serde_json::json!(
+            "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best"
+        );
+    }
+    let request = IPCRequest::new(&task_id, IPCAction::YoutubeDl)
+        .with_url(&url)
+        .with_params(params);
+
+    tokio::spawn(async move {
+        let _ = execute_download_and_send(
+            &bot,
+            chat_id,
+            status_msg_id,
+            &short_id,
+            mode_label,
+            &task_id,
+            &request,
+            mode,
+            &state,
+        ).await;
+    });
+
+    Ok(())
+}
+
+/// Forward/copy messages from Telegram channels to the user.
+/// Handles both single links and batch (multiple links).
+async fn cmd_telegram_forward(
+    bot: Bot,
+    msg: Message,
+    links: Vec<DetectedLink>,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    // Filter to only Telegram links, each post once even if pasted twice
+    let mut seen = HashSet::new();
+    let tg_links: Vec<&DetectedLink> = links.iter()
+        .filter(|l| l.is_telegram())
+        .filter(|l| seen.insert(hermes_shared::url_canon::canonical_url(l.url())))
+        .collect();
+    let duplicates = links.iter().filter(|l| l.is_telegram()).count() - tg_links.len();
+
+    if tg_links.is_empty() {
+        bot.send_message(msg.chat.id, "No valid Telegram links found.").await?;
+        return Ok(());
+    }
+
+    let chat_id = msg.chat.id;
+    let total = tg_links.len();
+
+    if total == 1 {
+        // Single link - simple forward
+        let link = tg_links[0];
+        let status_msg = bot.send_message(chat_id, "Forwarding from channel...").await?;
+
+        match copy_or_join_channel(&bot, chat_id, link, &state).await {
+            Ok(()) => {
+                // Status message served its purpose — remove it
+                let _ = bot.delete_message(chat_id, status_msg.id).await;
+            }
+            Err(e) => {
+                let err_text = user_errors::from_telegram(&e, "forward").render("forward");
+                let _ = bot.edit_message_text(chat_id, status_msg.id, decorate(err_text)).await;
+            }
+        }
+    } else {
+        // Batch - forward multiple
+        let mut text = format!("Forwarding 0/{} files...", total);
+        if duplicates > 0 {
+            text.push_str(&format!("\n({} duplicate link(s) skipped)", duplicates));
+        }
+        let status_msg = bot.send_message(chat_id, decorate(text)).await?;
+
+        // Persist the batch so it can be resumed if the bot restarts mid-way
+        let batch_id = Uuid::new_v4().to_string();
+        let urls: Vec<String> = tg_links.iter().map(|l| l.url().to_string()).collect();
+        if let Some(pool) = &state.db_pool {
+            if let Err(e) = hermes_shared::db::create_forward_batch(
+                pool, &batch_id, chat_id.0, &urls, Some(status_msg.id.0 as i64),
+            ).await {
+                warn!("Failed to persist forward batch {}: {}", batch_id, e);
+            }
+        }
+
+        run_forward_batch(&bot, &state, &batch_id, chat_id, status_msg.id, &urls, 0, (0, 0)).await;
+    }
+
+    Ok(())
+}
+
+/// Copy each link of a forward batch starting at `start`, persisting progress after every link.
+///
+/// `counts` carries (success, failed) totals from a previous run when resuming.
+#[allow(clippy::too_many_arguments)]
+async fn run_forward_batch(
+    bot: &Bot,
+    state: &AppState,
+    batch_id: &str,
+    chat_id: ChatId,
+    status_id: MessageId,
+    urls: &[String],
+    start: usize,
+    counts: (usize, usize),
+) {
+    let total = urls.len();
+    let (mut success_count, mut failed) = counts;
+    let mut last_edit = Instant::now();
+
+    for (i, url) in urls.iter().enumerate().skip(start) {
+        match link_detector::detect_first_link(url) {
+            Some(link) if link.is_telegram() => {
+                match copy_or_join_channel(bot, chat_id, &link, state).await {
+                    Ok(()) => success_count += 1,
+                    Err(e) => {
+                        failed += 1;
+                        warn!("Telegram forward failed for {}: {}", url, e);
+                    }
+                }
+            }
+            _ => {
+                failed += 1;
+                warn!("Skipping invalid link in forward batch {}: {}", batch_id, url);
+            }
+        }
+
+        let done = i + 1;
+        if let Some(pool) = &state.db_pool {
+            let _ = hermes_shared::db::update_forward_batch_progress(
+                pool, batch_id, done as i64, success_count as i64, failed as i64,
+            ).await;
+        }
+
+        // Throttle progress edits (every 3 messages or every 2 seconds)
+        if done == total || (done % 3 == 0 && last_edit.elapsed().as_secs() >= 2) {
+            let _ = bot.edit_message_text(chat_id, status_id, decorate(format!(
+                "Forwarding {}/{}", done, total
+            ))).await;
+            last_edit = Instant::now();
+        }
+
+        // Rate limit: 10s between copies (configurable via TELEGRAM_BATCH_DELAY_SECS)
+        if done < total {
+            let delay_secs: u64 = std::env::var("TELEGRAM_BATCH_DELAY_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10);
+            tokio::time::sleep(std::time::Duration::from_secs(delay_secs)).await;
+        }
+    }
+
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::finish_forward_batch(pool, batch_id).await;
+    }
+
+    // Final summary
+    let summary = if failed == 0 {
+        format!("Copied {} message{}", success_count, if success_count == 1 { "" } else { "s" })
+    } else {
+        format!("Copied {}/{} ({} failed)", success_count, total, failed)
+    };
+    let _ = bot.edit_message_text(chat_id, status_id, decorate(summary)).await;
+}
+
+/// Resume forward batches that were interrupted by a restart.
+///
+/// Called once at startup; each batch continues in its own task from the
+/// last persisted index.
+pub async fn resume_forward_batches(bot: Bot, state: Arc<AppState>) {
+    let pool = match &state.db_pool {
+        Some(p) => p,
+        None => return,
+    };
+
+    let batches = match hermes_shared::db::get_incomplete_forward_batches(pool).await {
+        Ok(b) => b,
+        Err(e) => {
+            warn!("Failed to load incomplete forward batches: {}", e);
+            return;
+        }
+    };
+
+    for batch in batches {
+        let urls = batch.links();
+        let start = batch.next_index.max(0) as usize;
+        let chat_id = ChatId(batch.chat_id);
+
+        if start >= urls.len() {
+            let _ = hermes_shared::db::finish_forward_batch(pool, &batch.id).await;
+            continue;
+        }
+
+        info!("Resuming forward batch {} at {}/{}", batch.id, start, urls.len());
+
+        // Reuse the original status message if it can still be edited, else post a new one
+        let resume_text = format!("Resuming interrupted forward {}/{}...", start, urls.len());
+        let previous = batch.status_msg_id.map(|id| MessageId(id as i32));
+        let status_id = match previous {
+            Some(id) if bot.edit_message_text(chat_id, id, decorate(&resume_text)).await.is_ok() => id,
+            _ => match bot.send_message(chat_id, decorate(resume_text)).await {
+                Ok(m) => m.id,
+                Err(e) => {
+                    warn!("Cannot resume forward batch {} (chat {}): {}", batch.id, batch.chat_id, e);
+                    let _ = hermes_shared::db::finish_forward_batch(pool, &batch.id).await;
+                    continue;
+                }
+            },
+        };
+
+        let bot = bot.clone();
+        let state = state.clone();
+        let counts = (batch.success_count.max(0) as usize, batch.failed_count.max(0) as usize);
+        tokio::spawn(async move {
+            run_forward_batch(&bot, &state, &batch.id, chat_id, status_id, &urls, start, counts).await;
+        });
+    }
+}
+
+/// Resume tasks that were queued or running when the bot last stopped.
+///
+/// Called once at startup with the tasks loaded before the web queue poller
+/// starts. Single downloads and direct file links start again under the same
+/// id (a direct download picks up its `.part` file); anything else is marked
+/// failed and the user is asked to send the link again.
+pub async fn resume_unfinished_tasks(bot: Bot, state: Arc<AppState>, tasks: Vec<hermes_shared::models::Task>) {
+    let Some(pool) = &state.db_pool else { return };
+    if !tasks.is_empty() {
+        info!("Resuming {} task(s) interrupted by the last restart", tasks.len());
+    }
+
+    for task in tasks {
+        let chat_id = ChatId(task.chat_id);
+        let task_id = task.id.clone();
+        let short_id = task_id.chars().take(8).collect::<String>();
+        let direct_name = (task.task_type == "direct")
+            .then(|| link_detector::detect_first_link(&task.url))
+            .flatten()
+            .and_then(|link| link.direct_file_name().and_then(direct_download_name));
+        // Replayed as sent; a task without one can't be rebuilt faithfully
+        let request = (task.task_type == "youtube_dl")
+            .then_some(task.ipc_request.as_deref())
+            .flatten()
+            .and_then(|json| serde_json::from_str::<IPCRequest>(json).ok())
+            .filter(|request| request.task_id == task_id);
+
+        if request.is_none() && direct_name.is_none() {
+            let _ = hermes_shared::db::fail_task(pool, &task_id, "Interrupted by a bot restart").await;
+            let _ = bot.send_message(chat_id, decorate(format!(
+                "⚠️ [{}] was interrupted by a bot restart. Please send the link again:\n{}",
+                short_id, task.url
+            ))).await;
+            continue;
+        }
+
+        let status_msg_id = match bot.send_message(chat_id, decorate(format!(
+            "🔄 Resuming [{}] after a bot restart\n\nSource:\n{}", short_id, task.url
+        ))).await {
+            Ok(m) => m.id,
+            Err(e) => {
+                warn!("Cannot resume task {} (chat {}): {}", short_id, task.chat_id, e);
+                let _ = hermes_shared::db::fail_task(pool, &task_id, "Interrupted by a bot restart").await;
+                continue;
+            }
+        };
+
+        // Picks up where it left off, ahead of work queued since the restart
+        state.task_queue.enqueue(&task_id, task.chat_id, &task.task_type, QueuePriority::High).await;
+        let _ = hermes_shared::db::set_task_priority(pool, &task_id, QueuePriority::High).await;
+        let bot = bot.clone();
+        let state = state.clone();
+        let request = match (request, direct_name) {
+            (Some(request), _) => request,
+            (None, Some(file_name)) => {
+                tokio::spawn(async move {
+                    let _ = execute_native_download(
+                        &bot, chat_id, status_msg_id, &short_id, &task_id, &task.url, &file_name, &state,
+                    ).await;
+                });
+                continue;
+            }
+            (None, None) => continue,
+        };
+
+        let mode = if request.params["extract_audio"] == serde_json::json!(true) {
+            DownloadMode::Audio
+        } else {
+            DownloadMode::Video
+        };
+        tokio::spawn(async move {
+            let _ = execute_download_and_send(
+                &bot, chat_id, status_msg_id, &short_id, "resume", &task_id, &request, mode, &state,
+            ).await;
+        });
+    }
+}
+
+/// Copy a single message from a Telegram channel to the user via copy_message.
+///
+/// copy_message sends content without the "Forwarded from" header, regardless of
+/// whether the original is media or text — the user just receives the content cleanly.
+async fn copy_telegram_message(
+    bot: &Bot,
+    chat_id: ChatId,
+    link: &DetectedLink,
+) -> Result<(), teloxide::RequestError> {
+    if let DetectedLink::TelegramFile { message_id, .. } = link {
+        let Some(from_chat) = telegram_source_chat(link) else {
+            return Err(teloxide::RequestError::Api(
+                teloxide::ApiError::Unknown("Invalid channel reference".to_string())
+            ));
+        };
+
+        // copy_message delivers the content without any "Forwarded from" header
+        bot.copy_message(chat_id, from_chat, MessageId(*message_id)).await?;
+        Ok(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Copy a channel post, falling back to the MTProto account when the bot has no access.
+///
+/// Bots cannot join channels themselves. For public channels (t.me/username/N)
+/// the user account joins, copies the post into the storage channel and leaves
+/// again; the bot then copies it from there. Private channels still need the
+/// bot to be added, so the original error is returned for those.
+async fn copy_or_join_channel(
+    bot: &Bot,
+    chat_id: ChatId,
+    link: &DetectedLink,
+    state: &AppState,
+) -> Result<(), teloxide::RequestError> {
+    let err = match copy_telegram_message(bot, chat_id, link).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+
+    let DetectedLink::TelegramFile { username: Some(username), message_id, .. } = link else {
+        return Err(err);
+    };
+    let err_str = err.to_string();
+    let no_access = err_str.contains("chat not found")
+        || err_str.contains("bot is not a member")
+        || err_str.contains("bot was kicked");
+    let storage_channel = storage_channel_id();
+    if !no_access || !mtproto_enabled() || storage_channel == 0 {
+        return Err(err);
+    }
+
+    info!("No bot access to @{}, copying post {} via MTProto", username, message_id);
+    let task_id = format!("join-{}", Uuid::new_v4());
+    let request = mtproto_copy_post_request(&task_id, username, *message_id, mtproto_leave_after_join());
+    match state.dispatcher.send_and_wait(&request, 60).await {
+        Ok(response) if response.is_done() => {
+            let Some(channel_msg_id) = response.data.get("channel_msg_id").and_then(|v| v.as_i64()) else {
+                return Err(err);
+            };
+            bot.copy_message(chat_id, ChatId(storage_channel), MessageId(channel_msg_id as i32)).await?;
+            Ok(())
+        }
+        Ok(response) => {
+            warn!("MTProto copy of @{}/{} failed: {:?} ({:?})",
+                username, message_id, response.error_message(), response.error_code());
+            Err(err)
+        }
+        Err(e) => {
+            warn!("MTProto copy of @{}/{} failed: {}", username, message_id, e);
+            Err(err)
+        }
+    }
+}
+
+/// Resolve the source channel of a Telegram link.
+fn telegram_source_chat(link: &DetectedLink) -> Option<Recipient> {
+    match link {
+        DetectedLink::TelegramFile { username: Some(uname), .. } => {
+            Some(Recipient::ChannelUsername(format!("@{}", uname)))
+        }
+        DetectedLink::TelegramFile { channel_id: Some(cid), .. } => Some(Recipient::Id(ChatId(*cid))),
+        _ => None,
+    }
+}
+
+/// /archive <t.me links> - Copy channel posts to the admin, then delete the originals.
+///
+/// Admin-only, and the requester must also be able to delete messages in each
+/// source channel. Never triggered by plain link forwarding.
+async fn cmd_archive(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let is_admin = state.admin_chat_id
+        .map(|id| id == msg.chat.id.0)
+        .unwrap_or(false);
+
+    if !is_admin {
+        bot.send_message(msg.chat.id, decorate("🔒 Admin Command\n\nThis command is restricted to administrators only."))
+            .await?;
+        return Ok(());
+    }
+
+    let links: Vec<DetectedLink> = link_detector::detect_links(&text)
+        .into_iter()
+        .filter(|l| l.is_telegram())
+        .collect();
+    if links.is_empty() {
+        bot.send_message(msg.chat.id,
+            "Usage: /archive <t.me links>\n\n\
+             Copies each post to you, then deletes it from the channel.\n\
+             Both you and the bot need delete rights in the channel."
+        ).await?;
+        return Ok(());
+    }
+
+    let Some(user_id) = msg.from().map(|u| u.id) else {
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id;
+    let total = links.len();
+    let status = bot.send_message(chat_id, decorate(format!("Archiving 0/{}...", total))).await?;
+
+    // Per-channel cache of whether the requester may delete there
+    let mut rights: HashMap<String, bool> = HashMap::new();
+    let (mut archived, mut copied_only, mut failed) = (0usize, 0usize, 0usize);
+    let mut notes: Vec<String> = Vec::new();
+
+    for (i, link) in links.iter().enumerate() {
+        let (Some(from_chat), DetectedLink::TelegramFile { message_id, .. }) = (telegram_source_chat(link), link) else {
+            failed += 1;
+            continue;
+        };
+
+        let key = format!("{:?}", from_chat);
+        let allowed = match rights.get(&key) {
+            Some(&ok) => ok,
+            None => {
+                let ok = bot.get_chat_member(from_chat.clone(), user_id).await
+                    .map(|m| m.can_delete_messages())
+                    .unwrap_or(false);
+                rights.insert(key, ok);
+                ok
+            }
+        };
+        if !allowed {
+            failed += 1;
+            notes.push(format!("{}: you lack delete rights in this channel", link.url()));
+            continue;
+        }
+
+        if let Err(e) = copy_telegram_message(&bot, chat_id, link).await {
+            failed += 1;
+            notes.push(format!("{}: {}", link.url(), user_errors::from_telegram(&e, "copy").render("archive")));
+            continue;
+        }
+
+        match bot.delete_message(from_chat, MessageId(*message_id)).await {
+            Ok(_) => {
+                archived += 1;
+                info!("Archived and deleted {}", link.url());
+            }
+            Err(e) => {
+                copied_only += 1;
+                warn!("Copied but could not delete {}: {}", link.url(), e);
+                notes.push(format!("{}: copied, but the bot could not delete it ({})", link.url(), e));
+            }
+        }
+
+        let done = i + 1;
+        if done < total {
+            let _ = bot.edit_message_text(chat_id, status.id, decorate(format!(
+                "Archiving {}/{}...", done, total
+            ))).await;
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    }
+
+    let mut summary = format!("Archive finished\nArchived: {}/{}", archived, total);
+    if copied_only > 0 {
+        summary.push_str(&format!("\nCopied, not deleted: {}", copied_only));
+    }
+    if failed > 0 {
+        summary.push_str(&format!("\nFailed: {}", failed));
+    }
+    for note in notes.iter().take(10) {
+        summary.push_str(&format!("\n• {}", note));
+    }
+    let _ = bot.edit_message_text(chat_id, status.id, decorate(summary)).await;
+    Ok(())
+}
+
+/// /dv or /da - Download with quality selection menu
+async fn cmd_download_with_quality(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    mode: DownloadMode,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let url = url.trim().to_string();
+    if url.is_empty() {
+        let cmd = if mode == DownloadMode::Video { "/dv" } else { "/da" };
+        let mode_name = mode.as_str();
+        bot.send_message(msg.chat.id, decorate(format!(
+            "Usage:\n\
+             {} <url> — Choose {} quality from a menu\n\
+             {} high <url> — Download best {} quality instantly\n\n\
+             Example:\n\
+             {} https://youtu.be/dQw4w9WgXcQ",
+            cmd, mode_name, cmd, mode_name, cmd
+        ))).await?;
+        return Ok(());
+    }
+    if quota_exceeded(&bot, msg.chat.id, &state).await? {
+        return Ok(());
+    }
+
+    // Check for "high" subcommand: /dv high <url> or /da high <url>
+    let (is_high, url) = {
+        let parts: Vec<&str> = url.splitn(2, char::is_whitespace).collect();
+        let first = parts[0].to_lowercase();
+        if first == "high" && parts.len() == 2 {
+            (true, parts[1].trim().to_string())
+        } else {
+            (false, url)
+        }
+    };
+
+    // Detect link type
+    let link = match link_detector::detect_first_link(&url) {
+        Some(l) if l.is_supported() && !l.is_telegram() => l,
+        Some(l) if l.is_telegram() => {
+            bot.send_message(msg.chat.id, "Quality selection is not available for Telegram links. Just paste the link directly.").await?;
+            return Ok(());
+        }
+        Some(_) => {
+            bot.send_message(msg.chat.id, decorate(UNSUPPORTED_LINK_TEXT)).await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, "Could not detect a valid YouTube URL.").await?;
+            return Ok(());
+        }
+    };
+
+    if link.is_playlist() {
+        bot.send_message(msg.chat.id, "Quality selection is not available for playlists. Use /playlist instead.").await?;
+        return Ok(());
+    }
+
+    let chat_id = msg.chat.id;
+
+    // /dv high or /da high — best quality direct download, no format picker
+    if is_high {
+        let extract_audio = mode == DownloadMode::Audio;
+        let mode_label = if extract_audio { "audio (best)" } else { "video (best)" };
+
+        let task_id = Uuid::new_v4().to_string();
+        let short_id = task_id[..8].to_string();
+
+        let priority = state.task_priority(chat_id.0, "youtube_dl");
+        state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl", priority).await;
+        if let Some(pool) = &state.db_pool {
+            let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", link.url(), Some(mode_label), priority).await;
+        }
+
+        let status_msg = bot.send_message(chat_id, decorate(format!(
+            "⚡ Best Quality [{}] ({})\n\nSource:\n{}", short_id, mode_label, link.url()
+        ))).await?;
+        let status_msg_id = status_msg.id;
+
+        let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(extract_audio)), chat_id.0, &task_id);
+        let prefs = load_user_prefs(&state, chat_id.0).await;
+
+        let mut params = serde_json::json!({
+            "extract_audio": extract_audio,
+            "audio_format": prefs.audio_format,
+            "audio_quality": "0",
+            "output_dir": out_dir,
+            "user_chat_id": chat_id.0,
+        });
+        if !extract_audio {
+            // Uncapped video format — no height<=1080 restriction
+            params["format"] = serde_json::json!(
+                "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best"
+            );
+        }
+        let request = IPCRequest::new(&task_id, IPCAction::YoutubeDl)
+            .with_url(link.url())
+            .with_params(params);
+
+        let dl_mode = mode.clone();
+        tokio::spawn(async move {
+            let _ = execute_download_and_send(
+                &bot, chat_id, status_msg_id, &short_id, mode_label,
+                &task_id, &request, dl_mode, &state,
+            ).await;
+        });
+
+        return Ok(());
+    }
+
+    let mode_label = mode.as_str();
+
+    let fetching_msg = bot.send_message(chat_id, decorate(format!(
+        "Fetching {} formats...", mode_label
+    ))).await?;
+
+    let listing = match fetch_format_listing(&state, link.url(), &mode).await {
+        Ok(listing) => listing,
+        Err(text) => {
+            bot.edit_message_text(chat_id, fetching_msg.id, decorate(text)).await?;
+            return Ok(());
+        }
+    };
+
+    let header = format!(
+        "Select {} quality:\n{} [{}]",
+        mode_label, listing.title, listing.duration
+    );
+    show_quality_menu(&bot, &state, chat_id, fetching_msg.id, link.url(), &mode, listing, header).await
+}
+
+/// Formats for `url` in `mode`, from the cache or a fresh `GetFormats` call.
+/// Err is the text to show the user.
+async fn fetch_format_listing(state: &AppState, url: &str, mode: &DownloadMode) -> Result<CachedFormats, String> {
+    let mode_label = mode.as_str();
+    if let Some(cached) = state.format_cache.get(url, mode).await {
+        info!("Format list cache hit ({}): {}", mode_label, url);
+        return Ok(cached);
+    }
+
+    let task_id = Uuid::new_v4().to_string();
+    let request = get_formats_request(&task_id, url, mode_label);
+    let response = match state.dispatcher.send_and_wait(&request, 30).await {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Get formats IPC failed: {}", e);
+            return Err(format!("Error fetching formats: {}", user_errors::from_hermes(&e).message));
+        }
+    };
+    if response.is_error() {
+        let err = user_errors::from_ipc_response(&response).render("get formats");
+        return Err(format!("Error: {}", err));
+    }
+
+    let title = response.data.get("title")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Unknown");
+    let duration_str = response.data.get("duration_string")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+
+    let formats_data = response.data.get("formats")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    if formats_data.is_empty() {
+        return Err("No formats available for this video.".to_string());
+    }
+
+    let languages = response.data.get("languages")
+        .and_then(|v| v.as_array())
+        .map(|l| parse_audio_languages(l))
+        .unwrap_or_default();
+    let listing = CachedFormats::new(
+        title.to_string(), duration_str.to_string(), parse_format_options(&formats_data), languages,
+    );
+    state.format_cache.insert(url, mode, listing.clone()).await;
+    Ok(listing)
+}
+
+/// Turn `message_id` into the quality picker for `listing`; a tap on a
+/// format starts the download through the normal callback flow.
+#[allow(clippy::too_many_arguments)]
+async fn show_quality_menu(
+    bot: &Bot,
+    state: &AppState,
+    chat_id: ChatId,
+    message_id: MessageId,
+    url: &str,
+    mode: &DownloadMode,
+    listing: CachedFormats,
+    header: String,
+) -> ResponseResult<()> {
+    // Default to the original (first) track
+    let audio_language = listing.languages.first().map(|l| l.code.clone());
+
+    // Generate a short key for callback data
+    let key = Uuid::new_v4().to_string()[..6].to_string();
+
+    // Build inline keyboard
+    let keyboard = build_quality_keyboard(
+        &listing.formats, mode, &key, &listing.languages, audio_language.as_deref(), false,
+    );
+
+    // Store state for callback
+    let pending = PendingSelection {
+        chat_id: chat_id.0,
+        url: url.to_string(),
+        message_id,
+        formats: listing.formats,
+        created_at: std::time::Instant::now(),
+        title: listing.title,
+        languages: listing.languages,
+        audio_language,
+        subtitles: false,
+    };
+    state.callback_store.store(key, pending).await;
+
+    bot.edit_message_text(chat_id, message_id, decorate(header))
+        .reply_markup(keyboard)
+        .await?;
+
+    Ok(())
+}
+
+/// /estimate [audio|video] <url> - Size and download-time estimate per quality,
+/// then the usual quality buttons to go ahead (or Cancel).
+async fn cmd_estimate(bot: Bot, msg: Message, args: String, state: Arc<AppState>) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let mut words = args.split_whitespace();
+    let (explicit_mode, url) = match words.next() {
+        Some("audio") => (Some(DownloadMode::Audio), words.next()),
+        Some("video") => (Some(DownloadMode::Video), words.next()),
+        first => (None, first),
+    };
+    let link = match url.and_then(link_detector::detect_first_link) {
+        Some(l) if !l.is_telegram() && !l.is_playlist() => l,
+        _ => {
+            bot.send_message(chat_id, decorate_markdown(
+                "📏 *Estimate*\n\nUsage: `/estimate <url>` or `/estimate audio <url>`\n\n\
+                 Shows the size of each quality and roughly how long it takes to download, \
+                 then lets you pick one"
+            ))
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+            return Ok(());
+        }
+    };
+    let mode = match explicit_mode {
+        Some(mode) => mode,
+        None if load_user_prefs(&state, chat_id.0).await.default_mode == "audio" => DownloadMode::Audio,
+        None => DownloadMode::Video,
+    };
+
+    let status = bot.send_message(chat_id, decorate("📏 Estimating...")).await?;
+    let listing = match fetch_format_listing(&state, link.url(), &mode).await {
+        Ok(listing) => listing,
+        Err(text) => {
+            bot.edit_message_text(chat_id, status.id, decorate(text)).await?;
+            return Ok(());
+        }
+    };
+
+    let bytes_per_sec = state.task_queue.average_throughput_bps().await;
+    let is_admin = state.admin_chat_id.map(|id| id == chat_id.0).unwrap_or(false);
+    let limit_mb = max_download_mb().filter(|_| !is_admin);
+    let lines: Vec<String> = listing.formats.iter()
+        .map(|f| format_estimate_line(&f.label, f.filesize, bytes_per_sec, limit_mb))
+        .collect();
+    let basis = match bytes_per_sec {
+        Some(bps) => format!("Times assume recent speed (~{:.1} MB/s).", bps / 1024.0 / 1024.0),
+        None => "No recent downloads to base times on yet.".to_string(),
+    };
+    let header = format!(
+        "📏 {} [{}]\n\n{}\n\n{}\nPick a quality to download it.",
+        listing.title, listing.duration, lines.join("\n"), basis
+    );
+    show_quality_menu(&bot, &state, chat_id, status.id, link.url(), &mode, listing, header).await
+}
+
+/// One /estimate row: "720p: ~85 MB, ~2 min", degrading to "size unknown".
+fn format_estimate_line(label: &str, size: Option<u64>, bytes_per_sec: Option<f64>, limit_mb: Option<u64>) -> String {
+    let Some(size) = size else {
+        return format!("• {}: size unknown", label);
+    };
+    let mut line = format!("• {}: ~{:.0} MB", label, size as f64 / 1024.0 / 1024.0);
+    if let Some(bps) = bytes_per_sec.filter(|&b| b > 0.0) {
+        line.push_str(&format!(", {}", format_wait((size as f64 / bps).ceil() as u64)));
+    }
+    if limit_mb.is_some_and(|mb| size > mb * 1024 * 1024) {
+        line.push_str(" (over the download limit)");
+    }
+    line
+}
+
+/// Build inline keyboard for format selection.
+/// Multi-audio videos get a row of language buttons above the audio options;
+/// video gets an "Include subtitles" toggle below the qualities.
+fn build_quality_keyboard(
+    formats: &[FormatOption],
+    mode: &DownloadMode,
+    key: &str,
+    languages: &[AudioLanguage],
+    selected_language: Option<&str>,
+    subtitles: bool,
+) -> InlineKeyboardMarkup {
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+
+    if *mode == DownloadMode::Audio && languages.len() > 1 {
+        for (chunk_idx, chunk) in languages.chunks(3).enumerate() {
+            let row = chunk.iter().enumerate().map(|(i, lang)| {
+                let mark = if selected_language == Some(lang.code.as_str()) { "✓ " } else { "" };
+                InlineKeyboardButton::callback(
+                    decorate(format!("{}🗣 {}", mark, lang.label)),
+                    encode_language_callback(key, chunk_idx * 3 + i),
+                )
+            }).collect();
+            rows.push(row);
+        }
+    }
+
+    if *mode == DownloadMode::Video {
+        // Video: 2 buttons per row
+        for chunk in formats.chunks(2) {
+            let row: Vec<InlineKeyboardButton> = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    let idx = formats.iter().position(|x| x.format_id == f.format_id && x.label == f.label).unwrap_or(i);
+                    InlineKeyboardButton::callback(
+                        decorate(&f.label),
+                        encode_callback(mode, key, idx),
+                    )
+                })
+                .collect();
+            rows.push(row);
+        }
+    } else {
+        // Audio: 1 button per row
+        for (i, f) in formats.iter().enumerate() {
+            rows.push(vec![
+                InlineKeyboardButton::callback(
+                    decorate(&f.label),
+                    encode_callback(mode, key, i),
+                )
+            ]);
+        }
+    }
+
+    if *mode == DownloadMode::Video {
+        let mark = if subtitles { "☑" } else { "☐" };
+        rows.push(vec![
+            InlineKeyboardButton::callback(
+                decorate(format!("{} Include subtitles ({})", mark, default_subtitle_lang())),
+                encode_subtitles_callback(key),
+            )
+        ]);
+    }
+
+    // Cancel button
+    rows.push(vec![
+        InlineKeyboardButton::callback("Cancel", encode_cancel(key))
+    ]);
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Handle callback query from inline keyboard button press.
+pub async fn handle_callback_query(
+    bot: Bot,
+    q: CallbackQuery,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let data = match q.data {
+        Some(ref d) => d.clone(),
+        None => return Ok(()),
+    };
+    if let Some(reply) = state.access_denied(q.from.id.0 as i64).await {
+        bot.answer_callback_query(&q.id).text(decorate(reply)).await?;
+        return Ok(());
+    }
+
+    // Handle search format selection (4-part: sf:key:index:a/v) — must run before decode_callback
+    if data.starts_with("sf:") {
+        let _ = bot.answer_callback_query(&q.id).await;
+        let parts: Vec<&str> = data.splitn(4, ':').collect();
+        let sf_key   = parts.get(1).copied().unwrap_or("");
+        let sf_idx: usize = parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(usize::MAX);
+        let is_audio = parts.get(3).copied().unwrap_or("a") == "a";
+
+        let pending = match state.search_store.peek(sf_key).await {
+            Some(p) => p,
+            None    => return Ok(()),
+        };
+        if sf_idx >= pending.results.len() { return Ok(()); }
+
+        let result   = &pending.results[sf_idx];
+        let url      = result.url.clone();
+        let chat_id  = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
+        let msg_id   = match q.message { Some(ref m) => m.id,      None => return Ok(()) };
+        if quota_exceeded(&bot, chat_id, &state).await? {
+            return Ok(());
+        }
+
+        let task_id  = Uuid::new_v4().to_string();
+        let short_id = task_id[..8].to_string();
+        let mode_label = if is_audio { "audio" } else { "video" };
+
+        let priority = state.task_priority(chat_id.0, "youtube_dl");
+        state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl", priority).await;
+
+        if let Some(pool) = &state.db_pool {
+            let _ = hermes_shared::db::create_task(
+                pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label), priority,
+            ).await;
+        }
+
+        // Edit the format-choice message to show download status
+        let _ = bot.edit_message_text(chat_id, msg_id,
+            decorate(format!("Queued [{}] ({}) — {}", short_id, mode_label, url))
+        ).reply_markup(InlineKeyboardMarkup::new(Vec::<Vec<InlineKeyboardButton>>::new())).await;
+
+        let out_dir  = task_output_dir(state.storage.base_for(StorageKind::for_media(is_audio)), chat_id.0, &task_id);
+        let dl_mode  = if is_audio { DownloadMode::Audio } else { DownloadMode::Video };
+        let prefs    = load_user_prefs(&state, chat_id.0).await;
+        let request  = download_request_prefs(
+            &task_id, &url, is_audio,
+            &prefs,
+            &out_dir, chat_id.0,
+        );
+
+        let state2 = state.clone();
+        tokio::spawn(async move {
+            let _ = execute_download_and_send(
+                &bot,
+                chat_id,
+                msg_id,
+                &short_id,
+                mode_label,
+                &task_id,
+                &request,
+                dl_mode,
+                &state2,
+            ).await;
+        });
+        return Ok(());
+    }
+
+    // /history buttons: hp:<page> or hr:<task_id>
+    if let Some(action) = decode_history_callback(&data) {
+        return handle_history_callback(bot, &q, action, state).await;
+    }
+
+    // /settings buttons: st:<field>
+    if let Some(action) = decode_settings_callback(&data) {
+        return handle_settings_callback(bot, &q, action, state).await;
+    }
+
+    // /saved buttons: sv:<a|v|x>:<id>
+    if let Some(action) = decode_saved_callback(&data) {
+        return handle_saved_callback(bot, &q, action, state).await;
+    }
+
+    // /failed buttons: rf:<task_id> or rf:all
+    if let Some(arg) = data.strip_prefix("rf:") {
+        return handle_retry_failed(&bot, &q, arg, &state).await;
+    }
+
+    // Retry a failed download from a fresh copy of the cookie file: rc:<task_id>
+    if let Some(task_id) = data.strip_prefix("rc:") {
+        let _ = bot.answer_callback_query(&q.id).await;
+        let chat_id = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
+        if let Some(ref m) = q.message {
+            let _ = bot.edit_message_reply_markup(chat_id, m.id).await;
+        }
+        return retry_with_cookies(bot, chat_id, task_id, state).await;
+    }
+
+    // Handle playlist confirm (pc:KEY:[p/s/x]) — before decode_callback
+    if data.starts_with("pc:") {
+        let _ = bot.answer_callback_query(&q.id).await;
+        let parts: Vec<&str> = data.splitn(3, ':').collect();
+        let pc_key    = parts.get(1).copied().unwrap_or("");
+        let pc_choice = parts.get(2).copied().unwrap_or("x");
+
+        let pending = match state.playlist_store.get(pc_key).await {
+            Some(p) => p,
+            None    => return Ok(()),
+        };
+        let chat_id = ChatId(pending.chat_id);
+        let msg_id  = pending.message_id;
+
+        if pc_choice == "x" {
+            state.playlist_store.take(pc_key).await;
+            let _ = bot.edit_message_text(chat_id, msg_id, "Cancelled.").await;
+            return Ok(());
+        }
+        if quota_exceeded(&bot, chat_id, &state).await? {
+            state.playlist_store.take(pc_key).await;
+            return Ok(());
+        }
+        if pc_choice == "s" {
+            state.playlist_store.set_single(pc_key, true).await;
+            // Show format selection for both /playlist and /playlistv2
+            let buttons = vec![vec![
+                InlineKeyboardButton::callback(decorate("🎵 Audio (MP3)"), encode_playlist_format(pc_key, true)),
+                InlineKeyboardButton::callback(decorate("🎬 Video (MP4)"), encode_playlist_format(pc_key, false)),
+            ]];
+            let _ = bot.edit_message_text(chat_id, msg_id, "Choose format for this video:")
+                .reply_markup(InlineKeyboardMarkup::new(buttons))
+                .await;
+            return Ok(());
+        }
+        // pc_choice == "p" — show limit selection
+        state.playlist_store.set_single(pc_key, false).await;
+        let buttons = vec![
+            vec![
+                InlineKeyboardButton::callback("10 tracks",  encode_playlist_limit(pc_key, 10)),
+                InlineKeyboardButton::callback("25 tracks",  encode_playlist_limit(pc_key, 25)),
+            ],
+            vec![
+                InlineKeyboardButton::callback("50 tracks",  encode_playlist_limit(pc_key, 50)),
+                InlineKeyboardButton::callback("All tracks", encode_playlist_limit(pc_key, 0)),
+            ],
+        ];
+        let _ = bot.edit_message_text(chat_id, msg_id, "How many tracks to download?")
+            .reply_markup(InlineKeyboardMarkup::new(buttons))
+            .await;
+        return Ok(());
+    }
+
+    // Handle playlist limit (pl:KEY:N) — before decode_callback
+    if data.starts_with("pl:") {
+        info!("Playlist limit callback received: {}", data);
+        let _ = bot.answer_callback_query(&q.id).await;
+        let parts: Vec<&str> = data.splitn(3, ':').collect();
+        let pl_key    = parts.get(1).copied().unwrap_or("");
+        let pl_limit: u32 = parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(0);
+
+        info!("Parsed: key={}, limit={}", pl_key, pl_limit);
+
+        let limit_opt = if pl_limit == 0 { None } else { Some(pl_limit) };
+        state.playlist_store.set_limit(pl_key, limit_opt).await;
+        info!("Limit set in store");
+
+        let pending = match state.playlist_store.get(pl_key).await {
+            Some(p) => {
+                info!("Found pending state: limit={:?}", p.limit);
+                p
+            }
+            None    => {
+                warn!("Playlist key not found in store: {}", pl_key);
+                return Ok(());
+            }
+        };
+        let chat_id = ChatId(pending.chat_id);
+        let msg_id  = pending.message_id;
+        info!("Edit parameters: chat_id={}, message_id={}", pending.chat_id, msg_id);
+        let limit_label = if pl_limit == 0 {
+            "all tracks".to_string()
+        } else {
+            format!("up to {} tracks", pl_limit)
+        };
+
+        // Show format selection for both /playlist and /playlistv2
+        let buttons = vec![vec![
+            InlineKeyboardButton::callback(decorate("🎵 Audio (MP3)"), encode_playlist_format(pl_key, true)),
+            InlineKeyboardButton::callback(decorate("🎬 Video (MP4)"), encode_playlist_format(pl_key, false)),
+        ]];
+        let format_msg_text = format!("Downloading {} — choose format:", limit_label);
+        let keyboard = InlineKeyboardMarkup::new(buttons);
+
+        // Send new format selection message (replaces limit selection message)
+        match bot.send_message(chat_id, decorate(format_msg_text))
+            .reply_markup(keyboard)
+            .await
+        {
+            Ok(new_msg) => {
+                state.playlist_store.set_message_id(pl_key, new_msg.id).await;
+                let _ = bot.delete_message(chat_id, msg_id).await;
+                info!("Sent format selection message (replaced limit selection message)");
+            }
+            Err(e) => {
+                error!("Failed to send format selection message: {:?}", e);
+            }
+        }
+        return Ok(());
+    }
+
+    // Handle playlist format (pf:KEY:[a/v]) — before decode_callback
+    if data.starts_with("pf:") {
+        let _ = bot.answer_callback_query(&q.id).await;
+        let parts: Vec<&str> = data.splitn(3, ':').collect();
+        let pf_key      = parts.get(1).copied().unwrap_or("");
+        let pf_is_audio = parts.get(2).copied().unwrap_or("a") == "a";
+
+        handle_playlist_format_download(&bot, &state, pf_key, pf_is_audio).await?;
+        return Ok(());
+    }
+
+    // Handle playlist preview download (pl_dl:[a|v]:URL) — triggered from preview
+    if let Some(after_prefix) = data.strip_prefix("pl_dl:") {
+        info!("Playlist preview download callback received");
+        let _ = bot.answer_callback_query(&q.id).await;
+
+        // Parse video_only flag: "v:URL" or "a:URL", fall back to plain URL for compat
+        let (is_video_only, url) = if let Some(rest) = after_prefix.strip_prefix("v:") {
+            (true, rest)
+        } else if let Some(rest) = after_prefix.strip_prefix("a:") {
+            (false, rest)
+        } else {
+            (false, after_prefix) // Legacy: no flag prefix
+        };
+
+        let chat_id = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
+        let msg_id  = match q.message { Some(ref m) => m.id,      None => return Ok(()) };
+        info!("Callback query message: chat_id={}, message_id={}", chat_id, msg_id);
+
+        // Create a new playlist store entry
+        let key = format!("{:x}", chrono::Utc::now().timestamp_millis());
+        info!("Created playlist store key: {}", key);
+        state.playlist_store.store(key.clone(), PlaylistPending {
+            url: url.to_string(),
+            chat_id: chat_id.0,
+            message_id: msg_id,
+            is_single: false,
+            limit: Some(10),
+            video_only: is_video_only,
+            created_at: std::time::Instant::now(),
+        }).await;
+        info!("Stored playlist pending: chat_id={}, message_id={}, video_only={}", chat_id.0, msg_id, is_video_only);
+
+        // Show track limit selection
+        let buttons = vec![
+            vec![
+                InlineKeyboardButton::callback(decorate("🎵 10 tracks"),  encode_playlist_limit(&key, 10)),
+                InlineKeyboardButton::callback(decorate("🎵 25 tracks"),  encode_playlist_limit(&key, 25)),
+            ],
+            vec![
+                InlineKeyboardButton::callback(decorate("🎵 50 tracks"),  encode_playlist_limit(&key, 50)),
+                InlineKeyboardButton::callback(decorate("🎵 All tracks"), encode_playlist_limit(&key, 0)),
+            ],
+        ];
+        let edit_result = bot.edit_message_text(chat_id, msg_id, "How many tracks to download?")
+            .reply_markup(InlineKeyboardMarkup::new(buttons))
+            .await;
+
+        match edit_result {
+            Ok(_) => info!("Successfully showed playlist limit selection"),
+            Err(e) => error!("Failed to show playlist limit selection: {}", e),
+        }
+        return Ok(());
+    }
+
+    let (mode_prefix, key, index) = match decode_callback(&data) {
+        Some(decoded) => decoded,
+        None => {
+            if let Some(id) = q.id.as_str().into() {
+                let _ = bot.answer_callback_query(id).await;
+            }
+            return Ok(());
+        }
+    };
+
+    // Answer the callback query immediately to stop the loading spinner
+    let _ = bot.answer_callback_query(&q.id).await;
+
+    // Handle cancel
+    if mode_prefix == "cx" {
+        if let Some(pending) = state.callback_store.take(&key).await {
+            let chat_id = ChatId(pending.chat_id);
+            let _ = bot.edit_message_text(chat_id, pending.message_id, "Cancelled.").await;
+        }
+        return Ok(());
+    }
+
+    // Completion message: rd:key:0 (again) or rd:key:1 (other quality)
+    if mode_prefix == "rd" {
+        return handle_redownload(bot, &q, &key, index == 1, state).await;
+    }
+
+    // /podcast episode: pe:key:index
+    if mode_prefix == "pe" {
+        return handle_podcast_episode(&bot, &q, &key, index, &state).await;
+    }
+
+    // Search "More" / "Back": sm:key:offset
+    if mode_prefix == "sm" {
+        return handle_search_page(&bot, &q, &key, index, &state).await;
+    }
+
+    // Handle search result selection — show audio/video format choice
+    if mode_prefix == "sr" {
+        let pending = match state.search_store.peek(&key).await {
+            Some(p) => p,
+            None    => return Ok(()),
+        };
+        if index >= pending.results.len() { return Ok(()); }
+
+        let result = &pending.results[index];
+        // type:playlist results go through the usual playlist confirmation
+        if link_detector::detect_first_link(&result.url).is_some_and(|l| l.is_playlist()) {
+            if let Some(message) = q.message.clone() {
+                return cmd_playlist_confirm(bot, message, result.url.clone(), state).await;
+            }
+            return Ok(());
+        }
+        let title  = if result.title.chars().count() > 50 {
+            format!("{}…", result.title.chars().take(49).collect::<String>())
+        } else {
+            result.title.clone()
+        };
+        let chat_id = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
+
+        // Send a new message with Audio / Video choice (search results message stays untouched)
+        let buttons = vec![vec![
+            InlineKeyboardButton::callback(decorate("🎵 Audio (MP3)"), encode_search_format_callback(&key, index, true)),
+            InlineKeyboardButton::callback(decorate("🎬 Video (MP4)"), encode_search_format_callback(&key, index, false)),
+        ]];
+        let _ = bot.send_message(chat_id, decorate(format!("Choose format:\n{}", title)))
+            .reply_markup(InlineKeyboardMarkup::new(buttons))
+            .await;
+
+        return Ok(());
+    }
+
+    // Switch audio language: redraw the keyboard with the new selection
+    if mode_prefix == "al" {
+        if let Some(pending) = state.callback_store.select_language(&key, index).await {
+            let keyboard = build_quality_keyboard(
+                &pending.formats, &DownloadMode::Audio, &key,
+                &pending.languages, pending.audio_language.as_deref(), false,
+            );
+            let _ = bot.edit_message_reply_markup(ChatId(pending.chat_id), pending.message_id)
+                .reply_markup(keyboard)
+                .await;
+        }
+        return Ok(());
+    }
+
+    // Toggle "Include subtitles" on the video keyboard
+    if mode_prefix == "sb" {
+        if let Some(pending) = state.callback_store.toggle_subtitles(&key).await {
+            let keyboard = build_quality_keyboard(
+                &pending.formats, &DownloadMode::Video, &key,
+                &pending.languages, pending.audio_language.as_deref(), pending.subtitles,
+            );
+            let _ = bot.edit_message_reply_markup(ChatId(pending.chat_id), pending.message_id)
+                .reply_markup(keyboard)
+                .await;
+        }
+        return Ok(());
+    }
+
+    // Parse mode
+    let mode = match DownloadMode::from_prefix(&mode_prefix) {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+
+    // Get pending selection
+    let pending = match state.callback_store.take(&key).await {
+        Some(p) => p,
+        None => {
+            // Expired or already used
+            if let Some(msg) = q.message {
+                let chat_id = msg.chat.id;
+                let _ = bot.edit_message_text(chat_id, msg.id, "Selection expired. Please try again.").await;
+            }
+            return Ok(());
+        }
+    };
+
+    // Validate index
+    if index >= pending.formats.len() {
+        return Ok(());
+    }
+
+    let format = &pending.formats[index];
+    let chat_id = ChatId(pending.chat_id);
+
+    // Refuse up front when the estimate is over the download cap (admins bypass)
+    let is_admin = state.admin_chat_id.map(|id| id == pending.chat_id).unwrap_or(false);
+    if let (Some(size), Some(max_mb), false) = (format.filesize, max_download_mb(), is_admin) {
+        if size > max_mb * 1024 * 1024 {
+            let _ = bot.edit_message_text(chat_id, pending.message_id, decorate(format!(
+                "{} [{}] is about {:.0} MB, over the {} MB download limit.",
+                pending.title, format.label, size as f64 / 1024.0 / 1024.0, max_mb
+            ))).await;
+            return Ok(());
+        }
+    }
+
+    // Update message to show download started
+    let short_label = &format.label;
+    let _ = bot.edit_message_text(
+        chat_id,
+        pending.message_id,
+        decorate(format!("Downloading: {} [{}]", pending.title, short_label)),
+    ).await;
+
+    let status_msg_id = pending.message_id;
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+
+    // Build IPC request based on format selection
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(format.extract_audio)), pending.chat_id, &task_id);
+    let mut request = download_request_with_format(
+        &task_id,
+        &pending.url,
+        &format.format_id,
+        format.extract_audio,
+        format.audio_format.as_deref(),
+        format.audio_quality.as_deref(),
+        &out_dir,
+        pending.chat_id,
+    );
+    if mode == DownloadMode::Audio {
+        if let Some(lang) = &pending.audio_language {
+            request = request.with_param("audio_language", lang.as_str());
+        }
+    }
+    if mode == DownloadMode::Video && pending.subtitles {
+        request = request.with_param("subtitles", default_subtitle_lang());
+    }
+
+    // Enqueue task
+    let priority = state.task_priority(pending.chat_id, "youtube_dl");
+    state.task_queue.enqueue(&task_id, pending.chat_id, "youtube_dl", priority).await;
+
+    // Create DB record so the task shows in web dashboard
+    if let Some(pool) = &state.db_pool {
+        let label = Some(mode.as_str());
+        let _ = hermes_shared::db::create_task(pool, &task_id, pending.chat_id, "youtube_dl", &pending.url, label, priority).await;
+    }
+
+    // Spawn download in background so the teloxide handler returns immediately.
+    let mode_str = mode.as_str().to_string();
+    tokio::spawn(async move {
+        let _ = execute_download_and_send(
+            &bot,
+            chat_id,
+            status_msg_id,
+            &short_id,
+            &mode_str,
+            &task_id,
+            &request,
+            mode,
+            &state,
+        ).await;
+    });
+
+    Ok(())
+}
+
+/// Deliver a single downloaded file to the user.
+///
+/// Handles all delivery paths:
+///   - ≤ 50 MB → send directly as audio or video
+///   - > 50 MB + MPROTO=true → upload via MTProto IPC, copy_message to user
+///   - > 50 MB + SPLIT_LARGE_FILES → send as numbered parts (see `deliver_in_parts`)
+///   - > 50 MB otherwise → generate and send 24h download link
+///   - `as_link` → skip the upload and send a download link regardless of size
+///
+/// `known_channel_msg_id`: if Some, skip the MTProto upload and copy_message directly
+/// (used by the dedup fast-path when the channel_msg_id is already cached in the DB).
+/// `audio_meta`: title, performer, duration and cover for the music player.
+/// `caption`: shown under the audio, video or document (see `delivery_caption`).
+#[allow(clippy::too_many_arguments)]
+async fn deliver_file(
+    bot: &Bot,
+    chat_id: ChatId,
+    file_path: &str,
+    filename: &str,
+    task_id: &str,
+    mode: DownloadMode,
+    audio_meta: Option<&AudioMetadata>,
+    caption: Option<String>,
+    known_channel_msg_id: Option<i64>,
+    as_link: bool,
+    state: &AppState,
+) -> ResponseResult<()> {
+    if file_path.is_empty() {
+        return Ok(());
+    }
+    let path = std::path::PathBuf::from(file_path);
+    if !path.exists() {
+        warn!("File not found at: {}", file_path);
+        bot.send_message(chat_id, decorate(format!(
+            "⚠️ {} was removed before delivery, please retry.", filename
+        ))).await?;
+        return Ok(());
+    }
+    let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+    if as_link {
+        let headline = format!("🔗 {} ({:.1}MB)", filename, file_size as f64 / 1024.0 / 1024.0);
+        if send_download_link(bot, chat_id, task_id, &headline, None, state).await? {
+            return Ok(());
+        }
+        // No DB or token failure: fall through and send the file normally
+    }
+
+    if file_size > 50 * 1024 * 1024 {
+        let size_mb    = file_size as f64 / 1024.0 / 1024.0;
+        if mtproto_enabled() {
+            let storage_channel_id = storage_channel_id();
+
+            // Use cached channel_msg_id when available (avoids re-upload)
+            let (channel_msg_id, upload_status_msg) = if let Some(cached) = known_channel_msg_id {
+                (Some(cached), None::<teloxide::types::Message>)
+            } else {
+                let upload_task_id = format!("up-{}", task_id);
+                let req = hermes_shared::ipc_protocol::mtproto_upload_request(
+                    &upload_task_id, file_path, chat_id.0, filename,
+                );
+                let sm = bot.send_message(chat_id, decorate(format!(
+                    "⬆️ {:.1}MB — uploading via MTProto...", size_mb
+                ))).await;
+
+                let mut ch_id: Option<i64> = None;
+                let mut last_edit = std::time::Instant::now();
+
+                if let Ok(mut rx) = state.dispatcher.send(&req, Priority::Bulk).await {
+                    loop {
+                        match rx.recv().await {
+                            Some(resp) if resp.is_progress() && last_edit.elapsed().as_secs() >= 4 => {
+                                last_edit = std::time::Instant::now();
+                                let pct  = resp.progress_percent().unwrap_or(0) as usize;
+                                let spd  = resp.progress_speed().unwrap_or_default();
+                                let done = pct / 10;
+                                let bar  = format!("{}{}", "█".repeat(done), "░".repeat(10 - done));
+                                if let Ok(ref m) = sm {
+                                    let _ = bot.edit_message_text(chat_id, m.id, decorate(format!(
+                                        "⬆️ Uploading via MTProto\n[{bar}] {pct}%  {spd}"
+                                    ))).await;
+                                }
+                            }
+                            Some(resp) if resp.is_done() => {
+                                ch_id = resp.data.get("channel_msg_id").and_then(|v| v.as_i64());
+                                break;
+                            }
+                            Some(resp) if resp.is_error() => {
+                                warn!("MTProto upload IPC error for {}: {:?}", task_id, resp.error_message());
+                                break;
+                            }
+                            None => break,
+                            _ => {}
+                        }
+                    }
+                } else {
+                    warn!("Failed to send mtproto_upload IPC request for {}", task_id);
+                }
+
+                (ch_id, sm.ok())
+            };
+
+            if let (Some(msg_id), true) = (channel_msg_id, storage_channel_id != 0) {
+                let from_chat = teloxide::types::ChatId(storage_channel_id);
+                let mut copy = bot.copy_message(chat_id, from_chat, teloxide::types::MessageId(msg_id as i32));
+                if let Some(caption) = &caption {
+                    copy = copy.caption(caption.clone());
+                }
+                match copy.await {
+                    Ok(_) => {
+                        // Persist channel_msg_id so future requests for this file skip the upload
+                        if let Some(pool) = &state.db_pool {
+                            let _ = hermes_shared::db::save_channel_msg_id(pool, task_id, msg_id).await;
+                        }
+                        if let Some(ref sm) = upload_status_msg {
+                            let _ = bot.delete_message(chat_id, sm.id).await;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("copy_message failed for {}: {}", task_id, e);
+                        let err_text = "⚠️ MTProto forward failed — try again";
+                        if let Some(ref sm) = upload_status_msg {
+                            let _ = bot.edit_message_text(chat_id, sm.id, decorate(err_text)).await;
+                        } else {
+                            let _ = bot.send_message(chat_id, decorate(err_text)).await;
+                        }
+                    }
+                }
+            } else {
+                // Upload failed or channel not configured — fall back to a download link
+                let headline = format!("⚠️ MTProto upload failed ({:.1}MB).", size_mb);
+                let replace = upload_status_msg.as_ref().map(|sm| sm.id);
+                if !send_download_link(bot, chat_id, task_id, &headline, replace, state).await? {
+                    send_too_large(bot, chat_id, size_mb, &mode).await?;
+                }
+            }
+        } else if split_large_files() {
+            deliver_in_parts(bot, chat_id, &path, filename, task_id, mode, state).await?;
+        } else {
+            let headline = format!("⚠️ File too large for Telegram ({:.1}MB)", size_mb);
+            if !send_download_link(bot, chat_id, task_id, &headline, None, state).await? {
+                send_too_large(bot, chat_id, size_mb, &mode).await?;
+            }
+        }
+    } else if mode == DownloadMode::Video {
+        let display_name = truncate_filename(path.file_name().and_then(|n| n.to_str()).unwrap_or(filename), MAX_FILENAME_BYTES);
+        let input = teloxide::types::InputFile::file(&path).file_name(display_name.clone());
+        let mut request = bot.send_video(chat_id, input);
+        if let Some(caption) = &caption {
+            request = request.caption(caption);
+        }
+        if let Err(e) = request.await {
+            warn!("Failed to send video, trying document: {}", e);
+            let input2 = teloxide::types::InputFile::file(&path).file_name(display_name);
+            let mut fallback = bot.send_document(chat_id, input2);
+            if let Some(caption) = caption {
+                fallback = fallback.caption(caption);
+            }
+            let _ = fallback.await;
+        }
+    } else {
+        let display_name = truncate_filename(path.file_name().and_then(|n| n.to_str()).unwrap_or(filename), MAX_FILENAME_BYTES);
+        let input = teloxide::types::InputFile::file(&path).file_name(display_name.clone());
+        let mut request = bot.send_audio(chat_id, input);
+        if let Some(caption) = &caption {
+            request = request.caption(caption);
+        }
+        if let Some(meta) = audio_meta {
+            if let Some(title) = &meta.title {
+                request = request.title(title);
+            }
+            if let Some(performer) = &meta.performer {
+                request = request.performer(performer);
+            }
+            if let Some(duration) = meta.duration {
+                request = request.duration(duration);
+            }
+            if let Some(thumb) = meta.thumbnail_path.as_deref().filter(|p| std::path::Path::new(p).is_file()) {
+                request = request.thumb(teloxide::types::InputFile::file(thumb));
+            }
+        }
+        if let Err(e) = request.await {
+            warn!("Failed to send audio, trying document: {}", e);
+            let input2 = teloxide::types::InputFile::file(&path).file_name(display_name);
+            let mut fallback = bot.send_document(chat_id, input2);
+            if let Some(caption) = caption {
+                fallback = fallback.caption(caption);
+            }
+            let _ = fallback.await;
+        }
+    }
+    Ok(())
+}
+
+/// Send an expiring download link (`/api/dl/<task_id>`, DOWNLOAD_LINK_TTL_SECS)
+/// for a finished task's file, as a button plus the plain URL. `replace` is a
+/// status message to delete first. Returns false when no link could be made
+/// (no database or the token insert failed) so the caller can fall back.
+async fn send_download_link(
+    bot: &Bot,
+    chat_id: ChatId,
+    task_id: &str,
+    headline: &str,
+    replace: Option<MessageId>,
+    state: &AppState,
+) -> ResponseResult<bool> {
+    let Some(pool) = &state.db_pool else { return Ok(false) };
+    let ttl = download_link_ttl_secs();
+    if let Err(e) = hermes_shared::db::create_file_download_token(pool, task_id, chat_id.0, ttl).await {
+        warn!("Failed to create download token for {}: {}", task_id, e);
+        return Ok(false);
+    }
+
+    if let Some(id) = replace {
+        let _ = bot.delete_message(chat_id, id).await;
+    }
+    let dl_url = format!("{}/api/dl/{}", dashboard_base_url(), task_id);
+    let expires = chrono::Utc::now() + chrono::Duration::seconds(ttl);
+    let text = decorate(format!(
+        "{}\n\n📥 Download link (valid {}, until {} UTC):\n{}",
+        headline, format_ttl(ttl), expires.format("%Y-%m-%d %H:%M"), dl_url
+    ));
+    // Telegram refuses buttons for some URLs (e.g. localhost); the text link still works
+    let sent_with_button = match url::Url::parse(&dl_url) {
+        Ok(link) => bot.send_message(chat_id, text.clone())
+            .reply_markup(InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::url(decorate("📥 Download"), link),
+            ]]))
+            .await
+            .is_ok(),
+        Err(_) => false,
+    };
+    if !sent_with_button {
+        bot.send_message(chat_id, text).await?;
+    }
+    Ok(true)
+}
+
+/// Last resort for an oversized file: say so, with a hint for videos.
+async fn send_too_large(bot: &Bot, chat_id: ChatId, size_mb: f64, mode: &DownloadMode) -> ResponseResult<()> {
+    let hint = if *mode == DownloadMode::Video {
+        "Use /dv to pick a lower resolution."
+    } else {
+        "The file exceeds Telegram's 50MB limit."
+    };
+    bot.send_message(chat_id, decorate(format!(
+        "⚠️ File too large for Telegram ({:.1}MB)\n\n{}", size_mb, hint
+    ))).await?;
+    Ok(())
+}
+
+/// Send a file over the upload limit as parts of at most `file_split::PART_SIZE`.
+/// Audio/video is cut into playable parts by the worker; anything else (or media
+/// the worker couldn't cut) is split byte for byte, with a join hint on the last part.
+async fn deliver_in_parts(
+    bot: &Bot,
+    chat_id: ChatId,
+    path: &std::path::Path,
+    filename: &str,
+    task_id: &str,
+    mode: DownloadMode,
+    state: &AppState,
+) -> ResponseResult<()> {
+    let size_mb = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0) as f64 / 1024.0 / 1024.0;
+    let status = bot.send_message(chat_id, decorate(format!(
+        "✂️ {:.1}MB is over Telegram's 50MB limit — splitting into parts...", size_mb
+    ))).await?;
+    let file_path = path.to_string_lossy().to_string();
+    let out_dir = path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut playable = None;
+    if file_split::is_splittable_media(path) {
+        let request = split_media_request(&format!("split-{}", task_id), &file_path, file_split::PART_SIZE, &out_dir);
+        match state.dispatcher.send_and_wait(&request, 900).await {
+            Ok(r) if r.is_done() => {
+                playable = r.data.get("parts").and_then(|v| v.as_array()).map(|parts| {
+                    parts.iter().filter_map(|p| p.as_str()).map(std::path::PathBuf::from).collect::<Vec<_>>()
+                });
+            }
+            Ok(r) => warn!("Media split failed for {}: {:?}, splitting bytes", task_id, r.error_message()),
+            Err(e) => warn!("Media split failed for {}: {}, splitting bytes", task_id, e),
+        }
+    }
+    let (parts, playable) = match playable.filter(|p| !p.is_empty()) {
+        Some(parts) => (parts, true),
+        None => match file_split::split_bytes(path, file_split::PART_SIZE).await {
+            Ok(parts) => (parts, false),
+            Err(e) => {
+                warn!("Byte split failed for {}: {}", task_id, e);
+                let headline = format!("⚠️ File too large for Telegram ({:.1}MB) and it couldn't be split.", size_mb);
+                if !send_download_link(bot, chat_id, task_id, &headline, Some(status.id), state).await? {
+                    send_too_large(bot, chat_id, size_mb, &mode).await?;
+                }
+                return Ok(());
+            }
+        },
+    };
+
+    let total = parts.len();
+    info!("Sending {} as {} {} parts", task_id, total, if playable { "playable" } else { "byte" });
+    let display_name = truncate_filename(filename, MAX_FILENAME_BYTES);
+    let mut failed = 0;
+    for (i, part) in parts.iter().enumerate() {
+        let part_name = part.file_name().and_then(|n| n.to_str()).unwrap_or(filename).to_string();
+        let mut caption = format!("Part {}/{} — {}", i + 1, total, display_name);
+        if !playable && i + 1 == total {
+            caption.push_str("\n\n");
+            caption.push_str(&file_split::join_hint(filename));
+        }
+        let caption = decorate(caption);
+        let input = || teloxide::types::InputFile::file(part).file_name(truncate_filename(&part_name, MAX_FILENAME_BYTES));
+        let sent = match (playable, &mode) {
+            (true, DownloadMode::Video) => bot.send_video(chat_id, input()).caption(caption.clone()).await.map(|_| ()),
+            (true, DownloadMode::Audio) => bot.send_audio(chat_id, input()).caption(caption.clone()).await.map(|_| ()),
+            _ => bot.send_document(chat_id, input()).caption(caption.clone()).await.map(|_| ()),
+        };
+        if let Err(e) = sent {
+            warn!("Failed to send part {}/{} of {}: {}", i + 1, total, task_id, e);
+            if bot.send_document(chat_id, input()).caption(caption).await.is_err() {
+                failed += 1;
+            }
+        }
+    }
+    file_split::remove_parts(&parts).await;
+
+    if failed == 0 {
+        let _ = bot.delete_message(chat_id, status.id).await;
+    } else {
+        let _ = bot.edit_message_text(chat_id, status.id, decorate(format!(
+            "⚠️ {} of {} parts could not be sent.", failed, total
+        ))).await;
+    }
+    Ok(())
+}
+
+/// Execute a download request, stream progress, and send the resulting file.
+/// Shared by cmd_download and handle_callback_query.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_download_and_send(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    kind: &str,
+    task_id: &str,
+    request: &IPCRequest,
+    mode: DownloadMode,
+    state: &AppState,
+) -> ResponseResult<()> {
+    info!("[{short_id}] Starting download: kind={}, action={:?}", kind, request.action);
+
+    // Kept so a restart resumes with the same choices (operator params are re-added)
+    if request.action == IPCAction::YoutubeDl {
+        if let (Some(pool), Ok(json)) = (&state.db_pool, serde_json::to_string(request)) {
+            let _ = hermes_shared::db::set_task_ipc_request(pool, task_id, &json).await;
+        }
+    }
+
+    // All slots busy: tell the user roughly how long they'll wait
+    if let Some(wait) = state.task_queue.estimated_start_secs(task_id).await.filter(|&w| w > 0) {
+        let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+            "⏳ Waiting for a free slot [{}]\nEst. start in {}", short_id, format_wait(wait)
+        ))).await;
+    }
+
+    // Operator-side params: HTTP overrides, size cap (admins bypass) and provenance tag
+    let mut request = request.clone();
+    if matches!(request.action, IPCAction::YoutubeDl | IPCAction::Playlist) {
+        if let Some(url) = request.url.clone() {
+            request = request.with_http_options(&http_options_for(&url));
+        }
+    }
+    if request.action == IPCAction::YoutubeDl {
+        let is_admin = state.admin_chat_id.map(|id| id == chat_id.0).unwrap_or(false);
+        if let Some(max_mb) = max_download_mb().filter(|_| !is_admin) {
+            request = request.with_param("max_filesize_mb", max_mb);
+        }
+        if let Some(tag) = file_metadata_tag() {
+            request = request.with_param("metadata_tag", tag);
+        }
+        // A clip is a time range of another video; the worker resolves both
+        let is_clip = request.url.as_deref()
+            .and_then(link_detector::detect_first_link)
+            .is_some_and(|l| l.is_clip());
+        if is_clip {
+            request = request.with_param("clip", true);
+        }
+        if request.params["extract_audio"] == serde_json::json!(true) {
+            for (key, value) in audio_output_params(state, chat_id.0).await {
+                request = request.with_param(key, value);
+            }
+            // Title, artist and cover art, echoed back for send_audio
+            request = request.with_param("embed_metadata", true);
+        }
+    }
+    let request = &request;
+
+    // Taken before waiting so a /cancel at any point below is seen
+    let cancel = state.task_queue.cancellation(task_id).await;
+
+    // Acquire concurrency slot (a queued task can be cancelled while it waits)
+    if !acquire_worker_slot(bot, chat_id, status_msg_id, short_id, task_id, &cancel, state).await? {
+        return Ok(());
+    }
+
+    info!("[{short_id}] Acquired download slot");
+    let started = Instant::now();
+    // Replaces "Task Queued" / "Waiting for a free slot" until the first progress event
+    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "▶️ Starting download [{}]...", short_id
+    ))).await;
+
+    // Send to Python worker and process response stream. A worker that is down
+    // (crashed or restarting) gets a grace period before the task is failed.
+    let grace = tokio::time::Duration::from_secs(worker_down_grace_secs());
+    let mut waiting_since: Option<Instant> = None;
+    let mut told_user = false;
+    let mut rx = loop {
+        let e = match state.dispatcher.send(request, Priority::Bulk).await {
+            Ok(rx) => break rx,
+            Err(e) => e,
+        };
+        let worker_down = matches!(
+            e,
+            HermesError::Ipc(IpcError::NotRunning | IpcError::WriteFailed(_))
+        );
+        let since = *waiting_since.get_or_insert_with(Instant::now);
+        if !worker_down || since.elapsed() >= grace {
+            state.task_queue.fail(task_id).await;
+            error!("Failed to send IPC request: {}", e);
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::fail_task(pool, task_id, &e.to_string()).await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Worker error: {} [{}]", user_errors::from_hermes(&e).message, short_id
+            ))).await?;
+            return Ok(());
+        }
+        if !told_user {
+            told_user = true;
+            warn!("[{short_id}] Worker unavailable ({}), holding task for up to {}s", e, grace.as_secs());
+            let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "⏳ Worker restarting, your download will resume shortly [{}]", short_id
+            ))).await;
+        }
+        let cancelled = tokio::select! {
+            biased;
+            _ = cancel.notified() => true,
+            _ = tokio::time::sleep(WORKER_RETRY_INTERVAL) => false,
+        };
+        if cancelled {
+            // cancel() already released the slot
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::cancel_task(pool, task_id).await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!("Cancelled [{}]", short_id))).await?;
+            return Ok(());
+        }
+    };
+    if let Some(since) = waiting_since {
+        info!("[{short_id}] Worker back after {}s, sending request", since.elapsed().as_secs());
+    }
+
+    info!("[{short_id}] Sent request to Python worker, waiting for responses");
+
+    // Process response stream with throttled progress updates
+    let mut last_edit = Instant::now();
+    let mut last_percent: i32 = -1;
+    // 10 min, or longer when the worker also re-encodes for burned-in subtitles
+    let timeout = tokio::time::Duration::from_secs(
+        if request.params.get("burn_subtitles").is_some() { hardsubs_timeout_secs() } else { 600 },
+    );
+    let idle_timeout = tokio::time::Duration::from_secs(ipc_idle_timeout_secs());
+
+    let result = tokio::time::timeout(timeout, async {
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = cancel.notified() => return StreamEnd::Cancelled,
+                next = tokio::time::timeout(idle_timeout, rx.recv()) => next,
+            };
+            let response = match next {
+                Ok(Some(response)) => response,
+                Ok(None) => return StreamEnd::Closed,
+                Err(_) => return StreamEnd::Stalled,
+            };
+            if response.is_progress() {
+                let pct = response.progress_percent().unwrap_or(0) as i32;
+                let speed = response.progress_speed().unwrap_or_default();
+                let status = response.data.get("status")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("downloading");
+                let eta = state.task_queue
+                    .update_progress(task_id, pct as u8, Some(speed.clone()), response.progress_eta())
+                    .await;
+
+                // Throttle edits: at least 3s apart and at least 5% change
+                let elapsed = last_edit.elapsed().as_secs();
+                if elapsed >= 3 && (pct - last_percent).abs() >= 5 {
+                    let bar = progress_bar(pct as u8);
+                    let mut text = format!(
+                        "{} [{}]\n{} {}%\nSpeed: {}\nStatus: {}",
+                        kind, short_id, bar, pct, speed, status
+                    );
+                    if let Some(secs) = eta {
+                        text.push_str(&format!("\nETA: {}", format_eta(secs)));
+                    }
+                    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(text)).await;
+                    last_edit = Instant::now();
+                    last_percent = pct;
+                }
+                continue;
+            }
+
+            // The worker is retrying on its own: say so and keep waiting
+            if response.is_retry() {
+                let (attempt, max) = response.retry_attempt().unwrap_or((2, None));
+                info!("[{short_id}] Worker retrying (attempt {}): {:?}", attempt, response.retry_reason());
+                let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(
+                    format_retry_status(kind, short_id, attempt, max, response.retry_reason().as_deref())
+                )).await;
+                last_edit = Instant::now();
+                last_percent = -1;
+                continue;
+            }
+
+            // The worker's answer to /cancel can beat the queue's notice
+            if response.error_code().as_deref() == Some("CANCELLED") {
+                return StreamEnd::Cancelled;
+            }
+
+            // Any other event = final response
+            return StreamEnd::Response(response);
+        }
+    }).await;
+
+    // Handle result
+    match result {
+        Ok(StreamEnd::Response(response)) => {
+            info!("[{short_id}] Received response: event={:?}, data keys={:?}",
+                response.event,
+                response.data.as_object().map(|obj| obj.keys().collect::<Vec<_>>())
+            );
+
+            if response.is_error() {
+                let error_msg = user_errors::from_ipc_response(&response).render(short_id);
+                state.task_queue.fail(task_id).await;
+                // Persist failure to DB
+                if let Some(pool) = &state.db_pool {
+                    let _ = hermes_shared::db::fail_task(pool, task_id, &error_msg).await;
+                }
+                let edit = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                    "Download failed [{}]\n{}", short_id, error_msg
+                )));
+                // Auth failures get a one-tap retry that forces cookies.
+                let auth_failure = response.error_code().is_some_and(|c| is_auth_error_code(&c))
+                    && request.params.get("use_cookies").is_none();
+                if auth_failure {
+                    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+                        InlineKeyboardButton::callback(decorate("🍪 Retry with cookies"), format!("rc:{}", task_id)),
+                    ]]);
+                    edit.reply_markup(keyboard).await?;
+                } else {
+                    edit.await?;
+                }
+            } else {
+                // Only local files can be delivered so far; see hermes_shared::file_source
+                let source = response.file_ref().map(|r| r.source());
+                let file_path = source.as_deref()
+                    .and_then(|s| s.local_path())
+                    .and_then(|p| p.to_str())
+                    .unwrap_or("");
+                let filename = response.data.get("filename")
+                    .and_then(|v| v.as_str())
+                    .or(source.as_deref().map(|s| s.name()))
+                    .unwrap_or("download");
+
+                // The worker reported success but the file is gone (cleanup task or a race):
+                // fail the task instead of showing "Download complete" with nothing sent.
+                let has_files = response.data.get("files").is_some();
+                if !file_path.is_empty() && !has_files && !std::path::Path::new(file_path).exists() {
+                    warn!("[{short_id}] Downloaded file vanished before delivery: {}", file_path);
+                    let error_msg = "File was removed before delivery";
+                    state.task_queue.fail(task_id).await;
+                    if let Some(pool) = &state.db_pool {
+                        let _ = hermes_shared::db::fail_task(pool, task_id, error_msg).await;
+                    }
+                    bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                        "⚠️ {} [{}]\nPlease retry the download.", error_msg, short_id
+                    ))).await?;
+                    state.dispatcher.remove_pending(task_id).await;
+                    return Ok(());
+                }
+
+                // Guard against the worker reporting a non-media file (e.g. an error log)
+                if !file_path.is_empty() && !is_allowed_output_file(file_path) {
+                    warn!("[{short_id}] Refusing to send unexpected output file: {}", file_path);
+                    let ext = std::path::Path::new(file_path)
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .unwrap_or("none");
+                    let error_msg = format!("Unexpected output file type (.{})", ext);
+                    state.task_queue.fail(task_id).await;
+                    if let Some(pool) = &state.db_pool {
+                        let _ = hermes_shared::db::fail_task(pool, task_id, &error_msg).await;
+                    }
+                    bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                        "Download failed [{}]\n{}", short_id, error_msg
+                    ))).await?;
+                    state.dispatcher.remove_pending(task_id).await;
+                    return Ok(());
+                }
+
+                // Feeds the download-time estimate shown by /estimate
+                if request.action == IPCAction::YoutubeDl {
+                    if let Some(bytes) = response.data.get("file_size").and_then(|v| v.as_u64()) {
+                        state.task_queue.record_transfer(bytes, started.elapsed().as_secs_f64()).await;
+                    }
+                }
+
+                // /both: a second deliverable, the audio taken from the downloaded
+                // file. Extracted before completing so the task keeps its slot.
+                let companion_audio = match request.params.get("also_audio").and_then(|v| v.as_str()) {
+                    Some(format) if !file_path.is_empty() => Some(
+                        extract_companion_audio(bot, chat_id, status_msg_id, short_id, task_id, file_path, format, state).await
+                    ),
+                    _ => None,
+                };
+
+                state.task_queue.complete(task_id).await;
+
+                // Persist completion to DB
+                if let Some(pool) = &state.db_pool {
+                    let _ = hermes_shared::db::complete_task(pool, task_id, file_path).await;
+                }
+
+                let mut file_lines = match response.data.get("files").and_then(|v| v.as_array()) {
+                    Some(files) if !files.is_empty() => format!("Files: {}", files.len()),
+                    _ => format!("File: {}", filename),
+                };
+                if let Some(Ok((_, audio_name))) = &companion_audio {
+                    file_lines.push_str(&format!("\nAudio: {}", audio_name));
+                }
+
+                // store_only: the dashboard library is the delivery. /link still sends its link.
+                let link_requested = request.params.get("deliver_as_link").and_then(|v| v.as_bool()).unwrap_or(false);
+                if !link_requested && prefers_store_only(state, chat_id.0).await {
+                    info!("[{short_id}] Stored only, not sending to chat");
+                    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                        "📚 Saved to your library [{}]\n{}\n\n{}/files.html",
+                        short_id, file_lines, dashboard_base_url()
+                    ))).await;
+                    state.dispatcher.remove_pending(task_id).await;
+                    return Ok(());
+                }
+
+                // Edit message to show completion (don't use ? - must continue to send files even if edit fails)
+                let edit = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                    "Download complete [{}]\n{}", short_id, file_lines
+                )));
+                let _ = match redownload_keyboard(request, &mode, state).await {
+                    Some(keyboard) => edit.reply_markup(keyboard).await,
+                    None => edit.await,
+                };
+
+                // Send the file to user
+                let as_link = link_requested || prefers_link_delivery(state, chat_id.0).await;
+                let audio_meta = response.audio_metadata();
+                let caption = delivery_caption(state, chat_id.0, &caption_fields(&response, request, filename)).await;
+                deliver_file(bot, chat_id, file_path, filename, task_id, mode, audio_meta.as_ref(), caption, None, as_link, state).await?;
+
+                match companion_audio {
+                    Some(Ok((audio_path, audio_name))) => {
+                        send_media_file(bot, chat_id, std::path::Path::new(&audio_path), &audio_name).await;
+                    }
+                    Some(Err(error_msg)) => {
+                        let _ = bot.send_message(chat_id, decorate(format!(
+                            "⚠️ Audio extraction failed [{}]\n{}", short_id, error_msg
+                        ))).await;
+                    }
+                    None => {}
+                }
+
+                // "Include subtitles": the track the worker saved next to the video
+                if request.params.get("subtitles").is_some() {
+                    match response.data.get("subtitle_file").and_then(|v| v.as_str()) {
+                        Some(subtitle_path) => {
+                            if let Err(e) = send_subtitle_file(bot, chat_id, subtitle_path).await {
+                                warn!("[{short_id}] Failed to send subtitles: {}", e);
+                            }
+                        }
+                        None => {
+                            let _ = bot.send_message(chat_id, decorate(
+                                "💬 No subtitles in that language for this video."
+                            )).await;
+                        }
+                    }
+                }
+
+                // Handle playlist files - grouped into albums, or sent one by one
+                if let Some(files) = response.data.get("files").and_then(|v| v.as_array()) {
+                    info!("[{short_id}] Found 'files' array with {} entries", files.len());
+                    if !files.is_empty() {
+                        let _ = bot.send_message(chat_id, decorate(format!(
+                            "📤 Sending {} track(s)...",
+                            files.len()
+                        ))).await;
+
+                        let mut tracks = Vec::with_capacity(files.len());
+                        for file_info in files {
+                            let file_path = file_info.get("path").and_then(|v| v.as_str()).unwrap_or("");
+                            let file_name = file_info.get("name").and_then(|v| v.as_str()).unwrap_or("track");
+
+                            if !is_allowed_output_file(file_name) {
+                                warn!("[{short_id}] Skipping unexpected playlist output: {}", file_name);
+                                continue;
+                            }
+
+                            let fpath = std::path::PathBuf::from(file_path);
+                            match tokio::fs::metadata(&fpath).await {
+                                Ok(meta) => tracks.push(media_group::Track {
+                                    path: fpath,
+                                    name: file_name.to_string(),
+                                    size: meta.len(),
+                                }),
+                                Err(_) => warn!("[{short_id}] File not found (path={}, name={}). Current dir: {:?}",
+                                    file_path, file_name,
+                                    std::env::current_dir().ok()
+                                ),
+                            }
+                        }
+
+                        let total = tracks.len();
+                        let batches = if playlist_albums() {
+                            media_group::plan_batches(tracks)
+                        } else {
+                            tracks.into_iter().map(media_group::Batch::Single).collect()
+                        };
+                        let playlist_name = response.data.get("playlist_name")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("Playlist")
+                            .to_string();
+
+                        // Up to PLAYLIST_SEND_CONCURRENCY uploads at a time; each slot
+                        // waits PLAYLIST_SEND_DELAY_MS after its upload before the next one
+                        let slots = Arc::new(tokio::sync::Semaphore::new(playlist_send_concurrency()));
+                        let delay = playlist_send_delay();
+                        let mut sends = tokio::task::JoinSet::new();
+                        let batch_count = batches.len();
+                        let mut first_track = 1;
+
+                        for (idx, batch) in batches.into_iter().enumerate() {
+                            let Ok(slot) = slots.clone().acquire_owned().await else { break };
+                            let bot = bot.clone();
+                            let is_last = idx + 1 == batch_count;
+                            let first = first_track;
+                            first_track += match &batch {
+                                media_group::Batch::Album(_, items) => items.len(),
+                                media_group::Batch::Single(_) => 1,
+                            };
+                            info!("[{short_id}] Sending track(s) from {}/{}", first, total);
+                            let caption = playlist_name.clone();
+                            sends.spawn(async move {
+                                match batch {
+                                    media_group::Batch::Album(kind, items) => {
+                                        let caption = media_group::album_caption(&caption, first, items.len(), total);
+                                        send_album(&bot, chat_id, kind, &items, &caption).await;
+                                    }
+                                    media_group::Batch::Single(track) => {
+                                        send_media_file(&bot, chat_id, &track.path, &track.name).await;
+                                    }
+                                }
+                                if !is_last {
+                                    tokio::time::sleep(delay).await;
+                                }
+                                drop(slot);
+                            });
+                        }
+
+                        while sends.join_next().await.is_some() {}
+
+                        let _ = bot.send_message(chat_id, decorate(format!(
+                            "✅ Sent all {} tracks", files.len()
+                        ))).await;
+                    }
+                } else {
+                    info!("[{short_id}] No 'files' array in response data");
+                    // Fallback: handle archives if present (for backward compatibility)
+                    if let Some(archives) = response.data.get("archives").and_then(|v| v.as_array()) {
+                        info!("[{short_id}] Found 'archives' array with {} entries", archives.len());
+                        for archive in archives {
+                            let archive_path = archive.get("path").and_then(|v| v.as_str()).unwrap_or("");
+                            let archive_name = archive.get("name").and_then(|v| v.as_str()).unwrap_or("archive.zip");
+
+                            let apath = std::path::PathBuf::from(archive_path);
+                            if apath.exists() {
+                                let input = teloxide::types::InputFile::file(&apath).file_name(archive_name.to_string());
+                                if let Err(e) = bot.send_document(chat_id, input).await {
+                                    warn!("Failed to send archive {}: {}", archive_name, e);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(StreamEnd::Cancelled) => {
+            // The queue slot was released by cancel(); stop listening and record it
+            info!("[{short_id}] Cancelled by user, no longer waiting on the worker");
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::cancel_task(pool, task_id).await;
+            }
+            let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Cancelled [{}]", short_id
+            ))).await;
+        }
+        Ok(StreamEnd::Closed) => {
+            state.task_queue.fail(task_id).await;
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::fail_task(pool, task_id, "Worker connection lost").await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Worker connection lost [{}]", short_id
+            ))).await?;
+        }
+        Ok(StreamEnd::Stalled) => {
+            warn!("[{short_id}] No worker events for {}s, treating download as stalled", idle_timeout.as_secs());
+            abandon_on_worker(state, task_id).await;
+            state.task_queue.fail(task_id).await;
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::fail_task(pool, task_id, "Download stalled").await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Download stalled [{}]\nNo progress from the worker for {}s.", short_id, idle_timeout.as_secs()
+            ))).await?;
+        }
+        Err(_) => {
+            abandon_on_worker(state, task_id).await;
+            state.task_queue.fail(task_id).await;
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::fail_task(pool, task_id, "Download timed out").await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Download timed out [{}]", short_id
+            ))).await?;
+        }
+    }
+
+    // Cleanup
+    state.dispatcher.remove_pending(task_id).await;
+    Ok(())
+}
+
+/// How the worker response stream of a download ended.
+enum StreamEnd {
+    /// Final (non-progress) event from the worker.
+    Response(IPCResponse),
+    /// The response channel closed without a final event.
+    Closed,
+    /// No event arrived within the idle timeout.
+    Stalled,
+    /// The user cancelled the task.
+    Cancelled,
+}
+
+/// How many times a send is retried after Telegram answers "retry after N".
+const RETRY_AFTER_ATTEMPTS: usize = 3;
+
+/// Run a Telegram request, waiting out flood-control `RetryAfter` replies.
+async fn with_retry_after<T, Fut>(mut request: impl FnMut() -> Fut) -> Result<T, teloxide::RequestError>
+where
+    Fut: std::future::Future<Output = Result<T, teloxide::RequestError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Err(teloxide::RequestError::RetryAfter(wait)) if attempt < RETRY_AFTER_ATTEMPTS => {
+                attempt += 1;
+                warn!("Rate limited by Telegram, retrying in {}s", wait.as_secs());
+                tokio::time::sleep(wait).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Have the worker extract the audio track of the downloaded `video_path` (for
+/// /both). Returns the audio file's path and name, or the error to show.
+#[allow(clippy::too_many_arguments)]
+async fn extract_companion_audio(
+    bot: &Bot,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    video_path: &str,
+    audio_format: &str,
+    state: &AppState,
+) -> Result<(String, String), String> {
+    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "🎵 Extracting audio [{}]...", short_id
+    ))).await;
+    // The download's response channel is finished with; the extraction reuses the task id
+    state.dispatcher.remove_pending(task_id).await;
+    let request = extract_audio_request(task_id, video_path, audio_format);
+    match state.dispatcher.send_and_wait(&request, 600).await {
+        Ok(response) if response.is_done() => {
+            let path = response.data.get("file_path").and_then(|v| v.as_str()).unwrap_or("");
+            let name = response.data.get("filename").and_then(|v| v.as_str()).unwrap_or("audio");
+            if path.is_empty() || !is_allowed_output_file(path) {
+                return Err("The worker returned no audio file".to_string());
+            }
+            info!("[{short_id}] Companion audio ready: {}", name);
+            Ok((path.to_string(), name.to_string()))
+        }
+        Ok(response) => Err(user_errors::from_ipc_response(&response).render(short_id)),
+        Err(e) => Err(user_errors::from_hermes(&e).render(short_id)),
+    }
+}
+
+/// Send a downloaded playlist item as video or audio by extension,
+/// falling back to a document if Telegram rejects it.
+async fn send_media_file(bot: &Bot, chat_id: ChatId, fpath: &std::path::Path, file_name: &str) {
+    let file_name = &truncate_filename(file_name, MAX_FILENAME_BYTES);
+
+    let input = || teloxide::types::InputFile::file(fpath).file_name(file_name.to_string());
+    if media_group::MediaKind::for_file(file_name) == media_group::MediaKind::Video {
+        if let Err(e) = with_retry_after(|| bot.send_video(chat_id, input()).send()).await {
+            warn!("Failed to send video {}: {}", file_name, e);
+            let _ = with_retry_after(|| bot.send_document(chat_id, input()).send()).await;
+        }
+    } else if let Err(e) = with_retry_after(|| bot.send_audio(chat_id, input()).send()).await {
+        warn!("Failed to send audio {}: {}", file_name, e);
+        let _ = with_retry_after(|| bot.send_document(chat_id, input()).send()).await;
+    }
+}
+
+/// Send playlist tracks as one album with `caption` on the first item. If
+/// Telegram rejects the album, each track is sent on its own instead.
+async fn send_album(
+    bot: &Bot,
+    chat_id: ChatId,
+    kind: media_group::MediaKind,
+    tracks: &[media_group::Track],
+    caption: &str,
+) {
+    use teloxide::types::{InputFile, InputMedia, InputMediaAudio, InputMediaVideo};
+
+    let media = || {
+        tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                let file = InputFile::file(&track.path)
+                    .file_name(truncate_filename(&track.name, MAX_FILENAME_BYTES));
+                let caption = (i == 0).then(|| decorate(caption));
+                match kind {
+                    media_group::MediaKind::Audio => {
+                        let item = InputMediaAudio::new(file);
+                        InputMedia::Audio(match caption {
+                            Some(c) => item.caption(c),
+                            None => item,
+                        })
+                    }
+                    media_group::MediaKind::Video => {
+                        let item = InputMediaVideo::new(file);
+                        InputMedia::Video(match caption {
+                            Some(c) => item.caption(c),
+                            None => item,
+                        })
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if let Err(e) = with_retry_after(|| bot.send_media_group(chat_id, media()).send()).await {
+        warn!("Failed to send album of {} track(s), sending them one by one: {}", tracks.len(), e);
+        for track in tracks {
+            send_media_file(bot, chat_id, &track.path, &track.name).await;
+        }
+    }
+}
+
+/// Shared logic for starting a playlist/single-video download after format is chosen.
+///
+/// Called from both the `pf:` callback handler (user clicked audio/video button)
+/// and directly from the `pl:`/`pc:` handlers when `video_only` is set.
+async fn handle_playlist_format_download(
+    bot: &Bot,
+    state: &Arc<AppState>,
+    key: &str,
+    is_audio: bool,
+) -> ResponseResult<()> {
+    let pending = match state.playlist_store.take(key).await {
+        Some(p) => p,
+        None    => return Ok(()),
+    };
+
+    let chat_id    = ChatId(pending.chat_id);
+    let msg_id     = pending.message_id;
+    let task_id    = Uuid::new_v4().to_string();
+    let short_id   = task_id[..8].to_string();
+    let mode_label = if is_audio { "audio" } else { "video" };
+    let is_single  = pending.is_single;
+    let storage    = if is_single { StorageKind::for_media(is_audio) } else { StorageKind::Playlist };
+    let out_dir    = task_output_dir(state.storage.base_for(storage), pending.chat_id, &task_id);
+
+    let prefs = load_user_prefs(state, pending.chat_id).await;
+
+    let (url, ipc_action, request) = if is_single {
+        let single_url = extract_single_video_url(&pending.url);
+        let req = download_request_prefs(
+            &task_id, &single_url, is_audio,
+            &prefs,
+            &out_dir, pending.chat_id,
+        );
+        (single_url, "youtube_dl", req)
+    } else {
+        let archive_opt = Some(format!("{}/playlist_archive.txt", state.download_dir));
+        info!("Playlist download: limit={:?}, url={}, is_audio={}, archive={:?}", pending.limit, &pending.url, is_audio, archive_opt.is_some());
+        let req = playlist_request_opts(
+            &task_id, &pending.url, &out_dir, pending.limit, is_audio, archive_opt.as_deref(), pending.chat_id,
+            Some(prefs.audio_format.as_str()),
+        );
+        (pending.url.clone(), "playlist", req)
+    };
+
+    let priority = state.task_priority(pending.chat_id, ipc_action);
+    state.task_queue.enqueue(&task_id, pending.chat_id, ipc_action, priority).await;
+
+    if let Some(pool) = &state.db_pool {
+        let db_kind = if is_single { "youtube_dl" } else { "playlist" };
+        let _ = hermes_shared::db::create_task(
+            pool, &task_id, pending.chat_id, db_kind, &url, Some(mode_label), priority,
+        ).await;
+    }
+
+    let dl_mode    = if is_audio { DownloadMode::Audio } else { DownloadMode::Video };
+    let kind_label = if is_single { "video" } else { "playlist" };
+
+    // Delete old message, send a fresh status message
+    let _ = bot.delete_message(chat_id, msg_id).await;
+    let status_msg = bot.send_message(chat_id,
+        decorate(format!("Queued {} [{}]", kind_label, short_id))
+    ).await;
+
+    let track_msg_id = match status_msg {
+        Ok(ref m) => m.id,
+        Err(_)    => msg_id,
+    };
+
+    let bot2 = bot.clone();
+    let state2 = state.clone();
+    tokio::spawn(async move {
+        let _ = execute_download_and_send(
+            &bot2, chat_id, track_msg_id, &short_id,
+            kind_label, &task_id, &request, dl_mode, &state2,
+        ).await;
+    });
+    Ok(())
+}
+
+/// /playlist <url> - Preview and download playlist
+async fn cmd_playlist_preview(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+    video_only: bool,
+) -> ResponseResult<()> {
+    use hermes_shared::ipc_protocol::{playlist_preview_request, IPCResponse};
+
+    let url = url.trim().to_string();
+    if url.is_empty() {
+        let help = if video_only {
+            "🎬 *Download Playlist as Video*\n\nUsage: `/playlistv2 \\<url\\>`\n\nI'll show you a preview of the first few tracks, then you choose how many to download\\.\nAll tracks download as video \\(MP4\\)\\.\n\nExample:\n`/playlistv2 https://www.youtube.com/playlist?list=...`"
+        } else {
+            "🎵 *Download Playlist*\n\nUsage: `/playlist \\<url\\>`\n\nI'll show you a preview of the first few tracks, then you can choose:\n• How many tracks to download\n• Audio or video format\n\nExample:\n`/playlist https://www.youtube.com/playlist?list=...`"
+        };
+        bot.send_message(msg.chat.id, decorate_markdown(help))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    // Detect link type
+    if let Some(link) = crate::link_detector::detect_first_link(&url) {
+        // Accept both playlists and single videos
+        match link {
+            crate::link_detector::DetectedLink::YoutubePlaylist { .. } => {
+                // Proceed with playlist preview
+            }
+            crate::link_detector::DetectedLink::YoutubeVideo { .. }
+            | crate::link_detector::DetectedLink::YoutubeShort { .. }
+            | crate::link_detector::DetectedLink::YoutubeMusic { .. }
+            | crate::link_detector::DetectedLink::YoutubeClip { .. } => {
+                // For single videos: treat as single-item playlist and download directly
+                // Show format selection instead of preview
+                return cmd_download(bot, msg, link.url().to_string(), state).await;
+            }
+            _ => {
+                bot.send_message(msg.chat.id, decorate("❌ This is not a supported YouTube link.\n\n✓ Playlists\n✓ Videos\n✓ Shorts\n\nPlease check the URL and try again.")).await?;
+                return Ok(());
+            }
+        }
+    } else {
+        bot.send_message(msg.chat.id, decorate("❌ Could not detect a valid URL. Please check and try again.")).await?;
+        return Ok(());
+    }
 
-            // Build inline keyboard
-            let keyboard = build_quality_keyboard(&format_options, &mode, &key);
+    // Check if this is a Radio Mix (list=RD pattern)
+    // Radio Mixes are infinite and slow to preview, so skip to track selection
+    // Match list=RD as a URL parameter (preceded by ? or &), not as part of a video ID
+    let is_radio_mix = url.contains("?list=RD") || url.contains("&list=RD");
+    if is_radio_mix {
+        let key = format!("{:x}", chrono::Utc::now().timestamp_millis());
+        state.playlist_store.store(key.clone(), PlaylistPending {
+            url: url.to_string(),
+            chat_id: msg.chat.id.0,
+            message_id: msg.id,
+            is_single: false,
+            limit: Some(10),
+            video_only,
+            created_at: std::time::Instant::now(),
+        }).await;
 
-            // Store state for callback
-            let pending = PendingSelection {
-                chat_id: chat_id.0,
-                url: link.url().to_string(),
-                message_id: fetching_msg.id,
-                formats: format_options,
-                created_at: std::time::Instant::now(),
-                title: title.to_string(),
-            };
-            state.callback_store.store(key, pending).await;
+        // For Radio Mixes, go straight to track limit selection (skip preview)
+        let buttons = vec![
+            vec![
+                InlineKeyboardButton::callback(decorate("🎵 10 tracks"),  encode_playlist_limit(&key, 10)),
+                InlineKeyboardButton::callback(decorate("🎵 25 tracks"),  encode_playlist_limit(&key, 25)),
+            ],
+            vec![
+                InlineKeyboardButton::callback(decorate("🎵 50 tracks"),  encode_playlist_limit(&key, 50)),
+                InlineKeyboardButton::callback(decorate("🎵 All tracks"), encode_playlist_limit(&key, 0)),
+            ],
+        ];
+        bot.send_message(msg.chat.id, decorate_markdown("🎵 Radio Mix detected\n\n\\(Infinite playlist \\- skipping preview\\)\n\nHow many tracks to download?"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .reply_markup(InlineKeyboardMarkup::new(buttons))
+            .await?;
+        return Ok(());
+    }
 
-            // Update message with keyboard
-            let header = format!(
-                "Select {} quality:\n{} [{}]",
-                mode_label, title, duration_str
-            );
-            bot.edit_message_text(chat_id, fetching_msg.id, header)
-                .reply_markup(keyboard)
-                .await?;
+    let task_id = uuid::Uuid::new_v4().to_string();
+    let status = bot.send_message(msg.chat.id, decorate("🎵 Fetching playlist info...")).await?;
+
+    // Send preview request
+    let req = playlist_preview_request(&task_id, &url, 5);
+    let mut rx = match state.dispatcher.send(&req, Priority::Interactive).await {
+        Ok(rx) => rx,
+        Err(e) => {
+            bot.edit_message_text(msg.chat.id, status.id, decorate(format!(
+                "❌ Worker error: {}", user_errors::from_hermes(&e).render("playlist preview")
+            ))).await?;
+            return Ok(());
+        }
+    };
+
+    // Wait for response (with timeout)
+    match tokio::time::timeout(std::time::Duration::from_secs(30), rx.recv()).await {
+        Ok(Some(response)) => {
+            let resp: IPCResponse = response;
+            if resp.is_error() {
+                let err_msg = user_errors::from_ipc_response(&resp).render("playlist preview");
+                bot.edit_message_text(msg.chat.id, status.id, decorate(format!("❌ Error: {}", err_msg))).await?;
+                return Ok(());
+            }
+
+            if resp.is_done() {
+                // Parse response data
+                if let Some(data) = resp.data.as_object() {
+                    let title = data.get("playlist_title")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("Playlist");
+                    let count = data.get("playlist_count")
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(0);
+                    let empty_vec = Vec::new();
+                    let tracks = data.get("tracks")
+                        .and_then(|v| v.as_array())
+                        .unwrap_or(&empty_vec);
+
+                    // Format message
+                    let safe_title = escape_markdown_v2(title);
+                    let mut msg_text = format!("🎵 **{}**\n\n", safe_title);
+
+                    // Show track count or note if unknown (infinite playlists)
+                    if count > 0 {
+                        msg_text.push_str(&format!("📊 {} tracks total\n\n", count));
+                    } else {
+                        msg_text.push_str("📊 Total tracks: Unknown \\(infinite or uncountable playlist\\)\n\n");
+                    }
+
+                    // Show first few tracks
+                    msg_text.push_str("**Preview \\(first tracks\\):**\n");
+                    for track in tracks.iter().take(5) {
+                        if let Some(track_obj) = track.as_object() {
+                            if let (Some(idx), Some(track_title)) = (
+                                track_obj.get("index").and_then(|v| v.as_u64()),
+                                track_obj.get("title").and_then(|v| v.as_str()),
+                            ) {
+                                let safe_track_title = escape_markdown_v2(track_title);
+                                msg_text.push_str(&format!("{}\\. {}\n", idx, safe_track_title));
+                            }
+                        }
+                    }
+
+                    if tracks.len() > 5 {
+                        if count > 5 {
+                            msg_text.push_str(&format!("\n\\.\\.\\. and {} more\n", count - 5));
+                        } else {
+                            msg_text.push_str("\n\\.\\.\\. and more available\n");
+                        }
+                    } else {
+                        msg_text.push('\n');
+                    }
+
+                    msg_text.push_str("\n**Choose how many tracks to download:**");
+
+                    // Update message with preview + button
+                    // Encode video_only flag: "pl_dl:v:URL" for video-only, "pl_dl:a:URL" for normal
+                    let dl_flag = if video_only { "v" } else { "a" };
+                    let keyboard = InlineKeyboardMarkup::new(vec![
+                        vec![InlineKeyboardButton::callback(decorate("⬇️ Download"), format!("pl_dl:{}:{}", dl_flag, url))],
+                    ]);
+
+                    bot.edit_message_text(msg.chat.id, status.id, decorate_markdown(msg_text))
+                        .parse_mode(ParseMode::MarkdownV2)
+                        .reply_markup(keyboard)
+                        .await?;
+                } else {
+                    bot.edit_message_text(msg.chat.id, status.id, "Could not parse playlist info").await?;
+                }
+            }
         }
+        Ok(None) => {
+            bot.edit_message_text(msg.chat.id, status.id, "Worker disconnected unexpectedly").await?;
+        }
+        Err(_) => {
+            bot.edit_message_text(msg.chat.id, status.id, "Request timed out").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// /search <query> - Search YouTube
+async fn cmd_search(
+    bot: Bot,
+    msg: Message,
+    query: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let (query, filters) = match parse_search_query(&query) {
+        Ok(parsed) => parsed,
         Err(e) => {
-            error!("Get formats IPC failed: {}", e);
-            bot.edit_message_text(chat_id, fetching_msg.id, format!(
-                "Error fetching formats: {}", e
-            )).await?;
+            bot.send_message(msg.chat.id, decorate(format!("⚠️ {}", e))).await?;
+            return Ok(());
+        }
+    };
+    if query.is_empty() {
+        bot.send_message(msg.chat.id, decorate_markdown(
+            "🔍 *Search YouTube*\n\nUsage: `/search <query>`\n\nExample:\n`/search billie eilish`\n\n\
+             Filters: `duration:short|medium|long` `date:hour|today|week|month|year` `type:video|playlist`\n\
+             `/search lofi beats duration:long date:week`"
+        ))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    let task_id = Uuid::new_v4().to_string();
+    let request = search_request(&task_id, &query, SEARCH_PAGE_SIZE as u32, 0, &filters);
+
+    let filter_note = if filters.is_empty() { String::new() } else { format!(" ({})", filters.summary()) };
+    let searching_msg = bot.send_message(msg.chat.id, decorate(format!(
+        "🔍 Searching for: {}{}\n⏳ Please wait...",
+        query, filter_note
+    )))
+        .await?;
+
+    match state.dispatcher.send_and_wait(&request, 30).await {
+        Ok(response) => {
+            if response.is_error() {
+                let err = user_errors::from_ipc_response(&response).render("search");
+                bot.edit_message_text(msg.chat.id, searching_msg.id, decorate_markdown(format!(
+                    "❌ *Search Error*\n\n{}", escape_markdown_v2(&err)
+                )))
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await?;
+            } else {
+                let results = parse_search_results(&response.data);
+
+                if results.is_empty() {
+                    bot.edit_message_text(msg.chat.id, searching_msg.id,
+                        decorate(format!("😕 No results found for \"{}\"", query))
+                    ).await?;
+                } else {
+                    // Store for callback retrieval (peek — buttons stay active)
+                    let key: String = task_id[..6].to_string();
+                    let pending = SearchPending {
+                        query: query.clone(),
+                        filters,
+                        results,
+                        has_more: response.data.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false),
+                        created_at: std::time::Instant::now(),
+                    };
+                    state.search_store.store(key.clone(), pending.clone()).await;
+
+                    let from_cache = response.data.get("from_cache")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false);
+                    let (text, keyboard) = search_results_view(&key, &pending, 0, from_cache);
+                    bot.edit_message_text(msg.chat.id, searching_msg.id, decorate(text))
+                        .reply_markup(keyboard)
+                        .await?;
+                }
+            }
+        }
+        Err(e) => {
+            error!("Search IPC failed: {}", e);
+            bot.edit_message_text(msg.chat.id, searching_msg.id, decorate(format!(
+                "Search error: {}", e
+            ))).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Results per /search page.
+const SEARCH_PAGE_SIZE: usize = 10;
+
+/// (url, title) of each result in a `search_results` response.
+fn parse_search_results(data: &serde_json::Value) -> Vec<SearchResultItem> {
+    data.get("results")
+        .and_then(|v| v.as_array())
+        .map(|results| results.iter().map(|r| SearchResultItem {
+            url:   r.get("url").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            title: r.get("title").and_then(|v| v.as_str()).unwrap_or("?").to_string(),
+        }).collect())
+        .unwrap_or_default()
+}
+
+/// Header and keyboard for the page of search results starting at `offset`:
+/// one button per result (truncated to 52 chars), then Back / More.
+fn search_results_view(
+    key: &str,
+    pending: &SearchPending,
+    offset: usize,
+    from_cache: bool,
+) -> (String, InlineKeyboardMarkup) {
+    let end = (offset + SEARCH_PAGE_SIZE).min(pending.results.len());
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = pending.results[offset..end]
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let label: String = if result.title.chars().count() > 52 {
+                format!("{}…", result.title.chars().take(51).collect::<String>())
+            } else {
+                result.title.clone()
+            };
+            vec![InlineKeyboardButton::callback(decorate(label), encode_search_callback(key, offset + i))]
+        })
+        .collect();
+
+    let mut nav = Vec::new();
+    if offset > 0 {
+        nav.push(InlineKeyboardButton::callback(
+            decorate("⬅️ Back"),
+            encode_search_page_callback(key, offset.saturating_sub(SEARCH_PAGE_SIZE)),
+        ));
+    }
+    if end < pending.results.len() || pending.has_more {
+        nav.push(InlineKeyboardButton::callback(decorate("➡️ More"), encode_search_page_callback(key, end)));
+    }
+    let paged = !nav.is_empty();
+    if paged {
+        rows.push(nav);
+    }
+
+    let filter_note = if pending.filters.is_empty() { String::new() } else { format!(" · {}", pending.filters.summary()) };
+    let cache_note = if from_cache { " · cached" } else { "" };
+    let page_note = if paged { format!(" · results {}–{}", offset + 1, end) } else { String::new() };
+    let text = format!(
+        "Search: \"{}\"{}{}{}  —  tap to download:",
+        pending.query, filter_note, cache_note, page_note
+    );
+    (text, InlineKeyboardMarkup::new(rows))
+}
+
+/// Handle a search "More" / "Back" button: show the page at `offset`,
+/// fetching it from the worker first if it hasn't been loaded yet.
+async fn handle_search_page(
+    bot: &Bot,
+    q: &CallbackQuery,
+    key: &str,
+    offset: usize,
+    state: &Arc<AppState>,
+) -> ResponseResult<()> {
+    let Some(message) = q.message.as_ref() else { return Ok(()) };
+    let Some(mut pending) = state.search_store.peek(key).await else {
+        let _ = bot.edit_message_reply_markup(message.chat.id, message.id).await;
+        return Ok(());
+    };
+
+    let mut from_cache = false;
+    if offset >= pending.results.len() {
+        if !pending.has_more || offset != pending.results.len() {
+            return Ok(());
+        }
+        let task_id = Uuid::new_v4().to_string();
+        let request = search_request(&task_id, &pending.query, SEARCH_PAGE_SIZE as u32, offset as u32, &pending.filters);
+        let response = match state.dispatcher.send_and_wait(&request, 30).await {
+            Ok(r) if !r.is_error() => r,
+            Ok(r) => {
+                let err = user_errors::from_ipc_response(&r).render("search");
+                let _ = bot.send_message(message.chat.id, decorate(format!("❌ Couldn't load more results: {}", err))).await;
+                return Ok(());
+            }
+            Err(e) => {
+                error!("Search page IPC failed: {}", e);
+                let err = user_errors::from_hermes(&e).render("search");
+                let _ = bot.send_message(message.chat.id, decorate(format!("❌ Couldn't load more results: {}", err))).await;
+                return Ok(());
+            }
+        };
+        let results = parse_search_results(&response.data);
+        let has_more = !results.is_empty()
+            && response.data.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false);
+        from_cache = response.data.get("from_cache").and_then(|v| v.as_bool()).unwrap_or(false);
+        let Some(updated) = state.search_store.append(key, offset, results, has_more).await else {
+            return Ok(());
+        };
+        pending = updated;
+        if offset >= pending.results.len() {
+            // Nothing further after all: drop the More button from the page shown
+            let (_, keyboard) = search_results_view(key, &pending, offset.saturating_sub(SEARCH_PAGE_SIZE), false);
+            let _ = bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await;
+            return Ok(());
         }
     }
 
+    let (text, keyboard) = search_results_view(key, &pending, offset, from_cache);
+    let _ = bot.edit_message_text(message.chat.id, message.id, decorate(text))
+        .reply_markup(keyboard)
+        .await;
+    Ok(())
+}
+
+/// Results shown for an inline query.
+const INLINE_SEARCH_LIMIT: u32 = 8;
+/// Telegram drops answers to inline queries after about 10 seconds.
+const INLINE_SEARCH_TIMEOUT_SECS: u64 = 9;
+
+/// `@bot <query>` in any chat: search YouTube and offer the results inline.
+///
+/// Each result's id is the YouTube video id, so the choice (see
+/// [`handle_chosen_inline_result`]) can be downloaded without keeping state.
+pub async fn handle_inline_query(
+    bot: Bot,
+    q: InlineQuery,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let query = q.query.trim();
+    if query.chars().count() < 2 || !state.is_user_allowed(q.from.id.0 as i64).await {
+        bot.answer_inline_query(&q.id, Vec::<InlineQueryResult>::new()).await?;
+        return Ok(());
+    }
+
+    let task_id = Uuid::new_v4().to_string();
+    let request = search_request(&task_id, query, INLINE_SEARCH_LIMIT, 0, &SearchFilters::default());
+    let results = match state.dispatcher.send_and_wait(&request, INLINE_SEARCH_TIMEOUT_SECS).await {
+        Ok(response) if !response.is_error() => response.data.get("results")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default(),
+        Ok(response) => {
+            warn!("Inline search failed for {:?}: {:?}", query, response.error_message());
+            Vec::new()
+        }
+        Err(e) => {
+            warn!("Inline search failed for {:?}: {}", query, e);
+            Vec::new()
+        }
+    };
+
+    let articles: Vec<InlineQueryResult> = results.iter().filter_map(|r| {
+        let field = |key: &str| r.get(key).and_then(|v| v.as_str()).unwrap_or("");
+        let (video_id, url) = (field("videoId"), field("url"));
+        if video_id.is_empty() || url.is_empty() {
+            return None;
+        }
+        let title = field("title");
+        let text = InputMessageContentText::new(decorate(format!("🎵 {}\n{}", title, url)));
+        let mut article = InlineQueryResultArticle::new(video_id, title, InputMessageContent::Text(text))
+            .description(format!("{} · {}", field("artist"), field("duration")));
+        if let Ok(thumb) = url::Url::parse(field("thumbnail")) {
+            article = article.thumb_url(thumb);
+        }
+        Some(InlineQueryResult::Article(article))
+    }).collect();
+
+    bot.answer_inline_query(&q.id, articles)
+        .is_personal(true)
+        .cache_time(300)
+        .await?;
     Ok(())
 }
 
-/// Build inline keyboard for format selection.
-fn build_quality_keyboard(
-    formats: &[FormatOption],
-    mode: &DownloadMode,
-    key: &str,
-) -> InlineKeyboardMarkup {
-    let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+/// A user picked an inline search result: download it and deliver the file in
+/// their private chat with the bot. Needs inline feedback turned on for the
+/// bot in @BotFather (/setinlinefeedback), or these updates never arrive.
+pub async fn handle_chosen_inline_result(
+    bot: Bot,
+    chosen: ChosenInlineResult,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let chat_id = ChatId::from(chosen.from.id);
+    let video_id = chosen.result_id.as_str();
+    let valid = video_id.len() == 11
+        && video_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid || !state.is_user_allowed(chat_id.0).await {
+        return Ok(());
+    }
+    let url = format!("https://www.youtube.com/watch?v={}", video_id);
+    info!("Inline result chosen by {}: {}", chat_id, url);
+    // Also tells the user, in the private chat, why nothing is coming
+    if quota_exceeded(&bot, chat_id, &state).await? {
+        return Ok(());
+    }
+
+    let prefs = load_user_prefs(&state, chat_id.0).await;
+    let extract_audio = prefs.default_mode == "audio";
+    let dl_mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
+    let mode_label = if extract_audio { "audio" } else { "video" };
 
-    if *mode == DownloadMode::Video {
-        // Video: 2 buttons per row
-        for chunk in formats.chunks(2) {
-            let row: Vec<InlineKeyboardButton> = chunk
-                .iter()
-                .enumerate()
-                .map(|(i, f)| {
-                    let idx = formats.iter().position(|x| x.format_id == f.format_id && x.label == f.label).unwrap_or(i);
-                    InlineKeyboardButton::callback(
-                        &f.label,
-                        encode_callback(mode, key, idx),
-                    )
-                })
-                .collect();
-            rows.push(row);
-        }
-    } else {
-        // Audio: 1 button per row
-        for (i, f) in formats.iter().enumerate() {
-            rows.push(vec![
-                InlineKeyboardButton::callback(
-                    &f.label,
-                    encode_callback(mode, key, i),
-                )
-            ]);
+    // Fails when the user never opened a private chat with the bot
+    let status_msg = match bot.send_message(chat_id, decorate(format!(
+        "⏳ Inline pick queued ({})\n\nSource:\n{}", mode_label, url
+    ))).await {
+        Ok(m) => m,
+        Err(e) => {
+            warn!("Can't reach {} for an inline download (bot not started?): {}", chat_id, e);
+            return Ok(());
         }
+    };
+
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let priority = state.task_priority(chat_id.0, "youtube_dl");
+    state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label), priority).await;
     }
 
-    // Cancel button
-    rows.push(vec![
-        InlineKeyboardButton::callback("Cancel", encode_cancel(key))
-    ]);
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(extract_audio)), chat_id.0, &task_id);
+    let request = download_request_prefs(
+        &task_id, &url, extract_audio,
+        &prefs,
+        &out_dir, chat_id.0,
+    );
 
-    InlineKeyboardMarkup::new(rows)
+    tokio::spawn(async move {
+        let _ = execute_download_and_send(
+            &bot,
+            chat_id,
+            status_msg.id,
+            &short_id,
+            mode_label,
+            &task_id,
+            &request,
+            dl_mode,
+            &state,
+        ).await;
+    });
+    Ok(())
 }
 
-/// Handle callback query from inline keyboard button press.
-pub async fn handle_callback_query(
+/// /status - Show active task status
+async fn cmd_status(
     bot: Bot,
-    q: CallbackQuery,
+    msg: Message,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let data = match q.data {
-        Some(ref d) => d.clone(),
-        None => return Ok(()),
-    };
+    let stats = state.task_queue.stats().await;
+    let user_tasks = state.task_queue.get_user_tasks(msg.chat.id.0).await;
 
-    // Handle search format selection (4-part: sf:key:index:a/v) — must run before decode_callback
-    if data.starts_with("sf:") {
-        let _ = bot.answer_callback_query(&q.id).await;
-        let parts: Vec<&str> = data.splitn(4, ':').collect();
-        let sf_key   = parts.get(1).copied().unwrap_or("");
-        let sf_idx: usize = parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(usize::MAX);
-        let is_audio = parts.get(3).copied().unwrap_or("a") == "a";
+    let mut text = format!(
+        "Queue Status:\n\
+         Running: {}/{}\n\
+         Queued: {}\n\
+         Completed: {}\n\
+         Failed: {}\n",
+        stats.running, stats.max_concurrent,
+        stats.queued, stats.completed, stats.failed,
+    );
 
-        let pending = match state.search_store.peek(sf_key).await {
-            Some(p) => p,
-            None    => return Ok(()),
+    if !user_tasks.is_empty() {
+        let tz = match &state.db_pool {
+            Some(pool) => hermes_shared::db::get_user_setting_or_default(pool, msg.chat.id.0, "timezone").await,
+            None => "UTC".to_string(),
         };
-        if sf_idx >= pending.results.len() { return Ok(()); }
+        let tz = hermes_shared::user_settings::timezone(&tz);
+        text.push_str("\nYour tasks:\n");
+        for task in user_tasks.iter().take(10) {
+            let bar = progress_bar(task.progress);
+            text.push_str(&format!(
+                "  {} {:?} ({}) {} {}%",
+                &task.task_id[..8], task.status, task.priority, bar, task.progress
+            ));
+            if let Some(started) = task.started_at {
+                text.push_str(&format!(" (started {})", started.with_timezone(&tz).format("%H:%M %Z")));
+            }
+            if let Some(wait) = state.task_queue.estimated_start_secs(&task.task_id).await {
+                text.push_str(&format!(" (est. start in {})", format_wait(wait)));
+            }
+            text.push('\n');
+        }
+    } else {
+        text.push_str("\nNo active tasks.");
+    }
 
-        let result   = &pending.results[sf_idx];
-        let url      = result.url.clone();
-        let chat_id  = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
-        let msg_id   = match q.message { Some(ref m) => m.id,      None => return Ok(()) };
+    bot.send_message(msg.chat.id, decorate(text)).await?;
+    Ok(())
+}
 
-        let task_id  = Uuid::new_v4().to_string();
-        let short_id = task_id[..8].to_string();
-        let mode_label = if is_audio { "audio" } else { "video" };
+/// Failed downloads shown by /failed.
+const FAILED_LIST_LIMIT: usize = 10;
 
-        state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl").await;
+/// /failed - The user's recent failed downloads with the reason for each,
+/// and Retry buttons for the ones that can be re-queued.
+async fn cmd_failed(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, "Task history is unavailable (no database).").await?;
+        return Ok(());
+    };
+    let (text, keyboard) = render_failed_list(pool, msg.chat.id.0).await;
+    bot.send_message(msg.chat.id, decorate(text)).reply_markup(keyboard).await?;
+    Ok(())
+}
 
-        if let Some(pool) = &state.db_pool {
-            let _ = hermes_shared::db::create_task(
-                pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label),
-            ).await;
-        }
+/// Recent failed tasks of a user, newest first.
+async fn recent_failed_tasks(pool: &SqlitePool, chat_id: i64) -> Vec<hermes_shared::models::Task> {
+    let mut tasks = hermes_shared::db::get_user_tasks_by_status(pool, chat_id, Some("error"))
+        .await
+        .unwrap_or_default();
+    tasks.truncate(FAILED_LIST_LIMIT);
+    tasks
+}
 
-        // Edit the format-choice message to show download status
-        let _ = bot.edit_message_text(chat_id, msg_id,
-            format!("Queued [{}] ({}) — {}", short_id, mode_label, url)
-        ).reply_markup(InlineKeyboardMarkup::new(Vec::<Vec<InlineKeyboardButton>>::new())).await;
+/// Only plain downloads go back through the web queue; other task types are
+/// re-run with their own command.
+fn is_requeueable(task: &hermes_shared::models::Task) -> bool {
+    task.task_type == "youtube_dl"
+}
 
-        let out_dir  = task_output_dir(&state.download_dir, chat_id.0, &task_id);
-        let dl_mode  = if is_audio { DownloadMode::Audio } else { DownloadMode::Video };
-        let prefs    = load_user_prefs(&state, chat_id.0).await;
-        let request  = download_request_prefs(
-            &task_id, &url, is_audio,
-            &prefs.audio_format, &prefs.audio_quality,
-            &out_dir, chat_id.0,
+/// The /failed message body and its Retry / Retry All buttons.
+async fn render_failed_list(pool: &SqlitePool, chat_id: i64) -> (String, InlineKeyboardMarkup) {
+    let tasks = recent_failed_tasks(pool, chat_id).await;
+    if tasks.is_empty() {
+        return (
+            "✅ No failed downloads.".to_string(),
+            InlineKeyboardMarkup::new(Vec::<Vec<InlineKeyboardButton>>::new()),
         );
+    }
+    let tz = hermes_shared::db::get_user_setting_or_default(pool, chat_id, "timezone").await;
+    let tz = hermes_shared::user_settings::timezone(&tz);
+
+    let mut text = format!("❌ Recent failed downloads ({}):\n", tasks.len());
+    let mut rows = Vec::new();
+    for task in &tasks {
+        let short_id = &task.id[..8.min(task.id.len())];
+        let reason: String = task.error_msg.as_deref().unwrap_or("Unknown error").chars().take(120).collect();
+        text.push_str(&format!(
+            "\n[{}] {} · {}\n{}\nReason: {}\n",
+            short_id,
+            task.label.as_deref().unwrap_or(&task.task_type),
+            task.created_at.and_utc().with_timezone(&tz).format("%b %d %H:%M"),
+            task.url,
+            reason,
+        ));
+        if is_requeueable(task) {
+            rows.push(vec![InlineKeyboardButton::callback(
+                decorate(format!("🔁 Retry [{}]", short_id)),
+                format!("rf:{}", task.id),
+            )]);
+        } else if task.task_type == "direct" {
+            text.push_str("(send the link again)\n");
+        } else {
+            text.push_str(&format!("(re-run it with /{})\n", task.task_type));
+        }
+    }
+    if rows.len() > 1 {
+        rows.push(vec![InlineKeyboardButton::callback(decorate("🔁 Retry All"), "rf:all")]);
+    }
+    (text, InlineKeyboardMarkup::new(rows))
+}
 
-        let state2 = state.clone();
-        tokio::spawn(async move {
-            let _ = execute_download_and_send(
-                &bot,
-                chat_id,
-                msg_id,
-                &short_id,
-                mode_label,
-                &task_id,
-                &request,
-                dl_mode,
-                &state2,
-            ).await;
-        });
+/// Handle a /failed button: `rf:<task_id>` or `rf:all`. Retried tasks go back
+/// to the web queue (same task id), which downloads and delivers them.
+async fn handle_retry_failed(bot: &Bot, q: &CallbackQuery, arg: &str, state: &AppState) -> ResponseResult<()> {
+    let Some(message) = q.message.as_ref() else {
+        let _ = bot.answer_callback_query(&q.id).await;
+        return Ok(());
+    };
+    let Some(pool) = &state.db_pool else {
+        let _ = bot.answer_callback_query(&q.id).await;
         return Ok(());
+    };
+    let chat_id = message.chat.id;
+
+    let targets: Vec<String> = if arg == "all" {
+        recent_failed_tasks(pool, chat_id.0).await
+            .into_iter()
+            .filter(is_requeueable)
+            .map(|t| t.id)
+            .collect()
+    } else {
+        // Only the owner's own failed downloads can be re-queued
+        match hermes_shared::db::get_task_by_id(pool, arg).await.ok().flatten() {
+            Some(task) if task.chat_id == chat_id.0 && task.status == "error" && is_requeueable(&task) => vec![task.id],
+            _ => Vec::new(),
+        }
+    };
+
+    let mut requeued = 0;
+    for task_id in &targets {
+        match hermes_shared::db::retry_task(pool, task_id).await {
+            Ok(true) => requeued += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Failed to re-queue {}: {}", task_id, e),
+        }
     }
+    info!("Re-queued {} failed task(s) for chat {}", requeued, chat_id.0);
+    let notice = match requeued {
+        0 => "Nothing to retry (already retried?)".to_string(),
+        1 => "Re-queued 1 download".to_string(),
+        n => format!("Re-queued {} downloads", n),
+    };
+    let _ = bot.answer_callback_query(&q.id).text(notice).await;
 
-    // Handle playlist confirm (pc:KEY:[p/s/x]) — before decode_callback
-    if data.starts_with("pc:") {
-        let _ = bot.answer_callback_query(&q.id).await;
-        let parts: Vec<&str> = data.splitn(3, ':').collect();
-        let pc_key    = parts.get(1).copied().unwrap_or("");
-        let pc_choice = parts.get(2).copied().unwrap_or("x");
+    let (text, keyboard) = render_failed_list(pool, chat_id.0).await;
+    let _ = bot.edit_message_text(chat_id, message.id, decorate(text)).reply_markup(keyboard).await;
+    Ok(())
+}
 
-        let pending = match state.playlist_store.get(pc_key).await {
-            Some(p) => p,
-            None    => return Ok(()),
-        };
-        let chat_id = ChatId(pending.chat_id);
-        let msg_id  = pending.message_id;
+/// /cancel <task_id> - Cancel a running task
+async fn cmd_cancel(
+    bot: Bot,
+    msg: Message,
+    task_id_prefix: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let prefix = task_id_prefix.trim().to_string();
+    if prefix.is_empty() {
+        bot.send_message(msg.chat.id, decorate_markdown("❌ *Cancel Download*\n\nUsage: `/cancel <task-id>`\n\nGet task IDs using `/status`"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
 
-        if pc_choice == "x" {
-            state.playlist_store.take(pc_key).await;
-            let _ = bot.edit_message_text(chat_id, msg_id, "Cancelled.").await;
-            return Ok(());
+    // Find matching task
+    let user_tasks = state.task_queue.get_user_tasks(msg.chat.id.0).await;
+    let text = match resolve_task_prefix(user_tasks, &prefix, |t| t.task_id.as_str()) {
+        PrefixMatch::Unique(task) => {
+            let full_id = task.task_id;
+            stop_task(&state, &full_id).await;
+            format!("Cancelled task [{}]", &full_id[..8])
         }
-        if pc_choice == "s" {
-            state.playlist_store.set_single(pc_key, true).await;
-            // Show format selection for both /playlist and /playlistv2
-            let buttons = vec![vec![
-                InlineKeyboardButton::callback("🎵 Audio (MP3)", encode_playlist_format(pc_key, true)),
-                InlineKeyboardButton::callback("🎬 Video (MP4)", encode_playlist_format(pc_key, false)),
-            ]];
-            let _ = bot.edit_message_text(chat_id, msg_id, "Choose format for this video:")
-                .reply_markup(InlineKeyboardMarkup::new(buttons))
-                .await;
-            return Ok(());
+        PrefixMatch::Ambiguous(tasks) => {
+            // The queue only tracks ids; look the URLs up so the user can tell them apart
+            let mut candidates = Vec::with_capacity(tasks.len());
+            for task in tasks {
+                let url = match &state.db_pool {
+                    Some(pool) => hermes_shared::db::get_task_by_id(pool, &task.task_id).await
+                        .ok().flatten().map(|t| t.url),
+                    None => None,
+                };
+                candidates.push((task.task_id, url));
+            }
+            task_prefix::ambiguous_text(&prefix, &candidates)
         }
-        // pc_choice == "p" — show limit selection
-        state.playlist_store.set_single(pc_key, false).await;
-        let buttons = vec![
-            vec![
-                InlineKeyboardButton::callback("10 tracks",  encode_playlist_limit(pc_key, 10)),
-                InlineKeyboardButton::callback("25 tracks",  encode_playlist_limit(pc_key, 25)),
-            ],
-            vec![
-                InlineKeyboardButton::callback("50 tracks",  encode_playlist_limit(pc_key, 50)),
-                InlineKeyboardButton::callback("All tracks", encode_playlist_limit(pc_key, 0)),
-            ],
-        ];
-        let _ = bot.edit_message_text(chat_id, msg_id, "How many tracks to download?")
-            .reply_markup(InlineKeyboardMarkup::new(buttons))
-            .await;
-        return Ok(());
-    }
+        PrefixMatch::NotFound { closest } => format!(
+            "{}\nUse /status to see task IDs.",
+            task_prefix::not_found_text(&prefix, closest.as_ref().map(|t| t.task_id.as_str()))
+        ),
+    };
+    bot.send_message(msg.chat.id, decorate(text)).await?;
 
-    // Handle playlist limit (pl:KEY:N) — before decode_callback
-    if data.starts_with("pl:") {
-        info!("Playlist limit callback received: {}", data);
-        let _ = bot.answer_callback_query(&q.id).await;
-        let parts: Vec<&str> = data.splitn(3, ':').collect();
-        let pl_key    = parts.get(1).copied().unwrap_or("");
-        let pl_limit: u32 = parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(0);
+    Ok(())
+}
 
-        info!("Parsed: key={}, limit={}", pl_key, pl_limit);
+/// /schedule <time> <url> - Download later; the time is read in the user's timezone.
+/// /schedule lists pending scheduled downloads, /schedule cancel <id> drops one.
+async fn cmd_schedule(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+    let chat_id = msg.chat.id;
+    let tz = hermes_shared::db::get_user_setting_or_default(pool, chat_id.0, "timezone").await;
 
-        let limit_opt = if pl_limit == 0 { None } else { Some(pl_limit) };
-        state.playlist_store.set_limit(pl_key, limit_opt).await;
-        info!("Limit set in store");
+    let args = args.trim();
+    let (first, rest) = args
+        .split_once(char::is_whitespace)
+        .map_or((args, ""), |(first, rest)| (first, rest.trim()));
 
-        let pending = match state.playlist_store.get(pl_key).await {
-            Some(p) => {
-                info!("Found pending state: limit={:?}", p.limit);
-                p
+    if args.is_empty() {
+        let tasks = hermes_shared::db::get_scheduled_tasks(pool, chat_id.0).await.unwrap_or_default();
+        let mut text = String::from(
+            "Usage: /schedule <time> <url>\n\
+             Time: 22:00, +2h, +1h30m or 2024-05-01T22:00 (your /setting timezone).\n\
+             Cancel: /schedule cancel <id>\n"
+        );
+        if tasks.is_empty() {
+            text.push_str("\nNo scheduled downloads.");
+        } else {
+            text.push_str("\nScheduled:");
+            for task in &tasks {
+                let at = task.scheduled_at
+                    .map(|at| hermes_shared::user_settings::format_local(at, &tz))
+                    .unwrap_or_default();
+                text.push_str(&format!("\n[{}] {} — {}", &task.id[..8], at, task.url));
             }
-            None    => {
-                warn!("Playlist key not found in store: {}", pl_key);
-                return Ok(());
+        }
+        bot.send_message(chat_id, decorate(text)).await?;
+        return Ok(());
+    }
+
+    if first.eq_ignore_ascii_case("cancel") {
+        if rest.is_empty() {
+            bot.send_message(chat_id, "Usage: /schedule cancel <id>").await?;
+            return Ok(());
+        }
+        let tasks = hermes_shared::db::get_scheduled_tasks(pool, chat_id.0).await.unwrap_or_default();
+        let text = match resolve_task_prefix(tasks, rest, |t| t.id.as_str()) {
+            PrefixMatch::Unique(task) => match hermes_shared::db::cancel_task(pool, &task.id).await {
+                Ok(true) => format!("Cancelled scheduled download [{}]", &task.id[..8]),
+                _ => format!("[{}] has already started. Use /cancel to stop it.", &task.id[..8]),
+            },
+            PrefixMatch::Ambiguous(tasks) => {
+                let candidates: Vec<_> = tasks.into_iter().map(|t| (t.id, Some(t.url))).collect();
+                task_prefix::ambiguous_text(rest, &candidates)
             }
+            PrefixMatch::NotFound { closest } => format!(
+                "{}\nUse /schedule to see scheduled downloads.",
+                task_prefix::not_found_text(rest, closest.as_ref().map(|t| t.id.as_str()))
+            ),
         };
-        let chat_id = ChatId(pending.chat_id);
-        let msg_id  = pending.message_id;
-        info!("Edit parameters: chat_id={}, message_id={}", pending.chat_id, msg_id);
-        let limit_label = if pl_limit == 0 {
-            "all tracks".to_string()
-        } else {
-            format!("up to {} tracks", pl_limit)
-        };
+        bot.send_message(chat_id, decorate(text)).await?;
+        return Ok(());
+    }
 
-        // Show format selection for both /playlist and /playlistv2
-        let buttons = vec![vec![
-            InlineKeyboardButton::callback("🎵 Audio (MP3)", encode_playlist_format(pl_key, true)),
-            InlineKeyboardButton::callback("🎬 Video (MP4)", encode_playlist_format(pl_key, false)),
-        ]];
-        let format_msg_text = format!("Downloading {} — choose format:", limit_label);
-        let keyboard = InlineKeyboardMarkup::new(buttons);
+    if rest.is_empty() {
+        bot.send_message(chat_id, decorate("Usage: /schedule <time> <url>\nExample: /schedule 22:00 https://youtu.be/...")).await?;
+        return Ok(());
+    }
 
-        // Send new format selection message (replaces limit selection message)
-        match bot.send_message(chat_id, format_msg_text)
-            .reply_markup(keyboard)
-            .await
-        {
-            Ok(new_msg) => {
-                state.playlist_store.set_message_id(pl_key, new_msg.id).await;
-                let _ = bot.delete_message(chat_id, msg_id).await;
-                info!("Sent format selection message (replaced limit selection message)");
-            }
-            Err(e) => {
-                error!("Failed to send format selection message: {:?}", e);
-            }
+    let now = chrono::Utc::now();
+    let at = match hermes_shared::schedule::parse_start_time(first, now, hermes_shared::user_settings::timezone(&tz)) {
+        Ok(at) => at,
+        Err(e) => {
+            bot.send_message(chat_id, decorate(format!("❌ {}", e))).await?;
+            return Ok(());
+        }
+    };
+
+    // Scheduled tasks run through the web queue, which downloads single items with yt-dlp
+    let url = match link_detector::detect_first_link(rest) {
+        Some(l) if l.is_playlist() || l.is_telegram() || l.is_spotify() || l.is_feed() => {
+            bot.send_message(chat_id, decorate("❌ Only single videos and tracks can be scheduled.")).await?;
+            return Ok(());
+        }
+        Some(l) if l.is_supported() || l.direct_file_name().is_some() => l.url().to_string(),
+        Some(_) => {
+            bot.send_message(chat_id, decorate(UNSUPPORTED_LINK_TEXT)).await?;
+            return Ok(());
         }
+        None => {
+            bot.send_message(chat_id, decorate("❌ Could not detect a valid URL. Please check and try again.")).await?;
+            return Ok(());
+        }
+    };
+    // Checked now rather than when it runs: the scheduled row already counts
+    // against the quota, so a check at promotion would count it twice
+    if quota_exceeded(&bot, chat_id, &state).await? {
         return Ok(());
     }
 
-    // Handle playlist format (pf:KEY:[a/v]) — before decode_callback
-    if data.starts_with("pf:") {
-        let _ = bot.answer_callback_query(&q.id).await;
-        let parts: Vec<&str> = data.splitn(3, ':').collect();
-        let pf_key      = parts.get(1).copied().unwrap_or("");
-        let pf_is_audio = parts.get(2).copied().unwrap_or("a") == "a";
-
-        handle_playlist_format_download(&bot, &state, pf_key, pf_is_audio).await?;
+    let prefs = load_user_prefs(&state, chat_id.0).await;
+    let mode_label = if prefs.default_mode == "audio" { "audio" } else { "video" };
+    let task_id = Uuid::new_v4().to_string();
+    if let Err(e) = hermes_shared::db::create_scheduled_task(
+        pool, &task_id, chat_id.0, "youtube_dl", &url, Some(mode_label), at.naive_utc(),
+    ).await {
+        error!("Failed to schedule download: {}", e);
+        bot.send_message(chat_id, decorate("❌ Could not save the scheduled download.")).await?;
         return Ok(());
     }
 
-    // Handle playlist preview download (pl_dl:[a|v]:URL) — triggered from preview
-    if data.starts_with("pl_dl:") {
-        info!("Playlist preview download callback received");
-        let _ = bot.answer_callback_query(&q.id).await;
-        let after_prefix = &data[6..]; // After "pl_dl:"
+    info!("Scheduled {} for chat {} at {}", url, chat_id, at);
+    bot.send_message(chat_id, decorate(format!(
+        "🕒 Scheduled [{}] ({}) for {}\n{}\n\nCancel: /schedule cancel {}",
+        &task_id[..8], mode_label, hermes_shared::user_settings::format_local_utc(at, &tz), url, &task_id[..8]
+    ))).await?;
+    Ok(())
+}
 
-        // Parse video_only flag: "v:URL" or "a:URL", fall back to plain URL for compat
-        let (is_video_only, url) = if after_prefix.starts_with("v:") {
-            (true, &after_prefix[2..])
-        } else if after_prefix.starts_with("a:") {
-            (false, &after_prefix[2..])
-        } else {
-            (false, after_prefix) // Legacy: no flag prefix
-        };
+/// Stop a task everywhere: its queue slot, the handler waiting on it, and
+/// whatever the worker is running for it (yt-dlp/ffmpeg get killed).
+pub async fn stop_task(state: &AppState, task_id: &str) {
+    // Worker first, while the handler still holds the task's pending entry
+    // (that is how the pool finds the worker); the handler drops it on wake-up
+    if let Err(e) = state.dispatcher.cancel(task_id).await {
+        warn!("Could not send cancel for task {} to the worker: {}", task_id, e);
+    }
+    state.task_queue.cancel(task_id).await;
+}
 
-        let chat_id = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
-        let msg_id  = match q.message { Some(ref m) => m.id,      None => return Ok(()) };
-        info!("Callback query message: chat_id={}, message_id={}", chat_id, msg_id);
+/// Tell the worker to drop a task the bot has given up on (stalled or timed
+/// out); it runs one request at a time, so a hung one blocks the rest.
+async fn abandon_on_worker(state: &AppState, task_id: &str) {
+    if let Err(e) = state.dispatcher.cancel(task_id).await {
+        warn!("Could not stop abandoned task {} on the worker: {}", task_id, e);
+    }
+}
 
-        // Create a new playlist store entry
-        let key = format!("{:x}", chrono::Utc::now().timestamp_millis());
-        info!("Created playlist store key: {}", key);
-        state.playlist_store.store(key.clone(), PlaylistPending {
-            url: url.to_string(),
-            chat_id: chat_id.0,
-            message_id: msg_id,
-            is_single: false,
-            limit: Some(10),
-            video_only: is_video_only,
-            created_at: std::time::Instant::now(),
-        }).await;
-        info!("Stored playlist pending: chat_id={}, message_id={}, video_only={}", chat_id.0, msg_id, is_video_only);
+/// Downloads per /history page.
+const HISTORY_PAGE_SIZE: usize = 5;
 
-        // Show track limit selection
-        let buttons = vec![
-            vec![
-                InlineKeyboardButton::callback("🎵 10 tracks",  encode_playlist_limit(&key, 10)),
-                InlineKeyboardButton::callback("🎵 25 tracks",  encode_playlist_limit(&key, 25)),
-            ],
-            vec![
-                InlineKeyboardButton::callback("🎵 50 tracks",  encode_playlist_limit(&key, 50)),
-                InlineKeyboardButton::callback("🎵 All tracks", encode_playlist_limit(&key, 0)),
-            ],
-        ];
-        let edit_result = bot.edit_message_text(chat_id, msg_id, "How many tracks to download?")
-            .reply_markup(InlineKeyboardMarkup::new(buttons))
-            .await;
+/// /history - Finished and failed downloads, newest first, with paging and
+/// re-download buttons
+async fn cmd_history(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, "Task history is unavailable (no database).").await?;
+        return Ok(());
+    };
+    let (text, keyboard) = render_history_page(pool, msg.chat.id.0, 0).await;
+    bot.send_message(msg.chat.id, decorate(text)).reply_markup(keyboard).await?;
+    Ok(())
+}
 
-        match edit_result {
-            Ok(_) => info!("Successfully showed playlist limit selection"),
-            Err(e) => error!("Failed to show playlist limit selection: {}", e),
+/// One /history page (0-based, clamped to the last page) and its buttons.
+async fn render_history_page(pool: &SqlitePool, chat_id: i64, page: usize) -> (String, InlineKeyboardMarkup) {
+    let page_size = HISTORY_PAGE_SIZE as i64;
+    let fetch = |page: usize| hermes_shared::db::get_user_history_page(pool, chat_id, page_size, page as i64 * page_size);
+    let (mut tasks, total) = fetch(page).await.unwrap_or_else(|e| {
+        warn!("History lookup failed for {}: {}", chat_id, e);
+        (Vec::new(), 0)
+    });
+    if total == 0 {
+        return (
+            "📜 No finished downloads yet.".to_string(),
+            InlineKeyboardMarkup::new(Vec::<Vec<InlineKeyboardButton>>::new()),
+        );
+    }
+    let pages = ((total + page_size - 1) / page_size) as usize;
+    // History shrank since the buttons were drawn: show the last page instead
+    let page = page.min(pages - 1);
+    if tasks.is_empty() {
+        tasks = fetch(page).await.map(|(tasks, _)| tasks).unwrap_or_default();
+    }
+
+    let tz = hermes_shared::db::get_user_setting_or_default(pool, chat_id, "timezone").await;
+    let tz = hermes_shared::user_settings::timezone(&tz);
+
+    let mut text = format!("📜 Download history — page {}/{} ({} total)\n", page + 1, pages, total);
+    let mut rows = Vec::new();
+    for task in &tasks {
+        let short_id = &task.id[..8.min(task.id.len())];
+        let icon = if task.status == "done" { "✅" } else { "❌" };
+        let name = task.file_path.as_deref()
+            .and_then(|p| std::path::Path::new(p).file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or(&task.url);
+        text.push_str(&format!(
+            "\n{} [{}] {} · {}\n{}\n",
+            icon,
+            short_id,
+            task.label.as_deref().unwrap_or(&task.task_type),
+            task.finished_at.unwrap_or(task.created_at).and_utc().with_timezone(&tz).format("%b %d %H:%M"),
+            name,
+        ));
+        if task.status == "error" {
+            let reason: String = task.error_msg.as_deref().unwrap_or("Unknown error").chars().take(80).collect();
+            text.push_str(&format!("Reason: {}\n", reason));
         }
-        return Ok(());
+        rows.push(vec![InlineKeyboardButton::callback(
+            decorate(format!("⬇️ Re-download [{}]", short_id)),
+            encode_history_redownload(&task.id),
+        )]);
     }
 
-    let (mode_prefix, key, index) = match decode_callback(&data) {
-        Some(decoded) => decoded,
-        None => {
-            if let Some(id) = q.id.as_str().into() {
-                let _ = bot.answer_callback_query(id).await;
+    let mut nav = Vec::new();
+    if page > 0 {
+        nav.push(InlineKeyboardButton::callback(decorate("◀️ Previous"), encode_history_page(page - 1)));
+    }
+    if page + 1 < pages {
+        nav.push(InlineKeyboardButton::callback(decorate("Next ▶️"), encode_history_page(page + 1)));
+    }
+    if !nav.is_empty() {
+        rows.push(nav);
+    }
+    (text, InlineKeyboardMarkup::new(rows))
+}
+
+/// Handle a /history button: turn the page in place, or download a past
+/// task's URL again as if the user had sent it.
+async fn handle_history_callback(
+    bot: Bot,
+    q: &CallbackQuery,
+    action: HistoryAction,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let _ = bot.answer_callback_query(&q.id).await;
+    let (Some(message), Some(pool)) = (q.message.clone(), state.db_pool.clone()) else {
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+
+    match action {
+        HistoryAction::Page(page) => {
+            let (text, keyboard) = render_history_page(&pool, chat_id.0, page).await;
+            let _ = bot.edit_message_text(chat_id, message.id, decorate(text)).reply_markup(keyboard).await;
+            Ok(())
+        }
+        HistoryAction::Redownload(task_id) => {
+            // Only the owner's own tasks
+            match hermes_shared::db::get_task_by_id(&pool, &task_id).await.ok().flatten() {
+                Some(task) if task.chat_id == chat_id.0 => {
+                    info!("Re-download of {} from history for chat {}", task_id, chat_id);
+                    download_url(bot, message, task.url, state, false, None).await
+                }
+                _ => Ok(()),
             }
-            return Ok(());
         }
-    };
+    }
+}
 
-    // Answer the callback query immediately to stop the loading spinner
-    let _ = bot.answer_callback_query(&q.id).await;
+/// /ping - Health check
+async fn cmd_ping(
+    bot: Bot,
+    msg: Message,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let task_id = Uuid::new_v4().to_string();
+    let request = health_check_request(&task_id);
 
-    // Handle cancel
-    if mode_prefix == "cx" {
-        if let Some(pending) = state.callback_store.take(&key).await {
-            let chat_id = ChatId(pending.chat_id);
-            let _ = bot.edit_message_text(chat_id, pending.message_id, "Cancelled.").await;
+    match state.dispatcher.send_and_wait(&request, 10).await {
+        Ok(response) => {
+            let version = response.data.get("version")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            let handlers = response.data.get("handlers")
+                .and_then(|v| v.as_array())
+                .map(|a| a.len())
+                .unwrap_or(0);
+            let stats = state.task_queue.stats().await;
+            bot.send_message(msg.chat.id, decorate_markdown(format!(
+                "✅ *System Status*\n\n\
+                 🤖 Worker: `{}`\n\
+                 ⚙️ Handlers: `{}`\n\
+                 ⏳ Queue: `{}/{}` running\n\n✓ All systems operational",
+                escape_markdown_v2_code(version), handlers, stats.running, stats.max_concurrent
+            )))
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, decorate_markdown(format!(
+                "🔴 *Worker Offline*\n\nError: {}", escape_markdown_v2(&user_errors::from_hermes(&e).render("ping"))
+            )))
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
         }
-        return Ok(());
     }
 
-    // Handle search result selection — show audio/video format choice
-    if mode_prefix == "sr" {
-        let pending = match state.search_store.peek(&key).await {
-            Some(p) => p,
-            None    => return Ok(()),
-        };
-        if index >= pending.results.len() { return Ok(()); }
+    Ok(())
+}
 
-        let result = &pending.results[index];
-        let title  = if result.title.chars().count() > 50 {
-            format!("{}…", result.title.chars().take(49).collect::<String>())
-        } else {
-            result.title.clone()
-        };
-        let chat_id = match q.message { Some(ref m) => m.chat.id, None => return Ok(()) };
+/// Latest commit subjects, captured by build.rs.
+const RECENT_CHANGES: &str = include_str!(concat!(env!("OUT_DIR"), "/recent_changes.txt"));
 
-        // Send a new message with Audio / Video choice (search results message stays untouched)
-        let buttons = vec![vec![
-            InlineKeyboardButton::callback("🎵 Audio (MP3)", encode_search_format_callback(&key, index, true)),
-            InlineKeyboardButton::callback("🎬 Video (MP4)", encode_search_format_callback(&key, index, false)),
-        ]];
-        let _ = bot.send_message(chat_id, format!("Choose format:\n{}", title))
-            .reply_markup(InlineKeyboardMarkup::new(buttons))
-            .await;
+/// /version - Build identity of the bot and the worker it talks to
+async fn cmd_version(
+    bot: Bot,
+    msg: Message,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let mut text = format!(
+        "🏷 Hermes Bot {} ({})\n",
+        env!("CARGO_PKG_VERSION"), env!("HERMES_GIT_HASH")
+    );
+
+    let request = health_check_request(&Uuid::new_v4().to_string());
+    match state.dispatcher.send_and_wait(&request, 10).await {
+        Ok(response) => {
+            let field = |key: &str| response.data.get(key).and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
+            text.push_str(&format!("🤖 Worker {}\n📦 yt-dlp {}\n", field("version"), field("yt_dlp_version")));
+        }
+        Err(_) => text.push_str("🤖 Worker offline\n"),
+    }
+
+    if !RECENT_CHANGES.trim().is_empty() {
+        text.push_str("\nRecent changes:\n");
+        for line in RECENT_CHANGES.lines() {
+            text.push_str(&format!("• {}\n", line));
+        }
+    }
+
+    bot.send_message(msg.chat.id, decorate(text)).await?;
+    Ok(())
+}
+
+/// Path of the worker's cookie file (YOUTUBE_COOKIE_FILE, resolved relative to WORKER_DIR).
+fn cookie_file_path() -> std::path::PathBuf {
+    let cookie_path = std::env::var("YOUTUBE_COOKIE_FILE")
+        .unwrap_or_else(|_| "./cookies.txt".to_string());
+
+    // Resolve relative to WORKER_DIR
+    let worker_dir = std::env::var("WORKER_DIR").unwrap_or_else(|_| ".".to_string());
+    if std::path::Path::new(&cookie_path).is_relative() {
+        std::path::PathBuf::from(&worker_dir).join(&cookie_path)
+    } else {
+        std::path::PathBuf::from(&cookie_path)
+    }
+}
+
+/// Whether the worker has cookies to pass to yt-dlp (the cookie file or inline YTDLP_COOKIES).
+fn cookies_available() -> bool {
+    let file_ok = std::fs::metadata(cookie_file_path())
+        .map(|m| m.is_file() && m.len() > 0)
+        .unwrap_or(false);
+    file_ok || std::env::var("YTDLP_COOKIES").map(|v| !v.trim().is_empty()).unwrap_or(false)
+}
+
+/// Worker error codes that a retry with cookies may fix.
+fn is_auth_error_code(code: &str) -> bool {
+    matches!(code, "REQUIRE_AUTH" | "COOKIE_EXPIRED" | "LOGIN_REQUIRED" | "BOT_DETECTION")
+}
 
+/// /retrycookie <task-id> - Re-run a failed download with cookies forced on.
+async fn cmd_retry_cookie(
+    bot: Bot,
+    msg: Message,
+    task_id_prefix: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let prefix = task_id_prefix.trim().to_string();
+    if prefix.is_empty() {
+        bot.send_message(msg.chat.id, decorate_markdown("🍪 *Retry With Cookies*\n\nUsage: `/retrycookie <task-id>`\n\nGet task IDs from the failure message or the dashboard"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
         return Ok(());
     }
+    retry_with_cookies(bot, msg.chat.id, &prefix, state).await
+}
 
-    // Parse mode
-    let mode = match DownloadMode::from_prefix(&mode_prefix) {
-        Some(m) => m,
-        None => return Ok(()),
+/// Re-dispatch a user's failed task with `use_cookies: true`, after checking a cookie file exists.
+/// The worker then re-copies the uploaded cookie file instead of reusing the
+/// working copy the failed attempt left behind, and refuses to run without it.
+async fn retry_with_cookies(
+    bot: Bot,
+    chat_id: ChatId,
+    prefix: &str,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(chat_id, "Task history is unavailable (no database).").await?;
+        return Ok(());
     };
-
-    // Get pending selection
-    let pending = match state.callback_store.take(&key).await {
-        Some(p) => p,
-        None => {
-            // Expired or already used
-            if let Some(msg) = q.message {
-                let chat_id = msg.chat.id;
-                let _ = bot.edit_message_text(chat_id, msg.id, "Selection expired. Please try again.").await;
-            }
+
+    let tasks = hermes_shared::db::get_user_tasks(pool, chat_id.0).await.unwrap_or_default();
+    let old = match resolve_task_prefix(tasks, prefix, |t| t.id.as_str()) {
+        PrefixMatch::Unique(task) => task,
+        PrefixMatch::Ambiguous(tasks) => {
+            let candidates: Vec<_> = tasks.into_iter().map(|t| (t.id, Some(t.url))).collect();
+            bot.send_message(chat_id, decorate(task_prefix::ambiguous_text(prefix, &candidates))).await?;
+            return Ok(());
+        }
+        PrefixMatch::NotFound { closest } => {
+            bot.send_message(chat_id, decorate(task_prefix::not_found_text(
+                prefix, closest.as_ref().map(|t| t.id.as_str()),
+            ))).await?;
             return Ok(());
         }
     };
 
-    // Validate index
-    if index >= pending.formats.len() {
+    if old.status != "error" {
+        bot.send_message(chat_id, decorate(format!(
+            "Task [{}] has not failed (status: {}).", &old.id[..8], old.status
+        ))).await?;
+        return Ok(());
+    }
+    if old.task_type != "youtube_dl" {
+        bot.send_message(chat_id, decorate(format!(
+            "Task [{}] cannot be retried with cookies ({}).", &old.id[..8], old.task_type
+        ))).await?;
+        return Ok(());
+    }
+    if !cookies_available() {
+        bot.send_message(chat_id, decorate(
+            "🍪 No cookie file is configured.\n\nAsk the admin to upload cookies with /upcook."
+        )).await?;
         return Ok(());
     }
 
-    let format = &pending.formats[index];
-    let chat_id = ChatId(pending.chat_id);
-
-    // Update message to show download started
-    let short_label = &format.label;
-    let _ = bot.edit_message_text(
-        chat_id,
-        pending.message_id,
-        format!("Downloading: {} [{}]", pending.title, short_label),
-    ).await;
-
-    let status_msg_id = pending.message_id;
     let task_id = Uuid::new_v4().to_string();
     let short_id = task_id[..8].to_string();
+    let prefs = load_user_prefs(&state, chat_id.0).await;
+    let extract_audio = match old.label.as_deref() {
+        Some("audio") => true,
+        Some("video") => false,
+        _ => prefs.default_mode == "audio",
+    };
+    let mode_label = if extract_audio { "audio" } else { "video" };
+    let dl_mode = if extract_audio { DownloadMode::Audio } else { DownloadMode::Video };
 
-    // Build IPC request based on format selection
-    let out_dir = task_output_dir(&state.download_dir, pending.chat_id, &task_id);
-    let request = download_request_with_format(
-        &task_id,
-        &pending.url,
-        &format.format_id,
-        format.extract_audio,
-        format.audio_format.as_deref(),
-        format.audio_quality.as_deref(),
-        &out_dir,
-        pending.chat_id,
-    );
+    state.task_queue.enqueue(&task_id, chat_id.0, "youtube_dl", QueuePriority::High).await;
+    let _ = hermes_shared::db::create_task(
+        pool, &task_id, chat_id.0, "youtube_dl", &old.url, Some(mode_label), QueuePriority::High,
+    ).await;
 
-    // Enqueue task
-    state.task_queue.enqueue(&task_id, pending.chat_id, "youtube_dl").await;
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "🍪 Retrying [{}] with cookies as [{}]\n\nSource:\n{}",
+        &old.id[..8], short_id, old.url
+    ))).await?;
+    let status_msg_id = status_msg.id;
 
-    // Create DB record so the task shows in web dashboard
-    if let Some(pool) = &state.db_pool {
-        let label = Some(mode.as_str());
-        let _ = hermes_shared::db::create_task(pool, &task_id, pending.chat_id, "youtube_dl", &pending.url, label).await;
-    }
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::for_media(extract_audio)), chat_id.0, &task_id);
+    let request = download_request_prefs(
+        &task_id, &old.url, extract_audio,
+        &prefs,
+        &out_dir, chat_id.0,
+    ).with_param("use_cookies", true);
 
-    // Spawn download in background so the teloxide handler returns immediately.
-    let mode_str = mode.as_str().to_string();
     tokio::spawn(async move {
         let _ = execute_download_and_send(
             &bot,
             chat_id,
             status_msg_id,
             &short_id,
-            &mode_str,
+            "retrycookie",
             &task_id,
             &request,
-            mode,
+            dl_mode,
             &state,
         ).await;
     });
@@ -1388,1092 +5882,1486 @@ pub async fn handle_callback_query(
     Ok(())
 }
 
-/// Deliver a single downloaded file to the user.
-///
-/// Handles all delivery paths:
-///   - ≤ 50 MB → send directly as audio or video
-///   - > 50 MB + MPROTO=true → upload via MTProto IPC, copy_message to user
-///   - > 50 MB + MPROTO=false → generate and send 24h download link
-///
-/// `known_channel_msg_id`: if Some, skip the MTProto upload and copy_message directly
-/// (used by the dedup fast-path when the channel_msg_id is already cached in the DB).
-async fn deliver_file(
-    bot: &Bot,
-    chat_id: ChatId,
-    file_path: &str,
-    filename: &str,
-    task_id: &str,
-    mode: DownloadMode,
-    known_channel_msg_id: Option<i64>,
-    state: &AppState,
+/// /upcook <content> - Update cookies.txt (admin only)
+async fn cmd_upcook(
+    bot: Bot,
+    msg: Message,
+    content: String,
+    state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    if file_path.is_empty() {
+    // Admin-only check
+    let is_admin = state.admin_chat_id
+        .map(|id| id == msg.chat.id.0)
+        .unwrap_or(false);
+
+    if !is_admin {
+        bot.send_message(msg.chat.id, decorate("🔒 Admin Command\n\nThis command is restricted to administrators only."))
+            .await?;
         return Ok(());
     }
-    let path = std::path::PathBuf::from(file_path);
-    if !path.exists() {
-        warn!("File not found at: {}", file_path);
+
+    let content = content.trim().to_string();
+
+    // Strip surrounding brackets: /upcook [content] → content
+    let content = if content.starts_with('[') && content.ends_with(']') {
+        content[1..content.len()-1].trim().to_string()
+    } else {
+        content
+    };
+
+    if content.is_empty() {
+        bot.send_message(msg.chat.id,
+            "Usage: /upcook [cookie content]\n\n\
+             Paste the Netscape cookie file content inside brackets."
+        ).await?;
         return Ok(());
     }
-    let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
 
-    if file_size > 50 * 1024 * 1024 {
-        let size_mb    = file_size as f64 / 1024.0 / 1024.0;
-        let use_mproto = std::env::var("MPROTO")
-            .map(|v| v.to_lowercase() == "true")
-            .unwrap_or(false);
+    let full_path = cookie_file_path();
 
-        if use_mproto {
-            let storage_channel_id: i64 = std::env::var("STORAGE_CHANNEL_ID")
-                .ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+    match std::fs::write(&full_path, &content) {
+        Ok(_) => {
+            let size = content.len();
+            let lines = content.lines().count();
+            info!("Cookies updated by admin: {} ({} bytes, {} lines)", full_path.display(), size, lines);
+            bot.send_message(msg.chat.id, decorate(format!(
+                "Cookies updated!\nFile: {}\nSize: {} bytes ({} lines)",
+                full_path.display(), size, lines
+            ))).await?;
+        }
+        Err(e) => {
+            error!("Failed to write cookies: {}", e);
+            bot.send_message(msg.chat.id, decorate(format!("Failed to write cookies: {}", e))).await?;
+        }
+    }
 
-            // Use cached channel_msg_id when available (avoids re-upload)
-            let (channel_msg_id, upload_status_msg) = if let Some(cached) = known_channel_msg_id {
-                (Some(cached), None::<teloxide::types::Message>)
-            } else {
-                let upload_task_id = format!("up-{}", task_id);
-                let req = hermes_shared::ipc_protocol::mtproto_upload_request(
-                    &upload_task_id, file_path, chat_id.0, filename,
-                );
-                let sm = bot.send_message(chat_id, format!(
-                    "⬆️ {:.1}MB — uploading via MTProto...", size_mb
-                )).await;
+    Ok(())
+}
 
-                let mut ch_id: Option<i64> = None;
-                let mut last_edit = std::time::Instant::now();
+/// Show playlist confirmation dialog — prompts user for playlist vs single video.
+async fn cmd_playlist_confirm(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let task_id = Uuid::new_v4().to_string();
+    let key     = task_id[..8].to_string();
 
-                if let Ok(mut rx) = state.dispatcher.send(&req).await {
-                    loop {
-                        match rx.recv().await {
-                            Some(resp) if resp.is_progress() => {
-                                if last_edit.elapsed().as_secs() >= 4 {
-                                    last_edit = std::time::Instant::now();
-                                    let pct  = resp.progress_percent().unwrap_or(0) as usize;
-                                    let spd  = resp.progress_speed().unwrap_or_default();
-                                    let done = pct / 10;
-                                    let bar  = format!("{}{}", "█".repeat(done), "░".repeat(10 - done));
-                                    if let Ok(ref m) = sm {
-                                        let _ = bot.edit_message_text(chat_id, m.id, format!(
-                                            "⬆️ Uploading via MTProto\n[{bar}] {pct}%  {spd}"
-                                        )).await;
-                                    }
-                                }
-                            }
-                            Some(resp) if resp.is_done() => {
-                                ch_id = resp.data.get("channel_msg_id").and_then(|v| v.as_i64());
-                                break;
-                            }
-                            Some(resp) if resp.is_error() => {
-                                warn!("MTProto upload IPC error for {}: {:?}", task_id, resp.error_message());
-                                break;
-                            }
-                            None => break,
-                            _ => {}
-                        }
-                    }
-                } else {
-                    warn!("Failed to send mtproto_upload IPC request for {}", task_id);
-                }
+    let display_url = if url.len() > 60 {
+        format!("{}\u{2026}", &url[..59])
+    } else {
+        url.clone()
+    };
 
-                (ch_id, sm.ok())
-            };
+    let buttons = vec![
+        vec![
+            InlineKeyboardButton::callback(decorate("🎵 Download Playlist"), encode_playlist_confirm(&key, 'p')),
+            InlineKeyboardButton::callback(decorate("🎬 Single Video"),      encode_playlist_confirm(&key, 's')),
+        ],
+        vec![
+            InlineKeyboardButton::callback(decorate("✖ Cancel"), encode_playlist_confirm(&key, 'x')),
+        ],
+    ];
 
-            if let (Some(msg_id), true) = (channel_msg_id, storage_channel_id != 0) {
-                let from_chat = teloxide::types::ChatId(storage_channel_id);
-                match bot.copy_message(chat_id, from_chat,
-                    teloxide::types::MessageId(msg_id as i32)).await
-                {
-                    Ok(_) => {
-                        // Persist channel_msg_id so future requests for this file skip the upload
-                        if let Some(pool) = &state.db_pool {
-                            let _ = hermes_shared::db::save_channel_msg_id(pool, task_id, msg_id).await;
-                        }
-                        if let Some(ref sm) = upload_status_msg {
-                            let _ = bot.delete_message(chat_id, sm.id).await;
-                        }
-                    }
-                    Err(e) => {
-                        warn!("copy_message failed for {}: {}", task_id, e);
-                        let err_text = "⚠️ MTProto forward failed — try again";
-                        if let Some(ref sm) = upload_status_msg {
-                            let _ = bot.edit_message_text(chat_id, sm.id, err_text).await;
-                        } else {
-                            let _ = bot.send_message(chat_id, err_text).await;
-                        }
-                    }
+    let sent = bot.send_message(chat_id, decorate(format!(
+        "Playlist detected!\n{}\n\nDownload the full playlist or just this video?",
+        display_url
+    )))
+    .reply_markup(InlineKeyboardMarkup::new(buttons))
+    .await?;
+
+    let pending = PlaylistPending {
+        url,
+        chat_id:    chat_id.0,
+        message_id: sent.id,
+        limit:      None,
+        is_single:  false,
+        video_only: false,
+        created_at: std::time::Instant::now(),
+    };
+    state.playlist_store.store(key, pending).await;
+    Ok(())
+}
+
+/// Strip list= and related params from a YouTube URL to return a single-video URL.
+fn extract_single_video_url(url: &str) -> String {
+    // Handle https://www.youtube.com/watch?v=VIDEO_ID&list=PL...
+    if let Some(v_pos) = url.find("v=") {
+        let after = &url[v_pos + 2..];
+        let id_end = after.find('&').unwrap_or(after.len());
+        let video_id = &after[..id_end];
+        if video_id.len() == 11 {
+            return format!("https://www.youtube.com/watch?v={}", video_id);
+        }
+    }
+    // Handle https://youtu.be/VIDEO_ID?list=...  — strip query string
+    if url.contains("youtu.be/") {
+        if let Some(q_pos) = url.find('?') {
+            return url[..q_pos].to_string();
+        }
+    }
+    url.to_string()
+}
+
+/// Show playlist confirmation dialog — prompts user for playlist vs single video.
+/// Handle plain messages (auto-detect links).
+pub async fn handle_message(
+    bot: Bot,
+    msg: Message,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    if let Some(text) = msg.text() {
+        if let Some(reply) = state.access_denied(msg.chat.id.0).await {
+            bot.send_message(msg.chat.id, decorate(reply)).await?;
+            return Ok(());
+        }
+
+        // Track user in DB (captures username from Telegram)
+        if let Some(pool) = &state.db_pool {
+            let username = msg.from()
+                .and_then(|u| u.username.as_deref());
+            let _ = hermes_shared::db::upsert_user(pool, msg.chat.id.0, username).await;
+        }
+
+        let links = link_detector::detect_links(text);
+        if !links.is_empty() {
+            let first = &links[0];
+            if first.is_telegram() {
+                // Telegram links: forward all detected links
+                info!("Auto-detected {} Telegram link(s)", links.len());
+                cmd_telegram_forward(bot, msg, links, state).await?;
+            } else if first.is_feed() {
+                info!("Podcast feed detected: {}", first.url());
+                cmd_podcast(bot, msg, first.url().to_string(), state).await?;
+            } else if let Some(name) = first.direct_file_name().and_then(direct_download_name) {
+                // A link straight to a media file or HLS stream: fetch it ourselves, no yt-dlp needed
+                info!("Direct file link detected: {}", first.url());
+                cmd_native_download(bot, msg, first.url().to_string(), name, state).await?;
+            } else if first.is_generic() {
+                // Other site — probe it first so sites yt-dlp can't handle fail fast
+                info!("Generic link detected, probing with yt-dlp: {}", first.url());
+                cmd_generic_link(bot, msg, first.url().to_string(), state).await?;
+            } else if first.is_supported() {
+                info!("Auto-detected link: {:?}", first);
+                if first.is_spotify() {
+                    cmd_spotify(bot, msg, first.url().to_string(), state).await?;
+                } else if first.is_playlist() {
+                    cmd_playlist_confirm(bot, msg, first.url().to_string(), state).await?;
+                } else {
+                    cmd_download(bot, msg, first.url().to_string(), state).await?;
                 }
             } else {
-                // Upload failed or channel not configured — fall back to 24h link
-                if let Some(pool) = &state.db_pool {
-                    let base = std::env::var("DASHBOARD_URL")
-                        .unwrap_or_else(|_| "https://tg-hermes-bot.pgwiz.cloud".to_string());
-                    if hermes_shared::db::create_file_download_token(
-                        pool, task_id, chat_id.0, 86400
-                    ).await.is_ok() {
-                        let dl_url  = format!("{}/api/dl/{}", base, task_id);
-                        let msg_txt = format!(
-                            "⚠️ MTProto upload failed.\n\n📥 Download link (24h):\n{}", dl_url
-                        );
-                        if let Some(ref sm) = upload_status_msg {
-                            let _ = bot.edit_message_text(chat_id, sm.id, msg_txt).await;
-                        } else {
-                            let _ = bot.send_message(chat_id, msg_txt).await;
-                        }
-                    }
-                }
-            }
-        } else if let Some(pool) = &state.db_pool {
-            let dashboard_url = std::env::var("DASHBOARD_URL")
-                .unwrap_or_else(|_| "https://tg-hermes-bot.pgwiz.cloud".to_string());
-            match hermes_shared::db::create_file_download_token(pool, task_id, chat_id.0, 86400).await {
-                Ok(_) => {
-                    let dl_url = format!("{}/api/dl/{}", dashboard_url, task_id);
-                    let _ = bot.send_message(chat_id, format!(
-                        "⚠️ File too large for Telegram ({:.1}MB)\n\n📥 Download link (24h):\n{}",
-                        size_mb, dl_url
-                    )).await;
-                }
-                Err(e) => {
-                    warn!("Failed to create download token for {}: {}", task_id, e);
-                    let _ = bot.send_message(chat_id, format!(
-                        "⚠️ File too large for Telegram ({:.1}MB)\nCouldn't generate download link.",
-                        size_mb
-                    )).await;
-                }
+                bot.send_message(msg.chat.id, decorate(UNSUPPORTED_LINK_TEXT)).await?;
             }
-        } else {
-            let hint = if mode == DownloadMode::Video {
-                "Use /dv to pick a lower resolution."
-            } else {
-                "The file exceeds Telegram's 50MB limit."
-            };
-            let _ = bot.send_message(chat_id, format!(
-                "⚠️ File too large for Telegram ({:.1}MB)\n\n{}",
-                size_mb, hint
-            )).await;
-        }
-    } else if mode == DownloadMode::Video {
-        let display_name = path.file_name().and_then(|n| n.to_str()).unwrap_or(filename).to_string();
-        let input = teloxide::types::InputFile::file(&path).file_name(display_name.clone());
-        if let Err(e) = bot.send_video(chat_id, input).await {
-            warn!("Failed to send video, trying document: {}", e);
-            let input2 = teloxide::types::InputFile::file(&path).file_name(display_name);
-            let _ = bot.send_document(chat_id, input2).await;
-        }
-    } else {
-        let display_name = path.file_name().and_then(|n| n.to_str()).unwrap_or(filename).to_string();
-        let input = teloxide::types::InputFile::file(&path).file_name(display_name.clone());
-        if let Err(e) = bot.send_audio(chat_id, input).await {
-            warn!("Failed to send audio, trying document: {}", e);
-            let input2 = teloxide::types::InputFile::file(&path).file_name(display_name);
-            let _ = bot.send_document(chat_id, input2).await;
         }
     }
     Ok(())
 }
 
-/// Execute a download request, stream progress, and send the resulting file.
-/// Shared by cmd_download and handle_callback_query.
-pub async fn execute_download_and_send(
-    bot: &Bot,
-    chat_id: ChatId,
-    status_msg_id: MessageId,
-    short_id: &str,
-    kind: &str,
-    task_id: &str,
-    request: &IPCRequest,
-    mode: DownloadMode,
-    state: &AppState,
-) -> ResponseResult<()> {
-    info!("[{short_id}] Starting download: kind={}, action={:?}", kind, request.action);
+/// Episodes listed per /podcast feed.
+const PODCAST_EPISODES: usize = 10;
 
-    // Acquire concurrency slot
-    if !state.task_queue.acquire(task_id).await {
-        bot.edit_message_text(chat_id, status_msg_id, format!(
-            "Failed to acquire download slot [{}]", short_id
-        )).await?;
+/// /podcast <feed-url> - List a feed's latest episodes as buttons. The feed is
+/// read here (`hermes_shared::feeds`); a tapped episode goes through the native
+/// downloader like any direct file link.
+async fn cmd_podcast(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let Some(url) = url.split_whitespace().next().filter(|u| u.starts_with("http://") || u.starts_with("https://")) else {
+        bot.send_message(msg.chat.id,
+            "Usage: /podcast <feed-url>\n\n\
+             Lists the latest episodes of a podcast's RSS feed. Pasting a feed link works too."
+        ).await?;
         return Ok(());
-    }
-
-    info!("[{short_id}] Acquired download slot");
+    };
 
-    // Send to Python worker and process response stream
-    let mut rx = match state.dispatcher.send(request).await {
-        Ok(rx) => rx,
+    let status = bot.send_message(msg.chat.id, decorate("📻 Reading feed...")).await?;
+    let feed = match hermes_shared::feeds::fetch_feed(url).await {
+        Ok(feed) => feed,
         Err(e) => {
-            state.task_queue.fail(task_id).await;
-            error!("Failed to send IPC request: {}", e);
-            bot.edit_message_text(chat_id, status_msg_id, format!(
-                "Worker error: {} [{}]", e, short_id
-            )).await?;
+            warn!("Feed {} failed: {}", url, e);
+            bot.edit_message_text(msg.chat.id, status.id, decorate(format!("❌ {}", e))).await?;
             return Ok(());
         }
     };
+    if feed.episodes.is_empty() {
+        bot.edit_message_text(msg.chat.id, status.id, decorate("😕 No downloadable episodes in this feed.")).await?;
+        return Ok(());
+    }
 
-    info!("[{short_id}] Sent request to Python worker, waiting for responses");
-
-    // Process response stream with throttled progress updates
-    let mut last_edit = Instant::now();
-    let mut last_percent: i32 = -1;
-    let timeout = tokio::time::Duration::from_secs(600); // 10 min
-
-    let result = tokio::time::timeout(timeout, async {
-        while let Some(response) = rx.recv().await {
-            if response.is_progress() {
-                let pct = response.progress_percent().unwrap_or(0) as i32;
-                let speed = response.progress_speed().unwrap_or_default();
-                let status = response.data.get("status")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("downloading");
-
-                // Throttle edits: at least 3s apart and at least 5% change
-                let elapsed = last_edit.elapsed().as_secs();
-                if elapsed >= 3 && (pct - last_percent).abs() >= 5 {
-                    let bar = progress_bar(pct as u8);
-                    let text = format!(
-                        "{} [{}]\n{} {}%\nSpeed: {}\nStatus: {}",
-                        kind, short_id, bar, pct, speed, status
-                    );
-                    let _ = bot.edit_message_text(chat_id, status_msg_id, text).await;
-                    last_edit = Instant::now();
-                    last_percent = pct;
-                }
-                state.task_queue.update_progress(task_id, pct as u8, Some(speed)).await;
-                continue;
-            }
+    let total = feed.episodes.len();
+    let episodes: Vec<_> = feed.episodes.into_iter().take(PODCAST_EPISODES).collect();
+    let key = Uuid::new_v4().to_string()[..6].to_string();
+    let buttons: Vec<Vec<InlineKeyboardButton>> = episodes.iter()
+        .enumerate()
+        .map(|(i, ep)| {
+            let label = match ep.date_label() {
+                Some(date) => format!("🎧 {} · {}", ep.title, date),
+                None => format!("🎧 {}", ep.title),
+            };
+            let label: String = if label.chars().count() > 52 {
+                format!("{}…", label.chars().take(51).collect::<String>())
+            } else {
+                label
+            };
+            vec![InlineKeyboardButton::callback(decorate(label), encode_podcast_callback(&key, i))]
+        })
+        .collect();
 
-            // Non-progress event = final response
-            return Some(response);
-        }
-        None
+    let feed_title = if feed.title.trim().is_empty() { url.to_string() } else { feed.title.trim().to_string() };
+    let text = format!(
+        "📻 {}\nLatest {} of {} episode(s) — tap to download:",
+        feed_title, episodes.len(), total
+    );
+    state.podcast_store.store(key, PodcastPending {
+        feed_title,
+        episodes,
+        created_at: std::time::Instant::now(),
     }).await;
+    bot.edit_message_text(msg.chat.id, status.id, decorate(text))
+        .reply_markup(InlineKeyboardMarkup::new(buttons))
+        .await?;
+    Ok(())
+}
 
-    // Handle result
-    match result {
-        Ok(Some(response)) => {
-            info!("[{short_id}] Received response: event={:?}, data keys={:?}",
-                response.event,
-                response.data.as_object().map(|obj| obj.keys().collect::<Vec<_>>())
-            );
-
-            if response.is_error() {
-                let error_msg = response.error_message().unwrap_or_else(|| "Unknown error".into());
-                state.task_queue.fail(task_id).await;
-                // Persist failure to DB
-                if let Some(pool) = &state.db_pool {
-                    let _ = hermes_shared::db::fail_task(pool, task_id, &error_msg).await;
-                }
-                bot.edit_message_text(chat_id, status_msg_id, format!(
-                    "Download failed [{}]\n{}", short_id, error_msg
-                )).await?;
-            } else {
-                state.task_queue.complete(task_id).await;
+/// Handle a /podcast episode button: download its enclosure with the native
+/// downloader. The list stays, so more episodes can be picked.
+async fn handle_podcast_episode(
+    bot: &Bot,
+    q: &CallbackQuery,
+    key: &str,
+    index: usize,
+    state: &Arc<AppState>,
+) -> ResponseResult<()> {
+    let Some(message) = q.message.clone() else { return Ok(()) };
+    let chat_id = message.chat.id;
+    let Some((feed_title, episode)) = state.podcast_store.peek(key).await
+        .and_then(|p| Some((p.feed_title, p.episodes.get(index).cloned()?))) else {
+        let _ = bot.send_message(chat_id, decorate("⌛ This episode list expired. Send /podcast again.")).await;
+        return Ok(());
+    };
 
-                let file_path = response.data.get("file_path")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("");
-                let filename = response.data.get("filename")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("download");
+    let file_name = episode.file_name();
+    if !is_allowed_output_file(&file_name) {
+        bot.send_message(chat_id, decorate(format!(
+            "❌ Can't send this episode's file type ({}).", file_name
+        ))).await?;
+        return Ok(());
+    }
+    info!("Podcast episode from {} for chat {}: {}", feed_title, chat_id, episode.url);
+    cmd_native_download(bot.clone(), message, episode.url, file_name, state.clone()).await
+}
 
-                // Persist completion to DB
-                if let Some(pool) = &state.db_pool {
-                    let _ = hermes_shared::db::complete_task(pool, task_id, file_path).await;
-                }
+/// Route a link from an unknown site after asking the worker whether it can handle it.
+///
+/// A probe failure (worker offline, timeout) falls through to a normal download
+/// attempt, which reports its own error.
+async fn cmd_generic_link(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let task_id = Uuid::new_v4().to_string();
+    let request = probe_request(&task_id, &url);
+    let probe = match state.dispatcher.send_and_wait(&request, 30).await {
+        Ok(response) => response.probe_result(),
+        Err(e) => {
+            warn!("Probe failed for {}: {}", url, e);
+            None
+        }
+    };
 
-                // Edit message to show completion (don't use ? - must continue to send files even if edit fails)
-                let _ = bot.edit_message_text(chat_id, status_msg_id, format!(
-                    "Download complete [{}]\nFile: {}", short_id, filename
-                )).await;
+    match probe {
+        Some(p) if !p.downloadable => {
+            let reason = p.reason.unwrap_or_else(|| "unsupported site".to_string());
+            bot.send_message(msg.chat.id, decorate(format!(
+                "I can't download this link.\n{}", reason
+            ))).await?;
+            Ok(())
+        }
+        Some(p) if p.is_playlist => cmd_playlist_confirm(bot, msg, url, state).await,
+        _ => cmd_download(bot, msg, url, state).await,
+    }
+}
 
-                // Send the file to user
-                deliver_file(&bot, chat_id, file_path, filename, task_id, mode, None, &state).await?;
+/// Download a Spotify track: the worker looks up its title and artist and
+/// searches YouTube for the closest match, which is then downloaded as audio.
+async fn cmd_spotify(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let status = bot.send_message(msg.chat.id, decorate("🔎 Looking up Spotify track...")).await?;
 
-                // Handle playlist files - send each individually
-                if let Some(files) = response.data.get("files").and_then(|v| v.as_array()) {
-                    info!("[{short_id}] Found 'files' array with {} entries", files.len());
-                    if !files.is_empty() {
-                        let _ = bot.send_message(chat_id, format!(
-                            "📤 Sending {} track(s)...",
-                            files.len()
-                        )).await;
+    let task_id = Uuid::new_v4().to_string();
+    let request = spotify_resolve_request(&task_id, &url);
+    let track = match state.dispatcher.send_and_wait(&request, 30).await {
+        Ok(response) if response.is_error() => {
+            warn!("Spotify lookup failed for {}: {:?}", url, response.error_message());
+            None
+        }
+        Ok(response) => response.spotify_track(),
+        Err(e) => {
+            warn!("Spotify lookup failed for {}: {}", url, e);
+            None
+        }
+    };
 
-                        for (idx, file_info) in files.iter().enumerate() {
-                            let file_path = file_info.get("path").and_then(|v| v.as_str()).unwrap_or("");
-                            let file_name = file_info.get("name").and_then(|v| v.as_str()).unwrap_or("track");
+    let Some(track) = track else {
+        bot.edit_message_text(msg.chat.id, status.id, decorate(
+            "❌ Couldn't look up this Spotify track. Try /search with the song name instead."
+        )).await?;
+        return Ok(());
+    };
+    let Some(youtube_url) = track.youtube_url else {
+        bot.edit_message_text(msg.chat.id, status.id, decorate(format!(
+            "❌ No YouTube match found for {} - {}.\nTry /search with a different spelling.",
+            track.artist, track.title
+        ))).await?;
+        return Ok(());
+    };
 
-                            info!("[{short_id}] Sending file {}/{}: {}", idx + 1, files.len(), file_name);
+    info!("Spotify {} resolved to {}", url, youtube_url);
+    bot.edit_message_text(msg.chat.id, status.id, decorate(format!(
+        "🎵 {} - {}\nMatched on YouTube: {}",
+        track.artist,
+        track.title,
+        track.youtube_title.as_deref().unwrap_or(&youtube_url)
+    ))).await?;
 
-                            let fpath = std::path::PathBuf::from(file_path);
-                            if fpath.exists() {
-                                let lower_name = file_name.to_lowercase();
-                                let is_video_file = lower_name.ends_with(".mp4")
-                                    || lower_name.ends_with(".webm")
-                                    || lower_name.ends_with(".mkv");
-
-                                let input = teloxide::types::InputFile::file(&fpath).file_name(file_name.to_string());
-                                if is_video_file {
-                                    if let Err(e) = bot.send_video(chat_id, input).await {
-                                        warn!("Failed to send video {}: {}", file_name, e);
-                                        let input2 = teloxide::types::InputFile::file(&fpath).file_name(file_name.to_string());
-                                        let _ = bot.send_document(chat_id, input2).await;
-                                    }
-                                } else {
-                                    if let Err(e) = bot.send_audio(chat_id, input).await {
-                                        warn!("Failed to send audio {}: {}", file_name, e);
-                                        let input2 = teloxide::types::InputFile::file(&fpath).file_name(file_name.to_string());
-                                        let _ = bot.send_document(chat_id, input2).await;
-                                    }
-                                }
+    cmd_direct_download(bot, msg, format!("mp3 {}", youtube_url), state).await
+}
 
-                                // Add delay between sends to avoid rate limiting
-                                if idx < files.len() - 1 {
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                                }
-                            } else {
-                                warn!("[{short_id}] File not found (path={}, name={}). Current dir: {:?}",
-                                    file_path, file_name,
-                                    std::env::current_dir().ok()
-                                );
-                            }
-                        }
+/// Download a direct file link with the native downloader (no worker round
+/// trip) and deliver it like any other download.
+async fn cmd_native_download(
+    bot: Bot,
+    msg: Message,
+    url: String,
+    file_name: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    if quota_exceeded(&bot, msg.chat.id, &state).await? {
+        return Ok(());
+    }
 
-                        let _ = bot.send_message(chat_id, format!(
-                            "✅ Sent all {} tracks", files.len()
-                        )).await;
-                    }
-                } else {
-                    info!("[{short_id}] No 'files' array in response data");
-                    // Fallback: handle archives if present (for backward compatibility)
-                    if let Some(archives) = response.data.get("archives").and_then(|v| v.as_array()) {
-                        info!("[{short_id}] Found 'archives' array with {} entries", archives.len());
-                        for archive in archives {
-                            let archive_path = archive.get("path").and_then(|v| v.as_str()).unwrap_or("");
-                            let archive_name = archive.get("name").and_then(|v| v.as_str()).unwrap_or("archive.zip");
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let chat_id = msg.chat.id;
 
-                            let apath = std::path::PathBuf::from(archive_path);
-                            if apath.exists() {
-                                let input = teloxide::types::InputFile::file(&apath).file_name(archive_name.to_string());
-                                if let Err(e) = bot.send_document(chat_id, input).await {
-                                    warn!("Failed to send archive {}: {}", archive_name, e);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        Ok(None) => {
-            state.task_queue.fail(task_id).await;
-            if let Some(pool) = &state.db_pool {
-                let _ = hermes_shared::db::fail_task(pool, task_id, "Worker connection lost").await;
-            }
-            bot.edit_message_text(chat_id, status_msg_id, format!(
-                "Worker connection lost [{}]", short_id
-            )).await?;
-        }
-        Err(_) => {
-            state.task_queue.fail(task_id).await;
-            if let Some(pool) = &state.db_pool {
-                let _ = hermes_shared::db::fail_task(pool, task_id, "Download timed out").await;
-            }
-            bot.edit_message_text(chat_id, status_msg_id, format!(
-                "Download timed out [{}]", short_id
-            )).await?;
-        }
+    let priority = state.task_priority(chat_id.0, "direct");
+    state.task_queue.enqueue(&task_id, chat_id.0, "direct", priority).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::create_task(pool, &task_id, chat_id.0, "direct", &url, Some("file"), priority).await;
     }
 
-    // Cleanup
-    state.dispatcher.remove_pending(task_id).await;
+    let status_msg = bot.send_message(chat_id, decorate(format!(
+        "⏳ Task Queued [{}] (file)\n\nSource:\n{}", short_id, url
+    ))).await?;
+    let status_msg_id = status_msg.id;
+
+    tokio::spawn(async move {
+        let _ = execute_native_download(
+            &bot, chat_id, status_msg_id, &short_id, &task_id, &url, &file_name, &state,
+        ).await;
+    });
     Ok(())
 }
 
-/// Shared logic for starting a playlist/single-video download after format is chosen.
-///
-/// Called from both the `pf:` callback handler (user clicked audio/video button)
-/// and directly from the `pl:`/`pc:` handlers when `video_only` is set.
-async fn handle_playlist_format_download(
+/// Run a native download in a queue slot, showing progress, then deliver the file.
+/// Cancelling or failing removes the partial file.
+#[allow(clippy::too_many_arguments)]
+async fn execute_native_download(
     bot: &Bot,
-    state: &Arc<AppState>,
-    key: &str,
-    is_audio: bool,
+    chat_id: ChatId,
+    status_msg_id: MessageId,
+    short_id: &str,
+    task_id: &str,
+    url: &str,
+    file_name: &str,
+    state: &AppState,
 ) -> ResponseResult<()> {
-    let pending = match state.playlist_store.take(key).await {
-        Some(p) => p,
-        None    => return Ok(()),
+    let cancel = state.task_queue.cancellation(task_id).await;
+    if !acquire_worker_slot(bot, chat_id, status_msg_id, short_id, task_id, &cancel, state).await? {
+        return Ok(());
+    }
+
+    let file_name = if hermes_shared::safe_path::is_safe_component(file_name) { file_name } else { "download" };
+    let out_dir = std::path::PathBuf::from(task_output_dir(state.storage.base_for(StorageKind::Other), chat_id.0, task_id));
+    let dest = out_dir.join(file_name);
+
+    let is_admin = state.admin_chat_id.map(|id| id == chat_id.0).unwrap_or(false);
+    let mut opts = hermes_downloader::Downloader::builder().chunks(native_download_chunks());
+    if let Some(max_mb) = max_download_mb().filter(|_| !is_admin) {
+        opts = opts.max_size(max_mb * 1024 * 1024);
+    }
+    if let Some(rate) = download_rate_limit() {
+        opts = opts.rate_limit(rate);
+    }
+    if let Some(limiter) = &state.download_limiter {
+        opts = opts.shared_limiter(limiter.clone());
+    }
+    let result = match hermes_downloader::download(url, &dest, opts) {
+        Ok(handle) => {
+            let mut progress = handle.progress();
+            let mut last_edit = Instant::now();
+            let mut last_percent: i32 = -1;
+            let cancelled = loop {
+                tokio::select! {
+                    biased;
+                    _ = cancel.notified() => break true,
+                    changed = progress.changed() => {
+                        if changed.is_err() {
+                            break false;
+                        }
+                        let Some(p) = *progress.borrow_and_update() else { continue };
+                        let pct = p.percent().unwrap_or(0);
+                        let speed = format!("{:.1}MiB/s", p.bytes_per_sec / 1_048_576.0);
+                        let eta = state.task_queue
+                            .update_progress(task_id, pct, Some(speed.clone()), p.eta_secs())
+                            .await;
+                        if last_edit.elapsed().as_secs() >= 3 && (pct as i32 - last_percent).abs() >= 5 {
+                            let mut text = format!(
+                                "file [{}]\n{} {}%\nSpeed: {}", short_id, progress_bar(pct), pct, speed
+                            );
+                            if let Some((done, count)) = p.segments {
+                                text.push_str(&format!("\nSegments: {}/{}", done, count));
+                            }
+                            if let Some(secs) = eta {
+                                text.push_str(&format!("\nETA: {}", format_eta(secs)));
+                            }
+                            let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(text)).await;
+                            last_edit = Instant::now();
+                            last_percent = pct as i32;
+                        }
+                    }
+                }
+            };
+            if cancelled {
+                handle.cancel();
+                let _ = handle.wait().await;
+                let _ = tokio::fs::remove_dir_all(&out_dir).await;
+                // cancel() already released the slot
+                if let Some(pool) = &state.db_pool {
+                    let _ = hermes_shared::db::cancel_task(pool, task_id).await;
+                }
+                bot.edit_message_text(chat_id, status_msg_id, decorate(format!("Cancelled [{}]", short_id))).await?;
+                return Ok(());
+            }
+            handle.wait().await
+        }
+        Err(e) => Err(e),
     };
 
-    let chat_id    = ChatId(pending.chat_id);
-    let msg_id     = pending.message_id;
-    let task_id    = Uuid::new_v4().to_string();
-    let short_id   = task_id[..8].to_string();
-    let out_dir    = task_output_dir(&state.download_dir, pending.chat_id, &task_id);
-    let mode_label = if is_audio { "audio" } else { "video" };
-    let is_single  = pending.is_single;
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            // The chain can hold HTTP client internals and signed URLs: log it, don't show it
+            error!("[{short_id}] Direct download of {} failed: {:#}", url, e);
+            let error_msg = "Couldn't download the file. The server may be unavailable or the link may have expired.";
+            let _ = tokio::fs::remove_dir_all(&out_dir).await;
+            state.task_queue.fail(task_id).await;
+            if let Some(pool) = &state.db_pool {
+                let _ = hermes_shared::db::fail_task(pool, task_id, error_msg).await;
+            }
+            bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+                "Download failed [{}]\n{}", short_id, error_msg
+            ))).await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "[{short_id}] Direct download done: {} bytes in {} range(s), {:.1}s",
+        outcome.bytes, outcome.chunks, outcome.elapsed.as_secs_f64()
+    );
 
-    let prefs = load_user_prefs(state, pending.chat_id).await;
+    let file_path = outcome.path.to_string_lossy().to_string();
+    state.task_queue.complete(task_id).await;
+    if let Some(pool) = &state.db_pool {
+        let _ = hermes_shared::db::complete_task(pool, task_id, &file_path).await;
+    }
 
-    let (url, ipc_action, request) = if is_single {
-        let single_url = extract_single_video_url(&pending.url);
-        let req = download_request_prefs(
-            &task_id, &single_url, is_audio,
-            &prefs.audio_format, &prefs.audio_quality,
-            &out_dir, pending.chat_id,
-        );
-        (single_url, "youtube_dl", req)
-    } else {
-        let archive_opt = Some(format!("{}/playlist_archive.txt", state.download_dir));
-        info!("Playlist download: limit={:?}, url={}, is_audio={}, archive={:?}", pending.limit, &pending.url, is_audio, archive_opt.is_some());
-        let req = playlist_request_opts(
-            &task_id, &pending.url, &out_dir, pending.limit, is_audio, archive_opt.as_deref(), pending.chat_id,
-            Some(prefs.audio_format.as_str()),
-        );
-        (pending.url.clone(), "playlist", req)
-    };
+    if prefers_store_only(state, chat_id.0).await {
+        let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+            "📚 Saved to your library [{}]\nFile: {}\n\n{}/files.html",
+            short_id, file_name, dashboard_base_url()
+        ))).await;
+        return Ok(());
+    }
+    let _ = bot.edit_message_text(chat_id, status_msg_id, decorate(format!(
+        "Download complete [{}]\nFile: {}", short_id, file_name
+    ))).await;
+
+    let is_video = matches!(
+        outcome.path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref(),
+        Some("mp4" | "webm" | "mkv" | "mov" | "avi" | "m4v")
+    );
+    let mode = if is_video { DownloadMode::Video } else { DownloadMode::Audio };
+    let as_link = prefers_link_delivery(state, chat_id.0).await;
+    deliver_file(bot, chat_id, &file_path, file_name, task_id, mode, None, None, None, as_link, state).await
+}
 
-    state.task_queue.enqueue(&task_id, pending.chat_id, ipc_action).await;
+/// /dedup_toggle - Toggle track deduplication for this user
+async fn cmd_dedup_toggle(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
 
     if let Some(pool) = &state.db_pool {
-        let db_kind = if is_single { "youtube_dl" } else { "playlist" };
-        let _ = hermes_shared::db::create_task(
-            pool, &task_id, pending.chat_id, db_kind, &url, Some(mode_label),
-        ).await;
-    }
+        // Get current preference
+        let current = hermes_shared::db::get_user_dedup_preference(pool, chat_id.0)
+            .await
+            .unwrap_or(true);
 
-    let dl_mode    = if is_audio { DownloadMode::Audio } else { DownloadMode::Video };
-    let kind_label = if is_single { "video" } else { "playlist" };
+        // Toggle
+        let new_state = !current;
 
-    // Delete old message, send a fresh status message
-    let _ = bot.delete_message(chat_id, msg_id).await;
-    let status_msg = bot.send_message(chat_id,
-        format!("Queued {} [{}]", kind_label, short_id)
-    ).await;
+        // Update database
+        if let Err(e) = hermes_shared::db::set_user_dedup_preference(pool, chat_id.0, new_state).await {
+            error!("Failed to set dedup preference: {}", e);
+            bot.send_message(chat_id, decorate("❌ Failed to update deduplication setting")).await?;
+            return Ok(());
+        }
 
-    let track_msg_id = match status_msg {
-        Ok(ref m) => m.id,
-        Err(_)    => msg_id,
-    };
+        let status = if new_state { "Enabled ✅" } else { "Disabled ❌" };
+        let message = format!(
+            "🔄 <b>Track Deduplication {}</b>\n\n\
+            <b>When enabled (default):</b>\n\
+            • Shared tracks use symlinks (saves space)\n\
+            • Automatic dedup across downloads\n\n\
+            <b>When disabled:</b>\n\
+            • You get fresh copies of each track\n\
+            • Uses more storage but fully independent",
+            status
+        );
+
+        bot.send_message(chat_id, decorate(message))
+            .parse_mode(ParseMode::Html)
+            .await?;
+    } else {
+        bot.send_message(chat_id, decorate("⚠️ Database not available")).await?;
+    }
 
-    let bot2 = bot.clone();
-    let state2 = state.clone();
-    tokio::spawn(async move {
-        let _ = execute_download_and_send(
-            &bot2, chat_id, track_msg_id, &short_id,
-            kind_label, &task_id, &request, dl_mode, &state2,
-        ).await;
-    });
     Ok(())
 }
 
-/// /playlist <url> - Preview and download playlist
-async fn cmd_playlist_preview(
+/// /allowuser and /denyuser — manage the DB allowlist (admin only).
+/// With no argument, lists the current allowlist.
+async fn cmd_allowlist_edit(
     bot: Bot,
     msg: Message,
-    url: String,
+    arg: String,
+    allow: bool,
     state: Arc<AppState>,
-    video_only: bool,
 ) -> ResponseResult<()> {
-    use hermes_shared::ipc_protocol::{playlist_preview_request, IPCResponse};
+    let is_admin = state.admin_chat_id
+        .map(|id| id == msg.chat.id.0)
+        .unwrap_or(false);
 
-    let url = url.trim().to_string();
-    if url.is_empty() {
-        let help = if video_only {
-            "🎬 *Download Playlist as Video*\n\nUsage: `/playlistv2 \\<url\\>`\n\nI'll show you a preview of the first few tracks, then you choose how many to download\\.\nAll tracks download as video \\(MP4\\)\\.\n\nExample:\n`/playlistv2 https://www.youtube.com/playlist?list=...`"
-        } else {
-            "🎵 *Download Playlist*\n\nUsage: `/playlist \\<url\\>`\n\nI'll show you a preview of the first few tracks, then you can choose:\n• How many tracks to download\n• Audio or video format\n\nExample:\n`/playlist https://www.youtube.com/playlist?list=...`"
-        };
-        bot.send_message(msg.chat.id, help)
-            .parse_mode(ParseMode::MarkdownV2)
+    if !is_admin {
+        bot.send_message(msg.chat.id, decorate("🔒 Admin Command\n\nThis command is restricted to administrators only."))
             .await?;
         return Ok(());
     }
 
-    // Detect link type
-    if let Some(link) = crate::link_detector::detect_first_link(&url) {
-        // Accept both playlists and single videos
-        match link {
-            crate::link_detector::DetectedLink::YoutubePlaylist { .. } => {
-                // Proceed with playlist preview
-            }
-            crate::link_detector::DetectedLink::YoutubeVideo { .. }
-            | crate::link_detector::DetectedLink::YoutubeShort { .. }
-            | crate::link_detector::DetectedLink::YoutubeMusic { .. } => {
-                // For single videos: treat as single-item playlist and download directly
-                // Show format selection instead of preview
-                return cmd_download(bot, msg, link.url().to_string(), state).await;
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+
+    let arg = arg.trim();
+    if arg.is_empty() {
+        let mut text = String::from("Allowlist\n\n");
+        match &state.allowed_users {
+            Some(env_users) => {
+                text.push_str(&format!("ALLOWED_USERS: {}\n", env_users.len()));
             }
-            _ => {
-                bot.send_message(msg.chat.id, "❌ This is not a supported YouTube link.\n\n✓ Playlists\n✓ Videos\n✓ Shorts\n\nPlease check the URL and try again.").await?;
-                return Ok(());
+            None => text.push_str("Allowlist mode is off (bot is public).\n"),
+        }
+        match hermes_shared::db::list_allowed_users(pool).await {
+            Ok(ids) if ids.is_empty() => text.push_str("Database: (empty)\n"),
+            Ok(ids) => {
+                text.push_str(&format!("Database ({}):\n", ids.len()));
+                for id in ids {
+                    text.push_str(&format!("  {}\n", id));
+                }
             }
+            Err(e) => text.push_str(&format!("Database: error ({})\n", e)),
         }
-    } else {
-        bot.send_message(msg.chat.id, "❌ Could not detect a valid URL. Please check and try again.").await?;
+        text.push_str("\nUsage: /allowuser <chat_id>, /denyuser <chat_id>");
+        bot.send_message(msg.chat.id, text).await?;
         return Ok(());
     }
 
-    // Check if this is a Radio Mix (list=RD pattern)
-    // Radio Mixes are infinite and slow to preview, so skip to track selection
-    // Match list=RD as a URL parameter (preceded by ? or &), not as part of a video ID
-    let is_radio_mix = url.contains("?list=RD") || url.contains("&list=RD");
-    if is_radio_mix {
-        let key = format!("{:x}", chrono::Utc::now().timestamp_millis());
-        state.playlist_store.store(key.clone(), PlaylistPending {
-            url: url.to_string(),
-            chat_id: msg.chat.id.0,
-            message_id: msg.id,
-            is_single: false,
-            limit: Some(10),
-            video_only,
-            created_at: std::time::Instant::now(),
-        }).await;
-
-        // For Radio Mixes, go straight to track limit selection (skip preview)
-        let buttons = vec![
-            vec![
-                InlineKeyboardButton::callback("🎵 10 tracks",  encode_playlist_limit(&key, 10)),
-                InlineKeyboardButton::callback("🎵 25 tracks",  encode_playlist_limit(&key, 25)),
-            ],
-            vec![
-                InlineKeyboardButton::callback("🎵 50 tracks",  encode_playlist_limit(&key, 50)),
-                InlineKeyboardButton::callback("🎵 All tracks", encode_playlist_limit(&key, 0)),
-            ],
-        ];
-        bot.send_message(msg.chat.id, "🎵 Radio Mix detected\n\n\\(Infinite playlist \\- skipping preview\\)\n\nHow many tracks to download?")
-            .parse_mode(ParseMode::MarkdownV2)
-            .reply_markup(InlineKeyboardMarkup::new(buttons))
-            .await?;
+    let Ok(target) = arg.parse::<i64>() else {
+        bot.send_message(msg.chat.id, decorate("⚠️ Invalid chat ID. Usage: /allowuser <chat_id>")).await?;
         return Ok(());
-    }
-
-    let task_id = uuid::Uuid::new_v4().to_string();
-    let status = bot.send_message(msg.chat.id, "🎵 Fetching playlist info...").await?;
-
-    // Send preview request
-    let req = playlist_preview_request(&task_id, &url, 5);
-    let mut rx = match state.dispatcher.send(&req).await {
-        Ok(rx) => rx,
-        Err(e) => {
-            bot.edit_message_text(msg.chat.id, status.id, format!("❌ Worker error: {}", e)).await?;
-            return Ok(());
-        }
     };
 
-    // Wait for response (with timeout)
-    match tokio::time::timeout(std::time::Duration::from_secs(30), rx.recv()).await {
-        Ok(Some(response)) => {
-            let resp: IPCResponse = response;
-            if resp.is_error() {
-                let err_msg = resp.error_message().unwrap_or_else(|| "Unknown error".to_string());
-                bot.edit_message_text(msg.chat.id, status.id, format!("❌ Error: {}", err_msg)).await?;
-                return Ok(());
+    let reply = if allow {
+        match hermes_shared::db::add_allowed_user(pool, target).await {
+            Ok(_) => {
+                info!("Admin allowlisted chat {}", target);
+                format!("✅ {} added to the allowlist.", target)
             }
+            Err(e) => format!("❌ Failed to update allowlist: {}", e),
+        }
+    } else {
+        match hermes_shared::db::remove_allowed_user(pool, target).await {
+            Ok(true) => {
+                info!("Admin removed chat {} from allowlist", target);
+                let mut text = format!("✅ {} removed from the allowlist.", target);
+                if state.allowed_users.as_ref().is_some_and(|u| u.contains(&target)) {
+                    text.push_str("\n\nNote: this ID is also in ALLOWED_USERS and stays allowed until the env is changed.");
+                }
+                text
+            }
+            Ok(false) => format!("ℹ️ {} was not on the allowlist.", target),
+            Err(e) => format!("❌ Failed to update allowlist: {}", e),
+        }
+    };
 
-            if resp.is_done() {
-                // Parse response data
-                if let Some(data) = resp.data.as_object() {
-                    let title = data.get("playlist_title")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("Playlist");
-                    let count = data.get("playlist_count")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0);
-                    let empty_vec = Vec::new();
-                    let tracks = data.get("tracks")
-                        .and_then(|v| v.as_array())
-                        .unwrap_or(&empty_vec);
-
-                    // Format message
-                    let safe_title = escape_markdown_v2(title);
-                    let mut msg_text = format!("🎵 **{}**\n\n", safe_title);
-
-                    // Show track count or note if unknown (infinite playlists)
-                    if count > 0 {
-                        msg_text.push_str(&format!("📊 {} tracks total\n\n", count));
-                    } else {
-                        msg_text.push_str("📊 Total tracks: Unknown \\(infinite or uncountable playlist\\)\n\n");
-                    }
-
-                    // Show first few tracks
-                    msg_text.push_str("**Preview \\(first tracks\\):**\n");
-                    for track in tracks.iter().take(5) {
-                        if let Some(track_obj) = track.as_object() {
-                            if let (Some(idx), Some(track_title)) = (
-                                track_obj.get("index").and_then(|v| v.as_u64()),
-                                track_obj.get("title").and_then(|v| v.as_str()),
-                            ) {
-                                let safe_track_title = escape_markdown_v2(track_title);
-                                msg_text.push_str(&format!("{}\\. {}\n", idx, safe_track_title));
-                            }
-                        }
-                    }
-
-                    if tracks.len() > 5 {
-                        if count > 5 {
-                            msg_text.push_str(&format!("\n\\.\\.\\. and {} more\n", count - 5));
-                        } else {
-                            msg_text.push_str("\n\\.\\.\\. and more available\n");
-                        }
-                    } else {
-                        msg_text.push('\n');
-                    }
-
-                    msg_text.push_str("\n**Choose how many tracks to download:**");
+    bot.send_message(msg.chat.id, decorate(reply)).await?;
+    Ok(())
+}
 
-                    // Update message with preview + button
-                    // Encode video_only flag: "pl_dl:v:URL" for video-only, "pl_dl:a:URL" for normal
-                    let dl_flag = if video_only { "v" } else { "a" };
-                    let keyboard = InlineKeyboardMarkup::new(vec![
-                        vec![InlineKeyboardButton::callback("⬇️ Download", format!("pl_dl:{}:{}", dl_flag, url))],
-                    ]);
+/// /ban and /unban — block or unblock a chat (admin only). Banned chats can't
+/// use the bot or start downloads through the API. With no argument, lists bans.
+async fn cmd_ban(
+    bot: Bot,
+    msg: Message,
+    arg: String,
+    ban: bool,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    if state.admin_chat_id != Some(msg.chat.id.0) {
+        bot.send_message(msg.chat.id, decorate("🔒 Admin Command\n\nThis command is restricted to administrators only."))
+            .await?;
+        return Ok(());
+    }
 
-                    bot.edit_message_text(msg.chat.id, status.id, msg_text)
-                        .parse_mode(ParseMode::MarkdownV2)
-                        .reply_markup(keyboard)
-                        .await?;
-                } else {
-                    bot.edit_message_text(msg.chat.id, status.id, "Could not parse playlist info").await?;
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+
+    let arg = arg.trim();
+    if arg.is_empty() {
+        let mut text = String::from("Banned users\n\n");
+        match hermes_shared::db::list_banned_users(pool).await {
+            Ok(ids) if ids.is_empty() => text.push_str("(none)\n"),
+            Ok(ids) => {
+                for id in ids {
+                    text.push_str(&format!("  {}\n", id));
                 }
             }
+            Err(e) => text.push_str(&format!("Error: {}\n", e)),
         }
-        Ok(None) => {
-            bot.edit_message_text(msg.chat.id, status.id, "Worker disconnected unexpectedly").await?;
+        text.push_str("\nUsage: /ban <chat_id>, /unban <chat_id>");
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
+    let Ok(target) = arg.parse::<i64>() else {
+        let usage = if ban { "/ban <chat_id>" } else { "/unban <chat_id>" };
+        bot.send_message(msg.chat.id, decorate(format!("⚠️ Invalid chat ID. Usage: {}", usage))).await?;
+        return Ok(());
+    };
+    if ban && state.admin_chat_id == Some(target) {
+        bot.send_message(msg.chat.id, decorate("⚠️ The admin can't be banned.")).await?;
+        return Ok(());
+    }
+
+    let reply = match hermes_shared::db::set_user_banned(pool, target, ban).await {
+        Ok(true) if ban => {
+            info!("Admin banned chat {}", target);
+            for task in state.task_queue.get_user_tasks(target).await {
+                stop_task(&state, &task.task_id).await;
+            }
+            format!("✅ {} is banned.", target)
         }
-        Err(_) => {
-            bot.edit_message_text(msg.chat.id, status.id, "Request timed out").await?;
+        Ok(true) => {
+            info!("Admin unbanned chat {}", target);
+            format!("✅ {} is no longer banned.", target)
         }
-    }
+        Ok(false) if ban => format!("ℹ️ {} was already banned.", target),
+        Ok(false) => format!("ℹ️ {} was not banned.", target),
+        Err(e) => format!("❌ Failed to update ban: {}", e),
+    };
 
+    bot.send_message(msg.chat.id, decorate(reply)).await?;
     Ok(())
 }
 
-/// /search <query> - Search YouTube
-async fn cmd_search(
+/// Quota fields `/quota set` accepts, with the unit multiplier for stored values.
+const QUOTA_FIELDS: [(&str, i64); 4] = [
+    ("daily", 1),
+    ("daily_mb", 1024 * 1024),
+    ("weekly", 1),
+    ("weekly_mb", 1024 * 1024),
+];
+
+/// Human-readable quota standing for `/quota`.
+fn format_quota(status: &hermes_shared::quota::QuotaStatus) -> String {
+    if status.unlimited {
+        return "No limits apply.".to_string();
+    }
+    let limit = |used: u64, limit: Option<u64>, unit: u64, suffix: &str| match limit {
+        Some(l) => format!("{}/{}{} ({} left)", used / unit, l / unit, suffix, l.saturating_sub(used) / unit),
+        None => format!("{}{} (no limit)", used / unit, suffix),
+    };
+    let mb = 1024 * 1024;
+    let mut text = String::new();
+    for (name, w) in [("Last 24 hours", &status.daily), ("Last 7 days", &status.weekly)] {
+        text.push_str(&format!(
+            "{}\n  Downloads: {}\n  Data: {}\n",
+            name,
+            limit(w.downloads_used, w.downloads_limit, 1, ""),
+            limit(w.bytes_used, w.bytes_limit, mb, " MB"),
+        ));
+    }
+    text
+}
+
+/// /quota - Show the remaining download allowance.
+/// Admins: /quota <chat_id>, /quota set <chat_id> <field> <value|default>,
+/// /quota reset <chat_id>.
+async fn cmd_quota(
     bot: Bot,
     msg: Message,
-    query: String,
+    args: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let query = query.trim().to_string();
-    if query.is_empty() {
-        bot.send_message(msg.chat.id, "🔍 *Search YouTube*\n\nUsage: `/search <query>`\n\nExample:\n`/search billie eilish`")
-            .parse_mode(ParseMode::MarkdownV2)
+    let chat_id = msg.chat.id;
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(chat_id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+    let is_admin = state.admin_chat_id == Some(chat_id.0);
+    let parts: Vec<&str> = args.split_whitespace().collect();
+
+    if parts.is_empty() {
+        let text = if is_admin {
+            "📊 Quota\n\nYou are the admin: no limits apply.\n\n\
+             Usage: /quota <chat_id>\n\
+             /quota set <chat_id> <daily|daily_mb|weekly|weekly_mb> <n|default>\n\
+             /quota set <chat_id> unlimited <on|off>\n\
+             /quota reset <chat_id>".to_string()
+        } else {
+            match hermes_shared::db::get_quota_status(pool, chat_id.0).await {
+                Ok(status) => format!("📊 Your quota\n\n{}", format_quota(&status)),
+                Err(e) => format!("❌ Failed to read quota: {}", e),
+            }
+        };
+        bot.send_message(chat_id, decorate(text)).await?;
+        return Ok(());
+    }
+
+    if !is_admin {
+        bot.send_message(chat_id, decorate("🔒 Admin Command\n\nOnly administrators can view or change other users' quotas."))
             .await?;
         return Ok(());
     }
 
-    let task_id = Uuid::new_v4().to_string();
-    let request = search_request(&task_id, &query, 10);
+    let (action, rest) = match parts[0] {
+        "set" | "reset" => (parts[0], &parts[1..]),
+        _ => ("show", &parts[..]),
+    };
+    let Some(Ok(target)) = rest.first().map(|s| s.parse::<i64>()) else {
+        bot.send_message(chat_id, decorate("⚠️ Invalid chat ID. Usage: /quota <chat_id>")).await?;
+        return Ok(());
+    };
 
-    let searching_msg = bot.send_message(msg.chat.id, format!(
-        "🔍 Searching for: {}\n⏳ Please wait...",
-        query
-    ))
-        .await?;
+    let reply = match action {
+        "reset" => match hermes_shared::db::delete_quota_override(pool, target).await {
+            Ok(true) => {
+                info!("Admin reset quota for chat {}", target);
+                format!("✅ {} is back on the default quota.", target)
+            }
+            Ok(false) => format!("ℹ️ {} already uses the default quota.", target),
+            Err(e) => format!("❌ Failed to update quota: {}", e),
+        },
+        "set" => {
+            let (Some(field), Some(value)) = (rest.get(1), rest.get(2)) else {
+                bot.send_message(chat_id, decorate("Usage: /quota set <chat_id> <daily|daily_mb|weekly|weekly_mb|unlimited> <value>")).await?;
+                return Ok(());
+            };
+            let mut quota = match hermes_shared::db::get_quota_override(pool, target).await {
+                Ok(q) => q.unwrap_or_default(),
+                Err(e) => {
+                    bot.send_message(chat_id, decorate(format!("❌ Failed to read quota: {}", e))).await?;
+                    return Ok(());
+                }
+            };
+            quota.chat_id = target;
 
-    match state.dispatcher.send_and_wait(&request, 30).await {
-        Ok(response) => {
-            if response.is_error() {
-                let err = response.error_message().unwrap_or_else(|| "Search failed".into());
-                bot.edit_message_text(msg.chat.id, searching_msg.id, format!(
-                    "❌ *Search Error*\n\n{}", err
-                ))
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await?;
+            let field = field.to_lowercase();
+            if field == "unlimited" {
+                quota.unlimited = matches!(value.to_lowercase().as_str(), "on" | "true" | "yes" | "1");
             } else {
-                let results = response.data.get("results")
-                    .and_then(|v| v.as_array())
-                    .cloned()
-                    .unwrap_or_default();
-
-                if results.is_empty() {
-                    bot.edit_message_text(msg.chat.id, searching_msg.id,
-                        format!("😕 No results found for \"{}\"", query)
-                    ).await?;
-                } else {
-                    // Build (url, title) pairs
-                    let items: Vec<(String, String)> = results.iter().map(|r| {
-                        let url   = r.get("url").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                        let title = r.get("title").and_then(|v| v.as_str()).unwrap_or("?").to_string();
-                        (url, title)
-                    }).collect();
-
-                    // Store for callback retrieval (peek — buttons stay active)
-                    let key: String = task_id[..6].to_string();
-                    state.search_store.store(key.clone(), SearchPending {
-                        results: items.iter().map(|(url, title)| SearchResultItem {
-                            url:   url.clone(),
-                            title: title.clone(),
-                        }).collect(),
-                        created_at: std::time::Instant::now(),
-                    }).await;
-
-                    // One button per result, truncated to 52 chars
-                    let buttons: Vec<Vec<InlineKeyboardButton>> = items.iter()
-                        .enumerate()
-                        .map(|(i, (_, title))| {
-                            let label: String = if title.chars().count() > 52 {
-                                format!("{}…", title.chars().take(51).collect::<String>())
-                            } else {
-                                title.clone()
-                            };
-                            vec![InlineKeyboardButton::callback(label, encode_search_callback(&key, i))]
-                        })
-                        .collect();
-
-                    let from_cache = response.data.get("from_cache")
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(false);
-                    let cache_note = if from_cache { " · cached" } else { "" };
-                    let text = format!("Search: \"{}\"{}  —  tap to download:", query, cache_note);
+                let Some(&(_, unit)) = QUOTA_FIELDS.iter().find(|(name, _)| *name == field) else {
+                    bot.send_message(chat_id, decorate(format!(
+                        "⚠️ Unknown field \"{}\". Use daily, daily_mb, weekly, weekly_mb or unlimited.", field
+                    ))).await?;
+                    return Ok(());
+                };
+                let parsed = match value.to_lowercase().as_str() {
+                    "default" => None,
+                    v => match v.parse::<i64>() {
+                        Ok(n) if n >= 0 => Some(n * unit),
+                        _ => {
+                            bot.send_message(chat_id, decorate("⚠️ Use a whole number (0 = no limit) or \"default\".")).await?;
+                            return Ok(());
+                        }
+                    },
+                };
+                match field.as_str() {
+                    "daily" => quota.daily_downloads = parsed,
+                    "daily_mb" => quota.daily_bytes = parsed,
+                    "weekly" => quota.weekly_downloads = parsed,
+                    _ => quota.weekly_bytes = parsed,
+                }
+            }
 
-                    bot.edit_message_text(msg.chat.id, searching_msg.id, text)
-                        .reply_markup(InlineKeyboardMarkup::new(buttons))
-                        .await?;
+            match hermes_shared::db::set_quota_override(pool, &quota).await {
+                Ok(()) => {
+                    info!("Admin set quota {} for chat {}", field, target);
+                    match hermes_shared::db::get_quota_status(pool, target).await {
+                        Ok(status) => format!("✅ Quota updated for {}\n\n{}", target, format_quota(&status)),
+                        Err(_) => format!("✅ Quota updated for {}", target),
+                    }
                 }
+                Err(e) => format!("❌ Failed to update quota: {}", e),
             }
         }
-        Err(e) => {
-            error!("Search IPC failed: {}", e);
-            bot.edit_message_text(msg.chat.id, searching_msg.id, format!(
-                "Search error: {}", e
-            )).await?;
+        _ => match hermes_shared::db::get_quota_status(pool, target).await {
+            Ok(status) => format!("📊 Quota for {}\n\n{}", target, format_quota(&status)),
+            Err(e) => format!("❌ Failed to read quota: {}", e),
+        },
+    };
+
+    bot.send_message(chat_id, decorate(reply)).await?;
+    Ok(())
+}
+
+/// /setting [key] [value] - View or change generic user settings
+/// (same store as the dashboard's /api/user/settings).
+async fn cmd_setting(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    use hermes_shared::user_settings;
+
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+    let chat_id = msg.chat.id.0;
+
+    let args = args.trim();
+    let (key, value) = match args.split_once(char::is_whitespace) {
+        Some((k, v)) => (k, Some(v.trim())),
+        None => (args, None),
+    };
+
+    if key.is_empty() {
+        let mut text = String::from("Settings:\n");
+        for def in user_settings::REGISTRY {
+            let current = hermes_shared::db::get_user_setting_or_default(pool, chat_id, def.key).await;
+            text.push_str(&format!("\n{} = {}\n  {}\n", def.key, current, def.description));
         }
+        text.push_str("\nChange with /setting <key> <value>");
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
+    if user_settings::find(key).is_none() {
+        bot.send_message(msg.chat.id, decorate(format!(
+            "⚠️ Unknown setting '{}'. Use /setting to list them.", key
+        ))).await?;
+        return Ok(());
     }
 
+    match value {
+        None => {
+            let current = hermes_shared::db::get_user_setting_or_default(pool, chat_id, key).await;
+            bot.send_message(msg.chat.id, format!("{} = {}", key, current)).await?;
+        }
+        Some(raw) => match user_settings::validate(key, raw) {
+            Ok(v) => {
+                match hermes_shared::db::set_user_setting(pool, chat_id, key, &v).await {
+                    Ok(_) => {
+                        bot.send_message(msg.chat.id, decorate(format!("✅ {} = {}", key, v))).await?;
+                    }
+                    Err(e) => {
+                        error!("Failed to save setting {}: {}", key, e);
+                        bot.send_message(msg.chat.id, decorate("❌ Failed to save setting")).await?;
+                    }
+                }
+            }
+            Err(msg_text) => {
+                bot.send_message(msg.chat.id, decorate(format!("⚠️ {}", msg_text))).await?;
+            }
+        },
+    }
     Ok(())
 }
 
-/// /status - Show active task status
-async fn cmd_status(
+/// /settings - Download defaults (mode, audio format/quality, video quality,
+/// dedup) as buttons. Same preferences as the dashboard's /api/user/preferences.
+async fn cmd_settings(
     bot: Bot,
     msg: Message,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let stats = state.task_queue.stats().await;
-    let user_tasks = state.task_queue.get_user_tasks(msg.chat.id.0).await;
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+    let prefs = hermes_shared::db::get_user_preferences(pool, msg.chat.id.0).await;
+    bot.send_message(msg.chat.id, decorate(SETTINGS_HEADER))
+        .reply_markup(settings_keyboard(&prefs))
+        .await?;
+    Ok(())
+}
 
-    let mut text = format!(
-        "Queue Status:\n\
-         Running: {}/{}\n\
-         Queued: {}\n\
-         Completed: {}\n\
-         Failed: {}\n",
-        stats.running, stats.max_concurrent,
-        stats.queued, stats.completed, stats.failed,
-    );
+const SETTINGS_HEADER: &str = "⚙️ Download settings\nTap a button to change it. Links you send use these defaults.";
 
-    if !user_tasks.is_empty() {
-        text.push_str("\nYour tasks:\n");
-        for task in user_tasks.iter().take(10) {
-            let bar = progress_bar(task.progress);
-            text.push_str(&format!(
-                "  {} {:?} {} {}%\n",
-                &task.task_id[..8], task.status, bar, task.progress
-            ));
+/// One button per preference, each showing its current value.
+fn settings_keyboard(prefs: &hermes_shared::models::UserPreferences) -> InlineKeyboardMarkup {
+    let mode = if prefs.default_mode == "video" { "🎬 Video" } else { "🎵 Audio" };
+    let audio_quality = match prefs.audio_quality.as_str() {
+        "0" => "Best (VBR)".to_string(),
+        kbps => format!("{} kbps", kbps),
+    };
+    let video_quality = match prefs.video_quality.as_str() {
+        "best" => "Best (up to 1080p)".to_string(),
+        height => format!("{}p", height),
+    };
+    let button = |label: String, action| {
+        vec![InlineKeyboardButton::callback(decorate(label), encode_settings_callback(action))]
+    };
+    InlineKeyboardMarkup::new(vec![
+        button(format!("Default mode: {}", mode), SettingsAction::Mode),
+        button(format!("Audio format: {}", prefs.audio_format.to_uppercase()), SettingsAction::AudioFormat),
+        button(format!("Audio quality: {}", audio_quality), SettingsAction::AudioQuality),
+        button(format!("Video quality: {}", video_quality), SettingsAction::VideoQuality),
+        button(format!("Track dedup: {}", if prefs.dedup_enabled { "On" } else { "Off" }), SettingsAction::Dedup),
+        vec![
+            InlineKeyboardButton::callback(decorate("↩️ Reset to defaults"), encode_settings_callback(SettingsAction::Reset)),
+            InlineKeyboardButton::callback(decorate("✖ Close"), encode_settings_callback(SettingsAction::Close)),
+        ],
+    ])
+}
+
+/// The option after `current` in `options`, wrapping around.
+fn next_option(options: &[&str], current: &str) -> String {
+    let i = options.iter().position(|o| *o == current).map_or(0, |i| (i + 1) % options.len());
+    options[i].to_string()
+}
+
+/// Handle a /settings button: cycle the tapped preference (or reset them all),
+/// save and redraw the keyboard, or close it.
+async fn handle_settings_callback(
+    bot: Bot,
+    q: &CallbackQuery,
+    action: SettingsAction,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    use hermes_shared::models::{AUDIO_FORMATS, AUDIO_QUALITIES, DOWNLOAD_MODES, VIDEO_QUALITIES};
+
+    let _ = bot.answer_callback_query(&q.id).await;
+    let (Some(message), Some(pool)) = (q.message.clone(), state.db_pool.clone()) else {
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+
+    let mut prefs = hermes_shared::db::get_user_preferences(&pool, chat_id.0).await;
+    match action {
+        SettingsAction::Close => {
+            let _ = bot.edit_message_text(chat_id, message.id, decorate("⚙️ Download settings saved.")).await;
+            return Ok(());
         }
-    } else {
-        text.push_str("\nNo active tasks.");
+        SettingsAction::Mode => prefs.default_mode = next_option(DOWNLOAD_MODES, &prefs.default_mode),
+        SettingsAction::AudioFormat => prefs.audio_format = next_option(AUDIO_FORMATS, &prefs.audio_format),
+        SettingsAction::AudioQuality => prefs.audio_quality = next_option(AUDIO_QUALITIES, &prefs.audio_quality),
+        SettingsAction::VideoQuality => prefs.video_quality = next_option(VIDEO_QUALITIES, &prefs.video_quality),
+        SettingsAction::Dedup => prefs.dedup_enabled = !prefs.dedup_enabled,
+        SettingsAction::Reset => prefs = hermes_shared::models::UserPreferences::default(),
     }
 
-    bot.send_message(msg.chat.id, text).await?;
+    if let Err(e) = hermes_shared::db::update_user_preferences(&pool, chat_id.0, &prefs).await {
+        error!("Failed to save preferences for chat {}: {}", chat_id, e);
+        let _ = bot.send_message(chat_id, decorate("❌ Failed to save setting")).await;
+        return Ok(());
+    }
+    let _ = bot.edit_message_reply_markup(chat_id, message.id)
+        .reply_markup(settings_keyboard(&prefs))
+        .await;
     Ok(())
 }
 
-/// /cancel <task_id> - Cancel a running task
-async fn cmd_cancel(
+/// /save <url> [note] - Put a link on the watch-later list
+async fn cmd_save(
     bot: Bot,
     msg: Message,
-    task_id_prefix: String,
+    args: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let prefix = task_id_prefix.trim().to_string();
-    if prefix.is_empty() {
-        bot.send_message(msg.chat.id, "❌ *Cancel Download*\n\nUsage: `/cancel <task-id>`\n\nGet task IDs using `/status`")
-            .parse_mode(ParseMode::MarkdownV2)
-            .await?;
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+
+    let args = args.trim();
+    let (url, note) = match args.split_once(char::is_whitespace) {
+        Some((url, note)) => (url, Some(note.trim()).filter(|n| !n.is_empty())),
+        None => (args, None),
+    };
+    if url.is_empty() {
+        bot.send_message(msg.chat.id,
+            "Usage: /save <url> [note]\n\n\
+             Keeps the link for later. /saved lists your links with buttons to \
+             download them as audio or video, or remove them."
+        ).await?;
         return Ok(());
     }
+    let Some(link) = link_detector::detect_first_link(url) else {
+        bot.send_message(msg.chat.id, decorate("❌ Could not detect a valid URL. Please check and try again.")).await?;
+        return Ok(());
+    };
+    let note: Option<String> = note.map(|n| n.chars().take(200).collect());
 
-    // Find matching task
-    let user_tasks = state.task_queue.get_user_tasks(msg.chat.id.0).await;
-    let matching = user_tasks.iter().find(|t| t.task_id.starts_with(&prefix));
+    let chat_id = msg.chat.id.0;
+    let count = hermes_shared::db::count_saved_items(pool, chat_id).await.unwrap_or(0);
+    if count >= MAX_SAVED_ITEMS {
+        bot.send_message(msg.chat.id, decorate(format!(
+            "⚠️ Your list is full ({}/{}). Remove something in /saved first.",
+            count, MAX_SAVED_ITEMS
+        ))).await?;
+        return Ok(());
+    }
 
-    match matching {
-        Some(task) => {
-            let full_id = task.task_id.clone();
-            state.task_queue.cancel(&full_id).await;
-            state.dispatcher.remove_pending(&full_id).await;
-            bot.send_message(msg.chat.id, format!(
-                "Cancelled task [{}]", &full_id[..8]
-            )).await?;
+    match hermes_shared::db::create_saved_item(pool, chat_id, link.url(), note.as_deref()).await {
+        Ok(Some(id)) => {
+            info!("Chat {} saved {} (#{})", chat_id, link.url(), id);
+            bot.send_message(msg.chat.id, decorate(format!(
+                "🕒 Saved [#{}]\n{}\n\nFind it in /saved.", id, link.url()
+            ))).await?;
         }
-        None => {
-            bot.send_message(msg.chat.id, format!(
-                "No task found matching \"{}\".\nUse /status to see task IDs.", prefix
-            )).await?;
+        Ok(None) => {
+            bot.send_message(msg.chat.id, "This link is already in /saved.").await?;
+        }
+        Err(e) => {
+            error!("Failed to save item: {}", e);
+            bot.send_message(msg.chat.id, decorate("❌ Failed to save the link")).await?;
         }
     }
-
     Ok(())
 }
 
-/// /history - Show download history
-async fn cmd_history(bot: Bot, msg: Message) -> ResponseResult<()> {
-    bot.send_message(msg.chat.id, "Download history coming soon.\nUse /status to see active tasks.").await?;
+/// /saved - The watch-later list, with download and remove buttons
+async fn cmd_saved(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
+    let (text, keyboard) = render_saved_list(pool, msg.chat.id.0).await;
+    bot.send_message(msg.chat.id, decorate(text)).reply_markup(keyboard).await?;
     Ok(())
 }
 
-/// /ping - Health check
-async fn cmd_ping(
+/// The /saved message: one entry per item, each with a row of
+/// Audio / Video / Remove buttons.
+async fn render_saved_list(pool: &SqlitePool, chat_id: i64) -> (String, InlineKeyboardMarkup) {
+    let items = hermes_shared::db::get_saved_items(pool, chat_id).await.unwrap_or_else(|e| {
+        warn!("Saved items lookup failed for {}: {}", chat_id, e);
+        Vec::new()
+    });
+    if items.is_empty() {
+        return (
+            "🕒 Nothing saved. Use /save <url> to keep a link for later.".to_string(),
+            InlineKeyboardMarkup::new(Vec::<Vec<InlineKeyboardButton>>::new()),
+        );
+    }
+
+    let shorten = |s: &str, max: usize| if s.chars().count() > max {
+        format!("{}…", s.chars().take(max - 1).collect::<String>())
+    } else {
+        s.to_string()
+    };
+    let mut text = format!("🕒 Saved for later ({}/{}):\n", items.len(), MAX_SAVED_ITEMS);
+    let mut rows = Vec::new();
+    let mut hidden = 0;
+    for item in &items {
+        let mut entry = format!("\n#{} · {}\n", item.id, item.created_at.format("%Y-%m-%d"));
+        if let Some(note) = &item.note {
+            entry.push_str(&format!("{}\n", shorten(note, 80)));
+        }
+        entry.push_str(&format!("{}\n", shorten(&item.url, 120)));
+        // Stay well under Telegram's 4096-char limit; every item keeps its buttons
+        if hidden == 0 && text.len() + entry.len() <= 3500 {
+            text.push_str(&entry);
+        } else {
+            hidden += 1;
+        }
+        rows.push(vec![
+            InlineKeyboardButton::callback(
+                decorate(format!("🎵 #{}", item.id)),
+                encode_saved_callback(SavedAction::Download { id: item.id, audio: true }),
+            ),
+            InlineKeyboardButton::callback(
+                decorate(format!("🎬 #{}", item.id)),
+                encode_saved_callback(SavedAction::Download { id: item.id, audio: false }),
+            ),
+            InlineKeyboardButton::callback(
+                decorate(format!("🗑 #{}", item.id)),
+                encode_saved_callback(SavedAction::Remove(item.id)),
+            ),
+        ]);
+    }
+    if hidden > 0 {
+        text.push_str(&format!("\n… and {} more (buttons below)", hidden));
+    }
+    (text, InlineKeyboardMarkup::new(rows))
+}
+
+/// Handle a /saved button: download the item in the chosen mode (it stays
+/// on the list), or remove it and redraw the list.
+async fn handle_saved_callback(
+    bot: Bot,
+    q: &CallbackQuery,
+    action: SavedAction,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let _ = bot.answer_callback_query(&q.id).await;
+    let (Some(message), Some(pool)) = (q.message.clone(), state.db_pool.clone()) else {
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+
+    match action {
+        SavedAction::Download { id, audio } => {
+            match hermes_shared::db::get_saved_item(&pool, chat_id.0, id).await.ok().flatten() {
+                Some(item) => {
+                    info!("Saved item #{} downloaded by chat {}", id, chat_id);
+                    download_url(bot, message, item.url, state, false, Some(audio)).await
+                }
+                None => {
+                    // Removed elsewhere (e.g. the dashboard): show the current list
+                    let (text, keyboard) = render_saved_list(&pool, chat_id.0).await;
+                    let _ = bot.edit_message_text(chat_id, message.id, decorate(text)).reply_markup(keyboard).await;
+                    Ok(())
+                }
+            }
+        }
+        SavedAction::Remove(id) => {
+            if let Err(e) = hermes_shared::db::delete_saved_item(&pool, chat_id.0, id).await {
+                error!("Failed to remove saved item {}: {}", id, e);
+            }
+            let (text, keyboard) = render_saved_list(&pool, chat_id.0).await;
+            let _ = bot.edit_message_text(chat_id, message.id, decorate(text)).reply_markup(keyboard).await;
+            Ok(())
+        }
+    }
+}
+
+/// /subscribe [video] <url> - Follow a playlist or channel for new uploads
+async fn cmd_subscribe(
     bot: Bot,
     msg: Message,
+    args: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    let task_id = Uuid::new_v4().to_string();
-    let request = health_check_request(&task_id);
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
+    };
 
-    match state.dispatcher.send_and_wait(&request, 10).await {
-        Ok(response) => {
-            let version = response.data.get("version")
-                .and_then(|v| v.as_str())
-                .unwrap_or("unknown");
-            let handlers = response.data.get("handlers")
-                .and_then(|v| v.as_array())
-                .map(|a| a.len())
-                .unwrap_or(0);
-            let stats = state.task_queue.stats().await;
-            bot.send_message(msg.chat.id, format!(
-                "✅ *System Status*\n\n\
-                 🤖 Worker: `{}`\n\
-                 ⚙️ Handlers: `{}`\n\
-                 ⏳ Queue: `{}/{}` running\n\n✓ All systems operational",
-                version, handlers, stats.running, stats.max_concurrent
-            ))
-                .parse_mode(ParseMode::MarkdownV2)
-                .await?;
+    let args = args.trim();
+    let (extract_audio, url) = match args.split_once(char::is_whitespace) {
+        Some(("video", rest)) => (false, rest.trim()),
+        Some(("audio", rest)) => (true, rest.trim()),
+        _ => (true, args),
+    };
+
+    if url.is_empty() {
+        bot.send_message(msg.chat.id,
+            "Usage:\n\
+             /subscribe <url> - New uploads as audio\n\
+             /subscribe video <url> - New uploads as video\n\n\
+             Works with YouTube playlists and channels."
+        ).await?;
+        return Ok(());
+    }
+
+    let url = match link_detector::detect_first_link(url) {
+        Some(link) if link.is_telegram() => None,
+        Some(DetectedLink::YoutubeVideo { .. })
+        | Some(DetectedLink::YoutubeShort { .. })
+        | Some(DetectedLink::YoutubeMusic { .. })
+        | Some(DetectedLink::YoutubeClip { .. })
+        | Some(DetectedLink::SpotifyTrack { .. }) => None,
+        Some(link) => Some(link.url().to_string()),
+        None => None,
+    };
+    let Some(url) = url else {
+        bot.send_message(msg.chat.id, "Please provide a playlist or channel URL.").await?;
+        return Ok(());
+    };
+
+    let chat_id = msg.chat.id.0;
+    let max = max_subscriptions_per_user();
+    let count = hermes_shared::db::count_user_subscriptions(pool, chat_id).await.unwrap_or(0);
+    if count >= max {
+        bot.send_message(msg.chat.id, decorate(format!(
+            "⚠️ Subscription limit reached ({}/{}). Use /unsubscribe to remove one.",
+            count, max
+        ))).await?;
+        return Ok(());
+    }
+
+    match hermes_shared::db::create_subscription(pool, chat_id, &url, extract_audio).await {
+        Ok(Some(id)) => {
+            info!("Chat {} subscribed to {} (#{})", chat_id, url, id);
+            bot.send_message(msg.chat.id, decorate(format!(
+                "🔔 Subscribed [#{}]\n{}\n\n\
+                 Checked every {}. The latest {} item(s) arrive on the first check, \
+                 then only new uploads.",
+                id, url, subscriptions::format_interval(subscription_interval_secs()), subscription_max_items()
+            ))).await?;
+        }
+        Ok(None) => {
+            bot.send_message(msg.chat.id, "You are already subscribed to this URL.").await?;
         }
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("🔴 *Worker Offline*\n\nError: {}", e))
-                .parse_mode(ParseMode::MarkdownV2)
-                .await?;
+            error!("Failed to create subscription: {}", e);
+            bot.send_message(msg.chat.id, decorate("❌ Failed to save subscription")).await?;
         }
     }
-
     Ok(())
 }
 
-/// /upcook <content> - Update cookies.txt (admin only)
-async fn cmd_upcook(
+/// /unsubscribe <id> - Remove one of the user's subscriptions
+async fn cmd_unsubscribe(
     bot: Bot,
     msg: Message,
-    content: String,
+    arg: String,
     state: Arc<AppState>,
 ) -> ResponseResult<()> {
-    // Admin-only check
-    let is_admin = state.admin_chat_id
-        .map(|id| id == msg.chat.id.0)
-        .unwrap_or(false);
-
-    if !is_admin {
-        bot.send_message(msg.chat.id, "🔒 Admin Command\n\nThis command is restricted to administrators only.")
-            .await?;
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
         return Ok(());
-    }
-
-    let content = content.trim().to_string();
-
-    // Strip surrounding brackets: /upcook [content] → content
-    let content = if content.starts_with('[') && content.ends_with(']') {
-        content[1..content.len()-1].trim().to_string()
-    } else {
-        content
     };
 
-    if content.is_empty() {
-        bot.send_message(msg.chat.id,
-            "Usage: /upcook [cookie content]\n\n\
-             Paste the Netscape cookie file content inside brackets."
-        ).await?;
+    let Ok(id) = arg.trim().trim_start_matches('#').parse::<i64>() else {
+        bot.send_message(msg.chat.id, "Usage: /unsubscribe <id>\n\nSee /subscriptions for IDs.").await?;
         return Ok(());
-    }
-
-    let cookie_path = std::env::var("YOUTUBE_COOKIE_FILE")
-        .unwrap_or_else(|_| "./cookies.txt".to_string());
-
-    // Resolve relative to WORKER_DIR
-    let worker_dir = std::env::var("WORKER_DIR").unwrap_or_else(|_| ".".to_string());
-    let full_path = if std::path::Path::new(&cookie_path).is_relative() {
-        std::path::PathBuf::from(&worker_dir).join(&cookie_path)
-    } else {
-        std::path::PathBuf::from(&cookie_path)
     };
 
-    match std::fs::write(&full_path, &content) {
-        Ok(_) => {
-            let size = content.len();
-            let lines = content.lines().count();
-            info!("Cookies updated by admin: {} ({} bytes, {} lines)", full_path.display(), size, lines);
-            bot.send_message(msg.chat.id, format!(
-                "Cookies updated!\nFile: {}\nSize: {} bytes ({} lines)",
-                full_path.display(), size, lines
-            )).await?;
+    match hermes_shared::db::delete_subscription(pool, msg.chat.id.0, id).await {
+        Ok(true) => {
+            let archive = subscription_archive_path(&state.download_dir, id);
+            let _ = tokio::fs::remove_file(&archive).await;
+            bot.send_message(msg.chat.id, decorate(format!("✅ Unsubscribed [#{}]", id))).await?;
+        }
+        Ok(false) => {
+            bot.send_message(msg.chat.id, decorate(format!("No subscription #{} found.", id))).await?;
         }
         Err(e) => {
-            error!("Failed to write cookies: {}", e);
-            bot.send_message(msg.chat.id, format!("Failed to write cookies: {}", e)).await?;
+            error!("Failed to delete subscription: {}", e);
+            bot.send_message(msg.chat.id, decorate("❌ Failed to remove subscription")).await?;
         }
     }
-
     Ok(())
 }
 
-/// Show playlist confirmation dialog — prompts user for playlist vs single video.
-async fn cmd_playlist_confirm(
-    bot: Bot,
-    msg: Message,
-    url: String,
-    state: Arc<AppState>,
-) -> ResponseResult<()> {
-    let chat_id = msg.chat.id;
-    let task_id = Uuid::new_v4().to_string();
-    let key     = task_id[..8].to_string();
-
-    let display_url = if url.len() > 60 {
-        format!("{}\u{2026}", &url[..59])
-    } else {
-        url.clone()
+/// /subscriptions - List the user's subscriptions
+async fn cmd_subscriptions(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
+    let Some(pool) = &state.db_pool else {
+        bot.send_message(msg.chat.id, decorate("❌ Database unavailable")).await?;
+        return Ok(());
     };
 
-    let buttons = vec![
-        vec![
-            InlineKeyboardButton::callback("🎵 Download Playlist", encode_playlist_confirm(&key, 'p')),
-            InlineKeyboardButton::callback("🎬 Single Video",      encode_playlist_confirm(&key, 's')),
-        ],
-        vec![
-            InlineKeyboardButton::callback("✖ Cancel", encode_playlist_confirm(&key, 'x')),
-        ],
-    ];
+    let subs = hermes_shared::db::get_user_subscriptions(pool, msg.chat.id.0)
+        .await
+        .unwrap_or_default();
 
-    let sent = bot.send_message(chat_id, format!(
-        "Playlist detected!\n{}\n\nDownload the full playlist or just this video?",
-        display_url
-    ))
-    .reply_markup(InlineKeyboardMarkup::new(buttons))
-    .await?;
+    if subs.is_empty() {
+        bot.send_message(msg.chat.id, "No subscriptions. Use /subscribe <url> to follow a playlist or channel.").await?;
+        return Ok(());
+    }
 
-    let pending = PlaylistPending {
-        url,
-        chat_id:    chat_id.0,
-        message_id: sent.id,
-        limit:      None,
-        is_single:  false,
-        video_only: false,
-        created_at: std::time::Instant::now(),
-    };
-    state.playlist_store.store(key, pending).await;
+    let mut text = format!("Subscriptions ({}/{}):\n", subs.len(), max_subscriptions_per_user());
+    for sub in &subs {
+        let mode = if sub.extract_audio { "audio" } else { "video" };
+        let checked = sub.last_checked_at
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "pending".to_string());
+        text.push_str(&format!("\n#{} [{}] {}\n  Last check: {}\n", sub.id, mode, sub.url, checked));
+    }
+    text.push_str("\nRemove with /unsubscribe <id>");
+
+    bot.send_message(msg.chat.id, decorate(text)).await?;
     Ok(())
 }
 
-/// Strip list= and related params from a YouTube URL to return a single-video URL.
-fn extract_single_video_url(url: &str) -> String {
-    // Handle https://www.youtube.com/watch?v=VIDEO_ID&list=PL...
-    if let Some(v_pos) = url.find("v=") {
-        let after = &url[v_pos + 2..];
-        let id_end = after.find('&').unwrap_or(after.len());
-        let video_id = &after[..id_end];
-        if video_id.len() == 11 {
-            return format!("https://www.youtube.com/watch?v={}", video_id);
-        }
-    }
-    // Handle https://youtu.be/VIDEO_ID?list=...  — strip query string
-    if url.contains("youtu.be/") {
-        if let Some(q_pos) = url.find('?') {
-            return url[..q_pos].to_string();
-        }
-    }
-    url.to_string()
+/// Download archive used to remember which items a subscription already delivered.
+fn subscription_archive_path(download_dir: &str, sub_id: i64) -> String {
+    std::path::PathBuf::from(download_dir)
+        .join("subscriptions")
+        .join(format!("{}_archive.txt", sub_id))
+        .to_string_lossy()
+        .to_string()
 }
 
-/// Show playlist confirmation dialog — prompts user for playlist vs single video.
-/// Handle plain messages (auto-detect links).
-pub async fn handle_message(
-    bot: Bot,
-    msg: Message,
-    state: Arc<AppState>,
-) -> ResponseResult<()> {
-    if let Some(text) = msg.text() {
-        // Track user in DB (captures username from Telegram)
-        if let Some(pool) = &state.db_pool {
-            let username = msg.from()
-                .and_then(|u| u.username.as_deref());
-            let _ = hermes_shared::db::upsert_user(pool, msg.chat.id.0, username).await;
+/// Ids of the `count` newest uploads at `url` (`channel_latest`), or `None`
+/// if the worker couldn't list any.
+async fn channel_latest_ids(state: &AppState, url: &str, count: u32) -> Option<Vec<String>> {
+    let task_id = Uuid::new_v4().to_string();
+    let request = channel_latest_request(&task_id, url, count);
+    match state.dispatcher.send_and_wait(&request, 120).await {
+        Ok(response) if response.is_done() => response.data.get("entries")
+            .and_then(|v| v.as_array())
+            .map(|entries| entries.iter()
+                .filter_map(|e| e.get("id")?.as_str().map(str::to_string))
+                .collect::<Vec<_>>())
+            .filter(|ids| !ids.is_empty()),
+        Ok(response) => {
+            warn!("Channel listing failed for {}: {:?}", url, response.error_message());
+            None
         }
-
-        let links = link_detector::detect_links(text);
-        if !links.is_empty() {
-            let first = &links[0];
-            if first.is_telegram() {
-                // Telegram links: forward all detected links
-                info!("Auto-detected {} Telegram link(s)", links.len());
-                cmd_telegram_forward(bot, msg, links, state).await?;
-            } else if first.is_supported() {
-                info!("Auto-detected link: {:?}", first);
-                if first.is_playlist() {
-                    cmd_playlist_confirm(bot, msg, first.url().to_string(), state).await?;
-                } else {
-                    cmd_download(bot, msg, first.url().to_string(), state).await?;
-                }
-            } else {
-                // Generic URL — let yt-dlp try it
-                info!("Generic link detected, passing to yt-dlp: {}", first.url());
-                cmd_download(bot, msg, first.url().to_string(), state).await?;
-            }
+        Err(e) => {
+            warn!("Channel listing failed for {}: {}", url, e);
+            None
         }
     }
-    Ok(())
 }
 
-/// /dedup_toggle - Toggle track deduplication for this user
-async fn cmd_dedup_toggle(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
-    let chat_id = msg.chat.id;
+/// Check one subscription for new items and deliver them.
+///
+/// Runs a playlist download against the subscription's own archive so
+/// previously delivered items are skipped; the user is only messaged
+/// when something new arrived.
+pub async fn check_subscription(
+    bot: &Bot,
+    state: &AppState,
+    sub: hermes_shared::models::Subscription,
+) {
+    let task_id = Uuid::new_v4().to_string();
+    let short_id = task_id[..8].to_string();
+    let chat_id = ChatId(sub.chat_id);
+    let out_dir = task_output_dir(state.storage.base_for(StorageKind::Playlist), sub.chat_id, &task_id);
+    let archive = subscription_archive_path(&state.download_dir, sub.id);
+    if let Some(parent) = std::path::Path::new(&archive).parent() {
+        let _ = tokio::fs::create_dir_all(parent).await;
+    }
 
     if let Some(pool) = &state.db_pool {
-        // Get current preference
-        let current = hermes_shared::db::get_user_dedup_preference(pool, chat_id.0)
-            .await
-            .unwrap_or(true);
-
-        // Toggle
-        let new_state = !current;
+        let _ = hermes_shared::db::mark_subscription_checked(pool, sub.id).await;
+    }
 
-        // Update database
-        if let Err(e) = hermes_shared::db::set_user_dedup_preference(pool, chat_id.0, new_state).await {
-            error!("Failed to set dedup preference: {}", e);
-            bot.send_message(chat_id, "❌ Failed to update deduplication setting").await?;
-            return Ok(());
+    // Cheap check first: only run the download pass when the newest uploads
+    // include something not in the archive yet. Without a listing (worker
+    // error, non-YouTube source) the download pass decides on its own.
+    if let Some(latest) = channel_latest_ids(state, &sub.url, subscription_max_items()).await {
+        let archived = tokio::fs::read_to_string(&archive).await.unwrap_or_default();
+        let unseen = subscriptions::unseen_ids(&latest, &archived);
+        if unseen.is_empty() {
+            info!("[{short_id}] Subscription #{}: nothing new", sub.id);
+            return;
         }
+        info!("[{short_id}] Subscription #{}: {} new upload(s)", sub.id, unseen.len());
+    }
 
-        let status = if new_state { "Enabled ✅" } else { "Disabled ❌" };
-        let message = format!(
-            "🔄 <b>Track Deduplication {}</b>\n\n\
-            <b>When enabled (default):</b>\n\
-            • Shared tracks use symlinks (saves space)\n\
-            • Automatic dedup across downloads\n\n\
-            <b>When disabled:</b>\n\
-            • You get fresh copies of each track\n\
-            • Uses more storage but fully independent",
-            status
-        );
+    let prefs = load_user_prefs(state, sub.chat_id).await;
+    let request = playlist_request_opts(
+        &task_id, &sub.url, &out_dir, Some(subscription_max_items()),
+        sub.extract_audio, Some(&archive), sub.chat_id,
+        Some(prefs.audio_format.as_str()),
+    ).with_http_options(&http_options_for(&sub.url));
+
+    info!("[{short_id}] Checking subscription #{} for chat {}", sub.id, sub.chat_id);
+    state.task_queue.enqueue(&task_id, sub.chat_id, "subscription", state.task_priority(sub.chat_id, "subscription")).await;
+    if !state.task_queue.acquire(&task_id).await {
+        return;
+    }
 
-        bot.send_message(chat_id, message)
-            .parse_mode(ParseMode::Html)
-            .await?;
-    } else {
-        bot.send_message(chat_id, "⚠️ Database not available").await?;
+    let mut rx = match state.dispatcher.send(&request, Priority::Bulk).await {
+        Ok(rx) => rx,
+        Err(e) => {
+            warn!("[{short_id}] Subscription check failed to reach worker: {}", e);
+            state.task_queue.fail(&task_id).await;
+            return;
+        }
+    };
+
+    let timeout = tokio::time::Duration::from_secs(1800);
+    let result = tokio::time::timeout(timeout, async {
+        while let Some(resp) = rx.recv().await {
+            if !resp.is_progress() {
+                return Some(resp);
+            }
+        }
+        None
+    }).await;
+    state.dispatcher.remove_pending(&task_id).await;
+
+    let response = match result {
+        Ok(Some(resp)) if resp.is_done() => resp,
+        Ok(Some(resp)) => {
+            warn!("[{short_id}] Subscription #{} check failed: {:?}", sub.id, resp.error_message());
+            state.task_queue.fail(&task_id).await;
+            return;
+        }
+        _ => {
+            warn!("[{short_id}] Subscription #{} check timed out or lost worker", sub.id);
+            state.task_queue.fail(&task_id).await;
+            return;
+        }
+    };
+    state.task_queue.complete(&task_id).await;
+
+    // Only items not already in the archive are new
+    let new_files: Vec<(String, String)> = response.data.get("files")
+        .and_then(|v| v.as_array())
+        .map(|files| files.iter()
+            .filter(|f| !f.get("cached").and_then(|v| v.as_bool()).unwrap_or(false))
+            .filter_map(|f| Some((
+                f.get("path")?.as_str()?.to_string(),
+                f.get("name").and_then(|v| v.as_str()).unwrap_or("track").to_string(),
+            )))
+            .collect())
+        .unwrap_or_default();
+
+    if new_files.is_empty() {
+        info!("[{short_id}] Subscription #{}: nothing new", sub.id);
+        return;
     }
 
-    Ok(())
+    let title = response.data.get("playlist_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(&sub.url);
+    let _ = bot.send_message(chat_id, decorate(format!(
+        "🔔 {} new item(s) from {} [#{}]", new_files.len(), title, sub.id
+    ))).await;
+
+    for (idx, (path, name)) in new_files.iter().enumerate() {
+        let fpath = std::path::PathBuf::from(path);
+        if fpath.exists() && is_allowed_output_file(name) {
+            send_media_file(bot, chat_id, &fpath, name).await;
+            if idx < new_files.len() - 1 {
+                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            }
+        }
+    }
 }
 
 /// /dedup_status - Show current deduplication status
@@ -2500,28 +7388,146 @@ async fn cmd_dedup_status(bot: Bot, msg: Message, state: Arc<AppState>) -> Respo
             icon, status_str, details
         );
 
-        bot.send_message(chat_id, message)
+        bot.send_message(chat_id, decorate(message))
             .parse_mode(ParseMode::Html)
             .await?;
     } else {
-        bot.send_message(chat_id, "⚠️ Database not available").await?;
+        bot.send_message(chat_id, decorate("⚠️ Database not available")).await?;
     }
 
     Ok(())
 }
 
-/// Escape special characters for Telegram MarkdownV2.
-/// Required characters to escape: _ * [ ] ( ) ~ ` > # + - = | { } . !
-fn escape_markdown_v2(text: &str) -> String {
-    text.chars()
-        .map(|c| match c {
-            '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|' | '{' | '}' | '.' | '!' => {
-                format!("\\{}", c)
-            }
-            '\\' => "\\\\".to_string(),
-            c => c.to_string(),
-        })
-        .collect()
+/// /sysinfo - Bot and worker process resource usage (admin only)
+async fn cmd_sysinfo(bot: Bot, msg: Message, state: Arc<AppState>) -> ResponseResult<()> {
+    let is_admin = state.admin_chat_id
+        .map(|id| id == msg.chat.id.0)
+        .unwrap_or(false);
+
+    if !is_admin {
+        bot.send_message(msg.chat.id, decorate("🔒 Admin Command\n\nThis command is restricted to administrators only."))
+            .await?;
+        return Ok(());
+    }
+
+    let uptime = state.started_at.elapsed().as_secs();
+    let mut text = format!("System Info\n\nUptime: {}\n", format_eta(uptime));
+
+    text.push_str("\nBot process:\n");
+    match sysinfo::process_stats(None) {
+        Some(p) => {
+            let cpu_pct = if uptime > 0 { p.cpu_secs / uptime as f64 * 100.0 } else { 0.0 };
+            text.push_str(&format!(
+                "  PID: {}\n  Memory: {} RSS / {} virt\n  CPU: {:.1}s total ({:.1}% avg)\n  Threads: {}\n  Open FDs: {}\n",
+                std::process::id(),
+                sysinfo::format_kb(p.rss_kb), sysinfo::format_kb(p.vm_kb),
+                p.cpu_secs, cpu_pct, p.threads,
+                p.open_fds.map(|n| n.to_string()).unwrap_or_else(|| "n/a".into()),
+            ));
+        }
+        None => text.push_str(&format!("  PID: {}\n  (resource stats n/a on this platform)\n", std::process::id())),
+    }
+
+    for (i, pid) in state.dispatcher.pids().await.into_iter().enumerate() {
+        text.push_str(&format!("\nWorker process #{}:\n", i + 1));
+        match pid {
+            Some(pid) => match sysinfo::process_stats(Some(pid)) {
+                Some(p) => text.push_str(&format!(
+                    "  PID: {}\n  Memory: {} RSS\n  CPU: {:.1}s total\n  Threads: {}\n",
+                    pid, sysinfo::format_kb(p.rss_kb), p.cpu_secs, p.threads,
+                )),
+                None => text.push_str(&format!("  PID: {}\n", pid)),
+            },
+            None => text.push_str("  Not running\n"),
+        }
+    }
+
+    if let Some((l1, l5, l15)) = sysinfo::load_average() {
+        text.push_str(&format!("\nLoad: {:.2} {:.2} {:.2}\n", l1, l5, l15));
+    }
+
+    let stats = state.task_queue.stats().await;
+    text.push_str(&format!(
+        "\nQueue:\n  Running: {}/{}\n  Queued: {}\n  Tracked: {}\n  Pending IPC: {}\n",
+        stats.running, stats.max_concurrent, stats.queued, stats.total_tracked,
+        state.dispatcher.pending_count().await,
+    ));
+    if let Some(stalled) = state.dispatcher.stdin_stalled_for().await {
+        text.push_str(&format!("  ⚠️ Worker stdin stalled for {}s\n", stalled.as_secs()));
+    }
+    text.push_str(&format!(
+        "\nCallback states:\n  Quality menus: {}\n  Searches: {}\n  Playlists: {}\n  Podcasts: {}\n  Re-downloads: {}",
+        state.callback_store.len().await,
+        state.search_store.len().await,
+        state.playlist_store.len().await,
+        state.podcast_store.len().await,
+        state.redownload_store.len().await,
+    ));
+
+    bot.send_message(msg.chat.id, decorate(text)).await?;
+    Ok(())
+}
+
+/// Caption placeholders from a `done` response: the audio tags when the file
+/// was tagged, else what yt-dlp reported (`media`), else the file name.
+fn caption_fields(response: &IPCResponse, request: &IPCRequest, filename: &str) -> caption::CaptionFields {
+    let audio = response.audio_metadata().unwrap_or_default();
+    let media = response.media_info().unwrap_or_default();
+    let stem = std::path::Path::new(filename).file_stem().and_then(|s| s.to_str()).map(String::from);
+    caption::CaptionFields {
+        title: audio.title.or(media.title).or(stem),
+        artist: audio.performer.or(media.artist).or(media.uploader),
+        duration: audio.duration.or(media.duration),
+        filename: Some(filename.to_string()),
+        url: request.url.clone(),
+    }
+}
+
+/// "🔁 Download again" / "🎬 Other quality" row for a finished single-file
+/// download. Clips, burned-in subtitles and playlists get none: a plain
+/// re-download wouldn't reproduce them.
+async fn redownload_keyboard(request: &IPCRequest, mode: &DownloadMode, state: &AppState) -> Option<InlineKeyboardMarkup> {
+    if request.action != IPCAction::YoutubeDl
+        || ["section_start", "burn_subtitles"].iter().any(|p| request.params.get(*p).is_some())
+    {
+        return None;
+    }
+    let url = request.url.clone().filter(|u| !u.is_empty())?;
+    let key = Uuid::new_v4().to_string()[..6].to_string();
+    state.redownload_store.store(key.clone(), RedownloadEntry {
+        url,
+        mode: mode.clone(),
+        created_at: std::time::Instant::now(),
+    }).await;
+    Some(InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(decorate("🔁 Download again"), encode_redownload_callback(&key, false)),
+        InlineKeyboardButton::callback(decorate("🎬 Other quality"), encode_redownload_callback(&key, true)),
+    ]]))
+}
+
+/// Handle a completion-message button: the same link again in the same mode,
+/// or the quality menu for it (as /dv or /da would show).
+async fn handle_redownload(
+    bot: Bot,
+    q: &CallbackQuery,
+    key: &str,
+    other_quality: bool,
+    state: Arc<AppState>,
+) -> ResponseResult<()> {
+    let Some(message) = q.message.clone() else { return Ok(()) };
+    let Some(entry) = state.redownload_store.peek(key).await else {
+        let _ = bot.edit_message_reply_markup(message.chat.id, message.id).await;
+        bot.send_message(message.chat.id, decorate("⌛ These buttons expired. Send the link again.")).await?;
+        return Ok(());
+    };
+    info!("Re-download ({}) for chat {}: {}",
+        if other_quality { "other quality" } else { "again" }, message.chat.id, entry.url);
+    if other_quality {
+        cmd_download_with_quality(bot, message, entry.url, entry.mode, state).await
+    } else {
+        let audio = entry.mode == DownloadMode::Audio;
+        download_url(bot, message, entry.url, state, false, Some(audio)).await
+    }
 }
 
 /// Generate a simple text progress bar.
@@ -2531,6 +7537,42 @@ fn progress_bar(percent: u8) -> String {
     format!("[{}{}]", "=".repeat(filled), " ".repeat(empty))
 }
 
+/// Format an ETA in seconds as m:ss or h:mm:ss.
+fn format_eta(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// Format a queue wait as "~4 min", rounding up to whole minutes.
+fn format_wait(secs: u64) -> String {
+    if secs < 60 {
+        return "<1 min".to_string();
+    }
+    let mins = secs.div_ceil(60);
+    if mins >= 60 {
+        format!("~{}h {}min", mins / 60, mins % 60)
+    } else {
+        format!("~{} min", mins)
+    }
+}
+
+/// Status text for a worker-side retry: "Retrying... (attempt 2/3)".
+fn format_retry_status(kind: &str, short_id: &str, attempt: u32, max: Option<u32>, reason: Option<&str>) -> String {
+    let attempt = match max {
+        Some(max) => format!("{}/{}", attempt, max),
+        None => attempt.to_string(),
+    };
+    let mut text = format!("{} [{}]\n🔁 Retrying... (attempt {})", kind, short_id, attempt);
+    if let Some(reason) = reason {
+        text.push_str(&format!("\nReason: {}", reason));
+    }
+    text
+}
+
 /// /restart - Restart Hermes services (admin only, silent for non-admin)
 async fn cmd_restart(
     bot: Bot,
@@ -2546,7 +7588,7 @@ async fn cmd_restart(
         return Ok(());
     }
 
-    bot.send_message(msg.chat.id, "🔄 Restarting Hermes services...")
+    bot.send_message(msg.chat.id, decorate("🔄 Restarting Hermes services..."))
         .await?;
 
     // Execute restart command
@@ -2562,26 +7604,26 @@ async fn cmd_restart(
             if output.status.success() {
                 let response = format!(
                     "✅ Restart Complete\n\n```\n{}\n```",
-                    stdout.trim()
+                    escape_markdown_v2_code(stdout.trim())
                 );
-                bot.send_message(msg.chat.id, response)
+                bot.send_message(msg.chat.id, decorate_markdown(response))
                     .parse_mode(ParseMode::MarkdownV2)
                     .await
                     .ok();
             } else {
                 let response = format!(
-                    "❌ Restart Failed\n\nExit code: {:?}\n\nstderr:\n```\n{}\n```",
-                    output.status.code(),
-                    stderr.trim()
+                    "❌ Restart Failed\n\nExit code: {}\n\nstderr:\n```\n{}\n```",
+                    escape_markdown_v2(&format!("{:?}", output.status.code())),
+                    escape_markdown_v2_code(stderr.trim())
                 );
-                bot.send_message(msg.chat.id, response)
+                bot.send_message(msg.chat.id, decorate_markdown(response))
                     .parse_mode(ParseMode::MarkdownV2)
                     .await
                     .ok();
             }
         }
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("❌ Failed to execute restart: {}", e))
+            bot.send_message(msg.chat.id, decorate(format!("❌ Failed to execute restart: {}", e)))
                 .await?;
         }
     }
@@ -2604,7 +7646,7 @@ async fn cmd_update(
         return Ok(());
     }
 
-    bot.send_message(msg.chat.id, "📦 Updating Hermes... This may take a few minutes.")
+    bot.send_message(msg.chat.id, decorate("📦 Updating Hermes... This may take a few minutes."))
         .await?;
 
     // Execute update command
@@ -2629,18 +7671,18 @@ async fn cmd_update(
                 };
                 
                 let response = format!("✅ Update Complete\n\n{}", truncated.trim());
-                bot.send_message(msg.chat.id, response).await.ok();
+                bot.send_message(msg.chat.id, decorate(response)).await.ok();
             } else {
                 let response = format!(
                     "❌ Update Failed\n\nExit code: {:?}\n\nstderr:\n{}",
                     output.status.code(),
                     stderr.trim()
                 );
-                bot.send_message(msg.chat.id, response).await.ok();
+                bot.send_message(msg.chat.id, decorate(response)).await.ok();
             }
         }
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("❌ Failed to execute update: {}", e))
+            bot.send_message(msg.chat.id, decorate(format!("❌ Failed to execute update: {}", e)))
                 .await?;
         }
     }
@@ -0,0 +1,87 @@
+//! `/start` deep-link payloads (`t.me/<bot>?start=<payload>`).
+//!
+//! Telegram allows up to 64 characters from `A-Z a-z 0-9 _ -`, so payloads are:
+//!
+//! - `login` — reply with a single-use dashboard login link
+//! - `yt_<video id>` — download that YouTube video
+//! - `dl_<base64url url>` — download any URL, base64url-encoded without
+//!   padding (fits URLs up to 45 bytes; longer ones need `yt_` or a shortener)
+//!
+//! Anything else falls back to the normal welcome message.
+
+/// What a `/start` payload asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartPayload {
+    /// Send the user a dashboard login link.
+    Login,
+    /// Download this URL right away.
+    Download(String),
+}
+
+/// Parse the argument of `/start`. None for an empty or unrecognised payload.
+pub fn parse_start_payload(payload: &str) -> Option<StartPayload> {
+    let payload = payload.trim();
+    if payload == "login" {
+        return Some(StartPayload::Login);
+    }
+    if let Some(id) = payload.strip_prefix("yt_") {
+        let valid = id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        return valid.then(|| StartPayload::Download(format!("https://www.youtube.com/watch?v={}", id)));
+    }
+    if let Some(encoded) = payload.strip_prefix("dl_") {
+        let url = String::from_utf8(decode_base64url(encoded)?).ok()?;
+        let is_web = url.starts_with("https://") || url.starts_with("http://");
+        return is_web.then_some(StartPayload::Download(url));
+    }
+    None
+}
+
+/// Decode unpadded base64url. None on any character outside the alphabet.
+fn decode_base64url(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in input.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_routing() {
+        assert_eq!(parse_start_payload("login"), Some(StartPayload::Login));
+        assert_eq!(
+            parse_start_payload("yt_dQw4w9WgXcQ"),
+            Some(StartPayload::Download("https://www.youtube.com/watch?v=dQw4w9WgXcQ".into()))
+        );
+        // base64url of "https://example.com/a?b=c"
+        assert_eq!(
+            parse_start_payload("dl_aHR0cHM6Ly9leGFtcGxlLmNvbS9hP2I9Yw"),
+            Some(StartPayload::Download("https://example.com/a?b=c".into()))
+        );
+
+        assert_eq!(parse_start_payload(""), None);
+        assert_eq!(parse_start_payload("yt_short"), None);
+        assert_eq!(parse_start_payload("dl_not+base64"), None);
+        // Decodes, but is not a web URL
+        assert_eq!(parse_start_payload("dl_ZmlsZTovLy9ldGMvcGFzc3dk"), None);
+        assert_eq!(parse_start_payload("ref_campaign42"), None);
+    }
+}
@@ -0,0 +1,112 @@
+/// Splitting files that are over Telegram's upload limit into sendable parts.
+///
+/// Audio and video are cut by the worker (`split_media`, playable parts). Other
+/// files, and media the worker couldn't cut, are split here byte for byte into
+/// `<name>.part01`, `<name>.part02`, ... which the user joins back together.
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Largest part sent, a little under the Bot API's 50 MB upload limit.
+pub const PART_SIZE: u64 = 49 * 1024 * 1024;
+
+/// Extensions the worker can cut into playable parts with ffmpeg.
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp3", "m4a", "aac", "opus", "ogg", "oga", "flac", "wav", "mp4", "webm", "mkv", "mov", "m4v",
+];
+
+/// Whether `path` is audio/video the worker can split into playable parts.
+pub fn is_splittable_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Split `path` into parts of at most `part_size` bytes next to it. Returns the
+/// part paths in order; on error, parts written so far are removed.
+pub async fn split_bytes(path: &Path, part_size: u64) -> std::io::Result<Vec<PathBuf>> {
+    let size = tokio::fs::metadata(path).await?.len();
+    let count = size.div_ceil(part_size).max(1);
+    let width = count.to_string().len().max(2);
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("download");
+
+    let mut parts = Vec::with_capacity(count as usize);
+    let result = async {
+        let mut source = tokio::fs::File::open(path).await?;
+        let mut buf = vec![0u8; 1024 * 1024];
+        for number in 1..=count {
+            let part = path.with_file_name(format!("{}.part{:0width$}", name, number, width = width));
+            let mut out = tokio::fs::File::create(&part).await?;
+            parts.push(part);
+            let mut left = part_size;
+            while left > 0 {
+                let want = buf.len().min(left as usize);
+                let n = source.read(&mut buf[..want]).await?;
+                if n == 0 {
+                    break;
+                }
+                out.write_all(&buf[..n]).await?;
+                left -= n as u64;
+            }
+            out.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        remove_parts(&parts).await;
+        return Err(e);
+    }
+    Ok(parts)
+}
+
+/// How to put byte-split parts back together, for the last part's caption.
+pub fn join_hint(file_name: &str) -> String {
+    format!(
+        "Join the parts to get the file:\ncat \"{0}\".part* > \"{0}\"\n(Windows: copy /b \"{0}.part*\" \"{0}\")",
+        file_name
+    )
+}
+
+/// Delete parts once they've been sent.
+pub async fn remove_parts(parts: &[PathBuf]) {
+    for part in parts {
+        let _ = tokio::fs::remove_file(part).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_split_bytes_round_trip() {
+        let dir = std::env::temp_dir().join(format!("hermes-split-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let file = dir.join("big.zip");
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(&file, &data).await.unwrap();
+
+        let parts = split_bytes(&file, 1000).await.unwrap();
+        let names: Vec<_> = parts.iter().map(|p| p.file_name().unwrap().to_str().unwrap().to_string()).collect();
+        assert_eq!(names, vec!["big.zip.part01", "big.zip.part02", "big.zip.part03"]);
+        assert_eq!(tokio::fs::metadata(&parts[2]).await.unwrap().len(), 500);
+
+        let mut joined = Vec::new();
+        for part in &parts {
+            joined.extend(tokio::fs::read(part).await.unwrap());
+        }
+        assert_eq!(joined, data);
+
+        remove_parts(&parts).await;
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[test]
+    fn test_media_detection() {
+        assert!(is_splittable_media(Path::new("/d/Song.MP3")));
+        assert!(is_splittable_media(Path::new("/d/Video.mkv")));
+        assert!(!is_splittable_media(Path::new("/d/archive.zip")));
+        assert!(!is_splittable_media(Path::new("/d/noext")));
+    }
+}
@@ -1,6 +1,6 @@
 /// Smart link detection for incoming Telegram messages.
 ///
-/// Detects YouTube URLs, Telegram links, and other URL patterns.
+/// Detects YouTube URLs, Spotify tracks, Telegram links, and other URL patterns.
 use regex::Regex;
 use once_cell::sync::Lazy;
 
@@ -15,6 +15,11 @@ pub enum DetectedLink {
     YoutubeShort { url: String, video_id: String },
     /// YouTube Music link.
     YoutubeMusic { url: String, video_id: String },
+    /// User-made YouTube clip (a time range of another video).
+    YoutubeClip { url: String, clip_id: String },
+    /// Spotify track. Spotify audio can't be downloaded, so the worker looks up
+    /// the title/artist and the bot downloads the best YouTube match instead.
+    SpotifyTrack { url: String, track_id: String },
     /// Telegram channel/group file link.
     TelegramFile {
         url: String,
@@ -25,7 +30,13 @@ pub enum DetectedLink {
         /// Message ID within the channel.
         message_id: i32,
     },
-    /// Unsupported URL (not YouTube or Telegram).
+    /// Podcast RSS/Atom feed (`feeds.` hosts, `.rss`/`.xml`, `/rss`, `/feed`).
+    /// The bot reads it itself and offers the latest episodes.
+    RssFeed { url: String },
+    /// Any other http(s) URL, handed to yt-dlp (SoundCloud, Vimeo, TikTok...).
+    /// Only produced when `YTDLP_GENERIC_ENABLED=true`.
+    GenericYtDlp { url: String },
+    /// Unsupported URL (not YouTube, Spotify or Telegram).
     Unsupported { url: String },
 }
 
@@ -37,7 +48,11 @@ impl DetectedLink {
             DetectedLink::YoutubePlaylist { url, .. } => url,
             DetectedLink::YoutubeShort { url, .. } => url,
             DetectedLink::YoutubeMusic { url, .. } => url,
+            DetectedLink::YoutubeClip { url, .. } => url,
+            DetectedLink::SpotifyTrack { url, .. } => url,
             DetectedLink::TelegramFile { url, .. } => url,
+            DetectedLink::RssFeed { url } => url,
+            DetectedLink::GenericYtDlp { url } => url,
             DetectedLink::Unsupported { url } => url,
         }
     }
@@ -47,11 +62,47 @@ impl DetectedLink {
         matches!(self, DetectedLink::YoutubePlaylist { .. })
     }
 
+    /// Whether this is a YouTube clip, which the worker must resolve to its
+    /// source video and time range.
+    pub fn is_clip(&self) -> bool {
+        matches!(self, DetectedLink::YoutubeClip { .. })
+    }
+
+    /// Whether this is a Spotify track, which must be resolved to a YouTube
+    /// video before downloading.
+    pub fn is_spotify(&self) -> bool {
+        matches!(self, DetectedLink::SpotifyTrack { .. })
+    }
+
+    /// Whether this is a podcast feed.
+    pub fn is_feed(&self) -> bool {
+        matches!(self, DetectedLink::RssFeed { .. })
+    }
+
     /// Whether this is a supported (downloadable) link.
     pub fn is_supported(&self) -> bool {
         !matches!(self, DetectedLink::Unsupported { .. })
     }
 
+    /// File name at the end of a generic URL's path, when it has an extension
+    /// (`https://host/media/clip.mp4?dl=1` → `clip.mp4`). Such links usually
+    /// point straight at a file rather than at a page.
+    pub fn direct_file_name(&self) -> Option<&str> {
+        let (DetectedLink::GenericYtDlp { url } | DetectedLink::Unsupported { url }) = self else {
+            return None;
+        };
+        let path = url.split(['?', '#']).next()?;
+        let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+        let name = path.split_once('/')?.1.rsplit('/').next()?;
+        let (stem, ext) = name.rsplit_once('.')?;
+        (!stem.is_empty() && !ext.is_empty()).then_some(name)
+    }
+
+    /// Whether this is a link to some other site that yt-dlp should try.
+    pub fn is_generic(&self) -> bool {
+        matches!(self, DetectedLink::GenericYtDlp { .. })
+    }
+
     /// Whether this is a Telegram link.
     pub fn is_telegram(&self) -> bool {
         matches!(self, DetectedLink::TelegramFile { .. })
@@ -63,38 +114,51 @@ impl DetectedLink {
             DetectedLink::YoutubePlaylist { .. } => "playlist",
             DetectedLink::YoutubeVideo { .. }
             | DetectedLink::YoutubeShort { .. }
-            | DetectedLink::YoutubeMusic { .. } => "youtube_dl",
+            | DetectedLink::YoutubeMusic { .. }
+            | DetectedLink::YoutubeClip { .. }
+            | DetectedLink::SpotifyTrack { .. } => "youtube_dl",
             DetectedLink::TelegramFile { .. } => "telegram_forward",
-            DetectedLink::Unsupported { .. } => "youtube_dl",
+            DetectedLink::RssFeed { .. } => "podcast",
+            DetectedLink::GenericYtDlp { .. } | DetectedLink::Unsupported { .. } => "youtube_dl",
         }
     }
 }
 
+/// Whether URLs from sites other than YouTube, Spotify and Telegram go to
+/// yt-dlp (`YTDLP_GENERIC_ENABLED=true`) or are reported as unsupported.
+static YTDLP_GENERIC_ENABLED: Lazy<bool> = Lazy::new(|| {
+    std::env::var("YTDLP_GENERIC_ENABLED")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false)
+});
+
 // ====== REGEX PATTERNS ======
 
+/// `v` may come after other query params (watch?feature=share&v=...).
 static YOUTUBE_VIDEO_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r"(?:https?://)?(?:www\.)?(?:youtube\.com/watch\?v=|youtu\.be/)([a-zA-Z0-9_-]{11})"
+        r"(?:https?://)?(?:www\.|m\.)?(?:youtube\.com/watch\?(?:[^\s#&]*&)*v=|youtu\.be/)([a-zA-Z0-9_-]{11})"
     ).unwrap()
 });
 
 static YOUTUBE_PLAYLIST_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r"(?:https?://)?(?:www\.)?youtube\.com/playlist\?list=([a-zA-Z0-9_-]+)"
+        r"(?:https?://)?(?:www\.|m\.)?youtube\.com/playlist\?list=([a-zA-Z0-9_-]+)"
     ).unwrap()
 });
 
 /// YouTube watch URL with playlist param (e.g., watch?v=xxx&list=RDyyy or watch?list=xxx&v=yyy).
 /// This pattern catches Radio Mix URLs: watch?v=SEED&list=RDxxx&start_radio=1
+/// The params before `list` stay within one URL (no whitespace or fragment).
 static YOUTUBE_WATCH_WITH_PLAYLIST_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r"(?:https?://)?(?:www\.)?youtube\.com/watch\?.*list=([a-zA-Z0-9_-]+)"
+        r"(?:https?://)?(?:www\.|m\.)?youtube\.com/watch\?(?:[^\s#&]*&)*list=([a-zA-Z0-9_-]+)"
     ).unwrap()
 });
 
 static YOUTUBE_SHORT_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r"(?:https?://)?(?:www\.)?youtube\.com/shorts/([a-zA-Z0-9_-]{11})"
+        r"(?:https?://)?(?:www\.|m\.)?youtube\.com/shorts/([a-zA-Z0-9_-]{11})"
     ).unwrap()
 });
 
@@ -104,10 +168,27 @@ static YOUTUBE_MUSIC_RE: Lazy<Regex> = Lazy::new(|| {
     ).unwrap()
 });
 
+/// YouTube clip: youtube.com/clip/{id} (ids are longer than video ids).
+static YOUTUBE_CLIP_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?:https?://)?(?:www\.|m\.)?youtube\.com/clip/([a-zA-Z0-9_-]+)"
+    ).unwrap()
+});
+
+/// Spotify track: open.spotify.com/track/{id}, optionally with a locale
+/// segment (open.spotify.com/intl-de/track/{id}).
+static SPOTIFY_TRACK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?:https?://)?open\.spotify\.com/(?:intl-[a-zA-Z-]+/)?track/([a-zA-Z0-9]{22})"
+    ).unwrap()
+});
+
 /// Generic URL pattern to catch any http/https link.
+/// Parentheses and commas are valid inside URLs (wiki/Foo_(bar), a,b);
+/// `trim_url_end` drops them again when they end the sentence instead.
 static GENERIC_URL_RE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r#"https?://[^\s<>\[\](){},"']+"#
+        r#"https?://[^\s<>\[\]{}"']+"#
     ).unwrap()
 });
 
@@ -125,9 +206,68 @@ static TELEGRAM_PUBLIC_RE: Lazy<Regex> = Lazy::new(|| {
     ).unwrap()
 });
 
+/// Whether a URL looks like a podcast feed: a `feeds.`/`feed.` host, or a path
+/// ending in `.rss`, `.xml`, `/rss` or `/feed`.
+fn is_feed_url(url: &str) -> bool {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.split(['?', '#']).next().unwrap_or_default().to_lowercase();
+    let (host, path) = rest.split_once('/').unwrap_or((&rest, ""));
+    let path = path.trim_end_matches('/');
+    host.starts_with("feeds.")
+        || host.starts_with("feed.")
+        || path.ends_with(".rss")
+        || path.ends_with(".xml")
+        || path == "rss"
+        || path == "feed"
+        || path.ends_with("/rss")
+        || path.ends_with("/feed")
+}
+
+/// Punctuation that ends a sentence rather than a URL.
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', '!', '?', ';', ':', '\'', '"'];
+
+/// Strip sentence punctuation and unbalanced closing brackets from the end of
+/// a matched URL. A closing bracket is kept when the URL opened it itself.
+fn trim_url_end(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().last() else { return url };
+        let opener = match last {
+            ')' => Some('('),
+            ']' => Some('['),
+            '}' => Some('{'),
+            _ => None,
+        };
+        let strip = match opener {
+            Some(open) => url.matches(last).count() > url.matches(open).count(),
+            None => TRAILING_PUNCTUATION.contains(&last),
+        };
+        if !strip {
+            return url;
+        }
+        url = &url[..url.len() - last.len_utf8()];
+    }
+}
+
+/// Whether an 11-char video id match really ends at `end`. The regex has no
+/// lookahead, so "youtu.be/dQw4w9WgXcQxyz" would otherwise yield a bogus id.
+fn id_ends_at(text: &str, end: usize) -> bool {
+    !text[end..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 /// Detect all supported links in a message.
 pub fn detect_links(text: &str) -> Vec<DetectedLink> {
+    detect_links_with(text, *YTDLP_GENERIC_ENABLED)
+}
+
+fn detect_links_with(text: &str, generic_enabled: bool) -> Vec<DetectedLink> {
     let mut links = Vec::new();
+    // Text covered by watch?...list= URLs; a video match starting inside one
+    // is the same URL (e.g. watch?list=PL...&v=ID) and is not a second link
+    let mut playlist_spans = Vec::new();
 
     // Check playlist first (more specific)
     for cap in YOUTUBE_PLAYLIST_RE.captures_iter(text) {
@@ -140,6 +280,7 @@ pub fn detect_links(text: &str) -> Vec<DetectedLink> {
     // Check for watch URLs with playlist parameter (Radio Mix format: watch?v=xxx&list=RDyyy)
     // This must be checked BEFORE regular video URLs to avoid misclassification
     for cap in YOUTUBE_WATCH_WITH_PLAYLIST_RE.captures_iter(text) {
+        playlist_spans.push(cap.get(0).map_or(0..0, |m| m.range()));
         let url = cap[0].to_string();
         let playlist_id = cap[1].to_string();
 
@@ -155,14 +296,28 @@ pub fn detect_links(text: &str) -> Vec<DetectedLink> {
 
     // YouTube Shorts
     for cap in YOUTUBE_SHORT_RE.captures_iter(text) {
+        if !id_ends_at(text, cap.get(0).map_or(0, |m| m.end())) {
+            continue;
+        }
         links.push(DetectedLink::YoutubeShort {
             url: cap[0].to_string(),
             video_id: cap[1].to_string(),
         });
     }
 
+    // YouTube clips
+    for cap in YOUTUBE_CLIP_RE.captures_iter(text) {
+        links.push(DetectedLink::YoutubeClip {
+            url: cap[0].to_string(),
+            clip_id: cap[1].to_string(),
+        });
+    }
+
     // YouTube Music
     for cap in YOUTUBE_MUSIC_RE.captures_iter(text) {
+        if !id_ends_at(text, cap.get(0).map_or(0, |m| m.end())) {
+            continue;
+        }
         links.push(DetectedLink::YoutubeMusic {
             url: cap[0].to_string(),
             video_id: cap[1].to_string(),
@@ -171,6 +326,10 @@ pub fn detect_links(text: &str) -> Vec<DetectedLink> {
 
     // Regular YouTube video (skip if already captured as playlist/short/music)
     for cap in YOUTUBE_VIDEO_RE.captures_iter(text) {
+        let Some(m) = cap.get(0) else { continue };
+        if !id_ends_at(text, m.end()) || playlist_spans.iter().any(|span| span.contains(&m.start())) {
+            continue;
+        }
         let url = cap[0].to_string();
         let video_id = cap[1].to_string();
 
@@ -181,7 +340,15 @@ pub fn detect_links(text: &str) -> Vec<DetectedLink> {
         }
     }
 
-    // If no YouTube links found, check for Telegram links
+    // Spotify tracks
+    for cap in SPOTIFY_TRACK_RE.captures_iter(text) {
+        links.push(DetectedLink::SpotifyTrack {
+            url: cap[0].to_string(),
+            track_id: cap[1].to_string(),
+        });
+    }
+
+    // If no YouTube or Spotify links found, check for Telegram links
     if links.is_empty() {
         // Private channel links first (more specific: t.me/c/{id}/{msg})
         for cap in TELEGRAM_PRIVATE_RE.captures_iter(text) {
@@ -220,8 +387,14 @@ pub fn detect_links(text: &str) -> Vec<DetectedLink> {
     // If no YouTube or Telegram links found, check for any generic URL
     if links.is_empty() {
         if let Some(m) = GENERIC_URL_RE.find(text) {
-            links.push(DetectedLink::Unsupported {
-                url: m.as_str().to_string(),
+            // "see https://example.com/file.mp4." — the period ends the sentence
+            let url = trim_url_end(m.as_str()).to_string();
+            links.push(if is_feed_url(&url) {
+                DetectedLink::RssFeed { url }
+            } else if generic_enabled {
+                DetectedLink::GenericYtDlp { url }
+            } else {
+                DetectedLink::Unsupported { url }
             });
         }
     }
@@ -294,6 +467,20 @@ mod tests {
         assert!(matches!(&links[0], DetectedLink::YoutubeMusic { .. }));
     }
 
+    #[test]
+    fn test_spotify_track() {
+        let links = detect_links("listen https://open.spotify.com/intl-de/track/4uLU6hMCjMI75M1A2tKUQC?si=abc123");
+        assert_eq!(links.len(), 1);
+        assert!(links[0].is_spotify());
+        assert!(links[0].is_supported());
+        assert!(matches!(&links[0], DetectedLink::SpotifyTrack { track_id, .. } if track_id == "4uLU6hMCjMI75M1A2tKUQC"));
+        assert_eq!(links[0].url(), "https://open.spotify.com/intl-de/track/4uLU6hMCjMI75M1A2tKUQC");
+
+        // Albums and playlists aren't tracks
+        let links = detect_links("https://open.spotify.com/album/4uLU6hMCjMI75M1A2tKUQC");
+        assert!(matches!(&links[0], DetectedLink::Unsupported { .. }));
+    }
+
     #[test]
     fn test_no_links() {
         let links = detect_links("Just a regular message with no links");
@@ -373,6 +560,36 @@ mod tests {
         let links = detect_links("Download from https://example.com/file.mp4");
         assert_eq!(links.len(), 1);
         assert!(matches!(&links[0], DetectedLink::Unsupported { .. }));
+        assert_eq!(links[0].direct_file_name(), Some("file.mp4"));
+    }
+
+    #[test]
+    fn test_generic_ytdlp_flag() {
+        let links = detect_links_with("https://soundcloud.com/artist/track", true);
+        assert!(matches!(&links[0], DetectedLink::GenericYtDlp { url } if url == "https://soundcloud.com/artist/track"));
+        assert!(links[0].is_supported());
+        assert!(links[0].is_generic());
+        assert_eq!(links[0].ipc_action(), "youtube_dl");
+
+        let links = detect_links_with("https://soundcloud.com/artist/track", false);
+        assert!(!links[0].is_supported());
+
+        // Direct file links are still recognised, and known sites keep their variant
+        let links = detect_links_with("https://cdn.example.com/song.mp3", true);
+        assert_eq!(links[0].direct_file_name(), Some("song.mp3"));
+        let links = detect_links_with("https://youtu.be/dQw4w9WgXcQ", true);
+        assert!(matches!(&links[0], DetectedLink::YoutubeVideo { .. }));
+    }
+
+    #[test]
+    fn test_direct_file_name() {
+        let name = |text: &str| detect_first_link(text).and_then(|l| l.direct_file_name().map(str::to_string));
+        assert_eq!(name("https://cdn.example.com/a/b/song.mp3?token=x#t"), Some("song.mp3".into()));
+        assert_eq!(name("https://example.com/watch/12345"), None);
+        assert_eq!(name("https://example.com/"), None);
+        assert_eq!(name("https://example.com"), None);
+        assert_eq!(name("https://example.com/.hidden"), None);
+        assert_eq!(name("https://youtu.be/dQw4w9WgXcQ"), None);
     }
 
     #[test]
@@ -382,3 +599,152 @@ mod tests {
         assert!(links[0].is_supported());
     }
 }
+
+/// Real-world messages that tripped up earlier versions of the patterns.
+#[cfg(test)]
+mod adversarial_tests {
+    use super::*;
+
+    fn first_url(text: &str) -> String {
+        detect_first_link(text).expect("a link").url().to_string()
+    }
+
+    #[test]
+    fn test_markdown_link() {
+        let links = detect_links("[my song](https://youtu.be/dQw4w9WgXcQ)");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url(), "https://youtu.be/dQw4w9WgXcQ");
+
+        assert_eq!(first_url("[docs](https://example.com/a/b)"), "https://example.com/a/b");
+    }
+
+    #[test]
+    fn test_sentence_punctuation_after_url() {
+        assert_eq!(first_url("watch this https://youtu.be/dQw4w9WgXcQ."), "https://youtu.be/dQw4w9WgXcQ");
+        assert_eq!(first_url("(https://www.youtube.com/watch?v=dQw4w9WgXcQ)."), "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(first_url("grab https://example.com/file.mp4."), "https://example.com/file.mp4");
+        assert_eq!(first_url("is it https://example.com/clip?"), "https://example.com/clip");
+        assert_eq!(first_url("https://example.com/a, https://example.com/b"), "https://example.com/a");
+    }
+
+    #[test]
+    fn test_fragments_and_extra_params() {
+        let links = detect_links("https://youtu.be/dQw4w9WgXcQ#t=30");
+        assert!(matches!(&links[0], DetectedLink::YoutubeVideo { video_id, .. } if video_id == "dQw4w9WgXcQ"));
+
+        let links = detect_links("https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=42s");
+        assert_eq!(links.len(), 1);
+        assert!(matches!(&links[0], DetectedLink::YoutubeVideo { video_id, .. } if video_id == "dQw4w9WgXcQ"));
+    }
+
+    #[test]
+    fn test_mobile_youtube_keeps_full_url() {
+        assert_eq!(first_url("https://m.youtube.com/watch?v=dQw4w9WgXcQ"), "https://m.youtube.com/watch?v=dQw4w9WgXcQ");
+    }
+
+    #[test]
+    fn test_overlong_video_id_is_rejected() {
+        let links = detect_links("https://youtu.be/dQw4w9WgXcQextra");
+        assert!(!links.iter().any(|l| matches!(l, DetectedLink::YoutubeVideo { .. })));
+    }
+
+    #[test]
+    fn test_video_then_playlist_in_one_message() {
+        // A greedy watch?...list= pattern used to swallow both URLs as one
+        let text = "first https://www.youtube.com/watch?v=dQw4w9WgXcQ then https://www.youtube.com/playlist?list=PLabc123";
+        let links = detect_links(text);
+        assert_eq!(links.len(), 2);
+        assert!(links.iter().all(|l| !l.url().contains(' ')));
+        assert!(links.iter().any(|l| matches!(l, DetectedLink::YoutubeVideo { .. })));
+        assert!(links.iter().any(|l| matches!(l, DetectedLink::YoutubePlaylist { playlist_id, .. } if playlist_id == "PLabc123")));
+    }
+
+    #[test]
+    fn test_multiple_playlists() {
+        let text = "https://www.youtube.com/playlist?list=PLone\nhttps://www.youtube.com/playlist?list=PLtwo";
+        let ids: Vec<_> = detect_links(text)
+            .into_iter()
+            .filter_map(|l| match l {
+                DetectedLink::YoutubePlaylist { playlist_id, .. } => Some(playlist_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec!["PLone", "PLtwo"]);
+    }
+
+    #[test]
+    fn test_unicode_domain_and_text() {
+        assert_eq!(first_url("смотри https://пример.рф/видео/1 тут"), "https://пример.рф/видео/1");
+        assert_eq!(first_url("🎵 https://youtu.be/dQw4w9WgXcQ 🎵"), "https://youtu.be/dQw4w9WgXcQ");
+    }
+
+    #[test]
+    fn test_generic_url_embedded_in_sentence() {
+        assert_eq!(
+            first_url("I found it at https://vimeo.com/123456 yesterday, can you get it?"),
+            "https://vimeo.com/123456"
+        );
+        assert_eq!(first_url("<https://example.com/x>"), "https://example.com/x");
+        assert_eq!(first_url("\"https://example.com/q?a=1&b=2\""), "https://example.com/q?a=1&b=2");
+    }
+
+    #[test]
+    fn test_parenthetical_urls() {
+        assert_eq!(first_url("(see https://example.com/video)"), "https://example.com/video");
+        assert_eq!(first_url("(see https://example.com/video)."), "https://example.com/video");
+        assert_eq!(first_url("[docs](https://example.com/a)!"), "https://example.com/a");
+        // Brackets that belong to the URL survive
+        assert_eq!(
+            first_url("https://en.wikipedia.org/wiki/Queen_(band)"),
+            "https://en.wikipedia.org/wiki/Queen_(band)"
+        );
+        assert_eq!(
+            first_url("(read https://en.wikipedia.org/wiki/Queen_(band))."),
+            "https://en.wikipedia.org/wiki/Queen_(band)"
+        );
+    }
+
+    #[test]
+    fn test_trailing_punctuation_variants() {
+        for suffix in [".", ",", "!", "?", "!?", "...", ";", ":", ").", "'", "\""] {
+            let text = format!("get https://example.com/clip{}", suffix);
+            assert_eq!(first_url(&text), "https://example.com/clip", "suffix {:?}", suffix);
+        }
+        // Commas and dots inside the URL are kept
+        assert_eq!(first_url("https://example.com/a,b/c.mp4."), "https://example.com/a,b/c.mp4");
+        assert_eq!(first_url("https://example.com/?q=1."), "https://example.com/?q=1");
+    }
+
+    #[test]
+    fn test_youtube_clip() {
+        let links = detect_links("lol https://youtube.com/clip/UgkxU2HSeGL_NvmDJ-nQJrlLwllwMDBdGZFs?si=abc");
+        assert_eq!(links.len(), 1);
+        assert!(matches!(&links[0], DetectedLink::YoutubeClip { clip_id, .. } if clip_id == "UgkxU2HSeGL_NvmDJ-nQJrlLwllwMDBdGZFs"));
+        assert!(links[0].is_clip() && links[0].is_supported());
+        assert_eq!(links[0].ipc_action(), "youtube_dl");
+        assert!(!detect_first_link("https://youtu.be/dQw4w9WgXcQ").unwrap().is_clip());
+    }
+
+    #[test]
+    fn test_podcast_feed() {
+        for url in [
+            "https://feeds.megaphone.fm/ABC123",
+            "https://example.com/podcast.rss",
+            "https://anchor.fm/s/abc/podcast/rss",
+            "https://example.com/feed/",
+            "https://example.com/shows/feed.xml?format=audio",
+        ] {
+            let link = detect_first_link(url).unwrap();
+            assert!(link.is_feed(), "{}", url);
+            assert!(link.direct_file_name().is_none());
+        }
+        assert!(!detect_first_link("https://example.com/feedback").unwrap().is_feed());
+        assert!(!detect_first_link("https://example.com/episode.mp3").unwrap().is_feed());
+    }
+
+    #[test]
+    fn test_not_a_link() {
+        assert!(detect_links("youtube.com is blocked here").is_empty());
+        assert!(detect_links("ftp://example.com/file").is_empty());
+    }
+}
@@ -4,18 +4,31 @@
 /// via IPC for downloading YouTube audio and playlists.
 mod commands;
 mod callback_state;
+mod clip;
+mod deep_link;
+mod file_split;
 mod link_detector;
+mod media_group;
+mod subscriptions;
+mod sysinfo;
+mod task_prefix;
+mod text;
+mod user_errors;
 mod workers;
 
+use std::collections::HashSet;
 use std::sync::Arc;
 use teloxide::prelude::*;
-use teloxide::types::CallbackQuery;
+use teloxide::types::{CallbackQuery, ChosenInlineResult, InlineQuery};
 use tracing::{info, error, warn};
 
-use hermes_shared::task_queue::TaskQueue;
-use workers::python_dispatcher::PythonDispatcher;
-use callback_state::{CallbackStateStore, SearchStateStore, PlaylistStateStore};
+use hermes_shared::task_queue::{Priority, TaskQueue};
+use hermes_shared::storage_dirs::{StorageDirs, StorageKind};
+use workers::python_dispatcher::WorkerEvent;
+use workers::worker_pool::WorkerPool;
+use callback_state::{CallbackStateStore, FormatCache, SearchStateStore, PlaylistStateStore, PodcastStateStore, RedownloadStateStore};
 use commands::{AppState, Command};
+use text::decorate;
 
 #[tokio::main]
 async fn main() {
@@ -47,8 +60,13 @@ async fn main() {
         .parse()
         .unwrap_or(3);
 
-    // Ensure download directory exists
+    // Ensure download directories exist
     std::fs::create_dir_all(&download_dir).expect("Failed to create download directory");
+    let storage = StorageDirs::from_env(&download_dir);
+    for root in storage.roots() {
+        std::fs::create_dir_all(root).expect("Failed to create download directory");
+    }
+    info!("Download roots: audio={} video={} playlist={}", storage.audio, storage.video, storage.playlist);
 
     // Initialize deduplication storage structure
     let storage_pool_dir = std::path::Path::new(&download_dir)
@@ -91,29 +109,28 @@ async fn main() {
         }
     }
 
-    // Initialize Python worker dispatcher
-    let dispatcher = PythonDispatcher::new(
+    // Initialize the Python worker pool (WORKER_POOL_SIZE processes, default 1)
+    let pool_size = std::env::var("WORKER_POOL_SIZE")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1);
+    let dispatcher = WorkerPool::new(
+        pool_size,
         std::path::PathBuf::from(&worker_dir),
         python_bin,
     );
 
-    // Start the Python worker
+    // Start the Python workers
     if let Err(e) = dispatcher.start().await {
         error!("Failed to start Python worker: {} — downloads will be unavailable until worker is fixed", e);
     } else {
-        info!("Python worker started successfully");
+        info!("Python worker pool started ({} worker(s))", dispatcher.size());
     }
 
     // Connect to shared database (for web queue polling)
     let database_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "./hermes.db".to_string());
-    let database_path = std::path::Path::new(&database_path)
-        .canonicalize()
-        .unwrap_or_else(|_| std::path::PathBuf::from(&database_path));
-    // Strip Windows UNC prefix (\\?\) which breaks SQLite URL parsing
-    let db_path_str = database_path.display().to_string();
-    let db_path_str = db_path_str.strip_prefix(r"\\?\").unwrap_or(&db_path_str).to_string();
-    let database_url = format!("sqlite://{}?mode=rwc", db_path_str);
-    info!("Database path: {}", db_path_str);
+    let database_url = hermes_shared::db::resolve_database_url(&database_path);
+    info!("Database: {}", database_url);
     let db_pool = match hermes_shared::db::create_pool(&database_url).await {
         Ok(pool) => {
             if let Err(e) = hermes_shared::db::run_migrations(&pool).await {
@@ -153,7 +170,30 @@ async fn main() {
     } else {
         max_concurrent
     };
-    let task_queue = TaskQueue::new(max_concurrent);
+    let mut task_queue = TaskQueue::new(max_concurrent);
+    // Per-user cap so one chat can't take every slot (0 = no cap)
+    let per_user: usize = std::env::var("MAX_TASKS_PER_USER")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2);
+    if per_user > 0 {
+        info!("Per-user concurrency limit: {}", per_user);
+        task_queue = task_queue.with_per_chat_limit(per_user);
+    }
+    // Mirror live progress into the tasks table so the web dashboard sees it
+    if let Some(pool) = db_pool.clone() {
+        task_queue = task_queue.with_progress_hook(Arc::new(move |update| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                if let Err(e) = hermes_shared::db::update_task_progress(
+                    &pool, &update.task_id, update.percent as i32,
+                    update.speed.as_deref(), update.eta_secs.map(|s| s as i64),
+                ).await {
+                    warn!("Failed to persist progress for {}: {}", update.task_id, e);
+                }
+            });
+        }));
+    }
 
     // Initialize callback state store
     let callback_store = CallbackStateStore::new();
@@ -164,20 +204,59 @@ async fn main() {
     // Initialize playlist confirmation store
     let playlist_store = PlaylistStateStore::new();
 
+    // Initialize podcast episode store
+    let podcast_store = PodcastStateStore::new();
+
+    // Initialize completion-message button store
+    let redownload_store = RedownloadStateStore::new();
+
     // Parse admin chat ID
     let admin_chat_id = std::env::var("ADMIN_CHAT_ID").ok()
         .and_then(|s| s.parse::<i64>().ok());
 
+    // Allowlist mode: ALLOWED_USERS=id1,id2 or ALLOWLIST_MODE=true (DB allowlist only)
+    let allowed_users: Option<HashSet<i64>> = {
+        let ids: HashSet<i64> = std::env::var("ALLOWED_USERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|s| s.trim().parse::<i64>().ok())
+            .collect();
+        let mode = std::env::var("ALLOWLIST_MODE")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+        (mode || !ids.is_empty()).then_some(ids)
+    };
+    if let Some(ids) = &allowed_users {
+        info!("Allowlist mode enabled ({} user(s) from ALLOWED_USERS)", ids.len());
+    }
+
+    // Total bandwidth for direct file downloads, on top of each one's own cap
+    let download_limiter = std::env::var("DOWNLOAD_RATE_LIMIT_TOTAL")
+        .ok()
+        .and_then(|s| hermes_downloader::parse_rate(&s))
+        .map(|rate| {
+            info!("Direct downloads capped at {} bytes/s in total", rate);
+            Arc::new(hermes_downloader::RateLimiter::new(rate))
+        });
+
     // Create shared application state
     let state = Arc::new(AppState {
         dispatcher,
         task_queue,
         download_dir: download_dir.clone(),
+        storage,
         callback_store: callback_store.clone(),
+        // Formats rarely change within minutes; 200 entries bounds the memory
+        format_cache: FormatCache::new(std::time::Duration::from_secs(300), 200),
         search_store: search_store.clone(),
         playlist_store: playlist_store.clone(),
+        podcast_store: podcast_store.clone(),
+        redownload_store: redownload_store.clone(),
         db_pool: db_pool.clone(),
         admin_chat_id,
+        allowed_users,
+        download_limiter,
+        started_at: std::time::Instant::now(),
     });
 
     // Build and start the Telegram bot
@@ -204,15 +283,52 @@ async fn main() {
             "Hermes Bot online\nWorker: ready\nDB: {}\nQueue: {}/{} slots",
             db_status, 0, max_concurrent
         );
-        match bot.send_message(ChatId(admin_id), msg).await {
-            Ok(_) => info!("Admin startup notification sent"),
-            Err(e) => warn!("Failed to send admin notification: {}", e),
+        // Retried in the background: on first setup the admin may not have
+        // opened a chat with the bot yet
+        tokio::spawn(notify_admin_online(bot.clone(), ChatId(admin_id), msg));
+    }
+
+    // Restart the worker if it dies, and tell the admin
+    let mut worker_events = state.dispatcher.supervise();
+    let events_bot = bot.clone();
+    tokio::spawn(async move {
+        while let Some((worker, event)) = worker_events.recv().await {
+            let text = match event {
+                WorkerEvent::Down { status, failed } => format!(
+                    "Python worker #{} down ({})\nFailed tasks: {}\nRestarting...", worker, status, failed
+                ),
+                // Backoff caps at a minute; don't report every attempt
+                WorkerEvent::RestartFailed { attempt, error, retry_in } if attempt == 1 || attempt % 10 == 0 => format!(
+                    "Worker #{} restart attempt {} failed: {}\nNext attempt in {}s", worker, attempt, error, retry_in.as_secs()
+                ),
+                WorkerEvent::RestartFailed { .. } => continue,
+                WorkerEvent::Restarted { attempt } => format!("Python worker #{} back up (attempt {})", worker, attempt),
+            };
+            if let Some(admin_id) = admin_chat_id {
+                if let Err(e) = events_bot.send_message(ChatId(admin_id), decorate(text)).await {
+                    warn!("Failed to notify admin about the worker: {}", e);
+                }
+            }
+        }
+    });
+
+    // Resume Telegram forward batches interrupted by the last shutdown
+    tokio::spawn(commands::resume_forward_batches(bot.clone(), state.clone()));
+
+    // Resume downloads interrupted by the last shutdown or crash. Loaded here,
+    // before the web queue poller claims web tasks into 'queued' too.
+    if let Some(pool) = &db_pool {
+        match hermes_shared::db::get_unfinished_tasks(pool).await {
+            Ok(tasks) => {
+                tokio::spawn(commands::resume_unfinished_tasks(bot.clone(), state.clone(), tasks));
+            }
+            Err(e) => error!("Failed to load unfinished tasks: {}", e),
         }
     }
 
     info!("Bot initialized, starting dispatcher...");
 
-    // Set up command handler, message handler, and callback query handler
+    // Set up command, message, callback query and inline query handlers
     let handler = dptree::entry()
         .branch(
             Update::filter_message()
@@ -244,6 +360,26 @@ async fn main() {
                         async move { commands::handle_callback_query(bot, q, state).await }
                     }
                 }),
+        )
+        .branch(
+            Update::filter_inline_query()
+                .endpoint({
+                    let state = state.clone();
+                    move |bot: Bot, q: InlineQuery| {
+                        let state = state.clone();
+                        async move { commands::handle_inline_query(bot, q, state).await }
+                    }
+                }),
+        )
+        .branch(
+            Update::filter_chosen_inline_result()
+                .endpoint({
+                    let state = state.clone();
+                    move |bot: Bot, chosen: ChosenInlineResult| {
+                        let state = state.clone();
+                        async move { commands::handle_chosen_inline_result(bot, chosen, state).await }
+                    }
+                }),
         );
 
     // Spawn background cleanup task for expired callback states
@@ -279,15 +415,114 @@ async fn main() {
         }
     });
 
+    let cleanup_podcast = podcast_store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(120)).await;
+            cleanup_podcast.cleanup_expired(1800).await; // 30 min TTL
+        }
+    });
+
+    let cleanup_redownload = redownload_store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(120)).await;
+            cleanup_redownload.cleanup_expired(86_400).await; // 24h TTL
+        }
+    });
+
+    // Background jobs that start new work; aborted first on shutdown
+    let mut intake_jobs = Vec::new();
+
+    // Spawn subscription scheduler: check due subscriptions one at a time
+    if let Some(pool) = db_pool.clone() {
+        let sub_state = state.clone();
+        let sub_bot = bot.clone();
+        intake_jobs.push(tokio::spawn(async move {
+            let interval_secs = commands::subscription_interval_secs();
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
+            loop {
+                interval.tick().await;
+                match hermes_shared::db::get_due_subscriptions(&pool, interval_secs).await {
+                    Ok(subs) => {
+                        for sub in subs {
+                            commands::check_subscription(&sub_bot, &sub_state, sub).await;
+                        }
+                    }
+                    Err(e) => warn!("Subscription poll error: {}", e),
+                }
+            }
+        }));
+        info!("Subscription scheduler started");
+    }
+
+    // Stop tasks cancelled through the API (DELETE /api/tasks/:id), which only
+    // marks them in the database
+    if let Some(pool) = db_pool.clone() {
+        let cancel_state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                let active = cancel_state.task_queue.active_task_ids().await;
+                if active.is_empty() {
+                    continue;
+                }
+                match hermes_shared::db::cancelled_among(&pool, &active).await {
+                    Ok(cancelled) => {
+                        for task_id in cancelled {
+                            info!("Task {} cancelled through the API, stopping it", task_id);
+                            commands::stop_task(&cancel_state, &task_id).await;
+                        }
+                    }
+                    Err(e) => warn!("Cancelled task poll error: {}", e),
+                }
+            }
+        });
+    }
+
+    // Release scheduled downloads (/schedule, POST /api/download) into the web queue
+    if let Some(pool) = db_pool.clone() {
+        intake_jobs.push(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(15));
+            loop {
+                interval.tick().await;
+                match hermes_shared::db::promote_due_scheduled_tasks(&pool).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("Released {} scheduled download(s)", n),
+                    Err(e) => warn!("Scheduled task poll error: {}", e),
+                }
+            }
+        }));
+        info!("Download scheduler started");
+    }
+
     // Spawn web download queue poller
     if let Some(pool) = db_pool {
         let web_state = state.clone();
         let web_bot = bot.clone();
-        tokio::spawn(async move {
+        intake_jobs.push(tokio::spawn(async move {
             use hermes_shared::ipc_protocol::download_request_prefs;
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+            let mut draining = false;
+            let instance = hermes_shared::db::instance_id();
             loop {
                 interval.tick().await;
+
+                // An admin drain (POST /api/admin/drain) leaves queued tasks for another instance
+                let drain_requested = hermes_shared::db::is_web_queue_draining(&pool, &instance).await.unwrap_or(false);
+                if drain_requested != draining {
+                    draining = drain_requested;
+                    if draining {
+                        info!("Web queue draining ({}): no longer claiming new web tasks", instance);
+                    } else {
+                        info!("Web queue drain lifted: claiming web tasks again");
+                    }
+                }
+                if draining {
+                    continue;
+                }
+
                 match hermes_shared::db::claim_web_queued_tasks(&pool).await {
                     Ok(tasks) if !tasks.is_empty() => {
                         for task in tasks {
@@ -296,7 +531,8 @@ async fn main() {
                             let short_id = task_id.chars().take(8).collect::<String>();
                             let url = task.url.clone();
                             let label = task.label.clone().unwrap_or_else(|| "audio".to_string());
-                            let is_video = label == "video";
+                            // "video" or "video (best)"
+                            let is_video = label.starts_with("video");
                             let mode = if is_video {
                                 crate::callback_state::DownloadMode::Video
                             } else {
@@ -305,10 +541,18 @@ async fn main() {
 
                             info!("Processing web-queued task {} for chat {}", short_id, task.chat_id);
 
+                            // Queued or scheduled before the user was banned
+                            if hermes_shared::db::is_banned_user(&pool, task.chat_id).await.unwrap_or(false) {
+                                warn!("Dropping web task {}: chat {} is banned", short_id, task.chat_id);
+                                let _ = hermes_shared::db::fail_task(&pool, &task_id, "User is banned").await;
+                                continue;
+                            }
+
                             // Notify user
+                            let origin = if task.scheduled_at.is_some() { "Scheduled" } else { "Web" };
                             let notify_result = web_bot.send_message(
                                 chat_id,
-                                format!("Web download started [{}]\n{}", short_id, url),
+                                decorate(format!("{} download started [{}]\n{}", origin, short_id, url)),
                             ).await;
 
                             let status_msg_id = match notify_result {
@@ -321,7 +565,8 @@ async fn main() {
 
                             // Build output dir and IPC request
                             let out_dir = commands::task_output_dir(
-                                &web_state.download_dir, task.chat_id, &task_id,
+                                web_state.storage.base_for(StorageKind::for_media(!is_video)),
+                                task.chat_id, &task_id,
                             );
                             let prefs = match &web_state.db_pool {
                                 Some(pool) => hermes_shared::db::get_user_preferences(pool, task.chat_id).await,
@@ -329,12 +574,17 @@ async fn main() {
                             };
                             let request = download_request_prefs(
                                 &task_id, &url, !is_video,
-                                &prefs.audio_format, &prefs.audio_quality,
+                                &prefs,
                                 &out_dir, task.chat_id,
                             );
 
-                            // Enqueue in task queue
-                            web_state.task_queue.enqueue(&task_id, task.chat_id, "youtube_dl").await;
+                            // Enqueue in task queue; web retries come back marked high
+                            let priority = Priority::parse(&task.priority)
+                                .max(web_state.task_priority(task.chat_id, "youtube_dl"));
+                            web_state.task_queue.enqueue(&task_id, task.chat_id, "youtube_dl", priority).await;
+                            if let Some(pool) = &web_state.db_pool {
+                                let _ = hermes_shared::db::set_task_priority(pool, &task_id, priority).await;
+                            }
 
                             // Execute download in background
                             let bot_clone = web_bot.clone();
@@ -354,7 +604,7 @@ async fn main() {
                     }
                 }
             }
-        });
+        }));
         info!("Web download queue poller started");
     }
 
@@ -368,10 +618,76 @@ async fn main() {
         .dispatch()
         .await;
 
-    // Cleanup on shutdown
+    // Cleanup on shutdown: stop intake → drain running tasks → stop worker → close pool
     info!("Bot shutting down...");
+    for job in &intake_jobs {
+        job.abort();
+    }
+    state.task_queue.close();
+
+    let drain_deadline = tokio::time::Instant::now()
+        + std::time::Duration::from_secs(commands::shutdown_drain_secs());
+    while state.task_queue.running_count().await > 0 && tokio::time::Instant::now() < drain_deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+
+    let interrupted = state.task_queue.active_task_ids().await;
+    if !interrupted.is_empty() {
+        info!("Requeueing {} unfinished task(s) for the next start", interrupted.len());
+    }
+    for task_id in &interrupted {
+        state.task_queue.fail(task_id).await;
+    }
+
     if let Err(e) = state.dispatcher.stop().await {
         error!("Error stopping worker: {}", e);
     }
+    // After the worker is gone, so a handler failing on the closed channel
+    // doesn't overwrite it: left queued, the next start resumes them
+    if let Some(pool) = &state.db_pool {
+        for task_id in &interrupted {
+            if let Err(e) = hermes_shared::db::requeue_task(pool, task_id).await {
+                error!("Failed to requeue interrupted task {}: {}", task_id, e);
+            }
+        }
+    }
+    if let Some(pool) = &state.db_pool {
+        pool.close().await;
+        info!("Database pool closed");
+    }
     info!("Hermes Download Bot stopped.");
 }
+
+/// Delays before each attempt at the admin startup notification (~1 minute in total).
+const ADMIN_NOTIFY_DELAYS_SECS: &[u64] = &[0, 5, 10, 15, 30];
+
+/// Send the "bot online" message to the admin, retrying over the first minute.
+/// Telegram refuses to message a user who never started the bot, so that case
+/// gets an explicit hint in the log instead of a bare error.
+async fn notify_admin_online(bot: Bot, admin_id: ChatId, msg: String) {
+    let attempts = ADMIN_NOTIFY_DELAYS_SECS.len();
+    for (attempt, delay) in ADMIN_NOTIFY_DELAYS_SECS.iter().enumerate() {
+        tokio::time::sleep(std::time::Duration::from_secs(*delay)).await;
+        let err = match bot.send_message(admin_id, decorate(&msg)).await {
+            Ok(_) => {
+                info!("Admin startup notification sent");
+                return;
+            }
+            Err(e) => e,
+        };
+        let unreachable = matches!(
+            err,
+            teloxide::RequestError::Api(teloxide::ApiError::ChatNotFound | teloxide::ApiError::BotBlocked)
+        );
+        if unreachable {
+            warn!(
+                "Admin startup notification failed (attempt {}/{}): {}. \
+                 The admin (ADMIN_CHAT_ID={}) must open the bot and send /start to receive notifications.",
+                attempt + 1, attempts, err, admin_id
+            );
+        } else {
+            warn!("Admin startup notification failed (attempt {}/{}): {}", attempt + 1, attempts, err);
+        }
+    }
+    error!("Giving up on the admin startup notification after {} attempts", attempts);
+}
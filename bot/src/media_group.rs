@@ -0,0 +1,129 @@
+/// Grouping playlist tracks into Telegram albums (`send_media_group`).
+///
+/// An album holds 2 to 10 items of one kind: audio can't be mixed with video.
+/// Tracks are kept in playlist order, so a run of audio followed by a video
+/// starts a new album. Files over the upload limit and runs of one are sent on
+/// their own.
+use std::path::PathBuf;
+
+/// Most items Telegram accepts in one album.
+pub const MAX_ALBUM_ITEMS: usize = 10;
+
+/// Largest file that can go into an album (the Bot API upload limit).
+pub const MAX_ALBUM_FILE_SIZE: u64 = 50 * 1024 * 1024;
+
+/// How a playlist file is sent, decided by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Audio,
+    Video,
+}
+
+impl MediaKind {
+    pub fn for_file(name: &str) -> Self {
+        let lower = name.to_lowercase();
+        if lower.ends_with(".mp4") || lower.ends_with(".webm") || lower.ends_with(".mkv") {
+            MediaKind::Video
+        } else {
+            MediaKind::Audio
+        }
+    }
+}
+
+/// A downloaded playlist file ready to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+}
+
+/// One send: an album, or a track that has to go on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Batch {
+    Album(MediaKind, Vec<Track>),
+    Single(Track),
+}
+
+/// Split `tracks` into albums of up to [`MAX_ALBUM_ITEMS`], keeping their order.
+pub fn plan_batches(tracks: Vec<Track>) -> Vec<Batch> {
+    let mut batches = Vec::new();
+    let mut run: Option<(MediaKind, Vec<Track>)> = None;
+
+    fn flush(run: &mut Option<(MediaKind, Vec<Track>)>, batches: &mut Vec<Batch>) {
+        match run.take() {
+            Some((_, mut items)) if items.len() == 1 => batches.push(Batch::Single(items.remove(0))),
+            Some((kind, items)) => batches.push(Batch::Album(kind, items)),
+            None => {}
+        }
+    }
+
+    for track in tracks {
+        if track.size > MAX_ALBUM_FILE_SIZE {
+            flush(&mut run, &mut batches);
+            batches.push(Batch::Single(track));
+            continue;
+        }
+        let kind = MediaKind::for_file(&track.name);
+        match &mut run {
+            Some((run_kind, items)) if *run_kind == kind && items.len() < MAX_ALBUM_ITEMS => items.push(track),
+            _ => {
+                flush(&mut run, &mut batches);
+                run = Some((kind, vec![track]));
+            }
+        }
+    }
+    flush(&mut run, &mut batches);
+    batches
+}
+
+/// Caption for the first item of an album: the playlist name and which tracks it holds.
+pub fn album_caption(playlist: &str, first: usize, count: usize, total: usize) -> String {
+    format!("🎵 {} · tracks {}–{} of {}", playlist, first, first + count - 1, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str, size: u64) -> Track {
+        Track { path: PathBuf::from(format!("/d/{}", name)), name: name.to_string(), size }
+    }
+
+    fn shape(batches: &[Batch]) -> Vec<String> {
+        batches
+            .iter()
+            .map(|b| match b {
+                Batch::Album(kind, items) => format!("{:?}x{}", kind, items.len()),
+                Batch::Single(t) => t.name.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_albums_of_ten_with_a_lone_remainder() {
+        let tracks = (1..=21).map(|i| track(&format!("{:02}.mp3", i), 1024)).collect();
+        let batches = plan_batches(tracks);
+        assert_eq!(shape(&batches), vec!["Audiox10", "Audiox10", "21.mp3"]);
+    }
+
+    #[test]
+    fn test_kind_changes_and_big_files_break_albums() {
+        let tracks = vec![
+            track("a.mp3", 1024),
+            track("b.m4a", 1024),
+            track("big.mp3", MAX_ALBUM_FILE_SIZE + 1),
+            track("c.mp3", 1024),
+            track("d.MP4", 1024),
+            track("e.webm", 1024),
+        ];
+        let batches = plan_batches(tracks);
+        assert_eq!(shape(&batches), vec!["Audiox2", "big.mp3", "c.mp3", "Videox2"]);
+    }
+
+    #[test]
+    fn test_album_caption() {
+        assert_eq!(album_caption("Mix", 11, 10, 25), "🎵 Mix · tracks 11–20 of 25");
+        assert_eq!(album_caption("Mix", 1, 2, 2), "🎵 Mix · tracks 1–2 of 2");
+    }
+}
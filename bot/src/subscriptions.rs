@@ -0,0 +1,48 @@
+/// New-upload detection for `/subscribe`.
+///
+/// Each check first lists the channel's newest uploads (`channel_latest`) and
+/// compares their ids with the subscription's yt-dlp download archive. The
+/// download pass only runs when something there isn't archived yet.
+use std::collections::HashSet;
+
+/// Ids from `latest` that aren't in `archive`, the contents of a yt-dlp
+/// download archive (one `<extractor> <id>` per line). Order is kept.
+pub fn unseen_ids<'a>(latest: &'a [String], archive: &str) -> Vec<&'a str> {
+    let seen: HashSet<&str> = archive
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .collect();
+    latest
+        .iter()
+        .map(String::as_str)
+        .filter(|id| !seen.contains(id))
+        .collect()
+}
+
+/// "30 min", "6h" or "1h 30 min" for the check interval.
+pub fn format_interval(secs: i64) -> String {
+    let minutes = secs / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{} min", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {} min", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unseen_ids_against_archive() {
+        let latest: Vec<String> = ["new2", "new1", "old1", "old2"].iter().map(|s| s.to_string()).collect();
+        let archive = "youtube old2\nyoutube old1\n\n";
+        assert_eq!(unseen_ids(&latest, archive), vec!["new2", "new1"]);
+        assert_eq!(unseen_ids(&latest, ""), vec!["new2", "new1", "old1", "old2"]);
+        assert!(unseen_ids(&latest[2..], archive).is_empty());
+
+        assert_eq!(format_interval(300), "5 min");
+        assert_eq!(format_interval(86_400), "24h");
+        assert_eq!(format_interval(5_400), "1h 30 min");
+    }
+}
@@ -0,0 +1,76 @@
+/// Process resource usage read from `/proc` (Linux only).
+///
+/// On other platforms every reader returns `None` and callers show "n/a".
+use std::path::PathBuf;
+
+/// Clock ticks per second used by `/proc/<pid>/stat` (USER_HZ, 100 on Linux).
+const CLK_TCK: f64 = 100.0;
+
+/// Resource snapshot for one process.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessStats {
+    /// Resident set size in KiB.
+    pub rss_kb: u64,
+    /// Virtual memory size in KiB.
+    pub vm_kb: u64,
+    pub threads: u64,
+    /// Total user + system CPU time in seconds.
+    pub cpu_secs: f64,
+    pub open_fds: Option<usize>,
+}
+
+/// Read stats for a process, or for this process when `pid` is `None`.
+pub fn process_stats(pid: Option<u32>) -> Option<ProcessStats> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let dir = match pid {
+        Some(p) => PathBuf::from(format!("/proc/{}", p)),
+        None => PathBuf::from("/proc/self"),
+    };
+
+    let status = std::fs::read_to_string(dir.join("status")).ok()?;
+    let mut stats = ProcessStats::default();
+    for line in status.lines() {
+        let mut parts = line.split_whitespace();
+        let key = parts.next().unwrap_or("");
+        let value = parts.next().and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+        match key {
+            "VmRSS:" => stats.rss_kb = value,
+            "VmSize:" => stats.vm_kb = value,
+            "Threads:" => stats.threads = value,
+            _ => {}
+        }
+    }
+
+    // utime and stime are fields 14 and 15; split after the parenthesised comm
+    // since the process name may contain spaces.
+    if let Ok(stat) = std::fs::read_to_string(dir.join("stat")) {
+        if let Some(rest) = stat.rsplit_once(')').map(|(_, r)| r) {
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            let ticks = |i: usize| fields.get(i).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+            stats.cpu_secs = (ticks(11) + ticks(12)) as f64 / CLK_TCK;
+        }
+    }
+
+    stats.open_fds = std::fs::read_dir(dir.join("fd")).ok().map(|d| d.count());
+    Some(stats)
+}
+
+/// Host load averages (1, 5, 15 min) from `/proc/loadavg`.
+pub fn load_average() -> Option<(f64, f64, f64)> {
+    let text = std::fs::read_to_string("/proc/loadavg").ok()?;
+    let mut parts = text.split_whitespace().map(|v| v.parse::<f64>().ok());
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+/// Format KiB as a human-readable size.
+pub fn format_kb(kb: u64) -> String {
+    if kb >= 1024 * 1024 {
+        format!("{:.2} GB", kb as f64 / 1024.0 / 1024.0)
+    } else if kb >= 1024 {
+        format!("{:.1} MB", kb as f64 / 1024.0)
+    } else {
+        format!("{} KB", kb)
+    }
+}
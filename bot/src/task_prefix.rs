@@ -0,0 +1,121 @@
+//! Resolving the short task-id prefixes users type into full task ids.
+//!
+//! Commands like /cancel accept the first few characters of a task id. A
+//! prefix can match several tasks or none; both cases are reported instead of
+//! silently picking one.
+
+/// How many candidates an ambiguous-prefix reply lists.
+pub const MAX_LISTED: usize = 5;
+
+/// Largest edit distance still offered as a "did you mean" suggestion.
+const MAX_SUGGEST_DISTANCE: usize = 2;
+
+/// Outcome of matching a prefix against a set of tasks.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PrefixMatch<T> {
+    /// Exactly one task id starts with the prefix (or equals it).
+    Unique(T),
+    /// Several task ids start with the prefix.
+    Ambiguous(Vec<T>),
+    /// Nothing matched; `closest` is a near miss worth suggesting.
+    NotFound { closest: Option<T> },
+}
+
+/// Match `prefix` against `items`, using `id` to read each item's task id.
+pub fn resolve_task_prefix<T>(items: Vec<T>, prefix: &str, id: impl Fn(&T) -> &str) -> PrefixMatch<T> {
+    let prefix = prefix.trim().to_lowercase();
+
+    if let Some(pos) = items.iter().position(|t| id(t).eq_ignore_ascii_case(&prefix)) {
+        return PrefixMatch::Unique(items.into_iter().nth(pos).expect("position is in range"));
+    }
+
+    let (mut matches, rest): (Vec<T>, Vec<T>) = items
+        .into_iter()
+        .partition(|t| id(t).to_lowercase().starts_with(&prefix));
+
+    match matches.len() {
+        1 => PrefixMatch::Unique(matches.remove(0)),
+        0 => {
+            let closest = rest
+                .into_iter()
+                .map(|t| {
+                    let head: String = id(&t).to_lowercase().chars().take(prefix.chars().count()).collect();
+                    (edit_distance(&head, &prefix), t)
+                })
+                .filter(|(d, _)| *d <= MAX_SUGGEST_DISTANCE)
+                .min_by_key(|(d, _)| *d)
+                .map(|(_, t)| t);
+            PrefixMatch::NotFound { closest }
+        }
+        _ => PrefixMatch::Ambiguous(matches),
+    }
+}
+
+/// Reply listing the tasks an ambiguous prefix matched, as (full id, url) pairs.
+pub fn ambiguous_text(prefix: &str, candidates: &[(String, Option<String>)]) -> String {
+    let mut text = format!("\"{}\" matches {} tasks. Use a longer id:\n", prefix, candidates.len());
+    for (id, url) in candidates.iter().take(MAX_LISTED) {
+        let short: String = id.chars().take(12).collect();
+        match url {
+            Some(url) => text.push_str(&format!("\n[{}] {}", short, url)),
+            None => text.push_str(&format!("\n[{}]", short)),
+        }
+    }
+    if candidates.len() > MAX_LISTED {
+        text.push_str(&format!("\n...and {} more", candidates.len() - MAX_LISTED));
+    }
+    text
+}
+
+/// Reply for a prefix that matched nothing, with an optional suggestion.
+pub fn not_found_text(prefix: &str, closest: Option<&str>) -> String {
+    let mut text = format!("No task found matching \"{}\".", prefix);
+    if let Some(id) = closest {
+        let short: String = id.chars().take(8).collect();
+        text.push_str(&format!("\nDid you mean [{}]?", short));
+    }
+    text
+}
+
+/// Levenshtein distance between two short strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids() -> Vec<&'static str> {
+        vec!["abc12345-0000", "abc19999-0000", "f00dcafe-0000"]
+    }
+
+    #[test]
+    fn test_unique_and_ambiguous() {
+        assert_eq!(resolve_task_prefix(ids(), "f00d", |t| t), PrefixMatch::Unique("f00dcafe-0000"));
+        assert_eq!(resolve_task_prefix(ids(), "ABC123", |t| t), PrefixMatch::Unique("abc12345-0000"));
+        assert_eq!(
+            resolve_task_prefix(ids(), "abc1", |t| t),
+            PrefixMatch::Ambiguous(vec!["abc12345-0000", "abc19999-0000"])
+        );
+    }
+
+    #[test]
+    fn test_not_found_suggests_close_match() {
+        assert_eq!(
+            resolve_task_prefix(ids(), "f00dcafx", |t| t),
+            PrefixMatch::NotFound { closest: Some("f00dcafe-0000") }
+        );
+        assert_eq!(resolve_task_prefix(ids(), "zzzzzzzz", |t| t), PrefixMatch::NotFound { closest: None });
+    }
+}
@@ -0,0 +1,213 @@
+/// User-facing text helpers.
+///
+/// All bot messages pass through [`decorate`] so operators can switch to
+/// ASCII-friendly output with `PLAIN_TEXT_MODE=true`.
+use once_cell::sync::Lazy;
+
+/// Whether emoji and decorative glyphs should be stripped from messages.
+static PLAIN_TEXT_MODE: Lazy<bool> = Lazy::new(|| {
+    std::env::var("PLAIN_TEXT_MODE")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false)
+});
+
+/// Prepare a user-facing string for sending.
+/// Returns the text unchanged unless plain-text mode is enabled.
+pub fn decorate(text: impl AsRef<str>) -> String {
+    if *PLAIN_TEXT_MODE {
+        strip_decorations(text.as_ref(), false)
+    } else {
+        text.as_ref().to_string()
+    }
+}
+
+/// Like [`decorate`], for messages sent with `ParseMode::MarkdownV2`.
+/// ASCII substitutes are escaped so they don't break the markup.
+pub fn decorate_markdown(text: impl AsRef<str>) -> String {
+    if *PLAIN_TEXT_MODE {
+        strip_decorations(text.as_ref(), true)
+    } else {
+        text.as_ref().to_string()
+    }
+}
+
+/// Escape special characters for Telegram MarkdownV2.
+/// Required characters to escape: _ * [ ] ( ) ~ ` > # + - = | { } . !
+pub fn escape_markdown_v2(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|' | '{' | '}' | '.' | '!' => {
+                format!("\\{}", c)
+            }
+            '\\' => "\\\\".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Escape text placed inside a MarkdownV2 `code` span or ```pre``` block,
+/// where only '`' and '\' are special.
+pub fn escape_markdown_v2_code(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('`', "\\`")
+}
+
+/// Longest file name, in bytes, handed to Telegram or the filesystem.
+pub const MAX_FILENAME_BYTES: usize = 200;
+
+/// Cap `name` at `max` bytes without splitting a UTF-8 character, keeping
+/// the extension intact so Telegram still recognises the file type.
+pub fn truncate_filename(name: &str, max: usize) -> String {
+    if name.len() <= max {
+        return name.to_string();
+    }
+    let (stem, ext) = match name.rfind('.') {
+        // Only treat short suffixes as an extension, not a dot inside the title
+        Some(dot) if dot > 0 && name.len() - dot <= 16 && name.len() - dot < max => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let mut end = max - ext.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", stem[..end].trim_end(), ext)
+}
+
+/// Remove emoji and map decorative glyphs to ASCII equivalents.
+///
+/// A space that directly follows a removed emoji is dropped too, so
+/// "🎵 Title" becomes "Title" rather than " Title".
+pub fn strip_decorations(text: &str, markdown: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut skip_space = false;
+
+    for c in text.chars() {
+        if skip_space && c == ' ' {
+            skip_space = false;
+            continue;
+        }
+        skip_space = false;
+
+        if let Some(ascii) = ascii_replacement(c) {
+            if markdown {
+                for a in ascii.chars() {
+                    out.push('\\');
+                    out.push(a);
+                }
+            } else {
+                out.push_str(ascii);
+            }
+        } else if is_emoji(c) {
+            // Only swallow the following space when the emoji started a word
+            skip_space = out.is_empty() || out.ends_with(char::is_whitespace);
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+/// ASCII stand-ins for decorative characters that carry meaning.
+fn ascii_replacement(c: char) -> Option<&'static str> {
+    match c {
+        '•' => Some("-"),
+        '—' | '–' => Some("-"),
+        '…' => Some("..."),
+        '█' => Some("#"),
+        '░' => Some("."),
+        '✓' | '✔' => Some("+"),
+        '✖' | '✗' => Some("x"),
+        '→' => Some("->"),
+        '·' => Some("-"),
+        '━' | '─' => Some("-"),
+        _ => None,
+    }
+}
+
+/// Whether a character belongs to an emoji / pictograph block.
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF   // pictographs, emoticons, transport, symbols
+        | 0x2600..=0x27BF   // misc symbols & dingbats
+        | 0x2B00..=0x2BFF   // arrows & stars (⭐, ⬆️, ⬇️)
+        | 0x2300..=0x23FF   // technical (⏳, ⏱, ⌛)
+        | 0x2190..=0x21FF   // arrows (↩)
+        | 0xFE00..=0xFE0F   // variation selectors
+        | 0x200D            // zero-width joiner
+        | 0x20E3            // combining keycap
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_leading_emoji_and_space() {
+        assert_eq!(strip_decorations("🎵 Hermes Download Bot", false), "Hermes Download Bot");
+        assert_eq!(strip_decorations("⚠️ File too large", false), "File too large");
+    }
+
+    #[test]
+    fn test_strips_emoji_mid_line() {
+        assert_eq!(
+            strip_decorations("Status: Enabled ✅\n🔗 Link", false),
+            "Status: Enabled \nLink"
+        );
+    }
+
+    #[test]
+    fn test_maps_decorative_glyphs() {
+        assert_eq!(strip_decorations("• one — two…", false), "- one - two...");
+        assert_eq!(strip_decorations("[██░░]", false), "[##..]");
+    }
+
+    #[test]
+    fn test_markdown_substitutes_are_escaped() {
+        assert_eq!(strip_decorations("✅ *Done* — ok…", true), "*Done* \\- ok\\.\\.\\.");
+    }
+
+    #[test]
+    fn test_escape_markdown_v2() {
+        assert_eq!(escape_markdown_v2("-1001234"), "\\-1001234");
+        assert_eq!(
+            escape_markdown_v2("Song (Live) - v1.2!"),
+            "Song \\(Live\\) \\- v1\\.2\\!"
+        );
+        assert_eq!(escape_markdown_v2("a_b*c[d]`e`"), "a\\_b\\*c\\[d\\]\\`e\\`");
+        assert_eq!(escape_markdown_v2("back\\slash"), "back\\\\slash");
+        assert_eq!(escape_markdown_v2("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_escape_markdown_v2_code() {
+        assert_eq!(
+            escape_markdown_v2_code("https://youtu.be/x?list=a-b.c"),
+            "https://youtu.be/x?list=a-b.c"
+        );
+        assert_eq!(escape_markdown_v2_code("a`b\\c"), "a\\`b\\\\c");
+    }
+
+    #[test]
+    fn test_truncate_filename_keeps_extension_and_char_boundaries() {
+        assert_eq!(truncate_filename("short.mp3", 200), "short.mp3");
+
+        let long = format!("{}.mp4", "a".repeat(300));
+        let cut = truncate_filename(&long, 200);
+        assert_eq!(cut.len(), 200);
+        assert!(cut.ends_with(".mp4"));
+
+        // 3-byte characters: the cut must land on a boundary, never mid-character
+        let cjk = format!("{}.m4a", "音".repeat(100));
+        let cut = truncate_filename(&cjk, 200);
+        assert!(cut.len() <= 200);
+        assert!(cut.ends_with(".m4a"));
+        assert_eq!(cut.trim_end_matches(".m4a").chars().count(), 65);
+    }
+
+    #[test]
+    fn test_plain_text_untouched() {
+        let text = "Queue Status:\n  Running: 1/3\n[====      ] 50%";
+        assert_eq!(strip_decorations(text, false), text);
+    }
+}
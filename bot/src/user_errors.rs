@@ -0,0 +1,162 @@
+/// Mapping of internal errors to user-facing messages.
+///
+/// Telegram API errors, worker errors and Hermes errors all render through
+/// here so the same failure reads the same wherever it surfaces. Details that
+/// only an operator can act on go in `admin_hint` and are logged, not shown.
+use hermes_shared::errors::{HermesError, IpcError, WorkerError};
+use hermes_shared::ipc_protocol::IPCResponse;
+use tracing::warn;
+
+/// A message safe to show the user, plus an optional operator hint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserError {
+    pub message: String,
+    pub admin_hint: Option<String>,
+}
+
+impl UserError {
+    fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), admin_hint: None }
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.admin_hint = Some(hint.into());
+        self
+    }
+
+    /// Log the admin hint (if any) and return the user message.
+    pub fn render(self, context: &str) -> String {
+        if let Some(hint) = &self.admin_hint {
+            warn!("{}: {}", context, hint);
+        }
+        self.message
+    }
+}
+
+/// Map a Telegram API error. `action` completes the fallback "Failed to {action}: ...".
+pub fn from_telegram(err: &teloxide::RequestError, action: &str) -> UserError {
+    let err_str = err.to_string();
+    if err_str.contains("chat not found") {
+        UserError::new("I don't have access to that channel.\nAdd me to the channel first, or make sure the link is correct.")
+    } else if err_str.contains("message to copy not found") || err_str.contains("message not found") {
+        UserError::new("Message not found. It may have been deleted.")
+    } else if err_str.contains("bot was kicked") || err_str.contains("bot is not a member") {
+        UserError::new("I'm not a member of that channel. Add me first.")
+    } else if err_str.contains("file is too big") || err_str.contains("Request Entity Too Large") {
+        UserError::new("The file is too large for Telegram.")
+    } else if let teloxide::RequestError::RetryAfter(secs) = err {
+        UserError::new(format!("Telegram is rate limiting me. Try again in {}s.", secs.as_secs()))
+    } else {
+        UserError::new(format!("Failed to {}: {}", action, err_str))
+    }
+}
+
+/// Map an error reported by the Python worker.
+pub fn from_worker(err: &WorkerError) -> UserError {
+    match err {
+        WorkerError::NetworkTimeout => {
+            UserError::new("The source took too long to respond. Please try again in a moment.")
+        }
+        WorkerError::RateLimited { retry_after_secs } => UserError::new(format!(
+            "The source is rate limiting downloads. Try again in {}s.", retry_after_secs
+        )),
+        WorkerError::AuthRequired => {
+            UserError::new("This video requires sign-in. Try /retrycookie with the task id.")
+                .with_hint("Cookies are missing or expired; refresh them with /upcook")
+        }
+        WorkerError::VideoUnavailable(message) => UserError::new(message.clone()),
+        // The worker already sends a user-friendly message for other codes
+        WorkerError::Remote { code, message, .. } => {
+            UserError::new(message.clone()).with_hint(format!("worker error code {}", code))
+        }
+        WorkerError::Unknown(message) => UserError::new("Something went wrong while downloading.")
+            .with_hint(message.clone()),
+    }
+}
+
+/// Map a worker error event.
+pub fn from_ipc_response(response: &IPCResponse) -> UserError {
+    from_worker(&WorkerError::from_ipc_data(&response.data))
+}
+
+/// Map an internal Hermes error (worker process, database, IO...).
+pub fn from_hermes(err: &HermesError) -> UserError {
+    let hint = err.to_string();
+    match err {
+        HermesError::Ipc(IpcError::Timeout(_)) => {
+            UserError::new("The download worker took too long to respond. Please try again.")
+        }
+        HermesError::Ipc(IpcError::StdinFull(_)) => {
+            UserError::new("The download worker is not responding. Please try again shortly.").with_hint(hint)
+        }
+        HermesError::Ipc(_) => {
+            UserError::new("The download worker is offline. Please try again shortly.").with_hint(hint)
+        }
+        HermesError::Worker(worker) => from_worker(worker),
+        HermesError::Telegram(message) => UserError::new(format!("Telegram error: {}", message)),
+        HermesError::Database(_) => UserError::new("A database error occurred. Please try again.").with_hint(hint),
+        HermesError::Config(_) | HermesError::Io(_) | HermesError::Json(_) => {
+            UserError::new("Something went wrong. Please try again.").with_hint(hint)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_auth_error_has_admin_hint() {
+        let data = serde_json::json!({"error_code": "COOKIE_EXPIRED", "message": "Cookies expired"});
+        let err = from_worker(&WorkerError::from_ipc_data(&data));
+        assert!(err.message.contains("/retrycookie"));
+        assert!(err.admin_hint.unwrap().contains("/upcook"));
+    }
+
+    #[test]
+    fn test_worker_rate_limit_and_remote_messages() {
+        let data = serde_json::json!({"error_code": "RATE_LIMITED", "retry_after": 30});
+        assert_eq!(
+            from_worker(&WorkerError::from_ipc_data(&data)).message,
+            "The source is rate limiting downloads. Try again in 30s."
+        );
+
+        let data = serde_json::json!({"error_code": "VIDEO_PRIVATE", "message": "This video is private"});
+        assert_eq!(from_worker(&WorkerError::from_ipc_data(&data)).message, "This video is private");
+
+        let data = serde_json::json!({"error_code": "FFMPEG_ERROR", "message": "Conversion failed"});
+        let err = from_worker(&WorkerError::from_ipc_data(&data));
+        assert_eq!(err.message, "Conversion failed");
+        assert_eq!(err.admin_hint.as_deref(), Some("worker error code FFMPEG_ERROR"));
+    }
+
+    #[test]
+    fn test_hermes_errors() {
+        let offline = from_hermes(&HermesError::Ipc(IpcError::NotRunning));
+        assert_eq!(offline.message, "The download worker is offline. Please try again shortly.");
+        assert_eq!(offline.admin_hint.as_deref(), Some("IPC error: Worker process not running"));
+
+        let timeout = from_hermes(&HermesError::Ipc(IpcError::Timeout(30)));
+        assert!(timeout.message.contains("too long"));
+        assert!(timeout.admin_hint.is_none());
+
+        let config = from_hermes(&HermesError::Config("bad".into()));
+        assert_eq!(config.message, "Something went wrong. Please try again.");
+    }
+
+    #[test]
+    fn test_telegram_errors() {
+        use teloxide::ApiError;
+        let err = teloxide::RequestError::Api(ApiError::ChatNotFound);
+        assert!(from_telegram(&err, "forward").message.starts_with("I don't have access"));
+
+        let err = teloxide::RequestError::Api(ApiError::Unknown("Bad Request: message to copy not found".into()));
+        assert_eq!(from_telegram(&err, "forward").message, "Message not found. It may have been deleted.");
+
+        let err = teloxide::RequestError::RetryAfter(std::time::Duration::from_secs(12));
+        assert_eq!(from_telegram(&err, "forward").message, "Telegram is rate limiting me. Try again in 12s.");
+
+        let err = teloxide::RequestError::Api(ApiError::Unknown("Bad Request: something odd".into()));
+        assert!(from_telegram(&err, "forward").message.starts_with("Failed to forward: "));
+    }
+}
@@ -1 +1,2 @@
 pub mod python_dispatcher;
+pub mod worker_pool;
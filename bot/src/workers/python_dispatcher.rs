@@ -5,7 +5,9 @@
 /// Stderr is forwarded to tracing logs.
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, mpsc};
@@ -100,10 +102,90 @@ fn discover_extra_paths() -> Vec<String> {
     extra
 }
 
-use hermes_shared::ipc_protocol::{IPCRequest, IPCResponse};
+use hermes_shared::ipc_protocol::{cancel_request, IPCAction, IPCEvent, IPCRequest, IPCResponse};
 use hermes_shared::errors::{IpcError, HermesError};
 
+/// Order in which queued requests are written to the worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Quick requests a user is waiting on (health, formats, search, info).
+    Interactive,
+    /// Long-running downloads and uploads.
+    Bulk,
+}
+
+impl Priority {
+    /// Default priority for an action.
+    pub fn for_action(action: &IPCAction) -> Self {
+        match action {
+            IPCAction::HealthCheck
+            | IPCAction::GetFormats
+            | IPCAction::GetVideoInfo
+            | IPCAction::Probe
+            | IPCAction::SpotifyResolve
+            | IPCAction::GetThumbnail
+            | IPCAction::YoutubeSearch
+            | IPCAction::PlaylistPreview
+            | IPCAction::CacheStats
+            | IPCAction::MtprotoCopyPost
+            | IPCAction::Cancel => Priority::Interactive,
+            IPCAction::YoutubeDl
+            | IPCAction::Playlist
+            | IPCAction::Concat
+            | IPCAction::Transcribe
+            | IPCAction::ExtractAudio
+            | IPCAction::Compress
+            | IPCAction::SplitMedia
+            | IPCAction::DownloadSubtitles
+            | IPCAction::ChannelLatest
+            | IPCAction::CacheCleanup
+            | IPCAction::MtprotoUpload => Priority::Bulk,
+        }
+    }
+}
+
+/// How long `send` waits for room in a full stdin queue before giving up.
+const STDIN_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Push a line onto a stdin queue, failing with `StdinFull` if it stays full.
+async fn push_with_timeout(tx: &mpsc::Sender<String>, line: String, timeout: Duration) -> Result<(), IpcError> {
+    match tx.send_timeout(line, timeout).await {
+        Ok(()) => Ok(()),
+        Err(mpsc::error::SendTimeoutError::Timeout(_)) => Err(IpcError::StdinFull(timeout.as_secs())),
+        Err(mpsc::error::SendTimeoutError::Closed(_)) => Err(IpcError::WriteFailed("stdin writer closed".into())),
+    }
+}
+
+/// First wait before restarting a dead worker; doubles after each failure.
+const RESTART_BACKOFF_MIN: Duration = Duration::from_secs(1);
+/// Longest wait between restart attempts.
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// A worker that stayed up this long starts the backoff over when it dies.
+const RESTART_STABLE_AFTER: Duration = Duration::from_secs(60);
+/// How often the supervisor checks the worker process.
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What the supervisor did about the worker, for admin notifications.
+#[derive(Debug, Clone)]
+pub enum WorkerEvent {
+    /// The worker died (or never started); `failed` pending tasks were failed.
+    Down { status: String, failed: usize },
+    /// Restart attempt `attempt` failed; the next one is in `retry_in`.
+    RestartFailed { attempt: u32, error: String, retry_in: Duration },
+    /// The worker is running again, after `attempt` attempts.
+    Restarted { attempt: u32 },
+}
+
+/// Stdin writer queues, drained interactive-first.
+struct StdinQueues {
+    interactive: mpsc::Sender<String>,
+    bulk: mpsc::Sender<String>,
+}
+
 /// Manages a Python worker subprocess.
+///
+/// Clones share the same worker; `supervise` runs on one.
+#[derive(Clone)]
 pub struct PythonDispatcher {
     /// Path to the worker directory (containing worker/ package).
     worker_dir: PathBuf,
@@ -111,12 +193,16 @@ pub struct PythonDispatcher {
     python_bin: String,
     /// Child process handle.
     child: Arc<Mutex<Option<Child>>>,
-    /// Sender for writing requests to worker stdin.
-    stdin_tx: Arc<Mutex<Option<mpsc::Sender<String>>>>,
+    /// Senders for writing requests to worker stdin.
+    stdin_tx: Arc<Mutex<Option<StdinQueues>>>,
     /// Per-task response channels.
     pending: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<IPCResponse>>>>,
     /// Whether the worker is running.
     running: Arc<Mutex<bool>>,
+    /// When a send last found stdin full; cleared by the next successful send.
+    stdin_stalled_since: Arc<Mutex<Option<Instant>>>,
+    /// Set by `stop`, so the supervisor doesn't bring the worker back.
+    stopping: Arc<AtomicBool>,
 }
 
 impl PythonDispatcher {
@@ -134,6 +220,8 @@ impl PythonDispatcher {
             stdin_tx: Arc::new(Mutex::new(None)),
             pending: Arc::new(Mutex::new(HashMap::new())),
             running: Arc::new(Mutex::new(false)),
+            stdin_stalled_since: Arc::new(Mutex::new(None)),
+            stopping: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -177,13 +265,21 @@ impl PythonDispatcher {
         let stdin = child.stdin.take()
             .ok_or_else(|| IpcError::WriteFailed("No stdin handle".into()))?;
 
-        // Create stdin writer channel
-        let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(100);
+        // Create stdin writer channels (interactive requests jump the bulk queue)
+        let (interactive_tx, mut interactive_rx) = mpsc::channel::<String>(100);
+        let (bulk_tx, mut bulk_rx) = mpsc::channel::<String>(100);
+        let stdin_tx = StdinQueues { interactive: interactive_tx, bulk: bulk_tx };
 
         // Stdin writer task
         let _stdin_handle = tokio::spawn(async move {
             let mut stdin = stdin;
-            while let Some(line) = stdin_rx.recv().await {
+            loop {
+                let line = tokio::select! {
+                    biased;
+                    Some(line) = interactive_rx.recv() => line,
+                    Some(line) = bulk_rx.recv() => line,
+                    else => break,
+                };
                 if let Err(e) = stdin.write_all(line.as_bytes()).await {
                     error!("Failed to write to worker stdin: {}", e);
                     break;
@@ -260,39 +356,6 @@ impl PythonDispatcher {
         *self.stdin_tx.lock().await = Some(stdin_tx);
         *self.running.lock().await = true;
 
-        // Spawn a background task to monitor the child process exit.
-        // This ensures we log the exit code if the worker dies unexpectedly.
-        let child_arc = self.child.clone();
-        let running_monitor = self.running.clone();
-        tokio::spawn(async move {
-            // Wait briefly to allow startup, then poll periodically
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-            loop {
-                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                let mut guard = child_arc.lock().await;
-                if let Some(ref mut child) = *guard {
-                    match child.try_wait() {
-                        Ok(Some(status)) => {
-                            error!("Python worker exited unexpectedly with status: {}", status);
-                            *running_monitor.lock().await = false;
-                            *guard = None;
-                            break;
-                        }
-                        Ok(None) => {
-                            // Still running
-                        }
-                        Err(e) => {
-                            error!("Failed to poll worker process status: {}", e);
-                            break;
-                        }
-                    }
-                } else {
-                    // Child handle was taken (e.g., by stop()), exit monitor
-                    break;
-                }
-            }
-        });
-
         // Brief startup health check: wait up to 3 seconds to confirm the worker
         // hasn't crashed immediately during initialization.
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
@@ -325,10 +388,12 @@ impl PythonDispatcher {
     /// Send a request and get a channel to receive responses.
     ///
     /// Returns an unbounded receiver that will get all responses for this task_id
-    /// (progress updates, then final done/error).
+    /// (progress updates, then final done/error). Interactive requests are written
+    /// before any bulk requests still waiting for stdin.
     pub async fn send(
         &self,
         request: &IPCRequest,
+        priority: Priority,
     ) -> Result<mpsc::UnboundedReceiver<IPCResponse>, HermesError> {
         if !*self.running.lock().await {
             return Err(IpcError::NotRunning.into());
@@ -343,31 +408,50 @@ impl PythonDispatcher {
 
         // Send to stdin writer
         let stdin_tx = self.stdin_tx.lock().await;
-        if let Some(tx) = stdin_tx.as_ref() {
-            tx.send(json).await
-                .map_err(|e| IpcError::WriteFailed(e.to_string()))?;
+        if let Some(queues) = stdin_tx.as_ref() {
+            let tx = match priority {
+                Priority::Interactive => &queues.interactive,
+                Priority::Bulk => &queues.bulk,
+            };
+            // A worker that stopped reading stdin must not hang every caller
+            if let Err(e) = push_with_timeout(tx, json, STDIN_SEND_TIMEOUT).await {
+                drop(stdin_tx);
+                self.pending.lock().await.remove(&request.task_id);
+                if matches!(e, IpcError::StdinFull(_)) {
+                    let mut stalled = self.stdin_stalled_since.lock().await;
+                    let since = *stalled.get_or_insert_with(Instant::now);
+                    error!("Worker stdin full for task {} (stalled for {}s), worker appears hung",
+                        request.task_id, since.elapsed().as_secs());
+                }
+                return Err(e.into());
+            }
         } else {
             return Err(IpcError::NotRunning.into());
         }
+        drop(stdin_tx);
+
+        if let Some(since) = self.stdin_stalled_since.lock().await.take() {
+            info!("Worker stdin recovered after {}s", since.elapsed().as_secs());
+        }
 
         Ok(rx)
     }
 
     /// Send a request and wait for the final response (done or error).
-    /// Ignores progress events.
+    /// Ignores progress and retry events. Priority follows the request action.
     pub async fn send_and_wait(
         &self,
         request: &IPCRequest,
         timeout_secs: u64,
     ) -> Result<IPCResponse, HermesError> {
-        let mut rx = self.send(request).await?;
+        let mut rx = self.send(request, Priority::for_action(&request.action)).await?;
 
         let result = tokio::time::timeout(
             std::time::Duration::from_secs(timeout_secs),
             async {
                 while let Some(response) = rx.recv().await {
-                    if response.is_progress() {
-                        continue; // Skip progress, wait for final
+                    if response.is_interim() {
+                        continue; // Skip progress and retries, wait for final
                     }
                     return Ok(response);
                 }
@@ -383,9 +467,122 @@ impl PythonDispatcher {
         result
     }
 
+    /// Tell the worker to stop `task_id`, killing its yt-dlp/ffmpeg process.
+    ///
+    /// Leaves the task's response channel open: its handler gets the worker's
+    /// `CANCELLED` reply (or the queue's cancel notice first) and removes the
+    /// pending entry itself. Jumps ahead of queued bulk requests.
+    pub async fn cancel(&self, task_id: &str) -> Result<(), HermesError> {
+        if !*self.running.lock().await {
+            return Err(IpcError::NotRunning.into());
+        }
+        let json = cancel_request(task_id).to_json_line()
+            .map_err(|e| IpcError::WriteFailed(e.to_string()))?;
+        let stdin_tx = self.stdin_tx.lock().await;
+        let queues = stdin_tx.as_ref().ok_or(IpcError::NotRunning)?;
+        push_with_timeout(&queues.interactive, json, STDIN_SEND_TIMEOUT).await?;
+        info!("Sent cancel for task {}", task_id);
+        Ok(())
+    }
+
+    /// Keep the worker running: when it exits (or failed to start), fail the
+    /// tasks waiting on it and restart it with exponential backoff. Returns
+    /// what happened, for notifying the admin. Ends after `stop`.
+    pub fn supervise(&self) -> mpsc::UnboundedReceiver<WorkerEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = RESTART_BACKOFF_MIN;
+            let mut up_since = Instant::now();
+            while let Some(status) = this.wait_for_exit().await {
+                let failed = this.fail_pending().await;
+                error!("Python worker down ({}), failed {} pending task(s)", status, failed);
+                let _ = tx.send(WorkerEvent::Down { status, failed });
+                if up_since.elapsed() >= RESTART_STABLE_AFTER {
+                    backoff = RESTART_BACKOFF_MIN;
+                }
+
+                let mut attempt = 0;
+                loop {
+                    info!("Restarting Python worker in {}s", backoff.as_secs());
+                    tokio::time::sleep(backoff).await;
+                    if this.stopping.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    attempt += 1;
+                    let result = this.start().await;
+                    backoff = (backoff * 2).min(RESTART_BACKOFF_MAX);
+                    match result {
+                        Ok(()) => {
+                            info!("Python worker restarted (attempt {})", attempt);
+                            let _ = tx.send(WorkerEvent::Restarted { attempt });
+                            up_since = Instant::now();
+                            break;
+                        }
+                        Err(e) => {
+                            error!("Python worker restart attempt {} failed: {}", attempt, e);
+                            let _ = tx.send(WorkerEvent::RestartFailed {
+                                attempt,
+                                error: e.to_string(),
+                                retry_in: backoff,
+                            });
+                        }
+                    }
+                }
+            }
+            debug!("Worker supervisor ended");
+        });
+        rx
+    }
+
+    /// Poll until the worker process is gone and return its exit status, or
+    /// None once `stop` was called.
+    async fn wait_for_exit(&self) -> Option<String> {
+        loop {
+            tokio::time::sleep(SUPERVISE_INTERVAL).await;
+            if self.stopping.load(Ordering::SeqCst) {
+                return None;
+            }
+            let mut guard = self.child.lock().await;
+            let Some(child) = guard.as_mut() else {
+                // start() failed, or caught a crash during startup
+                return Some("not running".to_string());
+            };
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    *guard = None;
+                    *self.running.lock().await = false;
+                    *self.stdin_tx.lock().await = None;
+                    return Some(format!("exited with {}", status));
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to poll worker process status: {}", e),
+            }
+        }
+    }
+
+    /// End every pending task with a `WORKER_CRASHED` error, so callers fail
+    /// now rather than at their idle timeout. Returns how many there were.
+    async fn fail_pending(&self) -> usize {
+        let pending: Vec<_> = self.pending.lock().await.drain().collect();
+        for (task_id, tx) in &pending {
+            let _ = tx.send(IPCResponse {
+                task_id: task_id.clone(),
+                event: IPCEvent::Error,
+                data: serde_json::json!({
+                    "message": "The download worker crashed. Please try again.",
+                    "error_code": "WORKER_CRASHED",
+                    "retriable": true,
+                }),
+            });
+        }
+        pending.len()
+    }
+
     /// Stop the Python worker process.
     pub async fn stop(&self) -> Result<(), HermesError> {
         info!("Stopping Python worker...");
+        self.stopping.store(true, Ordering::SeqCst);
 
         // Drop stdin sender to signal EOF
         *self.stdin_tx.lock().await = None;
@@ -418,6 +615,31 @@ impl PythonDispatcher {
         Ok(())
     }
 
+    /// Whether the worker is up and taking requests.
+    pub async fn is_running(&self) -> bool {
+        *self.running.lock().await
+    }
+
+    /// Whether `task_id` is waiting on this worker.
+    pub async fn has_pending(&self, task_id: &str) -> bool {
+        self.pending.lock().await.contains_key(task_id)
+    }
+
+    /// OS process id of the worker, if it is running.
+    pub async fn pid(&self) -> Option<u32> {
+        self.child.lock().await.as_ref().and_then(|c| c.id())
+    }
+
+    /// How long the worker has been refusing stdin input, if it is.
+    pub async fn stdin_stalled_for(&self) -> Option<Duration> {
+        self.stdin_stalled_since.lock().await.map(|since| since.elapsed())
+    }
+
+    /// Number of requests still waiting on worker responses.
+    pub async fn pending_count(&self) -> usize {
+        self.pending.lock().await.len()
+    }
+
     /// Remove a pending task (e.g., on cancellation).
     pub async fn remove_pending(&self, task_id: &str) {
         self.pending.lock().await.remove(task_id);
@@ -430,3 +652,40 @@ impl Drop for PythonDispatcher {
         // The child process will be killed when the handle is dropped
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_full_stdin_queue_times_out() {
+        let (tx, mut rx) = mpsc::channel::<String>(1);
+        push_with_timeout(&tx, "first".into(), Duration::from_millis(50)).await.unwrap();
+
+        // Nobody is reading: the second push must fail instead of blocking
+        let err = push_with_timeout(&tx, "second".into(), Duration::from_millis(50)).await.unwrap_err();
+        assert!(matches!(err, IpcError::StdinFull(_)));
+
+        // Once the reader catches up, sends go through again
+        assert_eq!(rx.recv().await.as_deref(), Some("first"));
+        push_with_timeout(&tx, "third".into(), Duration::from_millis(50)).await.unwrap();
+
+        drop(rx);
+        let err = push_with_timeout(&tx, "fourth".into(), Duration::from_millis(50)).await.unwrap_err();
+        assert!(matches!(err, IpcError::WriteFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_fail_pending_ends_waiting_tasks() {
+        let dispatcher = PythonDispatcher::new(PathBuf::from("."), None);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        dispatcher.pending.lock().await.insert("task-1".into(), tx);
+
+        assert_eq!(dispatcher.fail_pending().await, 1);
+        let response = rx.recv().await.unwrap();
+        assert!(response.is_error());
+        assert_eq!(response.error_code().as_deref(), Some("WORKER_CRASHED"));
+        assert!(rx.recv().await.is_none());
+        assert_eq!(dispatcher.pending_count().await, 0);
+    }
+}
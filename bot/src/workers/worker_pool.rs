@@ -0,0 +1,194 @@
+/// Pool of Python worker subprocesses.
+///
+/// Each worker is a `PythonDispatcher` with its own stdin queues and pending
+/// map, so stdout routing works per worker as before. New requests go to the
+/// running worker with the fewest requests in flight (ties take turns);
+/// cancels and cleanup go to whichever worker holds the task.
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use hermes_shared::errors::{HermesError, IpcError};
+use hermes_shared::ipc_protocol::{IPCRequest, IPCResponse};
+
+use super::python_dispatcher::{Priority, PythonDispatcher, WorkerEvent};
+
+/// Most workers `WORKER_POOL_SIZE` may ask for.
+pub const MAX_POOL_SIZE: usize = 8;
+
+pub struct WorkerPool {
+    workers: Vec<PythonDispatcher>,
+    /// Round-robin offset for breaking ties between equally loaded workers.
+    next: AtomicUsize,
+}
+
+impl WorkerPool {
+    /// Create a pool of `size` workers (clamped to 1..=MAX_POOL_SIZE).
+    pub fn new(size: usize, worker_dir: PathBuf, python_bin: Option<String>) -> Self {
+        let workers = (0..size.clamp(1, MAX_POOL_SIZE))
+            .map(|_| PythonDispatcher::new(worker_dir.clone(), python_bin.clone()))
+            .collect();
+        Self { workers, next: AtomicUsize::new(0) }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Start every worker. Succeeds if at least one came up; the supervisor
+    /// keeps retrying the others.
+    pub async fn start(&self) -> Result<(), HermesError> {
+        let mut started = 0;
+        let mut last_err = None;
+        for (i, worker) in self.workers.iter().enumerate() {
+            match worker.start().await {
+                Ok(()) => started += 1,
+                Err(e) => {
+                    error!("Python worker #{} failed to start: {}", i + 1, e);
+                    last_err = Some(e);
+                }
+            }
+        }
+        info!("{}/{} Python worker(s) started", started, self.workers.len());
+        match last_err {
+            Some(e) if started == 0 => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// The running worker with the fewest pending requests.
+    async fn pick(&self) -> Result<&PythonDispatcher, HermesError> {
+        let offset = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut best: Option<(usize, &PythonDispatcher)> = None;
+        for k in 0..self.workers.len() {
+            let worker = &self.workers[(offset + k) % self.workers.len()];
+            if !worker.is_running().await {
+                continue;
+            }
+            let load = worker.pending_count().await;
+            if best.is_none_or(|(least, _)| load < least) {
+                best = Some((load, worker));
+            }
+        }
+        best.map(|(_, worker)| worker).ok_or_else(|| IpcError::NotRunning.into())
+    }
+
+    /// The worker with a pending entry for `task_id`, if any.
+    async fn holding(&self, task_id: &str) -> Option<&PythonDispatcher> {
+        for worker in &self.workers {
+            if worker.has_pending(task_id).await {
+                return Some(worker);
+            }
+        }
+        None
+    }
+
+    /// See [`PythonDispatcher::send`].
+    pub async fn send(
+        &self,
+        request: &IPCRequest,
+        priority: Priority,
+    ) -> Result<mpsc::UnboundedReceiver<IPCResponse>, HermesError> {
+        self.pick().await?.send(request, priority).await
+    }
+
+    /// See [`PythonDispatcher::send_and_wait`].
+    pub async fn send_and_wait(
+        &self,
+        request: &IPCRequest,
+        timeout_secs: u64,
+    ) -> Result<IPCResponse, HermesError> {
+        self.pick().await?.send_and_wait(request, timeout_secs).await
+    }
+
+    /// Stop `task_id` on the worker running it. A task that never reached a
+    /// worker has nothing to stop.
+    pub async fn cancel(&self, task_id: &str) -> Result<(), HermesError> {
+        match self.holding(task_id).await {
+            Some(worker) => worker.cancel(task_id).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Remove a pending task (e.g., on cancellation).
+    pub async fn remove_pending(&self, task_id: &str) {
+        if let Some(worker) = self.holding(task_id).await {
+            worker.remove_pending(task_id).await;
+        }
+    }
+
+    /// Requests still waiting on worker responses, across the pool.
+    pub async fn pending_count(&self) -> usize {
+        let mut total = 0;
+        for worker in &self.workers {
+            total += worker.pending_count().await;
+        }
+        total
+    }
+
+    /// OS process id of each worker, None for one that isn't running.
+    pub async fn pids(&self) -> Vec<Option<u32>> {
+        let mut pids = Vec::with_capacity(self.workers.len());
+        for worker in &self.workers {
+            pids.push(worker.pid().await);
+        }
+        pids
+    }
+
+    /// Longest time any worker has been refusing stdin input.
+    pub async fn stdin_stalled_for(&self) -> Option<Duration> {
+        let mut longest = None;
+        for worker in &self.workers {
+            longest = longest.max(worker.stdin_stalled_for().await);
+        }
+        longest
+    }
+
+    /// Supervise every worker (see [`PythonDispatcher::supervise`]). Events
+    /// carry the worker's 1-based number.
+    pub fn supervise(&self) -> mpsc::UnboundedReceiver<(usize, WorkerEvent)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (i, worker) in self.workers.iter().enumerate() {
+            let mut events = worker.supervise();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                while let Some(event) = events.recv().await {
+                    if tx.send((i + 1, event)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        rx
+    }
+
+    /// Stop every worker.
+    pub async fn stop(&self) -> Result<(), HermesError> {
+        let mut result = Ok(());
+        for worker in &self.workers {
+            if let Err(e) = worker.stop().await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_no_running_worker_is_not_running_error() {
+        let pool = WorkerPool::new(3, PathBuf::from("."), None);
+        assert_eq!(pool.size(), 3);
+        let request = hermes_shared::ipc_protocol::health_check_request("t1");
+        let err = pool.send_and_wait(&request, 1).await.unwrap_err();
+        assert!(matches!(err, HermesError::Ipc(IpcError::NotRunning)));
+        // Nothing to stop for a task no worker has seen
+        assert!(pool.cancel("t1").await.is_ok());
+        assert_eq!(WorkerPool::new(0, PathBuf::from("."), None).size(), 1);
+    }
+}
@@ -0,0 +1,668 @@
+/// Chunked HTTP download engine.
+///
+/// A probe request learns the size and whether the server honours `Range`.
+/// If it does and the file is big enough, the body is split into ranges that
+/// are fetched concurrently and written in place; otherwise it is streamed in
+/// one request. Progress is reported to a callback a couple of times a second.
+/// Ranged downloads can be resumed after a crash or cancel (see `resume`), and
+/// reads can be rate limited (see `throttle`).
+use crate::resume::{manifest_path, part_path, Manifest};
+use crate::throttle::RateLimiter;
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+/// How often the progress callback runs.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress reports between resume-manifest saves (every 2s).
+const MANIFEST_SAVE_EVERY: u64 = 4;
+
+/// Snapshot passed to the progress callback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub downloaded: u64,
+    /// None when the server did not report a length.
+    pub total: Option<u64>,
+    /// Speed over the last reporting interval.
+    pub bytes_per_sec: f64,
+    /// Finished and total segments, for HLS streams.
+    pub segments: Option<(usize, usize)>,
+}
+
+impl Progress {
+    /// Share of bytes done, or of segments when the size isn't known up front.
+    pub fn percent(&self) -> Option<u8> {
+        let fraction = match (self.total, self.segments) {
+            (Some(total), _) if total > 0 => self.downloaded as f64 / total as f64,
+            (_, Some((done, count))) if count > 0 => done as f64 / count as f64,
+            _ => return None,
+        };
+        Some((fraction * 100.0).min(100.0) as u8)
+    }
+
+    /// Seconds left at the current speed. For HLS the size is extrapolated
+    /// from the segments finished so far.
+    pub fn eta_secs(&self) -> Option<u64> {
+        let total = match (self.total, self.segments) {
+            (Some(total), _) => total,
+            (None, Some((done, count))) if done > 0 => self.downloaded / done as u64 * count as u64,
+            _ => return None,
+        };
+        let remaining = total.saturating_sub(self.downloaded);
+        (self.bytes_per_sec > 0.0).then(|| (remaining as f64 / self.bytes_per_sec).ceil() as u64)
+    }
+}
+
+/// Callback run with download progress.
+pub type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Result of a finished download.
+#[derive(Debug, Clone)]
+pub struct DownloadOutcome {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// How many ranges were fetched in parallel (1 = single stream), or
+    /// the number of segments for HLS.
+    pub chunks: usize,
+    pub elapsed: Duration,
+}
+
+/// A byte range `[start, end]` (inclusive) and how much of it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Chunk {
+    pub start: u64,
+    pub end: u64,
+    pub done: u64,
+}
+
+impl Chunk {
+    pub(crate) fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Split `total` bytes into at most `count` ranges of at least `min_size` bytes.
+pub(crate) fn split_ranges(total: u64, count: usize, min_size: u64) -> Vec<Chunk> {
+    if total == 0 {
+        return Vec::new();
+    }
+    let by_size = (total / min_size.max(1)).max(1);
+    let count = (count.max(1) as u64).min(by_size);
+    let base = total / count;
+    let mut chunks = Vec::with_capacity(count as usize);
+    let mut start = 0;
+    for i in 0..count {
+        let len = if i == count - 1 { total - start } else { base };
+        chunks.push(Chunk { start, end: start + len - 1, done: 0 });
+        start += len;
+    }
+    chunks
+}
+
+/// Builder for [`Downloader`].
+#[derive(Debug, Clone)]
+pub struct DownloaderBuilder {
+    pub(crate) chunks: usize,
+    min_chunk_size: u64,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    pub(crate) retries: u32,
+    user_agent: String,
+    max_size: Option<u64>,
+    rate_limit: Option<u64>,
+    shared_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Default for DownloaderBuilder {
+    fn default() -> Self {
+        Self {
+            chunks: 4,
+            min_chunk_size: 1024 * 1024,
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(30),
+            retries: 3,
+            user_agent: concat!("hermes-downloader/", env!("CARGO_PKG_VERSION")).to_string(),
+            max_size: None,
+            rate_limit: None,
+            shared_limiter: None,
+        }
+    }
+}
+
+impl DownloaderBuilder {
+    /// Ranges fetched in parallel when the server supports them (default 4).
+    pub fn chunks(mut self, chunks: usize) -> Self {
+        self.chunks = chunks.max(1);
+        self
+    }
+
+    /// Files smaller than twice this are fetched in one request (default 1 MiB).
+    pub fn min_chunk_size(mut self, bytes: u64) -> Self {
+        self.min_chunk_size = bytes.max(1);
+        self
+    }
+
+    /// Time allowed to establish a connection (default 15s).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Longest gap between received bytes before a request counts as stalled (default 30s).
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Times a failed range is re-requested from where it stopped (default 3).
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Refuse files larger than this many bytes (default: no limit).
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Cap each download at this many bytes per second (default: unlimited).
+    pub fn rate_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.rate_limit = Some(bytes_per_sec).filter(|&r| r > 0);
+        self
+    }
+
+    /// Also draw from `limiter`, shared with other downloads, to cap their total.
+    pub fn shared_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.shared_limiter = Some(limiter);
+        self
+    }
+
+    pub fn build(self) -> Result<Downloader> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .user_agent(self.user_agent.clone())
+            .build()
+            .context("building HTTP client")?;
+        Ok(Downloader { client, opts: self, limiter: None })
+    }
+}
+
+/// What the probe request found out about a URL.
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    total: Option<u64>,
+    ranges: bool,
+}
+
+/// Native HTTP downloader.
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    pub(crate) client: reqwest::Client,
+    pub(crate) opts: DownloaderBuilder,
+    /// This download's own rate limit; set up fresh by each public entry point.
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Downloader {
+    pub fn builder() -> DownloaderBuilder {
+        DownloaderBuilder::default()
+    }
+
+    /// Download `url` to `dest`, calling `on_progress` while it runs.
+    ///
+    /// Data is written to `<dest>.part` and renamed into place when complete.
+    /// If an interrupted download of the same URL left a manifest behind, it
+    /// is resumed instead of starting over.
+    pub async fn download(&self, url: &str, dest: &Path, on_progress: ProgressCallback) -> Result<DownloadOutcome> {
+        let this = self.with_own_limiter();
+        if Manifest::load(dest).await.is_some_and(|m| m.url == url) {
+            return this.resume_from_manifest(dest, on_progress).await;
+        }
+        Manifest::discard(dest).await;
+        this.start(url, dest, on_progress).await
+    }
+
+    /// Continue an interrupted download of `dest` from its `.part` manifest,
+    /// fetching only the missing ranges. Starts over when the partial file is
+    /// gone or the remote file changed size.
+    pub async fn resume(&self, dest: &Path, on_progress: ProgressCallback) -> Result<DownloadOutcome> {
+        self.with_own_limiter().resume_from_manifest(dest, on_progress).await
+    }
+
+    /// A copy with a fresh bucket for the per-download rate limit.
+    pub(crate) fn with_own_limiter(&self) -> Self {
+        Self {
+            limiter: self.opts.rate_limit.map(|rate| Arc::new(RateLimiter::new(rate))),
+            ..self.clone()
+        }
+    }
+
+    async fn resume_from_manifest(&self, dest: &Path, on_progress: ProgressCallback) -> Result<DownloadOutcome> {
+        let started = Instant::now();
+        let manifest = Manifest::load(dest).await
+            .with_context(|| format!("no resumable download at {}", dest.display()))?;
+        let part_len = tokio::fs::metadata(part_path(dest)).await.map(|m| m.len()).ok();
+        self.check_size(manifest.total)?;
+        let probe = self.probe(&manifest.url).await?;
+        if part_len != Some(manifest.total) || probe.total != Some(manifest.total) || !probe.ranges {
+            warn!(
+                "Cannot resume {} (partial file {:?}, remote {:?}, ranges {}), starting over",
+                dest.display(), part_len, probe.total, probe.ranges
+            );
+            Manifest::discard(dest).await;
+            return self.start(&manifest.url, dest, on_progress).await;
+        }
+        info!(
+            "Resuming {}: {} of {} bytes already on disk",
+            manifest.url, manifest.downloaded(), manifest.total
+        );
+        self.run_ranges(dest, RangeState::from_manifest(manifest), on_progress, started).await
+    }
+
+    /// Fresh download: ranged when the server allows it, else one stream.
+    async fn start(&self, url: &str, dest: &Path, on_progress: ProgressCallback) -> Result<DownloadOutcome> {
+        let started = Instant::now();
+        let probe = self.probe(url).await?;
+        if let Some(total) = probe.total {
+            self.check_size(total)?;
+        }
+        if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        match probe.total {
+            Some(total) if probe.ranges && total >= self.opts.min_chunk_size * 2 => {
+                let chunks = split_ranges(total, self.opts.chunks, self.opts.min_chunk_size);
+                info!("Downloading {} in {} ranges ({} bytes)", url, chunks.len(), total);
+                let file = tokio::fs::File::create(part_path(dest)).await?;
+                file.set_len(total).await?;
+                drop(file);
+                let manifest = Manifest { url: url.to_string(), total, chunks };
+                manifest.save(dest).await?;
+                self.run_ranges(dest, RangeState::from_manifest(manifest), on_progress, started).await
+            }
+            _ => {
+                info!("Downloading {} in one stream ({:?} bytes)", url, probe.total);
+                let downloaded = Arc::new(AtomicU64::new(0));
+                let counter = downloaded.clone();
+                let total = probe.total;
+                let _reporter = spawn_reporter(
+                    move || Progress { downloaded: counter.load(Ordering::Relaxed), total, bytes_per_sec: 0.0, segments: None },
+                    on_progress.clone(),
+                    None,
+                );
+                let bytes = match self.fetch_whole(url, &part_path(dest), &downloaded).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        // A single stream can't be resumed, so nothing is worth keeping
+                        let _ = tokio::fs::remove_file(part_path(dest)).await;
+                        return Err(e);
+                    }
+                };
+                tokio::fs::rename(part_path(dest), dest).await?;
+                on_progress(Progress { downloaded: bytes, total: Some(bytes), bytes_per_sec: 0.0, segments: None });
+                Ok(DownloadOutcome { path: dest.to_path_buf(), bytes, chunks: 1, elapsed: started.elapsed() })
+            }
+        }
+    }
+
+    /// Fetch every incomplete range concurrently, keeping the manifest current,
+    /// then move the finished file into place.
+    async fn run_ranges(
+        &self,
+        dest: &Path,
+        state: RangeState,
+        on_progress: ProgressCallback,
+        started: Instant,
+    ) -> Result<DownloadOutcome> {
+        let state = Arc::new(state);
+        let count = state.bounds.len();
+        let reporter_state = state.clone();
+        let _reporter = spawn_reporter(
+            move || Progress {
+                downloaded: reporter_state.downloaded(),
+                total: Some(reporter_state.total),
+                bytes_per_sec: 0.0,
+                segments: None,
+            },
+            on_progress.clone(),
+            Some((dest.to_path_buf(), state.clone())),
+        );
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for index in 0..count {
+            let this = self.clone();
+            let part = part_path(dest);
+            let state = state.clone();
+            tasks.spawn(async move { this.fetch_chunk(&part, index, &state).await });
+        }
+        let mut failure = None;
+        while let Some(joined) = tasks.join_next().await {
+            if let Err(e) = joined.map_err(|e| anyhow!(e)).and_then(|r| r) {
+                tasks.abort_all();
+                failure.get_or_insert(e);
+            }
+        }
+        if let Some(e) = failure {
+            // Keep what was fetched so a later resume can continue from here
+            let _ = state.manifest().save(dest).await;
+            return Err(e);
+        }
+
+        tokio::fs::rename(part_path(dest), dest).await?;
+        let _ = tokio::fs::remove_file(manifest_path(dest)).await;
+        on_progress(Progress { downloaded: state.total, total: Some(state.total), bytes_per_sec: 0.0, segments: None });
+        Ok(DownloadOutcome { path: dest.to_path_buf(), bytes: state.total, chunks: count, elapsed: started.elapsed() })
+    }
+
+    /// Ask for the first byte: a 206 answer proves range support and carries the size.
+    async fn probe(&self, url: &str) -> Result<Probe> {
+        let response = self.client.get(url).header(RANGE, "bytes=0-0").send().await
+            .with_context(|| format!("requesting {}", url))?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("server answered {} for {}", status, url);
+        }
+        let headers = response.headers();
+        let probe = if status == StatusCode::PARTIAL_CONTENT {
+            let total = headers.get(CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit('/').next())
+                .and_then(|v| v.parse().ok());
+            Probe { total, ranges: total.is_some() }
+        } else {
+            let total = headers.get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok());
+            let ranges = headers.get(ACCEPT_RANGES).is_some_and(|v| v.as_bytes() == b"bytes");
+            Probe { total, ranges }
+        };
+        debug!("Probe {}: {:?}", url, probe);
+        Ok(probe)
+    }
+
+    /// Single request, streamed to `path`.
+    async fn fetch_whole(&self, url: &str, path: &Path, downloaded: &AtomicU64) -> Result<u64> {
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        let mut file = tokio::fs::File::create(path).await?;
+        let mut written = 0u64;
+        while let Some(bytes) = self.next_bytes(&mut response).await? {
+            file.write_all(&bytes).await?;
+            written += bytes.len() as u64;
+            self.check_size(written)?;
+            downloaded.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+
+    /// Fetch the rest of range `index`, re-requesting from where it stopped after a failure.
+    async fn fetch_chunk(&self, part: &Path, index: usize, state: &RangeState) -> Result<()> {
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(part).await?;
+        let (start, end) = state.bounds[index];
+        let mut attempt = 0;
+        while !state.is_complete(index) {
+            match self.fetch_chunk_once(&mut file, index, state).await {
+                Ok(()) => {}
+                Err(e) if attempt < self.opts.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Range {}-{} failed at +{} ({}), retry {}/{}",
+                        start, end, state.done[index].load(Ordering::Relaxed), e, attempt, self.opts.retries
+                    );
+                    tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+                }
+                Err(e) => return Err(e.context(format!("range {}-{}", start, end))),
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_chunk_once(&self, file: &mut tokio::fs::File, index: usize, state: &RangeState) -> Result<()> {
+        let (start, end) = state.bounds[index];
+        let done = &state.done[index];
+        let from = start + done.load(Ordering::Relaxed);
+        let mut response = self.client.get(&state.url)
+            .header(RANGE, format!("bytes={}-{}", from, end))
+            .send()
+            .await?
+            .error_for_status()?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            bail!("server ignored the range request ({})", response.status());
+        }
+        file.seek(std::io::SeekFrom::Start(from)).await?;
+        while let Some(bytes) = self.next_bytes(&mut response).await? {
+            let room = (end - start + 1 - done.load(Ordering::Relaxed)) as usize;
+            let bytes = &bytes[..bytes.len().min(room)];
+            file.write_all(bytes).await?;
+            // Count bytes only once they reach the file (see resume.rs)
+            file.flush().await?;
+            done.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            if state.is_complete(index) {
+                break;
+            }
+        }
+        if !state.is_complete(index) {
+            bail!("connection closed early");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_size(&self, bytes: u64) -> Result<()> {
+        match self.opts.max_size {
+            Some(max) if bytes > max => bail!(
+                "file is larger than the {:.0} MB limit", max as f64 / 1_048_576.0
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Next body chunk, failing if nothing arrives within the read timeout.
+    /// Waits out the rate limits before returning it.
+    pub(crate) async fn next_bytes(&self, response: &mut reqwest::Response) -> Result<Option<bytes::Bytes>> {
+        let chunk = match tokio::time::timeout(self.opts.read_timeout, response.chunk()).await {
+            Ok(chunk) => chunk?,
+            Err(_) => bail!("no data for {}s", self.opts.read_timeout.as_secs()),
+        };
+        if let Some(bytes) = &chunk {
+            for limiter in [&self.limiter, &self.opts.shared_limiter].into_iter().flatten() {
+                limiter.acquire(bytes.len()).await;
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+/// Live progress of a ranged download, shared by the range tasks and the reporter.
+pub(crate) struct RangeState {
+    url: String,
+    total: u64,
+    bounds: Vec<(u64, u64)>,
+    done: Vec<AtomicU64>,
+}
+
+impl RangeState {
+    fn from_manifest(manifest: Manifest) -> Self {
+        Self {
+            bounds: manifest.chunks.iter().map(|c| (c.start, c.end)).collect(),
+            done: manifest.chunks.iter().map(|c| AtomicU64::new(c.done.min(c.len()))).collect(),
+            url: manifest.url,
+            total: manifest.total,
+        }
+    }
+
+    fn is_complete(&self, index: usize) -> bool {
+        let (start, end) = self.bounds[index];
+        self.done[index].load(Ordering::Relaxed) > end - start
+    }
+
+    fn downloaded(&self) -> u64 {
+        self.done.iter().map(|d| d.load(Ordering::Relaxed)).sum()
+    }
+
+    fn manifest(&self) -> Manifest {
+        Manifest {
+            url: self.url.clone(),
+            total: self.total,
+            chunks: self.bounds.iter().zip(&self.done)
+                .map(|(&(start, end), done)| Chunk { start, end, done: done.load(Ordering::Relaxed) })
+                .collect(),
+        }
+    }
+}
+
+/// Aborts the wrapped task when dropped, so a cancelled download stops reporting.
+pub(crate) struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Report what `sample` returns every `PROGRESS_INTERVAL` until dropped, with
+/// the speed filled in. With `persist`, the resume manifest is rewritten every
+/// few reports as well.
+pub(crate) fn spawn_reporter(
+    sample: impl Fn() -> Progress + Send + 'static,
+    on_progress: ProgressCallback,
+    persist: Option<(PathBuf, Arc<RangeState>)>,
+) -> AbortOnDrop {
+    AbortOnDrop(tokio::spawn(async move {
+        let mut last = (Instant::now(), sample().downloaded);
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        ticker.tick().await;
+        for tick in 1u64.. {
+            ticker.tick().await;
+            let now = sample();
+            let secs = last.0.elapsed().as_secs_f64();
+            let bytes_per_sec = if secs > 0.0 { now.downloaded.saturating_sub(last.1) as f64 / secs } else { 0.0 };
+            last = (Instant::now(), now.downloaded);
+            on_progress(Progress { bytes_per_sec, ..now });
+            if let (Some((dest, state)), 0) = (&persist, tick % MANIFEST_SAVE_EVERY) {
+                if let Err(e) = state.manifest().save(dest).await {
+                    warn!("Could not save resume manifest for {}: {}", dest.display(), e);
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP/1.1 server for one body, honouring single `Range` requests
+    /// when `ranges` is set. Returns the URL and a count of body bytes sent.
+    pub(crate) async fn serve(body: Vec<u8>, ranges: bool) -> (String, Arc<AtomicU64>) {
+        let (base, served) = serve_routes(HashMap::from([("/file.bin".to_string(), body)]), ranges).await;
+        (format!("{}/file.bin", base), served)
+    }
+
+    /// Like [`serve`], with a body per path (404 for others). Returns the base URL.
+    pub(crate) async fn serve_routes(routes: HashMap<String, Vec<u8>>, ranges: bool) -> (String, Arc<AtomicU64>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = Arc::new(routes);
+        let served = Arc::new(AtomicU64::new(0));
+        let counter = served.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let routes = routes.clone();
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let raw = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let path = raw.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let Some(body) = routes.get(&path) else {
+                        let _ = socket.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+                        return;
+                    };
+                    let request = raw.to_lowercase();
+                    let range = request.lines()
+                        .find_map(|l| l.strip_prefix("range: bytes="))
+                        .and_then(|r| {
+                            let (a, b) = r.trim().split_once('-')?;
+                            Some((a.parse::<usize>().ok()?, b.parse::<usize>().ok()?))
+                        })
+                        .filter(|_| ranges);
+                    let (status, slice, extra) = match range {
+                        Some((a, b)) => {
+                            let b = b.min(body.len() - 1);
+                            ("206 Partial Content", &body[a..=b], format!("Content-Range: bytes {}-{}/{}\r\n", a, b, body.len()))
+                        }
+                        None => ("200 OK", &body[..], String::new()),
+                    };
+                    let head = format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                        status, slice.len(), extra
+                    );
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    if socket.write_all(slice).await.is_ok() {
+                        counter.fetch_add(slice.len() as u64, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        (format!("http://{}", addr), served)
+    }
+
+    pub(crate) fn sample_body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn splits_into_covering_ranges() {
+        let chunks = split_ranges(10, 3, 1);
+        assert_eq!(chunks.iter().map(|c| (c.start, c.end)).collect::<Vec<_>>(), vec![(0, 2), (3, 5), (6, 9)]);
+        // Never smaller than min_size
+        assert_eq!(split_ranges(10, 8, 4).len(), 2);
+        assert!(split_ranges(0, 4, 1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn downloads_in_ranges_and_in_one_stream() {
+        let body = sample_body(300_000);
+        let dir = std::env::temp_dir().join(format!("hermes-dl-engine-{}", std::process::id()));
+        let downloader = Downloader::builder().chunks(4).min_chunk_size(50_000).build().unwrap();
+        let last = Arc::new(std::sync::Mutex::new(None));
+        let sink = last.clone();
+        let on_progress: ProgressCallback = Arc::new(move |p: Progress| *sink.lock().unwrap() = Some(p));
+
+        let (url, _) = serve(body.clone(), true).await;
+        let outcome = downloader.download(&url, &dir.join("ranged.bin"), on_progress.clone()).await.unwrap();
+        assert_eq!(outcome.chunks, 4);
+        assert_eq!(std::fs::read(dir.join("ranged.bin")).unwrap(), body);
+        assert_eq!(last.lock().unwrap().unwrap().percent(), Some(100));
+
+        let (url, _) = serve(body.clone(), false).await;
+        let outcome = downloader.download(&url, &dir.join("whole.bin"), on_progress).await.unwrap();
+        assert_eq!(outcome.chunks, 1);
+        assert_eq!(std::fs::read(dir.join("whole.bin")).unwrap(), body);
+        assert!(!dir.join("whole.bin.part").exists());
+
+        let capped = Downloader::builder().max_size(100_000).build().unwrap();
+        let err = capped.download(&url, &dir.join("capped.bin"), Arc::new(|_: Progress| {})).await.unwrap_err();
+        assert!(err.to_string().contains("limit"));
+        assert!(!dir.join("capped.bin.part").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
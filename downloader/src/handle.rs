@@ -0,0 +1,98 @@
+/// Downloads running in the background, for callers such as the bot.
+///
+/// [`download`] starts a download on the tokio runtime and returns at once;
+/// the [`DownloadHandle`] carries live progress, cancellation and the result.
+use crate::engine::{DownloadOutcome, Downloader, DownloaderBuilder, Progress, ProgressCallback};
+use crate::hls::{is_hls_url, HlsFetcher};
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Start downloading `url` to `dest` with the given options.
+///
+/// Fails only if the options don't produce a working HTTP client; download
+/// errors are reported by [`DownloadHandle::wait`].
+pub fn download(url: &str, dest: impl Into<PathBuf>, opts: DownloaderBuilder) -> Result<DownloadHandle> {
+    Ok(opts.build()?.spawn(url, dest))
+}
+
+/// A download running in the background.
+///
+/// Dropping the handle does not stop the download; call [`cancel`](Self::cancel).
+pub struct DownloadHandle {
+    progress: watch::Receiver<Option<Progress>>,
+    task: JoinHandle<Result<DownloadOutcome>>,
+}
+
+impl DownloadHandle {
+    /// Progress updates, None until the first report. `changed()` fails once
+    /// the download has ended.
+    pub fn progress(&self) -> watch::Receiver<Option<Progress>> {
+        self.progress.clone()
+    }
+
+    /// Stop the download. A ranged download leaves its `.part` file and
+    /// manifest behind, so downloading to the same `dest` later resumes it.
+    pub fn cancel(&self) {
+        self.task.abort();
+    }
+
+    /// Wait for the download to finish.
+    pub async fn wait(self) -> Result<DownloadOutcome> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) if e.is_cancelled() => Err(anyhow!("download cancelled")),
+            Err(e) => Err(anyhow!(e)),
+        }
+    }
+}
+
+impl Downloader {
+    /// Run [`Downloader::download`] as a background task, or an
+    /// [`HlsFetcher`] for `.m3u8` URLs.
+    pub fn spawn(&self, url: &str, dest: impl Into<PathBuf>) -> DownloadHandle {
+        let (tx, progress) = watch::channel(None);
+        let on_progress: ProgressCallback = Arc::new(move |p: Progress| {
+            let _ = tx.send(Some(p));
+        });
+        let this = self.clone();
+        let url = url.to_string();
+        let dest = dest.into();
+        let task = tokio::spawn(async move {
+            if is_hls_url(&url) {
+                HlsFetcher::new(this).fetch(&url, &dest, on_progress).await
+            } else {
+                this.download(&url, &dest, on_progress).await
+            }
+        });
+        DownloadHandle { progress, task }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::{sample_body, serve};
+
+    #[tokio::test]
+    async fn reports_progress_until_done() {
+        let body = sample_body(200_000);
+        let (url, _) = serve(body.clone(), true).await;
+        let dest = std::env::temp_dir().join(format!("hermes-dl-handle-{}.bin", std::process::id()));
+
+        let handle = download(&url, &dest, Downloader::builder().min_chunk_size(50_000)).unwrap();
+        let mut progress = handle.progress();
+        let mut last = None;
+        while progress.changed().await.is_ok() {
+            last = *progress.borrow_and_update();
+        }
+        let outcome = handle.wait().await.unwrap();
+
+        assert_eq!(outcome.bytes, body.len() as u64);
+        assert_eq!(last.and_then(|p| p.percent()), Some(100));
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        std::fs::remove_file(&dest).unwrap();
+    }
+}
@@ -0,0 +1,395 @@
+/// HLS (`.m3u8`) streams.
+///
+/// A master playlist is resolved to its highest-bandwidth variant; the media
+/// playlist's segments are then fetched concurrently (as many at a time as the
+/// downloader's `chunks`) and joined in order. MPEG-TS segments are remuxed to
+/// mp4 with ffmpeg when `dest` ends in `.mp4`; fMP4 segments (with an
+/// `EXT-X-MAP` init section) join into an mp4 as they are.
+///
+/// Encrypted and live streams are refused, and a variant's alternate audio
+/// renditions (`EXT-X-MEDIA`) are not fetched.
+use crate::engine::{spawn_reporter, DownloadOutcome, Downloader, Progress, ProgressCallback};
+use crate::resume::with_suffix;
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::header::RANGE;
+use reqwest::Url;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Whether `url` points at an HLS playlist, judged by its path.
+pub fn is_hls_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|u| u.path().to_ascii_lowercase().ends_with(".m3u8"))
+}
+
+/// A parsed `.m3u8` file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Playlist {
+    /// Variants of the same stream at different bitrates.
+    Master(Vec<Variant>),
+    Media(MediaPlaylist),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Variant {
+    pub bandwidth: u64,
+    pub uri: Url,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MediaPlaylist {
+    /// fMP4 initialization section (`EXT-X-MAP`).
+    pub init: Option<Segment>,
+    pub segments: Vec<Segment>,
+    /// `EXT-X-ENDLIST` seen: the stream is complete, not live.
+    pub ended: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Segment {
+    pub uri: Url,
+    /// `(start, length)` when only part of the resource is the segment.
+    pub range: Option<(u64, u64)>,
+}
+
+/// Parse a playlist, resolving URIs against `base` (the playlist's own URL).
+pub(crate) fn parse_playlist(text: &str, base: &Url) -> Result<Playlist> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    if lines.next() != Some("#EXTM3U") {
+        bail!("not an HLS playlist");
+    }
+    let mut variants = Vec::new();
+    let mut segments = Vec::new();
+    let mut init = None;
+    let mut ended = false;
+    // BANDWIDTH of an EXT-X-STREAM-INF waiting for its URI line
+    let mut variant_bandwidth: Option<u64> = None;
+    // EXT-X-BYTERANGE waiting for its URI line; a range without an offset
+    // continues where the previous one ended
+    let mut byte_range: Option<(u64, Option<u64>)> = None;
+    let mut range_end = 0;
+
+    for line in lines {
+        if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            variant_bandwidth = Some(attribute(attrs, "BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0));
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-KEY:") {
+            match attribute(attrs, "METHOD") {
+                Some("NONE") => {}
+                method => bail!("encrypted HLS streams ({}) are not supported", method.unwrap_or("unknown")),
+            }
+        } else if let Some(attrs) = line.strip_prefix("#EXT-X-MAP:") {
+            let uri = attribute(attrs, "URI").context("EXT-X-MAP without a URI")?;
+            let range = attribute(attrs, "BYTERANGE")
+                .map(parse_byte_range)
+                .transpose()?
+                .map(|(len, offset)| (offset.unwrap_or(0), len));
+            init = Some(Segment { uri: base.join(uri)?, range });
+        } else if let Some(value) = line.strip_prefix("#EXT-X-BYTERANGE:") {
+            byte_range = Some(parse_byte_range(value)?);
+        } else if line == "#EXT-X-ENDLIST" {
+            ended = true;
+        } else if !line.starts_with('#') {
+            let uri = base.join(line).with_context(|| format!("bad playlist URI {:?}", line))?;
+            if let Some(bandwidth) = variant_bandwidth.take() {
+                variants.push(Variant { bandwidth, uri });
+            } else {
+                let range = byte_range.take().map(|(len, offset)| {
+                    let start = offset.unwrap_or(range_end);
+                    range_end = start + len;
+                    (start, len)
+                });
+                segments.push(Segment { uri, range });
+            }
+        }
+    }
+
+    if variants.is_empty() {
+        Ok(Playlist::Media(MediaPlaylist { init, segments, ended }))
+    } else {
+        Ok(Playlist::Master(variants))
+    }
+}
+
+/// Value of `name` in an attribute list (`A=1,B="x,y"`), without quotes.
+fn attribute<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], quoted[end + 1..].trim_start_matches(','))
+            }
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        if key.trim() == name {
+            return Some(value);
+        }
+        rest = next;
+    }
+    None
+}
+
+/// `<length>[@<offset>]`.
+fn parse_byte_range(value: &str) -> Result<(u64, Option<u64>)> {
+    let parse = |n: &str| n.trim().parse::<u64>().map_err(|_| anyhow!("bad byte range {:?}", value));
+    match value.split_once('@') {
+        Some((len, offset)) => Ok((parse(len)?, Some(parse(offset)?))),
+        None => Ok((parse(value)?, None)),
+    }
+}
+
+/// Downloads HLS streams with a [`Downloader`]'s client, limits and retries.
+#[derive(Debug, Clone)]
+pub struct HlsFetcher {
+    downloader: Downloader,
+    ffmpeg: String,
+}
+
+impl HlsFetcher {
+    pub fn new(downloader: Downloader) -> Self {
+        Self { downloader, ffmpeg: "ffmpeg".to_string() }
+    }
+
+    /// ffmpeg binary used to remux MPEG-TS into mp4 (default `ffmpeg` on PATH).
+    pub fn ffmpeg(mut self, path: impl Into<String>) -> Self {
+        self.ffmpeg = path.into();
+        self
+    }
+
+    /// Download the stream at `url` into `dest`, reporting segment progress.
+    pub async fn fetch(&self, url: &str, dest: &Path, on_progress: ProgressCallback) -> Result<DownloadOutcome> {
+        let started = Instant::now();
+        let dl = self.downloader.with_own_limiter();
+        let base = Url::parse(url).with_context(|| format!("bad URL {}", url))?;
+        let media = match parse_playlist(&get_text(&dl, &base).await?, &base)? {
+            Playlist::Media(media) => media,
+            Playlist::Master(variants) => {
+                let best = variants.iter().max_by_key(|v| v.bandwidth).context("empty master playlist")?;
+                info!("HLS {}: picked variant {} ({} b/s of {} variants)", url, best.uri, best.bandwidth, variants.len());
+                match parse_playlist(&get_text(&dl, &best.uri).await?, &best.uri)? {
+                    Playlist::Media(media) => media,
+                    Playlist::Master(_) => bail!("HLS variant is another master playlist"),
+                }
+            }
+        };
+        if !media.ended {
+            bail!("live HLS streams are not supported");
+        }
+        if media.segments.is_empty() {
+            bail!("HLS playlist has no segments");
+        }
+
+        if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let work_dir = with_suffix(dest, ".hls");
+        tokio::fs::create_dir_all(&work_dir).await?;
+        let result = self.fetch_segments(&dl, &media, dest, &work_dir, on_progress.clone()).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        let bytes = result?;
+
+        let count = media.segments.len();
+        on_progress(Progress { downloaded: bytes, total: Some(bytes), bytes_per_sec: 0.0, segments: Some((count, count)) });
+        Ok(DownloadOutcome { path: dest.to_path_buf(), bytes, chunks: count, elapsed: started.elapsed() })
+    }
+
+    /// Fetch every segment into `work_dir`, then join (and maybe remux) them into `dest`.
+    async fn fetch_segments(
+        &self,
+        dl: &Downloader,
+        media: &MediaPlaylist,
+        dest: &Path,
+        work_dir: &Path,
+        on_progress: ProgressCallback,
+    ) -> Result<u64> {
+        let count = media.segments.len();
+        info!("HLS: {} segments, {} at a time", count, dl.opts.chunks);
+        let downloaded = Arc::new(AtomicU64::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        let (bytes, finished) = (downloaded.clone(), done.clone());
+        let _reporter = spawn_reporter(
+            move || Progress {
+                downloaded: bytes.load(Ordering::Relaxed),
+                total: None,
+                bytes_per_sec: 0.0,
+                segments: Some((finished.load(Ordering::Relaxed), count)),
+            },
+            on_progress,
+            None,
+        );
+
+        // The init section, when present, is part 0 and doesn't count as a segment
+        let parts: Vec<(Segment, bool)> = media.init.iter().map(|s| (s.clone(), false))
+            .chain(media.segments.iter().map(|s| (s.clone(), true)))
+            .collect();
+        let paths: Vec<PathBuf> = (0..parts.len()).map(|i| work_dir.join(format!("{:05}.seg", i))).collect();
+        let slots = Arc::new(tokio::sync::Semaphore::new(dl.opts.chunks));
+        let mut tasks = tokio::task::JoinSet::new();
+        for ((segment, counts), path) in parts.into_iter().zip(paths.clone()) {
+            let (dl, slots, downloaded, done) = (dl.clone(), slots.clone(), downloaded.clone(), done.clone());
+            tasks.spawn(async move {
+                let _slot = slots.acquire_owned().await?;
+                fetch_segment(&dl, &segment, &path, &downloaded).await?;
+                if counts {
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok::<_, anyhow::Error>(())
+            });
+        }
+        while let Some(joined) = tasks.join_next().await {
+            if let Err(e) = joined.map_err(|e| anyhow!(e)).and_then(|r| r) {
+                tasks.abort_all();
+                return Err(e);
+            }
+        }
+
+        // TS segments concatenate into a valid TS stream, fMP4 ones (after
+        // their init section) into a fragmented mp4
+        let joined = with_suffix(dest, ".part");
+        let mut out = tokio::fs::File::create(&joined).await?;
+        for path in &paths {
+            let mut segment = tokio::fs::File::open(path).await?;
+            tokio::io::copy(&mut segment, &mut out).await?;
+        }
+        out.flush().await?;
+        drop(out);
+
+        let wants_mp4 = dest.extension().is_some_and(|e| e.eq_ignore_ascii_case("mp4"));
+        if wants_mp4 && media.init.is_none() {
+            let remuxed = self.remux_to_mp4(&joined, dest).await;
+            let _ = tokio::fs::remove_file(&joined).await;
+            remuxed?;
+        } else {
+            tokio::fs::rename(&joined, dest).await?;
+        }
+        Ok(tokio::fs::metadata(dest).await?.len())
+    }
+
+    /// Copy the streams of a joined TS file into an mp4 container (no re-encode).
+    async fn remux_to_mp4(&self, input: &Path, output: &Path) -> Result<()> {
+        let result = tokio::process::Command::new(&self.ffmpeg)
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(input)
+            .args(["-c", "copy", "-bsf:a", "aac_adtstoasc", "-movflags", "+faststart", "-f", "mp4"])
+            .arg(output)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .with_context(|| format!("running {}", self.ffmpeg))?;
+        if !result.status.success() {
+            let _ = tokio::fs::remove_file(output).await;
+            bail!("remuxing to mp4 failed: {}", String::from_utf8_lossy(&result.stderr).trim());
+        }
+        Ok(())
+    }
+}
+
+async fn get_text(dl: &Downloader, url: &Url) -> Result<String> {
+    let response = dl.client.get(url.clone()).send().await
+        .with_context(|| format!("requesting {}", url))?
+        .error_for_status()?;
+    Ok(response.text().await?)
+}
+
+/// Fetch one segment to `path`, starting over on failure up to the retry limit.
+async fn fetch_segment(dl: &Downloader, segment: &Segment, path: &Path, downloaded: &AtomicU64) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let written = AtomicU64::new(0);
+        let result = async {
+            let mut request = dl.client.get(segment.uri.clone());
+            if let Some((start, len)) = segment.range {
+                request = request.header(RANGE, format!("bytes={}-{}", start, start + len.max(1) - 1));
+            }
+            let mut response = request.send().await?.error_for_status()?;
+            let mut file = tokio::fs::File::create(path).await?;
+            while let Some(bytes) = dl.next_bytes(&mut response).await? {
+                file.write_all(&bytes).await?;
+                written.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                let total = downloaded.fetch_add(bytes.len() as u64, Ordering::Relaxed) + bytes.len() as u64;
+                dl.check_size(total)?;
+            }
+            file.flush().await?;
+            Ok::<_, anyhow::Error>(())
+        }.await;
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                downloaded.fetch_sub(written.load(Ordering::Relaxed), Ordering::Relaxed);
+                if attempt >= dl.opts.retries {
+                    return Err(e.context(format!("segment {}", segment.uri)));
+                }
+                attempt += 1;
+                warn!("Segment {} failed ({}), retry {}/{}", segment.uri, e, attempt, dl.opts.retries);
+                tokio::time::sleep(std::time::Duration::from_secs(attempt as u64)).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::serve_routes;
+    use std::collections::HashMap;
+
+    #[test]
+    fn parses_master_and_media_playlists() {
+        let base = Url::parse("https://cdn.example.com/v/master.m3u8?sig=1").unwrap();
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,CODECS=\"avc1.4d401f,mp4a.40.2\"\nlow/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\nhttps://other.example.com/hd.m3u8\n";
+        let Playlist::Master(variants) = parse_playlist(master, &base).unwrap() else { panic!("not a master") };
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].uri.as_str(), "https://cdn.example.com/v/low/index.m3u8");
+        assert_eq!(variants[1].bandwidth, 2_500_000);
+
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-MAP:URI=\"init.mp4\"\n\
+            #EXT-X-KEY:METHOD=NONE\n#EXTINF:6.0,\nseg0.m4s\n\
+            #EXT-X-BYTERANGE:1000@0\n#EXTINF:6.0,\nall.m4s\n#EXT-X-BYTERANGE:500\n#EXTINF:4.0,\nall.m4s\n#EXT-X-ENDLIST\n";
+        let Playlist::Media(media) = parse_playlist(media, &base).unwrap() else { panic!("not media") };
+        assert!(media.ended);
+        assert_eq!(media.init.unwrap().uri.as_str(), "https://cdn.example.com/v/init.mp4");
+        assert_eq!(media.segments.iter().map(|s| s.range).collect::<Vec<_>>(), vec![None, Some((0, 1000)), Some((1000, 500))]);
+
+        let encrypted = "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"k.bin\"\n#EXTINF:6.0,\ns.ts\n";
+        assert!(parse_playlist(encrypted, &base).unwrap_err().to_string().contains("AES-128"));
+        assert!(parse_playlist("<html>", &base).is_err());
+        assert!(is_hls_url("https://x.com/a/index.M3U8?t=1") && !is_hls_url("https://x.com/a.mp4"));
+    }
+
+    #[tokio::test]
+    async fn joins_segments_of_the_best_variant() {
+        let segments: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 10_000 + i as usize]).collect();
+        let mut media = String::from("#EXTM3U\n#EXT-X-TARGETDURATION:4\n");
+        for i in 0..segments.len() {
+            media.push_str(&format!("#EXTINF:4.0,\nseg{}.ts\n", i));
+        }
+        media.push_str("#EXT-X-ENDLIST\n");
+        let mut routes: HashMap<String, Vec<u8>> = segments.iter().enumerate()
+            .map(|(i, s)| (format!("/hd/seg{}.ts", i), s.clone()))
+            .collect();
+        routes.insert("/hd/index.m3u8".into(), media.into_bytes());
+        routes.insert("/master.m3u8".into(), b"#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=100\nsd/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=900\nhd/index.m3u8\n".to_vec());
+        let (base, _) = serve_routes(routes, true).await;
+
+        let dest = std::env::temp_dir().join(format!("hermes-dl-hls-{}.ts", std::process::id()));
+        let last = Arc::new(std::sync::Mutex::new(None));
+        let sink = last.clone();
+        let on_progress: ProgressCallback = Arc::new(move |p: Progress| *sink.lock().unwrap() = Some(p));
+        let fetcher = HlsFetcher::new(Downloader::builder().chunks(2).build().unwrap());
+        let outcome = fetcher.fetch(&format!("{}/master.m3u8", base), &dest, on_progress).await.unwrap();
+
+        assert_eq!(outcome.chunks, 5);
+        assert_eq!(std::fs::read(&dest).unwrap(), segments.concat());
+        assert_eq!(last.lock().unwrap().unwrap().segments, Some((5, 5)));
+        assert!(!with_suffix(&dest, ".hls").exists());
+        std::fs::remove_file(&dest).unwrap();
+    }
+}
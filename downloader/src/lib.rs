@@ -0,0 +1,18 @@
+//! Hermes native downloader: direct HTTP downloads in Rust, without yt-dlp.
+//!
+//! See [`engine`] for how a download is split into concurrent range requests;
+//! interrupted ranged downloads pick up where they stopped via [`Downloader::resume`].
+//! Callers that want a download in the background use [`download`], which
+//! returns a [`DownloadHandle`] and fetches `.m3u8` links with [`hls`].
+//! [`throttle`] caps their bandwidth.
+
+pub mod engine;
+mod handle;
+pub mod hls;
+mod resume;
+pub mod throttle;
+
+pub use engine::{DownloadOutcome, Downloader, DownloaderBuilder, Progress, ProgressCallback};
+pub use handle::{download, DownloadHandle};
+pub use hls::HlsFetcher;
+pub use throttle::{parse_rate, RateLimiter};
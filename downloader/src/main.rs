@@ -1,12 +1,52 @@
-/// Hermes Native Downloader (Phase E stub)
+/// Hermes Native Downloader CLI
 ///
-/// Will provide a Rust-native download engine for direct HTTP downloads
-/// (non-YouTube sources) with:
-/// - Concurrent chunk downloading
-/// - Resume support
-/// - Progress tracking
-/// - Bandwidth throttling
-fn main() {
-    println!("Hermes Native Downloader - Phase E (not yet implemented)");
-    println!("This crate will handle direct HTTP downloads natively in Rust.");
+/// Small command-line front end over the library's engine, handy for trying
+/// a URL by hand:
+///
+///     hermes-downloader <url> <dest> [chunks]
+///
+/// `.m3u8` URLs are fetched as HLS streams. DOWNLOAD_RATE_LIMIT (e.g. "2M")
+/// caps the speed.
+/// Running it again after an interruption resumes from `<dest>.part`.
+use hermes_downloader::hls::{is_hls_url, HlsFetcher};
+use hermes_downloader::{parse_rate, Downloader, Progress, ProgressCallback};
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "hermes_downloader=info".into()),
+        )
+        .init();
+
+    let mut args = std::env::args().skip(1);
+    let (Some(url), Some(dest)) = (args.next(), args.next()) else {
+        eprintln!("Usage: hermes-downloader <url> <dest> [chunks]");
+        std::process::exit(2);
+    };
+    let chunks = args.next().and_then(|c| c.parse().ok()).unwrap_or(4);
+
+    let mut builder = Downloader::builder().chunks(chunks);
+    if let Some(rate) = std::env::var("DOWNLOAD_RATE_LIMIT").ok().as_deref().and_then(parse_rate) {
+        builder = builder.rate_limit(rate);
+    }
+    let downloader = builder.build()?;
+    let on_progress: ProgressCallback = Arc::new(|p: Progress| {
+        let percent = p.percent().map(|p| format!("{}%", p)).unwrap_or_else(|| "?".into());
+        eprint!("\r{} {:.1} MB  {:.1} MB/s   ", percent, p.downloaded as f64 / 1_048_576.0, p.bytes_per_sec / 1_048_576.0);
+    });
+    let dest = std::path::Path::new(&dest);
+    let outcome = if is_hls_url(&url) {
+        HlsFetcher::new(downloader).fetch(&url, dest, on_progress).await?
+    } else {
+        downloader.download(&url, dest, on_progress).await?
+    };
+    eprintln!();
+    println!(
+        "{} ({} bytes, {} range(s), {:.1}s)",
+        outcome.path.display(), outcome.bytes, outcome.chunks, outcome.elapsed.as_secs_f64()
+    );
+    Ok(())
 }
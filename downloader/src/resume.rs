@@ -0,0 +1,108 @@
+/// Resume state for ranged downloads.
+///
+/// While a download runs its bytes go to `<dest>.part`, and `<dest>.part.json`
+/// records the ranges and how much of each is written. A crashed or cancelled
+/// download leaves both behind; resuming re-requests only the missing bytes.
+/// A range's progress is counted after its bytes are flushed to the file, so
+/// the manifest never claims data that isn't there.
+use crate::engine::Chunk;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Sidecar manifest of a partial download.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    pub url: String,
+    pub total: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+/// `<dest>.part`, where the data is written until the download completes.
+pub(crate) fn part_path(dest: &Path) -> PathBuf {
+    with_suffix(dest, ".part")
+}
+
+/// `<dest>.part.json`, the manifest next to the partial file.
+pub(crate) fn manifest_path(dest: &Path) -> PathBuf {
+    with_suffix(dest, ".part.json")
+}
+
+pub(crate) fn with_suffix(dest: &Path, suffix: &str) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+impl Manifest {
+    /// The manifest for `dest`, if a readable one exists.
+    pub async fn load(dest: &Path) -> Option<Self> {
+        let raw = tokio::fs::read(manifest_path(dest)).await.ok()?;
+        serde_json::from_slice(&raw).ok()
+    }
+
+    /// Write the manifest atomically (temp file, then rename).
+    pub async fn save(&self, dest: &Path) -> std::io::Result<()> {
+        let path = manifest_path(dest);
+        let tmp = with_suffix(&path, ".tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(self)?).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+
+    /// Drop the partial file and its manifest.
+    pub async fn discard(dest: &Path) {
+        let _ = tokio::fs::remove_file(part_path(dest)).await;
+        let _ = tokio::fs::remove_file(manifest_path(dest)).await;
+    }
+
+    pub fn downloaded(&self) -> u64 {
+        self.chunks.iter().map(|c| c.done.min(c.len())).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::{sample_body, serve};
+    use crate::engine::{Downloader, Progress, ProgressCallback};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn resumes_only_the_missing_bytes() {
+        let body = sample_body(300_000);
+        let (url, served) = serve(body.clone(), true).await;
+        let dir = std::env::temp_dir().join(format!("hermes-dl-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("video.bin");
+
+        // A previous run finished the first range and 10 000 bytes of the second
+        let mut partial = vec![0u8; body.len()];
+        partial[..85_000].copy_from_slice(&body[..85_000]);
+        std::fs::write(part_path(&dest), &partial).unwrap();
+        let manifest = Manifest {
+            url: url.clone(),
+            total: body.len() as u64,
+            chunks: vec![
+                Chunk { start: 0, end: 74_999, done: 75_000 },
+                Chunk { start: 75_000, end: 149_999, done: 10_000 },
+                Chunk { start: 150_000, end: 224_999, done: 0 },
+                Chunk { start: 225_000, end: 299_999, done: 0 },
+            ],
+        };
+        manifest.save(&dest).await.unwrap();
+        assert_eq!(Manifest::load(&dest).await.unwrap().downloaded(), 85_000);
+
+        let downloader = Downloader::builder().min_chunk_size(50_000).build().unwrap();
+        let on_progress: ProgressCallback = Arc::new(|_: Progress| {});
+        let outcome = downloader.resume(&dest, on_progress).await.unwrap();
+
+        assert_eq!(outcome.bytes, body.len() as u64);
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert!(!part_path(&dest).exists());
+        assert!(!manifest_path(&dest).exists());
+        // Probe byte plus the 215 000 missing bytes, nothing already on disk
+        assert_eq!(served.load(Ordering::Relaxed), 1 + 215_000);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+/// Bandwidth caps for downloads.
+///
+/// A [`RateLimiter`] is a token bucket refilled at `bytes_per_sec` and holding
+/// at most one second's worth, so short bursts pass and the average holds.
+/// Each download gets its own bucket; one shared bucket can cap all of them.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token-bucket limiter shared by the tasks reading one or more downloads.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that may be read now; negative while callers are waiting.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1) as f64;
+        Self {
+            bytes_per_sec,
+            bucket: Mutex::new(Bucket { tokens: bytes_per_sec, refilled_at: Instant::now() }),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec as u64
+    }
+
+    /// Account for `bytes` just read, sleeping until the rate allows them.
+    ///
+    /// The bucket may go into debt, so a read larger than the burst still
+    /// passes; concurrent callers queue up behind each other's debt.
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * self.bytes_per_sec;
+            bucket.tokens = (bucket.tokens + refill).min(self.bytes_per_sec) - bytes as f64;
+            bucket.refilled_at = now;
+            (bucket.tokens < 0.0).then(|| Duration::from_secs_f64(-bucket.tokens / self.bytes_per_sec))
+        };
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Parse a rate such as `500K`, `2M` or `1048576` (bytes per second; K, M and
+/// G are powers of 1024, a trailing `B` or `/s` is ignored). Empty or zero
+/// means no limit and gives None, as does anything unparseable.
+pub fn parse_rate(value: &str) -> Option<u64> {
+    let value = value.trim().to_ascii_uppercase();
+    let value = value.trim_end_matches("/S").trim_end_matches('B');
+    let (number, unit) = match value.char_indices().last()? {
+        (i, 'K') => (&value[..i], 1u64 << 10),
+        (i, 'M') => (&value[..i], 1 << 20),
+        (i, 'G') => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    let rate = number.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)? * unit as f64;
+    (rate >= 1.0).then_some(rate as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rates() {
+        assert_eq!(parse_rate("500K"), Some(512_000));
+        assert_eq!(parse_rate(" 1.5m "), Some(1_572_864));
+        assert_eq!(parse_rate("2MB/s"), Some(2_097_152));
+        assert_eq!(parse_rate("4096"), Some(4096));
+        assert_eq!(parse_rate(""), None);
+        assert_eq!(parse_rate("0"), None);
+        assert_eq!(parse_rate("fast"), None);
+    }
+
+    #[tokio::test]
+    async fn holds_the_average_rate() {
+        let limiter = RateLimiter::new(200_000);
+        let started = Instant::now();
+        // The first second's worth is the burst, the rest has to wait
+        for _ in 0..6 {
+            limiter.acquire(50_000).await;
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(450), "took {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1500), "took {:?}", elapsed);
+    }
+}
@@ -0,0 +1,18 @@
+-- Persist multi-link Telegram forward batches so they survive bot restarts.
+-- links_json holds the ordered list of t.me URLs; next_index is the position
+-- of the next link to copy. Batches with status 'running' are resumed on startup.
+
+CREATE TABLE IF NOT EXISTS forward_batches (
+    id TEXT PRIMARY KEY,
+    chat_id INTEGER NOT NULL,
+    links_json TEXT NOT NULL,
+    next_index INTEGER NOT NULL DEFAULT 0,
+    success_count INTEGER NOT NULL DEFAULT 0,
+    failed_count INTEGER NOT NULL DEFAULT 0,
+    status_msg_id INTEGER,
+    status TEXT NOT NULL DEFAULT 'running',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_forward_batches_status ON forward_batches(status);
@@ -0,0 +1,9 @@
+-- DB-backed allowlist for private deployments.
+-- When allowlist mode is on (ALLOWED_USERS / ALLOWLIST_MODE), only chats listed
+-- here or in ALLOWED_USERS (plus the admin) may use the bot.
+
+CREATE TABLE IF NOT EXISTS allowed_users (
+    chat_id INTEGER PRIMARY KEY,
+    added_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
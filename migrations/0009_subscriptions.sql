@@ -0,0 +1,15 @@
+-- Recurring playlist/channel subscriptions.
+-- A background job re-checks each subscription every SUBSCRIPTION_INTERVAL_SECS
+-- and delivers items not yet recorded in the subscription's download archive.
+
+CREATE TABLE IF NOT EXISTS subscriptions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    extract_audio BOOLEAN NOT NULL DEFAULT 1,
+    last_checked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(chat_id, url)
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_chat ON subscriptions(chat_id);
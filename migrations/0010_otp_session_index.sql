@@ -0,0 +1,4 @@
+-- Composite index for the per-user OTP session lookups and deletes
+-- (WHERE chat_id = ? AND token ...).
+
+CREATE INDEX IF NOT EXISTS idx_sessions_chat_token ON sessions(chat_id, token);
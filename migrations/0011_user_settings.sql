@@ -0,0 +1,20 @@
+-- Generic per-user key-value settings shared by the bot and the web UI.
+-- Keys are validated against hermes_shared::user_settings::REGISTRY.
+-- language/timezone move here from user_preferences (their columns stay for
+-- compatibility but are no longer read).
+
+CREATE TABLE IF NOT EXISTS user_settings (
+    chat_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chat_id, key)
+);
+
+INSERT OR IGNORE INTO user_settings (chat_id, key, value)
+SELECT chat_id, 'language', language FROM user_preferences
+WHERE language IS NOT NULL AND language != 'en';
+
+INSERT OR IGNORE INTO user_settings (chat_id, key, value)
+SELECT chat_id, 'timezone', timezone FROM user_preferences
+WHERE timezone IS NOT NULL AND timezone != 'UTC';
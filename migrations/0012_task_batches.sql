@@ -0,0 +1,6 @@
+-- Group tasks created by one POST /api/download/batch call so the dashboard
+-- can track the batch as a unit (GET /api/batch/:batch_id).
+
+ALTER TABLE tasks ADD COLUMN batch_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_batch ON tasks(batch_id);
@@ -0,0 +1,14 @@
+-- Periodic snapshots of task/user counters so the dashboard can chart trends
+-- (GET /api/admin/metrics-history). Written by the API server; old rows are
+-- pruned after METRICS_RETENTION_DAYS.
+
+CREATE TABLE IF NOT EXISTS metrics_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    taken_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    total_downloads INTEGER NOT NULL,
+    successes INTEGER NOT NULL,
+    failures INTEGER NOT NULL,
+    active_users INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_metrics_history_taken_at ON metrics_history(taken_at);
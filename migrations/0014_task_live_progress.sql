@@ -0,0 +1,5 @@
+-- Live download speed / ETA for the web dashboard. The bot mirrors its
+-- in-memory queue progress into these columns (throttled) while a task runs.
+
+ALTER TABLE tasks ADD COLUMN speed TEXT;
+ALTER TABLE tasks ADD COLUMN eta_secs INTEGER;
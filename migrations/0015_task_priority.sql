@@ -0,0 +1,4 @@
+-- Queue priority (low / normal / high) the bot scheduled a task with.
+-- Retries from the web dashboard are bumped to high.
+
+ALTER TABLE tasks ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal';
@@ -0,0 +1,4 @@
+-- Scheduled downloads wait with status 'scheduled' until scheduled_at (UTC),
+-- when the bot moves them to 'web_queued'. The column exists since 0001.
+
+CREATE INDEX IF NOT EXISTS idx_tasks_scheduled ON tasks(status, scheduled_at);
@@ -0,0 +1,16 @@
+-- Per-user download quotas.
+-- tasks.file_size records the delivered file's size so byte quotas can be summed.
+-- A quotas row overrides the QUOTA_* defaults for one chat: NULL keeps the
+-- default, 0 lifts that limit, and unlimited = 1 exempts the chat entirely.
+
+ALTER TABLE tasks ADD COLUMN file_size INTEGER;
+
+CREATE TABLE IF NOT EXISTS quotas (
+    chat_id INTEGER PRIMARY KEY,
+    daily_downloads INTEGER,
+    daily_bytes INTEGER,
+    weekly_downloads INTEGER,
+    weekly_bytes INTEGER,
+    unlimited BOOLEAN NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- Ban and allowlist flags on users.
+-- is_banned blocks a chat from the bot and the download API (the admin can't be
+-- banned). allowlisted replaces the allowed_users table as the DB allowlist used
+-- in allowlist mode (ALLOWED_USERS / ALLOWLIST_MODE).
+
+ALTER TABLE users ADD COLUMN is_banned BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN allowlisted BOOLEAN NOT NULL DEFAULT 0;
+
+INSERT OR IGNORE INTO users (chat_id) SELECT chat_id FROM allowed_users;
+UPDATE users SET allowlisted = 1 WHERE chat_id IN (SELECT chat_id FROM allowed_users);
+DROP TABLE allowed_users;
@@ -0,0 +1,13 @@
+-- Watch-later list: links saved with /save (or the dashboard) to download later.
+-- A chat saves each URL once; the list is capped at MAX_SAVED_ITEMS per chat.
+
+CREATE TABLE IF NOT EXISTS saved_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    note TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(chat_id, url)
+);
+
+CREATE INDEX IF NOT EXISTS idx_saved_items_chat ON saved_items(chat_id);
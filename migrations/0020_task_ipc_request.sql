@@ -0,0 +1,5 @@
+-- The worker request a youtube_dl task was started with (IPCRequest JSON), so
+-- a task interrupted by a restart is resumed with the same format, language
+-- and delivery choices instead of the user's defaults.
+
+ALTER TABLE tasks ADD COLUMN ipc_request TEXT;
@@ -0,0 +1,118 @@
+//! Captions on delivered files.
+//!
+//! A template such as `{title} — {artist} ({duration})\nvia Hermes` is filled
+//! from the download's completion payload. Placeholders:
+//!
+//! - `{title}` — media title (the file name without extension as a fallback)
+//! - `{artist}` — artist tag, else the uploader/channel
+//! - `{duration}` — `3:45` or `1:02:03`
+//! - `{filename}` — delivered file name
+//! - `{url}` — the link that was downloaded
+//!
+//! `\n` in a template is a line break. Fields without a value are left out,
+//! along with brackets and separators they leave empty, so one template works
+//! for files with and without tags.
+
+/// Telegram's caption limit, in characters.
+pub const MAX_CAPTION_CHARS: usize = 1024;
+
+/// Per-user `caption_template` value that means "use CAPTION_TEMPLATE".
+pub const USE_DEFAULT: &str = "default";
+/// Per-user `caption_template` value that turns captions off.
+pub const OFF: &str = "off";
+
+/// Values for a template's placeholders.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptionFields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// Length in whole seconds.
+    pub duration: Option<u32>,
+    pub filename: Option<String>,
+    pub url: Option<String>,
+}
+
+/// The template to use for a user: their `caption_template` setting, or the
+/// global one for `default`. `None` means no caption.
+pub fn effective_template<'a>(user_value: &'a str, global: &'a str) -> Option<&'a str> {
+    let template = match user_value.trim() {
+        "" | USE_DEFAULT => global,
+        OFF => return None,
+        custom => custom,
+    };
+    Some(template).filter(|t| !t.trim().is_empty())
+}
+
+/// "3:45" or "1:02:03".
+pub fn format_duration(secs: u32) -> String {
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// Fill `template` from `fields`. Returns `None` when nothing is left.
+pub fn render(template: &str, fields: &CaptionFields) -> Option<String> {
+    let value = |v: &Option<String>| v.as_deref().map(str::trim).unwrap_or_default().to_string();
+    let filled = template
+        .replace("\\n", "\n")
+        .replace("{title}", &value(&fields.title))
+        .replace("{artist}", &value(&fields.artist))
+        .replace("{duration}", &fields.duration.map(format_duration).unwrap_or_default())
+        .replace("{filename}", &value(&fields.filename))
+        .replace("{url}", &value(&fields.url));
+
+    let lines: Vec<String> = filled.lines().map(tidy_line).collect();
+    let caption = lines.join("\n").trim().to_string();
+    if caption.is_empty() {
+        return None;
+    }
+    Some(caption.chars().take(MAX_CAPTION_CHARS).collect())
+}
+
+/// Drop what an empty field leaves behind on a line: `()`, `[]`, doubled
+/// spaces and separators at either end.
+fn tidy_line(line: &str) -> String {
+    let mut line = line.to_string();
+    for empty in ["()", "[]"] {
+        line = line.replace(empty, "");
+    }
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    let separators: &[char] = &['—', '–', '-', '·', '|', ',', ':', ' '];
+    line.trim_matches(separators).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "{title} — {artist} ({duration})\\nvia Hermes";
+
+    #[test]
+    fn test_render_caption() {
+        let fields = CaptionFields {
+            title: Some("Song".into()),
+            artist: Some("Band".into()),
+            duration: Some(225),
+            filename: Some("Song.mp3".into()),
+            url: None,
+        };
+        assert_eq!(render(TEMPLATE, &fields).as_deref(), Some("Song — Band (3:45)\nvia Hermes"));
+
+        // Missing artist and duration: no dangling separator or brackets
+        let fields = CaptionFields { title: Some("Clip".into()), ..Default::default() };
+        assert_eq!(render(TEMPLATE, &fields).as_deref(), Some("Clip\nvia Hermes"));
+        assert_eq!(render("{url}", &fields), None);
+        assert_eq!(format_duration(3723), "1:02:03");
+    }
+
+    #[test]
+    fn test_effective_template() {
+        assert_eq!(effective_template("default", TEMPLATE), Some(TEMPLATE));
+        assert_eq!(effective_template("default", ""), None);
+        assert_eq!(effective_template("off", TEMPLATE), None);
+        assert_eq!(effective_template("{title}", ""), Some("{title}"));
+    }
+}
@@ -2,8 +2,56 @@
 use anyhow::Result;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteJournalMode};
 use sqlx::Row;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use tracing::info;
+use crate::task_queue::Priority;
+
+/// Build a SQLite connection URL from a DATABASE_PATH value.
+///
+/// Inputs that are already `sqlite:` URLs are returned unchanged. Paths are made
+/// absolute (the file itself need not exist yet), Windows verbatim prefixes
+/// (`\\?\`, `\\?\UNC\`) are stripped, and the result is opened with `mode=rwc`.
+pub fn resolve_database_url(path: &str) -> String {
+    let path = path.trim();
+    if path.starts_with("sqlite:") {
+        return path.to_string();
+    }
+    let path = if path.is_empty() { "./hermes.db" } else { path };
+    let absolute = absolutize_path(Path::new(path));
+    format!("sqlite://{}?mode=rwc", normalize_db_path(&absolute.display().to_string()))
+}
+
+/// Resolve a possibly relative path, canonicalizing the parent directory
+/// when the file has not been created yet.
+fn absolutize_path(path: &Path) -> PathBuf {
+    if let Ok(p) = path.canonicalize() {
+        return p;
+    }
+    if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        if let Ok(dir) = parent.canonicalize() {
+            return dir.join(name);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Strip Windows verbatim prefixes (which break SQLite URL parsing) and use
+/// forward slashes for Windows paths. Unix paths are left untouched.
+fn normalize_db_path(path: &str) -> String {
+    let path = match path.strip_prefix(r"\\?\UNC\") {
+        Some(rest) => format!(r"\\{}", rest),
+        None => path.strip_prefix(r"\\?\").unwrap_or(path).to_string(),
+    };
+    let is_windows = path.starts_with(r"\\")
+        || (path.as_bytes().get(1) == Some(&b':') && path.as_bytes()[0].is_ascii_alphabetic());
+    if is_windows {
+        path.replace('\\', "/")
+    } else {
+        path
+    }
+}
 
 /// Create SQLite connection pool with WAL mode and busy timeout.
 pub async fn create_pool(database_url: &str) -> Result<SqlitePool> {
@@ -39,11 +87,12 @@ pub async fn create_task(
     task_type: &str,
     url: &str,
     label: Option<&str>,
+    priority: Priority,
 ) -> Result<()> {
     sqlx::query(
         r#"
-        INSERT INTO tasks (id, chat_id, task_type, url, label, status, progress)
-        VALUES (?, ?, ?, ?, ?, 'queued', 0)
+        INSERT INTO tasks (id, chat_id, task_type, url, label, status, progress, priority)
+        VALUES (?, ?, ?, ?, ?, 'queued', 0, ?)
         "#,
     )
     .bind(task_id)
@@ -51,26 +100,34 @@ pub async fn create_task(
     .bind(task_type)
     .bind(url)
     .bind(label)
+    .bind(priority.as_str())
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
-/// Update task status and progress.
+/// Update live progress of a queued/running task, marking it running.
+/// Tasks that already finished or were cancelled are left alone, so a late
+/// write can't resurrect them.
 pub async fn update_task_progress(
     pool: &SqlitePool,
     task_id: &str,
-    status: &str,
     progress: i32,
+    speed: Option<&str>,
+    eta_secs: Option<i64>,
 ) -> Result<()> {
     sqlx::query(
         r#"
-        UPDATE tasks SET status = ?, progress = ? WHERE id = ?
+        UPDATE tasks
+        SET status = 'running', progress = ?, speed = ?, eta_secs = ?,
+            started_at = COALESCE(started_at, CURRENT_TIMESTAMP)
+        WHERE id = ? AND status IN ('queued', 'running')
         "#,
     )
-    .bind(status)
     .bind(progress)
+    .bind(speed)
+    .bind(eta_secs)
     .bind(task_id)
     .execute(pool)
     .await?;
@@ -78,20 +135,23 @@ pub async fn update_task_progress(
     Ok(())
 }
 
-/// Mark task as completed with file path.
+/// Mark task as completed with file path. The file's size is recorded for
+/// byte quotas when it can be read.
 pub async fn complete_task(
     pool: &SqlitePool,
     task_id: &str,
     file_path: &str,
 ) -> Result<()> {
+    let file_size = tokio::fs::metadata(file_path).await.ok().map(|m| m.len() as i64);
     sqlx::query(
         r#"
         UPDATE tasks
-        SET status = 'done', progress = 100, file_path = ?, finished_at = CURRENT_TIMESTAMP
+        SET status = 'done', progress = 100, file_path = ?, file_size = ?, finished_at = CURRENT_TIMESTAMP
         WHERE id = ?
         "#,
     )
     .bind(file_path)
+    .bind(file_size)
     .bind(task_id)
     .execute(pool)
     .await?;
@@ -130,6 +190,7 @@ pub async fn find_cached_download(
     let row = sqlx::query(
         r#"SELECT id, file_path, channel_msg_id FROM tasks
            WHERE url = ? AND status = 'done' AND file_path IS NOT NULL
+             AND task_type NOT IN ('hardsubs', 'clip', 'subtitles')
            ORDER BY finished_at DESC LIMIT 1"#,
     )
     .bind(url)
@@ -212,31 +273,56 @@ pub async fn count_running_tasks(pool: &SqlitePool) -> Result<i64> {
 
 // ====== SESSION MANAGEMENT ======
 
+/// Global cap on live OTP sessions across all users.
+pub const MAX_ACTIVE_OTP_SESSIONS: i64 = 500;
+
 /// Create an OTP session (temporary, 5-min expiry).
+///
+/// Returns false without inserting when `MAX_ACTIVE_OTP_SESSIONS` live OTP
+/// sessions already exist, so a flood of requests for many chat ids cannot
+/// grow the sessions table unbounded.
 pub async fn create_otp_session(
     pool: &SqlitePool,
     chat_id: i64,
     otp_code: &str,
-) -> Result<()> {
+) -> Result<bool> {
     // Delete any existing OTP sessions for this user first
     sqlx::query("DELETE FROM sessions WHERE chat_id = ? AND token LIKE 'otp:%'")
         .bind(chat_id)
         .execute(pool)
         .await?;
+    cleanup_expired_otp_sessions(pool).await?;
 
     let token = format!("otp:{}", otp_code);
-    sqlx::query(
+    let result = sqlx::query(
         r#"
         INSERT INTO sessions (token, chat_id, expires_at)
-        VALUES (?, ?, datetime('now', '+5 minutes'))
+        SELECT ?, ?, datetime('now', '+5 minutes')
+        WHERE (
+            SELECT COUNT(*) FROM sessions
+            WHERE token LIKE 'otp:%' AND expires_at > datetime('now')
+        ) < ?
         "#,
     )
     .bind(&token)
     .bind(chat_id)
+    .bind(MAX_ACTIVE_OTP_SESSIONS)
     .execute(pool)
     .await?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete expired OTP sessions only. Cheap enough to run far more often
+/// than the general session cleanup.
+pub async fn cleanup_expired_otp_sessions(pool: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query(
+        "DELETE FROM sessions WHERE token LIKE 'otp:%' AND expires_at <= datetime('now')",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
 }
 
 /// Verify an OTP code for a chat_id. Returns true if valid and not expired.
@@ -401,6 +487,95 @@ pub async fn get_user_tasks_by_status(
     Ok(tasks)
 }
 
+/// Column a paged task listing is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaskSort {
+    #[default]
+    CreatedAt,
+    /// Unfinished tasks (no finished_at) sort last either way.
+    FinishedAt,
+}
+
+impl TaskSort {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "created_at" => Some(TaskSort::CreatedAt),
+            "finished_at" => Some(TaskSort::FinishedAt),
+            _ => None,
+        }
+    }
+}
+
+/// One page of a user's tasks, optionally filtered by status, plus the total
+/// number of matching tasks.
+pub async fn get_user_tasks_page(
+    pool: &SqlitePool,
+    chat_id: i64,
+    status: Option<&str>,
+    sort: TaskSort,
+    descending: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<crate::models::Task>, i64)> {
+    let dir = if descending { "DESC" } else { "ASC" };
+    let order = match sort {
+        TaskSort::CreatedAt => format!("created_at {dir}, id {dir}"),
+        TaskSort::FinishedAt => format!("finished_at IS NULL, finished_at {dir}, created_at {dir}, id {dir}"),
+    };
+    let tasks = sqlx::query_as::<_, crate::models::Task>(&format!(
+        "SELECT * FROM tasks WHERE chat_id = ? AND (? IS NULL OR status = ?) ORDER BY {order} LIMIT ? OFFSET ?"
+    ))
+    .bind(chat_id)
+    .bind(status)
+    .bind(status)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM tasks WHERE chat_id = ? AND (? IS NULL OR status = ?)",
+    )
+    .bind(chat_id)
+    .bind(status)
+    .bind(status)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((tasks, total))
+}
+
+/// One page of a user's finished downloads (done or failed), most recently
+/// finished first, plus how many there are in total.
+pub async fn get_user_history_page(
+    pool: &SqlitePool,
+    chat_id: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<crate::models::Task>, i64)> {
+    let tasks = sqlx::query_as::<_, crate::models::Task>(
+        r#"
+        SELECT * FROM tasks WHERE chat_id = ? AND status IN ('done', 'error')
+        ORDER BY COALESCE(finished_at, created_at) DESC, id DESC
+        LIMIT ? OFFSET ?
+        "#,
+    )
+    .bind(chat_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM tasks WHERE chat_id = ? AND status IN ('done', 'error')",
+    )
+    .bind(chat_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((tasks, total))
+}
+
 /// Get user's completed downloads (files page).
 pub async fn get_user_completed_files(
     pool: &SqlitePool,
@@ -420,37 +595,71 @@ pub async fn get_user_completed_files(
     Ok(tasks)
 }
 
-/// Clear all completed/failed/cancelled tasks for a user.
-/// Returns the file_paths of deleted tasks so the caller can clean up files.
-pub async fn clear_user_history(
+/// Finished (done/error/cancelled) tasks of a user with their file paths, for
+/// clearing history. The caller removes each file before its row (`delete_task`),
+/// so a crash midway never leaves a file without a record pointing at it.
+pub async fn get_user_history_files(
     pool: &SqlitePool,
     chat_id: i64,
-) -> Result<Vec<Option<String>>> {
-    // First, get file paths for cleanup
-    let paths: Vec<(Option<String>,)> = sqlx::query_as(
-        "SELECT file_path FROM tasks WHERE chat_id = ? AND status IN ('done', 'error', 'cancelled')",
+) -> Result<Vec<(String, Option<String>)>> {
+    let rows: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT id, file_path FROM tasks WHERE chat_id = ? AND status IN ('done', 'error', 'cancelled')",
     )
     .bind(chat_id)
     .fetch_all(pool)
     .await?;
 
-    // Delete the records
-    sqlx::query(
-        "DELETE FROM tasks WHERE chat_id = ? AND status IN ('done', 'error', 'cancelled')",
+    Ok(rows)
+}
+
+/// Cancel a task by setting status to cancelled.
+pub async fn cancel_task(pool: &SqlitePool, task_id: &str) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE tasks SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP
+        WHERE id = ? AND status IN ('scheduled', 'web_queued', 'queued', 'running')
+        "#,
     )
-    .bind(chat_id)
+    .bind(task_id)
     .execute(pool)
     .await?;
 
-    Ok(paths.into_iter().map(|(p,)| p).collect())
+    Ok(result.rows_affected() > 0)
 }
 
-/// Cancel a task by setting status to cancelled.
-pub async fn cancel_task(pool: &SqlitePool, task_id: &str) -> Result<bool> {
+/// Tasks the bot had queued or running, oldest first, for resuming after a
+/// restart. Web-queued tasks are left to the web queue poller.
+pub async fn get_unfinished_tasks(pool: &SqlitePool) -> Result<Vec<crate::models::Task>> {
+    let tasks = sqlx::query_as::<_, crate::models::Task>(
+        "SELECT * FROM tasks WHERE status IN ('queued', 'running') ORDER BY created_at ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+/// Tasks that haven't finished yet (including web-queued ones), for live
+/// progress streaming.
+pub async fn get_active_tasks(pool: &SqlitePool) -> Result<Vec<crate::models::Task>> {
+    let tasks = sqlx::query_as::<_, crate::models::Task>(
+        "SELECT * FROM tasks WHERE status IN ('web_queued', 'queued', 'running') ORDER BY created_at ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+/// Put a task interrupted by shutdown back to 'queued' with its progress
+/// cleared, so the next start resumes it. 'error' counts as interrupted too:
+/// a handler may have failed it when the worker was stopped.
+pub async fn requeue_task(pool: &SqlitePool, task_id: &str) -> Result<bool> {
     let result = sqlx::query(
         r#"
-        UPDATE tasks SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP
-        WHERE id = ? AND status IN ('web_queued', 'queued', 'running')
+        UPDATE tasks SET status = 'queued', progress = 0, speed = NULL, eta_secs = NULL,
+            started_at = NULL, finished_at = NULL, error_msg = NULL
+        WHERE id = ? AND status IN ('queued', 'running', 'error')
         "#,
     )
     .bind(task_id)
@@ -460,6 +669,25 @@ pub async fn cancel_task(pool: &SqlitePool, task_id: &str) -> Result<bool> {
     Ok(result.rows_affected() > 0)
 }
 
+/// Which of `task_ids` are marked cancelled, e.g. through the API while the
+/// bot is still running them.
+pub async fn cancelled_among(pool: &SqlitePool, task_ids: &[String]) -> Result<Vec<String>> {
+    if task_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; task_ids.len()].join(", ");
+    let sql = format!(
+        "SELECT id FROM tasks WHERE status = 'cancelled' AND id IN ({})",
+        placeholders
+    );
+    let mut query = sqlx::query_as::<_, (String,)>(&sql);
+    for task_id in task_ids {
+        query = query.bind(task_id);
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
 // ====== ADMIN QUERIES ======
 
 /// Get all users (admin).
@@ -515,6 +743,73 @@ pub async fn get_system_stats(pool: &SqlitePool) -> Result<SystemStats> {
     })
 }
 
+// ====== METRICS HISTORY ======
+
+/// One periodic snapshot of the task/user counters.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MetricsSnapshot {
+    pub taken_at: String,
+    pub total_downloads: i64,
+    pub successes: i64,
+    pub failures: i64,
+    /// Users active in the 24 hours before the snapshot.
+    pub active_users: i64,
+}
+
+/// Write a snapshot of the current counters to `metrics_history`.
+pub async fn record_metrics_snapshot(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO metrics_history (total_downloads, successes, failures, active_users)
+        SELECT
+            (SELECT COUNT(*) FROM tasks),
+            (SELECT COUNT(*) FROM tasks WHERE status = 'done'),
+            (SELECT COUNT(*) FROM tasks WHERE status = 'error'),
+            (SELECT COUNT(*) FROM users WHERE last_activity >= datetime('now', '-1 day'))
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Snapshots taken at or after `since` ("YYYY-MM-DD HH:MM:SS", UTC), oldest first.
+pub async fn get_metrics_history(pool: &SqlitePool, since: &str) -> Result<Vec<MetricsSnapshot>> {
+    let rows: Vec<(String, i64, i64, i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT CAST(taken_at AS TEXT), total_downloads, successes, failures, active_users
+        FROM metrics_history
+        WHERE taken_at >= ?
+        ORDER BY taken_at ASC
+        "#,
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(taken_at, total_downloads, successes, failures, active_users)| MetricsSnapshot {
+            taken_at,
+            total_downloads,
+            successes,
+            failures,
+            active_users,
+        })
+        .collect())
+}
+
+/// Delete snapshots older than `retention_days`. Returns the number removed.
+pub async fn prune_metrics_history(pool: &SqlitePool, retention_days: i64) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM metrics_history WHERE taken_at < datetime('now', ?)")
+        .bind(format!("-{} days", retention_days))
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
 // ====== WEB DOWNLOAD QUEUE ======
 
 /// Create a task queued from the web dashboard.
@@ -526,11 +821,42 @@ pub async fn create_web_task(
     url: &str,
     task_type: &str,
     label: Option<&str>,
+    batch_id: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO tasks (id, chat_id, task_type, url, label, status, progress, batch_id)
+        VALUES (?, ?, ?, ?, ?, 'web_queued', 0, ?)
+        "#,
+    )
+    .bind(task_id)
+    .bind(chat_id)
+    .bind(task_type)
+    .bind(url)
+    .bind(label)
+    .bind(batch_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Create a task that waits until `scheduled_at` (UTC) before it is queued.
+/// Uses status 'scheduled'; [`promote_due_scheduled_tasks`] releases it.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_scheduled_task(
+    pool: &SqlitePool,
+    task_id: &str,
+    chat_id: i64,
+    url: &str,
+    task_type: &str,
+    label: Option<&str>,
+    scheduled_at: chrono::NaiveDateTime,
 ) -> Result<()> {
     sqlx::query(
         r#"
-        INSERT INTO tasks (id, chat_id, task_type, url, label, status, progress)
-        VALUES (?, ?, ?, ?, ?, 'web_queued', 0)
+        INSERT INTO tasks (id, chat_id, task_type, url, label, status, progress, scheduled_at)
+        VALUES (?, ?, ?, ?, ?, 'scheduled', 0, ?)
         "#,
     )
     .bind(task_id)
@@ -538,12 +864,110 @@ pub async fn create_web_task(
     .bind(task_type)
     .bind(url)
     .bind(label)
+    .bind(scheduled_at)
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
+/// Move scheduled tasks whose time has come to 'web_queued', where the web
+/// queue poller picks them up. Returns how many were released.
+pub async fn promote_due_scheduled_tasks(pool: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE tasks SET status = 'web_queued' WHERE status = 'scheduled' AND scheduled_at <= ?",
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// A user's tasks still waiting for their scheduled time, soonest first.
+pub async fn get_scheduled_tasks(pool: &SqlitePool, chat_id: i64) -> Result<Vec<crate::models::Task>> {
+    let tasks = sqlx::query_as::<_, crate::models::Task>(
+        "SELECT * FROM tasks WHERE chat_id = ? AND status = 'scheduled' ORDER BY scheduled_at ASC",
+    )
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tasks)
+}
+
+/// Aggregate progress of a web batch download.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BatchSummary {
+    pub batch_id: String,
+    pub total: i64,
+    pub done: i64,
+    pub running: i64,
+    pub queued: i64,
+    pub failed: i64,
+    pub cancelled: i64,
+    /// Mean progress across all tasks in the batch (0-100).
+    pub progress: i64,
+}
+
+/// Summarize a user's batch. Returns None if the batch has no tasks for this user.
+pub async fn get_batch_summary(
+    pool: &SqlitePool,
+    batch_id: &str,
+    chat_id: i64,
+) -> Result<Option<BatchSummary>> {
+    let (total, done, running, queued, failed, cancelled, progress): (i64, i64, i64, i64, i64, i64, i64) =
+        sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*),
+                COALESCE(SUM(status = 'done'), 0),
+                COALESCE(SUM(status = 'running'), 0),
+                COALESCE(SUM(status IN ('web_queued', 'queued')), 0),
+                COALESCE(SUM(status = 'error'), 0),
+                COALESCE(SUM(status = 'cancelled'), 0),
+                CAST(COALESCE(AVG(CASE WHEN status = 'done' THEN 100 ELSE progress END), 0) AS INTEGER)
+            FROM tasks WHERE batch_id = ? AND chat_id = ?
+            "#,
+        )
+        .bind(batch_id)
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await?;
+
+    if total == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(BatchSummary {
+        batch_id: batch_id.to_string(),
+        total,
+        done,
+        running,
+        queued,
+        failed,
+        cancelled,
+        progress,
+    }))
+}
+
+/// Tasks belonging to a user's batch, oldest first.
+pub async fn get_batch_tasks(
+    pool: &SqlitePool,
+    batch_id: &str,
+    chat_id: i64,
+) -> Result<Vec<crate::models::Task>> {
+    let tasks = sqlx::query_as::<_, crate::models::Task>(
+        "SELECT * FROM tasks WHERE batch_id = ? AND chat_id = ? ORDER BY created_at ASC",
+    )
+    .bind(batch_id)
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tasks)
+}
+
 /// Fetch and claim pending web-queued tasks (atomically set to 'queued').
 pub async fn claim_web_queued_tasks(
     pool: &SqlitePool,
@@ -571,10 +995,11 @@ pub async fn claim_web_queued_tasks(
 }
 
 /// Retry a failed/cancelled/error task by re-queuing it as web_queued.
+/// Retries run at high priority.
 pub async fn retry_task(pool: &SqlitePool, task_id: &str) -> Result<bool> {
     let result = sqlx::query(
         r#"
-        UPDATE tasks SET status = 'web_queued', progress = 0,
+        UPDATE tasks SET status = 'web_queued', progress = 0, priority = 'high',
             error_msg = NULL, finished_at = NULL, started_at = NULL
         WHERE id = ? AND status IN ('cancelled', 'error', 'done')
         "#,
@@ -586,6 +1011,26 @@ pub async fn retry_task(pool: &SqlitePool, task_id: &str) -> Result<bool> {
     Ok(result.rows_affected() > 0)
 }
 
+/// Record the queue priority a task was scheduled with.
+pub async fn set_task_priority(pool: &SqlitePool, task_id: &str, priority: Priority) -> Result<()> {
+    sqlx::query("UPDATE tasks SET priority = ? WHERE id = ?")
+        .bind(priority.as_str())
+        .bind(task_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Store the worker request a task was started with, for resuming it.
+pub async fn set_task_ipc_request(pool: &SqlitePool, task_id: &str, request_json: &str) -> Result<()> {
+    sqlx::query("UPDATE tasks SET ipc_request = ? WHERE id = ?")
+        .bind(request_json)
+        .bind(task_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 /// Update a task's URL and/or label (only if still queued).
 pub async fn update_task(
     pool: &SqlitePool,
@@ -620,6 +1065,37 @@ pub async fn update_task(
     Ok(affected > 0)
 }
 
+/// Set `label` on every task in `task_ids` that belongs to `chat_id`, in one
+/// transaction. Returns how many were updated and the ids that were skipped
+/// (unknown or owned by someone else; the two are not told apart).
+pub async fn bulk_update_labels(
+    pool: &SqlitePool,
+    chat_id: i64,
+    task_ids: &[String],
+    label: &str,
+) -> Result<(u64, Vec<String>)> {
+    let mut tx = pool.begin().await?;
+    let mut updated = 0u64;
+    let mut skipped = Vec::new();
+
+    for task_id in task_ids {
+        let r = sqlx::query("UPDATE tasks SET label = ? WHERE id = ? AND chat_id = ?")
+            .bind(label)
+            .bind(task_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await?;
+        if r.rows_affected() > 0 {
+            updated += 1;
+        } else {
+            skipped.push(task_id.clone());
+        }
+    }
+
+    tx.commit().await?;
+    Ok((updated, skipped))
+}
+
 /// Delete a task from the database.
 pub async fn delete_task(pool: &SqlitePool, task_id: &str) -> Result<()> {
     sqlx::query("DELETE FROM tasks WHERE id = ?")
@@ -659,45 +1135,518 @@ pub async fn get_allow_window_remaining(pool: &SqlitePool) -> Result<Option<i64>
     Ok(row.map(|r| r.0))
 }
 
-// ====== DEDUPLICATION PREFERENCES ======
-
-/// Get user's deduplication preference (default: true/enabled).
-/// Returns true if dedup is enabled, false if disabled, or true if preference not found.
-pub async fn get_user_dedup_preference(pool: &SqlitePool, chat_id: i64) -> Result<bool> {
-    // Try to read dedup preference; default to true if not found or column doesn't exist
-    // Using raw query to avoid sqlx compile-time checking of non-existent columns
-    match sqlx::query("SELECT COALESCE(dedup_enabled, 1) as enabled FROM user_preferences WHERE chat_id = ?")
-        .bind(chat_id)
-        .fetch_optional(pool)
-        .await {
-            Ok(Some(row)) => {
-                // try to extract the value; if it fails, default to true
-                match row.try_get::<i64, _>("enabled") {
-                    Ok(val) => Ok(val != 0),
-                    Err(_) => Ok(true), // Column doesn't exist or can't read, default to true
-                }
-            }
-            Ok(None) => Ok(true), // User not found, return default true
-            Err(_) => Ok(true), // Query failed (table/column doesn't exist), return default true
-        }
-}
+// ====== TELEGRAM FORWARD BATCHES ======
 
-/// Set user's deduplication preference.
-/// Inserts or updates the user's dedup preference.
-pub async fn set_user_dedup_preference(
+/// Persist a new forward batch before any links are copied.
+pub async fn create_forward_batch(
     pool: &SqlitePool,
+    batch_id: &str,
     chat_id: i64,
-    dedup_enabled: bool,
+    links: &[String],
+    status_msg_id: Option<i64>,
 ) -> Result<()> {
-    // First ensure user exists in users table
+    let links_json = serde_json::to_string(links)?;
     sqlx::query(
-        "INSERT OR IGNORE INTO users (chat_id) VALUES (?)"
+        r#"
+        INSERT INTO forward_batches (id, chat_id, links_json, status_msg_id, status)
+        VALUES (?, ?, ?, ?, 'running')
+        "#,
     )
+    .bind(batch_id)
     .bind(chat_id)
+    .bind(links_json)
+    .bind(status_msg_id)
     .execute(pool)
     .await?;
 
-    // Then ensure user has an entry in user_preferences
+    Ok(())
+}
+
+/// Record progress after each link: the next index to process and running counts.
+pub async fn update_forward_batch_progress(
+    pool: &SqlitePool,
+    batch_id: &str,
+    next_index: i64,
+    success_count: i64,
+    failed_count: i64,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE forward_batches
+        SET next_index = ?, success_count = ?, failed_count = ?, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(next_index)
+    .bind(success_count)
+    .bind(failed_count)
+    .bind(batch_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark a forward batch as finished so it is not resumed again.
+pub async fn finish_forward_batch(pool: &SqlitePool, batch_id: &str) -> Result<()> {
+    sqlx::query(
+        "UPDATE forward_batches SET status = 'done', updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(batch_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Get all batches that were still running when the bot last stopped.
+pub async fn get_incomplete_forward_batches(
+    pool: &SqlitePool,
+) -> Result<Vec<crate::models::ForwardBatch>> {
+    let batches = sqlx::query_as::<_, crate::models::ForwardBatch>(
+        "SELECT * FROM forward_batches WHERE status = 'running' ORDER BY created_at ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(batches)
+}
+
+// ====== USER ACCESS (allowlist, bans) ======
+
+/// Add a chat to the allowlist (no-op if already present).
+pub async fn add_allowed_user(pool: &SqlitePool, chat_id: i64) -> Result<()> {
+    set_user_flag(pool, chat_id, "allowlisted", true).await
+}
+
+/// Remove a chat from the allowlist. Returns true if it was listed.
+pub async fn remove_allowed_user(pool: &SqlitePool, chat_id: i64) -> Result<bool> {
+    let result = sqlx::query("UPDATE users SET allowlisted = 0 WHERE chat_id = ? AND allowlisted = 1")
+        .bind(chat_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Check whether a chat is on the DB allowlist.
+pub async fn is_allowed_user(pool: &SqlitePool, chat_id: i64) -> Result<bool> {
+    Ok(get_user_access(pool, chat_id).await?.allowlisted)
+}
+
+/// List all allowlisted chat ids, oldest first.
+pub async fn list_allowed_users(pool: &SqlitePool) -> Result<Vec<i64>> {
+    list_users_with_flag(pool, "allowlisted").await
+}
+
+/// Ban or unban a chat. Returns true if its state changed.
+pub async fn set_user_banned(pool: &SqlitePool, chat_id: i64, banned: bool) -> Result<bool> {
+    let was_banned = is_banned_user(pool, chat_id).await?;
+    set_user_flag(pool, chat_id, "is_banned", banned).await?;
+
+    Ok(was_banned != banned)
+}
+
+/// Check whether a chat is banned.
+pub async fn is_banned_user(pool: &SqlitePool, chat_id: i64) -> Result<bool> {
+    Ok(get_user_access(pool, chat_id).await?.banned)
+}
+
+/// List all banned chat ids, oldest first.
+pub async fn list_banned_users(pool: &SqlitePool) -> Result<Vec<i64>> {
+    list_users_with_flag(pool, "is_banned").await
+}
+
+/// A chat's ban and allowlist flags (both false for unknown chats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserAccess {
+    pub banned: bool,
+    pub allowlisted: bool,
+}
+
+/// Ban and allowlist flags in one query, for per-message access checks.
+pub async fn get_user_access(pool: &SqlitePool, chat_id: i64) -> Result<UserAccess> {
+    let row: Option<(bool, bool)> = sqlx::query_as("SELECT is_banned, allowlisted FROM users WHERE chat_id = ?")
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row
+        .map(|(banned, allowlisted)| UserAccess { banned, allowlisted })
+        .unwrap_or_default())
+}
+
+/// Set an access flag, creating the user row if the chat hasn't been seen yet.
+/// `flag` is one of the fixed column names above, never user input.
+async fn set_user_flag(pool: &SqlitePool, chat_id: i64, flag: &str, value: bool) -> Result<()> {
+    sqlx::query(&format!(
+        "INSERT INTO users (chat_id, {flag}) VALUES (?, ?) ON CONFLICT(chat_id) DO UPDATE SET {flag} = excluded.{flag}"
+    ))
+    .bind(chat_id)
+    .bind(value)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn list_users_with_flag(pool: &SqlitePool, flag: &str) -> Result<Vec<i64>> {
+    let rows: Vec<(i64,)> = sqlx::query_as(&format!("SELECT chat_id FROM users WHERE {flag} = 1 ORDER BY first_seen ASC"))
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|r| r.0).collect())
+}
+
+// ====== QUOTAS ======
+
+/// Per-chat quota override, if one is set.
+pub async fn get_quota_override(pool: &SqlitePool, chat_id: i64) -> Result<Option<crate::models::QuotaOverride>> {
+    let row = sqlx::query_as::<_, crate::models::QuotaOverride>(
+        "SELECT chat_id, daily_downloads, daily_bytes, weekly_downloads, weekly_bytes, unlimited \
+         FROM quotas WHERE chat_id = ?",
+    )
+    .bind(chat_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+/// Insert or replace a chat's quota override.
+pub async fn set_quota_override(pool: &SqlitePool, quota: &crate::models::QuotaOverride) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO quotas (chat_id, daily_downloads, daily_bytes, weekly_downloads, weekly_bytes, unlimited)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(chat_id) DO UPDATE SET
+            daily_downloads = excluded.daily_downloads,
+            daily_bytes = excluded.daily_bytes,
+            weekly_downloads = excluded.weekly_downloads,
+            weekly_bytes = excluded.weekly_bytes,
+            unlimited = excluded.unlimited,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(quota.chat_id)
+    .bind(quota.daily_downloads)
+    .bind(quota.daily_bytes)
+    .bind(quota.weekly_downloads)
+    .bind(quota.weekly_bytes)
+    .bind(quota.unlimited)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Drop a chat's quota override so the defaults apply. Returns true if one existed.
+pub async fn delete_quota_override(pool: &SqlitePool, chat_id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM quotas WHERE chat_id = ?")
+        .bind(chat_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Downloads started (failed and cancelled ones excluded) and bytes delivered
+/// by a chat over the last 24 hours and 7 days.
+pub async fn get_quota_usage(pool: &SqlitePool, chat_id: i64) -> Result<crate::quota::QuotaUsage> {
+    let row = sqlx::query(
+        r#"
+        SELECT
+            COUNT(CASE WHEN created_at >= datetime('now', '-1 day') THEN 1 END) AS daily_downloads,
+            COALESCE(SUM(CASE WHEN created_at >= datetime('now', '-1 day') THEN file_size END), 0) AS daily_bytes,
+            COUNT(*) AS weekly_downloads,
+            COALESCE(SUM(file_size), 0) AS weekly_bytes
+        FROM tasks
+        WHERE chat_id = ? AND status NOT IN ('error', 'cancelled')
+          AND created_at >= datetime('now', '-7 days')
+        "#,
+    )
+    .bind(chat_id)
+    .fetch_one(pool)
+    .await?;
+
+    let get = |col: &str| row.get::<i64, _>(col).max(0) as u64;
+    Ok(crate::quota::QuotaUsage {
+        daily_downloads: get("daily_downloads"),
+        daily_bytes: get("daily_bytes"),
+        weekly_downloads: get("weekly_downloads"),
+        weekly_bytes: get("weekly_bytes"),
+    })
+}
+
+/// A chat's quota standing: the QUOTA_* defaults with its override applied,
+/// against its current usage.
+pub async fn get_quota_status(pool: &SqlitePool, chat_id: i64) -> Result<crate::quota::QuotaStatus> {
+    let custom = get_quota_override(pool, chat_id).await?.unwrap_or_default();
+    let limits = crate::quota::QuotaLimits::from_env().with_override(&custom);
+    let usage = get_quota_usage(pool, chat_id).await?;
+
+    Ok(crate::quota::QuotaStatus::new(limits, usage, custom.unlimited))
+}
+
+// ====== SUBSCRIPTIONS ======
+
+/// Subscribe a chat to a playlist/channel URL.
+/// Returns the new subscription id, or None if the chat already follows this URL.
+pub async fn create_subscription(
+    pool: &SqlitePool,
+    chat_id: i64,
+    url: &str,
+    extract_audio: bool,
+) -> Result<Option<i64>> {
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO subscriptions (chat_id, url, extract_audio) VALUES (?, ?, ?)",
+    )
+    .bind(chat_id)
+    .bind(url)
+    .bind(extract_audio)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(result.last_insert_rowid()))
+}
+
+/// Count a chat's subscriptions.
+pub async fn count_user_subscriptions(pool: &SqlitePool, chat_id: i64) -> Result<i64> {
+    let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM subscriptions WHERE chat_id = ?")
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(row.0)
+}
+
+/// List a chat's subscriptions, oldest first.
+pub async fn get_user_subscriptions(
+    pool: &SqlitePool,
+    chat_id: i64,
+) -> Result<Vec<crate::models::Subscription>> {
+    let subs = sqlx::query_as::<_, crate::models::Subscription>(
+        "SELECT * FROM subscriptions WHERE chat_id = ? ORDER BY id ASC",
+    )
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subs)
+}
+
+/// Delete a subscription owned by the given chat. Returns true if it existed.
+pub async fn delete_subscription(pool: &SqlitePool, chat_id: i64, id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM subscriptions WHERE id = ? AND chat_id = ?")
+        .bind(id)
+        .bind(chat_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get subscriptions that have never been checked or were last checked
+/// more than `interval_secs` ago.
+pub async fn get_due_subscriptions(
+    pool: &SqlitePool,
+    interval_secs: i64,
+) -> Result<Vec<crate::models::Subscription>> {
+    let subs = sqlx::query_as::<_, crate::models::Subscription>(
+        r#"
+        SELECT * FROM subscriptions
+        WHERE last_checked_at IS NULL
+           OR last_checked_at <= datetime('now', '-' || ? || ' seconds')
+        ORDER BY last_checked_at ASC
+        "#,
+    )
+    .bind(interval_secs)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subs)
+}
+
+/// Record that a subscription was just checked.
+pub async fn mark_subscription_checked(pool: &SqlitePool, id: i64) -> Result<()> {
+    sqlx::query("UPDATE subscriptions SET last_checked_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// ====== SAVED ITEMS (WATCH LATER) ======
+
+/// Save a URL to a chat's watch-later list.
+/// Returns the new item id, or None if the chat already saved this URL.
+pub async fn create_saved_item(
+    pool: &SqlitePool,
+    chat_id: i64,
+    url: &str,
+    note: Option<&str>,
+) -> Result<Option<i64>> {
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO saved_items (chat_id, url, note) VALUES (?, ?, ?)",
+    )
+    .bind(chat_id)
+    .bind(url)
+    .bind(note)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(result.last_insert_rowid()))
+}
+
+/// Count a chat's saved items.
+pub async fn count_saved_items(pool: &SqlitePool, chat_id: i64) -> Result<i64> {
+    let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM saved_items WHERE chat_id = ?")
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(row.0)
+}
+
+/// List a chat's saved items, newest first.
+pub async fn get_saved_items(
+    pool: &SqlitePool,
+    chat_id: i64,
+) -> Result<Vec<crate::models::SavedItem>> {
+    let items = sqlx::query_as::<_, crate::models::SavedItem>(
+        "SELECT * FROM saved_items WHERE chat_id = ? ORDER BY id DESC",
+    )
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(items)
+}
+
+/// Get one of a chat's saved items.
+pub async fn get_saved_item(
+    pool: &SqlitePool,
+    chat_id: i64,
+    id: i64,
+) -> Result<Option<crate::models::SavedItem>> {
+    let item = sqlx::query_as::<_, crate::models::SavedItem>(
+        "SELECT * FROM saved_items WHERE id = ? AND chat_id = ?",
+    )
+    .bind(id)
+    .bind(chat_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(item)
+}
+
+/// Delete a saved item owned by the given chat. Returns true if it existed.
+pub async fn delete_saved_item(pool: &SqlitePool, chat_id: i64, id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM saved_items WHERE id = ? AND chat_id = ?")
+        .bind(id)
+        .bind(chat_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// ====== GENERIC USER SETTINGS ======
+
+/// Read a stored user setting, or None if the user never set it.
+pub async fn get_user_setting(pool: &SqlitePool, chat_id: i64, key: &str) -> Result<Option<String>> {
+    let row: Option<(String,)> = sqlx::query_as(
+        "SELECT value FROM user_settings WHERE chat_id = ? AND key = ?",
+    )
+    .bind(chat_id)
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| r.0))
+}
+
+/// Read a user setting, falling back to the registry default.
+pub async fn get_user_setting_or_default(pool: &SqlitePool, chat_id: i64, key: &str) -> String {
+    match get_user_setting(pool, chat_id, key).await {
+        Ok(Some(v)) => v,
+        _ => crate::user_settings::default_for(key).unwrap_or_default().to_string(),
+    }
+}
+
+/// All stored settings for a user.
+pub async fn get_user_settings(pool: &SqlitePool, chat_id: i64) -> Result<Vec<(String, String)>> {
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        "SELECT key, value FROM user_settings WHERE chat_id = ? ORDER BY key",
+    )
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Upsert a user setting. Callers validate via `user_settings::validate` first.
+pub async fn set_user_setting(pool: &SqlitePool, chat_id: i64, key: &str, value: &str) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO user_settings (chat_id, key, value) VALUES (?, ?, ?) \
+         ON CONFLICT(chat_id, key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(chat_id)
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// ====== DEDUPLICATION PREFERENCES ======
+
+/// Get user's deduplication preference (default: true/enabled).
+/// Returns true if dedup is enabled, false if disabled, or true if preference not found.
+pub async fn get_user_dedup_preference(pool: &SqlitePool, chat_id: i64) -> Result<bool> {
+    // Try to read dedup preference; default to true if not found or column doesn't exist
+    // Using raw query to avoid sqlx compile-time checking of non-existent columns
+    match sqlx::query("SELECT COALESCE(dedup_enabled, 1) as enabled FROM user_preferences WHERE chat_id = ?")
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await {
+            Ok(Some(row)) => {
+                // try to extract the value; if it fails, default to true
+                match row.try_get::<i64, _>("enabled") {
+                    Ok(val) => Ok(val != 0),
+                    Err(_) => Ok(true), // Column doesn't exist or can't read, default to true
+                }
+            }
+            Ok(None) => Ok(true), // User not found, return default true
+            Err(_) => Ok(true), // Query failed (table/column doesn't exist), return default true
+        }
+}
+
+/// Set user's deduplication preference.
+/// Inserts or updates the user's dedup preference.
+pub async fn set_user_dedup_preference(
+    pool: &SqlitePool,
+    chat_id: i64,
+    dedup_enabled: bool,
+) -> Result<()> {
+    // First ensure user exists in users table
+    sqlx::query(
+        "INSERT OR IGNORE INTO users (chat_id) VALUES (?)"
+    )
+    .bind(chat_id)
+    .execute(pool)
+    .await?;
+
+    // Then ensure user has an entry in user_preferences
     sqlx::query(
         "INSERT OR IGNORE INTO user_preferences (chat_id) VALUES (?)"
     )
@@ -803,6 +1752,36 @@ pub async fn set_config(pool: &SqlitePool, key: &str, value: &str) -> Result<()>
     Ok(())
 }
 
+// ====== WEB QUEUE DRAIN ======
+
+/// Config key prefix for the drain flags, one per instance:
+/// "web_queue_draining:<instance>".
+const WEB_QUEUE_DRAINING_KEY: &str = "web_queue_draining";
+
+/// Name of this instance for the drain flag: `INSTANCE_ID`, else the host
+/// name. A bot and the API deployed with it must resolve the same name.
+pub fn instance_id() -> String {
+    std::env::var("INSTANCE_ID")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Start or stop draining `instance`: while set, that instance's bot stops
+/// claiming web-queued tasks. Other instances sharing the database keep going.
+pub async fn set_web_queue_draining(pool: &SqlitePool, instance: &str, draining: bool) -> Result<()> {
+    let key = format!("{}:{}", WEB_QUEUE_DRAINING_KEY, instance);
+    set_config(pool, &key, if draining { "1" } else { "0" }).await
+}
+
+/// Whether `instance` is currently being drained.
+pub async fn is_web_queue_draining(pool: &SqlitePool, instance: &str) -> Result<bool> {
+    let key = format!("{}:{}", WEB_QUEUE_DRAINING_KEY, instance);
+    Ok(get_config(pool, &key).await?.as_deref() == Some("1"))
+}
+
 // ====== BYPASS TOKEN SESSIONS ======
 
 /// Create a per-user OTP bypass session token.
@@ -921,3 +1900,292 @@ pub async fn update_user_preferences(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Migrated database in a temp file, deleted with its WAL files on drop.
+    struct TestPool {
+        pool: SqlitePool,
+        path: std::path::PathBuf,
+    }
+
+    impl std::ops::Deref for TestPool {
+        type Target = SqlitePool;
+
+        fn deref(&self) -> &SqlitePool {
+            &self.pool
+        }
+    }
+
+    impl Drop for TestPool {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+            }
+        }
+    }
+
+    async fn test_pool(name: &str) -> TestPool {
+        let path = std::env::temp_dir().join(format!("hermes-{}-{}.db", name, std::process::id()));
+        let pool = create_pool(&resolve_database_url(&path.display().to_string())).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        TestPool { pool, path }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_tasks() {
+        let pool = test_pool("scheduled").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+
+        let now = chrono::Utc::now().naive_utc();
+        create_scheduled_task(&pool, "due", 1, "https://a", "youtube_dl", Some("audio"), now - chrono::Duration::minutes(1)).await.unwrap();
+        create_scheduled_task(&pool, "later", 1, "https://b", "youtube_dl", Some("audio"), now + chrono::Duration::hours(1)).await.unwrap();
+
+        assert_eq!(promote_due_scheduled_tasks(&pool).await.unwrap(), 1);
+        assert_eq!(get_task_by_id(&pool, "due").await.unwrap().unwrap().status, "web_queued");
+        let waiting = get_scheduled_tasks(&pool, 1).await.unwrap();
+        assert_eq!(waiting.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), vec!["later"]);
+
+        assert!(cancel_task(&pool, "later").await.unwrap());
+        assert!(get_scheduled_tasks(&pool, 1).await.unwrap().is_empty());
+        assert_eq!(promote_due_scheduled_tasks(&pool).await.unwrap(), 0);
+
+    }
+
+    #[tokio::test]
+    async fn test_user_access_flags() {
+        let pool = test_pool("access").await;
+        upsert_user(&pool, 1, Some("alice")).await.unwrap();
+
+        // Chats can be allowlisted or banned before they've used the bot
+        add_allowed_user(&pool, 2).await.unwrap();
+        assert!(is_allowed_user(&pool, 2).await.unwrap());
+        assert!(set_user_banned(&pool, 1, true).await.unwrap());
+        assert!(!set_user_banned(&pool, 1, true).await.unwrap());
+        assert_eq!(get_user_access(&pool, 1).await.unwrap(), UserAccess { banned: true, allowlisted: false });
+        assert_eq!(list_banned_users(&pool).await.unwrap(), vec![1]);
+        assert_eq!(get_user_access(&pool, 3).await.unwrap(), UserAccess::default());
+
+        // Seeing the user again keeps the flags
+        upsert_user(&pool, 1, None).await.unwrap();
+        assert!(is_banned_user(&pool, 1).await.unwrap());
+        assert!(set_user_banned(&pool, 1, false).await.unwrap());
+        assert!(list_banned_users(&pool).await.unwrap().is_empty());
+
+        assert!(remove_allowed_user(&pool, 2).await.unwrap());
+        assert!(!remove_allowed_user(&pool, 2).await.unwrap());
+        assert!(list_allowed_users(&pool).await.unwrap().is_empty());
+
+    }
+
+    #[tokio::test]
+    async fn test_quota_usage_and_override() {
+        let pool = test_pool("quota").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+
+        let file = std::env::temp_dir().join(format!("hermes-quota-{}.bin", std::process::id()));
+        std::fs::write(&file, vec![0u8; 2048]).unwrap();
+        for id in ["done", "failed", "running"] {
+            create_task(&pool, id, 1, "youtube_dl", "https://a", None, Priority::Normal).await.unwrap();
+        }
+        complete_task(&pool, "done", &file.display().to_string()).await.unwrap();
+        fail_task(&pool, "failed", "boom").await.unwrap();
+        sqlx::query("UPDATE tasks SET created_at = datetime('now', '-3 days') WHERE id = 'running'")
+            .execute(&*pool)
+            .await
+            .unwrap();
+
+        let usage = get_quota_usage(&pool, 1).await.unwrap();
+        assert_eq!((usage.daily_downloads, usage.daily_bytes), (1, 2048));
+        assert_eq!((usage.weekly_downloads, usage.weekly_bytes), (2, 2048));
+
+        assert!(get_quota_override(&pool, 1).await.unwrap().is_none());
+        let custom = crate::models::QuotaOverride { chat_id: 1, daily_downloads: Some(1), ..Default::default() };
+        set_quota_override(&pool, &custom).await.unwrap();
+        let status = get_quota_status(&pool, 1).await.unwrap();
+        assert_eq!(status.daily.downloads_remaining, Some(0));
+        assert!(status.exceeded().is_some());
+
+        set_quota_override(&pool, &crate::models::QuotaOverride { unlimited: true, ..custom }).await.unwrap();
+        assert!(get_quota_status(&pool, 1).await.unwrap().exceeded().is_none());
+        assert!(delete_quota_override(&pool, 1).await.unwrap());
+        assert!(!delete_quota_override(&pool, 1).await.unwrap());
+
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_url_input_passes_through() {
+        assert_eq!(resolve_database_url("sqlite::memory:"), "sqlite::memory:");
+        assert_eq!(
+            resolve_database_url(" sqlite:///data/hermes.db?mode=ro "),
+            "sqlite:///data/hermes.db?mode=ro"
+        );
+    }
+
+    #[test]
+    fn test_unix_paths() {
+        assert_eq!(normalize_db_path("/opt/hermes/hermes.db"), "/opt/hermes/hermes.db");
+        assert_eq!(
+            resolve_database_url("/nonexistent-dir/hermes.db"),
+            "sqlite:///nonexistent-dir/hermes.db?mode=rwc"
+        );
+    }
+
+    #[test]
+    fn test_relative_path_becomes_absolute() {
+        let url = resolve_database_url("./not-created-yet.db");
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        let expected = normalize_db_path(&cwd.join("not-created-yet.db").display().to_string());
+        assert_eq!(url, format!("sqlite://{}?mode=rwc", expected));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_update_labels_skips_other_users() {
+        let pool = test_pool("bulk-labels").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+        upsert_user(&pool, 2, None).await.unwrap();
+        create_task(&pool, "mine-1", 1, "youtube_dl", "https://a", Some("audio"), Priority::Normal).await.unwrap();
+        create_task(&pool, "mine-2", 1, "youtube_dl", "https://b", None, Priority::Normal).await.unwrap();
+        create_task(&pool, "theirs", 2, "youtube_dl", "https://c", Some("audio"), Priority::Normal).await.unwrap();
+
+        let ids = ["mine-1", "mine-2", "theirs", "missing"].map(String::from);
+        let (updated, skipped) = bulk_update_labels(&pool, 1, &ids, "video").await.unwrap();
+        assert_eq!(updated, 2);
+        assert_eq!(skipped, vec!["theirs".to_string(), "missing".to_string()]);
+        assert_eq!(get_task_by_id(&pool, "mine-2").await.unwrap().unwrap().label.as_deref(), Some("video"));
+        assert_eq!(get_task_by_id(&pool, "theirs").await.unwrap().unwrap().label.as_deref(), Some("audio"));
+
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_among() {
+        let pool = test_pool("cancelled").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+        create_task(&pool, "running", 1, "youtube_dl", "https://a", None, Priority::Normal).await.unwrap();
+        create_task(&pool, "stopped", 1, "youtube_dl", "https://b", None, Priority::Normal).await.unwrap();
+        assert!(cancel_task(&pool, "stopped").await.unwrap());
+
+        let ids = ["running", "stopped", "missing"].map(String::from);
+        assert_eq!(cancelled_among(&pool, &ids).await.unwrap(), vec!["stopped".to_string()]);
+
+    }
+
+    #[tokio::test]
+    async fn test_cached_download_skips_partial_outputs() {
+        let pool = test_pool("cache").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+        for (id, task_type) in [("full", "youtube_dl"), ("subs", "subtitles"), ("clip", "clip"), ("burned", "hardsubs")] {
+            create_task(&pool, id, 1, task_type, "https://a", None, Priority::Normal).await.unwrap();
+            complete_task(&pool, id, &format!("/tmp/{}.bin", id)).await.unwrap();
+        }
+        // The full download is the oldest, so only the type filter keeps the others out
+        sqlx::query("UPDATE tasks SET finished_at = datetime('now', '-1 hour') WHERE id = 'full'")
+            .execute(&*pool)
+            .await
+            .unwrap();
+
+        let (task_id, path, _) = find_cached_download(&pool, "https://a").await.unwrap();
+        assert_eq!((task_id.as_str(), path.as_str()), ("full", "/tmp/full.bin"));
+        assert!(find_cached_download(&pool, "https://b").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requeued_tasks_are_unfinished() {
+        let pool = test_pool("unfinished").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+        create_task(&pool, "running", 1, "youtube_dl", "https://a", Some("audio"), Priority::Normal).await.unwrap();
+        create_task(&pool, "done", 1, "youtube_dl", "https://b", None, Priority::Normal).await.unwrap();
+        update_task_progress(&pool, "running", 40, Some("1MiB/s"), Some(30)).await.unwrap();
+        complete_task(&pool, "done", "/tmp/b.mp3").await.unwrap();
+
+        assert!(requeue_task(&pool, "running").await.unwrap());
+        assert!(!requeue_task(&pool, "done").await.unwrap());
+        let unfinished = get_unfinished_tasks(&pool).await.unwrap();
+        assert_eq!(unfinished.len(), 1);
+        assert_eq!(unfinished[0].id, "running");
+        assert_eq!((unfinished[0].status.as_str(), unfinished[0].progress), ("queued", 0));
+        assert_eq!(unfinished[0].priority, "normal");
+
+        // A retried task comes back at high priority
+        assert!(retry_task(&pool, "done").await.unwrap());
+        assert_eq!(get_task_by_id(&pool, "done").await.unwrap().unwrap().priority, "high");
+
+    }
+
+    #[tokio::test]
+    async fn test_user_tasks_page() {
+        let pool = test_pool("task-page").await;
+        upsert_user(&pool, 1, None).await.unwrap();
+        for (i, id) in ["a", "b", "c", "d"].iter().enumerate() {
+            create_task(&pool, id, 1, "youtube_dl", "https://a", None, Priority::Normal).await.unwrap();
+            sqlx::query("UPDATE tasks SET created_at = datetime('2025-01-01', ?) WHERE id = ?")
+                .bind(format!("+{} minutes", i))
+                .bind(id)
+                .execute(&*pool)
+                .await
+                .unwrap();
+        }
+        // Finished in the opposite order to creation; "d" never finishes
+        for (id, at) in [("a", "12:00"), ("b", "11:00"), ("c", "10:00")] {
+            sqlx::query("UPDATE tasks SET status = 'done', finished_at = ? WHERE id = ?")
+                .bind(format!("2025-01-02 {at}:00"))
+                .bind(id)
+                .execute(&*pool)
+                .await
+                .unwrap();
+        }
+        let ids = |tasks: Vec<crate::models::Task>| tasks.into_iter().map(|t| t.id).collect::<Vec<_>>();
+
+        let (page, total) = get_user_tasks_page(&pool, 1, None, TaskSort::CreatedAt, true, 3, 0).await.unwrap();
+        assert_eq!((ids(page), total), (vec!["d".to_string(), "c".into(), "b".into()], 4));
+        let (page, _) = get_user_tasks_page(&pool, 1, None, TaskSort::CreatedAt, true, 3, 3).await.unwrap();
+        assert_eq!(ids(page), vec!["a"]);
+        let (page, _) = get_user_tasks_page(&pool, 1, None, TaskSort::FinishedAt, false, 10, 0).await.unwrap();
+        assert_eq!(ids(page), vec!["c", "b", "a", "d"]);
+        let (page, total) = get_user_tasks_page(&pool, 1, Some("done"), TaskSort::FinishedAt, true, 10, 0).await.unwrap();
+        assert_eq!((ids(page), total), (vec!["a".to_string(), "b".into(), "c".into()], 3));
+
+        // History leaves out unfinished tasks ("d")
+        let (page, total) = get_user_history_page(&pool, 1, 2, 0).await.unwrap();
+        assert_eq!((ids(page), total), (vec!["a".to_string(), "b".into()], 3));
+        let (page, _) = get_user_history_page(&pool, 1, 2, 2).await.unwrap();
+        assert_eq!(ids(page), vec!["c"]);
+
+    }
+
+    #[tokio::test]
+    async fn test_saved_items() {
+        let pool = test_pool("saved").await;
+
+        let first = create_saved_item(&pool, 1, "https://a", None).await.unwrap().unwrap();
+        let second = create_saved_item(&pool, 1, "https://b", Some("later")).await.unwrap().unwrap();
+        assert_eq!(create_saved_item(&pool, 1, "https://a", None).await.unwrap(), None);
+        create_saved_item(&pool, 2, "https://a", None).await.unwrap().unwrap();
+
+        let items = get_saved_items(&pool, 1).await.unwrap();
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![second, first]);
+        assert_eq!(items[0].note.as_deref(), Some("later"));
+        assert_eq!(count_saved_items(&pool, 1).await.unwrap(), 2);
+
+        // Other chats' items can't be read or removed
+        assert!(get_saved_item(&pool, 2, first).await.unwrap().is_none());
+        assert!(!delete_saved_item(&pool, 2, first).await.unwrap());
+        assert!(delete_saved_item(&pool, 1, first).await.unwrap());
+        assert_eq!(count_saved_items(&pool, 1).await.unwrap(), 1);
+
+    }
+
+    #[test]
+    fn test_windows_paths() {
+        assert_eq!(normalize_db_path(r"\\?\C:\hermes\hermes.db"), "C:/hermes/hermes.db");
+        assert_eq!(normalize_db_path(r"C:\hermes\hermes.db"), "C:/hermes/hermes.db");
+        assert_eq!(
+            normalize_db_path(r"\\?\UNC\server\share\hermes.db"),
+            "//server/share/hermes.db"
+        );
+        assert_eq!(normalize_db_path(r"\\server\share\hermes.db"), "//server/share/hermes.db");
+    }
+}
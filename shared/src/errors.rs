@@ -38,6 +38,9 @@ pub enum IpcError {
     #[error("Failed to write to worker stdin: {0}")]
     WriteFailed(String),
 
+    #[error("Worker stdin full for {0}s, worker appears hung")]
+    StdinFull(u64),
+
     #[error("Failed to read from worker stdout: {0}")]
     ReadFailed(String),
 
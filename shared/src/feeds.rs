@@ -0,0 +1,278 @@
+/// Podcast feeds (`/podcast`, pasted RSS links).
+///
+/// Reads RSS 2.0 (`<item>` with `<enclosure>`) and Atom (`<entry>` with
+/// `<link rel="enclosure">`). Only episodes with an enclosure are kept, in
+/// feed order (newest first in practice). The enclosure itself is fetched by
+/// the bot's native downloader.
+use std::time::Duration;
+
+use chrono::DateTime;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Largest feed read; big back catalogues run to a few MB.
+const MAX_FEED_BYTES: usize = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// One downloadable episode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Episode {
+    pub title: String,
+    /// Media file URL (the enclosure).
+    pub url: String,
+    /// Enclosure MIME type, e.g. `audio/mpeg`.
+    pub mime_type: Option<String>,
+    /// Enclosure size in bytes, when the feed states it.
+    pub size: Option<u64>,
+    /// `pubDate` / `published` as written in the feed.
+    pub published: Option<String>,
+}
+
+impl Episode {
+    /// Publication date as `2024-05-01`, when it can be read.
+    pub fn date_label(&self) -> Option<String> {
+        let raw = self.published.as_deref()?.trim();
+        DateTime::parse_from_rfc2822(raw)
+            .or_else(|_| DateTime::parse_from_rfc3339(raw))
+            .ok()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+    }
+
+    /// File name to save the episode under: the title made path-safe, with
+    /// the extension from the enclosure URL or MIME type.
+    pub fn file_name(&self) -> String {
+        let path = self.url.split(['?', '#']).next().unwrap_or_default();
+        let from_url = path
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_lowercase())
+            .filter(|ext| (2..=4).contains(&ext.len()) && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+        let ext = from_url.unwrap_or_else(|| {
+            match self.mime_type.as_deref() {
+                Some("audio/mp4" | "audio/x-m4a" | "audio/m4a") => "m4a",
+                Some("video/mp4") => "mp4",
+                Some("audio/ogg") => "ogg",
+                _ => "mp3",
+            }
+            .to_string()
+        });
+
+        let stem: String = self
+            .title
+            .chars()
+            .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+            .take(100)
+            .collect();
+        let stem = stem.trim().trim_matches('.');
+        format!("{}.{}", if stem.is_empty() { "episode" } else { stem }, ext)
+    }
+}
+
+/// A parsed feed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Feed {
+    pub title: String,
+    pub episodes: Vec<Episode>,
+}
+
+/// Download and parse the feed at `url`. Errors are user-facing.
+pub async fn fetch_feed(url: &str) -> Result<Feed, String> {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .user_agent(concat!("Hermes/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| format!("Couldn't fetch the feed: {}", e))?;
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Couldn't fetch the feed: {}", e))?;
+    if response.content_length().is_some_and(|n| n as usize > MAX_FEED_BYTES) {
+        return Err("The feed is too large.".to_string());
+    }
+    // Chunked responses have no Content-Length: count while reading
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Couldn't fetch the feed: {}", e))?
+    {
+        if body.len() + chunk.len() > MAX_FEED_BYTES {
+            return Err("The feed is too large.".to_string());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    parse_feed(&String::from_utf8_lossy(&body))
+}
+
+/// Where text inside the current element goes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    FeedTitle,
+    Title,
+    Published,
+    Other,
+}
+
+/// Parse an RSS or Atom document.
+pub fn parse_feed(xml: &str) -> Result<Feed, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut feed = Feed::default();
+    let mut is_feed = false;
+    let mut item: Option<Episode> = None;
+    let mut field = Field::Other;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("This isn't a readable feed ({}).", e))?;
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = e.name();
+                match name.as_ref() {
+                    b"rss" | b"feed" | b"channel" => is_feed = true,
+                    b"item" | b"entry" => {
+                        item = Some(Episode {
+                            title: String::new(),
+                            url: String::new(),
+                            mime_type: None,
+                            size: None,
+                            published: None,
+                        });
+                    }
+                    b"enclosure" => {
+                        if let Some(ep) = item.as_mut() {
+                            read_enclosure(&e, "url", ep);
+                        }
+                    }
+                    b"link" if attr(&e, "rel").as_deref() == Some("enclosure") => {
+                        if let Some(ep) = item.as_mut() {
+                            read_enclosure(&e, "href", ep);
+                        }
+                    }
+                    _ => {}
+                }
+                field = match (name.as_ref(), item.is_some()) {
+                    (b"title", true) => Field::Title,
+                    (b"title", false) if feed.title.is_empty() => Field::FeedTitle,
+                    (b"pubDate" | b"published", true) => Field::Published,
+                    _ => Field::Other,
+                };
+            }
+            Event::Text(t) => {
+                let text = t.unescape().map(|c| c.into_owned()).unwrap_or_default();
+                push_text(field, &text, &mut feed, item.as_mut());
+            }
+            Event::CData(c) => {
+                let text = String::from_utf8_lossy(&c.into_inner()).into_owned();
+                push_text(field, &text, &mut feed, item.as_mut());
+            }
+            Event::End(e) => {
+                if matches!(e.name().as_ref(), b"item" | b"entry") {
+                    if let Some(ep) = item.take().filter(|ep| !ep.url.is_empty()) {
+                        feed.episodes.push(ep);
+                    }
+                }
+                field = Field::Other;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !is_feed {
+        return Err("This doesn't look like a podcast feed.".to_string());
+    }
+    for ep in &mut feed.episodes {
+        if ep.title.trim().is_empty() {
+            ep.title = "Untitled episode".to_string();
+        }
+    }
+    Ok(feed)
+}
+
+fn push_text(field: Field, text: &str, feed: &mut Feed, item: Option<&mut Episode>) {
+    match (field, item) {
+        (Field::FeedTitle, _) => feed.title.push_str(text),
+        (Field::Title, Some(ep)) => ep.title.push_str(text),
+        (Field::Published, Some(ep)) => ep.published = Some(text.to_string()),
+        _ => {}
+    }
+}
+
+fn attr(e: &BytesStart, key: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key.as_bytes())
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+fn read_enclosure(e: &BytesStart, url_key: &str, ep: &mut Episode) {
+    let Some(url) = attr(e, url_key).filter(|u| u.starts_with("http")) else { return };
+    ep.url = url;
+    ep.mime_type = attr(e, "type");
+    ep.size = attr(e, "length").and_then(|l| l.trim().parse().ok()).filter(|&n| n > 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>Tech &amp; Talk</title>
+    <image><title>ignored</title></image>
+    <item>
+      <title><![CDATA[Ep. 2: Rust/async?]]></title>
+      <pubDate>Tue, 30 Apr 2024 08:00:00 +0000</pubDate>
+      <enclosure url="https://cdn.example.com/ep2.m4a?src=rss" type="audio/x-m4a" length="1234"/>
+    </item>
+    <item>
+      <title>Trailer (no audio)</title>
+    </item>
+    <item>
+      <title>Ep. 1</title>
+      <enclosure url="https://cdn.example.com/play/1" type="audio/mpeg" length="0"/>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn test_parse_rss() {
+        let feed = parse_feed(RSS).unwrap();
+        assert_eq!(feed.title, "Tech & Talk");
+        assert_eq!(feed.episodes.len(), 2);
+
+        let ep2 = &feed.episodes[0];
+        assert_eq!(ep2.title, "Ep. 2: Rust/async?");
+        assert_eq!(ep2.url, "https://cdn.example.com/ep2.m4a?src=rss");
+        assert_eq!(ep2.size, Some(1234));
+        assert_eq!(ep2.date_label().as_deref(), Some("2024-04-30"));
+        assert_eq!(ep2.file_name(), "Ep. 2_ Rust_async_.m4a");
+
+        let ep1 = &feed.episodes[1];
+        assert_eq!(ep1.size, None);
+        assert_eq!(ep1.date_label(), None);
+        assert_eq!(ep1.file_name(), "Ep. 1.mp3");
+    }
+
+    #[test]
+    fn test_parse_atom_and_reject_other_xml() {
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Atom Cast</title>
+            <entry><title>First</title><published>2024-05-01T10:00:00Z</published>
+            <link rel="alternate" href="https://example.com/first"/>
+            <link rel="enclosure" href="https://example.com/first.mp3" type="audio/mpeg"/></entry></feed>"#;
+        let feed = parse_feed(atom).unwrap();
+        assert_eq!(feed.title, "Atom Cast");
+        assert_eq!(feed.episodes[0].url, "https://example.com/first.mp3");
+        assert_eq!(feed.episodes[0].date_label().as_deref(), Some("2024-05-01"));
+
+        assert!(parse_feed("<html><body>hi</body></html>").is_err());
+        assert!(parse_feed("<rss><channel><item></rss>").is_err());
+    }
+}
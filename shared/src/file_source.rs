@@ -0,0 +1,127 @@
+/// Where the bytes of a finished download come from.
+///
+/// Workers have always reported results as a `file_path` on a filesystem the
+/// bot shares. A `done` payload may instead carry a typed `file_ref`, and the
+/// bot reads through a [`FileSource`] so it doesn't care how the file gets to
+/// it. Only local files exist today; a worker on another host would add a
+/// variant (streamed over a side channel, or a URL / object-store key) with its
+/// own `FileSource`.
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::io::AsyncRead;
+
+/// A readable stream over a file's contents.
+pub type FileReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Future returned by [`FileSource::open`].
+pub type OpenFuture<'a> = Pin<Box<dyn Future<Output = io::Result<FileReader>> + Send + 'a>>;
+
+/// A file produced by the worker, readable by the bot.
+pub trait FileSource: Send + Sync {
+    /// File name to show the user and send with the upload.
+    fn name(&self) -> &str;
+
+    /// Path on this host, when the file is already here. Lets callers hand
+    /// the path straight to Telegram or link it in place instead of copying.
+    fn local_path(&self) -> Option<&Path>;
+
+    /// Open the file for reading.
+    fn open(&self) -> OpenFuture<'_>;
+}
+
+/// A file on the filesystem shared with the worker.
+#[derive(Debug, Clone)]
+pub struct LocalFile {
+    path: PathBuf,
+    name: String,
+}
+
+impl LocalFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "download".to_string());
+        Self { path, name }
+    }
+}
+
+impl FileSource for LocalFile {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn open(&self) -> OpenFuture<'_> {
+        Box::pin(async move {
+            let file = tokio::fs::File::open(&self.path).await?;
+            Ok(Box::new(file) as FileReader)
+        })
+    }
+}
+
+/// A file reference as it travels in a worker `done` payload:
+/// `"file_ref": {"kind": "local", "path": "/downloads/..."}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileRef {
+    /// A path on the filesystem shared by worker and bot.
+    Local { path: String },
+}
+
+impl FileRef {
+    /// Read the reference from a `done` payload: `file_ref` if present,
+    /// otherwise the older bare `file_path`. None if neither is usable.
+    pub fn from_data(data: &serde_json::Value) -> Option<Self> {
+        if let Some(file_ref) = data.get("file_ref") {
+            return serde_json::from_value(file_ref.clone()).ok();
+        }
+        data.get("file_path")
+            .and_then(|v| v.as_str())
+            .filter(|p| !p.is_empty())
+            .map(|path| FileRef::Local { path: path.to_string() })
+    }
+
+    /// The source to read this file through.
+    pub fn source(&self) -> Box<dyn FileSource> {
+        match self {
+            FileRef::Local { path } => Box::new(LocalFile::new(path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn reads_local_refs_old_and_new() {
+        let path = std::env::temp_dir().join(format!("hermes-file-source-{}.mp3", std::process::id()));
+        std::fs::write(&path, b"ID3").unwrap();
+        let path_str = path.to_string_lossy().to_string();
+
+        let legacy = FileRef::from_data(&json!({"file_path": path_str})).unwrap();
+        let tagged = FileRef::from_data(&json!({"file_ref": {"kind": "local", "path": path_str}})).unwrap();
+        assert_eq!(legacy, tagged);
+        assert_eq!(FileRef::from_data(&json!({"file_path": ""})), None);
+        assert_eq!(FileRef::from_data(&json!({"file_ref": {"kind": "carrier_pigeon"}})), None);
+
+        let source = tagged.source();
+        assert_eq!(source.local_path(), Some(path.as_path()));
+        assert!(source.name().starts_with("hermes-file-source-"));
+        let mut bytes = Vec::new();
+        source.open().await.unwrap().read_to_end(&mut bytes).await.unwrap();
+        assert_eq!(bytes, b"ID3");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,8 +1,13 @@
 /// IPC Protocol types for Rust <-> Python worker communication.
 ///
 /// Messages are newline-delimited JSON on stdin/stdout of the Python subprocess.
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::models::UserPreferences;
+use crate::search_filters::SearchFilters;
+
 // ====== REQUEST (Rust -> Python) ======
 
 /// Request sent from Rust bot to Python worker via stdin.
@@ -24,12 +29,24 @@ pub enum IPCAction {
     YoutubeSearch,
     GetVideoInfo,
     GetFormats,
+    Probe,            // Fast "can yt-dlp handle this URL?" check
+    SpotifyResolve,   // Look up a Spotify track and find the matching YouTube video
+    GetThumbnail,     // Save the largest thumbnail as a JPEG
     Playlist,
     PlaylistPreview,  // Preview first N tracks without downloading
+    ChannelLatest,    // Ids/titles of a channel's (or playlist's) newest uploads, no download
+    Concat,           // Join downloaded playlist tracks into one file
+    Transcribe,       // Speech-to-text of a downloaded audio file
+    ExtractAudio,     // Audio track of an already-downloaded file
+    Compress,         // Re-encode a downloaded video to fit a target size
+    SplitMedia,       // Cut a downloaded audio/video file into parts under a size
+    DownloadSubtitles, // Fetch a video's subtitle track as .srt/.vtt, no media
     CacheCleanup,
     CacheStats,
     HealthCheck,
     MtprotoUpload,    // Upload large file to storage channel via MTProto
+    MtprotoCopyPost,  // Join a public channel via MTProto and copy a post to storage
+    Cancel,           // Stop the in-flight (or queued) request with this task_id
 }
 
 impl std::fmt::Display for IPCAction {
@@ -63,12 +80,96 @@ impl IPCRequest {
         self
     }
 
+    /// Set a single param, keeping the ones already present.
+    pub fn with_param(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        if !self.params.is_object() {
+            self.params = serde_json::Value::Object(serde_json::Map::new());
+        }
+        self.params[key] = value.into();
+        self
+    }
+
+    /// Attach a custom User-Agent and extra headers (`user_agent`, `http_headers`
+    /// params). Empty options leave the request unchanged.
+    pub fn with_http_options(mut self, options: &HttpOptions) -> Self {
+        if let Some(ua) = &options.user_agent {
+            self = self.with_param("user_agent", ua.as_str());
+        }
+        if !options.http_headers.is_empty() {
+            self = self.with_param("http_headers", serde_json::json!(options.http_headers));
+        }
+        self
+    }
+
     /// Serialize to a single JSON line (for stdin).
     pub fn to_json_line(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
     }
 }
 
+// ====== HTTP OPTIONS ======
+
+/// Custom User-Agent and extra HTTP headers for sites that block yt-dlp's
+/// default client. The worker passes them as `--user-agent` / `--add-header`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpOptions {
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub http_headers: BTreeMap<String, String>,
+}
+
+impl HttpOptions {
+    pub fn is_empty(&self) -> bool {
+        self.user_agent.is_none() && self.http_headers.is_empty()
+    }
+
+    /// Header names must be HTTP tokens; no value may contain control characters
+    /// (which would let a value smuggle in extra header lines).
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ua) = &self.user_agent {
+            if ua.trim().is_empty() || ua.chars().any(|c| c.is_control()) {
+                return Err("user agent must be non-empty and free of control characters".to_string());
+            }
+        }
+        for (name, value) in &self.http_headers {
+            let is_token = !name.is_empty()
+                && name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+            if !is_token {
+                return Err(format!("invalid header name: {:?}", name));
+            }
+            if value.chars().any(|c| c.is_control()) {
+                return Err(format!("header {} has control characters in its value", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Layer `other` on top of these options; its user agent and headers win.
+    /// Header names compare case-insensitively.
+    pub fn merge(&mut self, other: &HttpOptions) {
+        if other.user_agent.is_some() {
+            self.user_agent = other.user_agent.clone();
+        }
+        for (name, value) in &other.http_headers {
+            self.http_headers.retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+            self.http_headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Parse "Name: value | Name2: value2" into a header map.
+pub fn parse_header_list(list: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut headers = BTreeMap::new();
+    for entry in list.split(" | ").map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, value) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("expected \"Name: value\", got {:?}", entry))?;
+        headers.insert(name.trim().to_string(), value.trim().to_string());
+    }
+    Ok(headers)
+}
+
 // ====== RESPONSE (Python -> Rust) ======
 
 /// Response received from Python worker via stdout.
@@ -93,7 +194,12 @@ pub enum IPCEvent {
     HealthOk,
     CacheStats,
     CacheCleanupDone,
+    /// The worker hit a transient failure and is trying again. Not terminal:
+    /// the task still ends with `Done` or `Error`. Data: `attempt` (the one
+    /// about to start), optional `max_attempts` and `reason`.
     Retry,
+    ProbeResult,
+    SpotifyTrack,
 }
 
 impl IPCResponse {
@@ -122,6 +228,35 @@ impl IPCResponse {
         self.event == IPCEvent::FormatList
     }
 
+    /// Check if this is a (non-terminal) retry notice.
+    pub fn is_retry(&self) -> bool {
+        self.event == IPCEvent::Retry
+    }
+
+    /// Check if this is an intermediate event (progress or retry) rather than
+    /// the final response.
+    pub fn is_interim(&self) -> bool {
+        self.is_progress() || self.is_retry()
+    }
+
+    /// Extract the retry attempt number and the optional attempt limit.
+    pub fn retry_attempt(&self) -> Option<(u32, Option<u32>)> {
+        if !self.is_retry() {
+            return None;
+        }
+        let attempt = self.data.get("attempt").and_then(|v| v.as_u64()).unwrap_or(2) as u32;
+        let max = self.data.get("max_attempts").and_then(|v| v.as_u64()).map(|v| v as u32);
+        Some((attempt, max))
+    }
+
+    /// Extract the reason for a retry, if the worker gave one.
+    pub fn retry_reason(&self) -> Option<String> {
+        if !self.is_retry() {
+            return None;
+        }
+        self.data.get("reason").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(String::from)
+    }
+
     /// Extract error message if this is an error event.
     pub fn error_message(&self) -> Option<String> {
         if self.is_error() {
@@ -140,25 +275,135 @@ impl IPCResponse {
         }
     }
 
+    /// The file a response points at (`file_ref`, or the older `file_path`).
+    pub fn file_ref(&self) -> Option<crate::file_source::FileRef> {
+        crate::file_source::FileRef::from_data(&self.data)
+    }
+
     /// Extract progress percentage.
     pub fn progress_percent(&self) -> Option<u8> {
         self.data.get("percent").and_then(|v| v.as_u64()).map(|v| v.min(100) as u8)
     }
 
+    /// Extract ETA in seconds. The worker sends 0 when unknown.
+    pub fn progress_eta(&self) -> Option<u64> {
+        self.data.get("eta").and_then(|v| v.as_u64()).filter(|&v| v > 0)
+    }
+
     /// Extract download speed string.
     pub fn progress_speed(&self) -> Option<String> {
         self.data.get("speed").and_then(|v| v.as_str()).map(String::from)
     }
+
+    /// Parse a probe result, if this is one.
+    pub fn probe_result(&self) -> Option<ProbeResult> {
+        if self.event != IPCEvent::ProbeResult {
+            return None;
+        }
+        serde_json::from_value(self.data.clone()).ok()
+    }
+
+    /// Parse a resolved Spotify track, if this is one.
+    pub fn spotify_track(&self) -> Option<SpotifyTrack> {
+        if self.event != IPCEvent::SpotifyTrack {
+            return None;
+        }
+        serde_json::from_value(self.data.clone()).ok()
+    }
+
+    /// Tags of a finished audio download, for Telegram's music player.
+    /// `None` unless this is a `done` event carrying at least one of them.
+    pub fn audio_metadata(&self) -> Option<AudioMetadata> {
+        if self.event != IPCEvent::Done {
+            return None;
+        }
+        let meta: AudioMetadata = serde_json::from_value(self.data.clone()).ok()?;
+        (meta != AudioMetadata::default()).then_some(meta)
+    }
+
+    /// What yt-dlp reported about a finished download (`media` in `done`),
+    /// used for captions. `None` if the worker sent none.
+    pub fn media_info(&self) -> Option<MediaInfo> {
+        if self.event != IPCEvent::Done {
+            return None;
+        }
+        serde_json::from_value(self.data.get("media")?.clone()).ok()
+    }
+}
+
+/// Source details of a downloaded video or track, from yt-dlp.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MediaInfo {
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Channel or account that posted it.
+    #[serde(default)]
+    pub uploader: Option<String>,
+    /// Artist, for music.
+    #[serde(default)]
+    pub artist: Option<String>,
+    /// Length in whole seconds.
+    #[serde(default)]
+    pub duration: Option<u32>,
+}
+
+/// Metadata the worker embedded into a downloaded audio file (`embed_metadata`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AudioMetadata {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub performer: Option<String>,
+    /// Length in whole seconds.
+    #[serde(default)]
+    pub duration: Option<u32>,
+    /// JPEG cover scaled for Telegram (at most 320px, under 200 kB).
+    #[serde(default)]
+    pub thumbnail_path: Option<String>,
+}
+
+/// Outcome of a `Probe` request.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub downloadable: bool,
+    /// yt-dlp extractor name (e.g. "Youtube", "Vimeo").
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub is_playlist: bool,
+    /// Why the URL can't be downloaded (only when `downloadable` is false).
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Outcome of a `SpotifyResolve` request.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpotifyTrack {
+    pub title: String,
+    pub artist: String,
+    /// Track length from Spotify, in seconds.
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// Best-matching YouTube video, if the search found one.
+    #[serde(default)]
+    pub youtube_url: Option<String>,
+    #[serde(default)]
+    pub youtube_title: Option<String>,
 }
 
 // ====== CONVENIENCE BUILDERS ======
 
-/// Build a YouTube search request.
-pub fn search_request(task_id: &str, query: &str, limit: u32) -> IPCRequest {
+/// Build a YouTube search request for `limit` results, skipping the first
+/// `offset` (the next page of a search already shown), narrowed by `filters`.
+pub fn search_request(task_id: &str, query: &str, limit: u32, offset: u32, filters: &SearchFilters) -> IPCRequest {
     IPCRequest::new(task_id, IPCAction::YoutubeSearch)
         .with_params(serde_json::json!({
             "query": query,
             "limit": limit,
+            "offset": offset,
+            "filters": filters,
         }))
 }
 
@@ -170,27 +415,66 @@ pub fn download_request(
     output_dir: &str,
     user_chat_id: i64,
 ) -> IPCRequest {
-    download_request_prefs(task_id, url, extract_audio, "mp3", "0", output_dir, user_chat_id)
+    download_request_prefs(task_id, url, extract_audio, &UserPreferences::default(), output_dir, user_chat_id)
 }
 
-/// Build a YouTube download request with user audio preferences.
+/// Build a YouTube download request with the user's preferences: audio format
+/// and quality, and for video the height cap from `video_quality`.
 pub fn download_request_prefs(
     task_id: &str,
     url: &str,
     extract_audio: bool,
-    audio_format: &str,
-    audio_quality: &str,
+    prefs: &UserPreferences,
     output_dir: &str,
     user_chat_id: i64,
 ) -> IPCRequest {
-    IPCRequest::new(task_id, IPCAction::YoutubeDl)
+    let request = IPCRequest::new(task_id, IPCAction::YoutubeDl)
         .with_url(url)
         .with_params(serde_json::json!({
             "extract_audio": extract_audio,
-            "audio_format": audio_format,
-            "audio_quality": audio_quality,
+            "audio_format": prefs.audio_format,
+            "audio_quality": prefs.audio_quality,
             "output_dir": output_dir,
             "user_chat_id": user_chat_id,
+        }));
+    match prefs.video_format().filter(|_| !extract_audio) {
+        Some(format) => request.with_param("format", format),
+        None => request,
+    }
+}
+
+/// Build a video download request with `lang` subtitles burned into the frames.
+/// The worker re-encodes the whole video, so this takes far longer than a plain download.
+pub fn hardsubs_request(task_id: &str, url: &str, lang: &str, output_dir: &str, user_chat_id: i64) -> IPCRequest {
+    download_request(task_id, url, false, output_dir, user_chat_id)
+        .with_param("burn_subtitles", lang)
+}
+
+/// Build a download request for only the `start`..`end` seconds of the video
+/// (yt-dlp `--download-sections`), for /clip.
+pub fn clip_request(
+    task_id: &str,
+    url: &str,
+    extract_audio: bool,
+    start: u64,
+    end: u64,
+    output_dir: &str,
+    user_chat_id: i64,
+) -> IPCRequest {
+    download_request(task_id, url, extract_audio, output_dir, user_chat_id)
+        .with_param("section_start", start)
+        .with_param("section_end", end)
+}
+
+/// Build a subtitles-only request: fetch the `lang` track (manual, else
+/// automatic) of the video at `url` as `format` ("srt" or "vtt").
+pub fn subtitles_request(task_id: &str, url: &str, lang: &str, format: &str, output_dir: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::DownloadSubtitles)
+        .with_url(url)
+        .with_params(serde_json::json!({
+            "language": lang,
+            "format": format,
+            "output_dir": output_dir,
         }))
 }
 
@@ -209,6 +493,7 @@ pub fn playlist_request(task_id: &str, url: &str, output_dir: &str, user_chat_id
 
 /// Build a playlist download request with user-chosen options.
 /// `max_items = None` means all tracks; `extract_audio = false` means video.
+#[allow(clippy::too_many_arguments)]
 pub fn playlist_request_opts(
     task_id: &str,
     url: &str,
@@ -242,6 +527,83 @@ pub fn playlist_request_opts(
         .with_params(params)
 }
 
+/// Build a concat request: join `files` (in order) into one audio or video file
+/// named after `title`, written under `output_dir`.
+pub fn concat_request(
+    task_id: &str,
+    files: &[String],
+    extract_audio: bool,
+    title: &str,
+    output_dir: &str,
+) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::Concat)
+        .with_params(serde_json::json!({
+            "files": files,
+            "extract_audio": extract_audio,
+            "title": title,
+            "output_dir": output_dir,
+        }))
+}
+
+/// Build a transcribe request: run speech-to-text over the already-downloaded
+/// `file` and write the transcript under `output_dir`. `language` is an ISO
+/// 639-1 code, or "auto" to let the model detect it.
+pub fn transcribe_request(
+    task_id: &str,
+    file: &str,
+    language: &str,
+    title: &str,
+    output_dir: &str,
+) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::Transcribe)
+        .with_params(serde_json::json!({
+            "file": file,
+            "language": language,
+            "title": title,
+            "output_dir": output_dir,
+        }))
+}
+
+/// Build an extract-audio request: convert the audio track of the local `file`
+/// to `audio_format`, written next to it.
+pub fn extract_audio_request(task_id: &str, file: &str, audio_format: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::ExtractAudio)
+        .with_params(serde_json::json!({
+            "file": file,
+            "audio_format": audio_format,
+        }))
+}
+
+/// Build a request to re-encode a downloaded video to at most `target_size` bytes.
+pub fn compress_request(task_id: &str, file: &str, target_size: u64, output_dir: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::Compress)
+        .with_params(serde_json::json!({
+            "file": file,
+            "target_size": target_size,
+            "output_dir": output_dir,
+        }))
+}
+
+/// Build a request to cut a downloaded audio/video file into parts of at most
+/// `part_size` bytes (ffmpeg segments, no re-encode).
+pub fn split_media_request(task_id: &str, file: &str, part_size: u64, output_dir: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::SplitMedia)
+        .with_params(serde_json::json!({
+            "file": file,
+            "part_size": part_size,
+            "output_dir": output_dir,
+        }))
+}
+
+/// Build a request listing the `count` newest uploads of a channel or playlist.
+pub fn channel_latest_request(task_id: &str, url: &str, count: u32) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::ChannelLatest)
+        .with_url(url)
+        .with_params(serde_json::json!({
+            "count": count,
+        }))
+}
+
 /// Build a playlist preview request (list first N tracks without downloading).
 pub fn playlist_preview_request(
     task_id: &str,
@@ -260,12 +622,40 @@ pub fn health_check_request(task_id: &str) -> IPCRequest {
     IPCRequest::new(task_id, IPCAction::HealthCheck)
 }
 
+/// Build a cancel request for `task_id`. The worker kills whatever it is
+/// running for that task (or skips it if still queued) and answers with a
+/// `CANCELLED` error under the same task_id.
+pub fn cancel_request(task_id: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::Cancel)
+}
+
 /// Build a video info request.
 pub fn video_info_request(task_id: &str, url: &str) -> IPCRequest {
     IPCRequest::new(task_id, IPCAction::GetVideoInfo)
         .with_url(url)
 }
 
+/// Build a probe request (is this URL downloadable at all?).
+pub fn probe_request(task_id: &str, url: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::Probe)
+        .with_url(url)
+}
+
+/// Build a Spotify lookup request (track title/artist plus a YouTube match).
+pub fn spotify_resolve_request(task_id: &str, url: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::SpotifyResolve)
+        .with_url(url)
+}
+
+/// Build a thumbnail request (largest available image, saved under `output_dir`).
+pub fn thumbnail_request(task_id: &str, url: &str, output_dir: &str) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::GetThumbnail)
+        .with_url(url)
+        .with_params(serde_json::json!({
+            "output_dir": output_dir,
+        }))
+}
+
 /// Build a get_formats request (for quality selection menus).
 pub fn get_formats_request(task_id: &str, url: &str, mode: &str) -> IPCRequest {
     IPCRequest::new(task_id, IPCAction::GetFormats)
@@ -276,6 +666,7 @@ pub fn get_formats_request(task_id: &str, url: &str, mode: &str) -> IPCRequest {
 }
 
 /// Build a download request with a specific format selection.
+#[allow(clippy::too_many_arguments)]
 pub fn download_request_with_format(
     task_id: &str,
     url: &str,
@@ -318,16 +709,94 @@ pub fn mtproto_upload_request(
         }))
 }
 
+/// Build an MTProto channel copy request (join a public channel, copy a post to storage).
+pub fn mtproto_copy_post_request(
+    task_id:     &str,
+    username:    &str,
+    message_id:  i32,
+    leave_after: bool,
+) -> IPCRequest {
+    IPCRequest::new(task_id, IPCAction::MtprotoCopyPost)
+        .with_params(serde_json::json!({
+            "username":    username,
+            "message_id":  message_id,
+            "leave_after": leave_after,
+        }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_request_serialization() {
-        let req = search_request("task-1", "lo-fi beats", 5);
+        let filters = SearchFilters { duration: Some("long"), ..Default::default() };
+        let req = search_request("task-1", "lo-fi beats", 5, 10, &filters);
         let json = req.to_json_line().unwrap();
         assert!(json.contains("youtube_search"));
         assert!(json.contains("lo-fi beats"));
+        assert_eq!(req.params["offset"], 10);
+        assert_eq!(req.params["filters"], serde_json::json!({"duration": "long"}));
+    }
+
+    #[test]
+    fn test_cancel_request() {
+        let json = cancel_request("task-1").to_json_line().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["action"], "cancel");
+        assert_eq!(value["task_id"], "task-1");
+    }
+
+    #[test]
+    fn test_with_param_keeps_existing_params() {
+        let req = download_request("task-1", "https://youtu.be/x", true, "/tmp", 1)
+            .with_param("use_cookies", true);
+        assert_eq!(req.params["use_cookies"], serde_json::json!(true));
+        assert_eq!(req.params["extract_audio"], serde_json::json!(true));
+        assert_eq!(req.params["output_dir"], serde_json::json!("/tmp"));
+    }
+
+    #[test]
+    fn test_download_request_prefs() {
+        let mut prefs = UserPreferences { audio_format: "opus".to_string(), ..Default::default() };
+        let req = download_request_prefs("task-1", "https://youtu.be/x", false, &prefs, "/tmp", 1);
+        assert_eq!(req.params["audio_format"], serde_json::json!("opus"));
+        assert!(req.params.get("format").is_none());
+
+        prefs.video_quality = "720".to_string();
+        let req = download_request_prefs("task-1", "https://youtu.be/x", false, &prefs, "/tmp", 1);
+        assert!(req.params["format"].as_str().unwrap().starts_with("bestvideo[height<=720]"));
+        // Audio downloads ignore the video cap
+        let req = download_request_prefs("task-1", "https://youtu.be/x", true, &prefs, "/tmp", 1);
+        assert!(req.params.get("format").is_none());
+    }
+
+    #[test]
+    fn test_http_options() {
+        let mut options = HttpOptions {
+            user_agent: Some("Mozilla/5.0".into()),
+            http_headers: parse_header_list("Referer: https://vimeo.com/ | X-Test: a:b").unwrap(),
+        };
+        assert!(options.validate().is_ok());
+        assert_eq!(options.http_headers["X-Test"], "a:b");
+
+        options.merge(&HttpOptions {
+            user_agent: None,
+            http_headers: BTreeMap::from([("referer".to_string(), "https://example.com/".to_string())]),
+        });
+        assert_eq!(options.user_agent.as_deref(), Some("Mozilla/5.0"));
+        assert_eq!(options.http_headers.len(), 2);
+        assert_eq!(options.http_headers["referer"], "https://example.com/");
+
+        let req = download_request("t1", "https://vimeo.com/1", false, "/tmp", 1).with_http_options(&options);
+        assert_eq!(req.params["user_agent"], "Mozilla/5.0");
+        assert_eq!(req.params["http_headers"]["X-Test"], "a:b");
+
+        let bad_name = HttpOptions { user_agent: None, http_headers: parse_header_list("Bad Name: x").unwrap() };
+        assert!(bad_name.validate().is_err());
+        let injected = HttpOptions { user_agent: Some("ua\r\nX-Evil: 1".into()), http_headers: BTreeMap::new() };
+        assert!(injected.validate().is_err());
+        assert!(parse_header_list("no colon here").is_err());
     }
 
     #[test]
@@ -339,6 +808,73 @@ mod tests {
         assert_eq!(resp.progress_percent(), Some(42));
     }
 
+    #[test]
+    fn test_retry_event() {
+        let json = r#"{"task_id":"t2","event":"retry","data":{"attempt":2,"max_attempts":3,"reason":"HTTP 503"}}"#;
+        let resp = IPCResponse::from_json_line(json).unwrap();
+        assert!(resp.is_retry());
+        assert!(resp.is_interim());
+        assert!(!resp.is_done() && !resp.is_error());
+        assert_eq!(resp.retry_attempt(), Some((2, Some(3))));
+        assert_eq!(resp.retry_reason().as_deref(), Some("HTTP 503"));
+
+        let bare = IPCResponse::from_json_line(r#"{"task_id":"t2","event":"retry"}"#).unwrap();
+        assert_eq!(bare.retry_attempt(), Some((2, None)));
+        assert_eq!(bare.retry_reason(), None);
+
+        let done = IPCResponse::from_json_line(r#"{"task_id":"t2","event":"done","data":{}}"#).unwrap();
+        assert!(!done.is_interim());
+        assert_eq!(done.retry_attempt(), None);
+    }
+
+    #[test]
+    fn test_probe_result() {
+        let json = r#"{"task_id":"t3","event":"probe_result","data":{"downloadable":true,"platform":"Vimeo","title":"Clip","is_playlist":false}}"#;
+        let probe = IPCResponse::from_json_line(json).unwrap().probe_result().unwrap();
+        assert!(probe.downloadable);
+        assert_eq!(probe.platform.as_deref(), Some("Vimeo"));
+        assert!(probe.reason.is_none());
+
+        let json = r#"{"task_id":"t3","event":"done","data":{"downloadable":true}}"#;
+        assert!(IPCResponse::from_json_line(json).unwrap().probe_result().is_none());
+    }
+
+    #[test]
+    fn test_spotify_track() {
+        let json = r#"{"task_id":"t4","event":"spotify_track","data":{"title":"Song","artist":"Band","duration":215,"youtube_url":"https://www.youtube.com/watch?v=dQw4w9WgXcQ"}}"#;
+        let track = IPCResponse::from_json_line(json).unwrap().spotify_track().unwrap();
+        assert_eq!((track.title.as_str(), track.artist.as_str()), ("Song", "Band"));
+        assert_eq!(track.duration, Some(215));
+        assert!(track.youtube_url.is_some());
+        assert!(track.youtube_title.is_none());
+
+        assert_eq!(serde_json::to_value(IPCAction::SpotifyResolve).unwrap(), "spotify_resolve");
+    }
+
+    #[test]
+    fn test_audio_metadata() {
+        let json = r#"{"task_id":"t5","event":"done","data":{"file_path":"/d/a.mp3","title":"Song","performer":"Band","duration":215,"thumbnail_path":"/d/a.thumb.jpg"}}"#;
+        let meta = IPCResponse::from_json_line(json).unwrap().audio_metadata().unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Song"));
+        assert_eq!(meta.performer.as_deref(), Some("Band"));
+        assert_eq!(meta.duration, Some(215));
+        assert_eq!(meta.thumbnail_path.as_deref(), Some("/d/a.thumb.jpg"));
+
+        let json = r#"{"task_id":"t5","event":"done","data":{"file_path":"/d/v.mp4"}}"#;
+        assert!(IPCResponse::from_json_line(json).unwrap().audio_metadata().is_none());
+        assert!(IPCResponse::from_json_line(json).unwrap().media_info().is_none());
+
+        let json = r#"{"task_id":"t5","event":"done","data":{"file_path":"/d/v.mp4","media":{"title":"Talk","uploader":"Chan","duration":61}}}"#;
+        let resp = IPCResponse::from_json_line(json).unwrap();
+        assert!(resp.audio_metadata().is_none());
+        assert_eq!(resp.media_info(), Some(MediaInfo {
+            title: Some("Talk".into()),
+            uploader: Some("Chan".into()),
+            artist: None,
+            duration: Some(61),
+        }));
+    }
+
     #[test]
     fn test_error_response() {
         let json = r#"{"task_id":"t2","event":"error","data":{"message":"Video private","error_code":"VIDEO_PRIVATE"}}"#;
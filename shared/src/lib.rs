@@ -3,3 +3,13 @@ pub mod models;
 pub mod db;
 pub mod task_queue;
 pub mod errors;
+pub mod user_settings;
+pub mod schedule;
+pub mod search_filters;
+pub mod quota;
+pub mod url_canon;
+pub mod safe_path;
+pub mod storage_dirs;
+pub mod file_source;
+pub mod feeds;
+pub mod caption;
@@ -11,6 +11,10 @@ pub struct User {
     pub first_seen: NaiveDateTime,
     pub is_admin: bool,
     pub last_activity: NaiveDateTime,
+    /// Blocked from the bot and the download API.
+    pub is_banned: bool,
+    /// On the DB allowlist (only consulted in allowlist mode).
+    pub allowlisted: bool,
 }
 
 /// Download task status.
@@ -59,6 +63,9 @@ impl std::fmt::Display for TaskType {
     }
 }
 
+/// Labels a task can carry (what the dashboard files it under).
+pub const TASK_LABELS: &[&str] = &["audio", "video"];
+
 /// Download task record.
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Task {
@@ -76,6 +83,20 @@ pub struct Task {
     pub finished_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub error_msg: Option<String>,
+    /// Set for tasks created together by a web batch download.
+    pub batch_id: Option<String>,
+    /// Last reported download speed while running (e.g. "1.2MiB/s").
+    pub speed: Option<String>,
+    /// Last smoothed ETA in seconds while running.
+    pub eta_secs: Option<i64>,
+    /// Queue priority: "low", "normal" or "high".
+    pub priority: String,
+    /// Size of the delivered file in bytes, once done.
+    pub file_size: Option<i64>,
+    /// Worker request (IPCRequest JSON) a youtube_dl task was started with,
+    /// replayed when it is resumed after a restart.
+    #[serde(default, skip_serializing)]
+    pub ipc_request: Option<String>,
 }
 
 /// Media task record (enhanced).
@@ -158,7 +179,28 @@ pub struct ArchiveInfo {
     pub path: String,
 }
 
-/// User download preferences (Settings page).
+/// Per-user quota override (`quotas` table). `None` keeps the QUOTA_* default,
+/// `Some(0)` lifts that limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct QuotaOverride {
+    pub chat_id: i64,
+    pub daily_downloads: Option<i64>,
+    pub daily_bytes: Option<i64>,
+    pub weekly_downloads: Option<i64>,
+    pub weekly_bytes: Option<i64>,
+    /// Exempt from all quotas.
+    pub unlimited: bool,
+}
+
+/// Accepted `UserPreferences` values, in the order /settings cycles through them.
+pub const AUDIO_FORMATS: &[&str] = &["mp3", "m4a", "opus", "flac"];
+/// "0" is the best VBR quality; the others are kbps.
+pub const AUDIO_QUALITIES: &[&str] = &["0", "128", "192", "256", "320"];
+pub const DOWNLOAD_MODES: &[&str] = &["audio", "video"];
+/// "best" keeps the worker's default (best up to 1080p); the others cap the height.
+pub const VIDEO_QUALITIES: &[&str] = &["best", "1080", "720", "480"];
+
+/// User download preferences (Settings page, /settings).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPreferences {
     pub audio_format: String,
@@ -179,3 +221,62 @@ impl Default for UserPreferences {
         }
     }
 }
+
+impl UserPreferences {
+    /// yt-dlp format for video downloads at `video_quality`, or `None` for
+    /// "best" (the worker's default).
+    pub fn video_format(&self) -> Option<String> {
+        let height: u32 = self.video_quality.parse().ok()?;
+        Some(format!(
+            "bestvideo[height<={h}][ext=mp4]+bestaudio[ext=m4a]/bestvideo[height<={h}]+bestaudio/best[height<={h}]/best",
+            h = height
+        ))
+    }
+}
+
+/// Persisted state of a multi-link Telegram forward batch.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ForwardBatch {
+    pub id: String,
+    pub chat_id: i64,
+    pub links_json: String,
+    pub next_index: i64,
+    pub success_count: i64,
+    pub failed_count: i64,
+    pub status_msg_id: Option<i64>,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ForwardBatch {
+    /// Decode the stored link list (empty if the JSON is malformed).
+    pub fn links(&self) -> Vec<String> {
+        serde_json::from_str(&self.links_json).unwrap_or_default()
+    }
+}
+
+/// Saved items kept per chat (/save and `POST /api/saved`).
+pub const MAX_SAVED_ITEMS: i64 = 25;
+
+/// A link on a chat's watch-later list.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SavedItem {
+    pub id: i64,
+    pub chat_id: i64,
+    pub url: String,
+    /// Free text the user added when saving.
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A recurring playlist/channel subscription.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Subscription {
+    pub id: i64,
+    pub chat_id: i64,
+    pub url: String,
+    pub extract_audio: bool,
+    pub last_checked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,196 @@
+/// Per-user download quotas (`/quota`, `GET /api/user/quota`).
+///
+/// Limits cover rolling windows of the last 24 hours and the last 7 days. Each
+/// window caps the number of downloads started and the bytes delivered. The
+/// defaults come from QUOTA_DAILY_DOWNLOADS, QUOTA_DAILY_MB,
+/// QUOTA_WEEKLY_DOWNLOADS and QUOTA_WEEKLY_MB (unset or 0 = no limit). A row in
+/// the `quotas` table overrides them for one chat.
+use serde::Serialize;
+
+use crate::models::QuotaOverride;
+
+const MB: u64 = 1024 * 1024;
+
+/// Effective limits for one chat. `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    pub daily_downloads: Option<u64>,
+    pub daily_bytes: Option<u64>,
+    pub weekly_downloads: Option<u64>,
+    pub weekly_bytes: Option<u64>,
+}
+
+impl QuotaLimits {
+    /// Defaults from the QUOTA_* environment variables.
+    pub fn from_env() -> Self {
+        let var = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .filter(|&n| n > 0)
+        };
+        Self {
+            daily_downloads: var("QUOTA_DAILY_DOWNLOADS"),
+            daily_bytes: var("QUOTA_DAILY_MB").map(|mb| mb * MB),
+            weekly_downloads: var("QUOTA_WEEKLY_DOWNLOADS"),
+            weekly_bytes: var("QUOTA_WEEKLY_MB").map(|mb| mb * MB),
+        }
+    }
+
+    /// Apply a per-chat override: set fields replace the default, 0 lifts the limit.
+    pub fn with_override(self, o: &QuotaOverride) -> Self {
+        let pick = |custom: Option<i64>, default: Option<u64>| match custom {
+            Some(n) if n > 0 => Some(n as u64),
+            Some(_) => None,
+            None => default,
+        };
+        Self {
+            daily_downloads: pick(o.daily_downloads, self.daily_downloads),
+            daily_bytes: pick(o.daily_bytes, self.daily_bytes),
+            weekly_downloads: pick(o.weekly_downloads, self.weekly_downloads),
+            weekly_bytes: pick(o.weekly_bytes, self.weekly_bytes),
+        }
+    }
+}
+
+/// Downloads started and bytes delivered in each window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub daily_downloads: u64,
+    pub daily_bytes: u64,
+    pub weekly_downloads: u64,
+    pub weekly_bytes: u64,
+}
+
+/// Usage against the limits of one window.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct QuotaWindow {
+    pub downloads_used: u64,
+    pub downloads_limit: Option<u64>,
+    pub downloads_remaining: Option<u64>,
+    pub bytes_used: u64,
+    pub bytes_limit: Option<u64>,
+    pub bytes_remaining: Option<u64>,
+}
+
+impl QuotaWindow {
+    fn new(downloads_used: u64, downloads_limit: Option<u64>, bytes_used: u64, bytes_limit: Option<u64>) -> Self {
+        Self {
+            downloads_used,
+            downloads_limit,
+            downloads_remaining: downloads_limit.map(|l| l.saturating_sub(downloads_used)),
+            bytes_used,
+            bytes_limit,
+            bytes_remaining: bytes_limit.map(|l| l.saturating_sub(bytes_used)),
+        }
+    }
+
+    /// Why this window refuses another download, if it does.
+    fn exceeded(&self, period: &str) -> Option<String> {
+        if self.downloads_remaining == Some(0) {
+            return Some(format!(
+                "{} download limit reached ({}/{}).",
+                period,
+                self.downloads_used,
+                self.downloads_limit.unwrap_or_default()
+            ));
+        }
+        if self.bytes_remaining == Some(0) {
+            return Some(format!(
+                "{} data limit reached ({} of {} MB).",
+                period,
+                self.bytes_used / MB,
+                self.bytes_limit.unwrap_or_default() / MB
+            ));
+        }
+        None
+    }
+}
+
+/// A chat's quota standing, as shown by `/quota` and the API.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct QuotaStatus {
+    /// Exempt from all limits (admin override).
+    pub unlimited: bool,
+    /// Last 24 hours.
+    pub daily: QuotaWindow,
+    /// Last 7 days.
+    pub weekly: QuotaWindow,
+}
+
+impl QuotaStatus {
+    pub fn new(limits: QuotaLimits, usage: QuotaUsage, unlimited: bool) -> Self {
+        let limits = if unlimited { QuotaLimits::default() } else { limits };
+        Self {
+            unlimited,
+            daily: QuotaWindow::new(usage.daily_downloads, limits.daily_downloads, usage.daily_bytes, limits.daily_bytes),
+            weekly: QuotaWindow::new(usage.weekly_downloads, limits.weekly_downloads, usage.weekly_bytes, limits.weekly_bytes),
+        }
+    }
+
+    /// User-facing reason a new download is refused, or `None` if it may start.
+    pub fn exceeded(&self) -> Option<String> {
+        self.daily.exceeded("Daily").or_else(|| self.weekly.exceeded("Weekly"))
+    }
+
+    /// Downloads that may still start: the tighter window's, `None` if unlimited.
+    pub fn downloads_remaining(&self) -> Option<u64> {
+        match (self.daily.downloads_remaining, self.weekly.downloads_remaining) {
+            (Some(daily), Some(weekly)) => Some(daily.min(weekly)),
+            (daily, weekly) => daily.or(weekly),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> QuotaLimits {
+        QuotaLimits {
+            daily_downloads: Some(5),
+            daily_bytes: Some(100 * MB),
+            weekly_downloads: Some(20),
+            weekly_bytes: None,
+        }
+    }
+
+    #[test]
+    fn test_override_replaces_or_lifts_defaults() {
+        let o = QuotaOverride {
+            daily_downloads: Some(10),
+            daily_bytes: Some(0),
+            ..Default::default()
+        };
+        let merged = limits().with_override(&o);
+        assert_eq!(merged.daily_downloads, Some(10));
+        assert_eq!(merged.daily_bytes, None);
+        assert_eq!(merged.weekly_downloads, Some(20));
+        assert_eq!(merged.weekly_bytes, None);
+    }
+
+    #[test]
+    fn test_remaining_and_exceeded() {
+        let mut usage = QuotaUsage { daily_downloads: 3, daily_bytes: 40 * MB, weekly_downloads: 12, weekly_bytes: 0 };
+        let status = QuotaStatus::new(limits(), usage, false);
+        assert_eq!(status.daily.downloads_remaining, Some(2));
+        assert_eq!(status.downloads_remaining(), Some(2));
+        assert_eq!(status.daily.bytes_remaining, Some(60 * MB));
+        assert_eq!(status.weekly.bytes_remaining, None);
+        assert_eq!(status.exceeded(), None);
+
+        usage.daily_bytes = 120 * MB;
+        let status = QuotaStatus::new(limits(), usage, false);
+        assert_eq!(status.daily.bytes_remaining, Some(0));
+        assert_eq!(status.exceeded().as_deref(), Some("Daily data limit reached (120 of 100 MB)."));
+
+        usage.daily_bytes = 0;
+        usage.weekly_downloads = 20;
+        let status = QuotaStatus::new(limits(), usage, false);
+        assert_eq!(status.exceeded().as_deref(), Some("Weekly download limit reached (20/20)."));
+
+        let status = QuotaStatus::new(limits(), usage, true);
+        assert_eq!(status.weekly.downloads_limit, None);
+        assert_eq!(status.exceeded(), None);
+    }
+}
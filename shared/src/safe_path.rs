@@ -0,0 +1,72 @@
+/// Path containment checks, so task ids and stored file paths can never
+/// reach outside the download directory.
+use std::path::{Component, Path, PathBuf};
+
+/// True if `name` is usable as a single path component: non-empty, not `.` or
+/// `..`, and free of separators and NUL bytes.
+pub fn is_safe_component(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\', '\0'])
+}
+
+/// Resolve `path` and return it only if it stays within `base`.
+///
+/// Both sides are canonicalized, so symlinks and `..` are followed before the
+/// comparison. A path that does not exist yet is resolved through its deepest
+/// existing ancestor; its remaining components must be plain names. None if
+/// `base` cannot be resolved or the path escapes it.
+pub fn resolve_within(base: &Path, path: &Path) -> Option<PathBuf> {
+    let base = base.canonicalize().ok()?;
+    let mut existing = path;
+    let mut rest = Vec::new();
+    let resolved = loop {
+        match existing.canonicalize() {
+            Ok(resolved) => break resolved,
+            Err(_) => {
+                rest.push(existing.file_name()?);
+                existing = existing.parent()?;
+                if existing.as_os_str().is_empty() {
+                    existing = Path::new(".");
+                }
+            }
+        }
+    };
+    let mut resolved = resolved;
+    for name in rest.into_iter().rev() {
+        match Path::new(name).components().next() {
+            Some(Component::Normal(_)) => resolved.push(name),
+            _ => return None,
+        }
+    }
+    resolved.starts_with(&base).then_some(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_only_inside_base() {
+        let root = std::env::temp_dir().join(format!("hermes-safe-path-{}", std::process::id()));
+        let base = root.join("downloads");
+        std::fs::create_dir_all(base.join("42/task")).unwrap();
+        std::fs::write(base.join("42/task/a.mp3"), b"x").unwrap();
+        std::fs::write(root.join("secret"), b"x").unwrap();
+
+        assert!(resolve_within(&base, &base.join("42/task/a.mp3")).is_some());
+        // Not created yet, but would land inside
+        assert!(resolve_within(&base, &base.join("42/new-task/b.mp3")).is_some());
+        assert!(resolve_within(&base, &base.join("42/../../secret")).is_none());
+        assert!(resolve_within(&base, &root.join("secret")).is_none());
+        assert!(resolve_within(&base, Path::new("/etc/passwd")).is_none());
+
+        assert!(is_safe_component("0b7c3c1e-uuid"));
+        assert!(!is_safe_component(".."));
+        assert!(!is_safe_component("a/../../b"));
+        assert!(!is_safe_component(""));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
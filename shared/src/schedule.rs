@@ -0,0 +1,134 @@
+/// Start times for scheduled downloads (`/schedule`, `POST /api/download`).
+///
+/// Accepted forms, read in the user's timezone:
+///
+/// - `+2h`, `+30m`, `+1d`, `+1h30m` — relative to now (the `+` is optional)
+/// - `22:00`, `7:30` — the next time the clock shows that time
+/// - `2024-05-01T22:00` — a local date and time
+/// - `2024-05-01T22:00:00Z`, `...+02:00` — RFC 3339 with an explicit offset
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+
+/// How far ahead a download may be scheduled.
+pub const MAX_AHEAD_DAYS: i64 = 30;
+
+/// Parse a start time. Returns the UTC instant (whole seconds), or a
+/// user-facing error for unreadable, past or too-distant times.
+pub fn parse_start_time(input: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+    let at = parse_relative(input)
+        .and_then(|offset| now.checked_add_signed(offset))
+        .or_else(|| parse_clock(input, now, tz))
+        .or_else(|| parse_local(input, tz))
+        .or_else(|| DateTime::parse_from_rfc3339(input).ok().map(|t| t.with_timezone(&Utc)))
+        .ok_or_else(|| format!(
+            "Can't read the time \"{}\". Use e.g. 22:00, +2h, +1h30m or 2024-05-01T22:00.", input
+        ))?
+        .with_nanosecond(0)
+        .unwrap_or(now);
+
+    if at <= now {
+        return Err("That time has already passed.".to_string());
+    }
+    if at > now + Duration::days(MAX_AHEAD_DAYS) {
+        return Err(format!("Downloads can be scheduled at most {} days ahead.", MAX_AHEAD_DAYS));
+    }
+    Ok(at)
+}
+
+/// `+1h30m` style offsets: days, hours and minutes, each unit at most once.
+/// `None` for offsets chrono can't represent.
+fn parse_relative(input: &str) -> Option<Duration> {
+    let spec = input.strip_prefix('+').unwrap_or(input);
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    let mut seen = String::new();
+    for c in spec.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let n: i64 = digits.parse().ok()?;
+        let unit = c.to_ascii_lowercase();
+        if seen.contains(unit) {
+            return None;
+        }
+        let part = match unit {
+            'd' => Duration::try_days(n),
+            'h' => Duration::try_hours(n),
+            'm' => Duration::try_minutes(n),
+            _ => return None,
+        };
+        total = total.checked_add(&part?)?;
+        seen.push(unit);
+        digits.clear();
+    }
+    (digits.is_empty() && !seen.is_empty()).then_some(total)
+}
+
+/// `HH:MM`: today in `tz` if that's still ahead, otherwise tomorrow.
+fn parse_clock(input: &str, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::parse_from_str(input, "%H:%M").ok()?;
+    let today = now.with_timezone(&tz).date_naive();
+    [today, today.succ_opt()?]
+        .into_iter()
+        .filter_map(|day| tz.from_local_datetime(&day.and_time(time)).earliest())
+        .map(|t| t.with_timezone(&Utc))
+        .find(|t| *t > now)
+}
+
+/// `2024-05-01T22:00` (or with a space) in `tz`.
+fn parse_local(input: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    let local = ["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(input, fmt).ok())?;
+    tz.from_local_datetime(&local).earliest().map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_relative_times() {
+        let now = utc("2024-05-01T12:00:30.5Z");
+        let parse = |s: &str| parse_start_time(s, now, Tz::UTC);
+        assert_eq!(parse("+2h"), Ok(utc("2024-05-01T14:00:30Z")));
+        assert_eq!(parse("90m"), Ok(utc("2024-05-01T13:30:30Z")));
+        assert_eq!(parse("+1h30m"), Ok(utc("2024-05-01T13:30:30Z")));
+        assert_eq!(parse("+1d"), Ok(utc("2024-05-02T12:00:30Z")));
+        assert!(parse("+0m").is_err());
+        assert!(parse("+2x").is_err());
+        assert!(parse("+1h1h").is_err());
+        assert!(parse("+31d").is_err());
+        // Out of chrono's range: unreadable, not a panic
+        assert!(parse("+9999999999h").unwrap_err().starts_with("Can't read the time"));
+        assert!(parse("+999999999999999d").unwrap_err().starts_with("Can't read the time"));
+        assert!(parse("+100000000000d1h").is_err());
+    }
+
+    #[test]
+    fn test_clock_times_use_the_user_timezone() {
+        let now = utc("2024-07-01T20:30:00Z"); // 21:30 in Lisbon (WEST)
+        let lisbon: Tz = "Europe/Lisbon".parse().unwrap();
+        assert_eq!(parse_start_time("22:00", now, lisbon), Ok(utc("2024-07-01T21:00:00Z")));
+        // Already past today: tomorrow
+        assert_eq!(parse_start_time("21:00", now, lisbon), Ok(utc("2024-07-02T20:00:00Z")));
+        assert_eq!(parse_start_time("07:15", now, Tz::UTC), Ok(utc("2024-07-02T07:15:00Z")));
+        assert!(parse_start_time("25:00", now, Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn test_absolute_times() {
+        let now = utc("2024-07-01T12:00:00Z");
+        let lisbon: Tz = "Europe/Lisbon".parse().unwrap();
+        assert_eq!(parse_start_time("2024-07-03T22:00", now, lisbon), Ok(utc("2024-07-03T21:00:00Z")));
+        assert_eq!(parse_start_time("2024-07-03T22:00:00+02:00", now, lisbon), Ok(utc("2024-07-03T20:00:00Z")));
+        assert!(parse_start_time("2024-06-30T22:00", now, Tz::UTC).is_err());
+        assert!(parse_start_time("tomorrow", now, Tz::UTC).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+/// `/search` modifiers that narrow YouTube results.
+///
+/// Words of the form `key:value` anywhere in the query set a filter:
+///
+/// - `duration:short|medium|long` — under 4 min, 4–20 min, over 20 min
+/// - `date:hour|today|week|month|year` — upload date
+/// - `type:video|playlist` — kind of result
+///
+/// The worker turns them into YouTube's own search filters. Other words with a
+/// colon (`c++:`, `12:30`) stay part of the query.
+use serde::Serialize;
+
+const DURATIONS: &[(&str, &str)] = &[
+    ("short", "under 4 min"),
+    ("medium", "4–20 min"),
+    ("long", "over 20 min"),
+];
+const DATES: &[(&str, &str)] = &[
+    ("hour", "last hour"),
+    ("today", "today"),
+    ("week", "this week"),
+    ("month", "this month"),
+    ("year", "this year"),
+];
+const TYPES: &[(&str, &str)] = &[("video", "videos"), ("playlist", "playlists")];
+
+/// Active filters; `None` means not filtered on that field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SearchFilters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<&'static str>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'static str>,
+}
+
+impl SearchFilters {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// "over 20 min · this week · playlists", for the results header.
+    pub fn summary(&self) -> String {
+        [(self.duration, DURATIONS), (self.date, DATES), (self.kind, TYPES)]
+            .iter()
+            .filter_map(|(value, table)| label(table, (*value)?))
+            .collect::<Vec<_>>()
+            .join(" · ")
+    }
+}
+
+fn label(table: &[(&'static str, &'static str)], value: &str) -> Option<&'static str> {
+    table.iter().find(|(v, _)| *v == value).map(|(_, l)| *l)
+}
+
+/// Split `input` into the plain query and its filters, or a user-facing
+/// error for an unknown filter value.
+pub fn parse_search_query(input: &str) -> Result<(String, SearchFilters), String> {
+    let mut filters = SearchFilters::default();
+    let mut words = Vec::new();
+
+    for word in input.split_whitespace() {
+        let Some((key, value)) = word.split_once(':') else {
+            words.push(word);
+            continue;
+        };
+        let key = key.to_lowercase();
+        let (slot, table) = match key.as_str() {
+            "duration" => (&mut filters.duration, DURATIONS),
+            "date" => (&mut filters.date, DATES),
+            "type" => (&mut filters.kind, TYPES),
+            _ => {
+                words.push(word);
+                continue;
+            }
+        };
+        let value = value.to_lowercase();
+        match table.iter().find(|(v, _)| *v == value) {
+            Some((v, _)) => *slot = Some(v),
+            None => {
+                let options: Vec<&str> = table.iter().map(|(v, _)| *v).collect();
+                return Err(format!("Unknown {} \"{}\". Use {}:{}.", key, value, key, options.join("|")));
+            }
+        }
+    }
+
+    Ok((words.join(" "), filters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search_query() {
+        let (query, filters) = parse_search_query("lofi beats duration:long DATE:week type:playlist").unwrap();
+        assert_eq!(query, "lofi beats");
+        assert_eq!(filters, SearchFilters { duration: Some("long"), date: Some("week"), kind: Some("playlist") });
+        assert_eq!(filters.summary(), "over 20 min · this week · playlists");
+        assert_eq!(
+            serde_json::to_value(filters).unwrap(),
+            serde_json::json!({"duration": "long", "date": "week", "type": "playlist"})
+        );
+
+        let (query, filters) = parse_search_query("c++: tutorial 12:30").unwrap();
+        assert_eq!(query, "c++: tutorial 12:30");
+        assert!(filters.is_empty());
+        assert_eq!(serde_json::to_value(filters).unwrap(), serde_json::json!({}));
+
+        assert_eq!(
+            parse_search_query("news date:decade"),
+            Err("Unknown date \"decade\". Use date:hour|today|week|month|year.".to_string())
+        );
+    }
+}
@@ -0,0 +1,90 @@
+/// Download roots per kind of task.
+///
+/// Everything lands under `DOWNLOAD_DIR` by default. `AUDIO_DIR`, `VIDEO_DIR`
+/// and `PLAYLIST_DIR` move audio, video and playlist downloads elsewhere (for
+/// example onto a bigger disk); each unset one falls back to `DOWNLOAD_DIR`.
+use std::path::Path;
+
+/// Which root a task writes into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Audio,
+    Video,
+    Playlist,
+    /// Anything else (wallpapers, transcripts of no particular kind).
+    Other,
+}
+
+impl StorageKind {
+    /// Audio or Video, from a download's `extract_audio` flag.
+    pub fn for_media(extract_audio: bool) -> Self {
+        if extract_audio { StorageKind::Audio } else { StorageKind::Video }
+    }
+}
+
+/// The configured download roots.
+#[derive(Debug, Clone)]
+pub struct StorageDirs {
+    pub download: String,
+    pub audio: String,
+    pub video: String,
+    pub playlist: String,
+}
+
+impl StorageDirs {
+    /// All kinds share `download_dir` unless given their own root.
+    pub fn new(download_dir: &str, audio: Option<String>, video: Option<String>, playlist: Option<String>) -> Self {
+        let or_default = |dir: Option<String>| {
+            dir.filter(|d| !d.trim().is_empty()).unwrap_or_else(|| download_dir.to_string())
+        };
+        StorageDirs {
+            download: download_dir.to_string(),
+            audio: or_default(audio),
+            video: or_default(video),
+            playlist: or_default(playlist),
+        }
+    }
+
+    /// Read `AUDIO_DIR`, `VIDEO_DIR` and `PLAYLIST_DIR` on top of `download_dir`.
+    pub fn from_env(download_dir: &str) -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        Self::new(download_dir, var("AUDIO_DIR"), var("VIDEO_DIR"), var("PLAYLIST_DIR"))
+    }
+
+    /// Root for a task of this kind.
+    pub fn base_for(&self, kind: StorageKind) -> &str {
+        match kind {
+            StorageKind::Audio => &self.audio,
+            StorageKind::Video => &self.video,
+            StorageKind::Playlist => &self.playlist,
+            StorageKind::Other => &self.download,
+        }
+    }
+
+    /// Every distinct root, `DOWNLOAD_DIR` first.
+    pub fn roots(&self) -> Vec<&str> {
+        let mut roots: Vec<&str> = Vec::with_capacity(4);
+        for dir in [&self.download, &self.audio, &self.video, &self.playlist] {
+            if !roots.iter().any(|r| Path::new(r) == Path::new(dir)) {
+                roots.push(dir);
+            }
+        }
+        roots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_download_dir() {
+        let dirs = StorageDirs::new("/data/dl", Some("/music".into()), Some(" ".into()), None);
+        assert_eq!(dirs.base_for(StorageKind::Audio), "/music");
+        assert_eq!(dirs.base_for(StorageKind::Video), "/data/dl");
+        assert_eq!(dirs.base_for(StorageKind::Playlist), "/data/dl");
+        assert_eq!(dirs.base_for(StorageKind::Other), "/data/dl");
+        assert_eq!(dirs.roots(), vec!["/data/dl", "/music"]);
+        assert_eq!(StorageKind::for_media(true), StorageKind::Audio);
+    }
+}
@@ -1,9 +1,11 @@
 /// Concurrent task queue for managing download operations.
 ///
-/// Uses tokio Semaphore to limit concurrency and track active tasks.
-use std::collections::HashMap;
+/// Uses tokio Semaphore to limit concurrency and track active tasks. Tasks
+/// waiting for a slot are served by priority, then round-robin across chats.
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{Mutex, Semaphore, OwnedSemaphorePermit};
+use tokio::sync::{Mutex, Notify, Semaphore, OwnedSemaphorePermit};
 use tracing::{info, warn};
 use chrono::Utc;
 
@@ -13,11 +15,161 @@ pub struct TrackedTask {
     pub task_id: String,
     pub chat_id: i64,
     pub task_type: String,
+    pub priority: Priority,
     pub status: TaskState,
     pub progress: u8,
     pub speed: Option<String>,
+    /// Raw ETA in seconds as last reported by the worker.
+    pub eta_secs: Option<u64>,
+    /// Smoothed ETA used for display.
+    pub eta: EtaSmoother,
     pub enqueued_at: chrono::DateTime<Utc>,
     pub started_at: Option<chrono::DateTime<Utc>>,
+    /// When progress was last handed to the progress hook.
+    pub progress_reported_at: Option<std::time::Instant>,
+}
+
+/// Progress snapshot passed to the queue's progress hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    pub task_id: String,
+    pub percent: u8,
+    pub speed: Option<String>,
+    /// Smoothed ETA in seconds.
+    pub eta_secs: Option<u64>,
+}
+
+/// Callback run (throttled) on progress updates, e.g. to persist them for the web API.
+pub type ProgressHook = Arc<dyn Fn(ProgressUpdate) + Send + Sync>;
+
+/// Minimum gap between progress hook calls for the same task.
+const PROGRESS_REPORT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Scheduling priority: free slots go to the highest level first, FIFO within a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Bulk work (playlists, merges, subscription refreshes).
+    Low,
+    #[default]
+    Normal,
+    /// Admin-submitted tasks and retries.
+    High,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+
+    /// Parse a stored priority; anything unrecognised is Normal.
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "low" => Priority::Low,
+            "high" => Priority::High,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A task waiting in `acquire`.
+#[derive(Debug, Clone, Copy)]
+struct Waiter {
+    priority: Priority,
+    chat_id: i64,
+    seq: u64,
+}
+
+#[derive(Default)]
+struct LineState {
+    waiters: Vec<Waiter>,
+    /// Chat of each task holding a slot.
+    running: HashMap<String, i64>,
+    /// Turn number at which each chat last got a slot, for round-robin.
+    served: HashMap<i64, u64>,
+    turns: u64,
+}
+
+/// Waiters in `acquire`, plus a signal fired whenever the head of the line may have changed.
+#[derive(Default)]
+struct WaitLine {
+    state: std::sync::Mutex<LineState>,
+    changed: Notify,
+    next_seq: AtomicU64,
+}
+
+impl WaitLine {
+    fn join(&self, priority: Priority, chat_id: i64) -> u64 {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        self.state.lock().unwrap().waiters.push(Waiter { priority, chat_id, seq });
+        self.changed.notify_waiters();
+        seq
+    }
+
+    fn leave(&self, seq: u64) {
+        self.state.lock().unwrap().waiters.retain(|w| w.seq != seq);
+        self.changed.notify_waiters();
+    }
+
+    /// Move a waiter out of line and into a slot.
+    fn admit(&self, seq: u64, task_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let Some(pos) = state.waiters.iter().position(|w| w.seq == seq) else { return };
+        let waiter = state.waiters.remove(pos);
+        state.turns += 1;
+        let turn = state.turns;
+        state.served.insert(waiter.chat_id, turn);
+        state.running.insert(task_id.to_string(), waiter.chat_id);
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// A task gave its slot back; its chat may be under the cap again.
+    fn finish(&self, task_id: &str) {
+        if self.state.lock().unwrap().running.remove(task_id).is_some() {
+            self.changed.notify_waiters();
+        }
+    }
+
+    /// Whether `seq` is next in line. Chats already at `per_chat_limit`
+    /// running tasks are passed over; the rest go by priority, then the
+    /// chat served longest ago, then arrival.
+    fn is_head(&self, seq: u64, per_chat_limit: Option<usize>) -> bool {
+        let state = self.state.lock().unwrap();
+        let at_limit = |chat_id: i64| {
+            per_chat_limit.is_some_and(|limit| state.running.values().filter(|&&c| c == chat_id).count() >= limit)
+        };
+        state.waiters
+            .iter()
+            .filter(|w| !at_limit(w.chat_id))
+            .min_by_key(|w| {
+                let last_served = state.served.get(&w.chat_id).copied().unwrap_or(0);
+                (std::cmp::Reverse(w.priority), last_served, w.seq)
+            })
+            .is_some_and(|w| w.seq == seq)
+    }
+}
+
+/// Takes a waiter out of line when `acquire` finishes or is dropped.
+struct LeaveOnDrop<'a> {
+    line: &'a WaitLine,
+    seq: u64,
+}
+
+impl Drop for LeaveOnDrop<'_> {
+    fn drop(&mut self) {
+        self.line.leave(self.seq);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,10 +189,25 @@ pub struct TaskQueue {
     permits: Arc<Mutex<HashMap<String, OwnedSemaphorePermit>>>,
     /// Tracked task metadata.
     tasks: Arc<Mutex<HashMap<String, TrackedTask>>>,
+    /// Tasks waiting for a slot, in priority order.
+    wait_line: Arc<WaitLine>,
     /// Max concurrent tasks.
     max_concurrent: usize,
+    /// Max concurrent tasks per chat (see `with_per_chat_limit`).
+    per_chat_limit: Option<usize>,
+    /// Run durations (start→complete, seconds) of the most recent completed tasks.
+    recent_durations: Arc<Mutex<VecDeque<f64>>>,
+    /// (bytes, seconds) of the most recent completed downloads.
+    recent_transfers: Arc<Mutex<VecDeque<(u64, f64)>>>,
+    /// Per-task cancellation signals, fired by `cancel`.
+    cancel_signals: Arc<Mutex<HashMap<String, Arc<Notify>>>>,
+    /// Optional sink for live progress (see `with_progress_hook`).
+    progress_hook: Option<ProgressHook>,
 }
 
+/// How many completed tasks the duration average looks back over.
+const DURATION_WINDOW: usize = 20;
+
 impl TaskQueue {
     /// Create a new task queue with the given concurrency limit.
     pub fn new(max_concurrent: usize) -> Self {
@@ -48,12 +215,32 @@ impl TaskQueue {
             semaphore: Arc::new(Semaphore::new(max_concurrent)),
             permits: Arc::new(Mutex::new(HashMap::new())),
             tasks: Arc::new(Mutex::new(HashMap::new())),
+            wait_line: Arc::new(WaitLine::default()),
             max_concurrent,
+            per_chat_limit: None,
+            recent_durations: Arc::new(Mutex::new(VecDeque::with_capacity(DURATION_WINDOW))),
+            recent_transfers: Arc::new(Mutex::new(VecDeque::with_capacity(DURATION_WINDOW))),
+            cancel_signals: Arc::new(Mutex::new(HashMap::new())),
+            progress_hook: None,
         }
     }
 
+    /// Report progress to `hook`, at most every couple of seconds per task
+    /// (plus the first and the 100% update).
+    pub fn with_progress_hook(mut self, hook: ProgressHook) -> Self {
+        self.progress_hook = Some(hook);
+        self
+    }
+
+    /// Let each chat run at most `limit` tasks at once. Waiting chats then
+    /// take turns: the one served longest ago gets the next free slot.
+    pub fn with_per_chat_limit(mut self, limit: usize) -> Self {
+        self.per_chat_limit = Some(limit.max(1));
+        self
+    }
+
     /// Enqueue a task. Returns false if already tracked.
-    pub async fn enqueue(&self, task_id: &str, chat_id: i64, task_type: &str) -> bool {
+    pub async fn enqueue(&self, task_id: &str, chat_id: i64, task_type: &str, priority: Priority) -> bool {
         let mut tasks = self.tasks.lock().await;
         if tasks.contains_key(task_id) {
             warn!("Task {} already in queue", task_id);
@@ -64,30 +251,74 @@ impl TaskQueue {
             task_id: task_id.to_string(),
             chat_id,
             task_type: task_type.to_string(),
+            priority,
             status: TaskState::Queued,
             progress: 0,
             speed: None,
+            eta_secs: None,
+            eta: EtaSmoother::default(),
             enqueued_at: Utc::now(),
             started_at: None,
+            progress_reported_at: None,
         });
 
-        info!("Task {} enqueued (type: {})", task_id, task_type);
+        info!("Task {} enqueued (type: {}, priority: {})", task_id, task_type, priority);
         true
     }
 
-    /// Acquire a concurrency permit. Waits if at capacity.
+    /// Acquire a concurrency permit. Waits if at capacity (or the chat is at
+    /// its per-chat limit), and lets higher-priority waiters go first.
     pub async fn acquire(&self, task_id: &str) -> bool {
-        let permit = match self.semaphore.clone().acquire_owned().await {
-            Ok(p) => p,
-            Err(_) => {
+        let (priority, chat_id) = self.tasks.lock().await
+            .get(task_id)
+            .map(|t| (t.priority, t.chat_id))
+            .unwrap_or_default();
+        let line = &*self.wait_line;
+        let seq = line.join(priority, chat_id);
+        let _place = LeaveOnDrop { line, seq };
+
+        let permit = loop {
+            // Register before checking so a change in between isn't missed
+            let changed = line.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            if self.semaphore.is_closed() {
                 warn!("Semaphore closed for task {}", task_id);
                 return false;
             }
+            if !line.is_head(seq, self.per_chat_limit) {
+                changed.await;
+                continue;
+            }
+            let permit = tokio::select! {
+                permit = self.semaphore.clone().acquire_owned() => match permit {
+                    Ok(p) => p,
+                    Err(_) => {
+                        warn!("Semaphore closed for task {}", task_id);
+                        return false;
+                    }
+                },
+                _ = &mut changed => continue,
+            };
+            if line.is_head(seq, self.per_chat_limit) {
+                break permit;
+            }
+            // Someone more urgent arrived while the slot was freeing up: hand it over
+            drop(permit);
         };
 
+        // Cancelled while waiting for a slot: hand the slot straight back
+        let mut tasks = self.tasks.lock().await;
+        if tasks.get(task_id).is_some_and(|t| t.status == TaskState::Cancelled) {
+            info!("Task {} was cancelled while queued", task_id);
+            return false;
+        }
+        line.admit(seq, task_id);
+
         // Store permit and mark running
         self.permits.lock().await.insert(task_id.to_string(), permit);
-        if let Some(task) = self.tasks.lock().await.get_mut(task_id) {
+        if let Some(task) = tasks.get_mut(task_id) {
             task.status = TaskState::Running;
             task.started_at = Some(Utc::now());
         }
@@ -97,21 +328,60 @@ impl TaskQueue {
     }
 
     /// Update progress for a running task.
-    pub async fn update_progress(&self, task_id: &str, percent: u8, speed: Option<String>) {
-        if let Some(task) = self.tasks.lock().await.get_mut(task_id) {
-            task.progress = percent;
-            task.speed = speed;
+    /// Returns the smoothed ETA in seconds, if one is known.
+    pub async fn update_progress(
+        &self,
+        task_id: &str,
+        percent: u8,
+        speed: Option<String>,
+        eta_secs: Option<u64>,
+    ) -> Option<u64> {
+        let mut tasks = self.tasks.lock().await;
+        let task = tasks.get_mut(task_id)?;
+        task.progress = percent;
+        task.speed = speed;
+        if eta_secs.is_some() {
+            task.eta_secs = eta_secs;
         }
+        let now = std::time::Instant::now();
+        let eta = match eta_secs {
+            Some(raw) => Some(task.eta.update_at(raw, now)),
+            None => task.eta.current(),
+        };
+
+        let report = self.progress_hook.as_ref().filter(|_| {
+            percent >= 100
+                || task.progress_reported_at.is_none_or(|at| now.duration_since(at) >= PROGRESS_REPORT_INTERVAL)
+        });
+        if let Some(hook) = report {
+            task.progress_reported_at = Some(now);
+            let update = ProgressUpdate {
+                task_id: task_id.to_string(),
+                percent,
+                speed: task.speed.clone(),
+                eta_secs: eta,
+            };
+            drop(tasks);
+            hook(update);
+        }
+        eta
     }
 
     /// Mark task as completed and release its permit.
     pub async fn complete(&self, task_id: &str) {
+        let mut started_at = None;
         if let Some(task) = self.tasks.lock().await.get_mut(task_id) {
             task.status = TaskState::Done;
             task.progress = 100;
+            started_at = task.started_at;
+        }
+        if let Some(started) = started_at {
+            let secs = (Utc::now() - started).num_milliseconds().max(0) as f64 / 1000.0;
+            self.record_duration(secs).await;
         }
         // Drop the permit to free the slot
-        self.permits.lock().await.remove(task_id);
+        self.release(task_id).await;
+        self.cancel_signals.lock().await.remove(task_id);
         info!("Task {} completed, slot released", task_id);
     }
 
@@ -120,17 +390,23 @@ impl TaskQueue {
         if let Some(task) = self.tasks.lock().await.get_mut(task_id) {
             task.status = TaskState::Failed;
         }
-        self.permits.lock().await.remove(task_id);
+        self.release(task_id).await;
+        self.cancel_signals.lock().await.remove(task_id);
         warn!("Task {} failed, slot released", task_id);
     }
 
-    /// Cancel a task (removes from queue, releases permit if held).
+    /// Cancel a task (removes from queue, releases permit if held) and wake
+    /// whoever is waiting on its cancellation signal.
     pub async fn cancel(&self, task_id: &str) -> bool {
         let mut tasks = self.tasks.lock().await;
         if let Some(task) = tasks.get_mut(task_id) {
             task.status = TaskState::Cancelled;
             drop(tasks);
-            self.permits.lock().await.remove(task_id);
+            self.release(task_id).await;
+            if let Some(signal) = self.cancel_signals.lock().await.remove(task_id) {
+                // notify_one stores a permit, so a waiter that hasn't started yet still sees it
+                signal.notify_one();
+            }
             info!("Task {} cancelled", task_id);
             true
         } else {
@@ -138,6 +414,44 @@ impl TaskQueue {
         }
     }
 
+    /// Give back the task's slot, if it holds one.
+    async fn release(&self, task_id: &str) {
+        self.permits.lock().await.remove(task_id);
+        self.wait_line.finish(task_id);
+    }
+
+    /// Cancellation signal for a task; completes once `cancel` is called.
+    /// Take it before waiting on the task so a cancel can't slip past.
+    pub async fn cancellation(&self, task_id: &str) -> Arc<Notify> {
+        self.cancel_signals.lock().await
+            .entry(task_id.to_string())
+            .or_insert_with(|| Arc::new(Notify::new()))
+            .clone()
+    }
+
+    /// Whether a task has been cancelled.
+    pub async fn is_cancelled(&self, task_id: &str) -> bool {
+        self.tasks.lock().await
+            .get(task_id)
+            .is_some_and(|t| t.status == TaskState::Cancelled)
+    }
+
+    /// Stop handing out slots: queued tasks waiting in `acquire` get false.
+    /// Used on shutdown; running tasks keep the slots they hold.
+    pub fn close(&self) {
+        self.semaphore.close();
+        self.wait_line.changed.notify_waiters();
+    }
+
+    /// Ids of tasks still queued or running.
+    pub async fn active_task_ids(&self) -> Vec<String> {
+        self.tasks.lock().await
+            .values()
+            .filter(|t| matches!(t.status, TaskState::Queued | TaskState::Running))
+            .map(|t| t.task_id.clone())
+            .collect()
+    }
+
     /// Get the current status of a task.
     pub async fn get_status(&self, task_id: &str) -> Option<TrackedTask> {
         self.tasks.lock().await.get(task_id).cloned()
@@ -165,6 +479,64 @@ impl TaskQueue {
             .count()
     }
 
+    /// Record how long a completed task ran, for queue-time estimates.
+    pub async fn record_duration(&self, secs: f64) {
+        let mut durations = self.recent_durations.lock().await;
+        if durations.len() == DURATION_WINDOW {
+            durations.pop_front();
+        }
+        durations.push_back(secs);
+    }
+
+    /// Moving average of recent run durations, if any task has completed.
+    pub async fn average_duration_secs(&self) -> Option<f64> {
+        let durations = self.recent_durations.lock().await;
+        if durations.is_empty() {
+            None
+        } else {
+            Some(durations.iter().sum::<f64>() / durations.len() as f64)
+        }
+    }
+
+    /// Record the size and run time of a finished download, for download-time estimates.
+    pub async fn record_transfer(&self, bytes: u64, secs: f64) {
+        if bytes == 0 || secs <= 0.0 {
+            return;
+        }
+        let mut transfers = self.recent_transfers.lock().await;
+        if transfers.len() == DURATION_WINDOW {
+            transfers.pop_front();
+        }
+        transfers.push_back((bytes, secs));
+    }
+
+    /// Average throughput in bytes/second over recent downloads (total bytes
+    /// over total time, so one tiny file doesn't skew it). None without history.
+    pub async fn average_throughput_bps(&self) -> Option<f64> {
+        let transfers = self.recent_transfers.lock().await;
+        let (bytes, secs) = transfers.iter()
+            .fold((0u64, 0.0f64), |(b, s), &(bytes, secs)| (b + bytes, s + secs));
+        (secs > 0.0).then(|| bytes as f64 / secs)
+    }
+
+    /// Estimated seconds until a queued task gets a slot (tasks of higher
+    /// priority, or the same priority and enqueued earlier, go first).
+    /// `None` if the task isn't queued or there is no completed-task history yet.
+    pub async fn estimated_start_secs(&self, task_id: &str) -> Option<u64> {
+        let avg = self.average_duration_secs().await?;
+        let running = self.permits.lock().await.len();
+        let tasks = self.tasks.lock().await;
+        let task = tasks.get(task_id).filter(|t| t.status == TaskState::Queued)?;
+        let ahead = tasks.values()
+            .filter(|t| {
+                t.status == TaskState::Queued
+                    && (t.priority > task.priority
+                        || (t.priority == task.priority && t.enqueued_at < task.enqueued_at))
+            })
+            .count();
+        Some(estimate_start_secs(avg, running, ahead, self.max_concurrent))
+    }
+
     /// Get queue statistics.
     pub async fn stats(&self) -> QueueStats {
         let tasks = self.tasks.lock().await;
@@ -191,6 +563,79 @@ impl TaskQueue {
     }
 }
 
+/// Time until a slot frees up for a task with `ahead` queued tasks in front of it.
+///
+/// The task starts once `running + ahead - max_concurrent + 1` tasks finish; with
+/// `max_concurrent` slots working in parallel, one finishes every `avg / max_concurrent`.
+pub fn estimate_start_secs(avg_duration_secs: f64, running: usize, ahead: usize, max_concurrent: usize) -> u64 {
+    let max_concurrent = max_concurrent.max(1);
+    let occupied = running + ahead;
+    if occupied < max_concurrent {
+        return 0;
+    }
+    let completions_needed = (occupied - max_concurrent + 1) as f64;
+    (avg_duration_secs * completions_needed / max_concurrent as f64).round() as u64
+}
+
+/// Exponential moving average for download ETAs.
+///
+/// yt-dlp's ETA swings wildly with momentary speed changes. Each new sample is
+/// blended with the previous estimate, which is first aged by the time since
+/// the last sample so the displayed value keeps counting down between updates.
+#[derive(Debug, Clone)]
+pub struct EtaSmoother {
+    /// Weight given to the newest sample (0..=1).
+    alpha: f64,
+    value: Option<f64>,
+    last_sample: Option<std::time::Instant>,
+}
+
+impl Default for EtaSmoother {
+    fn default() -> Self {
+        Self::new(0.3)
+    }
+}
+
+impl EtaSmoother {
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            value: None,
+            last_sample: None,
+        }
+    }
+
+    /// Feed a raw ETA sample taken `elapsed_secs` after the previous one.
+    /// Returns the smoothed ETA in whole seconds.
+    pub fn update(&mut self, raw_secs: u64, elapsed_secs: f64) -> u64 {
+        let raw = raw_secs as f64;
+        let next = match self.value {
+            Some(prev) => {
+                let aged = (prev - elapsed_secs.max(0.0)).max(0.0);
+                self.alpha * raw + (1.0 - self.alpha) * aged
+            }
+            None => raw,
+        };
+        self.value = Some(next);
+        next.round() as u64
+    }
+
+    /// Feed a raw ETA sample observed at `now`.
+    pub fn update_at(&mut self, raw_secs: u64, now: std::time::Instant) -> u64 {
+        let elapsed = self
+            .last_sample
+            .map(|t| now.saturating_duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
+        self.last_sample = Some(now);
+        self.update(raw_secs, elapsed)
+    }
+
+    /// Current smoothed ETA, if any sample has been seen.
+    pub fn current(&self) -> Option<u64> {
+        self.value.map(|v| v.round() as u64)
+    }
+}
+
 /// Queue statistics snapshot.
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct QueueStats {
@@ -209,7 +654,7 @@ mod tests {
     #[tokio::test]
     async fn test_enqueue_and_acquire() {
         let queue = TaskQueue::new(2);
-        assert!(queue.enqueue("t1", 123, "youtube").await);
+        assert!(queue.enqueue("t1", 123, "youtube", Priority::Normal).await);
         assert!(queue.acquire("t1").await);
         assert_eq!(queue.running_count().await, 1);
     }
@@ -217,7 +662,7 @@ mod tests {
     #[tokio::test]
     async fn test_complete_releases_slot() {
         let queue = TaskQueue::new(1);
-        queue.enqueue("t1", 123, "youtube").await;
+        queue.enqueue("t1", 123, "youtube", Priority::Normal).await;
         queue.acquire("t1").await;
         assert_eq!(queue.running_count().await, 1);
 
@@ -225,18 +670,44 @@ mod tests {
         assert_eq!(queue.running_count().await, 0);
     }
 
+    #[tokio::test]
+    async fn test_progress_hook_is_throttled() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let queue = TaskQueue::new(1)
+            .with_progress_hook(Arc::new(move |u: ProgressUpdate| sink.lock().unwrap().push(u.percent)));
+        queue.enqueue("t1", 123, "youtube", Priority::Normal).await;
+        queue.acquire("t1").await;
+
+        queue.update_progress("t1", 10, None, None).await;
+        queue.update_progress("t1", 20, None, None).await; // within the interval: skipped
+        queue.update_progress("t1", 100, None, None).await; // completion always reported
+        assert_eq!(*seen.lock().unwrap(), vec![10, 100]);
+    }
+
+    #[tokio::test]
+    async fn test_average_throughput() {
+        let queue = TaskQueue::new(1);
+        assert_eq!(queue.average_throughput_bps().await, None);
+        queue.record_transfer(10_000_000, 10.0).await;
+        queue.record_transfer(1_000, 0.5).await;
+        queue.record_transfer(0, 3.0).await; // ignored
+        let bps = queue.average_throughput_bps().await.unwrap();
+        assert!((bps - 10_001_000.0 / 10.5).abs() < 1.0);
+    }
+
     #[tokio::test]
     async fn test_duplicate_enqueue() {
         let queue = TaskQueue::new(2);
-        assert!(queue.enqueue("t1", 123, "youtube").await);
-        assert!(!queue.enqueue("t1", 123, "youtube").await);
+        assert!(queue.enqueue("t1", 123, "youtube", Priority::Normal).await);
+        assert!(!queue.enqueue("t1", 123, "youtube", Priority::Normal).await);
     }
 
     #[tokio::test]
     async fn test_stats() {
         let queue = TaskQueue::new(3);
-        queue.enqueue("t1", 100, "youtube").await;
-        queue.enqueue("t2", 100, "playlist").await;
+        queue.enqueue("t1", 100, "youtube", Priority::Normal).await;
+        queue.enqueue("t2", 100, "playlist", Priority::Normal).await;
         queue.acquire("t1").await;
 
         let stats = queue.stats().await;
@@ -244,4 +715,211 @@ mod tests {
         assert_eq!(stats.queued, 1);
         assert_eq!(stats.max_concurrent, 3);
     }
+
+    #[tokio::test]
+    async fn test_cancel_wakes_waiter_and_blocks_acquire() {
+        let queue = Arc::new(TaskQueue::new(1));
+        queue.enqueue("t1", 1, "youtube", Priority::Normal).await;
+        queue.acquire("t1").await;
+        queue.enqueue("t2", 1, "youtube", Priority::Normal).await;
+
+        let signal = queue.cancellation("t1").await;
+        let waiter = tokio::spawn(async move { signal.notified().await });
+        assert!(queue.cancel("t1").await);
+        tokio::time::timeout(std::time::Duration::from_secs(1), waiter)
+            .await
+            .expect("cancel should wake the waiter")
+            .unwrap();
+        assert_eq!(queue.running_count().await, 0);
+
+        // A task cancelled while queued never starts
+        queue.cancel("t2").await;
+        assert!(!queue.acquire("t2").await);
+        assert!(queue.is_cancelled("t2").await);
+        assert_eq!(queue.running_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_stops_queued_tasks() {
+        let queue = TaskQueue::new(1);
+        queue.enqueue("t1", 1, "youtube", Priority::Normal).await;
+        queue.acquire("t1").await;
+        queue.enqueue("t2", 1, "youtube", Priority::Normal).await;
+
+        queue.close();
+        assert!(!queue.acquire("t2").await);
+
+        let mut active = queue.active_task_ids().await;
+        active.sort();
+        assert_eq!(active, vec!["t1", "t2"]);
+    }
+
+    #[tokio::test]
+    async fn test_higher_priority_gets_next_slot() {
+        let queue = Arc::new(TaskQueue::new(1));
+        queue.enqueue("running", 1, "youtube", Priority::Normal).await;
+        queue.acquire("running").await;
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (id, priority) in [("bulk", Priority::Low), ("normal", Priority::Normal), ("retry", Priority::High)] {
+            queue.enqueue(id, 1, "youtube", priority).await;
+            let (queue, order) = (queue.clone(), order.clone());
+            handles.push(tokio::spawn(async move {
+                assert!(queue.acquire(id).await);
+                order.lock().unwrap().push(id);
+                queue.complete(id).await;
+            }));
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.estimated_start_secs("bulk").await, None); // no history yet
+        queue.record_duration(10.0).await;
+        assert_eq!(queue.estimated_start_secs("retry").await, Some(10));
+        assert_eq!(queue.estimated_start_secs("bulk").await, Some(30));
+
+        queue.complete("running").await;
+        for handle in handles {
+            tokio::time::timeout(std::time::Duration::from_secs(1), handle)
+                .await
+                .expect("every waiter should get a slot")
+                .unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), vec!["retry", "normal", "bulk"]);
+    }
+
+    #[tokio::test]
+    async fn test_per_chat_limit_lets_other_chats_through() {
+        let queue = Arc::new(TaskQueue::new(2).with_per_chat_limit(1));
+        queue.enqueue("a1", 1, "youtube", Priority::Normal).await;
+        queue.enqueue("a2", 1, "youtube", Priority::Normal).await;
+        queue.enqueue("b1", 2, "youtube", Priority::Normal).await;
+        assert!(queue.acquire("a1").await);
+
+        let waiting = queue.clone();
+        let a2 = tokio::spawn(async move { waiting.acquire("a2").await });
+        tokio::task::yield_now().await;
+        // A free slot, but chat 1 is at its limit: b1 gets it despite arriving later
+        let b1 = tokio::time::timeout(std::time::Duration::from_secs(1), queue.acquire("b1")).await;
+        assert_eq!(b1.ok(), Some(true));
+        assert_eq!(queue.get_status("a2").await.unwrap().status, TaskState::Queued);
+
+        queue.complete("a1").await;
+        let a2 = tokio::time::timeout(std::time::Duration::from_secs(1), a2).await;
+        assert!(a2.expect("a2 should start once a1 is done").unwrap());
+        assert_eq!(queue.running_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_chats_take_turns() {
+        let queue = Arc::new(TaskQueue::new(1));
+        queue.enqueue("a0", 1, "youtube", Priority::Normal).await;
+        queue.acquire("a0").await;
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (id, chat_id) in [("a1", 1), ("a2", 1), ("b1", 2), ("b2", 2)] {
+            queue.enqueue(id, chat_id, "youtube", Priority::Normal).await;
+            let (queue, order) = (queue.clone(), order.clone());
+            handles.push(tokio::spawn(async move {
+                assert!(queue.acquire(id).await);
+                order.lock().unwrap().push(id);
+                queue.complete(id).await;
+            }));
+            tokio::task::yield_now().await;
+        }
+
+        queue.complete("a0").await;
+        for handle in handles {
+            tokio::time::timeout(std::time::Duration::from_secs(1), handle)
+                .await
+                .expect("every waiter should get a slot")
+                .unwrap();
+        }
+        // Chat 1 just had a turn, so chat 2 goes first and they alternate
+        assert_eq!(*order.lock().unwrap(), vec!["b1", "a1", "b2", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_acquire_leaves_the_line() {
+        let queue = TaskQueue::new(1);
+        queue.enqueue("t1", 1, "youtube", Priority::Normal).await;
+        queue.acquire("t1").await;
+        queue.enqueue("t2", 1, "youtube", Priority::High).await;
+        queue.enqueue("t3", 1, "youtube", Priority::Normal).await;
+
+        // t2 gives up waiting; t3 must not stay stuck behind it
+        let gave_up = tokio::time::timeout(std::time::Duration::from_millis(50), queue.acquire("t2")).await;
+        assert!(gave_up.is_err());
+        queue.complete("t1").await;
+        let got = tokio::time::timeout(std::time::Duration::from_secs(1), queue.acquire("t3")).await;
+        assert_eq!(got.ok(), Some(true));
+    }
+
+    #[test]
+    fn test_estimate_start_secs() {
+        // Free slot: starts immediately
+        assert_eq!(estimate_start_secs(120.0, 1, 0, 2), 0);
+        // Full, first in line: one of two slots frees up
+        assert_eq!(estimate_start_secs(120.0, 2, 0, 2), 60);
+        // Three tasks ahead on a single slot
+        assert_eq!(estimate_start_secs(60.0, 1, 3, 1), 240);
+    }
+
+    #[tokio::test]
+    async fn test_estimated_start_uses_moving_average() {
+        let queue = TaskQueue::new(1);
+        assert_eq!(queue.average_duration_secs().await, None);
+        queue.record_duration(100.0).await;
+        queue.record_duration(200.0).await;
+        assert_eq!(queue.average_duration_secs().await, Some(150.0));
+
+        queue.enqueue("t1", 1, "youtube", Priority::Normal).await;
+        queue.acquire("t1").await;
+        queue.enqueue("t2", 1, "youtube", Priority::Normal).await;
+        assert_eq!(queue.estimated_start_secs("t2").await, Some(150));
+        // Running tasks have no start estimate
+        assert_eq!(queue.estimated_start_secs("t1").await, None);
+
+        for _ in 0..DURATION_WINDOW {
+            queue.record_duration(10.0).await;
+        }
+        assert_eq!(queue.average_duration_secs().await, Some(10.0));
+    }
+
+    #[test]
+    fn test_eta_smoother_damps_noise() {
+        // True remaining time counts down 60, 58, 56... with spikes in the raw feed
+        let raw = [60, 5, 120, 54, 10, 150, 48, 46, 44, 42];
+        let mut smoother = EtaSmoother::new(0.3);
+        let smoothed: Vec<u64> = raw.iter().map(|&r| smoother.update(r, 2.0)).collect();
+
+        assert_eq!(smoothed[0], 60);
+        // Spikes are damped: never as low or high as the raw outliers
+        assert!(smoothed[1] > 30 && smoothed[2] < 90 && smoothed[5] < 100);
+        // Jumps between consecutive displayed values stay small
+        for w in smoothed.windows(2) {
+            assert!((w[0] as i64 - w[1] as i64).abs() < 45, "{:?}", smoothed);
+        }
+        // Settles near the real value once the feed calms down
+        assert!((smoothed[9] as i64 - 42).abs() < 15, "{:?}", smoothed);
+    }
+
+    #[test]
+    fn test_eta_smoother_ages_previous_estimate() {
+        let mut smoother = EtaSmoother::new(0.5);
+        smoother.update(100, 0.0);
+        // 20s later the worker still says 100: estimate is blended with 80
+        assert_eq!(smoother.update(100, 20.0), 90);
+        assert_eq!(smoother.current(), Some(90));
+    }
+
+    #[tokio::test]
+    async fn test_update_progress_returns_smoothed_eta() {
+        let queue = TaskQueue::new(1);
+        queue.enqueue("t1", 1, "youtube", Priority::Normal).await;
+        assert_eq!(queue.update_progress("t1", 10, None, Some(30)).await, Some(30));
+        assert_eq!(queue.update_progress("t1", 20, None, None).await, Some(30));
+        assert_eq!(queue.get_status("t1").await.unwrap().eta_secs, Some(30));
+        assert_eq!(queue.update_progress("missing", 20, None, Some(5)).await, None);
+    }
 }
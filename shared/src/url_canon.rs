@@ -0,0 +1,143 @@
+/// URL canonicalization, so the same media reached through different link
+/// forms (youtu.be vs watch?v=, shorts, tracking params) compares equal.
+use std::collections::HashSet;
+
+/// Query parameters that never change what is downloaded.
+const TRACKING_PARAMS: &[&str] = &["si", "feature", "fbclid", "gclid", "igshid", "pp", "ab_channel"];
+
+/// Canonical form of a URL, for duplicate detection only (the original URL is
+/// still what gets downloaded).
+///
+/// YouTube videos collapse to `https://www.youtube.com/watch?v=ID` and
+/// playlists to `.../playlist?list=ID`; a watch URL carrying both keeps both.
+/// Other URLs get a lowercase scheme and host, no `www.`/`m.` prefix, no
+/// fragment, no trailing slash and no tracking parameters.
+pub fn canonical_url(url: &str) -> String {
+    let url = url.trim();
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme.to_ascii_lowercase(), rest),
+        None => ("https".to_string(), url),
+    };
+    let rest = rest.split('#').next().unwrap_or("");
+    let (authority, path_query) = match rest.find(['/', '?']) {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let mut host = authority.to_ascii_lowercase();
+    for prefix in ["www.", "m."] {
+        if let Some(stripped) = host.strip_prefix(prefix) {
+            host = stripped.to_string();
+        }
+    }
+    let (path, query) = path_query.split_once('?').unwrap_or((path_query, ""));
+    let params: Vec<(&str, &str)> = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.split_once('=').unwrap_or((p, "")))
+        .collect();
+    let param = |name: &str| params.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+
+    if let Some(canonical) = canonical_youtube(&host, path, param("v"), param("list")) {
+        return canonical;
+    }
+
+    let kept: Vec<String> = params
+        .iter()
+        .filter(|(k, _)| !k.starts_with("utm_") && !TRACKING_PARAMS.contains(k))
+        .map(|(k, v)| if v.is_empty() { k.to_string() } else { format!("{}={}", k, v) })
+        .collect();
+    let mut canonical = format!("{}://{}{}", scheme, host, path.trim_end_matches('/'));
+    if !kept.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&kept.join("&"));
+    }
+    canonical
+}
+
+fn canonical_youtube(host: &str, path: &str, v: Option<&str>, list: Option<&str>) -> Option<String> {
+    let path_id = |prefix: &str| {
+        path.strip_prefix(prefix)
+            .map(|id| id.split('/').next().unwrap_or(""))
+            .filter(|id| !id.is_empty())
+    };
+    let video_id = match host {
+        "youtu.be" => path_id("/"),
+        "youtube.com" | "music.youtube.com" => v
+            .filter(|_| path == "/watch")
+            .or_else(|| path_id("/shorts/"))
+            .or_else(|| path_id("/embed/"))
+            .or_else(|| path_id("/live/")),
+        _ => return None,
+    };
+    match (video_id, list) {
+        (Some(id), Some(list)) => Some(format!("https://www.youtube.com/watch?v={}&list={}", id, list)),
+        (Some(id), None) => Some(format!("https://www.youtube.com/watch?v={}", id)),
+        (None, Some(list)) => Some(format!("https://www.youtube.com/playlist?list={}", list)),
+        (None, None) => None,
+    }
+}
+
+/// Drop URLs whose canonical form already appeared earlier in `urls`.
+/// Returns the kept URLs (first occurrence, original spelling) and how many
+/// duplicates were removed.
+pub fn dedup_urls<S: AsRef<str>>(urls: &[S]) -> (Vec<String>, usize) {
+    let mut seen = HashSet::new();
+    let kept: Vec<String> = urls
+        .iter()
+        .map(|u| u.as_ref())
+        .filter(|u| seen.insert(canonical_url(u)))
+        .map(String::from)
+        .collect();
+    let removed = urls.len() - kept.len();
+    (kept, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn youtube_forms_collapse_to_one_video() {
+        let forms = [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc123",
+            "http://m.youtube.com/watch?feature=share&v=dQw4w9WgXcQ",
+            "https://youtube.com/shorts/dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ#t=30",
+            "  https://WWW.YOUTUBE.COM/watch?v=dQw4w9WgXcQ  ",
+        ];
+        for form in forms {
+            assert_eq!(canonical_url(form), "https://www.youtube.com/watch?v=dQw4w9WgXcQ", "{}", form);
+        }
+        assert_eq!(
+            canonical_url("https://www.youtube.com/playlist?list=PL123&si=x"),
+            "https://www.youtube.com/playlist?list=PL123"
+        );
+        assert_ne!(
+            canonical_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ"),
+            canonical_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ")
+        );
+    }
+
+    #[test]
+    fn generic_urls_drop_noise_but_keep_meaningful_params() {
+        assert_eq!(
+            canonical_url("HTTPS://www.Example.com/video/42/?utm_source=x&id=7#top"),
+            "https://example.com/video/42?id=7"
+        );
+        assert_ne!(canonical_url("https://example.com/a?id=1"), canonical_url("https://example.com/a?id=2"));
+    }
+
+    #[test]
+    fn dedup_keeps_first_spelling_and_counts_removed() {
+        let urls = [
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://example.com/clip",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&si=1",
+            "https://example.com/clip/",
+        ];
+        let (kept, removed) = dedup_urls(&urls);
+        assert_eq!(kept, vec!["https://youtu.be/dQw4w9WgXcQ", "https://example.com/clip"]);
+        assert_eq!(removed, 2);
+    }
+}
@@ -0,0 +1,198 @@
+/// Registry of generic per-user settings stored in the `user_settings` table.
+///
+/// Core preferences (audio format/quality, default mode, dedup, video quality)
+/// stay as columns on `user_preferences`; everything else is a key-value pair
+/// that must be declared here so the bot and the web API validate it the same way.
+use chrono::{DateTime, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Allowed shape of a setting value.
+#[derive(Debug, Clone, Copy)]
+pub enum SettingKind {
+    /// Free text up to `max_len` characters.
+    Text { max_len: usize },
+    /// One of a fixed set of values.
+    Choice(&'static [&'static str]),
+    /// "true" / "false".
+    Bool,
+    /// Integer in an inclusive range.
+    Int { min: i64, max: i64 },
+    /// IANA timezone name (e.g. Europe/Lisbon).
+    Timezone,
+}
+
+/// A registered setting key.
+#[derive(Debug, Clone, Copy)]
+pub struct SettingDef {
+    pub key: &'static str,
+    pub default: &'static str,
+    pub kind: SettingKind,
+    pub description: &'static str,
+}
+
+/// All known setting keys.
+pub const REGISTRY: &[SettingDef] = &[
+    SettingDef {
+        key: "language",
+        default: "en",
+        kind: SettingKind::Text { max_len: 8 },
+        description: "Preferred language code (e.g. en, pt-BR)",
+    },
+    SettingDef {
+        key: "timezone",
+        default: "UTC",
+        kind: SettingKind::Timezone,
+        description: "IANA timezone used when showing dates (e.g. Europe/Lisbon)",
+    },
+    SettingDef {
+        key: "deliver_as_link",
+        default: "false",
+        kind: SettingKind::Bool,
+        description: "Send a download link instead of uploading files",
+    },
+    SettingDef {
+        key: "store_only",
+        default: "false",
+        kind: SettingKind::Bool,
+        description: "Keep downloads in your library (dashboard) instead of sending them to the chat",
+    },
+    SettingDef {
+        key: "audio_sample_rate",
+        default: "source",
+        kind: SettingKind::Choice(&["source", "44100", "48000"]),
+        description: "Audio sample rate in Hz (source = keep the original)",
+    },
+    SettingDef {
+        key: "audio_channels",
+        default: "source",
+        kind: SettingKind::Choice(&["source", "mono", "stereo"]),
+        description: "Audio channels; mono roughly halves the size of voice/podcast files",
+    },
+    SettingDef {
+        key: "caption_template",
+        default: crate::caption::USE_DEFAULT,
+        kind: SettingKind::Text { max_len: 300 },
+        description: "Caption on sent files: default, off, or a template with {title} {artist} {duration} {filename} {url} (\\n = new line)",
+    },
+];
+
+/// Look up a setting definition by key.
+pub fn find(key: &str) -> Option<&'static SettingDef> {
+    REGISTRY.iter().find(|d| d.key == key)
+}
+
+/// Default value for a key, if registered.
+pub fn default_for(key: &str) -> Option<&'static str> {
+    find(key).map(|d| d.default)
+}
+
+/// Validate a value for a key. Returns the normalized value to store,
+/// or a user-facing error message.
+pub fn validate(key: &str, value: &str) -> Result<String, String> {
+    let def = find(key).ok_or_else(|| format!("Unknown setting '{}'", key))?;
+    let value = value.trim();
+
+    match def.kind {
+        SettingKind::Text { max_len } => {
+            if value.is_empty() || value.chars().count() > max_len {
+                return Err(format!("{} must be 1-{} characters", key, max_len));
+            }
+            if value.chars().any(char::is_control) {
+                return Err(format!("{} contains invalid characters", key));
+            }
+            Ok(value.to_string())
+        }
+        SettingKind::Choice(options) => {
+            let lower = value.to_lowercase();
+            if options.contains(&lower.as_str()) {
+                Ok(lower)
+            } else {
+                Err(format!("{} must be one of: {}", key, options.join(", ")))
+            }
+        }
+        SettingKind::Bool => match value.to_lowercase().as_str() {
+            "true" | "on" | "yes" | "1" => Ok("true".to_string()),
+            "false" | "off" | "no" | "0" => Ok("false".to_string()),
+            _ => Err(format!("{} must be true or false", key)),
+        },
+        SettingKind::Int { min, max } => match value.parse::<i64>() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n.to_string()),
+            _ => Err(format!("{} must be a number between {} and {}", key, min, max)),
+        },
+        SettingKind::Timezone => parse_timezone(value)
+            .map(|tz| tz.name().to_string())
+            .ok_or_else(|| format!("{} must be an IANA timezone name like Europe/Lisbon or America/New_York", key)),
+    }
+}
+
+/// IANA timezone by name, ignoring case ("europe/lisbon" works too).
+fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse::<Tz>().ok().or_else(|| {
+        chrono_tz::TZ_VARIANTS.iter().copied().find(|tz| tz.name().eq_ignore_ascii_case(name))
+    })
+}
+
+/// Parse a stored `timezone` value, falling back to UTC.
+pub fn timezone(name: &str) -> Tz {
+    parse_timezone(name).unwrap_or(Tz::UTC)
+}
+
+/// Show a UTC timestamp (as stored in the database) in `tz`, e.g. "2024-05-01 14:32 WEST".
+pub fn format_local(utc: NaiveDateTime, tz: &str) -> String {
+    format_local_utc(Utc.from_utc_datetime(&utc), tz)
+}
+
+/// [`format_local`] for an aware UTC timestamp.
+pub fn format_local_utc(utc: DateTime<Utc>, tz: &str) -> String {
+    utc.with_timezone(&timezone(tz)).format("%Y-%m-%d %H:%M %Z").to_string()
+}
+
+/// Current offset of `tz` from UTC in seconds (for clients that cannot resolve IANA names).
+pub fn utc_offset_secs(tz: &str) -> i32 {
+    Utc::now().with_timezone(&timezone(tz)).offset().fix().local_minus_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        assert!(validate("no_such_setting", "x").is_err());
+        assert!(find("no_such_setting").is_none());
+    }
+
+    #[test]
+    fn test_text_setting() {
+        assert_eq!(validate("language", " pt-BR "), Ok("pt-BR".to_string()));
+        assert!(validate("language", "").is_err());
+        assert!(validate("language", "far-too-long").is_err());
+    }
+
+    #[test]
+    fn test_choice_setting() {
+        assert_eq!(validate("audio_channels", "Mono"), Ok("mono".to_string()));
+        assert_eq!(validate("audio_sample_rate", "48000"), Ok("48000".to_string()));
+        assert!(validate("audio_sample_rate", "96000").is_err());
+    }
+
+    #[test]
+    fn test_timezone_setting() {
+        assert_eq!(validate("timezone", "europe/lisbon"), Ok("Europe/Lisbon".to_string()));
+        assert!(validate("timezone", "Mars/Olympus").is_err());
+        assert!(validate("timezone", "+02:00").is_err());
+
+        let utc = NaiveDateTime::parse_from_str("2024-07-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(format_local(utc, "Europe/Lisbon"), "2024-07-01 13:00 WEST");
+        assert_eq!(format_local(utc, "America/New_York"), "2024-07-01 08:00 EDT");
+        // Unknown names stored before validation existed fall back to UTC
+        assert_eq!(format_local(utc, "nowhere"), "2024-07-01 12:00 UTC");
+    }
+
+    #[test]
+    fn test_defaults_pass_validation() {
+        for def in REGISTRY {
+            assert_eq!(validate(def.key, def.default), Ok(def.default.to_string()), "{}", def.key);
+        }
+    }
+}
@@ -226,10 +226,12 @@ function renderTaskCard(task) {
     let progressHtml = '';
     if (status === 'running') {
         const pct = task.progress || 0;
+        const live = [task.speed, task.eta_secs ? `ETA ${Math.ceil(task.eta_secs / 60)}m` : '']
+            .filter(Boolean).map(escapeHtml).join(' &middot; ');
         progressHtml = `
             <div class="progress-container">
                 <div class="progress-fill animated" style="width:${pct}%"></div>
-                <span class="progress-text">${pct}%</span>
+                <span class="progress-text">${pct}%${live ? ' &middot; ' + live : ''}</span>
             </div>
         `;
     }
@@ -270,6 +272,7 @@ function statusBadge(status) {
 
 async function loadTasks() {
     const data = await api.get('/api/tasks');
+    rememberTimezone(data);
     if (data && data.tasks) {
         allTasks = data.tasks;
         renderTasks();
@@ -298,9 +301,38 @@ async function retryTask(taskId) {
     }
 }
 
+function applyTaskEvent(event) {
+    const update = JSON.parse(event.data);
+    const task = allTasks.find(t => t.id === update.task_id);
+    if (!task) {
+        // A task we haven't listed yet
+        loadTasks();
+        return;
+    }
+    task.status = update.status;
+    task.progress = update.progress;
+    task.speed = update.speed;
+    task.eta_secs = update.eta_secs;
+    task.error_msg = update.error;
+    renderTasks();
+}
+
 function startPolling() {
     loadTasks();
-    pollInterval = setInterval(loadTasks, 3000);
+    if (!window.EventSource || !api.token) {
+        pollInterval = setInterval(loadTasks, 3000);
+        return;
+    }
+    // Live updates over SSE; the slow poll only picks up list changes
+    const events = new EventSource('/api/tasks/events?token=' + encodeURIComponent(api.token));
+    events.addEventListener('progress', applyTaskEvent);
+    events.addEventListener('status', applyTaskEvent);
+    events.onerror = () => {
+        if (events.readyState === EventSource.CLOSED && !pollInterval) {
+            pollInterval = setInterval(loadTasks, 3000);
+        }
+    };
+    setInterval(loadTasks, 30000);
 }
 
 // =============================================
@@ -312,6 +344,7 @@ async function loadFiles() {
     if (!container) return;
 
     const data = await api.get('/api/files');
+    rememberTimezone(data);
     if (!data || !data.files) return;
 
     if (data.files.length === 0) {
@@ -327,7 +360,7 @@ async function loadFiles() {
     // Group by date
     const groups = {};
     for (const file of data.files) {
-        const date = file.finished_at ? file.finished_at.substring(0, 10) : 'Unknown';
+        const date = file.finished_at ? formatDay(file.finished_at) : 'Unknown';
         if (!groups[date]) groups[date] = [];
         groups[date].push(file);
     }
@@ -585,16 +618,38 @@ function escapeHtml(str) {
     return str.replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/"/g, '&quot;');
 }
 
+// The user's `timezone` setting, as reported alongside task and file lists
+let userTimezone = undefined;
+
+function rememberTimezone(data) {
+    if (data && data.timezone) userTimezone = data.timezone;
+}
+
+// Server timestamps are UTC without a zone suffix
+function parseUtc(dateStr) {
+    return new Date(dateStr + (dateStr.includes('Z') ? '' : 'Z'));
+}
+
 function formatDate(dateStr) {
     if (!dateStr) return '';
     try {
-        const d = new Date(dateStr + (dateStr.includes('Z') ? '' : 'Z'));
-        return d.toLocaleDateString() + ' ' + d.toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' });
+        const d = parseUtc(dateStr);
+        return d.toLocaleDateString([], { timeZone: userTimezone }) + ' '
+            + d.toLocaleTimeString([], { hour: '2-digit', minute: '2-digit', timeZone: userTimezone });
     } catch (e) {
         return dateStr;
     }
 }
 
+// YYYY-MM-DD in the user's timezone, for grouping
+function formatDay(dateStr) {
+    try {
+        return parseUtc(dateStr).toLocaleDateString('en-CA', { timeZone: userTimezone });
+    } catch (e) {
+        return dateStr.substring(0, 10);
+    }
+}
+
 function extractFilename(path) {
     if (!path) return 'Unknown';
     const parts = path.replace(/\\/g, '/').split('/');
@@ -14,9 +14,14 @@ from worker.cookies import cookie_manager
 
 # Import handlers
 from worker.youtube_dl import handle_youtube_download
-from worker.youtube_search import handle_youtube_search, handle_get_video_info, handle_get_formats
+from worker.youtube_search import handle_youtube_search, handle_get_video_info, handle_get_formats, handle_probe, handle_get_thumbnail
+from worker.spotify import handle_spotify_resolve
 from worker.playlist_dl import handle_playlist_download
-from worker.playlist_utils import get_playlist_preview
+from worker.concat import handle_concat
+from worker.transcribe import handle_transcribe
+from worker.convert import handle_extract_audio, handle_compress, handle_split_media
+from worker.subtitles import handle_download_subtitles
+from worker.playlist_utils import get_playlist_preview, get_channel_latest
 
 # Import database and cache
 from worker.database import get_database, close_database
@@ -39,7 +44,16 @@ def setup_handlers():
     ipc_handler.register('youtube_search', handle_youtube_search)
     ipc_handler.register('get_video_info', handle_get_video_info)
     ipc_handler.register('get_formats', handle_get_formats)
+    ipc_handler.register('probe', handle_probe)
+    ipc_handler.register('spotify_resolve', handle_spotify_resolve)
+    ipc_handler.register('get_thumbnail', handle_get_thumbnail)
     ipc_handler.register('playlist', handle_playlist_download)
+    ipc_handler.register('concat', handle_concat)
+    ipc_handler.register('transcribe', handle_transcribe)
+    ipc_handler.register('extract_audio', handle_extract_audio)
+    ipc_handler.register('compress', handle_compress)
+    ipc_handler.register('split_media', handle_split_media)
+    ipc_handler.register('download_subtitles', handle_download_subtitles)
 
     # Playlist preview (list first N tracks without downloading)
     async def playlist_preview(ipc, task_id, request):
@@ -54,6 +68,19 @@ def setup_handlers():
 
     ipc_handler.register('playlist_preview', playlist_preview)
 
+    # Newest uploads of a channel (subscription checks)
+    async def channel_latest(ipc, task_id, request):
+        """List the newest N uploads of a channel or playlist."""
+        url = request.get('url')
+        count = request.get('params', {}).get('count', 10)
+        result = await get_channel_latest(url, count)
+        if result:
+            ipc.send_response(task_id, 'done', result)
+        else:
+            ipc.send_error(task_id, "Failed to list channel uploads")
+
+    ipc_handler.register('channel_latest', channel_latest)
+
     # Admin handlers
     async def cache_cleanup(ipc, task_id, request):
         """Cleanup expired cache entries."""
@@ -71,11 +98,16 @@ def setup_handlers():
     # Health check
     async def health_check(ipc, task_id, request):
         """Simple health check handler."""
+        try:
+            from yt_dlp.version import __version__ as yt_dlp_version
+        except ImportError:
+            yt_dlp_version = 'not installed'
         ipc.send_response(task_id, 'health_ok', {
             'worker': 'Hermes Media Worker',
             'version': '1.0.0-phase-c',
+            'yt_dlp_version': yt_dlp_version,
             'config': config.to_dict(),
-            'handlers': ['youtube_dl', 'youtube_search', 'get_video_info', 'get_formats', 'playlist', 'playlist_preview', 'cache_cleanup', 'cache_stats', 'health_check']
+            'handlers': ['youtube_dl', 'youtube_search', 'get_video_info', 'get_formats', 'probe', 'spotify_resolve', 'get_thumbnail', 'playlist', 'concat', 'transcribe', 'extract_audio', 'compress', 'split_media', 'download_subtitles', 'playlist_preview', 'channel_latest', 'cache_cleanup', 'cache_stats', 'health_check', 'cancel']
         })
 
     ipc_handler.register('health_check', health_check)
@@ -87,6 +119,10 @@ def setup_handlers():
         ipc_handler.register('mtproto_upload', handle_mtproto_upload)
         logger.info("✅ MTProto upload handler registered")
 
+        from worker.mtproto_join import handle_mtproto_copy_post
+        ipc_handler.register('mtproto_copy_post', handle_mtproto_copy_post)
+        logger.info("✅ MTProto channel copy handler registered")
+
 
 def log_startup():
     """Log startup information."""
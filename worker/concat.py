@@ -0,0 +1,181 @@
+"""
+Playlist concatenation for Hermes (/concat).
+
+The bot downloads a playlist with the normal 'playlist' action, then sends the
+track paths here in playlist order. `handle_concat` joins them into one
+continuous file. Tracks rarely share a format (sample rate, codec, resolution,
+frame rate), so every input is normalised and re-encoded through ffmpeg's
+concat filter rather than stream-copied.
+"""
+import asyncio
+import os
+import re
+import time
+import logging
+
+from worker.config import config
+from worker.error_handlers import get_error
+from worker.utils import sanitize_filename, safe_mkdir, kill_on_cancel
+
+logger = logging.getLogger(__name__)
+
+_DURATION_RE = re.compile(r'Duration:\s*(\d+):(\d+):(\d+(?:\.\d+)?)')
+_TIME_RE = re.compile(r'time=\s*(\d+):(\d+):(\d+(?:\.\d+)?)')
+
+# Common output shape for video mixes
+_VIDEO_WIDTH = 1280
+_VIDEO_HEIGHT = 720
+_VIDEO_FPS = 30
+
+
+def _seconds(match) -> float:
+    hours, minutes, seconds = match.groups()
+    return int(hours) * 3600 + int(minutes) * 60 + float(seconds)
+
+
+def _filter_graph(count: int, extract_audio: bool) -> str:
+    """ffmpeg filter that normalises each input and joins them in order."""
+    audio_norm = 'aresample=48000,aformat=sample_fmts=fltp:channel_layouts=stereo'
+    chains = []
+    labels = ''
+    for i in range(count):
+        chains.append(f'[{i}:a:0]{audio_norm}[a{i}]')
+        if extract_audio:
+            labels += f'[a{i}]'
+        else:
+            chains.append(
+                f'[{i}:v:0]scale={_VIDEO_WIDTH}:{_VIDEO_HEIGHT}:force_original_aspect_ratio=decrease,'
+                f'pad={_VIDEO_WIDTH}:{_VIDEO_HEIGHT}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={_VIDEO_FPS}[v{i}]'
+            )
+            labels += f'[v{i}][a{i}]'
+    if extract_audio:
+        chains.append(f'{labels}concat=n={count}:v=0:a=1[outa]')
+    else:
+        chains.append(f'{labels}concat=n={count}:v=1:a=1[outv][outa]')
+    return ';'.join(chains)
+
+
+async def handle_concat(ipc, task_id: str, request: dict) -> None:
+    """
+    Join already-downloaded tracks into a single file.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "concat",
+        "params": {
+            "files": ["/downloads/.../001 - a.mp3", "/downloads/.../002 - b.mp3"],
+            "extract_audio": true,
+            "title": "My Playlist",
+            "output_dir": "/downloads/..."
+        }
+    }
+
+    Responds with `done` carrying `file_path`, `filename`, `track_count` and
+    `duration`, or an error with code CONCAT_FAILED.
+    """
+    params = request.get('params', {})
+    files = [f for f in params.get('files', []) if isinstance(f, str) and os.path.isfile(f)]
+    extract_audio = bool(params.get('extract_audio', True))
+    title = sanitize_filename(params.get('title') or 'Playlist')
+    output_dir = params.get('output_dir', config.DOWNLOAD_DIR)
+
+    if not files:
+        error = get_error('CONCAT_FAILED', 'None of the playlist tracks could be downloaded.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    safe_mkdir(output_dir)
+    ext = '.mp3' if extract_audio else '.mp4'
+    output_file = os.path.join(output_dir, f'{title} (mix){ext}')
+    partial_file = os.path.join(output_dir, f'{title} (mix).joining{ext}')
+
+    command = ['ffmpeg', '-y', '-hide_banner']
+    for path in files:
+        command.extend(['-i', path])
+    command.extend(['-filter_complex', _filter_graph(len(files), extract_audio)])
+    if extract_audio:
+        command.extend(['-map', '[outa]', '-c:a', 'libmp3lame', '-b:a', '192k'])
+    else:
+        command.extend([
+            '-map', '[outv]', '-map', '[outa]',
+            '-c:v', 'libx264', '-preset', 'veryfast', '-crf', '23',
+            '-c:a', 'aac', '-b:a', '192k',
+            '-movflags', '+faststart',
+        ])
+    command.append(partial_file)
+
+    logger.info(f"[{task_id}] Concatenating {len(files)} tracks into {os.path.basename(output_file)}")
+    ipc.send_progress(task_id, 0, status='joining tracks')
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.DEVNULL,
+            stderr=asyncio.subprocess.PIPE,
+        )
+    except FileNotFoundError:
+        error = get_error('CONCAT_FAILED', 'ffmpeg is not installed on the worker.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    tail = []  # last ffmpeg lines, for the log on failure
+    total = 0.0  # sum of input durations, printed before encoding starts
+
+    async def read_progress():
+        nonlocal total
+        last_sent = 0.0
+        buffer = ''
+        while True:
+            chunk = await process.stderr.read(4096)
+            if not chunk:
+                break
+            buffer += chunk.decode('utf-8', errors='replace')
+            # ffmpeg rewrites its stats line with '\r'
+            *lines, buffer = re.split(r'[\r\n]', buffer)
+            for line in filter(None, (l.strip() for l in lines)):
+                tail.append(line)
+                del tail[:-20]
+                match = _DURATION_RE.search(line)
+                if match:
+                    total += _seconds(match)
+                    continue
+                match = _TIME_RE.search(line)
+                if match and total and time.monotonic() - last_sent >= 2:
+                    percent = min(99, int(_seconds(match) / total * 100))
+                    ipc.send_progress(task_id, percent, status='joining tracks')
+                    last_sent = time.monotonic()
+
+    try:
+        with kill_on_cancel(process):
+            await asyncio.wait_for(read_progress(), timeout=config.CONCAT_TIMEOUT)
+            returncode = await process.wait()
+    except asyncio.TimeoutError:
+        process.kill()
+        await process.wait()
+        returncode = None
+        tail.append(f'timed out after {config.CONCAT_TIMEOUT}s')
+
+    if returncode != 0:
+        logger.error(f"[{task_id}] Concatenation failed (exit {returncode}):")
+        for line in tail:
+            logger.error(f"[{task_id}]   {line}")
+        try:
+            os.remove(partial_file)
+        except OSError:
+            pass
+        error = get_error('CONCAT_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    os.replace(partial_file, output_file)
+    size = os.path.getsize(output_file)
+    logger.info(f"[{task_id}] Mix ready: {os.path.basename(output_file)} ({size / (1024 * 1024):.1f} MB)")
+    ipc.send_progress(task_id, 100, status='completed')
+    ipc.send_response(task_id, 'done', {
+        'file_path': output_file,
+        'filename': os.path.basename(output_file),
+        'file_size': size,
+        'track_count': len(files),
+        'duration': int(total),
+    })
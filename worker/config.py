@@ -21,10 +21,22 @@ class WorkerConfig:
     NODE_BIN: str = os.getenv('NODE_BIN', '')
     MAX_RETRIES: int = int(os.getenv('MAX_RETRIES', '3'))
     RETRY_DELAY_SECONDS: int = int(os.getenv('RETRY_DELAY_SECONDS', '5'))
+    # Per-download bandwidth cap, e.g. "2M" (bytes/s); empty = unlimited
+    DOWNLOAD_RATE_LIMIT: str = os.getenv('DOWNLOAD_RATE_LIMIT', '')
 
     # Timeouts
     YT_TIMEOUT: int = int(os.getenv('YT_TIMEOUT', '300'))  # 5 minutes
     IPC_TIMEOUT: int = int(os.getenv('IPC_TIMEOUT', '600'))  # 10 minutes
+    # Re-encoding for burned-in subtitles (/hardsubs), on top of the download
+    SUBTITLE_BURN_TIMEOUT: int = int(os.getenv('HARDSUBS_TIMEOUT_SECS', '3600'))  # 1 hour
+    # Joining playlist tracks into one file (/concat), after the tracks are downloaded
+    CONCAT_TIMEOUT: int = int(os.getenv('CONCAT_TIMEOUT_SECS', '3600'))  # 1 hour
+    # Speech-to-text of a downloaded file (/transcribe)
+    TRANSCRIBE_TIMEOUT: int = int(os.getenv('TRANSCRIBE_TIMEOUT_SECS', '3600'))  # 1 hour
+    # Two-pass re-encode of an oversized video to fit the upload limit (/fit)
+    COMPRESS_TIMEOUT: int = int(os.getenv('COMPRESS_TIMEOUT_SECS', '1800'))  # 30 min
+    # Whisper model size: tiny, base, small, medium, large-v3 (bigger = slower, more accurate)
+    WHISPER_MODEL: str = os.getenv('WHISPER_MODEL', 'base')
 
     # Output directories
     DOWNLOAD_DIR: str = os.getenv('DOWNLOAD_DIR', './downloads')
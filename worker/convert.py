@@ -0,0 +1,388 @@
+"""
+Local-file conversions for Hermes.
+
+`handle_extract_audio` pulls the audio track out of a file the worker already
+downloaded (used by /both, which sends the video and its audio separately), so
+the source is never fetched twice. `handle_compress` re-encodes a video down to
+a target size (used by /fit, to get under Telegram's upload limit).
+`handle_split_media` cuts a file that is too big to upload into playable parts.
+"""
+import asyncio
+import glob
+import math
+import os
+import logging
+
+from worker.config import config
+from worker.error_handlers import get_error
+from worker.ipc import local_file_ref
+from worker.utils import kill_on_cancel
+
+logger = logging.getLogger(__name__)
+
+# Output codec arguments per audio format
+_AUDIO_CODECS = {
+    'mp3': ['-c:a', 'libmp3lame', '-q:a', '0'],
+    'm4a': ['-c:a', 'aac', '-b:a', '192k'],
+    'opus': ['-c:a', 'libopus', '-b:a', '160k'],
+    'flac': ['-c:a', 'flac'],
+}
+
+# Re-encoding only the audio track is quick; this is generous for long videos
+_EXTRACT_TIMEOUT = 600
+
+
+async def handle_extract_audio(ipc, task_id: str, request: dict) -> None:
+    """
+    Extract the first audio track of a local file.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "extract_audio",
+        "params": {
+            "file": "/downloads/.../Video.mp4",
+            "audio_format": "mp3",
+            "output_dir": "/downloads/..."
+        }
+    }
+
+    Responds with `done` carrying `file_path`, `filename` and `file_size`, or
+    an error with code EXTRACT_AUDIO_FAILED.
+    """
+    params = request.get('params', {})
+    source = params.get('file', '')
+    audio_format = params.get('audio_format') or 'mp3'
+    if audio_format not in _AUDIO_CODECS:
+        audio_format = 'mp3'
+    output_dir = params.get('output_dir') or os.path.dirname(source)
+
+    if not source or not os.path.isfile(source):
+        error = get_error('EXTRACT_AUDIO_FAILED', 'The downloaded video could not be found.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    stem = os.path.splitext(os.path.basename(source))[0]
+    output_file = os.path.join(output_dir, f'{stem}.{audio_format}')
+    if os.path.abspath(output_file) == os.path.abspath(source):
+        output_file = os.path.join(output_dir, f'{stem} (audio).{audio_format}')
+
+    command = ['ffmpeg', '-y', '-hide_banner', '-loglevel', 'error',
+               '-i', source, '-vn', '-map', '0:a:0', *_AUDIO_CODECS[audio_format], output_file]
+
+    logger.info(f"[{task_id}] Extracting {audio_format} audio from {os.path.basename(source)}")
+    ipc.send_progress(task_id, 0, status='extracting audio')
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.DEVNULL,
+            stderr=asyncio.subprocess.PIPE,
+        )
+    except FileNotFoundError:
+        error = get_error('EXTRACT_AUDIO_FAILED', 'ffmpeg is not installed on the worker.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    try:
+        with kill_on_cancel(process):
+            _, stderr = await asyncio.wait_for(process.communicate(), timeout=_EXTRACT_TIMEOUT)
+    except asyncio.TimeoutError:
+        process.kill()
+        await process.wait()
+        stderr = f'timed out after {_EXTRACT_TIMEOUT}s'.encode()
+
+    if process.returncode != 0 or not os.path.isfile(output_file):
+        logger.error(f"[{task_id}] Audio extraction failed: {stderr.decode('utf-8', errors='replace').strip()[-500:]}")
+        try:
+            os.remove(output_file)
+        except OSError:
+            pass
+        error = get_error('EXTRACT_AUDIO_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    size = os.path.getsize(output_file)
+    logger.info(f"[{task_id}] Audio ready: {os.path.basename(output_file)} ({size / (1024 * 1024):.1f} MB)")
+    ipc.send_progress(task_id, 100, status='completed')
+    ipc.send_response(task_id, 'done', {
+        'file_path': output_file,
+        'filename': os.path.basename(output_file),
+        'file_size': size,
+    })
+
+
+# Bitrate budget for /fit: container overhead eats a few percent of the target
+_COMPRESS_OVERHEAD = 0.96
+_COMPRESS_AUDIO_KBPS = 96
+# Below this the picture is unwatchable; fall back to a link instead
+_COMPRESS_MIN_VIDEO_KBPS = 150
+
+
+def _compress_scale(video_kbps: int):
+    """Height cap for a video bitrate: fewer pixels look better than heavy blocking."""
+    if video_kbps < 500:
+        return 480
+    if video_kbps < 1200:
+        return 720
+    return None
+
+
+async def _probe_duration(path: str) -> float:
+    """Duration in seconds via ffprobe, 0 when unknown."""
+    try:
+        process = await asyncio.create_subprocess_exec(
+            'ffprobe', '-v', 'error', '-show_entries', 'format=duration',
+            '-of', 'default=noprint_wrappers=1:nokey=1', path,
+            stdout=asyncio.subprocess.PIPE,
+            stderr=asyncio.subprocess.DEVNULL,
+        )
+        stdout, _ = await asyncio.wait_for(process.communicate(), timeout=30)
+        return float(stdout.decode().strip() or 0)
+    except (OSError, ValueError, asyncio.TimeoutError):
+        return 0.0
+
+
+async def _run_pass(ipc, task_id: str, command: list, duration: float, pass_no: int, deadline: float) -> bytes:
+    """
+    Run one ffmpeg pass, reporting progress from its `-progress` output as
+    half of the overall percentage. Returns stderr; raises TimeoutError.
+    """
+    process = await asyncio.create_subprocess_exec(
+        *command,
+        stdout=asyncio.subprocess.PIPE,
+        stderr=asyncio.subprocess.PIPE,
+    )
+    stderr_task = asyncio.ensure_future(process.stderr.read())
+    loop = asyncio.get_running_loop()
+    last_percent = -1
+    try:
+        with kill_on_cancel(process):
+            while True:
+                remaining = deadline - loop.time()
+                if remaining <= 0:
+                    raise asyncio.TimeoutError()
+                line = await asyncio.wait_for(process.stdout.readline(), timeout=remaining)
+                if not line:
+                    break
+                key, _, value = line.decode(errors='replace').strip().partition('=')
+                # out_time_ms is also microseconds (an old ffmpeg misnomer)
+                if key in ('out_time_us', 'out_time_ms') and value.isdigit() and duration > 0:
+                    done = min(1.0, int(value) / 1_000_000 / duration)
+                    percent = int(((pass_no - 1) + done) * 50)
+                    if percent >= last_percent + 5:
+                        last_percent = percent
+                        ipc.send_progress(task_id, percent, status=f'compressing (pass {pass_no}/2)')
+            await asyncio.wait_for(process.wait(), timeout=max(1, deadline - loop.time()))
+    except asyncio.TimeoutError:
+        process.kill()
+        await process.wait()
+        raise
+    finally:
+        stderr = await stderr_task
+    if process.returncode != 0:
+        raise RuntimeError(stderr.decode('utf-8', errors='replace').strip()[-500:])
+    return stderr
+
+
+async def handle_compress(ipc, task_id: str, request: dict) -> None:
+    """
+    Re-encode a local video to fit a target size (two-pass H.264 + AAC).
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "compress",
+        "params": {
+            "file": "/downloads/.../Video.mp4",
+            "target_size": 50331648,   // bytes
+            "output_dir": "/downloads/..."
+        }
+    }
+
+    The video bitrate is what is left of the size budget over the duration
+    after the audio. Responds with `done` carrying `file_path`, `file_ref`,
+    `filename`, `file_size` and `video_kbps`, or an error with code
+    COMPRESS_CANNOT_FIT (the budget is too small for a watchable video, or the
+    result still came out too big) or COMPRESS_FAILED.
+    """
+    params = request.get('params', {})
+    source = params.get('file', '')
+    target_size = int(params.get('target_size') or 0)
+    output_dir = params.get('output_dir') or os.path.dirname(source)
+
+    if not source or not os.path.isfile(source) or target_size <= 0:
+        error = get_error('COMPRESS_FAILED', 'The downloaded video could not be found.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    duration = await _probe_duration(source)
+    if duration <= 0:
+        error = get_error('COMPRESS_FAILED', 'Could not read the video length.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    total_kbps = int(target_size * 8 * _COMPRESS_OVERHEAD / 1000 / duration)
+    video_kbps = total_kbps - _COMPRESS_AUDIO_KBPS
+    if video_kbps < _COMPRESS_MIN_VIDEO_KBPS:
+        logger.info(f"[{task_id}] Cannot fit {duration:.0f}s into {target_size} bytes ({video_kbps} kbps video)")
+        error = get_error('COMPRESS_CANNOT_FIT')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    stem = os.path.splitext(os.path.basename(source))[0]
+    output_file = os.path.join(output_dir, f'{stem} (compressed).mp4')
+    passlog = os.path.join(output_dir, f'.{task_id}-ffmpeg2pass')
+    video_args = ['-c:v', 'libx264', '-preset', 'medium', '-b:v', f'{video_kbps}k',
+                  '-passlogfile', passlog]
+    height = _compress_scale(video_kbps)
+    if height:
+        video_args += ['-vf', f"scale=-2:'min({height},ih)'"]
+    base = ['ffmpeg', '-y', '-hide_banner', '-loglevel', 'error', '-progress', 'pipe:1', '-i', source]
+    first = base + video_args + ['-pass', '1', '-an', '-f', 'null', os.devnull]
+    second = base + video_args + ['-pass', '2', '-c:a', 'aac', '-b:a', f'{_COMPRESS_AUDIO_KBPS}k',
+                                  '-movflags', '+faststart', output_file]
+
+    logger.info(
+        f"[{task_id}] Compressing {os.path.basename(source)} ({duration:.0f}s) "
+        f"to {target_size / (1024 * 1024):.0f} MB: video {video_kbps} kbps, height cap {height}"
+    )
+    ipc.send_progress(task_id, 0, status='compressing (pass 1/2)')
+    deadline = asyncio.get_running_loop().time() + config.COMPRESS_TIMEOUT
+
+    try:
+        await _run_pass(ipc, task_id, first, duration, 1, deadline)
+        await _run_pass(ipc, task_id, second, duration, 2, deadline)
+    except FileNotFoundError:
+        error = get_error('COMPRESS_FAILED', 'ffmpeg is not installed on the worker.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+    except (asyncio.TimeoutError, RuntimeError) as e:
+        reason = f'timed out after {config.COMPRESS_TIMEOUT}s' if isinstance(e, asyncio.TimeoutError) else str(e)
+        logger.error(f"[{task_id}] Compression failed: {reason}")
+        _remove_quietly(output_file)
+        error = get_error('COMPRESS_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+    finally:
+        for suffix in ('-0.log', '-0.log.mbtree'):
+            _remove_quietly(passlog + suffix)
+
+    size = os.path.getsize(output_file) if os.path.isfile(output_file) else 0
+    if not size or size > target_size:
+        logger.warning(f"[{task_id}] Compressed file is {size} bytes, over the {target_size} target")
+        _remove_quietly(output_file)
+        error = get_error('COMPRESS_CANNOT_FIT')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    logger.info(f"[{task_id}] Compressed: {os.path.basename(output_file)} ({size / (1024 * 1024):.1f} MB)")
+    ipc.send_progress(task_id, 100, status='completed')
+    ipc.send_response(task_id, 'done', {
+        'file_path': output_file,
+        'file_ref': local_file_ref(output_file),
+        'filename': os.path.basename(output_file),
+        'file_size': size,
+        'video_kbps': video_kbps,
+    })
+
+
+def _remove_quietly(path: str) -> None:
+    try:
+        os.remove(path)
+    except OSError:
+        pass
+
+
+# Segments are cut at keyframes, so aim under the part size to leave slack
+_SPLIT_HEADROOM = 0.9
+_SPLIT_TIMEOUT = 900
+
+
+async def handle_split_media(ipc, task_id: str, request: dict) -> None:
+    """
+    Cut a local audio/video file into time segments without re-encoding.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "split_media",
+        "params": {
+            "file": "/downloads/.../Album.mp3",
+            "part_size": 50331648,   // bytes, upper bound per part
+            "output_dir": "/downloads/..."
+        }
+    }
+
+    Parts are named "<stem> (part N of M).<ext>". Responds with `done` carrying
+    `parts` (paths in order), or an error with code SPLIT_FAILED when ffmpeg
+    fails or a part still comes out over `part_size`; the bot then falls back
+    to a byte split.
+    """
+    params = request.get('params', {})
+    source = params.get('file', '')
+    part_size = int(params.get('part_size') or 0)
+    output_dir = params.get('output_dir') or os.path.dirname(source)
+
+    if not source or not os.path.isfile(source) or part_size <= 0:
+        error = get_error('SPLIT_FAILED', 'The downloaded file could not be found.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    size = os.path.getsize(source)
+    duration = await _probe_duration(source)
+    if duration <= 0:
+        error = get_error('SPLIT_FAILED', 'Could not read the media length.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    segment_secs = max(1, int(duration * part_size * _SPLIT_HEADROOM / size))
+    stem, ext = os.path.splitext(os.path.basename(source))
+    pattern = os.path.join(output_dir, f'.{task_id}-split-%03d{ext}')
+    command = ['ffmpeg', '-y', '-hide_banner', '-loglevel', 'error', '-i', source,
+               '-map', '0', '-c', 'copy', '-f', 'segment', '-segment_time', str(segment_secs),
+               '-reset_timestamps', '1', pattern]
+
+    logger.info(f"[{task_id}] Splitting {os.path.basename(source)} ({size / (1024 * 1024):.1f} MB) "
+                f"into ~{math.ceil(duration / segment_secs)} parts of {segment_secs}s")
+    ipc.send_progress(task_id, 0, status='splitting')
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.DEVNULL,
+            stderr=asyncio.subprocess.PIPE,
+        )
+    except FileNotFoundError:
+        error = get_error('SPLIT_FAILED', 'ffmpeg is not installed on the worker.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    try:
+        with kill_on_cancel(process):
+            _, stderr = await asyncio.wait_for(process.communicate(), timeout=_SPLIT_TIMEOUT)
+    except asyncio.TimeoutError:
+        process.kill()
+        await process.wait()
+        stderr = f'timed out after {_SPLIT_TIMEOUT}s'.encode()
+
+    segments = sorted(glob.glob(os.path.join(glob.escape(output_dir), f'.{task_id}-split-*{ext}')))
+    oversized = [p for p in segments if os.path.getsize(p) > part_size]
+    if process.returncode != 0 or not segments or oversized:
+        reason = 'a part is still too big' if oversized else stderr.decode('utf-8', errors='replace').strip()[-500:]
+        logger.error(f"[{task_id}] Split failed: {reason}")
+        for path in segments:
+            _remove_quietly(path)
+        error = get_error('SPLIT_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    parts = []
+    for number, segment in enumerate(segments, start=1):
+        part = os.path.join(output_dir, f'{stem} (part {number} of {len(segments)}){ext}')
+        os.replace(segment, part)
+        parts.append(part)
+
+    logger.info(f"[{task_id}] Split into {len(parts)} parts")
+    ipc.send_progress(task_id, 100, status='completed')
+    ipc.send_response(task_id, 'done', {'parts': parts})
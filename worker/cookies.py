@@ -22,7 +22,7 @@ class CookieManager:
         self.loaded: bool = False
         self.last_validated: Optional[datetime] = None
 
-    def get_cookie_file(self) -> Optional[str]:
+    def get_cookie_file(self, fresh: bool = False) -> Optional[str]:
         """
         Get a TEMP COPY of the cookie file for yt-dlp.
 
@@ -31,7 +31,8 @@ class CookieManager:
         cookies, we always give yt-dlp a temp copy, not the real file.
 
         The copy is refreshed whenever the source file changes, so /upcook
-        updates are always picked up on the next download.
+        updates are always picked up on the next download. `fresh` re-copies
+        it regardless, dropping whatever yt-dlp wrote into it since.
 
         Returns temp cookie file path or None if unavailable.
         """
@@ -49,7 +50,7 @@ class CookieManager:
                     source_hash = hashlib.md5(f.read()).hexdigest()
 
                 # Only copy if temp doesn't exist or source changed
-                if not os.path.exists(temp_path) or self.cookie_path != temp_path \
+                if fresh or not os.path.exists(temp_path) or self.cookie_path != temp_path \
                         or getattr(self, '_source_hash', None) != source_hash:
                     shutil.copy2(source_path, temp_path)
                     try:
@@ -137,13 +138,13 @@ class CookieManager:
         except Exception as e:
             logger.error(f"Cookie verification error: {e}")
 
-    def build_yt_dlp_args(self) -> list:
+    def build_yt_dlp_args(self, fresh: bool = False) -> list:
         """
         Build yt-dlp command arguments for cookie handling.
 
         Returns empty list if no cookie file is available.
         """
-        cookie_file = self.get_cookie_file()
+        cookie_file = self.get_cookie_file(fresh)
         if cookie_file:
             return ['--cookies', cookie_file]
         return []
@@ -177,9 +178,9 @@ def get_cookies_file() -> Optional[str]:
     return cookie_manager.get_cookie_file()
 
 
-def get_yt_dlp_cookie_args() -> list:
+def get_yt_dlp_cookie_args(fresh: bool = False) -> list:
     """Convenience function to get yt-dlp cookie arguments."""
-    return cookie_manager.build_yt_dlp_args()
+    return cookie_manager.build_yt_dlp_args(fresh)
 
 
 def validate_cookies() -> bool:
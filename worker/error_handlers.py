@@ -148,8 +148,113 @@ ERROR_DEFINITIONS = {
         category=ErrorCategory.PERMANENT,
         retriable=False,
     ),
+    'SUBTITLES_UNAVAILABLE': WorkerError(
+        code='SUBTITLES_UNAVAILABLE',
+        user_message='No subtitles are available in that language.',
+        technical_message='Requested subtitle language not offered',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'SUBTITLES_FAILED': WorkerError(
+        code='SUBTITLES_FAILED',
+        user_message='Could not download the subtitles.',
+        technical_message='yt-dlp subtitle fetch failed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'SUBTITLE_BURN_FAILED': WorkerError(
+        code='SUBTITLE_BURN_FAILED',
+        user_message='Could not burn the subtitles into the video.',
+        technical_message='ffmpeg subtitle re-encode failed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'CONCAT_FAILED': WorkerError(
+        code='CONCAT_FAILED',
+        user_message='Could not join the playlist tracks into one file.',
+        technical_message='ffmpeg concat re-encode failed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'CLIP_UNRESOLVED': WorkerError(
+        code='CLIP_UNRESOLVED',
+        user_message='Could not find the video behind this clip. It may have been deleted or made private.',
+        technical_message='yt-dlp returned no source video or time range for the clip',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'INVALID_SECTION': WorkerError(
+        code='INVALID_SECTION',
+        user_message='That clip time range is not valid.',
+        technical_message='section_start/section_end missing, negative or reversed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'SPOTIFY_LOOKUP_FAILED': WorkerError(
+        code='SPOTIFY_LOOKUP_FAILED',
+        user_message='Could not look up this Spotify track.',
+        technical_message='Spotify track page missing or without title/artist metadata',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'EXTRACT_AUDIO_FAILED': WorkerError(
+        code='EXTRACT_AUDIO_FAILED',
+        user_message='Could not extract the audio from the video.',
+        technical_message='ffmpeg audio extraction failed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'COMPRESS_FAILED': WorkerError(
+        code='COMPRESS_FAILED',
+        user_message='Could not compress the video.',
+        technical_message='ffmpeg two-pass re-encode failed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'COMPRESS_CANNOT_FIT': WorkerError(
+        code='COMPRESS_CANNOT_FIT',
+        user_message='The video is too long to fit the size limit at a watchable quality.',
+        technical_message='Bitrate budget below the minimum, or output over target',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'SPLIT_FAILED': WorkerError(
+        code='SPLIT_FAILED',
+        user_message='Could not split the file into parts.',
+        technical_message='ffmpeg segmenting failed or a part exceeded the size limit',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'TRANSCRIBE_FAILED': WorkerError(
+        code='TRANSCRIBE_FAILED',
+        user_message='Could not transcribe the audio.',
+        technical_message='Whisper transcription failed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'TRANSCRIBER_UNAVAILABLE': WorkerError(
+        code='TRANSCRIBER_UNAVAILABLE',
+        user_message='Transcription is not set up on this server.',
+        technical_message='Neither faster-whisper nor openai-whisper is installed',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
+    'NO_SPEECH': WorkerError(
+        code='NO_SPEECH',
+        user_message='No speech was detected in the audio.',
+        technical_message='Transcription produced no segments',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
 
     # System errors
+    'CANCELLED': WorkerError(
+        code='CANCELLED',
+        user_message='Cancelled.',
+        technical_message='Task cancelled by a cancel request',
+        category=ErrorCategory.PERMANENT,
+        retriable=False,
+    ),
     'UNKNOWN_ERROR': WorkerError(
         code='UNKNOWN_ERROR',
         user_message='Unknown error occurred. Check logs.',
@@ -3,12 +3,17 @@ IPC Protocol Handler for Hermes Media Worker
 Handles JSON communication via stdin/stdout with Rust bot
 """
 
+import asyncio
+import itertools
 import json
 import sys
 import logging
-from typing import Dict, Callable, Optional, Any
+from collections import deque
+from typing import Deque, Dict, Callable, Optional, Any, Tuple
 from dataclasses import asdict
 
+from worker.error_handlers import get_error
+
 
 # Setup logging
 logging.basicConfig(
@@ -18,6 +23,21 @@ logging.basicConfig(
 )
 logger = logging.getLogger(__name__)
 
+# Quick requests a user is waiting on; they run before queued downloads.
+# Mirrors `Priority::for_action` in the bot's python_dispatcher.rs.
+INTERACTIVE_ACTIONS = frozenset({
+    'health_check', 'get_formats', 'get_video_info', 'probe', 'spotify_resolve',
+    'get_thumbnail', 'youtube_search', 'playlist_preview', 'cache_stats',
+    'mtproto_copy_post',
+})
+
+
+def request_priority(request: Optional[Dict[str, Any]]) -> int:
+    """Queue rank of a request: 0 interactive, 1 bulk, 2 for the end-of-input marker."""
+    if request is None:
+        return 2
+    return 0 if request.get('action') in INTERACTIVE_ACTIONS else 1
+
 
 class IPCHandler:
     """
@@ -26,12 +46,24 @@ class IPCHandler:
     Communication is line-delimited JSON:
     - Rust sends requests via stdin
     - Python sends responses via stdout
+
+    Requests run one at a time. Queued interactive requests (see
+    INTERACTIVE_ACTIONS) go before queued downloads, otherwise first come
+    first served. stdin is read alongside them so a 'cancel' request can stop
+    the running one (or drop a queued one).
     """
 
     def __init__(self):
         self.handlers: Dict[str, Callable] = {}
         self.request_count = 0
         self.response_count = 0
+        # (task_id, asyncio task) of the request being handled
+        self.current: Optional[Tuple[str, asyncio.Task]] = None
+        # Cancelled before they started. The request may still be on its way
+        # (queued in the bot), so ids are kept a while rather than matched now.
+        self.cancelled: Deque[str] = deque(maxlen=256)
+        # Tie-breaker keeping arrival order within a priority
+        self.sequence = itertools.count()
 
     def register(self, action: str, handler: Callable) -> None:
         """
@@ -107,6 +139,26 @@ class IPCHandler:
         }
         self.send_response(task_id, 'progress', data)
 
+    def send_retry(self, task_id: str, attempt: int, max_attempts: Optional[int] = None,
+                   reason: Optional[str] = None) -> None:
+        """
+        Tell the Rust bot an operation failed transiently and is being retried.
+
+        Not terminal: the task keeps running and still ends with 'done' or 'error'.
+
+        Args:
+            task_id: Task ID
+            attempt: Number of the attempt about to start (2 = first retry)
+            max_attempts: Total attempts allowed, if bounded
+            reason: Short description of the failure being retried
+        """
+        data: Dict[str, Any] = {'attempt': attempt}
+        if max_attempts:
+            data['max_attempts'] = max_attempts
+        if reason:
+            data['reason'] = reason
+        self.send_response(task_id, 'retry', data)
+
     async def process_request(self, request: Dict[str, Any]) -> None:
         """
         Process single IPC request.
@@ -139,17 +191,36 @@ class IPCHandler:
             self.send_error(task_id, f"Handler error: {str(e)}")
             logger.error(f"Exception in process_request: {e}", exc_info=True)
 
-    async def run(self) -> None:
+    def cancel(self, task_id: str) -> bool:
         """
-        Main event loop - read JSON from stdin, dispatch to handlers.
-
-        This runs indefinitely, reading one JSON object per line.
+        Stop `task_id`: cancel it if it is running (its handler's subprocess is
+        killed), otherwise skip it when it comes up. Returns whether it was running.
         """
-        logger.info("🚀 Hermes Media Worker started")
-        logger.info(f"Registered handlers: {list(self.handlers.keys())}")
-
+        if self.current and self.current[0] == task_id:
+            logger.info(f"Cancelling running task {task_id}")
+            self.current[1].cancel()
+            return True
+        logger.info(f"Cancelling task {task_id} before it starts")
+        self.cancelled.append(task_id)
+        return False
+
+    def enqueue(self, queue: asyncio.PriorityQueue, request: Optional[Dict[str, Any]]) -> None:
+        """Queue `request` (None = end of input) behind others of its priority."""
+        queue.put_nowait((request_priority(request), next(self.sequence), request))
+
+    async def read_requests(self, queue: asyncio.PriorityQueue) -> None:
+        """
+        Read JSON lines from stdin into `queue`, acting on 'cancel' requests at
+        once. Queues None at end of input, after anything still waiting.
+        """
+        loop = asyncio.get_running_loop()
         try:
-            for line in sys.stdin:
+            while True:
+                line = await loop.run_in_executor(None, sys.stdin.readline)
+                if not line:
+                    logger.info("End of stdin reached, shutting down")
+                    break
+
                 line = line.strip()
 
                 # Skip empty lines
@@ -160,21 +231,63 @@ class IPCHandler:
 
                 try:
                     request = json.loads(line)
-                    logger.debug(f"Received request {self.request_count}: {request.get('action')}")
-
-                    # Process request (handler will send responses)
-                    await self.process_request(request)
-
                 except json.JSONDecodeError as e:
                     logger.error(f"IPC JSON decode error: {e} for line: {line[:100]}")
                     self.send_error('unknown', f"Invalid JSON: {e}")
+                    continue
+
+                logger.debug(f"Received request {self.request_count}: {request.get('action')}")
+                if request.get('action') == 'cancel':
+                    self.cancel(request.get('task_id', 'unknown'))
+                else:
+                    self.enqueue(queue, request)
+        finally:
+            self.enqueue(queue, None)
+
+    async def run(self) -> None:
+        """
+        Main event loop - read JSON from stdin, dispatch to handlers.
+
+        This runs until stdin closes, handling one request at a time.
+        """
+        logger.info("🚀 Hermes Media Worker started")
+        logger.info(f"Registered handlers: {list(self.handlers.keys())}")
+
+        queue: asyncio.PriorityQueue = asyncio.PriorityQueue()
+        reader = asyncio.create_task(self.read_requests(queue))
+
+        try:
+            while True:
+                _, _, request = await queue.get()
+                if request is None:
+                    break
+
+                task_id = request.get('task_id', 'unknown')
+                if task_id in self.cancelled:
+                    self.cancelled.remove(task_id)
+                    error = get_error('CANCELLED')
+                    self.send_error(task_id, error.user_message, error.code)
+                    continue
+
+                # Process request (handler will send responses)
+                task = asyncio.create_task(self.process_request(request))
+                self.current = (task_id, task)
+                try:
+                    await task
+                except asyncio.CancelledError:
+                    if not task.cancelled():
+                        raise
+                    error = get_error('CANCELLED')
+                    self.send_error(task_id, error.user_message, error.code)
+                finally:
+                    self.current = None
 
         except KeyboardInterrupt:
             logger.info("Worker interrupted by keyboard")
-        except EOFError:
-            logger.info("End of stdin reached, shutting down")
         except Exception as e:
             logger.critical(f"Fatal error in main loop: {e}", exc_info=True)
+        finally:
+            reader.cancel()
 
         logger.info(f"📊 Worker shutdown. Processed {self.request_count} requests, sent {self.response_count} responses")
 
@@ -204,6 +317,14 @@ def create_ipc_handler() -> IPCHandler:
     return ipc_handler
 
 
+def local_file_ref(path: str) -> Dict[str, Any]:
+    """
+    Typed file reference for a `done` payload: a file on the filesystem shared
+    with the bot. Sent as 'file_ref' next to the plain 'file_path'.
+    """
+    return {'kind': 'local', 'path': path}
+
+
 def send_response(task_id: str, event: str, data: Optional[Dict[str, Any]] = None) -> None:
     """Convenience function to send response."""
     ipc_handler.send_response(task_id, event, data)
@@ -214,6 +335,12 @@ def send_error(task_id: str, message: str, error_code: Optional[str] = None) ->
     ipc_handler.send_error(task_id, message, error_code)
 
 
+def send_retry(task_id: str, attempt: int, max_attempts: Optional[int] = None,
+               reason: Optional[str] = None) -> None:
+    """Convenience function to send a retry notice."""
+    ipc_handler.send_retry(task_id, attempt, max_attempts, reason)
+
+
 def send_progress(task_id: str, percent: int, speed: Optional[str] = None,
                   eta_seconds: Optional[int] = None, status: Optional[str] = None) -> None:
     """Convenience function to send progress."""
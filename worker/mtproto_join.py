@@ -0,0 +1,105 @@
+"""
+MTProto channel copy IPC handler for Hermes.
+
+Handles the 'mtproto_copy_post' action: bots cannot join channels on their
+own, so when the bot has no access to a public channel the user account
+joins it, copies the post into the private storage channel and (optionally)
+leaves again. The Rust bot then copy_message's it from the storage channel.
+"""
+import logging
+
+from worker.mtproto_client import mtproto, CHANNEL_ID
+
+logger = logging.getLogger(__name__)
+
+
+async def _is_member(client, entity) -> bool:
+    from telethon.errors import UserNotParticipantError
+    from telethon.tl.functions.channels import GetParticipantRequest
+
+    try:
+        await client(GetParticipantRequest(entity, 'me'))
+        return True
+    except UserNotParticipantError:
+        return False
+
+
+async def handle_mtproto_copy_post(ipc, task_id: str, request: dict) -> None:
+    """
+    IPC handler for 'mtproto_copy_post' action.
+
+    Request params:
+        username:    str  — public channel username (without @)
+        message_id:  int  — post id in that channel
+        leave_after: bool — leave the channel again if we joined it here
+
+    Response (done):
+        channel_msg_id: int — message ID of the copy in the storage channel
+    """
+    from telethon.errors import (
+        ChannelPrivateError,
+        InviteRequestSentError,
+        UsernameInvalidError,
+        UsernameNotOccupiedError,
+    )
+    from telethon.tl.functions.channels import JoinChannelRequest, LeaveChannelRequest
+
+    params      = request.get("params", {})
+    username    = str(params.get("username", "")).lstrip("@")
+    message_id  = params.get("message_id")
+    leave_after = bool(params.get("leave_after", True))
+
+    # ── Validate ──────────────────────────────────────────────────────────────
+    if not username or not isinstance(message_id, int):
+        ipc.send_error(task_id, "Missing channel username or message id", "INVALID_URL")
+        return
+
+    if CHANNEL_ID == 0:
+        ipc.send_error(task_id, "STORAGE_CHANNEL_ID not set", "CONFIG_ERROR")
+        return
+
+    try:
+        client = mtproto.client  # triggers RuntimeError if not connected
+    except RuntimeError as e:
+        ipc.send_error(task_id, str(e), "MTPROTO_NOT_CONNECTED")
+        return
+
+    # ── Join (public channels only) ───────────────────────────────────────────
+    try:
+        entity = await client.get_entity(username)
+    except (UsernameNotOccupiedError, UsernameInvalidError, ValueError):
+        ipc.send_error(task_id, "Channel not found", "CHANNEL_NOT_FOUND")
+        return
+
+    joined_here = False
+    try:
+        if not await _is_member(client, entity):
+            await client(JoinChannelRequest(entity))
+            joined_here = True
+            logger.info(f"[{task_id}] Joined @{username} to copy post {message_id}")
+    except (ChannelPrivateError, InviteRequestSentError):
+        ipc.send_error(task_id, "This channel is private or needs approval to join", "CHANNEL_PRIVATE")
+        return
+    except Exception as e:
+        logger.error(f"[{task_id}] Failed to join @{username}: {e}")
+        ipc.send_error(task_id, "Could not join the channel", "CHANNEL_JOIN_FAILED")
+        return
+
+    # ── Copy into storage channel ─────────────────────────────────────────────
+    try:
+        post = await client.get_messages(entity, ids=message_id)
+        if post is None:
+            ipc.send_error(task_id, "Message not found. It may have been deleted.", "MESSAGE_NOT_FOUND")
+            return
+        copy = await client.send_message(CHANNEL_ID, post)
+        logger.info(f"[{task_id}] Copied @{username}/{message_id} → channel_msg_id={copy.id}")
+        ipc.send_response(task_id, "done", {"channel_msg_id": copy.id})
+    except Exception as e:
+        logger.error(f"[{task_id}] Copy from @{username} failed: {e}")
+        ipc.send_error(task_id, f"Copy failed: {e}", "MTPROTO_COPY_FAILED")
+    finally:
+        if joined_here and leave_after:
+            try:
+                await client(LeaveChannelRequest(entity))
+            except Exception as e:
+                logger.warning(f"[{task_id}] Failed to leave @{username}: {e}")
@@ -15,10 +15,10 @@ from typing import List, Dict, Any, Optional
 from worker.config import config
 from worker.ipc import IPCHandler
 from worker.cookies import get_yt_dlp_cookie_args
-from worker.utils import sanitize_filename, sanitize_folder_name, safe_mkdir, safe_rmtree, find_node_binary
+from worker.utils import sanitize_filename, sanitize_folder_name, safe_mkdir, safe_rmtree, find_node_binary, http_option_args, rate_limit_args, kill_on_cancel
 from worker.error_handlers import categorize_error, get_error
 from worker.progress_hooks import StreamProgressCollector
-from worker.storage import StorageManager
+from worker.storage import StorageManager, storage_root_for
 
 
 logger = logging.getLogger(__name__)
@@ -540,15 +540,21 @@ async def _download_playlist_tracks(ipc: IPCHandler, task_id: str, url: str, out
         # Radio Mixes may lack playlist_index; use title-only naming for them
         is_radio_mix = 'list=RD' in url
         if is_radio_mix:
-            output_template = os.path.join(output_dir, '%(title)s.%(ext)s')
+            output_template = os.path.join(output_dir, '%(title).200B.%(ext)s')
         else:
-            output_template = os.path.join(output_dir, '%(playlist_index)03d - %(title)s.%(ext)s')
+            output_template = os.path.join(output_dir, '%(playlist_index)03d - %(title).200B.%(ext)s')
         command.extend(['-o', output_template])
 
         # Cookies
         cookie_args = get_yt_dlp_cookie_args()
         command.extend(cookie_args)
 
+        # Custom User-Agent / headers for sites that fingerprint the default client
+        command.extend(http_option_args(params))
+
+        # Bandwidth cap (DOWNLOAD_RATE_LIMIT, or the task's own rate_limit)
+        command.extend(rate_limit_args(params, config.DOWNLOAD_RATE_LIMIT))
+
         # android client doesn't support cookies — use web-only when cookies are present
         player_clients = 'web' if cookie_args else 'android,web'
         command.extend(['--extractor-args', f'youtube:player_client={player_clients}'])
@@ -646,8 +652,9 @@ async def _download_playlist_tracks(ipc: IPCHandler, task_id: str, url: str, out
                 logger.error(f"[{task_id}] Error reading output: {e}")
 
         # Read stderr (progress) and stdout (video IDs) concurrently
-        await asyncio.gather(read_output(), read_stdout())
-        await process.wait()
+        with kill_on_cancel(process):
+            await asyncio.gather(read_output(), read_stdout())
+            await process.wait()
 
         # Log yt-dlp exit code and any errors
         if process.returncode != 0:
@@ -673,7 +680,7 @@ async def _download_playlist_tracks(ipc: IPCHandler, task_id: str, url: str, out
                 user_cid = params.get('user_chat_id', 0)
                 if user_cid:
                     logger.info(f"[{task_id}] Processing {len(downloaded_files)} files through dedup system (ID mappings: {len(filepath_to_video_id)})")
-                    storage_manager = StorageManager(config.DOWNLOAD_DIR)
+                    storage_manager = StorageManager(storage_root_for(params.get('output_dir'), config.DOWNLOAD_DIR))
                     final_paths = []
                     for temp_file in downloaded_files:
                         try:
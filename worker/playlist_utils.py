@@ -47,6 +47,80 @@ def normalize_playlist_url(url: str) -> str:
     return url
 
 
+# A channel's home page (no tab): yt-dlp lists its tabs there, not its videos
+CHANNEL_HOME = re.compile(
+    r'^(https?://(?:www\.|m\.)?youtube\.com/(?:@[^/?#]+|channel/[^/?#]+|c/[^/?#]+|user/[^/?#]+))/?(?:[?#].*)?$'
+)
+
+
+def channel_uploads_url(url: str) -> str:
+    """
+    Point a channel home URL at its Videos tab; other URLs are unchanged.
+
+    Examples:
+        youtube.com/@name → youtube.com/@name/videos
+        youtube.com/@name/shorts → unchanged
+    """
+    match = CHANNEL_HOME.match(url.strip())
+    return f"{match.group(1)}/videos" if match else url
+
+
+async def get_channel_latest(url: str, count: int = 10) -> Optional[Dict[str, Any]]:
+    """
+    List the newest `count` uploads of a channel or playlist without downloading.
+
+    Returns dict with:
+    - entries: list of {'id': str, 'title': str}, newest first
+
+    Used by the subscription poller to see whether anything is new before
+    starting a download pass.
+    """
+    try:
+        url = normalize_playlist_url(channel_uploads_url(url))
+
+        command = [
+            sys.executable, '-m', 'yt_dlp',
+            '--flat-playlist',
+            '--print', '%(id)s\t%(title)s',
+            '--playlist-end', str(max(1, int(count))),
+            '--no-cache-dir',
+            url,
+            *get_yt_dlp_cookie_args(),
+        ]
+
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.PIPE,
+            stderr=asyncio.subprocess.PIPE,
+        )
+
+        stdout_bytes, stderr_bytes = await asyncio.wait_for(
+            process.communicate(),
+            timeout=config.YT_TIMEOUT
+        )
+
+        if process.returncode != 0:
+            stderr = stderr_bytes.decode('utf-8', errors='replace')
+            error_msg = stderr.split('ERROR:')[-1].strip()[:200] if 'ERROR:' in stderr else 'Unknown error'
+            logger.error(f"Channel listing failed: {error_msg}")
+            return None
+
+        entries = []
+        for line in stdout_bytes.decode('utf-8', errors='replace').splitlines():
+            video_id, _, title = line.partition('\t')
+            if video_id and video_id != 'NA':
+                entries.append({'id': video_id, 'title': title})
+
+        return {'entries': entries}
+
+    except asyncio.TimeoutError:
+        logger.error("Channel listing timeout")
+        return None
+    except Exception as e:
+        logger.error(f"Channel listing failed: {e}")
+        return None
+
+
 async def get_playlist_preview(url: str, preview_count: int = 5) -> Optional[Dict[str, Any]]:
     """
     Fetch first N tracks of a playlist without downloading.
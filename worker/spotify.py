@@ -0,0 +1,138 @@
+"""
+Spotify link support for Hermes.
+
+Spotify audio can't be downloaded, so `handle_spotify_resolve` reads the
+track's title, artist and length from its public page and searches YouTube for
+the same song. The bot then downloads the best match like any other video.
+"""
+import asyncio
+import html
+import logging
+import re
+import urllib.request
+from typing import Any, Dict, List, Optional
+
+from worker.error_handlers import get_error
+from worker.ipc import IPCHandler
+from worker.youtube_search import _search_youtube
+
+logger = logging.getLogger(__name__)
+
+# Results to compare against the Spotify track length
+_SEARCH_LIMIT = 5
+_FETCH_TIMEOUT = 15
+_META_RE = re.compile(
+    r'<meta\s+(?:property|name)="([^"]+)"\s+content="([^"]*)"',
+    re.IGNORECASE,
+)
+
+
+def _fetch_page(url: str) -> str:
+    request = urllib.request.Request(url, headers={
+        # Without a browser UA Spotify serves a page without the meta tags
+        'User-Agent': 'Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36',
+        'Accept-Language': 'en',
+    })
+    with urllib.request.urlopen(request, timeout=_FETCH_TIMEOUT) as response:
+        return response.read().decode('utf-8', errors='replace')
+
+
+def parse_track_page(page: str) -> Optional[Dict[str, Any]]:
+    """
+    Title, artist and duration (seconds) from a Spotify track page's meta tags.
+
+    og:description reads "Artist · Album · Song · 2021"; the first part is the
+    artist (several artists are comma-separated there).
+    """
+    meta = {key.lower(): html.unescape(value) for key, value in _META_RE.findall(page)}
+    title = meta.get('og:title', '').strip()
+    artist = meta.get('og:description', '').split('·')[0].strip()
+    if not title or not artist:
+        return None
+    duration = meta.get('music:duration', '')
+    return {
+        'title': title,
+        'artist': artist,
+        'duration': int(duration) if duration.isdigit() else None,
+    }
+
+
+def _duration_secs(text: str) -> Optional[int]:
+    """Seconds from yt-dlp's duration_string ("3:35", "1:02:03")."""
+    try:
+        secs = 0
+        for part in text.split(':'):
+            secs = secs * 60 + int(part)
+        return secs
+    except ValueError:
+        return None
+
+
+def best_match(results: List[Dict[str, Any]], duration: Optional[int]) -> Optional[Dict[str, Any]]:
+    """
+    The search result closest in length to the Spotify track. Ties (and an
+    unknown length) keep YouTube's ranking.
+    """
+    if not results:
+        return None
+    if duration is None:
+        return results[0]
+
+    def distance(result):
+        secs = _duration_secs(str(result.get('duration', '')))
+        return abs(secs - duration) if secs is not None else float('inf')
+
+    return min(results, key=distance)
+
+
+async def handle_spotify_resolve(ipc: IPCHandler, task_id: str, request: dict) -> None:
+    """
+    Look up a Spotify track and find the matching YouTube video.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "spotify_resolve",
+        "url": "https://open.spotify.com/track/..."
+    }
+
+    Response (spotify_track):
+    {
+        "title": "...",
+        "artist": "...",
+        "duration": 215,          # seconds, null if unknown
+        "youtube_url": "...",     # null if the search found nothing
+        "youtube_title": "..."
+    }
+    """
+    url = request.get('url', '').strip()
+    if not url:
+        ipc.send_error(task_id, "Missing 'url' parameter", 'INVALID_URL')
+        return
+
+    loop = asyncio.get_running_loop()
+    try:
+        page = await loop.run_in_executor(None, _fetch_page, url)
+    except Exception as e:
+        logger.warning(f"[{task_id}] Spotify page fetch failed: {e}")
+        error = get_error('SPOTIFY_LOOKUP_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    track = parse_track_page(page)
+    if not track:
+        logger.warning(f"[{task_id}] No track metadata on Spotify page {url}")
+        error = get_error('SPOTIFY_LOOKUP_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    query = f"{track['artist']} - {track['title']}"
+    results = await _search_youtube(task_id, query, _SEARCH_LIMIT)
+    match = best_match(results or [], track['duration'])
+    logger.info(f"[{task_id}] Spotify '{query}' -> {match['url'] if match else 'no match'}")
+
+    ipc.send_response(task_id, 'spotify_track', {
+        **track,
+        'youtube_url': match['url'] if match else None,
+        'youtube_title': match['title'] if match else None,
+    })
@@ -351,3 +351,16 @@ class StorageManager:
             logger.error(f"Error during symlink cleanup: {e}")
 
         return repaired, removed
+
+
+def storage_root_for(task_dir: Optional[str], default_root: str) -> str:
+    """
+    Storage root that a task's output directory sits in.
+
+    The bot writes each task to <root>/<chat_id>/<task_id> under the root for
+    its kind (audio, video, playlist), so the pool is rooted there and deduped
+    files stay in that tier. Requests without an output_dir use default_root.
+    """
+    if not task_dir:
+        return default_root
+    return os.path.dirname(os.path.dirname(os.path.normpath(task_dir)))
@@ -0,0 +1,173 @@
+"""
+Hard-burned subtitles for Hermes video downloads.
+
+When a download request carries `burn_subtitles: <lang>`, yt-dlp fetches the
+subtitle track next to the video (see `subtitle_download_args`) and
+`burn_subtitles` re-encodes the video with the text drawn into the frames,
+so the captions show on any player. This is a full re-encode and can take
+several times the video's duration on a small VPS.
+"""
+import asyncio
+import os
+import re
+import shutil
+import time
+import logging
+from typing import Optional
+
+from worker.config import config
+from worker.error_handlers import get_error
+from worker.utils import kill_on_cancel
+
+logger = logging.getLogger(__name__)
+
+# Subtitle language codes as yt-dlp reports them ("en", "pt-BR", "zh-Hans")
+LANG_PATTERN = re.compile(r'[A-Za-z0-9-]{1,16}')
+
+_DURATION_RE = re.compile(r'Duration:\s*(\d+):(\d+):(\d+(?:\.\d+)?)')
+_TIME_RE = re.compile(r'time=\s*(\d+):(\d+):(\d+(?:\.\d+)?)')
+
+# Fixed name for the subtitle copy, so the ffmpeg filter never sees a title
+# containing ':' or quotes (both special in filter arguments)
+_SUBS_NAME = 'hardsubs.srt'
+
+
+def subtitle_download_args(lang: str, fmt: str = 'srt') -> list:
+    """yt-dlp args that save the `lang` subtitle track (manual, else auto) as .srt (or `fmt`)."""
+    return [
+        '--write-subs', '--write-auto-subs',
+        '--sub-langs', lang,
+        '--convert-subs', fmt,
+    ]
+
+
+def _seconds(match) -> float:
+    hours, minutes, seconds = match.groups()
+    return int(hours) * 3600 + int(minutes) * 60 + float(seconds)
+
+
+def _find_subtitle_file(output_dir: str) -> Optional[str]:
+    try:
+        for name in sorted(os.listdir(output_dir)):
+            if name.lower().endswith('.srt') and name != _SUBS_NAME:
+                return os.path.join(output_dir, name)
+    except OSError as e:
+        logger.error(f"Error scanning for subtitles: {e}")
+    return None
+
+
+async def burn_subtitles(ipc, task_id: str, video_file: str, output_dir: str, lang: str) -> Optional[str]:
+    """
+    Re-encode `video_file` with the downloaded subtitles burned in.
+
+    Returns the path of the new .mp4, or None after sending an error response
+    (no subtitles in that language, ffmpeg failure or timeout).
+    """
+    subtitle_file = _find_subtitle_file(output_dir)
+    if not subtitle_file:
+        error = get_error(
+            'SUBTITLES_UNAVAILABLE',
+            f"No '{lang}' subtitles are available for this video.",
+        )
+        logger.info(f"[{task_id}] No {lang} subtitles found, dropping {os.path.basename(video_file)}")
+        try:
+            os.remove(video_file)
+        except OSError:
+            pass
+        ipc.send_error(task_id, error.user_message, error.code)
+        return None
+
+    shutil.copyfile(subtitle_file, os.path.join(output_dir, _SUBS_NAME))
+
+    stem, ext = os.path.splitext(video_file)
+    output_file = f'{stem}.burning.mp4'
+    # mp4 sources already carry AAC audio; other containers may hold opus/vorbis
+    audio_args = ['-c:a', 'copy'] if ext.lower() == '.mp4' else ['-c:a', 'aac', '-b:a', '192k']
+    command = [
+        'ffmpeg', '-y', '-hide_banner',
+        '-i', video_file,
+        '-vf', f'subtitles={_SUBS_NAME}',
+        '-c:v', 'libx264', '-preset', 'veryfast', '-crf', '23',
+        *audio_args,
+        '-movflags', '+faststart',
+        output_file,
+    ]
+
+    logger.info(f"[{task_id}] Burning {lang} subtitles into {os.path.basename(video_file)}")
+    ipc.send_progress(task_id, 0, status='burning subtitles')
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            cwd=output_dir,
+            stdout=asyncio.subprocess.DEVNULL,
+            stderr=asyncio.subprocess.PIPE,
+        )
+    except FileNotFoundError:
+        error = get_error('SUBTITLE_BURN_FAILED', 'ffmpeg is not installed on the worker.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return None
+
+    tail = []  # last ffmpeg lines, for the log on failure
+
+    async def read_progress():
+        duration = None
+        last_sent = 0.0
+        buffer = ''
+        while True:
+            chunk = await process.stderr.read(4096)
+            if not chunk:
+                break
+            buffer += chunk.decode('utf-8', errors='replace')
+            # ffmpeg rewrites its stats line with '\r'
+            *lines, buffer = re.split(r'[\r\n]', buffer)
+            for line in filter(None, (l.strip() for l in lines)):
+                tail.append(line)
+                del tail[:-20]
+                if duration is None:
+                    match = _DURATION_RE.search(line)
+                    if match:
+                        duration = _seconds(match)
+                    continue
+                match = _TIME_RE.search(line)
+                if match and duration and time.monotonic() - last_sent >= 2:
+                    percent = min(99, int(_seconds(match) / duration * 100))
+                    ipc.send_progress(task_id, percent, status='burning subtitles')
+                    last_sent = time.monotonic()
+
+    try:
+        with kill_on_cancel(process):
+            await asyncio.wait_for(read_progress(), timeout=config.SUBTITLE_BURN_TIMEOUT)
+            returncode = await process.wait()
+    except asyncio.TimeoutError:
+        process.kill()
+        await process.wait()
+        returncode = None
+        tail.append(f'timed out after {config.SUBTITLE_BURN_TIMEOUT}s')
+    finally:
+        try:
+            os.remove(os.path.join(output_dir, _SUBS_NAME))
+        except OSError:
+            pass
+
+    if returncode != 0:
+        logger.error(f"[{task_id}] Subtitle burn failed (exit {returncode}):")
+        for line in tail:
+            logger.error(f"[{task_id}]   {line}")
+        try:
+            os.remove(output_file)
+        except OSError:
+            pass
+        error = get_error('SUBTITLE_BURN_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return None
+
+    final_file = f'{stem}.mp4'
+    os.replace(output_file, final_file)
+    if final_file != video_file:
+        try:
+            os.remove(video_file)
+        except OSError:
+            pass
+    ipc.send_progress(task_id, 100, status='completed')
+    return final_file
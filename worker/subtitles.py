@@ -0,0 +1,121 @@
+"""
+Subtitle files for Hermes (/subs and the "Include subtitles" toggle).
+
+`handle_download_subtitles` fetches only the subtitle track of a video, no
+media. Video downloads with a `subtitles: <lang>` param save the track next to
+the video instead (see `subtitle_download_args`); `find_subtitle_file` picks
+it up afterwards so the bot can send it alongside.
+"""
+import asyncio
+import os
+import sys
+import logging
+from typing import Optional
+
+from worker.config import config
+from worker.cookies import get_yt_dlp_cookie_args
+from worker.error_handlers import get_error
+from worker.subtitle_burn import LANG_PATTERN, subtitle_download_args
+from worker.utils import safe_mkdir, http_option_args, kill_on_cancel
+
+logger = logging.getLogger(__name__)
+
+SUBTITLE_FORMATS = ('srt', 'vtt')
+
+
+def find_subtitle_file(output_dir: str, fmt: str = 'srt') -> Optional[str]:
+    """First `.fmt` file in `output_dir`, or None."""
+    try:
+        for name in sorted(os.listdir(output_dir)):
+            if name.lower().endswith(f'.{fmt}'):
+                return os.path.join(output_dir, name)
+    except OSError as e:
+        logger.error(f"Error scanning for subtitles: {e}")
+    return None
+
+
+async def handle_download_subtitles(ipc, task_id: str, request: dict) -> None:
+    """
+    Download a video's subtitles in one language, without the video.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "download_subtitles",
+        "url": "https://www.youtube.com/watch?v=...",
+        "params": {
+            "language": "en",
+            "format": "srt",  // or "vtt"
+            "output_dir": "/downloads/..."
+        }
+    }
+
+    Manual subtitles are preferred, automatic captions are the fallback.
+    Responds with `done` carrying `file_path`, `filename`, `language` and
+    `format`, or an error with code SUBTITLES_UNAVAILABLE or SUBTITLES_FAILED.
+    """
+    url = request.get('url', '').strip()
+    params = request.get('params', {})
+    lang = str(params.get('language') or 'en')
+    fmt = params.get('format') if params.get('format') in SUBTITLE_FORMATS else 'srt'
+    output_dir = params.get('output_dir', config.DOWNLOAD_DIR)
+
+    if not url:
+        ipc.send_error(task_id, "Missing 'url' parameter", 'INVALID_URL')
+        return
+    if not LANG_PATTERN.fullmatch(lang):
+        ipc.send_error(task_id, f"Invalid subtitle language: {lang}", 'SUBTITLES_UNAVAILABLE')
+        return
+
+    safe_mkdir(output_dir)
+    command = [
+        sys.executable, '-m', 'yt_dlp', url,
+        '--no-playlist',
+        '--skip-download',
+        *subtitle_download_args(lang, fmt),
+        '--no-cache-dir',
+        '-o', os.path.join(output_dir, '%(title).200B.%(ext)s'),
+        *get_yt_dlp_cookie_args(),
+        *http_option_args(params),
+    ]
+
+    logger.info(f"[{task_id}] Fetching {lang} subtitles ({fmt}) for {url[:50]}")
+    ipc.send_progress(task_id, 0, status='fetching subtitles')
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.DEVNULL,
+            stderr=asyncio.subprocess.PIPE,
+        )
+        with kill_on_cancel(process):
+            _, stderr_bytes = await asyncio.wait_for(process.communicate(), timeout=config.YT_TIMEOUT)
+    except asyncio.TimeoutError:
+        process.kill()
+        await process.wait()
+        error = get_error('NETWORK_TIMEOUT')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    if process.returncode != 0:
+        stderr = stderr_bytes.decode('utf-8', errors='replace').strip()
+        logger.error(f"[{task_id}] Subtitle fetch failed: {stderr[-300:]}")
+        error = get_error('SUBTITLES_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    # yt-dlp exits 0 when the language isn't offered and simply writes nothing
+    file_path = find_subtitle_file(output_dir, fmt)
+    if not file_path:
+        logger.info(f"[{task_id}] No {lang} subtitles offered")
+        error = get_error('SUBTITLES_UNAVAILABLE', f"No '{lang}' subtitles are available for this video.")
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    ipc.send_progress(task_id, 100, status='completed')
+    ipc.send_response(task_id, 'done', {
+        'file_path': file_path,
+        'filename': os.path.basename(file_path),
+        'language': lang,
+        'format': fmt,
+    })
@@ -0,0 +1,41 @@
+"""
+Tests for the worker's request queue ordering.
+Run from the project root: python -m unittest worker.test_ipc
+"""
+
+import unittest
+
+from worker.ipc import IPCHandler
+
+
+class RequestOrderTest(unittest.IsolatedAsyncioTestCase):
+    async def test_interactive_requests_run_before_queued_downloads(self):
+        handler = IPCHandler()
+        handled = []
+
+        async def record(ipc, task_id, request):
+            handled.append(task_id)
+
+        for action in ('youtube_dl', 'playlist', 'health_check', 'get_formats'):
+            handler.register(action, record)
+
+        async def read_requests(queue):
+            for task_id, action in [
+                ('dl-1', 'youtube_dl'),
+                ('pl-1', 'playlist'),
+                ('ping', 'health_check'),
+                ('dl-2', 'youtube_dl'),
+                ('formats', 'get_formats'),
+            ]:
+                handler.enqueue(queue, {'task_id': task_id, 'action': action})
+            handler.enqueue(queue, None)
+
+        handler.read_requests = read_requests
+        await handler.run()
+
+        # Interactive first, then bulk, each in arrival order
+        self.assertEqual(handled, ['ping', 'formats', 'dl-1', 'pl-1', 'dl-2'])
+
+
+if __name__ == '__main__':
+    unittest.main()
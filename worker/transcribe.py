@@ -0,0 +1,172 @@
+"""
+Speech-to-text for Hermes (/transcribe).
+
+The bot downloads the audio with the normal 'youtube_dl' action, then sends the
+file here. `handle_transcribe` runs Whisper over it and writes a timestamped
+plain-text transcript next to the audio. faster-whisper is used when installed,
+otherwise the reference openai-whisper package (no progress reporting there).
+Both are optional: without either, the action fails with TRANSCRIBER_UNAVAILABLE.
+"""
+import asyncio
+import os
+import threading
+import time
+import logging
+
+from worker.config import config
+from worker.error_handlers import get_error
+from worker.utils import sanitize_filename, safe_mkdir
+
+logger = logging.getLogger(__name__)
+
+# Loaded on first use and kept: loading a model takes longer than a short clip
+_model = None
+_model_lock = threading.Lock()
+
+
+class _Cancelled(Exception):
+    """Raised inside the transcription thread once the time limit has passed."""
+
+
+def _load_model():
+    """Return (backend, model), loading the configured Whisper model once."""
+    global _model
+    with _model_lock:
+        if _model is None:
+            try:
+                from faster_whisper import WhisperModel
+                _model = ('faster_whisper', WhisperModel(config.WHISPER_MODEL, device='auto', compute_type='int8'))
+            except ImportError:
+                import whisper  # raises ImportError when neither backend is installed
+                _model = ('whisper', whisper.load_model(config.WHISPER_MODEL))
+            logger.info(f"Loaded Whisper model '{config.WHISPER_MODEL}' ({_model[0]})")
+        return _model
+
+
+def _timestamp(seconds: float) -> str:
+    seconds = int(seconds)
+    hours, rest = divmod(seconds, 3600)
+    minutes, secs = divmod(rest, 60)
+    return f'{hours}:{minutes:02}:{secs:02}' if hours else f'{minutes:02}:{secs:02}'
+
+
+def _transcribe(path: str, language, on_progress, stop: threading.Event):
+    """
+    Blocking transcription, run in a thread.
+
+    Returns (segments, detected_language, duration) where segments is a list of
+    (start_seconds, text).
+    """
+    backend, model = _load_model()
+    segments = []
+    if backend == 'faster_whisper':
+        pieces, info = model.transcribe(path, language=language, vad_filter=True)
+        for piece in pieces:
+            if stop.is_set():
+                raise _Cancelled()
+            if piece.text.strip():
+                segments.append((piece.start, piece.text.strip()))
+            if info.duration:
+                on_progress(min(99, int(piece.end / info.duration * 100)))
+        return segments, info.language, info.duration
+
+    result = model.transcribe(path, language=language)
+    for piece in result.get('segments', []):
+        if piece['text'].strip():
+            segments.append((piece['start'], piece['text'].strip()))
+    duration = result['segments'][-1]['end'] if result.get('segments') else 0
+    return segments, result.get('language'), duration
+
+
+async def handle_transcribe(ipc, task_id: str, request: dict) -> None:
+    """
+    Transcribe an already-downloaded audio file to text.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "transcribe",
+        "params": {
+            "file": "/downloads/.../Lecture.mp3",
+            "language": "en",  // or "auto"
+            "title": "Lecture",
+            "output_dir": "/downloads/..."
+        }
+    }
+
+    Responds with `done` carrying `file_path`, `filename`, `language`,
+    `duration`, `segment_count` and `char_count`, or an error with code
+    TRANSCRIBER_UNAVAILABLE, NO_SPEECH or TRANSCRIBE_FAILED.
+    """
+    params = request.get('params', {})
+    source = params.get('file', '')
+    language = params.get('language') or 'auto'
+    title = sanitize_filename(params.get('title') or 'Transcript')
+    output_dir = params.get('output_dir', config.DOWNLOAD_DIR)
+
+    if not source or not os.path.isfile(source):
+        error = get_error('TRANSCRIBE_FAILED', 'The downloaded audio could not be found.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    loop = asyncio.get_running_loop()
+    stop = threading.Event()
+    last_sent = [0.0]
+
+    def on_progress(percent: int):
+        # Called from the worker thread; stdout writes belong on the loop
+        if time.monotonic() - last_sent[0] >= 2:
+            last_sent[0] = time.monotonic()
+            loop.call_soon_threadsafe(ipc.send_progress, task_id, percent, None, None, 'transcribing')
+
+    logger.info(f"[{task_id}] Transcribing {os.path.basename(source)} (language={language})")
+    ipc.send_progress(task_id, 0, status='loading speech model')
+    started = time.monotonic()
+
+    try:
+        segments, detected, duration = await asyncio.wait_for(
+            asyncio.to_thread(_transcribe, source, None if language == 'auto' else language, on_progress, stop),
+            timeout=config.TRANSCRIBE_TIMEOUT,
+        )
+    except ImportError:
+        error = get_error('TRANSCRIBER_UNAVAILABLE')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+    except asyncio.TimeoutError:
+        stop.set()
+        logger.error(f"[{task_id}] Transcription timed out after {config.TRANSCRIBE_TIMEOUT}s")
+        error = get_error('TRANSCRIBE_FAILED', 'Transcription took too long.')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+    except Exception as e:
+        logger.error(f"[{task_id}] Transcription failed: {e}", exc_info=True)
+        error = get_error('TRANSCRIBE_FAILED')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    if not segments:
+        logger.info(f"[{task_id}] No speech detected")
+        error = get_error('NO_SPEECH')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    safe_mkdir(output_dir)
+    output_file = os.path.join(output_dir, f'{title} (transcript).txt')
+    with open(output_file, 'w', encoding='utf-8') as f:
+        for start, text in segments:
+            f.write(f'[{_timestamp(start)}] {text}\n')
+
+    char_count = sum(len(text) for _, text in segments)
+    logger.info(
+        f"[{task_id}] Transcript ready: {len(segments)} segments, {char_count} chars, "
+        f"language={detected}, took {time.monotonic() - started:.0f}s"
+    )
+    ipc.send_progress(task_id, 100, status='completed')
+    ipc.send_response(task_id, 'done', {
+        'file_path': output_file,
+        'filename': os.path.basename(output_file),
+        'language': detected or language,
+        'duration': int(duration or 0),
+        'segment_count': len(segments),
+        'char_count': char_count,
+    })
@@ -3,10 +3,12 @@ Utility functions for Hermes Media Worker
 Path sanitization, formatting, and helpers
 """
 
+import asyncio
 import os
 import re
 import glob as _glob
 import shutil
+from contextlib import contextmanager
 from pathlib import Path
 from typing import Optional
 
@@ -350,3 +352,66 @@ def ensure_parent_dir(filepath: str) -> bool:
         return True
     except Exception:
         return False
+
+
+_HEADER_NAME_RE = re.compile(r"[A-Za-z0-9!#$%&'*+.^_`|~-]+")
+
+
+def http_option_args(params: dict) -> list:
+    """
+    yt-dlp args for the `user_agent` / `http_headers` request params.
+
+    The bot validates these already; anything malformed (bad header name,
+    control characters that could inject extra header lines) is dropped here too.
+    """
+    args = []
+    user_agent = params.get('user_agent')
+    if isinstance(user_agent, str) and user_agent.strip() and user_agent.isprintable():
+        args.extend(['--user-agent', user_agent])
+    headers = params.get('http_headers')
+    if isinstance(headers, dict):
+        for name, value in headers.items():
+            value = str(value)
+            if _HEADER_NAME_RE.fullmatch(str(name)) and value.isprintable():
+                args.extend(['--add-header', f'{name}:{value}'])
+    return args
+
+
+_RATE_RE = re.compile(r'(\d+(?:\.\d+)?)\s*([KMG]?)(?:B)?(?:/S)?')
+
+
+def parse_rate(value: str) -> Optional[int]:
+    """
+    Bytes per second from a rate like "500K", "2M" or "1048576" (K/M/G are
+    powers of 1024). Empty, zero or malformed means no limit: None.
+    """
+    match = _RATE_RE.fullmatch((value or '').strip().upper())
+    if not match:
+        return None
+    rate = int(float(match.group(1)) * 1024 ** ' KMG'.index(match.group(2) or ' '))
+    return rate or None
+
+
+def rate_limit_args(params: dict, default: str) -> list:
+    """
+    yt-dlp --limit-rate for a download: the `rate_limit` request param
+    (bytes/s, 0 = unlimited for this task) or else `default` (DOWNLOAD_RATE_LIMIT).
+    """
+    rate = params.get('rate_limit')
+    if not isinstance(rate, int) or isinstance(rate, bool) or rate < 0:
+        rate = parse_rate(default)
+    return ['--limit-rate', str(rate)] if rate else []
+
+
+@contextmanager
+def kill_on_cancel(process):
+    """
+    Kill `process` if the task awaiting it inside this block is cancelled
+    (a cancel request from the bot), so yt-dlp/ffmpeg don't keep running.
+    """
+    try:
+        yield process
+    except asyncio.CancelledError:
+        if process.returncode is None:
+            process.kill()
+        raise
@@ -3,18 +3,23 @@ YouTube single video download handler
 Handles video downloads with progress tracking and error recovery
 """
 
+import json
 import os
+import re
+import shlex
 import subprocess
 import sys
 import logging
 import asyncio
 from typing import Optional
 from worker.config import config
-from worker.ipc import IPCHandler
-from worker.cookies import get_yt_dlp_cookie_args
-from worker.utils import sanitize_filename, safe_mkdir, file_exists_and_valid, find_node_binary
+from worker.ipc import IPCHandler, local_file_ref
+from worker.cookies import get_yt_dlp_cookie_args, validate_cookies
+from worker.utils import sanitize_filename, safe_mkdir, file_exists_and_valid, find_node_binary, http_option_args, rate_limit_args, kill_on_cancel
 from worker.error_handlers import categorize_error, get_error
 from worker.progress_hooks import StreamProgressCollector
+from worker.subtitle_burn import LANG_PATTERN, burn_subtitles, subtitle_download_args
+from worker.subtitles import find_subtitle_file
 
 
 logger = logging.getLogger(__name__)
@@ -35,6 +40,19 @@ async def handle_youtube_download(ipc: IPCHandler, task_id: str, request: dict)
             "audio_format": "mp3",
             "audio_quality": "192",
             "best_audio_limit_mb": 15,
+            "max_filesize_mb": 2048,
+            "metadata_tag": "via Hermes",
+            "embed_metadata": true,
+            "audio_sample_rate": 48000,
+            "audio_channels": 1,
+            "burn_subtitles": "en",
+            "subtitles": "en",
+            "clip": true,
+            "section_start": 60,
+            "section_end": 90,
+            "user_agent": "Mozilla/5.0 ...",
+            "http_headers": {"Referer": "https://example.com/"},
+            "rate_limit": 2097152,
             "output_dir": "/path/to/output"
         }
     }
@@ -58,8 +76,34 @@ async def handle_youtube_download(ipc: IPCHandler, task_id: str, request: dict)
         # Send initial progress
         ipc.send_progress(task_id, 0, status='preparing')
 
+        # YouTube clip (youtube.com/clip/...): download only its segment of the source video
+        section_args = []
+        if params.get('clip'):
+            clip = await _resolve_clip(url, task_id)
+            if clip is None:
+                error = get_error('CLIP_UNRESOLVED')
+                ipc.send_error(task_id, error.user_message, error.code)
+                return
+            url, start, end = clip
+            section_args = ['--download-sections', f'*{start:.3f}-{end:.3f}', '--force-keyframes-at-cuts']
+        elif 'section_start' in params or 'section_end' in params:
+            # /clip: only this time range of the video, in seconds
+            section = _section_range(params)
+            if section is None:
+                error = get_error('INVALID_SECTION')
+                ipc.send_error(task_id, error.user_message, error.code)
+                return
+            start, end = section
+            section_args = ['--download-sections', f'*{start}-{end}', '--force-keyframes-at-cuts']
+
         # Build yt-dlp command
-        command = [sys.executable, '-m', 'yt_dlp', url]
+        command = [sys.executable, '-m', 'yt_dlp', url, *section_args]
+
+        # Audio track language for multi-audio videos (e.g. "en", "pt-BR")
+        audio_language = params.get('audio_language')
+        if audio_language and not re.fullmatch(r'[A-Za-z0-9-]{1,16}', str(audio_language)):
+            logger.warning(f"[{task_id}] Ignoring invalid audio_language: {audio_language!r}")
+            audio_language = None
 
         # Audio extraction
         extract_audio = params.get('extract_audio', False)
@@ -69,11 +113,22 @@ async def handle_youtube_download(ipc: IPCHandler, task_id: str, request: dict)
 
             # Prefer m4a audio stream; --audio-quality 0 = best VBR quality for ffmpeg.
             # Fall back to best (any format) if no dedicated audio stream is available.
-            command.extend(['-f', 'bestaudio[ext=m4a]/bestaudio/best'])
+            audio_selector = 'bestaudio[ext=m4a]/bestaudio/best'
+            if audio_language:
+                audio_selector = (
+                    f'bestaudio[language={audio_language}][ext=m4a]'
+                    f'/bestaudio[language={audio_language}]/{audio_selector}'
+                )
+            command.extend(['-f', audio_selector])
 
             command.extend(['-x', '--audio-format', audio_format])
             if audio_quality:
                 command.extend(['--audio-quality', audio_quality])
+
+            # Optional resampling / downmix (default: keep the source)
+            ffmpeg_args = _resample_args(params, task_id)
+            if ffmpeg_args:
+                command.extend(['--postprocessor-args', f"ExtractAudio:{' '.join(ffmpeg_args)}"])
         else:
             # Video format selection
             # Modern YouTube rarely provides combined streams — use merge format
@@ -85,6 +140,9 @@ async def handle_youtube_download(ipc: IPCHandler, task_id: str, request: dict)
                 '/bestvideo+bestaudio'
                 '/best'
             )
+            if audio_language and 'format' in params:
+                # Native audio pick: best stream in the requested language, else the chosen format
+                format_str = f'bestaudio[language={audio_language}]/{format_str}'
             command.extend(['-f', format_str])
             # When merging separate video+audio streams, output as mp4
             if '+' in format_str:
@@ -94,14 +152,67 @@ async def handle_youtube_download(ipc: IPCHandler, task_id: str, request: dict)
         output_dir = params.get('output_dir', config.DOWNLOAD_DIR)
         safe_mkdir(output_dir)
 
-        # Output template - use title with sanitization
-        output_template = os.path.join(output_dir, '%(title)s.%(ext)s')
+        # Output template - use title with sanitization, capped at 200 bytes
+        # so very long titles stay within filesystem and Telegram limits
+        output_template = os.path.join(output_dir, '%(title).200B.%(ext)s')
         command.extend(['-o', output_template])
 
-        # Cookie handling
-        cookie_args = get_yt_dlp_cookie_args()
+        # Title, uploader, artist and duration for captions (see _media_info)
+        media_info_file = os.path.join(output_dir, MEDIA_INFO_FILE)
+        for field in MEDIA_INFO_FIELDS:
+            command.extend(['--print-to-file', f'after_move:{field}=%({field})s', media_info_file])
+
+        # Size cap: yt-dlp aborts before downloading when the estimate is larger
+        max_filesize_mb = params.get('max_filesize_mb')
+        if isinstance(max_filesize_mb, int) and max_filesize_mb > 0:
+            command.extend(['--max-filesize', f'{max_filesize_mb}M'])
+
+        # Hard-burned subtitles (video only): fetch the track, burn it in after download
+        burn_lang = params.get('burn_subtitles')
+        if burn_lang and not extract_audio:
+            if not LANG_PATTERN.fullmatch(str(burn_lang)):
+                ipc.send_error(task_id, f"Invalid subtitle language: {burn_lang}", 'SUBTITLES_UNAVAILABLE')
+                return
+            command.extend(subtitle_download_args(burn_lang))
+
+        # Subtitle file sent alongside the video (video only, skipped when burning)
+        subs_lang = params.get('subtitles')
+        if subs_lang and not extract_audio and not burn_lang:
+            if LANG_PATTERN.fullmatch(str(subs_lang)):
+                command.extend(subtitle_download_args(subs_lang))
+            else:
+                logger.warning(f"[{task_id}] Ignoring invalid subtitles language: {subs_lang!r}")
+
+        # Provenance tag in the comment field (mp3 ID3 / mp4 metadata)
+        metadata_tag = params.get('metadata_tag')
+        if metadata_tag:
+            command.extend(_metadata_tag_args(str(metadata_tag)))
+
+        # Title/artist tags and cover art for music players; the thumbnail is
+        # also kept on disk so the bot can send a scaled copy with the audio
+        if extract_audio and params.get('embed_metadata'):
+            command.extend([
+                '--embed-metadata', '--embed-thumbnail',
+                '--write-thumbnail', '--convert-thumbnails', 'jpg',
+            ])
+
+        # Cookie handling. Cookies are always passed when present; a "retry
+        # with cookies" starts from a fresh copy of the uploaded file (yt-dlp
+        # rewrites the working copy with the failed attempt's session cookies)
+        # and refuses to run without YouTube/Google cookies in it.
+        use_cookies = bool(params.get('use_cookies'))
+        cookie_args = get_yt_dlp_cookie_args(fresh=use_cookies)
+        if use_cookies and not (cookie_args and validate_cookies()):
+            ipc.send_error(task_id, "Cookies were requested but no usable cookie file is available", 'COOKIES_MISSING')
+            return
         command.extend(cookie_args)
 
+        # Custom User-Agent / headers for sites that fingerprint the default client
+        command.extend(http_option_args(params))
+
+        # Bandwidth cap (DOWNLOAD_RATE_LIMIT, or the task's own rate_limit)
+        command.extend(rate_limit_args(params, config.DOWNLOAD_RATE_LIMIT))
+
         # Other flags
         # android client bypasses VPS bot detection but doesn't support cookies.
         # Use android+web when no cookies (android handles bot detection),
@@ -162,11 +273,12 @@ async def _execute_download(ipc: IPCHandler, task_id: str, command: list, output
         destination_file = None
         has_error = False
         error_message = None
+        too_large = False
         stderr_lines = []  # collect all yt-dlp output for error reporting
 
         # Read stderr for progress
         async def read_progress():
-            nonlocal destination_file, has_error, error_message
+            nonlocal destination_file, has_error, error_message, too_large
 
             try:
                 while process.returncode is None:
@@ -185,6 +297,9 @@ async def _execute_download(ipc: IPCHandler, task_id: str, command: list, output
                     logger.debug(f"[{task_id}] yt-dlp: {line}")
                     stderr_lines.append(line)
 
+                    if 'larger than max-filesize' in line:
+                        too_large = True
+
                     # Parse progress
                     result = progress_collector.process_line(line)
 
@@ -222,7 +337,8 @@ async def _execute_download(ipc: IPCHandler, task_id: str, command: list, output
 
         # Run progress reader
         try:
-            await asyncio.wait_for(read_progress(), timeout=config.IPC_TIMEOUT)
+            with kill_on_cancel(process):
+                await asyncio.wait_for(read_progress(), timeout=config.IPC_TIMEOUT)
         except asyncio.TimeoutError:
             error = get_error('NETWORK_TIMEOUT')
             logger.error(f"[{task_id}] Download timeout: {error.user_message}")
@@ -233,6 +349,17 @@ async def _execute_download(ipc: IPCHandler, task_id: str, command: list, output
         # Wait for process to complete
         returncode = await process.wait()
 
+        # yt-dlp exits 0 when it skips a file over --max-filesize
+        if too_large:
+            limit_mb = (params or {}).get('max_filesize_mb')
+            error = get_error(
+                'FILE_SIZE_EXCEEDS_LIMIT',
+                f'This file is larger than the {limit_mb} MB download limit.',
+            )
+            logger.info(f"[{task_id}] Skipped: larger than {limit_mb} MB")
+            ipc.send_error(task_id, error.user_message, error.code)
+            return
+
         if returncode != 0:
             # Always log full yt-dlp output so the real error is visible
             if stderr_lines:
@@ -269,36 +396,66 @@ async def _execute_download(ipc: IPCHandler, task_id: str, command: list, output
             # Fallback: scan output directory for the newest matching file
             final_file = _find_newest_media_file(output_dir)
 
+        # Subtitles are fetched first, so the last destination may be the .srt
+        burn_lang = (params or {}).get('burn_subtitles') if not extract_audio else None
+        subs_lang = (params or {}).get('subtitles') if not extract_audio and not burn_lang else None
+        if (burn_lang or subs_lang) and final_file and not final_file.lower().endswith(MEDIA_EXTENSIONS):
+            final_file = _find_newest_media_file(output_dir)
+
+        if final_file and burn_lang:
+            final_file = await burn_subtitles(ipc, task_id, final_file, output_dir, burn_lang)
+            if not final_file:
+                return  # error already sent
+
         if final_file:
             file_size = os.path.getsize(final_file)
             logger.info(f"[{task_id}] Download completed: {os.path.basename(final_file)} ({file_size} bytes)")
 
-            # Dedup: move file to central pool and create symlink
-            try:
-                from worker.storage import StorageManager
-                from worker.database import get_database
-                db = await get_database()
-                user_chat_id = (params or {}).get('user_chat_id', 0)
-                storage = StorageManager(config.DOWNLOAD_DIR)
-                success, final_path = await storage.store_or_link(
-                    source_file=final_file,
-                    target_path=final_file,
-                    database=db,
-                    user_chat_id=user_chat_id,
-                    youtube_url=url,
-                    title=os.path.splitext(os.path.basename(final_file))[0],
-                    use_symlink=True,
-                )
-                if success:
-                    final_file = final_path
-                    logger.info(f"[{task_id}] Dedup: stored in pool, serving via symlink")
-            except Exception as e:
-                logger.warning(f"[{task_id}] Dedup failed (using original): {e}")
+            audio_metadata = {}
+            if extract_audio and (params or {}).get('embed_metadata'):
+                audio_metadata = await _audio_metadata(final_file, task_id)
+
+            # A missing subtitle track doesn't fail the download; the video is sent without it
+            subtitle_info = {}
+            if subs_lang:
+                subtitle_file = find_subtitle_file(output_dir)
+                if subtitle_file:
+                    subtitle_info = {'subtitle_file': subtitle_file}
+                else:
+                    logger.info(f"[{task_id}] No {subs_lang} subtitles offered, sending the video alone")
+
+            # Dedup: move file to central pool and create symlink.
+            # Burned-in copies differ from the source, so they stay out of the pool.
+            if not burn_lang:
+                try:
+                    from worker.storage import StorageManager, storage_root_for
+                    from worker.database import get_database
+                    db = await get_database()
+                    user_chat_id = (params or {}).get('user_chat_id', 0)
+                    storage = StorageManager(storage_root_for((params or {}).get('output_dir'), config.DOWNLOAD_DIR))
+                    success, final_path = await storage.store_or_link(
+                        source_file=final_file,
+                        target_path=final_file,
+                        database=db,
+                        user_chat_id=user_chat_id,
+                        youtube_url=url,
+                        title=os.path.splitext(os.path.basename(final_file))[0],
+                        use_symlink=True,
+                    )
+                    if success:
+                        final_file = final_path
+                        logger.info(f"[{task_id}] Dedup: stored in pool, serving via symlink")
+                except Exception as e:
+                    logger.warning(f"[{task_id}] Dedup failed (using original): {e}")
 
             ipc.send_response(task_id, 'done', {
                 'file_path': final_file,
+                'file_ref': local_file_ref(final_file),
                 'file_size': file_size,
                 'filename': os.path.basename(final_file),
+                **audio_metadata,
+                **subtitle_info,
+                **_media_info(output_dir, task_id),
             })
         else:
             logger.error(f"[{task_id}] Downloaded file not found at {destination_file}")
@@ -310,15 +467,176 @@ async def _execute_download(ipc: IPCHandler, task_id: str, command: list, output
         ipc.send_error(task_id, error.user_message, error.code)
 
 
+# yt-dlp writes `field=value` lines here after the download; read by _media_info
+MEDIA_INFO_FILE = '.media_info.txt'
+MEDIA_INFO_FIELDS = ('title', 'uploader', 'artist', 'duration')
+
+MEDIA_EXTENSIONS = ('.mp3', '.m4a', '.mp4', '.webm', '.opus', '.ogg', '.wav', '.flac', '.mkv')
+
+ALLOWED_SAMPLE_RATES = {22050, 44100, 48000}
+ALLOWED_CHANNELS = {1, 2}
+
+
+async def _resolve_clip(url: str, task_id: str) -> Optional[tuple]:
+    """
+    Resolve a YouTube clip URL to (video_url, start_seconds, end_seconds).
+
+    yt-dlp's clip extractor reports the source video plus section_start and
+    section_end. None when the clip can't be resolved (deleted, private, or
+    the extractor no longer understands the page).
+    """
+    command = [
+        sys.executable, '-m', 'yt_dlp', url,
+        '--dump-single-json', '--skip-download', '--no-warnings', '--no-cache-dir',
+        *get_yt_dlp_cookie_args(),
+    ]
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.PIPE,
+        )
+        stdout_bytes, stderr_bytes = await asyncio.wait_for(process.communicate(), timeout=config.YT_TIMEOUT)
+    except (asyncio.TimeoutError, OSError) as e:
+        logger.warning(f"[{task_id}] Clip resolution failed: {e}")
+        return None
+
+    try:
+        info = json.loads(stdout_bytes.decode('utf-8', errors='replace') or '{}')
+    except json.JSONDecodeError:
+        info = {}
+    video_id = info.get('id')
+    start, end = info.get('section_start'), info.get('section_end')
+    if process.returncode != 0 or not video_id or start is None or end is None or end <= start:
+        stderr = stderr_bytes.decode('utf-8', errors='replace').strip()
+        logger.warning(f"[{task_id}] Could not resolve clip {url}: {stderr[-300:] or 'no section in metadata'}")
+        return None
+
+    logger.info(f"[{task_id}] Clip resolved to video {video_id}, {start:.1f}s-{end:.1f}s")
+    return f'https://www.youtube.com/watch?v={video_id}', float(start), float(end)
+
+
+def _section_range(params: dict) -> Optional[tuple]:
+    """(start, end) seconds from the section_start/section_end params, or None if invalid."""
+    start, end = params.get('section_start'), params.get('section_end')
+    if not all(isinstance(v, int) and not isinstance(v, bool) and v >= 0 for v in (start, end)):
+        return None
+    return (start, end) if end > start else None
+
+
+def _resample_args(params: dict, task_id: str) -> list:
+    """ffmpeg -ar/-ac args for the requested sample rate and channel count."""
+    args = []
+    sample_rate = params.get('audio_sample_rate')
+    if sample_rate is not None:
+        if sample_rate in ALLOWED_SAMPLE_RATES:
+            args.extend(['-ar', str(sample_rate)])
+        else:
+            logger.warning(f"[{task_id}] Ignoring invalid audio_sample_rate: {sample_rate!r}")
+    channels = params.get('audio_channels')
+    if channels is not None:
+        if channels in ALLOWED_CHANNELS:
+            args.extend(['-ac', str(channels)])
+        else:
+            logger.warning(f"[{task_id}] Ignoring invalid audio_channels: {channels!r}")
+    return args
+
+
+def _metadata_tag_args(tag: str) -> list:
+    """
+    yt-dlp args that write `tag` as the file's comment, and nothing else:
+    title/artist tags stay with the embed_metadata option.
+
+    The "ffmpeg" key adds the args to every ffmpeg post-processor (audio
+    extraction, merging, cover art), next to their own args. yt-dlp splits
+    them shell-style, hence the quoting.
+    """
+    return ['--postprocessor-args', f"ffmpeg:-metadata {shlex.quote('comment=' + tag)}"]
+
+
+def _media_info(output_dir: str, task_id: str) -> dict:
+    """
+    `{'media': {...}}` with what yt-dlp printed to MEDIA_INFO_FILE, for the
+    bot's caption template, or `{}` if there is nothing. The file is removed.
+    """
+    path = os.path.join(output_dir, MEDIA_INFO_FILE)
+    media = {}
+    try:
+        with open(path, encoding='utf-8', errors='replace') as f:
+            for line in f:
+                field, _, value = line.rstrip('\n').partition('=')
+                value = value.strip()
+                if field not in MEDIA_INFO_FIELDS or not value or value == 'NA':
+                    continue
+                if field == 'duration':
+                    try:
+                        media[field] = int(float(value))
+                    except ValueError:
+                        continue
+                else:
+                    media[field] = value
+        os.remove(path)
+    except OSError as e:
+        logger.debug(f"[{task_id}] No media info: {e}")
+    return {'media': media} if media else {}
+
+
+async def _audio_metadata(audio_file: str, task_id: str) -> dict:
+    """
+    Title, performer and duration read back from the tagged file, plus a
+    Telegram-sized cover (`thumbnail_path`) made from the thumbnail yt-dlp
+    wrote next to it. Missing fields are left out.
+    """
+    metadata = {}
+    try:
+        process = await asyncio.create_subprocess_exec(
+            'ffprobe', '-v', 'error', '-show_entries', 'format=duration:format_tags',
+            '-of', 'json', audio_file,
+            stdout=asyncio.subprocess.PIPE,
+            stderr=asyncio.subprocess.DEVNULL,
+        )
+        stdout, _ = await asyncio.wait_for(process.communicate(), timeout=30)
+        fmt = json.loads(stdout.decode('utf-8', errors='replace') or '{}').get('format', {})
+        tags = {k.lower(): v for k, v in (fmt.get('tags') or {}).items()}
+        if tags.get('title'):
+            metadata['title'] = tags['title']
+        if tags.get('artist'):
+            metadata['performer'] = tags['artist']
+        if fmt.get('duration'):
+            metadata['duration'] = int(float(fmt['duration']))
+    except (OSError, ValueError, asyncio.TimeoutError) as e:
+        logger.warning(f"[{task_id}] Could not read audio tags: {e}")
+
+    # Telegram wants a JPEG of at most 320x320 and under 200 kB
+    cover = os.path.splitext(audio_file)[0] + '.jpg'
+    thumb = os.path.splitext(audio_file)[0] + '.thumb.jpg'
+    if os.path.isfile(cover):
+        try:
+            process = await asyncio.create_subprocess_exec(
+                'ffmpeg', '-y', '-hide_banner', '-loglevel', 'error', '-i', cover,
+                '-vf', 'scale=320:320:force_original_aspect_ratio=decrease', '-q:v', '5', thumb,
+                stdout=asyncio.subprocess.DEVNULL,
+                stderr=asyncio.subprocess.DEVNULL,
+            )
+            await asyncio.wait_for(process.wait(), timeout=30)
+            if process.returncode == 0 and 0 < os.path.getsize(thumb) < 200 * 1024:
+                metadata['thumbnail_path'] = thumb
+        except (OSError, asyncio.TimeoutError) as e:
+            logger.warning(f"[{task_id}] Could not scale the cover: {e}")
+        try:
+            os.remove(cover)
+        except OSError:
+            pass
+
+    return metadata
+
+
 def _find_newest_media_file(output_dir: str) -> Optional[str]:
     """Find the most recently modified media file in the output directory."""
-    media_extensions = ('.mp3', '.m4a', '.mp4', '.webm', '.opus', '.ogg', '.wav', '.flac', '.mkv')
     newest_file = None
     newest_mtime = 0
 
     try:
         for filename in os.listdir(output_dir):
-            if filename.lower().endswith(media_extensions):
+            if filename.lower().endswith(MEDIA_EXTENSIONS):
                 filepath = os.path.join(output_dir, filename)
                 mtime = os.path.getmtime(filepath)
                 if mtime > newest_mtime and os.path.getsize(filepath) > 0:
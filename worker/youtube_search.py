@@ -4,12 +4,15 @@ Searches YouTube and returns results via IPC
 Includes caching to minimize API calls
 """
 
+import os
 import sys
 import json
+import base64
 import subprocess
 import logging
 import asyncio
 from typing import List, Dict, Any
+from urllib.parse import quote, quote_plus
 from worker.config import config
 from worker.ipc import IPCHandler
 from worker.cookies import get_yt_dlp_cookie_args
@@ -20,6 +23,32 @@ from worker.cache import SearchCache, MetadataCache
 
 logger = logging.getLogger(__name__)
 
+# Deepest result a search can page to (each page re-fetches everything before it)
+MAX_SEARCH_OFFSET = 100
+
+# /search filters -> field number and values of YouTube's search filter (`sp`)
+SEARCH_FILTER_FIELDS = {
+    'date': (1, {'hour': 1, 'today': 2, 'week': 3, 'month': 4, 'year': 5}),
+    'type': (2, {'video': 1, 'playlist': 3}),
+    'duration': (3, {'short': 1, 'long': 2, 'medium': 3}),
+}
+
+
+def search_filter_param(filters: Dict[str, str]) -> str:
+    """
+    YouTube's `sp` URL parameter for `filters` (e.g. {"duration": "long"}),
+    or '' when nothing is filtered. It is a small protobuf: field 2 holds one
+    varint per filter.
+    """
+    inner = bytearray()
+    for name, (field, values) in SEARCH_FILTER_FIELDS.items():
+        value = values.get((filters or {}).get(name))
+        if value:
+            inner += bytes([field << 3, value])
+    if not inner:
+        return ''
+    return quote(base64.b64encode(bytes([0x12, len(inner)]) + inner).decode(), safe='')
+
 
 async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) -> None:
     """
@@ -31,7 +60,9 @@ async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) ->
         "action": "youtube_search",
         "params": {
             "query": "lo-fi beats",
-            "limit": 5
+            "limit": 5,
+            "offset": 0,  // skip this many results (next page)
+            "filters": {"duration": "long", "date": "week", "type": "playlist"}  // all optional
         }
     }
 
@@ -51,7 +82,9 @@ async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) ->
                 }
             ],
             "query": "lo-fi beats",
-            "total_results": 5
+            "total_results": 5,
+            "offset": 0,
+            "has_more": true  // a further page may exist
         }
     }
 
@@ -64,6 +97,8 @@ async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) ->
         params = request.get('params', {})
         query = params.get('query', '').strip()
         limit = params.get('limit', 5)
+        offset = params.get('offset', 0)
+        filters = params.get('filters') or {}
 
         if not query:
             ipc.send_error(task_id, "Missing 'query' parameter")
@@ -76,25 +111,33 @@ async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) ->
 
         limit = min(limit, 20)  # Cap at 20 results
         limit = max(limit, 1)   # Min 1 result
+        offset = min(max(offset, 0), MAX_SEARCH_OFFSET)
+        wanted = offset + limit
+        sp = search_filter_param(filters)
+        # Filtered searches are cached apart from the plain query
+        cache_key = query if not sp else f"{query} [{sp}]"
 
-        logger.info(f"[{task_id}] Searching YouTube: {query} (limit: {limit})")
+        logger.info(f"[{task_id}] Searching YouTube: {query} (limit: {limit}, offset: {offset}, filters: {filters or 'none'})")
         ipc.send_progress(task_id, 0, status='searching')
 
-        # Check cache first
-        cached_results = await SearchCache.get(query)
-        if cached_results is not None:
+        # Check cache first; a later page needs the cached list to reach that far
+        cached_results = await SearchCache.get(cache_key)
+        if cached_results is not None and (offset == 0 or len(cached_results) >= wanted):
             logger.info(f"[{task_id}] Using cached results for '{query}'")
+            page = cached_results[offset:wanted]
             ipc.send_progress(task_id, 100, status='completed')
             ipc.send_response(task_id, 'search_results', {
-                'results': cached_results[:limit],
+                'results': page,
                 'query': query,
-                'total_results': len(cached_results[:limit]),
+                'total_results': len(page),
+                'offset': offset,
+                'has_more': len(cached_results) >= wanted and wanted < MAX_SEARCH_OFFSET,
                 'from_cache': True,
             })
             return
 
-        # Execute search
-        results = await _search_youtube(task_id, query, limit)
+        # Execute search (yt-dlp has no offset: fetch up to the end of the page and slice)
+        results = await _search_youtube(task_id, query, wanted, sp)
 
         if results is None:
             error = get_error('UNKNOWN_ERROR')
@@ -104,12 +147,15 @@ async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) ->
         logger.info(f"[{task_id}] Found {len(results)} results for '{query}'")
 
         # Cache the results
-        await SearchCache.set(query, results)
+        await SearchCache.set(cache_key, results)
 
+        page = results[offset:wanted]
         ipc.send_response(task_id, 'search_results', {
-            'results': results,
+            'results': page,
             'query': query,
-            'total_results': len(results),
+            'total_results': len(page),
+            'offset': offset,
+            'has_more': len(results) >= wanted and wanted < MAX_SEARCH_OFFSET,
             'from_cache': False,
         })
 
@@ -119,7 +165,7 @@ async def handle_youtube_search(ipc: IPCHandler, task_id: str, request: dict) ->
         ipc.send_error(task_id, error.user_message, error.code)
 
 
-async def _search_youtube(task_id: str, query: str, limit: int) -> List[Dict[str, Any]]:
+async def _search_youtube(task_id: str, query: str, limit: int, sp: str = '') -> List[Dict[str, Any]]:
     """
     Execute YouTube search via yt-dlp.
 
@@ -127,13 +173,18 @@ async def _search_youtube(task_id: str, query: str, limit: int) -> List[Dict[str
         task_id: Task ID for logging
         query: Search query
         limit: Number of results
+        sp: YouTube search filter from `search_filter_param`, '' for none
 
     Returns:
         List of result dictionaries, or None on error
     """
     try:
         # Build yt-dlp command
-        search_query = f'ytsearch{limit}:{query}'
+        # ytsearch can't filter; a filtered search goes through the results page URL
+        if sp:
+            search_query = f'https://www.youtube.com/results?search_query={quote_plus(query)}&sp={sp}'
+        else:
+            search_query = f'ytsearch{limit}:{query}'
 
         command = [
             sys.executable, '-m', 'yt_dlp',
@@ -142,6 +193,8 @@ async def _search_youtube(task_id: str, query: str, limit: int) -> List[Dict[str
             '--flat-playlist',
             '--no-cache-dir',
         ]
+        if sp:
+            command.extend(['--playlist-end', str(limit)])
 
         # Add cookies
         cookie_args = get_yt_dlp_cookie_args()
@@ -193,13 +246,15 @@ async def _search_youtube(task_id: str, query: str, limit: int) -> List[Dict[str
                 if not video_id:
                     continue
 
+                # type:playlist results link to the playlist, not a video
+                entry_url = entry.get('url') or ''
                 result = {
                     'videoId': video_id,
                     'title': entry.get('title', 'Untitled'),
                     'artist': entry.get('uploader', 'Unknown'),
                     'duration': entry.get('duration_string', 'Unknown'),
                     'thumbnail': entry.get('thumbnail', _generate_thumbnail_url(video_id)),
-                    'url': f"https://www.youtube.com/watch?v={video_id}",
+                    'url': entry_url if 'list=' in entry_url else f"https://www.youtube.com/watch?v={video_id}",
                 }
 
                 results.append(result)
@@ -348,6 +403,173 @@ async def handle_get_video_info(ipc: IPCHandler, task_id: str, request: dict) ->
         ipc.send_error(task_id, error.user_message, error.code)
 
 
+async def handle_probe(ipc: IPCHandler, task_id: str, request: dict) -> None:
+    """
+    Check whether yt-dlp can handle a URL, without resolving formats or downloading.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "probe",
+        "url": "https://..."
+    }
+
+    Response (probe_result):
+    {
+        "downloadable": true,
+        "platform": "Vimeo",
+        "title": "...",
+        "is_playlist": false,
+        "reason": "..."          # only when not downloadable
+    }
+    """
+    url = request.get('url', '').strip()
+    if not url:
+        ipc.send_error(task_id, "Missing 'url' parameter", 'INVALID_URL')
+        return
+
+    # --flat-playlist keeps playlists to a single listing request
+    command = [
+        sys.executable, '-m', 'yt_dlp',
+        url,
+        '--dump-single-json',
+        '--skip-download',
+        '--flat-playlist',
+        '--no-warnings',
+        '--no-cache-dir',
+    ]
+    command.extend(get_yt_dlp_cookie_args())
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.PIPE,
+            stderr=asyncio.subprocess.PIPE,
+        )
+        stdout_bytes, stderr_bytes = await asyncio.wait_for(
+            process.communicate(),
+            timeout=config.YT_TIMEOUT
+        )
+    except asyncio.TimeoutError:
+        error = get_error('NETWORK_TIMEOUT')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+    except Exception as e:
+        error = categorize_error(e)
+        logger.error(f"[{task_id}] Probe failed: {error.user_message}", exc_info=True)
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    if process.returncode != 0:
+        stderr = stderr_bytes.decode('utf-8', errors='replace')
+        reason = next(
+            (l.split('ERROR:', 1)[1].strip() for l in reversed(stderr.splitlines()) if 'ERROR:' in l),
+            'Unsupported or unreachable URL',
+        )
+        logger.info(f"[{task_id}] Probe: not downloadable ({reason[:100]})")
+        ipc.send_response(task_id, 'probe_result', {
+            'downloadable': False,
+            'platform': None,
+            'title': None,
+            'is_playlist': False,
+            'reason': reason,
+        })
+        return
+
+    try:
+        data = json.loads(stdout_bytes.decode('utf-8', errors='replace'))
+    except json.JSONDecodeError:
+        ipc.send_error(task_id, "Could not read probe output", 'UNKNOWN_ERROR')
+        return
+
+    result = {
+        'downloadable': True,
+        'platform': data.get('extractor_key') or data.get('extractor'),
+        'title': data.get('title'),
+        'is_playlist': data.get('_type') == 'playlist',
+    }
+    logger.info(f"[{task_id}] Probe: {result['platform']} playlist={result['is_playlist']}")
+    ipc.send_response(task_id, 'probe_result', result)
+
+
+async def handle_get_thumbnail(ipc: IPCHandler, task_id: str, request: dict) -> None:
+    """
+    Save the largest available thumbnail of a video as a JPEG.
+
+    IPC Request format:
+    {
+        "task_id": "uuid",
+        "action": "get_thumbnail",
+        "url": "https://www.youtube.com/watch?v=...",
+        "params": {"output_dir": "/path/to/output"}
+    }
+
+    Response (done): {file_path, title, width, height}. width/height are 0
+    when the site doesn't report them. yt-dlp tries thumbnails best-first
+    and falls back when the maxres one doesn't exist.
+    """
+    url = request.get('url', '').strip()
+    output_dir = request.get('params', {}).get('output_dir', config.DOWNLOAD_DIR)
+    if not url:
+        ipc.send_error(task_id, "Missing 'url' parameter", 'INVALID_URL')
+        return
+
+    os.makedirs(output_dir, exist_ok=True)
+    command = [
+        sys.executable, '-m', 'yt_dlp',
+        url,
+        '--no-playlist',
+        '--skip-download',
+        '--no-simulate',
+        '--write-thumbnail',
+        '--convert-thumbnails', 'jpg',
+        '--dump-json',
+        '--no-cache-dir',
+        '-o', os.path.join(output_dir, 'wallpaper.%(ext)s'),
+    ]
+    command.extend(get_yt_dlp_cookie_args())
+
+    try:
+        process = await asyncio.create_subprocess_exec(
+            *command,
+            stdout=asyncio.subprocess.PIPE,
+            stderr=asyncio.subprocess.PIPE,
+        )
+        stdout_bytes, stderr_bytes = await asyncio.wait_for(
+            process.communicate(),
+            timeout=config.YT_TIMEOUT
+        )
+    except asyncio.TimeoutError:
+        error = get_error('NETWORK_TIMEOUT')
+        ipc.send_error(task_id, error.user_message, error.code)
+        return
+
+    if process.returncode != 0:
+        stderr = stderr_bytes.decode('utf-8', errors='replace')
+        logger.error(f"[{task_id}] Thumbnail fetch failed: {stderr[:200]}")
+        ipc.send_error(task_id, "Failed to fetch the thumbnail", 'UNKNOWN_ERROR')
+        return
+
+    file_path = os.path.join(output_dir, 'wallpaper.jpg')
+    if not os.path.exists(file_path):
+        ipc.send_error(task_id, "This video has no thumbnail", 'FILE_NOT_FOUND')
+        return
+
+    try:
+        data = json.loads(stdout_bytes.decode('utf-8', errors='replace'))
+    except json.JSONDecodeError:
+        data = {}
+
+    # The written thumbnail is the one yt-dlp recorded a filepath for
+    written = next((t for t in data.get('thumbnails') or [] if t.get('filepath')), {})
+    ipc.send_response(task_id, 'done', {
+        'file_path': file_path,
+        'title': data.get('title', ''),
+        'width': written.get('width') or 0,
+        'height': written.get('height') or 0,
+    })
+
+
 async def handle_get_formats(ipc: IPCHandler, task_id: str, request: dict) -> None:
     """
     Get available download formats for a video.
@@ -420,10 +642,15 @@ async def handle_get_formats(ipc: IPCHandler, task_id: str, request: dict) -> No
         duration = data.get('duration', 0)
         thumbnail = data.get('thumbnail', '')
 
+        languages = []
         if mode == 'video':
             grouped = _group_video_formats(raw_formats)
         else:
             grouped = _group_audio_formats(raw_formats)
+            # Only offer a language choice for multi-audio (dubbed) videos
+            languages = _audio_languages(raw_formats)
+            if len(languages) < 2:
+                languages = []
 
         ipc.send_response(task_id, 'format_list', {
             'title': title,
@@ -432,6 +659,7 @@ async def handle_get_formats(ipc: IPCHandler, task_id: str, request: dict) -> No
             'thumbnail': thumbnail,
             'mode': mode,
             'formats': grouped,
+            'languages': languages,
         })
         logger.info(f"[{task_id}] Returned {len(grouped)} format options ({mode} mode)")
 
@@ -564,6 +792,38 @@ def _group_audio_formats(raw_formats: list) -> list:
     return result
 
 
+def _audio_languages(raw_formats: list) -> list:
+    """
+    List the distinct audio track languages of a video, original track first.
+
+    Returns [{'code': 'en', 'label': 'English', 'original': True}, ...].
+    """
+    languages = {}
+
+    for fmt in raw_formats:
+        if fmt.get('acodec', 'none') == 'none':
+            continue
+        code = fmt.get('language')
+        if not code:
+            continue
+
+        note = fmt.get('format_note') or ''
+        original = (fmt.get('language_preference') or 0) >= 10 or 'original' in note.lower()
+
+        # format_note looks like "English (United States) original (default), medium"
+        label = note.split(',')[0].replace('(default)', '').replace('original', '').strip()
+        if not label or label.lower() in ('low', 'medium', 'high'):
+            label = code
+
+        entry = languages.get(code)
+        if entry is None:
+            languages[code] = {'code': code, 'label': label[:24], 'original': original}
+        elif original:
+            entry['original'] = True
+
+    return sorted(languages.values(), key=lambda l: (not l['original'], l['code']))
+
+
 def _format_filesize(size_bytes: int) -> str:
     """Format bytes to human-readable size."""
     if not size_bytes or size_bytes <= 0: